@@ -0,0 +1,35 @@
+# Copyright © Aptos Foundation
+# SPDX-License-Identifier: Apache-2.0
+
+import aptos_framework as stdlib
+import aptos_types as aptos
+import serde_types as st
+
+
+def address(last: int) -> aptos.AccountAddress:
+    return aptos.AccountAddress(value=tuple(st.uint8(0) for _ in range(31)) + (st.uint8(last),))
+
+
+def demo_coin_transfer() -> None:
+    token = aptos.TypeTag__Struct(
+        value=aptos.StructTag(
+            address=address(1),
+            module=aptos.Identifier(value="aptos_coin"),
+            name=aptos.Identifier(value="AptosCoin"),
+            type_params=[],
+        )
+    )
+    to = aptos.AccountAddress(value=tuple(st.uint8(0x22) for _ in range(32)))
+    amount = st.uint64(1_234_567)
+
+    payload = stdlib.encode_coin_transfer(token, to, amount)
+
+    call = stdlib.decode_entry_function_payload(payload)
+    assert isinstance(call, stdlib.EntryFunctionCall__CoinTransfer)
+    assert call.amount == amount and call.to == to, "wrong script content"
+
+    print(" ".join(str(b) for b in payload.bcs_serialize()), "")
+
+
+if __name__ == "__main__":
+    demo_coin_transfer()
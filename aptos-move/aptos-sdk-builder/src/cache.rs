@@ -0,0 +1,217 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Incremental generation: the hash of the inputs of each generated output is kept in a cache file
+//! in the target directory, and outputs whose inputs didn't change since the last run are not
+//! generated again.
+
+use anyhow::Result;
+use aptos_crypto::HashValue;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+const CACHE_FILE_NAME: &str = ".aptos-sdk-builder-cache.yaml";
+
+/// Hashes the inputs of an output, given as a list of byte strings.
+pub fn hash_inputs(inputs: &[&[u8]]) -> HashValue {
+    let mut bytes = Vec::new();
+    for input in inputs {
+        // Length prefixes keep ["ab", "c"] and ["a", "bc"] apart.
+        bytes.extend_from_slice(&(input.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(input);
+    }
+    HashValue::sha3_256_of(&bytes)
+}
+
+/// Identifies the running generator, so that outputs are regenerated after it is rebuilt.
+pub fn generator_fingerprint() -> Vec<u8> {
+    let mut fingerprint = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+    if let Ok(metadata) = std::env::current_exe().and_then(std::fs::metadata) {
+        fingerprint.extend_from_slice(&metadata.len().to_le_bytes());
+        if let Ok(modified) = metadata.modified() {
+            fingerprint.extend_from_slice(format!("{:?}", modified).as_bytes());
+        }
+    }
+    fingerprint
+}
+
+/// What became of an output in the last run, by module.
+struct Report {
+    output: String,
+    regenerated: bool,
+    /// Whether files of the output were missing, e.g. deleted since the last run.
+    missing_files: bool,
+    /// Modules added, changed or removed since the last run.
+    changed: Vec<String>,
+    unchanged: Vec<String>,
+}
+
+pub struct GenerationCache {
+    path: PathBuf,
+    /// Hashes of the inputs of each module, by output.
+    hashes: BTreeMap<String, BTreeMap<String, String>>,
+    reports: Vec<Report>,
+}
+
+impl GenerationCache {
+    /// Loads the cache of the given target directory. A missing or unreadable cache is treated as
+    /// empty, so that everything is generated.
+    pub fn load(target_dir: &Path) -> Self {
+        let path = target_dir.join(CACHE_FILE_NAME);
+        let hashes = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_yaml::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            hashes,
+            reports: vec![],
+        }
+    }
+
+    /// Runs `generate` for `output`, unless each of its modules was already generated from the
+    /// same inputs, given by `module_hashes`, and all of `files` still exist. Outputs are written
+    /// in one go, so a single changed module regenerates the whole output. Returns whether it ran.
+    pub fn generate<E>(
+        &mut self,
+        output: &str,
+        module_hashes: &BTreeMap<String, HashValue>,
+        files: &[PathBuf],
+        generate: impl FnOnce() -> std::result::Result<(), E>,
+    ) -> std::result::Result<bool, E> {
+        let hashes: BTreeMap<String, String> = module_hashes
+            .iter()
+            .map(|(module, hash)| (module.clone(), hash.to_hex()))
+            .collect();
+        let previous = self.hashes.get(output).cloned().unwrap_or_default();
+        let (unchanged, mut changed): (Vec<String>, Vec<String>) = hashes
+            .keys()
+            .cloned()
+            .partition(|module| previous.get(module) == hashes.get(module));
+        changed.extend(
+            previous
+                .keys()
+                .filter(|module| !hashes.contains_key(*module))
+                .cloned(),
+        );
+        let missing_files = files.iter().any(|file| !file.exists());
+        let regenerated = missing_files || !changed.is_empty();
+        if regenerated {
+            // Forget the previous hashes first, so that a failed generation is retried next time.
+            self.hashes.remove(output);
+            generate()?;
+            self.hashes.insert(output.to_string(), hashes);
+        }
+        self.reports.push(Report {
+            output: output.to_string(),
+            regenerated,
+            missing_files,
+            changed,
+            unchanged,
+        });
+        Ok(regenerated)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_yaml::to_string(&self.hashes)?)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for GenerationCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for report in &self.reports {
+            if report.regenerated {
+                write!(f, "{}: regenerated", report.output)?;
+                if report.missing_files {
+                    write!(f, " (files missing)")?;
+                }
+            } else {
+                write!(f, "{}: skipped", report.output)?;
+            }
+            writeln!(
+                f,
+                ", {} changed module(s) [{}], {} unchanged module(s) [{}]",
+                report.changed.len(),
+                report.changed.join(", "),
+                report.unchanged.len(),
+                report.unchanged.join(", "),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_inputs, GenerationCache};
+    use aptos_crypto::HashValue;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_generation_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        let modules = |inputs: &[(&str, &[u8])]| -> BTreeMap<String, HashValue> {
+            inputs
+                .iter()
+                .map(|(module, input)| (module.to_string(), hash_inputs(&[input, b"options"])))
+                .collect()
+        };
+        let run = |inputs: &[(&str, &[u8])]| {
+            let mut cache = GenerationCache::load(dir.path());
+            let mut count = 0;
+            cache
+                .generate("builders", &modules(inputs), &[file.clone()], || {
+                    count += 1;
+                    std::fs::write(&file, "")
+                })
+                .unwrap();
+            cache.save().unwrap();
+            (count, cache.to_string())
+        };
+
+        assert_eq!(run(&[("coin", b"abi"), ("stake", b"abi")]).0, 1);
+        assert_eq!(
+            run(&[("coin", b"abi"), ("stake", b"abi")]),
+            (
+                0,
+                "builders: skipped, 0 changed module(s) [], 2 unchanged module(s) [coin, stake]\n"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run(&[("coin", b"abi2"), ("stake", b"abi")]),
+            (
+                1,
+                "builders: regenerated, 1 changed module(s) [coin], 1 unchanged module(s) [stake]\n"
+                    .to_string()
+            )
+        );
+        // Removed modules are changes too.
+        assert_eq!(run(&[("coin", b"abi2")]).0, 1);
+        assert_eq!(run(&[("coin", b"abi2")]).0, 0);
+
+        // So are deleted outputs.
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(
+            run(&[("coin", b"abi2")]),
+            (
+                1,
+                "builders: regenerated (files missing), 0 changed module(s) [], 1 unchanged \
+                 module(s) [coin]\n"
+                    .to_string()
+            )
+        );
+
+        let mut cache = GenerationCache::load(dir.path());
+        assert!(cache
+            .generate("builders", &modules(&[("coin", b"x")]), &[], || Err(()))
+            .is_err());
+        cache.save().unwrap();
+        assert_eq!(run(&[("coin", b"abi2")]).0, 1);
+    }
+}
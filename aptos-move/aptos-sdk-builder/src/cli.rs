@@ -0,0 +1,255 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Command line of the builder, shared by the `aptos-sdk-builder` binary and those of crates
+//! adding languages of their own, see `generator`.
+
+use crate::{
+    cache::{generator_fingerprint, hash_inputs, GenerationCache},
+    generator::{GeneratorInput, GeneratorOptions, Generators},
+    naming::Naming,
+    read_abis, read_event_abis, read_return_abis,
+    rust::RustEdition,
+    EventABI, ReturnABI,
+};
+use aptos_crypto::HashValue;
+use aptos_types::transaction::EntryABI;
+use serde_reflection::Registry;
+use std::{collections::BTreeMap, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "Aptos SDK Builder", about = "Generate boilerplate Aptos SDKs")]
+struct Options {
+    /// Path to the directory containing ABI files in BCS encoding.
+    abi_directories: Vec<PathBuf>,
+
+    /// Language for code generation: Rust, Go, Python3, RustCli, or one registered by the binary.
+    #[structopt(long, default_value = "Rust")]
+    language: String,
+
+    /// Directory where to write generated modules (otherwise print code on stdout).
+    #[structopt(long)]
+    target_source_dir: Option<PathBuf>,
+
+    /// Also install the aptos types described by the given YAML file, along with the BCS runtime.
+    #[structopt(long)]
+    with_aptos_types: Option<PathBuf>,
+
+    /// Module name for the transaction builders installed in the `target_source_dir`.
+    /// * Rust crates may contain a version number, e.g. "test:1.2.0".
+    /// * In Java, this is expected to be a package name, e.g. "com.test" to create Java files in `com/test`.
+    /// * In Go, this is expected to be of the format "go_module/path/go_package_name",
+    /// and `aptos_types` is assumed to be in "go_module/path/aptos_types".
+    #[structopt(long)]
+    module_name: Option<String>,
+
+    /// Optional package name (Python) or module path (Go) of the Serde and BCS runtime dependencies.
+    #[structopt(long)]
+    serde_package_name: Option<String>,
+
+    /// Optional version number for the `aptos_types` module (useful in Rust).
+    /// If `--with-aptos-types` is passed, this will be the version of the generated `aptos_types` module.
+    #[structopt(long, default_value = "0.1.0")]
+    aptos_version_number: String,
+
+    /// Optional package name (Python) or module path (Go) of the `aptos_types` dependency.
+    #[structopt(long)]
+    package_name: Option<String>,
+
+    /// Rust edition targeted by the generated transaction builders (Rust only).
+    /// Older editions avoid features that require a recent toolchain.
+    #[structopt(long, possible_values = &["2018", "2021"], default_value = "2021")]
+    rust_edition: RustEdition,
+
+    /// Generate Rust transaction builders for `no_std` crates relying on `alloc` (Rust only).
+    #[structopt(long)]
+    rust_no_std: bool,
+
+    /// Serialize the fields of the generated Rust event types to the JSON representation of the
+    /// REST API with `serde_json`, e.g. integers over 32 bits as strings and addresses as hex,
+    /// without changing their BCS encoding (Rust only).
+    #[structopt(long)]
+    rust_json: bool,
+
+    /// YAML file mapping the addresses of the modules to namespaces and renaming functions in
+    /// generated names, for projects with modules at several addresses. See `naming`.
+    #[structopt(long)]
+    naming_config: Option<PathBuf>,
+
+    /// Option of a language registered by the binary, as `KEY=VALUE`. Can be repeated.
+    #[structopt(long = "generator-option", parse(try_from_str = parse_key_value))]
+    generator_options: Vec<(String, String)>,
+
+    /// Skip outputs whose inputs (ABIs, registry file, options and generator) are unchanged since
+    /// the last incremental run into the same `target_source_dir`.
+    #[structopt(long)]
+    incremental: bool,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("Expected KEY=VALUE, got {}", s))
+}
+
+/// Parses the command line and generates the transaction builders in the language it asks for,
+/// among the given ones.
+pub fn run(generators: &Generators) {
+    let options = Options::from_args();
+    let generator = generators.get(&options.language).unwrap_or_else(|| {
+        panic!(
+            "Unknown language {}, expected one of: {}",
+            options.language,
+            generators.names().join(", ")
+        )
+    });
+    let abis = read_abis(&options.abi_directories).expect("Failed to read ABI in directory");
+    // Only used for Rust, the other languages don't decode returned values and events yet.
+    let returns =
+        read_return_abis(&options.abi_directories).expect("Failed to read return ABI in directory");
+    let events =
+        read_event_abis(&options.abi_directories).expect("Failed to read event ABI in directory");
+    let naming_content = options.naming_config.as_ref().map(|naming_file| {
+        std::fs::read_to_string(naming_file).expect("naming config file must be readable")
+    });
+    let naming = naming_content
+        .as_ref()
+        .map(|content| Naming::from_yaml(content).expect("Failed to read naming config"))
+        .unwrap_or_default();
+    for collision in naming.collisions(&abis) {
+        eprintln!(
+            "Generated name shared by several functions, map their addresses to namespaces or \
+            rename them with --naming-config: {}",
+            collision
+        );
+    }
+    let generator_options = GeneratorOptions {
+        serde_package_name: options.serde_package_name.clone(),
+        package_name: options.package_name.clone(),
+        aptos_version_number: options.aptos_version_number.clone(),
+        rust_edition: options.rust_edition,
+        rust_no_std: options.rust_no_std,
+        rust_json: options.rust_json,
+        naming,
+        extra: options.generator_options.iter().cloned().collect(),
+    };
+    let registry_content = options.with_aptos_types.as_ref().map(|registry_file| {
+        std::fs::read_to_string(registry_file).expect("registry file must be readable")
+    });
+    let registry = registry_content
+        .as_ref()
+        .map(|content| serde_yaml::from_str::<Registry>(content).unwrap());
+    let input = GeneratorInput {
+        abis: &abis,
+        returns: &returns,
+        events: &events,
+        registry: registry.as_ref(),
+        options: &generator_options,
+    };
+
+    // Everything that affects the outputs, other than the ABIs and the registry.
+    let options_fingerprint = [
+        format!("{:?}", options).into_bytes(),
+        naming_content.unwrap_or_default().into_bytes(),
+        generator_fingerprint(),
+    ]
+    .concat();
+
+    let install_dir = match &options.target_source_dir {
+        None => {
+            // Nothing to install. Just print to stdout.
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            generator
+                .output(&mut out, options.module_name.as_deref(), &input)
+                .unwrap();
+            return;
+        },
+        Some(dir) => dir,
+    };
+
+    let mut cache = GenerationCache::load(install_dir);
+
+    // Aptos types
+    if let (Some(content), Some(registry)) = (&registry_content, &registry) {
+        let install = || generator.install_aptos_types(install_dir, registry, &generator_options);
+        if options.incremental {
+            let module_hashes = BTreeMap::from([(
+                "aptos_types".to_string(),
+                hash_inputs(&[&options_fingerprint, content.as_bytes()]),
+            )]);
+            cache
+                .generate("aptos-types", &module_hashes, &[], install)
+                .unwrap();
+        } else {
+            install().unwrap();
+        }
+    }
+
+    // Transaction builders
+    if let Some(name) = &options.module_name {
+        let install = || generator.install_transaction_builders(install_dir, name, &input);
+        if options.incremental {
+            let files = generator.transaction_builders_files(install_dir, name);
+            cache
+                .generate(
+                    name,
+                    &module_hashes(&input, &options_fingerprint),
+                    &files,
+                    install,
+                )
+                .unwrap();
+        } else {
+            install().unwrap();
+        }
+    }
+
+    if options.incremental {
+        cache.save().expect("Failed to save generation cache");
+        eprint!("{}", cache);
+    }
+}
+
+/// Hashes the ABIs of each Move module, along with the return and event ABIs of the module, as
+/// keyed in the generation cache. Scripts are grouped as `scripts`.
+fn module_hashes(
+    input: &GeneratorInput,
+    options_fingerprint: &[u8],
+) -> BTreeMap<String, HashValue> {
+    let mut modules: BTreeMap<String, (Vec<&EntryABI>, Vec<&ReturnABI>, Vec<&EventABI>)> =
+        BTreeMap::new();
+    for abi in input.abis {
+        let module = match abi {
+            EntryABI::EntryFunction(abi) => abi.module_name().to_string(),
+            EntryABI::TransactionScript(_) => "scripts".to_string(),
+        };
+        modules.entry(module).or_default().0.push(abi);
+    }
+    for abi in input.returns {
+        modules
+            .entry(abi.module_name.to_string())
+            .or_default()
+            .1
+            .push(abi);
+    }
+    for abi in input.events {
+        modules
+            .entry(abi.module_name.to_string())
+            .or_default()
+            .2
+            .push(abi);
+    }
+    modules
+        .into_iter()
+        .map(|(module, (abis, returns, events))| {
+            let hash = hash_inputs(&[
+                options_fingerprint,
+                &bcs::to_bytes(&abis).expect("ABIs must serialize"),
+                &bcs::to_bytes(&returns).expect("Return ABIs must serialize"),
+                &bcs::to_bytes(&events).expect("Event ABIs must serialize"),
+            ]);
+            (module, hash)
+        })
+        .collect()
+}
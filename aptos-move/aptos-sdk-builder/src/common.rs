@@ -2,6 +2,7 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::{naming::Naming, EventABI, ReturnABI};
 use aptos_types::transaction::{
     ArgumentABI, EntryABI, EntryFunctionABI, TransactionScriptABI, TypeArgumentABI,
 };
@@ -27,6 +28,42 @@ pub(crate) fn prepare_doc_string(doc: &str) -> String {
     doc.replace("\n ", "\n").trim().to_string()
 }
 
+/// Whether values of the type have a representation in generated code, which is the case of the
+/// types transaction arguments can have.
+pub(crate) fn is_representable(type_tag: &TypeTag) -> bool {
+    use TypeTag::*;
+    let str_tag: Lazy<StructTag> =
+        Lazy::new(|| StructTag::from_str("0x1::string::String").unwrap());
+    match type_tag {
+        Bool | U8 | U16 | U32 | U64 | U128 | U256 | Address => true,
+        Vector(type_tag) => is_representable(type_tag),
+        Struct(tag) => &**tag == Lazy::force(&str_tag),
+        Signer => false,
+    }
+}
+
+/// The functions whose returned values can be decoded by generated code, i.e. those returning
+/// something, and only values with a representation.
+pub(crate) fn decodable_return_abis(abis: &[ReturnABI]) -> Vec<ReturnABI> {
+    abis.iter()
+        .filter(|abi| !abi.returns.is_empty() && abi.returns.iter().all(is_representable))
+        .cloned()
+        .collect()
+}
+
+/// The events that can be decoded by generated code, i.e. those whose fields all have a
+/// representation.
+pub(crate) fn decodable_event_abis(abis: &[EventABI]) -> Vec<EventABI> {
+    abis.iter()
+        .filter(|abi| {
+            abi.fields
+                .iter()
+                .all(|field| is_representable(&field.type_tag))
+        })
+        .cloned()
+        .collect()
+}
+
 fn quote_type_as_format(type_tag: &TypeTag) -> Format {
     use TypeTag::*;
     let str_tag: Lazy<StructTag> =
@@ -63,7 +100,23 @@ fn quote_parameter_as_field(arg: &ArgumentABI) -> Named<Format> {
     }
 }
 
-pub(crate) fn make_abi_enum_container(abis: &[EntryABI]) -> ContainerFormat {
+/// Name of the variant of the call enums for the function, e.g. `CoinTransfer`.
+pub(crate) fn variant_name(abi: &EntryABI, naming: &Naming) -> String {
+    match abi {
+        EntryABI::EntryFunction(sf) => {
+            format!(
+                "{}{}",
+                naming.module_name(sf.module_name()).to_camel_case(),
+                naming
+                    .function_name(sf.module_name(), sf.name())
+                    .to_camel_case()
+            )
+        },
+        _ => abi.name().to_camel_case(),
+    }
+}
+
+pub(crate) fn make_abi_enum_container(abis: &[EntryABI], naming: &Naming) -> ContainerFormat {
     let mut variants = BTreeMap::new();
     for (index, abi) in abis.iter().enumerate() {
         let mut fields = Vec::new();
@@ -74,19 +127,8 @@ pub(crate) fn make_abi_enum_container(abis: &[EntryABI]) -> ContainerFormat {
             fields.push(quote_parameter_as_field(arg));
         }
 
-        let name = match abi {
-            EntryABI::EntryFunction(sf) => {
-                format!(
-                    "{}{}",
-                    sf.module_name().name().to_string().to_camel_case(),
-                    abi.name().to_camel_case()
-                )
-            },
-            _ => abi.name().to_camel_case(),
-        };
-
         variants.insert(index as u32, Named {
-            name,
+            name: variant_name(abi, naming),
             value: VariantFormat::Struct(fields),
         });
     }
@@ -0,0 +1,457 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Languages the transaction builders can be generated in.
+//!
+//! Besides the built-in ones, a language is added by implementing `LanguageGenerator` in a crate
+//! of its own, which registers it next to the built-in ones and runs the command line of the
+//! builder with it:
+//!
+//! ```ignore
+//! fn main() {
+//!     aptos_sdk_builder::cli::run(&Generators::builtin().register(Box::new(KotlinGenerator)));
+//! }
+//! ```
+
+use crate::{
+    golang, naming::Naming, python3, rust, rust::RustEdition, rust_cli, EventABI, ReturnABI,
+    SourceInstaller,
+};
+use aptos_types::transaction::EntryABI;
+use serde_generate::{self as serdegen, SourceInstaller as _};
+use serde_reflection::Registry;
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub type GeneratorResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+/// Options of the command line, which each generator uses as it sees fit.
+#[derive(Clone, Debug)]
+pub struct GeneratorOptions {
+    /// Package name (Python) or module path (Go) of the Serde and BCS runtime dependencies.
+    pub serde_package_name: Option<String>,
+    /// Package name (Python) or module path (Go) of the `aptos_types` dependency.
+    pub package_name: Option<String>,
+    /// Version of the `aptos_types` module (Rust).
+    pub aptos_version_number: String,
+    pub rust_edition: RustEdition,
+    pub rust_no_std: bool,
+    /// Whether the Rust event types serialize to the JSON representation of the REST API.
+    pub rust_json: bool,
+    /// Namespaces and renames of the functions in generated names, from `--naming-config`.
+    pub naming: Naming,
+    /// `--generator-option KEY=VALUE` pairs, for generators other than the built-in ones.
+    pub extra: BTreeMap<String, String>,
+}
+
+/// What the transaction builders are generated from.
+pub struct GeneratorInput<'a> {
+    pub abis: &'a [EntryABI],
+    /// Return types of the functions, see `ReturnABI`.
+    pub returns: &'a [ReturnABI],
+    /// Layouts of the structs emitted as events, see `EventABI`.
+    pub events: &'a [EventABI],
+    /// Definitions of the Aptos types the arguments map to, if given with `--with-aptos-types`.
+    pub registry: Option<&'a Registry>,
+    pub options: &'a GeneratorOptions,
+}
+
+pub trait LanguageGenerator {
+    /// Name the language is selected by with `--language`, case insensitively.
+    fn name(&self) -> &str;
+
+    /// Writes the transaction builders, when there is no target directory to install them in.
+    fn output(
+        &self,
+        out: &mut dyn Write,
+        module_name: Option<&str>,
+        input: &GeneratorInput,
+    ) -> GeneratorResult;
+
+    /// Installs the transaction builders as module `module_name` in `install_dir`.
+    fn install_transaction_builders(
+        &self,
+        install_dir: &Path,
+        module_name: &str,
+        input: &GeneratorInput,
+    ) -> GeneratorResult;
+
+    /// Files written by `install_transaction_builders`, which incremental runs regenerate when
+    /// missing even if the ABIs are unchanged.
+    fn transaction_builders_files(&self, install_dir: &Path, module_name: &str) -> Vec<PathBuf> {
+        vec![install_dir.join(module_name)]
+    }
+
+    /// Installs the definitions of the Aptos types, and the runtimes they need if any, in
+    /// `install_dir`.
+    fn install_aptos_types(
+        &self,
+        _install_dir: &Path,
+        _registry: &Registry,
+        _options: &GeneratorOptions,
+    ) -> GeneratorResult {
+        Err(format!("{} can't install the Aptos types", self.name()).into())
+    }
+}
+
+/// The languages to pick from.
+pub struct Generators(Vec<Box<dyn LanguageGenerator>>);
+
+impl Generators {
+    /// Rust, Go and Python 3, and command lines in Rust.
+    pub fn builtin() -> Self {
+        Self(vec![
+            Box::new(RustGenerator),
+            Box::new(GoGenerator),
+            Box::new(Python3Generator),
+            Box::new(RustCliGenerator),
+        ])
+    }
+
+    /// Adds a language, in place of any of the same name.
+    pub fn register(mut self, generator: Box<dyn LanguageGenerator>) -> Self {
+        self.0
+            .retain(|g| !g.name().eq_ignore_ascii_case(generator.name()));
+        self.0.push(generator);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn LanguageGenerator> {
+        self.0
+            .iter()
+            .find(|g| g.name().eq_ignore_ascii_case(name))
+            .map(|g| g.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|g| g.name()).collect()
+    }
+}
+
+pub struct RustGenerator;
+
+impl LanguageGenerator for RustGenerator {
+    fn name(&self) -> &str {
+        "Rust"
+    }
+
+    fn output(
+        &self,
+        out: &mut dyn Write,
+        _module_name: Option<&str>,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        let rust_options = rust::RustOptions::new(/* local types */ true)
+            .with_edition(input.options.rust_edition)
+            .with_no_std(input.options.rust_no_std)
+            .with_returns(input.returns.to_vec())
+            .with_events(input.events.to_vec())
+            .with_naming(input.options.naming.clone())
+            .with_json(input.options.rust_json);
+        Ok(rust::output_with_options(out, input.abis, &rust_options)?)
+    }
+
+    fn install_transaction_builders(
+        &self,
+        install_dir: &Path,
+        module_name: &str,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        rust::Installer::new(
+            install_dir.to_path_buf(),
+            input.options.aptos_version_number.clone(),
+        )
+        .with_edition(input.options.rust_edition)
+        .with_no_std(input.options.rust_no_std)
+        .with_returns(input.returns.to_vec())
+        .with_events(input.events.to_vec())
+        .with_naming(input.options.naming.clone())
+        .with_json(input.options.rust_json)
+        .install_transaction_builders(module_name, input.abis)
+    }
+
+    fn transaction_builders_files(&self, install_dir: &Path, module_name: &str) -> Vec<PathBuf> {
+        let dir = install_dir.join(crate_name(module_name));
+        vec![dir.join("Cargo.toml"), dir.join("src/lib.rs")]
+    }
+
+    fn install_aptos_types(
+        &self,
+        install_dir: &Path,
+        registry: &Registry,
+        options: &GeneratorOptions,
+    ) -> GeneratorResult {
+        let package_name = if options.aptos_version_number == "0.1.0" {
+            "aptos-types".to_string()
+        } else {
+            format!("aptos-types:{}", options.aptos_version_number)
+        };
+        // Prevent language keywords from being used.
+        let mut registry = registry.clone();
+        rust::replace_keywords(&mut registry);
+        install_module(
+            &serdegen::rust::Installer::new(install_dir.to_path_buf()),
+            package_name,
+            &registry,
+        )
+    }
+}
+
+pub struct GoGenerator;
+
+impl LanguageGenerator for GoGenerator {
+    fn name(&self) -> &str {
+        "Go"
+    }
+
+    fn output(
+        &self,
+        out: &mut dyn Write,
+        module_name: Option<&str>,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        Ok(golang::output_with_naming(
+            out,
+            input.options.serde_package_name.clone(),
+            input.options.package_name.clone(),
+            module_name.unwrap_or("main").to_string(),
+            input.abis,
+            &input.options.naming,
+        )?)
+    }
+
+    fn install_transaction_builders(
+        &self,
+        install_dir: &Path,
+        module_name: &str,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        golang::Installer::new(
+            install_dir.to_path_buf(),
+            input.options.serde_package_name.clone(),
+            input.options.package_name.clone(),
+        )
+        .with_naming(input.options.naming.clone())
+        .install_transaction_builders(module_name, input.abis)
+    }
+
+    fn transaction_builders_files(&self, install_dir: &Path, module_name: &str) -> Vec<PathBuf> {
+        vec![install_dir.join(module_name).join("lib.go")]
+    }
+
+    fn install_aptos_types(
+        &self,
+        install_dir: &Path,
+        registry: &Registry,
+        options: &GeneratorOptions,
+    ) -> GeneratorResult {
+        install_module(
+            &serdegen::golang::Installer::new(
+                install_dir.to_path_buf(),
+                options.serde_package_name.clone(),
+            ),
+            "aptostypes".to_string(),
+            registry,
+        )
+    }
+}
+
+pub struct Python3Generator;
+
+impl LanguageGenerator for Python3Generator {
+    fn name(&self) -> &str {
+        "Python3"
+    }
+
+    fn output(
+        &self,
+        out: &mut dyn Write,
+        _module_name: Option<&str>,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        Ok(python3::output_with_naming(
+            out,
+            input.options.serde_package_name.clone(),
+            input.options.package_name.clone(),
+            input.abis,
+            &input.options.naming,
+        )?)
+    }
+
+    fn install_transaction_builders(
+        &self,
+        install_dir: &Path,
+        module_name: &str,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        python3::Installer::new(
+            install_dir.to_path_buf(),
+            input.options.serde_package_name.clone(),
+            input.options.package_name.clone(),
+        )
+        .with_naming(input.options.naming.clone())
+        .install_transaction_builders(module_name, input.abis)
+    }
+
+    fn transaction_builders_files(&self, install_dir: &Path, module_name: &str) -> Vec<PathBuf> {
+        let dir = module_name
+            .split('.')
+            .fold(install_dir.to_path_buf(), |dir, part| dir.join(part));
+        vec![dir.join("__init__.py"), dir.join("py.typed")]
+    }
+
+    fn install_aptos_types(
+        &self,
+        install_dir: &Path,
+        registry: &Registry,
+        options: &GeneratorOptions,
+    ) -> GeneratorResult {
+        let installer = serdegen::python3::Installer::new(
+            install_dir.to_path_buf(),
+            options.serde_package_name.clone(),
+        );
+        install_module(&installer, "aptos_types".to_string(), registry)?;
+        // Unlike Rust and Go, Python has no package manager to fetch the runtimes from.
+        installer.install_serde_runtime()?;
+        installer.install_bcs_runtime()?;
+        Ok(())
+    }
+}
+
+/// Command line with a subcommand per entry function, see `rust_cli`. The revision of aptos-core
+/// the installed crate depends on is given with `--generator-option aptos_rev=REV`.
+pub struct RustCliGenerator;
+
+impl LanguageGenerator for RustCliGenerator {
+    fn name(&self) -> &str {
+        "RustCli"
+    }
+
+    fn output(
+        &self,
+        out: &mut dyn Write,
+        _module_name: Option<&str>,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        Ok(rust_cli::output(out, input.abis, &input.options.naming)?)
+    }
+
+    fn install_transaction_builders(
+        &self,
+        install_dir: &Path,
+        module_name: &str,
+        input: &GeneratorInput,
+    ) -> GeneratorResult {
+        rust_cli::Installer::new(install_dir.to_path_buf())
+            .with_aptos_rev(input.options.extra.get("aptos_rev").cloned())
+            .with_naming(input.options.naming.clone())
+            .install_transaction_builders(module_name, input.abis)
+    }
+
+    fn transaction_builders_files(&self, install_dir: &Path, module_name: &str) -> Vec<PathBuf> {
+        let dir = install_dir.join(crate_name(module_name));
+        vec![
+            dir.join("Cargo.toml"),
+            dir.join("src/lib.rs"),
+            dir.join("src/main.rs"),
+        ]
+    }
+}
+
+/// Name of the crate installed as `module_name`, without its version if any, e.g. `test` for
+/// `test:1.2.0`.
+fn crate_name(module_name: &str) -> &str {
+    module_name.split(':').next().unwrap_or(module_name)
+}
+
+fn install_module(
+    installer: &dyn serdegen::SourceInstaller<Error = Box<dyn std::error::Error>>,
+    package_name: String,
+    registry: &Registry,
+) -> GeneratorResult {
+    let config = serdegen::CodeGeneratorConfig::new(package_name)
+        .with_encodings(vec![serdegen::Encoding::Bcs]);
+    installer.install_module(&config, registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeneratorInput, GeneratorOptions, GeneratorResult, Generators, LanguageGenerator};
+    use crate::rust::RustEdition;
+    use std::{io::Write, path::Path};
+
+    struct CountingGenerator;
+
+    impl LanguageGenerator for CountingGenerator {
+        fn name(&self) -> &str {
+            "Counting"
+        }
+
+        fn output(
+            &self,
+            out: &mut dyn Write,
+            _module_name: Option<&str>,
+            input: &GeneratorInput,
+        ) -> GeneratorResult {
+            Ok(writeln!(out, "{} functions", input.abis.len())?)
+        }
+
+        fn install_transaction_builders(
+            &self,
+            _install_dir: &Path,
+            _module_name: &str,
+            _input: &GeneratorInput,
+        ) -> GeneratorResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let generators = Generators::builtin().register(Box::new(CountingGenerator));
+        assert_eq!(generators.names(), vec![
+            "Rust", "Go", "Python3", "RustCli", "Counting"
+        ]);
+        assert_eq!(generators.get("counting").unwrap().name(), "Counting");
+        assert_eq!(generators.get("RUST").unwrap().name(), "Rust");
+        assert!(generators.get("Kotlin").is_none());
+        assert_eq!(
+            generators
+                .get("Rust")
+                .unwrap()
+                .transaction_builders_files(Path::new("sdk"), "test:1.2.0"),
+            vec![
+                Path::new("sdk/test/Cargo.toml"),
+                Path::new("sdk/test/src/lib.rs")
+            ]
+        );
+        assert_eq!(
+            generators
+                .get("Counting")
+                .unwrap()
+                .transaction_builders_files(Path::new("sdk"), "test"),
+            vec![Path::new("sdk/test")]
+        );
+        assert!(generators
+            .get("Counting")
+            .unwrap()
+            .install_aptos_types(Path::new("."), &Default::default(), &test_options())
+            .is_err());
+    }
+
+    fn test_options() -> GeneratorOptions {
+        GeneratorOptions {
+            serde_package_name: None,
+            package_name: None,
+            aptos_version_number: "0.1.0".to_string(),
+            rust_edition: RustEdition::Edition2021,
+            rust_no_std: false,
+            rust_json: false,
+            naming: Default::default(),
+            extra: Default::default(),
+        }
+    }
+}
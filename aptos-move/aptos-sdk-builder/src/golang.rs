@@ -2,7 +2,7 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::common;
+use crate::{common, naming::Naming};
 use aptos_types::transaction::{
     ArgumentABI, EntryABI, EntryFunctionABI, TransactionScriptABI, TypeArgumentABI,
 };
@@ -31,12 +31,33 @@ pub fn output(
     aptos_module_path: Option<String>,
     package_name: String,
     abis: &[EntryABI],
+) -> Result<()> {
+    output_with_naming(
+        out,
+        serde_module_path,
+        aptos_module_path,
+        package_name,
+        abis,
+        &Naming::default(),
+    )
+}
+
+/// Output transaction builders and decoders in Go for the given ABIs, with the functions named as
+/// given, see `Naming`.
+pub fn output_with_naming(
+    out: &mut dyn Write,
+    serde_module_path: Option<String>,
+    aptos_module_path: Option<String>,
+    package_name: String,
+    abis: &[EntryABI],
+    naming: &Naming,
 ) -> Result<()> {
     let mut emitter = GoEmitter {
         out: IndentedWriter::new(out, IndentConfig::Tab),
         serde_module_path,
         aptos_module_path,
         package_name,
+        naming: naming.clone(),
     };
 
     // Some functions have complex types which are not currently supported in bcs or in this
@@ -107,6 +128,8 @@ struct GoEmitter<T> {
     aptos_module_path: Option<String>,
     /// Name of the package owning the generated definitions (e.g. "my_package")
     package_name: String,
+    /// Namespaces and renames of the functions in generated names.
+    naming: Naming,
 }
 
 impl<T> GoEmitter<T>
@@ -136,7 +159,10 @@ where
         // Generate `ScriptCall` enums for all old-style transaction scripts
         let mut script_registry: BTreeMap<_, _> = vec![(
             "ScriptCall".to_string(),
-            crate::common::make_abi_enum_container(transaction_script_abis.as_slice()),
+            crate::common::make_abi_enum_container(
+                transaction_script_abis.as_slice(),
+                &self.naming,
+            ),
         )]
         .into_iter()
         .collect();
@@ -144,7 +170,7 @@ where
         // Generate `EntryFunctionCall` enums for all new transaction scripts
         let mut entry_function_registry: BTreeMap<_, _> = vec![(
             "EntryFunctionCall".to_string(),
-            crate::common::make_abi_enum_container(entry_fun_abis.as_slice()),
+            crate::common::make_abi_enum_container(entry_fun_abis.as_slice(), &self.naming),
         )]
         .into_iter()
         .collect();
@@ -256,8 +282,10 @@ func EncodeEntryFunction(call EntryFunctionCall) aptostypes.TransactionPayload {
                         self.out,
                         r#"case *EntryFunctionCall__{0}{1}:
                 return Encode{0}{1}({2})"#,
-                        abi.module_name().name().to_string().to_camel_case(),
-                        abi.name().to_camel_case(),
+                        self.naming.module_name(abi.module_name()).to_camel_case(),
+                        self.naming
+                            .function_name(abi.module_name(), abi.name())
+                            .to_camel_case(),
                         params,
                     )?;
                 }
@@ -344,8 +372,10 @@ func DecodeEntryFunctionPayload(script aptostypes.TransactionPayload) (EntryFunc
             self.out,
             "\n{}\nfunc Encode{}{}({}) aptostypes.TransactionPayload {{",
             Self::quote_doc(abi.doc()),
-            abi.module_name().name().to_string().to_camel_case(),
-            abi.name().to_camel_case(),
+            self.naming.module_name(abi.module_name()).to_camel_case(),
+            self.naming
+                .function_name(abi.module_name(), abi.name())
+                .to_camel_case(),
             [
                 Self::quote_type_parameters(abi.ty_args()),
                 Self::quote_parameters(abi.args()),
@@ -430,8 +460,8 @@ func DecodeEntryFunctionPayload(script aptostypes.TransactionPayload) (EntryFunc
         writeln!(
             self.out,
             "\nfunc decode_{}_{}(script aptostypes.TransactionPayload) (EntryFunctionCall, error) {{",
-            abi.module_name().name(),
-            abi.name(),
+            self.naming.module_name(abi.module_name()),
+            self.naming.function_name(abi.module_name(), abi.name()),
         )?;
         self.out.indent();
         writeln!(self.out, "switch script := interface{{}}(script).(type) {{")?;
@@ -454,8 +484,10 @@ func DecodeEntryFunctionPayload(script aptostypes.TransactionPayload) (EntryFunc
         writeln!(
             self.out,
             "var call EntryFunctionCall__{0}{1}",
-            abi.module_name().name().to_string().to_camel_case(),
-            abi.name().to_camel_case(),
+            self.naming.module_name(abi.module_name()).to_camel_case(),
+            self.naming
+                .function_name(abi.module_name(), abi.name())
+                .to_camel_case(),
         )?;
         for (index, ty_arg) in abi.ty_args().iter().enumerate() {
             writeln!(
@@ -540,9 +572,11 @@ var entry_function_decoder_map = map[string]func(aptostypes.TransactionPayload)
         for abi in abis {
             writeln!(
                 self.out,
-                "\"{0}_{1}\": decode_{0}_{1},",
+                "\"{}_{}\": decode_{}_{},",
                 abi.module_name().name(),
                 abi.name(),
+                self.naming.module_name(abi.module_name()),
+                self.naming.function_name(abi.module_name(), abi.name()),
             )?;
         }
         self.out.unindent();
@@ -806,6 +840,7 @@ pub struct Installer {
     install_dir: PathBuf,
     serde_module_path: Option<String>,
     aptos_module_path: Option<String>,
+    naming: Naming,
 }
 
 impl Installer {
@@ -818,8 +853,14 @@ impl Installer {
             install_dir,
             serde_module_path,
             aptos_module_path,
+            naming: Naming::default(),
         }
     }
+
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
 }
 
 impl crate::SourceInstaller for Installer {
@@ -833,12 +874,13 @@ impl crate::SourceInstaller for Installer {
         let dir_path = self.install_dir.join(name);
         std::fs::create_dir_all(&dir_path)?;
         let mut file = std::fs::File::create(dir_path.join("lib.go"))?;
-        output(
+        output_with_naming(
             &mut file,
             self.serde_module_path.clone(),
             self.aptos_module_path.clone(),
             name.to_string(),
             abis,
+            &self.naming,
         )?;
         Ok(())
     }
@@ -3,23 +3,71 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use aptos_types::transaction::EntryABI;
+use move_core_types::language_storage::{ModuleId, TypeTag};
+use serde::{Deserialize, Serialize};
 use std::{ffi::OsStr, fs, io::Read, path::Path};
 
+pub mod cache;
+pub mod cli;
+pub mod generator;
 pub mod golang;
+pub mod naming;
+pub mod python3;
 pub mod rust;
+pub mod rust_cli;
 
 /// Internals shared between languages.
 mod common;
 
-fn get_abi_paths(dir: &Path) -> std::io::Result<Vec<String>> {
+/// Extension of the files holding a `ReturnABI`, next to the `.abi` file of the same function.
+pub const RETURN_ABI_EXTENSION: &str = "ret";
+
+/// Declared return types of a Move function, which `EntryABI` doesn't carry. Entry functions
+/// return nothing when called by a transaction, but their values can be consumed by the next
+/// call of a composed script, and view functions are only ever called for them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnABI {
+    /// Module of the function.
+    pub module_name: ModuleId,
+    /// Name of the function.
+    pub name: String,
+    /// Whether the function is a `#[view]` function.
+    pub is_view: bool,
+    /// Types of the values returned, in order.
+    pub returns: Vec<TypeTag>,
+}
+
+/// Extension of the files holding an `EventABI`, in the ABI directory of the module declaring the
+/// struct.
+pub const EVENT_ABI_EXTENSION: &str = "evt";
+
+/// Layout of a Move struct emitted as an event, for generated code to decode the events of the
+/// modules without hand-written mirror structs. Generic structs are left out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventABI {
+    /// Module declaring the struct.
+    pub module_name: ModuleId,
+    /// Name of the struct.
+    pub name: String,
+    /// Fields of the struct, in declaration order, which is the order they are encoded in.
+    pub fields: Vec<EventFieldABI>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFieldABI {
+    pub name: String,
+    pub type_tag: TypeTag,
+}
+
+fn get_abi_paths(dir: &Path, extension: &str) -> std::io::Result<Vec<String>> {
     let mut abi_paths = Vec::new();
     if dir.is_dir() {
         for entry in fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
             if path.is_dir() {
-                abi_paths.append(&mut get_abi_paths(&path)?);
-            } else if let Some("abi") = path.extension().and_then(OsStr::to_str) {
+                abi_paths.append(&mut get_abi_paths(&path, extension)?);
+            } else if path.extension().and_then(OsStr::to_str) == Some(extension) {
                 abi_paths.push(path.to_str().unwrap().to_string());
             }
         }
@@ -31,7 +79,7 @@ fn get_abi_paths(dir: &Path) -> std::io::Result<Vec<String>> {
 pub fn read_abis(dir_paths: &[impl AsRef<Path>]) -> anyhow::Result<Vec<EntryABI>> {
     let mut abis = Vec::<EntryABI>::new();
     for dir in dir_paths.iter() {
-        for path in get_abi_paths(dir.as_ref())? {
+        for path in get_abi_paths(dir.as_ref(), "abi")? {
             let mut buffer = Vec::new();
             let mut f = std::fs::File::open(path)?;
             f.read_to_end(&mut buffer)?;
@@ -56,6 +104,32 @@ pub fn read_abis(dir_paths: &[impl AsRef<Path>]) -> anyhow::Result<Vec<EntryABI>
     Ok(abis)
 }
 
+/// Read the return types stored next to the ABI files of the specified directories, if any,
+/// sorted like `read_abis`.
+pub fn read_return_abis(dir_paths: &[impl AsRef<Path>]) -> anyhow::Result<Vec<ReturnABI>> {
+    let mut abis = Vec::<ReturnABI>::new();
+    for dir in dir_paths.iter() {
+        for path in get_abi_paths(dir.as_ref(), RETURN_ABI_EXTENSION)? {
+            abis.push(bcs::from_bytes(&std::fs::read(path)?)?);
+        }
+    }
+    abis.sort_by(|a, b| (a.module_name.name(), &a.name).cmp(&(b.module_name.name(), &b.name)));
+    Ok(abis)
+}
+
+/// Read the event layouts stored next to the ABI files of the specified directories, if any,
+/// sorted by module and struct name.
+pub fn read_event_abis(dir_paths: &[impl AsRef<Path>]) -> anyhow::Result<Vec<EventABI>> {
+    let mut abis = Vec::<EventABI>::new();
+    for dir in dir_paths.iter() {
+        for path in get_abi_paths(dir.as_ref(), EVENT_ABI_EXTENSION)? {
+            abis.push(bcs::from_bytes(&std::fs::read(path)?)?);
+        }
+    }
+    abis.sort_by(|a, b| (a.module_name.name(), &a.name).cmp(&(b.module_name.name(), &b.name)));
+    Ok(abis)
+}
+
 /// How to copy ABI-generated source code for a given language.
 pub trait SourceInstaller {
     type Error;
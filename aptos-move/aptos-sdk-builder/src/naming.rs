@@ -0,0 +1,175 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Names generated for the functions of the modules, which by default are made of the name of the
+//! module and the name of the function, e.g. `coin_transfer`. Projects publishing modules at
+//! several addresses map the addresses to namespaces, e.g. `0x1` to `aptos_framework` for
+//! `aptos_framework_coin_transfer`, and rename the functions whose names still collide, with a
+//! YAML file given to `--naming-config`:
+//!
+//! ```yaml
+//! namespaces:
+//!   "0x1": aptos_framework
+//!   "0xcafe": my_project
+//! renames:
+//!   "0xcafe::coin::transfer": transfer_with_fee
+//! ```
+//!
+//! Only the generated names change, the payloads still call the functions of the modules.
+
+use crate::common;
+use anyhow::{bail, format_err, Result};
+use aptos_types::transaction::EntryABI;
+use move_core_types::{
+    account_address::AccountAddress, identifier::Identifier, language_storage::ModuleId,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamingConfig {
+    /// Namespace of the modules at an address, keyed by the address, e.g. `"0x1"`.
+    #[serde(default)]
+    namespaces: BTreeMap<String, String>,
+    /// Name of a function, keyed by `<address>::<module>::<function>`.
+    #[serde(default)]
+    renames: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Naming {
+    namespaces: BTreeMap<AccountAddress, String>,
+    renames: BTreeMap<(ModuleId, String), String>,
+}
+
+impl Naming {
+    /// Reads the naming config, see the module documentation.
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let config: NamingConfig = serde_yaml::from_str(content)?;
+        let mut naming = Self::default();
+        for (address, namespace) in config.namespaces {
+            naming = naming.with_namespace(parse_address(&address)?, namespace)?;
+        }
+        for (function, name) in config.renames {
+            let parts: Vec<_> = function.split("::").collect();
+            if parts.len() != 3 {
+                bail!(
+                    "Expected <address>::<module>::<function> to rename, got {}",
+                    function
+                );
+            }
+            let module = ModuleId::new(parse_address(parts[0])?, Identifier::new(parts[1])?);
+            naming = naming.with_rename(module, parts[2], name)?;
+        }
+        Ok(naming)
+    }
+
+    /// Prefixes the names generated for the modules at the address with the namespace.
+    pub fn with_namespace(mut self, address: AccountAddress, namespace: String) -> Result<Self> {
+        if !Identifier::is_valid(&namespace) {
+            bail!("Invalid namespace {} for {}", namespace, address);
+        }
+        self.namespaces.insert(address, namespace);
+        Ok(self)
+    }
+
+    /// Generates the names of the function with the name given in its place.
+    pub fn with_rename(mut self, module: ModuleId, function: &str, name: String) -> Result<Self> {
+        if !Identifier::is_valid(&name) {
+            bail!(
+                "Invalid name {} for {}::{}",
+                name,
+                module.short_str_lossless(),
+                function
+            );
+        }
+        self.renames.insert((module, function.to_string()), name);
+        Ok(self)
+    }
+
+    /// Name of the module in generated names, prefixed with the namespace of its address if any,
+    /// e.g. `aptos_framework_coin`.
+    pub fn module_name(&self, module: &ModuleId) -> String {
+        match self.namespaces.get(module.address()) {
+            Some(namespace) => format!("{}_{}", namespace, module.name()),
+            None => module.name().to_string(),
+        }
+    }
+
+    /// Name of the function in generated names, which are then prefixed with `module_name`.
+    pub fn function_name(&self, module: &ModuleId, function: &str) -> String {
+        self.renames
+            .get(&(module.clone(), function.to_string()))
+            .cloned()
+            .unwrap_or_else(|| function.to_string())
+    }
+
+    /// The generated names shared by several functions, each with the functions sharing it, e.g.
+    /// `CoinTransfer: 0x1::coin::transfer, 0xcafe::coin::transfer`.
+    pub fn collisions(&self, abis: &[EntryABI]) -> Vec<String> {
+        let mut functions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for abi in abis {
+            let function = match abi {
+                EntryABI::EntryFunction(abi) => {
+                    format!("{}::{}", abi.module_name().short_str_lossless(), abi.name())
+                },
+                EntryABI::TransactionScript(abi) => abi.name().to_string(),
+            };
+            functions
+                .entry(common::variant_name(abi, self))
+                .or_default()
+                .push(function);
+        }
+        functions
+            .into_iter()
+            .filter(|(_, functions)| functions.len() > 1)
+            .map(|(name, functions)| format!("{}: {}", name, functions.join(", ")))
+            .collect()
+    }
+}
+
+fn parse_address(address: &str) -> Result<AccountAddress> {
+    AccountAddress::from_hex_literal(address)
+        .map_err(|_| format_err!("Invalid address {}, expected e.g. 0x1", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Naming;
+    use move_core_types::{
+        account_address::AccountAddress, identifier::Identifier, language_storage::ModuleId,
+    };
+
+    #[test]
+    fn test_naming() {
+        let naming = Naming::from_yaml(
+            r#"
+namespaces:
+  "0x1": aptos_framework
+renames:
+  "0xcafe::coin::transfer": transfer_with_fee
+"#,
+        )
+        .unwrap();
+        let framework_coin = ModuleId::new(AccountAddress::ONE, Identifier::new("coin").unwrap());
+        let cafe_coin = ModuleId::new(
+            AccountAddress::from_hex_literal("0xcafe").unwrap(),
+            Identifier::new("coin").unwrap(),
+        );
+        assert_eq!(naming.module_name(&framework_coin), "aptos_framework_coin");
+        assert_eq!(naming.module_name(&cafe_coin), "coin");
+        assert_eq!(
+            naming.function_name(&framework_coin, "transfer"),
+            "transfer"
+        );
+        assert_eq!(
+            naming.function_name(&cafe_coin, "transfer"),
+            "transfer_with_fee"
+        );
+
+        assert!(Naming::from_yaml("namespaces:\n  \"0x1\": aptos-framework").is_err());
+        assert!(Naming::from_yaml("renames:\n  \"coin::transfer\": send").is_err());
+        assert!(Naming::from_yaml("aliases: {}").is_err());
+    }
+}
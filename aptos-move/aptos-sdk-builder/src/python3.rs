@@ -0,0 +1,810 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{common, naming::Naming};
+use aptos_types::transaction::{
+    ArgumentABI, EntryABI, EntryFunctionABI, TransactionScriptABI, TypeArgumentABI,
+};
+use heck::CamelCase;
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{ModuleId, StructTag, TypeTag},
+};
+use once_cell::sync::Lazy;
+use serde_generate::{
+    indent::{IndentConfig, IndentedWriter},
+    python3, CodeGeneratorConfig,
+};
+use std::{
+    collections::BTreeMap,
+    io::{Result, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Output transaction builders and decoders in Python for the given ABIs.
+pub fn output(
+    out: &mut dyn Write,
+    serde_package_name: Option<String>,
+    aptos_package_name: Option<String>,
+    abis: &[EntryABI],
+) -> Result<()> {
+    output_with_naming(
+        out,
+        serde_package_name,
+        aptos_package_name,
+        abis,
+        &Naming::default(),
+    )
+}
+
+/// Output transaction builders and decoders in Python for the given ABIs, with the functions
+/// named as given, see `Naming`.
+pub fn output_with_naming(
+    out: &mut dyn Write,
+    serde_package_name: Option<String>,
+    aptos_package_name: Option<String>,
+    abis: &[EntryABI],
+    naming: &Naming,
+) -> Result<()> {
+    let mut emitter = PythonEmitter {
+        out: IndentedWriter::new(out, IndentConfig::Space(4)),
+        serde_package_name,
+        aptos_package_name,
+        naming: naming.clone(),
+    };
+    emitter.output_script_call_enum_with_imports(abis)?;
+
+    let (transaction_script_abis, entry_fun_abis): (Vec<_>, Vec<_>) = abis
+        .iter()
+        .cloned()
+        .partition(|abi| abi.is_transaction_script_abi());
+    if !transaction_script_abis.is_empty() {
+        emitter.output_transaction_script_encode_method()?;
+        emitter.output_transaction_script_decode_method()?;
+    }
+    if !entry_fun_abis.is_empty() {
+        emitter.output_entry_function_encode_method()?;
+        emitter.output_entry_function_decode_method()?;
+    }
+
+    for abi in abis {
+        match abi {
+            EntryABI::TransactionScript(abi) => {
+                emitter.output_transaction_script_encoder_function(abi)?
+            },
+            EntryABI::EntryFunction(abi) => emitter.output_entry_function_encoder_function(abi)?,
+        };
+    }
+
+    for abi in abis {
+        match abi {
+            EntryABI::TransactionScript(abi) => {
+                emitter.output_transaction_script_decoder_function(abi)?
+            },
+            EntryABI::EntryFunction(abi) => emitter.output_entry_function_decoder_function(abi)?,
+        };
+    }
+
+    for abi in abis {
+        emitter.output_code_constant(abi)?;
+    }
+    if !transaction_script_abis.is_empty() {
+        emitter.output_transaction_script_encoder_map(&common::transaction_script_abis(abis))?;
+        emitter.output_transaction_script_decoder_map(&common::transaction_script_abis(abis))?;
+    }
+    if !entry_fun_abis.is_empty() {
+        emitter.output_entry_function_encoder_map(&common::entry_function_abis(abis))?;
+        emitter.output_entry_function_decoder_map(&common::entry_function_abis(abis))?;
+    }
+
+    emitter.output_decoding_helpers(&common::filter_transaction_scripts(abis))?;
+
+    Ok(())
+}
+
+/// Shared state for the Python code generator.
+struct PythonEmitter<T> {
+    /// Writer.
+    out: IndentedWriter<T>,
+    /// Package where to find the serde module (if any).
+    serde_package_name: Option<String>,
+    /// Package where to find the aptos module (if any).
+    aptos_package_name: Option<String>,
+    /// Namespaces and renames of the functions in generated names.
+    naming: Naming,
+}
+
+impl<T> PythonEmitter<T>
+where
+    T: Write,
+{
+    fn quote_from_package(package_name: &Option<String>) -> String {
+        match package_name {
+            None => "".to_string(),
+            Some(name) => format!("from {} ", name),
+        }
+    }
+
+    fn output_script_call_enum_with_imports(&mut self, abis: &[EntryABI]) -> Result<()> {
+        let external_definitions = common::get_external_definitions("aptos_types");
+        let (transaction_script_abis, entry_fun_abis): (Vec<_>, Vec<_>) = abis
+            .iter()
+            .cloned()
+            .partition(|abi| abi.is_transaction_script_abi());
+
+        let mut registry = BTreeMap::new();
+        if !transaction_script_abis.is_empty() {
+            registry.insert(
+                "ScriptCall".to_string(),
+                common::make_abi_enum_container(transaction_script_abis.as_slice(), &self.naming),
+            );
+        }
+        if !entry_fun_abis.is_empty() {
+            registry.insert(
+                "EntryFunctionCall".to_string(),
+                common::make_abi_enum_container(entry_fun_abis.as_slice(), &self.naming),
+            );
+        }
+
+        let mut comments: BTreeMap<_, _> = abis
+            .iter()
+            .map(|abi| {
+                let enum_name = match abi {
+                    EntryABI::TransactionScript(_) => "ScriptCall",
+                    EntryABI::EntryFunction(_) => "EntryFunctionCall",
+                };
+                (
+                    vec![
+                        String::new(),
+                        enum_name.to_string(),
+                        common::variant_name(abi, &self.naming),
+                    ],
+                    common::prepare_doc_string(abi.doc()),
+                )
+            })
+            .collect();
+        comments.insert(
+            vec![String::new(), "ScriptCall".to_string()],
+            "Structured representation of a call into a known Move transaction script (legacy)."
+                .into(),
+        );
+        comments.insert(
+            vec![String::new(), "EntryFunctionCall".to_string()],
+            "Structured representation of a call into a known Move entry function.".into(),
+        );
+
+        // The generated classes refer to `aptos_types`, which must be imported first. BCS is needed
+        // for argument encoding and decoding.
+        writeln!(
+            self.out,
+            "{}import bcs\n{}import aptos_types",
+            Self::quote_from_package(&self.serde_package_name),
+            Self::quote_from_package(&self.aptos_package_name),
+        )?;
+        // The fields of the calls refer to `U256`, which neither runtime defines.
+        if abis
+            .iter()
+            .flat_map(|abi| abi.args())
+            .any(|arg| Self::mentions_u256(arg.type_tag()))
+        {
+            writeln!(
+                self.out,
+                "\n# Move `u256`, encoded by BCS as 32 little-endian bytes.\nU256 = int"
+            )?;
+        }
+
+        let config = CodeGeneratorConfig::new(String::new())
+            .with_comments(comments)
+            .with_external_definitions(external_definitions)
+            .with_serialization(false);
+        python3::CodeGenerator::new(&config)
+            .with_serde_package_name(self.serde_package_name.clone())
+            .output(&mut self.out, &registry)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, format!("{}", err)))?;
+        Ok(())
+    }
+
+    fn output_transaction_script_encode_method(&mut self) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+def encode_script(call: ScriptCall) -> aptos_types.Script:
+    """Build an Aptos `Script` from a structured object `ScriptCall`."""
+    helper = TRANSACTION_SCRIPT_ENCODER_MAP[call.__class__]
+    return helper(call)
+"#
+        )
+    }
+
+    fn output_entry_function_encode_method(&mut self) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+def encode_entry_function(call: EntryFunctionCall) -> aptos_types.TransactionPayload:
+    """Build an Aptos `TransactionPayload` from a structured object `EntryFunctionCall`."""
+    helper = ENTRY_FUNCTION_ENCODER_MAP[call.__class__]
+    return helper(call)
+"#
+        )
+    }
+
+    fn output_transaction_script_decode_method(&mut self) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+def decode_script(script: aptos_types.Script) -> ScriptCall:
+    """Try to recognize an Aptos `Script` and convert it into a structured object `ScriptCall`."""
+    helper = TRANSACTION_SCRIPT_DECODER_MAP.get(script.code)
+    if helper is None:
+        raise ValueError("Unknown script bytecode")
+    return helper(script)
+"#
+        )
+    }
+
+    fn output_entry_function_decode_method(&mut self) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+def decode_entry_function_payload(payload: aptos_types.TransactionPayload) -> EntryFunctionCall:
+    """Try to recognize an Aptos `TransactionPayload` and convert it into a structured object `EntryFunctionCall`."""
+    if not isinstance(payload, aptos_types.TransactionPayload__EntryFunction):
+        raise ValueError("Unexpected transaction payload")
+    script = payload.value
+    helper = ENTRY_FUNCTION_DECODER_MAP.get(script.module.name.value + "_" + script.function.value)
+    if helper is None:
+        raise ValueError("Unknown entry function: " + script.module.name.value + "::" + script.function.value)
+    return helper(script)
+"#
+        )
+    }
+
+    fn output_transaction_script_encoder_function(
+        &mut self,
+        abi: &TransactionScriptABI,
+    ) -> Result<()> {
+        writeln!(
+            self.out,
+            "\ndef encode_{}_script({}) -> aptos_types.Script:",
+            abi.name(),
+            [
+                Self::quote_type_parameters(abi.ty_args()),
+                Self::quote_parameters(abi.args()),
+            ]
+            .concat()
+            .join(", ")
+        )?;
+        self.out.indent();
+        writeln!(self.out, "{}", Self::quote_doc(abi.doc()))?;
+        writeln!(
+            self.out,
+            r#"return aptos_types.Script(
+    code={}_CODE,
+    ty_args=[{}],
+    args=[{}],
+)
+"#,
+            abi.name().to_uppercase(),
+            Self::quote_type_arguments(abi.ty_args()),
+            Self::quote_arguments_for_script(abi.args()),
+        )?;
+        self.out.unindent();
+        Ok(())
+    }
+
+    fn output_entry_function_encoder_function(&mut self, abi: &EntryFunctionABI) -> Result<()> {
+        writeln!(
+            self.out,
+            "\ndef encode_{}_{}({}) -> aptos_types.TransactionPayload:",
+            self.naming.module_name(abi.module_name()),
+            self.naming.function_name(abi.module_name(), abi.name()),
+            [
+                Self::quote_type_parameters(abi.ty_args()),
+                Self::quote_parameters(abi.args()),
+            ]
+            .concat()
+            .join(", ")
+        )?;
+        self.out.indent();
+        writeln!(self.out, "{}", Self::quote_doc(abi.doc()))?;
+        writeln!(
+            self.out,
+            r#"return aptos_types.TransactionPayload__EntryFunction(
+    value=aptos_types.EntryFunction(
+        module={},
+        function={},
+        ty_args=[{}],
+        args=[{}],
+    )
+)
+"#,
+            Self::quote_module_id(abi.module_name()),
+            Self::quote_identifier(abi.name()),
+            Self::quote_type_arguments(abi.ty_args()),
+            Self::quote_arguments(abi.args()),
+        )?;
+        self.out.unindent();
+        Ok(())
+    }
+
+    fn output_transaction_script_decoder_function(
+        &mut self,
+        abi: &TransactionScriptABI,
+    ) -> Result<()> {
+        writeln!(
+            self.out,
+            "\ndef decode_{}_script(script: aptos_types.Script) -> ScriptCall:",
+            abi.name(),
+        )?;
+        self.out.indent();
+        self.output_argument_count_checks("script", abi.ty_args().len(), abi.args().len())?;
+        writeln!(
+            self.out,
+            "return ScriptCall__{}(",
+            abi.name().to_camel_case()
+        )?;
+        self.out.indent();
+        for (index, ty_arg) in abi.ty_args().iter().enumerate() {
+            writeln!(self.out, "{}=script.ty_args[{}],", ty_arg.name(), index)?;
+        }
+        for (index, arg) in abi.args().iter().enumerate() {
+            writeln!(
+                self.out,
+                "{}=decode_{}_argument(script.args[{}]),",
+                arg.name(),
+                common::mangle_type(arg.type_tag()),
+                index,
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, ")\n")?;
+        self.out.unindent();
+        Ok(())
+    }
+
+    fn output_entry_function_decoder_function(&mut self, abi: &EntryFunctionABI) -> Result<()> {
+        writeln!(
+            self.out,
+            "\ndef decode_{}_{}(script: aptos_types.EntryFunction) -> EntryFunctionCall:",
+            self.naming.module_name(abi.module_name()),
+            self.naming.function_name(abi.module_name(), abi.name()),
+        )?;
+        self.out.indent();
+        self.output_argument_count_checks("script", abi.ty_args().len(), abi.args().len())?;
+        writeln!(
+            self.out,
+            "return EntryFunctionCall__{}{}(",
+            self.naming.module_name(abi.module_name()).to_camel_case(),
+            self.naming
+                .function_name(abi.module_name(), abi.name())
+                .to_camel_case(),
+        )?;
+        self.out.indent();
+        for (index, ty_arg) in abi.ty_args().iter().enumerate() {
+            writeln!(self.out, "{}=script.ty_args[{}],", ty_arg.name(), index)?;
+        }
+        for (index, arg) in abi.args().iter().enumerate() {
+            if arg.type_tag() == &TypeTag::U256 {
+                writeln!(
+                    self.out,
+                    "{}=int.from_bytes(script.args[{}], \"little\"),",
+                    arg.name(),
+                    index,
+                )?;
+            } else {
+                writeln!(
+                    self.out,
+                    "{}=bcs.deserialize(script.args[{}], {})[0],",
+                    arg.name(),
+                    index,
+                    Self::quote_serialized_type(arg.type_tag()),
+                )?;
+            }
+        }
+        self.out.unindent();
+        writeln!(self.out, ")\n")?;
+        self.out.unindent();
+        Ok(())
+    }
+
+    fn output_argument_count_checks(
+        &mut self,
+        script: &str,
+        ty_args: usize,
+        args: usize,
+    ) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"if len({0}.ty_args) < {1}:
+    raise ValueError("Was expecting {1} type arguments")
+if len({0}.args) < {2}:
+    raise ValueError("Was expecting {2} regular arguments")"#,
+            script, ty_args, args,
+        )
+    }
+
+    fn output_code_constant(&mut self, abi: &EntryABI) -> Result<()> {
+        if let EntryABI::TransactionScript(abi) = abi {
+            writeln!(
+                self.out,
+                "\n{}_CODE = b\"{}\"",
+                abi.name().to_uppercase(),
+                abi.code()
+                    .iter()
+                    .map(|x| format!("\\x{:02x}", x))
+                    .collect::<Vec<_>>()
+                    .join(""),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn output_transaction_script_encoder_map(
+        &mut self,
+        abis: &[TransactionScriptABI],
+    ) -> Result<()> {
+        writeln!(
+            self.out,
+            "\n# pyre-ignore\nTRANSACTION_SCRIPT_ENCODER_MAP: typing.Dict[typing.Type[ScriptCall], typing.Callable[[ScriptCall], aptos_types.Script]] = {{"
+        )?;
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "ScriptCall__{}: lambda call: encode_{}_script({}),",
+                abi.name().to_camel_case(),
+                abi.name(),
+                Self::quote_call_fields(abi.ty_args(), abi.args()),
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_transaction_script_decoder_map(
+        &mut self,
+        abis: &[TransactionScriptABI],
+    ) -> Result<()> {
+        writeln!(
+            self.out,
+            "\nTRANSACTION_SCRIPT_DECODER_MAP: typing.Dict[bytes, typing.Callable[[aptos_types.Script], ScriptCall]] = {{"
+        )?;
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "{}_CODE: decode_{}_script,",
+                abi.name().to_uppercase(),
+                abi.name()
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_entry_function_encoder_map(&mut self, abis: &[EntryFunctionABI]) -> Result<()> {
+        writeln!(
+            self.out,
+            "\n# pyre-ignore\nENTRY_FUNCTION_ENCODER_MAP: typing.Dict[typing.Type[EntryFunctionCall], typing.Callable[[EntryFunctionCall], aptos_types.TransactionPayload]] = {{"
+        )?;
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "EntryFunctionCall__{}{}: lambda call: encode_{}_{}({}),",
+                self.naming.module_name(abi.module_name()).to_camel_case(),
+                self.naming
+                    .function_name(abi.module_name(), abi.name())
+                    .to_camel_case(),
+                self.naming.module_name(abi.module_name()),
+                self.naming.function_name(abi.module_name(), abi.name()),
+                Self::quote_call_fields(abi.ty_args(), abi.args()),
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_entry_function_decoder_map(&mut self, abis: &[EntryFunctionABI]) -> Result<()> {
+        writeln!(
+            self.out,
+            "\nENTRY_FUNCTION_DECODER_MAP: typing.Dict[str, typing.Callable[[aptos_types.EntryFunction], EntryFunctionCall]] = {{"
+        )?;
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "\"{}_{}\": decode_{}_{},",
+                abi.module_name().name(),
+                abi.name(),
+                self.naming.module_name(abi.module_name()),
+                self.naming.function_name(abi.module_name(), abi.name()),
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_decoding_helpers(&mut self, abis: &[EntryABI]) -> Result<()> {
+        let required_types = common::get_required_helper_types(abis);
+        for required_type in required_types {
+            self.output_decoding_helper(required_type)?;
+        }
+        Ok(())
+    }
+
+    fn output_decoding_helper(&mut self, type_tag: &TypeTag) -> Result<()> {
+        use TypeTag::*;
+        let constructor = match type_tag {
+            Bool => "Bool",
+            U8 => "U8",
+            U16 => "U16",
+            U32 => "U32",
+            U64 => "U64",
+            U128 => "U128",
+            U256 => "U256",
+            Address => "Address",
+            Vector(type_tag) => match type_tag.as_ref() {
+                U8 => "U8Vector",
+                _ => common::type_not_allowed(type_tag),
+            },
+            Struct(_) | Signer => common::type_not_allowed(type_tag),
+        };
+        let value = match type_tag {
+            U256 => "int.from_bytes(bytes(arg.value), \"little\")",
+            _ => "arg.value",
+        };
+        writeln!(
+            self.out,
+            r#"
+def decode_{0}_argument(arg: aptos_types.TransactionArgument) -> {1}:
+    if not isinstance(arg, aptos_types.TransactionArgument__{2}):
+        raise ValueError("Was expecting a {2} argument")
+    return {3}
+"#,
+            common::mangle_type(type_tag),
+            Self::quote_type(type_tag),
+            constructor,
+            value,
+        )
+    }
+
+    fn quote_identifier(ident: &str) -> String {
+        format!("aptos_types.Identifier(value=\"{}\")", ident)
+    }
+
+    fn quote_address(address: &AccountAddress) -> String {
+        format!(
+            "aptos_types.AccountAddress(value=({}))",
+            address
+                .to_vec()
+                .iter()
+                .map(|x| format!("st.uint8({}),", x))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    }
+
+    fn quote_module_id(module_id: &ModuleId) -> String {
+        format!(
+            "aptos_types.ModuleId(address={}, name={})",
+            Self::quote_address(module_id.address()),
+            Self::quote_identifier(module_id.name().as_str()),
+        )
+    }
+
+    fn quote_doc(doc: &str) -> String {
+        let doc = common::prepare_doc_string(doc);
+        let s: Vec<_> = doc.splitn(2, |c| c == '.').collect();
+        if s.len() <= 1 || s[1].is_empty() {
+            format!("\"\"\"{}.\"\"\"", s[0])
+        } else {
+            format!(
+                "\"\"\"{}.\n\n{}\n\"\"\"",
+                s[0],
+                s[1].trim().replace("\"\"\"", "\\\"\\\"\\\"")
+            )
+        }
+    }
+
+    fn quote_type_parameters(ty_args: &[TypeArgumentABI]) -> Vec<String> {
+        ty_args
+            .iter()
+            .map(|ty_arg| format!("{}: aptos_types.TypeTag", ty_arg.name()))
+            .collect()
+    }
+
+    fn quote_parameters(args: &[ArgumentABI]) -> Vec<String> {
+        args.iter()
+            .map(|arg| format!("{}: {}", arg.name(), Self::quote_type(arg.type_tag())))
+            .collect()
+    }
+
+    fn quote_type_arguments(ty_args: &[TypeArgumentABI]) -> String {
+        ty_args
+            .iter()
+            .map(|ty_arg| ty_arg.name().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The fields of a call object, in the order of the parameters of its encoder.
+    fn quote_call_fields(ty_args: &[TypeArgumentABI], args: &[ArgumentABI]) -> String {
+        std::iter::empty()
+            .chain(ty_args.iter().map(TypeArgumentABI::name))
+            .chain(args.iter().map(ArgumentABI::name))
+            .map(|name| format!("call.{}", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn quote_arguments(args: &[ArgumentABI]) -> String {
+        args.iter()
+            .map(|arg| match arg.type_tag() {
+                TypeTag::U256 => format!("{}.to_bytes(32, \"little\")", arg.name()),
+                type_tag => format!(
+                    "bcs.serialize({}, {})",
+                    arg.name(),
+                    Self::quote_serialized_type(type_tag)
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn quote_arguments_for_script(args: &[ArgumentABI]) -> String {
+        args.iter()
+            .map(|arg| Self::quote_transaction_argument_for_script(arg.type_tag(), arg.name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn quote_type(type_tag: &TypeTag) -> String {
+        use TypeTag::*;
+        let str_tag: Lazy<StructTag> =
+            Lazy::new(|| StructTag::from_str("0x1::string::String").unwrap());
+        match type_tag {
+            Bool => "st.bool".into(),
+            U8 => "st.uint8".into(),
+            U16 => "st.uint16".into(),
+            U32 => "st.uint32".into(),
+            U64 => "st.uint64".into(),
+            U128 => "st.uint128".into(),
+            U256 => "int".into(),
+            Address => "aptos_types.AccountAddress".into(),
+            Vector(type_tag) => format!("typing.Sequence[{}]", Self::quote_type(type_tag)),
+            Struct(struct_tag) => match struct_tag {
+                tag if &**tag == Lazy::force(&str_tag) => "typing.Sequence[st.uint8]".into(),
+                _ => common::type_not_allowed(type_tag),
+            },
+            Signer => common::type_not_allowed(type_tag),
+        }
+    }
+
+    /// The type of a value serialized by the BCS runtime, which has no `u256`: those are only
+    /// supported as arguments on their own, not within vectors.
+    fn quote_serialized_type(type_tag: &TypeTag) -> String {
+        if Self::mentions_u256(type_tag) {
+            common::type_not_allowed(type_tag)
+        }
+        Self::quote_type(type_tag)
+    }
+
+    fn mentions_u256(type_tag: &TypeTag) -> bool {
+        match type_tag {
+            TypeTag::U256 => true,
+            TypeTag::Vector(type_tag) => Self::mentions_u256(type_tag),
+            _ => false,
+        }
+    }
+
+    fn quote_transaction_argument_for_script(type_tag: &TypeTag, name: &str) -> String {
+        use TypeTag::*;
+        match type_tag {
+            Bool => format!("aptos_types.TransactionArgument__Bool(value={})", name),
+            U8 => format!("aptos_types.TransactionArgument__U8(value={})", name),
+            U16 => format!("aptos_types.TransactionArgument__U16(value={})", name),
+            U32 => format!("aptos_types.TransactionArgument__U32(value={})", name),
+            U64 => format!("aptos_types.TransactionArgument__U64(value={})", name),
+            U128 => format!("aptos_types.TransactionArgument__U128(value={})", name),
+            U256 => format!(
+                "aptos_types.TransactionArgument__U256(value=tuple(st.uint8(x) for x in {}.to_bytes(32, \"little\")))",
+                name
+            ),
+            Address => format!("aptos_types.TransactionArgument__Address(value={})", name),
+            Vector(type_tag) => match type_tag.as_ref() {
+                U8 => format!("aptos_types.TransactionArgument__U8Vector(value={})", name),
+                _ => common::type_not_allowed(type_tag),
+            },
+            Struct(_) | Signer => common::type_not_allowed(type_tag),
+        }
+    }
+}
+
+pub struct Installer {
+    install_dir: PathBuf,
+    serde_package_name: Option<String>,
+    aptos_package_name: Option<String>,
+    naming: Naming,
+}
+
+impl Installer {
+    pub fn new(
+        install_dir: PathBuf,
+        serde_package_name: Option<String>,
+        aptos_package_name: Option<String>,
+    ) -> Self {
+        Installer {
+            install_dir,
+            serde_package_name,
+            aptos_package_name,
+            naming: Naming::default(),
+        }
+    }
+
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+}
+
+impl crate::SourceInstaller for Installer {
+    type Error = Box<dyn std::error::Error>;
+
+    /// Installs the builders as a package, i.e. `<name>/__init__.py`, along with a `py.typed`
+    /// marker so that type checkers use the type hints.
+    fn install_transaction_builders(
+        &self,
+        name: &str,
+        abis: &[EntryABI],
+    ) -> std::result::Result<(), Self::Error> {
+        let mut dir_path = self.install_dir.clone();
+        for part in name.split('.') {
+            dir_path = dir_path.join(part);
+        }
+        std::fs::create_dir_all(&dir_path)?;
+        let mut file = std::fs::File::create(dir_path.join("__init__.py"))?;
+        output_with_naming(
+            &mut file,
+            self.serde_package_name.clone(),
+            self.aptos_package_name.clone(),
+            abis,
+            &self.naming,
+        )?;
+        std::fs::File::create(dir_path.join("py.typed"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::output;
+    use aptos_types::transaction::{ArgumentABI, EntryABI, EntryFunctionABI};
+    use move_core_types::language_storage::{ModuleId, TypeTag};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_integer_arguments() {
+        let abis = vec![EntryABI::EntryFunction(EntryFunctionABI::new(
+            "mint".to_string(),
+            ModuleId::from_str("0x1::big_coin").unwrap(),
+            "".to_string(),
+            vec![],
+            vec![
+                ArgumentABI::new("small".to_string(), TypeTag::U128),
+                ArgumentABI::new("large".to_string(), TypeTag::U256),
+            ],
+        ))];
+
+        let mut out = vec![];
+        output(&mut out, None, None, &abis).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("U256 = int"));
+        assert!(code.contains("def encode_big_coin_mint(small: st.uint128, large: int)"));
+        assert!(code
+            .contains(r#"args=[bcs.serialize(small, st.uint128), large.to_bytes(32, "little")],"#));
+        assert!(code.contains("small=bcs.deserialize(script.args[0], st.uint128)[0],"));
+        assert!(code.contains(r#"large=int.from_bytes(script.args[1], "little"),"#));
+    }
+}
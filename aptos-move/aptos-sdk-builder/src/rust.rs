@@ -2,7 +2,7 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::common;
+use crate::{common, naming::Naming, EventABI, ReturnABI};
 use aptos_types::transaction::{
     ArgumentABI, EntryABI, EntryFunctionABI, TransactionScriptABI, TypeArgumentABI,
 };
@@ -19,26 +19,152 @@ use serde_generate::{
 use serde_reflection::ContainerFormat;
 use std::{
     collections::BTreeMap,
+    fmt,
     io::{Result, Write},
     path::PathBuf,
     str::FromStr,
 };
 
+/// Rust edition the generated code is written for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum RustEdition {
+    Edition2018,
+    Edition2021,
+}
+
+impl FromStr for RustEdition {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "2018" => Ok(Self::Edition2018),
+            "2021" => Ok(Self::Edition2021),
+            _ => Err(format!("Unsupported Rust edition: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for RustEdition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Edition2018 => write!(f, "2018"),
+            Self::Edition2021 => write!(f, "2021"),
+        }
+    }
+}
+
+/// Options of the Rust code generator.
+#[derive(Clone, Debug)]
+pub struct RustOptions {
+    /// Generate a file suitable for the Aptos codebase itself rather than using serde-generated,
+    /// standalone definitions.
+    pub local_types: bool,
+    /// Only rely on what is available to toolchains of this edition. Before 2021, decoders are
+    /// plain `match` statements instead of maps initialized through `once_cell`.
+    pub edition: RustEdition,
+    /// Generate code that builds in a `#![no_std]` crate with `alloc`. This also avoids the
+    /// `once_cell` decoder maps.
+    pub no_std: bool,
+    /// Return types of the functions, for which to generate decoders of the returned values.
+    pub returns: Vec<ReturnABI>,
+    /// Layouts of the event structs, for which to generate types decoding the events.
+    pub events: Vec<EventABI>,
+    /// Namespaces and renames of the functions in generated names.
+    pub naming: Naming,
+    /// Give the fields of the event types the JSON representation of the REST API when serialized
+    /// with a human-readable format such as `serde_json`, keeping their BCS encoding.
+    pub json: bool,
+}
+
+impl RustOptions {
+    pub fn new(local_types: bool) -> Self {
+        Self {
+            local_types,
+            edition: RustEdition::Edition2021,
+            no_std: false,
+            returns: vec![],
+            events: vec![],
+            naming: Naming::default(),
+            json: false,
+        }
+    }
+
+    pub fn with_edition(mut self, edition: RustEdition) -> Self {
+        self.edition = edition;
+        self
+    }
+
+    pub fn with_no_std(mut self, no_std: bool) -> Self {
+        self.no_std = no_std;
+        self
+    }
+
+    pub fn with_returns(mut self, returns: Vec<ReturnABI>) -> Self {
+        self.returns = returns;
+        self
+    }
+
+    pub fn with_events(mut self, events: Vec<EventABI>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Whether the generated code decodes events from their JSON representation, which needs
+    /// `serde_json`.
+    fn decodes_json_events(&self) -> bool {
+        !self.no_std && !common::decodable_event_abis(&self.events).is_empty()
+    }
+
+    fn use_decoder_maps(&self) -> bool {
+        !self.no_std && self.edition >= RustEdition::Edition2021
+    }
+}
+
 /// Output transaction builders in Rust for the given ABIs.
 /// If `local_types` is true, we generate a file suitable for the Aptos codebase itself
 /// rather than using serde-generated, standalone definitions.
 pub fn output(out: &mut dyn Write, abis: &[EntryABI], local_types: bool) -> Result<()> {
+    output_with_options(out, abis, &RustOptions::new(local_types))
+}
+
+/// Output transaction builders in Rust for the given ABIs, see `RustOptions`.
+pub fn output_with_options(
+    out: &mut dyn Write,
+    abis: &[EntryABI],
+    options: &RustOptions,
+) -> Result<()> {
     if abis.is_empty() {
         return Ok(());
     }
     let mut emitter = RustEmitter {
         out: IndentedWriter::new(out, IndentConfig::Space(4)),
-        local_types,
+        local_types: options.local_types,
+        no_std: options.no_std,
+        use_decoder_maps: options.use_decoder_maps(),
+        naming: options.naming.clone(),
+        json: options.json,
     };
 
     emitter.output_preamble()?;
     writeln!(emitter.out, "#![allow(dead_code)]")?;
     writeln!(emitter.out, "#![allow(unused_imports)]")?;
+    if emitter.no_std {
+        writeln!(emitter.out, "extern crate alloc;")?;
+        writeln!(
+            emitter.out,
+            "use alloc::{{boxed::Box, format, string::{{String, ToString}}, vec, vec::Vec}};"
+        )?;
+    }
 
     emitter.output_script_call_enum_with_imports(abis)?;
 
@@ -63,11 +189,26 @@ pub fn output(out: &mut dyn Write, abis: &[EntryABI], local_types: bool) -> Resu
     }
     writeln!(emitter.out, "}}")?;
 
-    if !txn_script_abis.is_empty() {
-        emitter.output_transaction_script_decoder_map(&txn_script_abis)?;
+    let return_abis = common::decodable_return_abis(&options.returns);
+    if !return_abis.is_empty() {
+        emitter.output_returns_module(&return_abis)?;
     }
-    if !entry_function_abis.is_empty() {
-        emitter.output_entry_function_decoder_map(&entry_function_abis)?;
+
+    let event_abis = common::decodable_event_abis(&options.events);
+    if !event_abis.is_empty() {
+        emitter.output_events_module(&event_abis)?;
+        if emitter.json {
+            emitter.output_json_module()?;
+        }
+    }
+
+    if emitter.use_decoder_maps {
+        if !txn_script_abis.is_empty() {
+            emitter.output_transaction_script_decoder_map(&txn_script_abis)?;
+        }
+        if !entry_function_abis.is_empty() {
+            emitter.output_entry_function_decoder_map(&entry_function_abis)?;
+        }
     }
 
     emitter.output_decoding_helpers(&common::filter_transaction_scripts(abis))?;
@@ -84,6 +225,14 @@ struct RustEmitter<T> {
     out: IndentedWriter<T>,
     /// Whether we are targetting the Aptos repository itself (as opposed to generated Aptos types).
     local_types: bool,
+    /// Whether the code must build in a `no_std` crate.
+    no_std: bool,
+    /// Whether decoding goes through `once_cell` maps, as opposed to `match` statements.
+    use_decoder_maps: bool,
+    /// Namespaces and renames of the functions in generated names.
+    naming: Naming,
+    /// Whether the fields of the event types are serialized with the helpers of the `json` module.
+    json: bool,
 }
 
 impl<T> RustEmitter<T>
@@ -97,7 +246,7 @@ where
         writeln!(self.out, "\nimpl ScriptCall {{")?;
         self.out.indent();
         self.output_transaction_script_encode_method(transaction_script_abis)?;
-        self.output_transaction_script_decode_method()?;
+        self.output_transaction_script_decode_method(transaction_script_abis)?;
         self.output_transaction_script_name_method(transaction_script_abis)?;
         self.out.unindent();
         writeln!(self.out, "\n}}")
@@ -110,7 +259,7 @@ where
         writeln!(self.out, "\nimpl EntryFunctionCall {{")?;
         self.out.indent();
         self.output_entry_function_encode_method(entry_function_abis)?;
-        self.output_entry_function_decode_method()?;
+        self.output_entry_function_decode_method(entry_function_abis)?;
         self.out.unindent();
         writeln!(self.out, "\n}}")
     }
@@ -150,7 +299,7 @@ where
         let mut script_registry: BTreeMap<_, _> = if has_script {
             vec![(
                 "ScriptCall".to_string(),
-                common::make_abi_enum_container(transaction_script_abis.as_slice()),
+                common::make_abi_enum_container(transaction_script_abis.as_slice(), &self.naming),
             )]
             .into_iter()
             .collect()
@@ -160,7 +309,7 @@ where
 
         let mut entry_function_registry: BTreeMap<_, _> = vec![(
             "EntryFunctionCall".to_string(),
-            common::make_abi_enum_container(entry_fun_abis.as_slice()),
+            common::make_abi_enum_container(entry_fun_abis.as_slice(), &self.naming),
         )]
         .into_iter()
         .collect();
@@ -178,16 +327,7 @@ where
                             "EntryFunctionCall"
                         }
                         .to_string(),
-                        match abi {
-                            EntryABI::EntryFunction(sf) => {
-                                format!(
-                                    "{}{}",
-                                    sf.module_name().name().to_string().to_camel_case(),
-                                    abi.name().to_camel_case()
-                                )
-                            },
-                            _ => abi.name().to_camel_case(),
-                        },
+                        common::variant_name(abi, &self.naming),
                     ],
                     common::prepare_doc_string(abi.doc()),
                 )
@@ -337,16 +477,19 @@ pub fn encode(self) -> TransactionPayload {{"#
             .collect::<Vec<_>>()
             .join(", ");
 
-        let prefix = if let EntryABI::EntryFunction(sf) = abi {
-            sf.module_name().name().to_string().to_camel_case()
+        let (prefix, name) = if let EntryABI::EntryFunction(sf) = abi {
+            (
+                self.naming.module_name(sf.module_name()).to_camel_case(),
+                self.naming.function_name(sf.module_name(), sf.name()),
+            )
         } else {
-            String::new()
+            (String::new(), abi.name().to_string())
         };
         writeln!(
             self.out,
             "{5}{0}{{{2}}} => {3}{4}{1}({2}),",
-            abi.name().to_camel_case(),
-            abi.name(),
+            name.to_camel_case(),
+            name,
             params,
             prefix.to_snake_case(),
             if prefix.is_empty() { "" } else { "_" },
@@ -354,7 +497,39 @@ pub fn encode(self) -> TransactionPayload {{"#
         )
     }
 
-    fn output_transaction_script_decode_method(&mut self) -> Result<()> {
+    fn output_transaction_script_decode_method(
+        &mut self,
+        abis: &[TransactionScriptABI],
+    ) -> Result<()> {
+        if !self.use_decoder_maps {
+            writeln!(
+                self.out,
+                r#"
+/// Try to recognize an Aptos `Script` and convert it into a structured object `ScriptCall`.
+pub fn decode(script: &Script) -> Option<ScriptCall> {{"#
+            )?;
+            self.out.indent();
+            writeln!(
+                self.out,
+                "let code: &[u8] = {};",
+                if self.local_types {
+                    "script.code()"
+                } else {
+                    "&script.code[..]"
+                }
+            )?;
+            for abi in abis {
+                writeln!(
+                    self.out,
+                    "if code == {}_CODE {{ return decoder::{}_script(script); }}",
+                    abi.name().to_shouty_snake_case(),
+                    abi.name(),
+                )?;
+            }
+            writeln!(self.out, "None")?;
+            self.out.unindent();
+            return writeln!(self.out, "}}");
+        }
         writeln!(
             self.out,
             r#"
@@ -373,7 +548,10 @@ pub fn decode(script: &Script) -> Option<ScriptCall> {{
         )
     }
 
-    fn output_entry_function_decode_method(&mut self) -> Result<()> {
+    fn output_entry_function_decode_method(&mut self, abis: &[EntryFunctionABI]) -> Result<()> {
+        if !self.use_decoder_maps {
+            return self.output_entry_function_decode_match(abis);
+        }
         writeln!(
             self.out,
             r#"
@@ -401,6 +579,52 @@ pub fn decode(payload: &TransactionPayload) -> Option<EntryFunctionCall> {{
         )
     }
 
+    fn output_entry_function_decode_match(&mut self, abis: &[EntryFunctionABI]) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+/// Try to recognize an Aptos `TransactionPayload` and convert it into a structured object `EntryFunctionCall`.
+pub fn decode(payload: &TransactionPayload) -> Option<EntryFunctionCall> {{
+    if let TransactionPayload::EntryFunction(script) = payload {{
+        match ({}, {}) {{"#,
+            if self.local_types {
+                "script.module().name().as_str()"
+            } else {
+                "script.module.name.0.as_str()"
+            },
+            if self.local_types {
+                "script.function().as_str()"
+            } else {
+                "script.function.0.as_str()"
+            }
+        )?;
+        self.out.indent();
+        self.out.indent();
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "(\"{}\", \"{}\") => decoder::{}_{}(payload),",
+                abi.module_name().name(),
+                abi.name(),
+                self.naming.module_name(abi.module_name()).to_snake_case(),
+                self.naming.function_name(abi.module_name(), abi.name()),
+            )?;
+        }
+        writeln!(self.out, "_ => None,")?;
+        self.out.unindent();
+        self.out.unindent();
+        self.out.unindent();
+        writeln!(
+            self.out,
+            r#"        }}
+    }} else {{
+        None
+    }}
+}}"#
+        )
+    }
+
     fn output_transaction_script_name_method(
         &mut self,
         abis: &[TransactionScriptABI],
@@ -487,8 +711,8 @@ Script {{
         write!(
             self.out,
             "pub fn {}_{}({}) -> TransactionPayload {{",
-            abi.module_name().name().to_string().to_snake_case(),
-            abi.name(),
+            self.naming.module_name(abi.module_name()).to_snake_case(),
+            self.naming.function_name(abi.module_name(), abi.name()),
             [
                 Self::quote_type_parameters(abi.ty_args()),
                 Self::quote_parameters(abi.args(), self.local_types),
@@ -554,8 +778,8 @@ TransactionPayload::EntryFunction(EntryFunction {{
         writeln!(
             self.out,
             "\npub fn {}_{}(payload: &TransactionPayload) -> Option<EntryFunctionCall> {{",
-            abi.module_name().name().to_string().to_snake_case(),
-            abi.name(),
+            self.naming.module_name(abi.module_name()).to_snake_case(),
+            self.naming.function_name(abi.module_name(), abi.name()),
         )?;
         self.out.indent();
         writeln!(
@@ -572,8 +796,10 @@ TransactionPayload::EntryFunction(EntryFunction {{
         writeln!(
             self.out,
             "Some(EntryFunctionCall::{}{} {{",
-            abi.module_name().name().to_string().to_camel_case(),
-            abi.name().to_camel_case(),
+            self.naming.module_name(abi.module_name()).to_camel_case(),
+            self.naming
+                .function_name(abi.module_name(), abi.name())
+                .to_camel_case(),
         )?;
         self.out.indent();
         for (index, ty_arg) in abi.ty_args().iter().enumerate() {
@@ -702,8 +928,8 @@ static SCRIPT_FUNCTION_DECODER_MAP: once_cell::sync::Lazy<EntryFunctionDecoderMa
                 "map.insert(\"{}_{}\".to_string(), Box::new(decoder::{}_{}));",
                 abi.module_name().name(),
                 abi.name(),
-                abi.module_name().name().to_string().to_snake_case(),
-                abi.name()
+                self.naming.module_name(abi.module_name()).to_snake_case(),
+                self.naming.function_name(abi.module_name(), abi.name()),
             )?;
         }
         writeln!(self.out, "map")?;
@@ -711,6 +937,452 @@ static SCRIPT_FUNCTION_DECODER_MAP: once_cell::sync::Lazy<EntryFunctionDecoderMa
         writeln!(self.out, "}});")
     }
 
+    fn output_returns_module(&mut self, abis: &[ReturnABI]) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+/// Decoders of the values returned by functions, e.g. by a view function or by a call of a
+/// composed script, given as one BCS-encoded value per returned value."#
+        )?;
+        writeln!(self.out, "pub mod returns {{")?;
+        self.out.indent();
+        writeln!(self.out, "use super::*;")?;
+        for abi in abis {
+            self.emit_return_decoder_function(abi)?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn emit_return_decoder_function(&mut self, abi: &ReturnABI) -> Result<()> {
+        let types = abi
+            .returns
+            .iter()
+            .map(|type_tag| Self::quote_type(type_tag, self.local_types))
+            .collect::<Vec<_>>();
+        let values = (0..types.len())
+            .map(|index| format!("bcs::from_bytes(&values[{}]).ok()?", index))
+            .collect::<Vec<_>>();
+        let (return_type, value) = if types.len() == 1 {
+            (types[0].clone(), values[0].clone())
+        } else {
+            (
+                format!("({})", types.join(", ")),
+                format!("({})", values.join(", ")),
+            )
+        };
+        writeln!(
+            self.out,
+            "\n/// Decodes the values returned by `{}::{}`.",
+            abi.module_name.name(),
+            abi.name,
+        )?;
+        writeln!(
+            self.out,
+            "pub fn {}_{}(values: &[Vec<u8>]) -> Option<{}> {{",
+            self.naming.module_name(&abi.module_name).to_snake_case(),
+            self.naming.function_name(&abi.module_name, &abi.name),
+            return_type,
+        )?;
+        self.out.indent();
+        writeln!(self.out, "if values.len() != {} {{", types.len())?;
+        self.out.indent();
+        writeln!(self.out, "return None;")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        writeln!(self.out, "Some({})", value)?;
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_events_module(&mut self, abis: &[EventABI]) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"
+/// Types of the events emitted by the modules, decoded from the BCS-encoded event data or, outside
+/// of `no_std`, from the JSON representation of the data given by the REST API."#
+        )?;
+        writeln!(self.out, "pub mod events {{")?;
+        self.out.indent();
+        writeln!(self.out, "use super::*;")?;
+        writeln!(self.out, "use serde::{{Deserialize, Serialize}};")?;
+        if !self.no_std {
+            writeln!(self.out, "use core::convert::TryInto;")?;
+        }
+        for abi in abis {
+            self.emit_event_struct(abi)?;
+        }
+        if !self.no_std {
+            self.output_json_helpers()?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn emit_event_struct(&mut self, abi: &EventABI) -> Result<()> {
+        let name = format!(
+            "{}{}",
+            self.naming.module_name(&abi.module_name).to_camel_case(),
+            abi.name
+        );
+        writeln!(
+            self.out,
+            "
+/// Data of the `{}::{}` events.",
+            abi.module_name.name(),
+            abi.name,
+        )?;
+        writeln!(
+            self.out,
+            "#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]"
+        )?;
+        writeln!(self.out, "pub struct {} {{", name)?;
+        self.out.indent();
+        for field in &abi.fields {
+            if self.json {
+                if let Some(helper) = quote_json_serde_helper(&field.type_tag) {
+                    writeln!(self.out, "#[serde(with = \"super::json::{}\")]", helper)?;
+                }
+            }
+            writeln!(
+                self.out,
+                "pub {}: {},",
+                quote_field_name(&field.name),
+                Self::quote_type(&field.type_tag, self.local_types)
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+
+        writeln!(
+            self.out,
+            "
+impl {} {{",
+            name
+        )?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "pub const TYPE_TAG: &'static str = \"{}::{}::{}\";",
+            abi.module_name.address().to_hex_literal(),
+            abi.module_name.name(),
+            abi.name,
+        )?;
+        writeln!(
+            self.out,
+            r#"
+/// Decodes the BCS-encoded data of an event.
+pub fn from_bcs(bytes: &[u8]) -> Option<Self> {{
+    bcs::from_bytes(bytes).ok()
+}}"#
+        )?;
+        if !self.no_std {
+            writeln!(
+                self.out,
+                "
+/// Decodes the data of an event as given by the REST API."
+            )?;
+            writeln!(
+                self.out,
+                "pub fn from_json(value: &serde_json::Value) -> Option<Self> {{"
+            )?;
+            self.out.indent();
+            writeln!(self.out, "Some(Self {{")?;
+            self.out.indent();
+            for field in &abi.fields {
+                writeln!(
+                    self.out,
+                    "{}: {},",
+                    quote_field_name(&field.name),
+                    quote_json_decoder(&field.type_tag, &format!("value.get(\"{}\")?", field.name))
+                )?;
+            }
+            self.out.unindent();
+            writeln!(self.out, "}})")?;
+            self.out.unindent();
+            writeln!(self.out, "}}")?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    /// Decoders of the JSON representation of the values given as hex strings.
+    fn output_json_helpers(&mut self) -> Result<()> {
+        let address = if self.local_types {
+            "AccountAddress::new(bytes)"
+        } else {
+            "AccountAddress(bytes)"
+        };
+        writeln!(
+            self.out,
+            r#"
+fn address_from_json(value: &serde_json::Value) -> Option<AccountAddress> {{
+    let hex = value.as_str()?.strip_prefix("0x")?;
+    if hex.len() > 64 {{
+        return None;
+    }}
+    let hex = format!("{{:0>64}}", hex);
+    let mut bytes = [0u8; 32];
+    for (index, byte) in bytes.iter_mut().enumerate() {{
+        *byte = u8::from_str_radix(hex.get(2 * index..2 * index + 2)?, 16).ok()?;
+    }}
+    Some({})
+}}
+
+fn bytes_from_json(value: &serde_json::Value) -> Option<Vec<u8>> {{
+    let hex = value.as_str()?.strip_prefix("0x")?;
+    if hex.len() % 2 != 0 {{
+        return None;
+    }}
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}}"#,
+            address
+        )
+    }
+
+    /// Serde helpers for the fields whose JSON representation in the REST API differs from the
+    /// one derived by serde: integers over 32 bits as decimal strings, addresses and bytes as hex
+    /// strings, and strings as such rather than as their bytes.
+    fn output_json_module(&mut self) -> Result<()> {
+        let (address_bytes, address) = if self.local_types {
+            ("address.into_bytes()", "AccountAddress::new(bytes)")
+        } else {
+            ("address.0", "AccountAddress(bytes)")
+        };
+        writeln!(
+            self.out,
+            r#"
+/// Serde helpers giving values the JSON representation of the REST API when serialized with a
+/// human-readable format, and their usual representation otherwise, so that the event types
+/// round-trip both through BCS and through the JSON of the REST API.
+pub mod json {{
+    use super::*;
+    use core::{{convert::TryInto, fmt::Display, str::FromStr}};
+    use serde::{{de::Error, Deserialize, Deserializer, Serialize, Serializer}};
+
+    fn encode_hex(bytes: &[u8]) -> String {{
+        let mut hex = String::with_capacity(2 + 2 * bytes.len());
+        hex.push_str("0x");
+        for byte in bytes {{
+            hex.push_str(&format!("{{:02x}}", byte));
+        }}
+        hex
+    }}
+
+    fn decode_hex(hex: &str) -> Option<Vec<u8>> {{
+        let hex = hex.strip_prefix("0x")?;
+        if hex.len() % 2 != 0 {{
+            return None;
+        }}
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+            .collect()
+    }}
+
+    /// Addresses are given without their leading zeros, e.g. `0x1`.
+    fn encode_address(address: &AccountAddress) -> String {{
+        let hex = encode_hex(&{});
+        match hex[2..].trim_start_matches('0') {{
+            "" => "0x0".to_string(),
+            digits => format!("0x{{}}", digits),
+        }}
+    }}
+
+    fn decode_address(hex: &str) -> Option<AccountAddress> {{
+        let hex = hex.strip_prefix("0x")?;
+        if hex.is_empty() || hex.len() > 64 {{
+            return None;
+        }}
+        let bytes = decode_hex(&format!("0x{{:0>64}}", hex))?.try_into().ok()?;
+        Some({})
+    }}
+
+    /// Integers over 32 bits, as decimal strings.
+    pub mod string {{
+        use super::*;
+
+        pub fn serialize<T, S>(value: &T, serializer: S) -> core::result::Result<S::Ok, S::Error>
+        where
+            T: Display + Serialize,
+            S: Serializer,
+        {{
+            if serializer.is_human_readable() {{
+                serializer.collect_str(value)
+            }} else {{
+                value.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> core::result::Result<T, D::Error>
+        where
+            T: FromStr + Deserialize<'de>,
+            T::Err: Display,
+            D: Deserializer<'de>,
+        {{
+            if deserializer.is_human_readable() {{
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(D::Error::custom)
+            }} else {{
+                T::deserialize(deserializer)
+            }}
+        }}
+    }}
+
+    /// Vectors of integers over 32 bits, as arrays of decimal strings.
+    pub mod strings {{
+        use super::*;
+
+        pub fn serialize<T, S>(values: &[T], serializer: S) -> core::result::Result<S::Ok, S::Error>
+        where
+            T: Display + Serialize,
+            S: Serializer,
+        {{
+            if serializer.is_human_readable() {{
+                serializer.collect_seq(values.iter().map(|value| value.to_string()))
+            }} else {{
+                values.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> core::result::Result<Vec<T>, D::Error>
+        where
+            T: FromStr + Deserialize<'de>,
+            T::Err: Display,
+            D: Deserializer<'de>,
+        {{
+            if deserializer.is_human_readable() {{
+                Vec::<String>::deserialize(deserializer)?
+                    .iter()
+                    .map(|value| value.parse().map_err(D::Error::custom))
+                    .collect()
+            }} else {{
+                Vec::<T>::deserialize(deserializer)
+            }}
+        }}
+    }}
+
+    /// Addresses, as hex strings.
+    pub mod address {{
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            address: &AccountAddress,
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {{
+            if serializer.is_human_readable() {{
+                serializer.serialize_str(&encode_address(address))
+            }} else {{
+                address.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> core::result::Result<AccountAddress, D::Error> {{
+            if deserializer.is_human_readable() {{
+                let hex = String::deserialize(deserializer)?;
+                decode_address(&hex)
+                    .ok_or_else(|| D::Error::custom(format!("Invalid address: {{}}", hex)))
+            }} else {{
+                AccountAddress::deserialize(deserializer)
+            }}
+        }}
+    }}
+
+    /// Vectors of addresses, as arrays of hex strings.
+    pub mod addresses {{
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            addresses: &[AccountAddress],
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {{
+            if serializer.is_human_readable() {{
+                serializer.collect_seq(addresses.iter().map(encode_address))
+            }} else {{
+                addresses.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> core::result::Result<Vec<AccountAddress>, D::Error> {{
+            if deserializer.is_human_readable() {{
+                Vec::<String>::deserialize(deserializer)?
+                    .iter()
+                    .map(|hex| {{
+                        decode_address(hex)
+                            .ok_or_else(|| D::Error::custom(format!("Invalid address: {{}}", hex)))
+                    }})
+                    .collect()
+            }} else {{
+                Vec::<AccountAddress>::deserialize(deserializer)
+            }}
+        }}
+    }}
+
+    /// `vector<u8>`, as a hex string.
+    pub mod bytes {{
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            bytes: &[u8],
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {{
+            if serializer.is_human_readable() {{
+                serializer.serialize_str(&encode_hex(bytes))
+            }} else {{
+                bytes.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> core::result::Result<Vec<u8>, D::Error> {{
+            if deserializer.is_human_readable() {{
+                let hex = String::deserialize(deserializer)?;
+                decode_hex(&hex).ok_or_else(|| D::Error::custom(format!("Invalid hex: {{}}", hex)))
+            }} else {{
+                Vec::<u8>::deserialize(deserializer)
+            }}
+        }}
+    }}
+
+    /// `String`, as a string rather than its UTF-8 bytes.
+    pub mod utf8 {{
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            bytes: &[u8],
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {{
+            if serializer.is_human_readable() {{
+                serializer.serialize_str(core::str::from_utf8(bytes).map_err(serde::ser::Error::custom)?)
+            }} else {{
+                bytes.serialize(serializer)
+            }}
+        }}
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> core::result::Result<Vec<u8>, D::Error> {{
+            if deserializer.is_human_readable() {{
+                Ok(String::deserialize(deserializer)?.into_bytes())
+            }} else {{
+                Vec::<u8>::deserialize(deserializer)
+            }}
+        }}
+    }}
+}}"#,
+            address_bytes, address
+        )
+    }
+
     fn output_decoding_helpers(&mut self, abis: &[EntryABI]) -> Result<()> {
         let required_types = common::get_required_helper_types(abis);
         for required_type in required_types {
@@ -911,9 +1583,66 @@ fn decode_{}_argument(arg: TransactionArgument) -> Option<{}> {{
     }
 }
 
+/// Field names which are Rust keywords are written as raw identifiers.
+pub(crate) fn quote_field_name(name: &str) -> String {
+    match name {
+        "as" | "break" | "const" | "continue" | "crate" | "else" | "enum" | "extern" | "false"
+        | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move"
+        | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait" | "true" | "type"
+        | "unsafe" | "use" | "where" | "while" | "async" | "await" | "dyn" => {
+            format!("r#{}", name)
+        },
+        _ => name.to_string(),
+    }
+}
+
+/// Expression decoding the value of the given type from the JSON representation the REST API gives
+/// of it, in a function returning an `Option`. Integers over 32 bits are given as strings.
+fn quote_json_decoder(type_tag: &TypeTag, value: &str) -> String {
+    use TypeTag::*;
+    match type_tag {
+        Bool => format!("{}.as_bool()?", value),
+        U8 | U16 | U32 => format!("{}.as_u64()?.try_into().ok()?", value),
+        U64 | U128 | U256 => format!("{}.as_str()?.parse().ok()?", value),
+        Address => format!("address_from_json({})?", value),
+        Vector(type_tag) => match type_tag.as_ref() {
+            U8 => format!("bytes_from_json({})?", value),
+            type_tag => format!(
+                "{}.as_array()?.iter().map(|value| Some({})).collect::<Option<Vec<_>>>()?",
+                value,
+                quote_json_decoder(type_tag, "value")
+            ),
+        },
+        // Strings, the only structs with a representation.
+        Struct(_) => format!("{}.as_str()?.as_bytes().to_vec()", value),
+        Signer => common::type_not_allowed(type_tag),
+    }
+}
+
+/// Helper of the `json` module serializing a field of the given type, if its JSON representation
+/// in the REST API isn't the one derived by serde. Vectors of vectors and of strings keep the
+/// derived one.
+fn quote_json_serde_helper(type_tag: &TypeTag) -> Option<&'static str> {
+    use TypeTag::*;
+    match type_tag {
+        U64 | U128 | U256 => Some("string"),
+        Address => Some("address"),
+        Vector(type_tag) => match type_tag.as_ref() {
+            U8 => Some("bytes"),
+            U64 | U128 | U256 => Some("strings"),
+            Address => Some("addresses"),
+            _ => None,
+        },
+        // Strings, the only structs with a representation.
+        Struct(_) => Some("utf8"),
+        Bool | U8 | U16 | U32 | Signer => None,
+    }
+}
+
 pub struct Installer {
     install_dir: PathBuf,
     aptos_types_version: String,
+    options: RustOptions,
 }
 
 impl Installer {
@@ -921,8 +1650,39 @@ impl Installer {
         Installer {
             install_dir,
             aptos_types_version,
+            options: RustOptions::new(/* local_types */ false),
         }
     }
+
+    pub fn with_edition(mut self, edition: RustEdition) -> Self {
+        self.options.edition = edition;
+        self
+    }
+
+    pub fn with_no_std(mut self, no_std: bool) -> Self {
+        self.options.no_std = no_std;
+        self
+    }
+
+    pub fn with_returns(mut self, returns: Vec<ReturnABI>) -> Self {
+        self.options.returns = returns;
+        self
+    }
+
+    pub fn with_events(mut self, events: Vec<EventABI>) -> Self {
+        self.options.events = events;
+        self
+    }
+
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.options.naming = naming;
+        self
+    }
+
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.options.json = json;
+        self
+    }
 }
 
 impl crate::SourceInstaller for Installer {
@@ -949,20 +1709,45 @@ impl crate::SourceInstaller for Installer {
             r#"[package]
 name = "{}"
 version = "{}"
-edition = "2021"
+edition = "{}"
 
 [dependencies]
-once_cell = "1.10.0"
-serde = {{ version = "1.0", features = ["derive"] }}
-serde_bytes = "0.11.6"
-aptos-types = {{ path = "../aptos-types", version = "{}" }}
 "#,
-            name, version, self.aptos_types_version,
+            name, version, self.options.edition,
+        )?;
+        if self.options.use_decoder_maps() {
+            writeln!(cargo, r#"once_cell = "1.10.0""#)?;
+        }
+        if self.options.no_std {
+            write!(
+                cargo,
+                r#"serde = {{ version = "1.0", default-features = false, features = ["derive", "alloc"] }}
+serde_bytes = {{ version = "0.11.6", default-features = false, features = ["alloc"] }}
+"#
+            )?;
+        } else {
+            write!(
+                cargo,
+                r#"serde = {{ version = "1.0", features = ["derive"] }}
+serde_bytes = "0.11.6"
+"#
+            )?;
+        }
+        if self.options.decodes_json_events() {
+            writeln!(cargo, r#"serde_json = "1.0""#)?;
+        }
+        writeln!(
+            cargo,
+            r#"aptos-types = {{ path = "../aptos-types", version = "{}" }}"#,
+            self.aptos_types_version,
         )?;
         std::fs::create_dir(dir_path.join("src"))?;
         let source_path = dir_path.join("src/lib.rs");
         let mut source = std::fs::File::create(source_path)?;
-        output(&mut source, abis, /* local_types */ false)?;
+        if self.options.no_std {
+            writeln!(source, "#![no_std]")?;
+        }
+        output_with_options(&mut source, abis, &self.options)?;
         Ok(())
     }
 }
@@ -1002,3 +1787,214 @@ fn swap_keyworded_fields(fields: Option<&mut ContainerFormat>) {
         _ => (),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{output_with_options, RustOptions};
+    use crate::{naming::Naming, EventABI, EventFieldABI, ReturnABI};
+    use aptos_types::transaction::{ArgumentABI, EntryABI, EntryFunctionABI};
+    use move_core_types::language_storage::{ModuleId, StructTag, TypeTag};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_return_decoders() {
+        let module_name = ModuleId::from_str("0x1::coin").unwrap();
+        let abis = vec![EntryABI::EntryFunction(EntryFunctionABI::new(
+            "transfer".to_string(),
+            module_name.clone(),
+            "".to_string(),
+            vec![],
+            vec![ArgumentABI::new("amount".to_string(), TypeTag::U64)],
+        ))];
+        let return_abi = |name: &str, returns: Vec<TypeTag>| ReturnABI {
+            module_name: module_name.clone(),
+            name: name.to_string(),
+            is_view: true,
+            returns,
+        };
+        let options = RustOptions::new(/* local types */ true).with_returns(vec![
+            return_abi("balance", vec![TypeTag::U64]),
+            return_abi("supply", vec![
+                TypeTag::Bool,
+                TypeTag::Vector(Box::new(TypeTag::Address)),
+            ]),
+            return_abi("transfer", vec![]),
+            return_abi("info", vec![TypeTag::Struct(Box::new(
+                StructTag::from_str("0x1::coin::CoinInfo").unwrap(),
+            ))]),
+        ]);
+
+        let mut out = vec![];
+        output_with_options(&mut out, &abis, &options).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("pub mod returns {"));
+        assert!(code.contains("pub fn coin_balance(values: &[Vec<u8>]) -> Option<u64> {"));
+        assert!(code.contains(
+            "pub fn coin_supply(values: &[Vec<u8>]) -> Option<(bool, Vec<AccountAddress>)> {"
+        ));
+        // Nothing to decode, or no representation for the values.
+        assert!(!code.contains("pub fn coin_transfer(values"));
+        assert!(!code.contains("pub fn coin_info(values"));
+    }
+
+    #[test]
+    fn test_event_structs() {
+        let module_name = ModuleId::from_str("0x1::coin").unwrap();
+        let abis = vec![EntryABI::EntryFunction(EntryFunctionABI::new(
+            "transfer".to_string(),
+            module_name.clone(),
+            "".to_string(),
+            vec![],
+            vec![ArgumentABI::new("amount".to_string(), TypeTag::U64)],
+        ))];
+        let field = |name: &str, type_tag: TypeTag| EventFieldABI {
+            name: name.to_string(),
+            type_tag,
+        };
+        let options = RustOptions::new(/* local types */ true).with_events(vec![
+            EventABI {
+                module_name: module_name.clone(),
+                name: "DepositEvent".to_string(),
+                fields: vec![
+                    field("amount", TypeTag::U64),
+                    field("type", TypeTag::Vector(Box::new(TypeTag::Address))),
+                ],
+            },
+            EventABI {
+                module_name: module_name.clone(),
+                name: "InfoEvent".to_string(),
+                fields: vec![field(
+                    "info",
+                    TypeTag::Struct(Box::new(
+                        StructTag::from_str("0x1::coin::CoinInfo").unwrap(),
+                    )),
+                )],
+            },
+        ]);
+
+        let mut out = vec![];
+        output_with_options(&mut out, &abis, &options).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("pub mod events {"));
+        assert!(code.contains("pub struct CoinDepositEvent {"));
+        assert!(code.contains("pub r#type: Vec<AccountAddress>,"));
+        assert!(code.contains("pub const TYPE_TAG: &'static str = \"0x1::coin::DepositEvent\";"));
+        assert!(code.contains("amount: value.get(\"amount\")?.as_str()?.parse().ok()?,"));
+        // No representation for the fields.
+        assert!(!code.contains("CoinInfoEvent"));
+        // The derived serde representation, unless asked for the JSON one.
+        assert!(!code.contains("#[serde(with"));
+        assert!(!code.contains("pub mod json {"));
+    }
+
+    #[test]
+    fn test_json_representations() {
+        let module_name = ModuleId::from_str("0x1::coin").unwrap();
+        let abis = vec![EntryABI::EntryFunction(EntryFunctionABI::new(
+            "transfer".to_string(),
+            module_name.clone(),
+            "".to_string(),
+            vec![],
+            vec![ArgumentABI::new("amount".to_string(), TypeTag::U64)],
+        ))];
+        let field = |name: &str, type_tag: TypeTag| EventFieldABI {
+            name: name.to_string(),
+            type_tag,
+        };
+        let vector = |type_tag: TypeTag| TypeTag::Vector(Box::new(type_tag));
+        let events = vec![EventABI {
+            module_name,
+            name: "MintEvent".to_string(),
+            fields: vec![
+                field("amount", TypeTag::U64),
+                field("decimals", TypeTag::U8),
+                field("to", TypeTag::Address),
+                field("memo", vector(TypeTag::U8)),
+                field("splits", vector(TypeTag::U128)),
+                field("owners", vector(TypeTag::Address)),
+                field("chunks", vector(vector(TypeTag::U8))),
+                field(
+                    "symbol",
+                    TypeTag::Struct(Box::new(
+                        StructTag::from_str("0x1::string::String").unwrap(),
+                    )),
+                ),
+            ],
+        }];
+
+        for local_types in [true, false] {
+            let mut out = vec![];
+            output_with_options(
+                &mut out,
+                &abis,
+                &RustOptions::new(local_types)
+                    .with_events(events.clone())
+                    .with_json(true),
+            )
+            .unwrap();
+            let code = String::from_utf8(out).unwrap();
+            assert!(code.contains("pub mod json {"));
+            for (helper, field) in [
+                ("string", "amount: u64"),
+                ("address", "to: AccountAddress"),
+                ("bytes", "memo: Vec<u8>"),
+                ("strings", "splits: Vec<u128>"),
+                ("addresses", "owners: Vec<AccountAddress>"),
+                ("utf8", "symbol: Vec<u8>"),
+            ] {
+                assert!(code.contains(&format!(
+                    "#[serde(with = \"super::json::{}\")]\n        pub {},",
+                    helper, field
+                )));
+            }
+            // The derived representation already matches the REST API, or no helper gives it.
+            assert!(code.contains("\n        pub decimals: u8,"));
+            assert!(code.contains("\n        pub chunks: Vec<Vec<u8>>,"));
+            assert!(!code.contains("\")]\n        pub decimals"));
+            assert!(!code.contains("\")]\n        pub chunks"));
+        }
+    }
+
+    #[test]
+    fn test_naming() {
+        let transfer = |module_name: &str| {
+            EntryABI::EntryFunction(EntryFunctionABI::new(
+                "transfer".to_string(),
+                ModuleId::from_str(module_name).unwrap(),
+                "".to_string(),
+                vec![],
+                vec![ArgumentABI::new("amount".to_string(), TypeTag::U64)],
+            ))
+        };
+        let abis = vec![transfer("0x1::coin"), transfer("0xcafe::coin")];
+        assert_eq!(Naming::default().collisions(&abis), vec![
+            "CoinTransfer: 0x1::coin::transfer, 0xcafe::coin::transfer".to_string()
+        ]);
+
+        let naming = Naming::from_yaml(
+            r#"
+namespaces:
+  "0x1": aptos_framework
+renames:
+  "0xcafe::coin::transfer": transfer_with_fee
+"#,
+        )
+        .unwrap();
+        assert!(naming.collisions(&abis).is_empty());
+        let mut out = vec![];
+        output_with_options(
+            &mut out,
+            &abis,
+            &RustOptions::new(/* local types */ true).with_naming(naming),
+        )
+        .unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("AptosFrameworkCoinTransfer {"));
+        assert!(code.contains("CoinTransferWithFee {"));
+        assert!(code.contains("pub fn aptos_framework_coin_transfer(amount: u64)"));
+        assert!(code.contains("pub fn coin_transfer_with_fee(amount: u64)"));
+        // The payloads still call the functions of the modules.
+        assert!(code.contains("ident_str!(\"transfer\").to_owned()"));
+        assert!(!code.contains("ident_str!(\"transfer_with_fee\")"));
+    }
+}
@@ -0,0 +1,529 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Generation of an operator command line for a package, with a subcommand per entry function.
+//!
+//! Subcommands take the type arguments and arguments of their function as flags, e.g.
+//! `coin-transfer --coin-type 0x1::aptos_coin::AptosCoin --to alice --amount 10`, and sign and
+//! submit the transaction with the options of the `aptos` CLI, e.g. `--profile`. Addresses are
+//! given as such or as the name of a profile, and bytes in hex. Functions with arguments of other
+//! types, e.g. nested vectors other than `vector<vector<u8>>`, get no subcommand.
+
+use crate::{common, naming::Naming, rust::quote_field_name};
+use aptos_types::transaction::{ArgumentABI, EntryABI, EntryFunctionABI};
+use heck::SnakeCase;
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{StructTag, TypeTag},
+};
+use once_cell::sync::Lazy;
+use serde_generate::indent::{IndentConfig, IndentedWriter};
+use std::{
+    io::{Result, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Name of the enum of the subcommands.
+const COMMAND_ENUM: &str = "EntryFunctionCommand";
+
+/// How an argument is taken on the command line and encoded into the payload.
+struct CliArgument {
+    /// Rust type of the field.
+    field_type: String,
+    /// Attributes of the field, besides `long`.
+    clap_attributes: Option<&'static str>,
+    /// Expression of the value to BCS encode, given the field.
+    value: fn(&str) -> String,
+}
+
+impl CliArgument {
+    fn new(field_type: impl Into<String>) -> Self {
+        Self {
+            field_type: field_type.into(),
+            clap_attributes: None,
+            value: |field| format!("&self.{}", field),
+        }
+    }
+
+    fn with_clap_attributes(mut self, clap_attributes: &'static str) -> Self {
+        self.clap_attributes = Some(clap_attributes);
+        self
+    }
+
+    fn with_value(mut self, value: fn(&str) -> String) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// How an argument of the type is given, if it can be given at all.
+    fn for_type(type_tag: &TypeTag) -> Option<Self> {
+        use TypeTag::*;
+        Some(match type_tag {
+            Vector(type_tag) => match type_tag.as_ref() {
+                U8 => Self::new("HexBytes").with_value(|field| format!("&self.{}.0", field)),
+                Vector(type_tag) if type_tag.as_ref() == &U8 => Self::new("Vec<HexBytes>")
+                    .with_value(|field| {
+                        format!(
+                            "&self.{}.iter().map(|bytes| &bytes.0).collect::<Vec<_>>()",
+                            field
+                        )
+                    }),
+                Vector(_) => return None,
+                type_tag => {
+                    let inner = Self::for_type(type_tag)?;
+                    Self {
+                        field_type: format!("Vec<{}>", inner.field_type),
+                        ..inner
+                    }
+                },
+            },
+            _ => Self::for_scalar_type(type_tag)?,
+        })
+    }
+
+    fn for_scalar_type(type_tag: &TypeTag) -> Option<Self> {
+        use TypeTag::*;
+        let str_tag: Lazy<StructTag> =
+            Lazy::new(|| StructTag::from_str("0x1::string::String").unwrap());
+        Some(match type_tag {
+            // Taking a value, as opposed to a flag being present or not.
+            Bool => Self::new("bool").with_clap_attributes("parse(try_from_str)"),
+            U8 => Self::new("u8"),
+            U16 => Self::new("u16"),
+            U32 => Self::new("u32"),
+            U64 => Self::new("u64"),
+            U128 => Self::new("u128"),
+            U256 => Self::new("U256"),
+            Address => Self::new("AccountAddress")
+                .with_clap_attributes("parse(try_from_str = load_account_arg)"),
+            Struct(tag) if &**tag == Lazy::force(&str_tag) => Self::new("String"),
+            Vector(_) | Struct(_) | Signer => return None,
+        })
+    }
+}
+
+/// The entry functions whose arguments can all be given on the command line.
+fn cli_function_abis(abis: &[EntryABI]) -> Vec<EntryFunctionABI> {
+    common::entry_function_abis(abis)
+        .into_iter()
+        .filter(|abi| {
+            abi.args()
+                .iter()
+                .all(|arg| CliArgument::for_type(arg.type_tag()).is_some())
+        })
+        .collect()
+}
+
+/// Output the command line in Rust for the entry functions of the given ABIs.
+pub fn output(out: &mut dyn Write, abis: &[EntryABI], naming: &Naming) -> Result<()> {
+    let abis = cli_function_abis(abis);
+    if abis.is_empty() {
+        return Ok(());
+    }
+    let mut emitter = RustCliEmitter {
+        out: IndentedWriter::new(out, IndentConfig::Space(4)),
+        naming: naming.clone(),
+    };
+    emitter.output_preamble()?;
+    emitter.output_command_enum(&abis)?;
+    for abi in &abis {
+        emitter.output_command(abi)?;
+    }
+    Ok(())
+}
+
+/// Shared state for the command line generator.
+struct RustCliEmitter<T> {
+    /// Writer.
+    out: IndentedWriter<T>,
+    /// Namespaces and renames of the functions in generated names.
+    naming: Naming,
+}
+
+impl<T> RustCliEmitter<T>
+where
+    T: Write,
+{
+    fn output_preamble(&mut self) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"// Command line calling entry functions, with a subcommand per function, which signs and submits
+// the transaction with the options of the `aptos` CLI.
+//
+// This code was generated by compiling known Script interfaces ("ABIs") with the tool `aptos-sdk-builder`.
+
+#![allow(dead_code)]
+#![allow(unused_imports)]
+
+use aptos::common::types::{{
+    load_account_arg, CliCommand, CliResult, CliTypedResult, TransactionOptions,
+    TransactionSummary,
+}};
+use aptos_types::transaction::{{EntryFunction, TransactionPayload}};
+use async_trait::async_trait;
+use clap::Parser;
+use move_core_types::{{
+    account_address::AccountAddress,
+    ident_str,
+    language_storage::{{ModuleId, TypeTag}},
+    parser::parse_type_tag,
+    u256::U256,
+}};
+use std::str::FromStr;
+
+/// Bytes given in hex, with or without a `0x` prefix.
+#[derive(Clone, Debug)]
+pub struct HexBytes(pub Vec<u8>);
+
+impl FromStr for HexBytes {{
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {{
+        hex::decode(s.strip_prefix("0x").unwrap_or(s)).map(HexBytes)
+    }}
+}}"#
+        )
+    }
+
+    fn output_comment(&mut self, doc: &str) -> Result<()> {
+        let doc = common::prepare_doc_string(doc);
+        if doc.is_empty() {
+            return Ok(());
+        }
+        let text = textwrap::indent(&doc, "/// ").replace("\n\n", "\n///\n");
+        writeln!(self.out, "{}", text.trim_end())
+    }
+
+    fn output_command_enum(&mut self, abis: &[EntryFunctionABI]) -> Result<()> {
+        writeln!(self.out, "\n#[derive(Debug, Parser)]")?;
+        writeln!(self.out, "pub enum {} {{", COMMAND_ENUM)?;
+        self.out.indent();
+        for abi in abis {
+            self.output_comment(abi.doc())?;
+            let name = self.command_name(abi);
+            writeln!(self.out, "{}({}),", name, name)?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+
+        writeln!(self.out, "\nimpl {} {{", COMMAND_ENUM)?;
+        self.out.indent();
+        writeln!(self.out, "pub async fn execute(self) -> CliResult {{")?;
+        self.out.indent();
+        writeln!(self.out, "match self {{")?;
+        self.out.indent();
+        for abi in abis {
+            writeln!(
+                self.out,
+                "{}::{}(cmd) => cmd.execute_serialized().await,",
+                COMMAND_ENUM,
+                self.command_name(abi)
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn output_command(&mut self, abi: &EntryFunctionABI) -> Result<()> {
+        let name = self.command_name(abi);
+        writeln!(self.out)?;
+        self.output_comment(abi.doc())?;
+        writeln!(self.out, "#[derive(Debug, Parser)]")?;
+        writeln!(self.out, "pub struct {} {{", name)?;
+        self.out.indent();
+        for ty_arg in abi.ty_args() {
+            writeln!(
+                self.out,
+                "#[clap(long, parse(try_from_str = parse_type_tag))]"
+            )?;
+            writeln!(
+                self.out,
+                "pub {}: TypeTag,",
+                quote_field_name(&ty_arg.name().to_snake_case())
+            )?;
+        }
+        for arg in abi.args() {
+            let cli_arg = Self::cli_argument(arg);
+            match cli_arg.clap_attributes {
+                Some(attributes) => writeln!(self.out, "#[clap(long, {})]", attributes)?,
+                None => writeln!(self.out, "#[clap(long)]")?,
+            }
+            writeln!(
+                self.out,
+                "pub {}: {},",
+                quote_field_name(arg.name()),
+                cli_arg.field_type
+            )?;
+        }
+        writeln!(self.out, "#[clap(flatten)]")?;
+        writeln!(self.out, "pub txn_options: TransactionOptions,")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+
+        writeln!(self.out, "\n#[async_trait]")?;
+        writeln!(
+            self.out,
+            "impl CliCommand<TransactionSummary> for {} {{",
+            name
+        )?;
+        self.out.indent();
+        writeln!(self.out, "fn command_name(&self) -> &'static str {{")?;
+        writeln!(self.out, "    \"{}\"", name)?;
+        writeln!(self.out, "}}")?;
+        writeln!(
+            self.out,
+            "\nasync fn execute(self) -> CliTypedResult<TransactionSummary> {{"
+        )?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "let payload = TransactionPayload::EntryFunction(EntryFunction::new("
+        )?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "ModuleId::new({}, ident_str!(\"{}\").to_owned()),",
+            quote_address(abi.module_name().address()),
+            abi.module_name().name()
+        )?;
+        writeln!(self.out, "ident_str!(\"{}\").to_owned(),", abi.name())?;
+        writeln!(
+            self.out,
+            "vec![{}],",
+            abi.ty_args()
+                .iter()
+                .map(|ty_arg| format!(
+                    "self.{}.clone()",
+                    quote_field_name(&ty_arg.name().to_snake_case())
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(self.out, "vec![")?;
+        self.out.indent();
+        for arg in abi.args() {
+            writeln!(
+                self.out,
+                "bcs::to_bytes({}).unwrap(),",
+                (Self::cli_argument(arg).value)(&quote_field_name(arg.name()))
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "],")?;
+        self.out.unindent();
+        writeln!(self.out, "));")?;
+        writeln!(self.out, "self.txn_options")?;
+        writeln!(self.out, "    .submit_transaction(payload)")?;
+        writeln!(self.out, "    .await")?;
+        writeln!(self.out, "    .map(TransactionSummary::from)")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    fn command_name(&self, abi: &EntryFunctionABI) -> String {
+        common::variant_name(&EntryABI::EntryFunction(abi.clone()), &self.naming)
+    }
+
+    fn cli_argument(arg: &ArgumentABI) -> CliArgument {
+        CliArgument::for_type(arg.type_tag())
+            .expect("Functions with arguments of other types are filtered out")
+    }
+}
+
+fn quote_address(address: &AccountAddress) -> String {
+    format!(
+        "AccountAddress::new([{}])",
+        address
+            .to_vec()
+            .iter()
+            .map(|x| format!("{}", x))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Installs the command line as a crate with both a library, to embed the subcommands in another
+/// command line, and a binary running them.
+pub struct Installer {
+    install_dir: PathBuf,
+    /// Revision of aptos-core the `aptos` and `aptos-types` dependencies are taken at, rather than
+    /// the tip of `main`.
+    aptos_rev: Option<String>,
+    naming: Naming,
+}
+
+impl Installer {
+    pub fn new(install_dir: PathBuf) -> Self {
+        Installer {
+            install_dir,
+            aptos_rev: None,
+            naming: Naming::default(),
+        }
+    }
+
+    pub fn with_aptos_rev(mut self, aptos_rev: Option<String>) -> Self {
+        self.aptos_rev = aptos_rev;
+        self
+    }
+
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+}
+
+impl crate::SourceInstaller for Installer {
+    type Error = Box<dyn std::error::Error>;
+
+    fn install_transaction_builders(
+        &self,
+        public_name: &str,
+        abis: &[EntryABI],
+    ) -> std::result::Result<(), Self::Error> {
+        let (name, version) = match public_name.split_once(':') {
+            Some((name, version)) => (name.to_string(), version.to_string()),
+            None => (public_name.to_string(), "0.1.0".to_string()),
+        };
+        let dir_path = self.install_dir.join(&name);
+        std::fs::create_dir_all(dir_path.join("src"))?;
+        let aptos_source = match &self.aptos_rev {
+            Some(rev) => format!(
+                r#"git = "https://github.com/aptos-labs/aptos-core.git", rev = "{}""#,
+                rev
+            ),
+            None => r#"git = "https://github.com/aptos-labs/aptos-core.git", branch = "main""#
+                .to_string(),
+        };
+        let mut cargo = std::fs::File::create(dir_path.join("Cargo.toml"))?;
+        write!(
+            cargo,
+            r#"[package]
+name = "{name}"
+version = "{version}"
+edition = "2021"
+
+[dependencies]
+aptos = {{ {aptos_source} }}
+aptos-types = {{ {aptos_source} }}
+async-trait = "0.1.53"
+bcs = {{ git = "https://github.com/aptos-labs/bcs.git", rev = "d31fab9d81748e2594be5cd5cdf845786a30562d" }}
+clap = {{ version = "3.2.23", features = ["derive", "env"] }}
+hex = "0.4.3"
+move-core-types = {{ git = "https://github.com/move-language/move", rev = "69c1bd0b31827603653e03fa31752a10c275b0fc", features = ["address32"] }}
+tokio = {{ version = "1.21.0", features = ["full"] }}
+"#,
+            name = name,
+            version = version,
+            aptos_source = aptos_source,
+        )?;
+        let mut source = std::fs::File::create(dir_path.join("src/lib.rs"))?;
+        output(&mut source, abis, &self.naming)?;
+        let mut main = std::fs::File::create(dir_path.join("src/main.rs"))?;
+        write!(
+            main,
+            r#"use clap::Parser;
+use {crate_name}::EntryFunctionCommand;
+use std::process::exit;
+
+#[tokio::main]
+async fn main() {{
+    match EntryFunctionCommand::parse().execute().await {{
+        Ok(result) => println!("{{}}", result),
+        Err(error) => {{
+            println!("{{}}", error);
+            exit(1);
+        }},
+    }}
+}}
+"#,
+            crate_name = name.replace('-', "_"),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::output;
+    use crate::naming::Naming;
+    use aptos_types::transaction::{ArgumentABI, EntryABI, EntryFunctionABI, TypeArgumentABI};
+    use move_core_types::language_storage::{ModuleId, StructTag, TypeTag};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_commands() {
+        let module_name = ModuleId::from_str("0x1::coin").unwrap();
+        let function = |name: &str, ty_args: Vec<TypeArgumentABI>, args: Vec<ArgumentABI>| {
+            EntryABI::EntryFunction(EntryFunctionABI::new(
+                name.to_string(),
+                module_name.clone(),
+                "Does the thing.".to_string(),
+                ty_args,
+                args,
+            ))
+        };
+        let arg = |name: &str, type_tag: TypeTag| ArgumentABI::new(name.to_string(), type_tag);
+        let vector = |type_tag: TypeTag| TypeTag::Vector(Box::new(type_tag));
+        let abis = vec![
+            function(
+                "transfer",
+                vec![TypeArgumentABI::new("CoinType".to_string())],
+                vec![arg("to", TypeTag::Address), arg("amount", TypeTag::U64)],
+            ),
+            function("register", vec![], vec![
+                arg(
+                    "name",
+                    TypeTag::Struct(Box::new(
+                        StructTag::from_str("0x1::string::String").unwrap(),
+                    )),
+                ),
+                arg("type", TypeTag::Bool),
+                arg("metadata", vector(TypeTag::U8)),
+                arg("proofs", vector(vector(TypeTag::U8))),
+                arg("owners", vector(TypeTag::Address)),
+            ]),
+            function("batch", vec![], vec![arg(
+                "amounts",
+                vector(vector(TypeTag::U64)),
+            )]),
+        ];
+
+        let mut out = vec![];
+        output(&mut out, &abis, &Naming::default()).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("CoinTransfer(CoinTransfer),"));
+        assert!(code.contains(
+            "EntryFunctionCommand::CoinRegister(cmd) => cmd.execute_serialized().await,"
+        ));
+        assert!(code
+            .contains("/// Does the thing.\n#[derive(Debug, Parser)]\npub struct CoinTransfer {"));
+        assert!(code.contains(
+            "#[clap(long, parse(try_from_str = parse_type_tag))]\n    pub coin_type: TypeTag,"
+        ));
+        assert!(code.contains(
+            "#[clap(long, parse(try_from_str = load_account_arg))]\n    pub to: AccountAddress,"
+        ));
+        assert!(code.contains("pub name: String,"));
+        assert!(code.contains("#[clap(long, parse(try_from_str))]\n    pub r#type: bool,"));
+        assert!(code.contains("pub metadata: HexBytes,"));
+        assert!(code.contains("pub proofs: Vec<HexBytes>,"));
+        assert!(code.contains(
+            "#[clap(long, parse(try_from_str = load_account_arg))]\n    pub owners: Vec<AccountAddress>,"
+        ));
+        assert!(code.contains("ident_str!(\"transfer\").to_owned(),"));
+        assert!(code.contains("vec![self.coin_type.clone()],"));
+        assert!(code.contains("bcs::to_bytes(&self.metadata.0).unwrap(),"));
+        assert!(code.contains(
+            "bcs::to_bytes(&self.proofs.iter().map(|bytes| &bytes.0).collect::<Vec<_>>()).unwrap(),"
+        ));
+        // No way to give nested vectors of other types.
+        assert!(!code.contains("CoinBatch"));
+    }
+}
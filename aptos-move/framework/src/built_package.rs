@@ -3,12 +3,15 @@
 
 use crate::{
     docgen::DocgenOptions,
-    extended_checks,
+    extended_checks, get_metadata_from_compiled_module,
     natives::code::{ModuleMetadata, MoveOption, PackageDep, PackageMetadata, UpgradePolicy},
     zip_metadata, zip_metadata_str, RuntimeModuleMetadataV1, APTOS_METADATA_KEY,
     APTOS_METADATA_KEY_V1, METADATA_V1_MIN_FILE_FORMAT_VERSION,
 };
 use anyhow::bail;
+use aptos_sdk_builder::{
+    EventABI, EventFieldABI, ReturnABI, EVENT_ABI_EXTENSION, RETURN_ABI_EXTENSION,
+};
 use aptos_types::{account_address::AccountAddress, transaction::EntryABI};
 use clap::Parser;
 use codespan_reporting::{
@@ -16,10 +19,10 @@ use codespan_reporting::{
     term::termcolor::{ColorChoice, StandardStream},
 };
 use itertools::Itertools;
-use move_binary_format::CompiledModule;
+use move_binary_format::{normalized, CompiledModule};
 use move_command_line_common::files::MOVE_COMPILED_EXTENSION;
 use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule};
-use move_core_types::{language_storage::ModuleId, metadata::Metadata};
+use move_core_types::{identifier::Identifier, language_storage::ModuleId, metadata::Metadata};
 use move_model::model::GlobalEnv;
 use move_package::{
     compilation::{compiled_package::CompiledPackage, package_layout::CompiledPackageLayout},
@@ -184,11 +187,16 @@ impl BuiltPackage {
             docgen.run(package_path.display().to_string(), dep_paths, model)?
         }
 
-        Ok(Self {
+        let built = Self {
             options,
             package_path,
             package,
-        })
+        };
+        if built.options.with_abis {
+            built.save_return_abis()?;
+            built.save_event_abis()?;
+        }
+        Ok(built)
     }
 
     /// Returns the name of this package.
@@ -227,6 +235,72 @@ impl BuiltPackage {
         })
     }
 
+    /// Returns the declared return types of the entry and view functions of this package, which
+    /// the abis don't carry.
+    pub fn extract_return_abis(&self) -> Vec<ReturnABI> {
+        self.modules().flat_map(return_abis).collect()
+    }
+
+    /// Saves the return types next to the abis of the functions, for the SDK builder to read.
+    fn save_return_abis(&self) -> anyhow::Result<()> {
+        let abis_path = self
+            .package_artifacts_path()
+            .join(CompiledPackageLayout::CompiledABIs.path());
+        for abi in self.extract_return_abis() {
+            let dir = abis_path.join(abi.module_name.name().as_str());
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(
+                dir.join(&abi.name).with_extension(RETURN_ABI_EXTENSION),
+                bcs::to_bytes(&abi)?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the layouts of the structs of this package emitted as events, i.e. held in an
+    /// `EventHandle` by any of its modules.
+    pub fn extract_event_abis(&self) -> Vec<EventABI> {
+        let modules = self
+            .modules()
+            .map(|module| (module.self_id(), normalized::Module::new(module)))
+            .collect::<Vec<_>>();
+        let mut event_types = BTreeSet::new();
+        for (_, module) in &modules {
+            for struct_ in module.structs.values() {
+                for field in &struct_.fields {
+                    collect_event_types(&field.type_, &mut event_types);
+                }
+            }
+        }
+        modules
+            .iter()
+            .flat_map(|(module_id, module)| {
+                module
+                    .structs
+                    .iter()
+                    .filter(|(name, _)| event_types.contains(&(module_id.clone(), (*name).clone())))
+                    .filter_map(|(name, struct_)| event_abi(module_id.clone(), name, struct_))
+            })
+            .collect()
+    }
+
+    /// Saves the event layouts next to the abis of the module declaring them, for the SDK builder
+    /// to read.
+    fn save_event_abis(&self) -> anyhow::Result<()> {
+        let abis_path = self
+            .package_artifacts_path()
+            .join(CompiledPackageLayout::CompiledABIs.path());
+        for abi in self.extract_event_abis() {
+            let dir = abis_path.join(abi.module_name.name().as_str());
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(
+                dir.join(&abi.name).with_extension(EVENT_ABI_EXTENSION),
+                bcs::to_bytes(&abi)?,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Returns an iterator for all compiled proper (non-script) modules.
     pub fn modules(&self) -> impl Iterator<Item = &CompiledModule> {
         self.package
@@ -340,6 +414,104 @@ fn extract_custom_fields(toml: &str) -> anyhow::Result<BTreeMap<String, String>>
         .collect())
 }
 
+/// Return types of the entry and view functions of the module. Functions returning values whose
+/// type depends on their type parameters are skipped.
+fn return_abis(module: &CompiledModule) -> Vec<ReturnABI> {
+    let view_functions = get_metadata_from_compiled_module(module)
+        .map(|metadata| {
+            metadata
+                .fun_attributes
+                .into_iter()
+                .filter(|(_, attrs)| attrs.iter().any(|attr| attr.is_view_function()))
+                .map(|(name, _)| name)
+                .collect::<BTreeSet<_>>()
+        })
+        .unwrap_or_default();
+    module
+        .function_defs
+        .iter()
+        .filter_map(|def| {
+            let (name, function) = normalized::Function::new(module, def);
+            let is_view = view_functions.contains(name.as_str());
+            if !function.is_entry && !is_view {
+                return None;
+            }
+            let returns = function
+                .return_
+                .into_iter()
+                .map(|ty| ty.into_type_tag())
+                .collect::<Option<Vec<_>>>()?;
+            Some(ReturnABI {
+                module_name: module.self_id(),
+                name: name.to_string(),
+                is_view,
+                returns,
+            })
+        })
+        .collect()
+}
+
+/// Collects the types `T` of the `0x1::event::EventHandle<T>` found in the type.
+fn collect_event_types(
+    type_: &normalized::Type,
+    event_types: &mut BTreeSet<(ModuleId, Identifier)>,
+) {
+    match type_ {
+        normalized::Type::Struct {
+            address,
+            module,
+            name,
+            type_arguments,
+        } => {
+            if *address == AccountAddress::ONE
+                && module.as_str() == "event"
+                && name.as_str() == "EventHandle"
+            {
+                if let Some(normalized::Type::Struct {
+                    address,
+                    module,
+                    name,
+                    ..
+                }) = type_arguments.first()
+                {
+                    event_types.insert((ModuleId::new(*address, module.clone()), name.clone()));
+                }
+            }
+            for type_argument in type_arguments {
+                collect_event_types(type_argument, event_types);
+            }
+        },
+        normalized::Type::Vector(type_) => collect_event_types(type_, event_types),
+        _ => (),
+    }
+}
+
+/// Layout of an event struct, unless it is generic.
+fn event_abi(
+    module_name: ModuleId,
+    name: &Identifier,
+    struct_: &normalized::Struct,
+) -> Option<EventABI> {
+    if !struct_.type_parameters.is_empty() {
+        return None;
+    }
+    let fields = struct_
+        .fields
+        .iter()
+        .map(|field| {
+            Some(EventFieldABI {
+                name: field.name.to_string(),
+                type_tag: field.type_.clone().into_type_tag()?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(EventABI {
+        module_name,
+        name: name.to_string(),
+        fields,
+    })
+}
+
 fn inject_runtime_metadata(
     package_path: PathBuf,
     pack: &mut CompiledPackage,
@@ -7,7 +7,7 @@ use crate::{
     release_bundle::{ReleaseBundle, ReleasePackage},
 };
 use anyhow::anyhow;
-use aptos_sdk_builder::rust;
+use aptos_sdk_builder::{rust, ReturnABI};
 use aptos_types::transaction::EntryABI;
 use clap::Parser;
 use std::path::{Path, PathBuf};
@@ -52,7 +52,11 @@ impl ReleaseOptions {
                 let abis = built
                     .extract_abis()
                     .ok_or_else(|| anyhow!("abis not available, can't generate sdk"))?;
-                Self::generate_rust_bindings(&abis, &PathBuf::from(rust_binding_path))?;
+                Self::generate_rust_bindings(
+                    &abis,
+                    built.extract_return_abis(),
+                    &PathBuf::from(rust_binding_path),
+                )?;
             }
             let released = ReleasePackage::new(built)?;
             let size = bcs::to_bytes(&released)?.len();
@@ -71,10 +75,15 @@ impl ReleaseOptions {
         Ok(())
     }
 
-    fn generate_rust_bindings(abis: &[EntryABI], path: &Path) -> anyhow::Result<()> {
+    fn generate_rust_bindings(
+        abis: &[EntryABI],
+        returns: Vec<ReturnABI>,
+        path: &Path,
+    ) -> anyhow::Result<()> {
         {
             let mut file = std::fs::File::create(path)?;
-            rust::output(&mut file, abis, true)?;
+            let options = rust::RustOptions::new(/* local types */ true).with_returns(returns);
+            rust::output_with_options(&mut file, abis, &options)?;
         }
         std::process::Command::new("rustfmt")
             .arg("--config")
@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::anyhow;
-use aptos_config::{config::NodeConfig, utils::get_genesis_txn};
+use aptos_config::{
+    config::{BackupServiceConfig, NodeConfig},
+    utils::get_genesis_txn,
+};
 use aptos_db::AptosDB;
 use aptos_executor::db_bootstrapper::maybe_bootstrap;
 use aptos_logger::{debug, info};
@@ -16,11 +19,16 @@ use tokio::runtime::Runtime;
 pub(crate) fn bootstrap_db(
     aptos_db: AptosDB,
     backup_service_address: SocketAddr,
+    backup_service_config: BackupServiceConfig,
 ) -> (Arc<AptosDB>, DbReaderWriter, Option<Runtime>) {
-    use aptos_backup_service::start_backup_service;
+    use aptos_backup_service::start_backup_service_with_config;
 
     let (aptos_db, db_rw) = DbReaderWriter::wrap(aptos_db);
-    let db_backup_service = start_backup_service(backup_service_address, aptos_db.clone());
+    let db_backup_service = start_backup_service_with_config(
+        backup_service_address,
+        aptos_db.clone(),
+        backup_service_config,
+    );
     (aptos_db, db_rw, Some(db_backup_service))
 }
 
@@ -30,6 +38,7 @@ pub(crate) fn bootstrap_db(
 pub(crate) fn bootstrap_db(
     aptos_db: AptosDB,
     _backup_service_address: SocketAddr,
+    _backup_service_config: BackupServiceConfig,
 ) -> (
     Arc<aptos_db::fake_aptosdb::FakeAptosDB>,
     DbReaderWriter,
@@ -98,8 +107,11 @@ pub fn initialize_database_and_checkpoints(
         node_config.storage.max_num_nodes_per_lru_cache_shard,
     )
     .map_err(|err| anyhow!("DB failed to open {}", err))?;
-    let (aptos_db, db_rw, backup_service) =
-        bootstrap_db(aptos_db, node_config.storage.backup_service_address);
+    let (aptos_db, db_rw, backup_service) = bootstrap_db(
+        aptos_db,
+        node_config.storage.backup_service_address,
+        node_config.storage.backup_service.clone(),
+    );
 
     // TODO: handle non-genesis waypoints for state sync!
     // If there's a genesis txn and waypoint, commit it if the result matches.
@@ -11,6 +11,9 @@ pub struct InspectionServiceConfig {
     pub port: u16,
     pub expose_configuration: bool,
     pub expose_system_information: bool,
+    /// Exposes at `/backup_service` whether the backup service of the node is up, and the
+    /// versions it serves, for fleet tooling to find backup sources.
+    pub expose_backup_service: bool,
 }
 
 impl Default for InspectionServiceConfig {
@@ -20,6 +23,7 @@ impl Default for InspectionServiceConfig {
             port: 9101,
             expose_configuration: false,
             expose_system_information: true,
+            expose_backup_service: true,
         }
     }
 }
@@ -75,10 +75,99 @@ impl Default for RocksdbConfigs {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupServiceConfig {
+    /// Maximum number of state snapshot streams served at the same time, 0 for no limit. Each
+    /// stream iterates the whole state, so a few restore jobs starting at once can overload the
+    /// node.
+    pub max_concurrent_state_snapshot_streams: usize,
+    /// How long a state snapshot request waits for one of the streams above to finish before it
+    /// is rejected with 429.
+    pub state_snapshot_queue_timeout_ms: u64,
+    /// Value of the Retry-After header sent along with 429 responses.
+    pub retry_after_secs: u64,
+    /// If set, a JSON line per request is appended to this file, with the client address, the
+    /// versions requested, the bytes served and whether the response completed, to account for
+    /// egress of public backup endpoints.
+    pub audit_log_path: Option<PathBuf>,
+    /// The audit log is rotated once it grows over this size.
+    pub audit_log_max_file_size_bytes: u64,
+    /// Number of rotated audit log files kept, e.g. `audit.log.1` to `audit.log.10`.
+    pub audit_log_max_files: usize,
+    /// If set, transactions and epoch ending ledger infos are served compressed with the zstd
+    /// dictionary in this file to clients asking for it. If the file doesn't exist, a dictionary
+    /// is trained on the latest ones and saved there.
+    pub zstd_dictionary_path: Option<PathBuf>,
+    /// Endpoints served, e.g. for public nodes to serve proofs but not full state snapshots.
+    pub endpoints: BackupServiceEndpoints,
+    /// If set, `/admin/streams` lists the streams in flight and cancels them, for requests with
+    /// `Authorization: Bearer <admin_token>`. Not served otherwise.
+    pub admin_token: Option<String>,
+    /// Maximum number of records of a stream sent in one chunk of the HTTP body. Larger chunks
+    /// make fewer writes, which matters on high latency links, at the cost of holding more
+    /// encoded records in memory per stream.
+    pub max_records_per_chunk: usize,
+    /// Where versions pruned by this node may be found instead, e.g. the addresses of backup
+    /// services of archive nodes, or URLs of backup buckets. Sent along with the 410 replies to
+    /// requests for pruned versions, for clients to fail over to them.
+    pub pruned_data_hints: Vec<String>,
+}
+
+impl Default for BackupServiceConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_state_snapshot_streams: 4,
+            state_snapshot_queue_timeout_ms: 5000,
+            retry_after_secs: 60,
+            audit_log_path: None,
+            audit_log_max_file_size_bytes: 100 << 20,
+            audit_log_max_files: 10,
+            zstd_dictionary_path: None,
+            endpoints: BackupServiceEndpoints::default(),
+            admin_token: None,
+            max_records_per_chunk: 64,
+            pruned_data_hints: vec![],
+        }
+    }
+}
+
+/// Whether each endpoint of the backup service is served, all of them by default. Requests to a
+/// disabled endpoint, including HEAD and checksum ones, are replied with 404.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupServiceEndpoints {
+    pub db_state: bool,
+    pub state_range_proof: bool,
+    /// Streams the whole state at a version, the most expensive endpoint to serve.
+    pub state_snapshot: bool,
+    pub state_root_proof: bool,
+    pub epoch_ending_ledger_infos: bool,
+    pub transactions: bool,
+    pub transaction_range_proof: bool,
+    pub zstd_dictionary: bool,
+}
+
+impl Default for BackupServiceEndpoints {
+    fn default() -> Self {
+        Self {
+            db_state: true,
+            state_range_proof: true,
+            state_snapshot: true,
+            state_root_proof: true,
+            epoch_ending_ledger_infos: true,
+            transactions: true,
+            transaction_range_proof: true,
+            zstd_dictionary: true,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct StorageConfig {
     pub backup_service_address: SocketAddr,
+    pub backup_service: BackupServiceConfig,
     pub dir: PathBuf,
     pub storage_pruner_config: PrunerConfig,
     #[serde(skip)]
@@ -234,6 +323,7 @@ impl Default for StorageConfig {
     fn default() -> StorageConfig {
         StorageConfig {
             backup_service_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 6186),
+            backup_service: BackupServiceConfig::default(),
             dir: PathBuf::from("db"),
             // The prune window must at least out live a RPC request because its sub requests are
             // to return a consistent view of the DB at exactly same version. Considering a few
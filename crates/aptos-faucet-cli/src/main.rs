@@ -96,7 +96,9 @@ impl FaucetCliArgs {
                 auth_key: None,
                 address: Some(account.to_hex_literal()),
                 pub_key: None,
+                name: None,
                 return_txns: None,
+                detailed: None,
             })
             .await;
             match response {
@@ -0,0 +1,240 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    types::{ErrorBody, Rejection, RejectionCode, RejectionsBody},
+    MAX_RETRY_DELAY,
+};
+use reqwest::StatusCode;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FaucetClientError {
+    /// The checkers of the faucet rejected the request, see `Rejection::code`.
+    #[error("Request rejected: {}", join(rejections))]
+    Rejected {
+        status: StatusCode,
+        rejections: Vec<Rejection>,
+    },
+    /// The request is malformed, e.g. the address is invalid, or the faucet doesn't fund names.
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    /// The faucet has too many requests queued to take this one. `retryable` is whether the
+    /// faucet says the same request may succeed later, `None` for faucets that don't say.
+    #[error("Faucet busy: {message}")]
+    Busy {
+        message: String,
+        retry_after: Option<Duration>,
+        retryable: Option<bool>,
+    },
+    /// The faucet failed to serve the request. `retryable` is whether the faucet says the same
+    /// request may succeed later, `None` for faucets that don't say.
+    #[error("Faucet error {status}: {message}")]
+    Server {
+        status: StatusCode,
+        message: String,
+        retryable: Option<bool>,
+    },
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to decode the reply: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+fn join(rejections: &[Rejection]) -> String {
+    rejections
+        .iter()
+        .map(|rejection| rejection.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl FaucetClientError {
+    /// Makes sense of the reply to a request that failed, asked for with `Accept:
+    /// application/json`.
+    pub(crate) fn from_reply(
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    ) -> Self {
+        if let Ok(RejectionsBody { rejections }) = serde_json::from_str(&body) {
+            return Self::Rejected { status, rejections };
+        }
+        let (message, retryable, retry_after) = match serde_json::from_str(&body) {
+            Ok(ErrorBody { error }) => (
+                error.message,
+                Some(error.retryable),
+                error
+                    .retry_after_secs
+                    .map(Duration::from_secs)
+                    .or(retry_after),
+            ),
+            Err(_) => (body, None, retry_after),
+        };
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest(message),
+            StatusCode::SERVICE_UNAVAILABLE => Self::Busy {
+                message,
+                retry_after,
+                retryable,
+            },
+            _ => Self::Server {
+                status,
+                message,
+                retryable,
+            },
+        }
+    }
+
+    /// Codes of the rejections, empty unless rejected by the checkers of the faucet.
+    pub fn rejection_codes(&self) -> Vec<RejectionCode> {
+        match self {
+            Self::Rejected { rejections, .. } => rejections.iter().map(|r| r.code).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// How long the faucet asked to wait before sending the request again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Busy { retry_after, .. } => *retry_after,
+            Self::Rejected { rejections, .. } => rejections
+                .iter()
+                .filter_map(|rejection| rejection.retry_after_secs)
+                .max()
+                .map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    /// Whether the request may be sent again. Requests that aren't idempotent, i.e. funding, are
+    /// only sent again when the faucet is known not to have acted on them: it says they may be
+    /// retried, or they never reached it. Rejections are only retried if they all may be lifted,
+    /// within the longest wait before a retry.
+    pub(crate) fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            Self::Busy {
+                retryable: Some(retryable),
+                ..
+            }
+            | Self::Server {
+                retryable: Some(retryable),
+                ..
+            } => *retryable,
+            Self::Busy {
+                retryable: None, ..
+            } => idempotent,
+            Self::Server {
+                status,
+                retryable: None,
+                ..
+            } => idempotent && status.is_server_error(),
+            Self::Http(e) => idempotent || e.is_connect(),
+            Self::Rejected { rejections, .. } => {
+                rejections.iter().all(|rejection| rejection.retryable)
+                    && self
+                        .retry_after()
+                        .map_or(true, |retry_after| retry_after <= MAX_RETRY_DELAY)
+            },
+            Self::BadRequest(_) | Self::Decode(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaucetClientError;
+    use crate::types::RejectionCode;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn test_from_reply() {
+        let body = r#"{"rejections":[
+            {"code":"usage_limit_exhausted","reason":"IP 1.2.3.4 has exceeded the daily limit","checker":"ip_ratelimit","limit":10,"retry_after_secs":3600},
+            {"code":"something_new","reason":"Who knows","checker":"new"},
+            {"code":"captcha_required","reason":"Solve a captcha","checker":"geo_policy","challenge":{"kind":"captcha","url":"https://captcha","token_header":"x-captcha-token"}}
+        ]}"#;
+        let error =
+            FaucetClientError::from_reply(StatusCode::TOO_MANY_REQUESTS, None, body.to_string());
+        assert_eq!(error.rejection_codes(), vec![
+            RejectionCode::UsageLimitExhausted,
+            RejectionCode::Unknown,
+            RejectionCode::CaptchaRequired,
+        ]);
+        assert!(error
+            .to_string()
+            .starts_with("Request rejected: IP 1.2.3.4 has exceeded the daily limit; Who knows"));
+        assert!(!error.is_retryable(true));
+
+        let error = FaucetClientError::from_reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some(Duration::from_secs(3)),
+            "The faucet is busy".to_string(),
+        );
+        assert!(
+            matches!(error, FaucetClientError::Busy { retry_after: Some(d), .. } if d.as_secs() == 3)
+        );
+        // Unless the faucet says, funding isn't sent again, as it may have been acted on.
+        assert!(error.is_retryable(true));
+        assert!(!error.is_retryable(false));
+
+        let error = FaucetClientError::from_reply(
+            StatusCode::BAD_REQUEST,
+            None,
+            "Invalid address".to_string(),
+        );
+        assert!(matches!(error, FaucetClientError::BadRequest(_)));
+        assert!(!error.is_retryable(true));
+
+        // Funding may have gone through before the faucet failed.
+        let error = FaucetClientError::from_reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            None,
+            "Faucet account not found".to_string(),
+        );
+        assert!(error.is_retryable(true));
+        assert!(!error.is_retryable(false));
+
+        // Faucets saying whether to retry.
+        let error = FaucetClientError::from_reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            None,
+            r#"{"error":{"code":"something_new","message":"Not worth it","retryable":false}}"#
+                .to_string(),
+        );
+        assert!(
+            matches!(&error, FaucetClientError::Server { message, .. } if message == "Not worth it")
+        );
+        assert!(!error.is_retryable(true));
+        let error = FaucetClientError::from_reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            None,
+            r#"{"error":{"code":"overloaded","message":"Busy","retryable":true,"retry_after_secs":12}}"#
+                .to_string(),
+        );
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(12)));
+        assert!(error.is_retryable(false));
+        let error = FaucetClientError::from_reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            None,
+            r#"{"error":{"code":"something_new","message":"Busy","retryable":false}}"#.to_string(),
+        );
+        assert!(!error.is_retryable(false));
+
+        let rejected = |retry_after_secs: u64| {
+            FaucetClientError::from_reply(
+                StatusCode::SERVICE_UNAVAILABLE,
+                None,
+                format!(
+                    r#"{{"rejections":[{{"code":"checker_unavailable","reason":"Try again later","checker":"cooldown","retryable":true,"retry_after_secs":{}}}]}}"#,
+                    retry_after_secs
+                ),
+            )
+        };
+        assert!(rejected(1).is_retryable(false));
+        // Not worth waiting for.
+        assert!(!rejected(3600).is_retryable(false));
+    }
+}
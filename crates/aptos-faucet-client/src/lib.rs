@@ -0,0 +1,197 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed client of the faucet, for tools to fund accounts without reimplementing its endpoints:
+//! `POST /mint`, `POST /fund_batch`, `GET /requests/<id>` and `GET /health`. Rejections by the
+//! checkers of the faucet come back as `FaucetClientError::Rejected`, with their machine readable
+//! codes, and requests are retried with a backoff when the faucet is busy or unreachable, or says
+//! they may succeed later, as long as retrying can't fund anything twice.
+
+mod error;
+mod types;
+
+use aptos_types::account_address::AccountAddress;
+pub use error::FaucetClientError;
+use reqwest::{
+    header::{ACCEPT, RETRY_AFTER},
+    Client as ReqwestClient, RequestBuilder, Response,
+};
+use std::time::Duration;
+pub use types::{
+    Challenge, ChallengeKind, FundBatchItemResult, FundBatchResponse, FundResponse, Rejection,
+    RejectionCode, RequestStatus, RequestStatusResponse,
+};
+use types::{FundBatchItem, FundBatchRequest};
+use url::Url;
+
+pub type Result<T, E = FaucetClientError> = std::result::Result<T, E>;
+
+/// Times a request is retried, unless configured otherwise.
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+/// Delay before the first retry, doubled for each one after.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Longest wait before a retry, including when the faucet asks for longer.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+pub struct FaucetClient {
+    faucet_url: Url,
+    inner: ReqwestClient,
+    max_retries: usize,
+    retry_delay: Duration,
+}
+
+impl FaucetClient {
+    pub fn new(faucet_url: Url) -> Self {
+        Self {
+            faucet_url,
+            inner: ReqwestClient::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Funds `address` with `amount`, creating the account if needed. Returns once the faucet
+    /// submitted the transaction, not once it is committed.
+    pub async fn fund(&self, address: AccountAddress, amount: u64) -> Result<FundResponse> {
+        let url = self.url("mint");
+        let query = [
+            ("address", address.to_hex_literal()),
+            ("amount", amount.to_string()),
+            ("detailed", "true".to_string()),
+        ];
+        let body = self
+            .send(false, || {
+                self.inner
+                    .post(url.clone())
+                    .query(&query)
+                    .header("content-length", 0)
+            })
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Funds each of the receivers with its amount, in as few transactions as the faucet can.
+    /// Receivers rejected by the faucet are reported in the results rather than failing the
+    /// whole batch.
+    pub async fn fund_batch(
+        &self,
+        receivers: &[(AccountAddress, u64)],
+    ) -> Result<FundBatchResponse> {
+        let url = self.url("fund_batch");
+        let request = FundBatchRequest {
+            items: receivers
+                .iter()
+                .map(|(address, amount)| FundBatchItem::new(*address, *amount))
+                .collect(),
+        };
+        let body = self
+            .send(false, || self.inner.post(url.clone()).json(&request))
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Where a request queued with `async=true` stands.
+    pub async fn request_status(&self, request_id: &str) -> Result<RequestStatusResponse> {
+        let url = self.url(&format!("requests/{}", request_id));
+        let body = self.send(true, || self.inner.get(url.clone())).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Checks that the faucet is up and can reach its funder account, returning the sequence
+    /// number of the latter.
+    pub async fn health(&self) -> Result<u64> {
+        let url = self.url("health");
+        let body = self.send(true, || self.inner.get(url.clone())).await?;
+        body.trim().parse().map_err(|_| FaucetClientError::Server {
+            status: reqwest::StatusCode::OK,
+            message: format!("Unexpected health reply: {}", body),
+            retryable: Some(false),
+        })
+    }
+
+    /// The URL of the endpoint at `path`, under the path the faucet is served at, if any.
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.faucet_url.clone();
+        url.path_segments_mut()
+            .expect("The faucet URL must be a base URL")
+            .pop_if_empty()
+            .extend(path.split('/'));
+        url
+    }
+
+    /// Sends the request built by `build`, retrying it as `FaucetClientError::is_retryable`
+    /// allows, and returns the body of the reply once successful.
+    async fn send(&self, idempotent: bool, build: impl Fn() -> RequestBuilder) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            let res = match build().header(ACCEPT, "application/json").send().await {
+                Ok(response) => Self::read(response).await,
+                Err(e) => Err(e.into()),
+            };
+            let error = match res {
+                Ok(body) => return Ok(body),
+                Err(e) if attempt < self.max_retries && e.is_retryable(idempotent) => e,
+                Err(e) => return Err(e),
+            };
+            let delay = error
+                .retry_after()
+                .unwrap_or_else(|| self.retry_delay * 2u32.saturating_pow(attempt as u32));
+            tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn read(response: Response) -> Result<String> {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+        let body = response.text().await?;
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(FaucetClientError::from_reply(status, retry_after, body))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaucetClient;
+    use url::Url;
+
+    #[test]
+    fn test_url() {
+        let client = FaucetClient::new(Url::parse("http://localhost:8081").unwrap());
+        assert_eq!(client.url("mint").as_str(), "http://localhost:8081/mint");
+
+        // Faucets served under a path, with or without a trailing slash.
+        for faucet_url in ["https://example.com/faucet/", "https://example.com/faucet"] {
+            let client = FaucetClient::new(Url::parse(faucet_url).unwrap());
+            assert_eq!(
+                client.url("mint").as_str(),
+                "https://example.com/faucet/mint"
+            );
+            assert_eq!(
+                client.url("requests/1f2e").as_str(),
+                "https://example.com/faucet/requests/1f2e"
+            );
+        }
+    }
+}
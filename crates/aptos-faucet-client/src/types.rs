@@ -0,0 +1,182 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bodies of the requests and replies of the faucet, as it serializes them.
+
+use aptos_crypto::HashValue;
+use aptos_types::account_address::AccountAddress;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reply to `POST /mint?detailed=true`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct FundResponse {
+    pub txn_hashes: Vec<HashValue>,
+    /// Explorer page of each transaction, if the faucet is configured with a URL template.
+    pub explorer_urls: Option<Vec<String>>,
+    /// Latest ledger version seen by the faucet when it submitted the transactions.
+    pub ledger_version: u64,
+    /// Account the funds were sent from.
+    pub funder: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct FundBatchRequest {
+    pub items: Vec<FundBatchItem>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct FundBatchItem {
+    pub address: String,
+    pub amount: u64,
+}
+
+impl FundBatchItem {
+    pub fn new(address: AccountAddress, amount: u64) -> Self {
+        Self {
+            address: address.to_hex_literal(),
+            amount,
+        }
+    }
+}
+
+/// Reply to `POST /fund_batch`, with a result for each receiver, in the order of the request.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct FundBatchResponse {
+    pub results: Vec<FundBatchItemResult>,
+    /// Transactions funding the accepted receivers, empty if none was.
+    pub txn_hashes: Vec<HashValue>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct FundBatchItemResult {
+    pub address: String,
+    /// Amount sent, after capping and scaling by the faucet.
+    pub amount: u64,
+    pub funded: bool,
+    /// Why the receiver wasn't funded.
+    pub rejection: Option<String>,
+    /// The rejections by the checkers of the faucet, in full, if any.
+    #[serde(default)]
+    pub rejections: Vec<Rejection>,
+}
+
+/// Where a request queued with `async=true` stands, as of `GET /requests/<id>`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct RequestStatusResponse {
+    pub request_id: String,
+    #[serde(flatten)]
+    pub status: RequestStatus,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RequestStatus {
+    Queued {
+        position: usize,
+        eta_secs: u64,
+    },
+    Processing,
+    /// `response` is what the request would have been answered with, had it not been queued.
+    Done {
+        response: String,
+    },
+    Failed {
+        error: String,
+        /// Whether sending the request again may succeed. Faucets before this was reported
+        /// leave it false.
+        #[serde(default)]
+        retryable: bool,
+        retry_after_secs: Option<u64>,
+    },
+}
+
+/// Why the faucet rejected a request, see `RejectionCode`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Rejection {
+    pub code: RejectionCode,
+    pub reason: String,
+    /// Name of the checker that rejected the request.
+    pub checker: String,
+    /// Whether the same request may be accepted later, after `retry_after_secs` if set. Faucets
+    /// before this was reported leave it false.
+    #[serde(default)]
+    pub retryable: bool,
+    /// The limit that was reached, e.g. the number of requests per day.
+    pub limit: Option<u64>,
+    /// How long until the request would be accepted again.
+    pub retry_after_secs: Option<u64>,
+    /// What to do to get the request accepted right away.
+    pub challenge: Option<Challenge>,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+/// Stable, machine readable code of a rejection.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionCode {
+    /// Too many requests from the client.
+    UsageLimitExhausted,
+    /// Requests from the network or autonomous system of the client spiked unusually.
+    AnomalousVelocity,
+    /// The receiver isn't in the allowlist, or not eligible on chain.
+    ReceiverNotAllowed,
+    /// The receiver was funded too recently.
+    ReceiverCoolingDown,
+    /// The request must carry a solved proof of work challenge.
+    ProofOfWorkRequired,
+    /// Requests from the country of the client are not served.
+    CountryBlocked,
+    /// Requests from the country of the client must carry the token of a solved captcha.
+    CaptchaRequired,
+    /// A code this client doesn't know of, from a later faucet.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeKind {
+    Captcha,
+    ProofOfWork,
+    #[serde(other)]
+    Unknown,
+}
+
+/// A challenge whose proof is sent in the `token_header` header when retrying the request.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Challenge {
+    pub kind: ChallengeKind,
+    /// Where to complete a captcha.
+    pub url: Option<String>,
+    /// Seed of a proof of work.
+    pub seed: Option<String>,
+    /// Leading zero bits required of the hash of a proof of work.
+    pub difficulty: Option<u8>,
+    pub token_header: String,
+}
+
+/// Body of the reply to a rejected request.
+#[derive(Debug, Deserialize)]
+pub(crate) struct RejectionsBody {
+    pub rejections: Vec<Rejection>,
+}
+
+/// A failure of the faucet other than a rejection.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorDetails {
+    pub message: String,
+    pub retryable: bool,
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Body of the reply to a failed request.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorBody {
+    pub error: ErrorDetails,
+}
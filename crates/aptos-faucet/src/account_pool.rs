@@ -0,0 +1,219 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Dispenser mode: the faucet keeps a pool of accounts it created and funded ahead of time, and
+//! hands one out, private key included, to whoever asks. Getting an account that way doesn't
+//! wait for any transaction, which makes it much faster for CI than creating one on demand.
+
+use crate::{
+    checkers::{is_shadow_banned, rejection_reply, CheckerData},
+    errors::{error_reply, FaucetError, FaucetErrorCode},
+    mint::{self, MintParams},
+    Service, MAX_OUTSTANDING_TRANSACTIONS,
+};
+use anyhow::Result;
+use aptos_crypto::ValidCryptoMaterialStringExt;
+use aptos_logger::{info, warn};
+use aptos_sdk::types::LocalAccount;
+use chrono::Utc;
+use futures::lock::Mutex;
+use serde::Serialize;
+use std::{collections::VecDeque, convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::Notify;
+use warp::{http::HeaderMap, Filter, Rejection, Reply};
+
+/// How long to wait before trying again after failing to create accounts.
+const REFILL_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Body of the reply to `POST /account`.
+#[derive(Debug, Serialize)]
+pub struct PooledAccount {
+    pub address: String,
+    pub private_key: String,
+}
+
+pub struct AccountPool {
+    /// Number of accounts the pool is refilled up to.
+    target_size: usize,
+    /// Amount each account is funded with.
+    amount: u64,
+    /// Created and funded accounts, the transactions creating them committed.
+    accounts: Mutex<VecDeque<LocalAccount>>,
+    /// Wakes up the refill task when an account was taken.
+    taken: Notify,
+}
+
+impl AccountPool {
+    pub fn new(target_size: usize, amount: u64) -> Self {
+        Self {
+            target_size,
+            amount,
+            accounts: Mutex::new(VecDeque::with_capacity(target_size)),
+            taken: Notify::new(),
+        }
+    }
+
+    pub async fn available(&self) -> usize {
+        self.accounts.lock().await.len()
+    }
+
+    /// Takes the oldest account out of the pool, if any.
+    pub async fn take(&self) -> Option<LocalAccount> {
+        let account = self.accounts.lock().await.pop_front();
+        if account.is_some() {
+            self.taken.notify_one();
+        }
+        account
+    }
+
+    /// Puts back an account that was taken but not handed out.
+    async fn put_back(&self, account: LocalAccount) {
+        self.accounts.lock().await.push_front(account);
+    }
+
+    /// Keeps the pool filled, creating accounts through the given service. Runs forever.
+    pub async fn refill(self: Arc<Self>, service: Arc<Service>) {
+        loop {
+            let missing = self.target_size.saturating_sub(self.available().await);
+            if missing == 0 {
+                self.taken.notified().await;
+                continue;
+            }
+
+            // Leave room for the mint requests served in the meantime.
+            let batch = missing
+                .min(MAX_OUTSTANDING_TRANSACTIONS as usize / 2)
+                .max(1);
+            let results =
+                futures::future::join_all((0..batch).map(|_| self.create_account(&service))).await;
+            let mut failed = false;
+            for result in results {
+                match result {
+                    Ok(account) => self.accounts.lock().await.push_back(account),
+                    Err(err) => {
+                        warn!("Failed to create an account for the pool: {:#}", err);
+                        failed = true;
+                    },
+                }
+            }
+            info!(
+                available = self.available().await,
+                target = self.target_size,
+                "refilled account pool"
+            );
+            if failed {
+                tokio::time::sleep(REFILL_RETRY_INTERVAL).await;
+            }
+        }
+    }
+
+    async fn create_account(&self, service: &Service) -> Result<LocalAccount> {
+        let account = LocalAccount::generate(&mut rand::rngs::OsRng);
+        // Pooled accounts are never funded from the treasury, however large their amount.
+        let response = mint::process_from_faucet(service, MintParams {
+            amount: self.amount,
+            auth_key: None,
+            address: Some(account.address().to_hex_literal()),
+            pub_key: None,
+            name: None,
+            return_txns: Some(true),
+            detailed: None,
+        })
+        .await?;
+        match response {
+            mint::Response::SubmittedTxns(txns) => {
+                for txn in txns {
+                    service.client.wait_for_signed_transaction(&txn).await?;
+                }
+            },
+            _ => anyhow::bail!("Expected a set of Response::SubmittedTxns"),
+        }
+        Ok(account)
+    }
+}
+
+pub fn account_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // POST /account
+    warp::path!("account")
+        .and(warp::post())
+        .and(warp::any().map(move || service.clone()))
+        .and(warp::addr::remote())
+        .and(warp::header::headers_cloned())
+        .and_then(handle)
+}
+
+async fn handle(
+    service: Arc<Service>,
+    remote_addr: Option<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let pool = match service.account_pool() {
+        Some(pool) => pool,
+        None => {
+            return Ok(error_reply(
+                &FaucetError::new(
+                    FaucetErrorCode::NotFound,
+                    "This faucet doesn't hand out accounts, use /mint instead".to_string(),
+                ),
+                &headers,
+            ))
+        },
+    };
+    let account = match pool.take().await {
+        Some(account) => account,
+        None => {
+            return Ok(error_reply(
+                &FaucetError::new(
+                    FaucetErrorCode::Overloaded,
+                    "No account available at the moment, try again shortly".to_string(),
+                ),
+                &headers,
+            ))
+        },
+    };
+
+    // The account is checked as if it was being funded, so that per client limits apply alike.
+    let data = CheckerData {
+        receiver: account.address(),
+        amount: pool.amount,
+        source_ip: service.client_ip(remote_addr, &headers),
+        headers,
+        time: Utc::now(),
+    };
+    let account = match service.run_checkers(&data).await {
+        Ok(rejections) if is_shadow_banned(&rejections) => {
+            // Hand out an account that was never funded instead.
+            info!(
+                source_ip = data.source_ip,
+                "shadow banned account request, answering with an unfunded account"
+            );
+            pool.put_back(account).await;
+            LocalAccount::generate(&mut rand::rngs::OsRng)
+        },
+        Ok(rejections) if !rejections.is_empty() => {
+            pool.put_back(account).await;
+            return Ok(rejection_reply(&rejections, &data.headers));
+        },
+        Ok(_) => account,
+        Err(err) => {
+            pool.put_back(account).await;
+            return Ok(error_reply(&FaucetError::internal(err), &data.headers));
+        },
+    };
+
+    match account.private_key().to_encoded_string() {
+        Ok(private_key) => {
+            info!(address = account.address(), "handed out pooled account");
+            Ok(Box::new(warp::reply::json(&PooledAccount {
+                address: account.address().to_hex_literal(),
+                private_key,
+            })))
+        },
+        Err(err) => Ok(error_reply(&FaucetError::internal(err), &data.headers)),
+    }
+}
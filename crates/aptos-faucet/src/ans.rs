@@ -0,0 +1,155 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Resolution of Aptos Names (ANS), e.g. `alice.apt`, to the address they point to, so that
+//! requests can be funded by name.
+
+use anyhow::{bail, format_err, Result};
+use aptos_sdk::types::account_address::AccountAddress;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+const APT_SUFFIX: &str = ".apt";
+
+/// Names that are not registered are only cached briefly, since people tend to register a name
+/// and retry right away.
+const NOT_REGISTERED_TTL: Duration = Duration::from_secs(10);
+
+/// Bound on the number of cached names, beyond which expired entries are dropped.
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// Normalizes a name to the form the resolver expects: lowercase, without the `.apt` suffix.
+/// Subdomains (`sub.alice.apt`) are allowed.
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_suffix(APT_SUFFIX).unwrap_or(&name);
+    let labels: Vec<&str> = name.split('.').collect();
+    if labels.len() > 2 {
+        bail!("'{}' has too many levels of subdomains", name);
+    }
+    for label in labels {
+        if label.len() < 3 || label.len() > 63 {
+            bail!(
+                "'{}' must be between 3 and 63 characters long",
+                label.to_string() + APT_SUFFIX
+            );
+        }
+        if !label
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            || label.starts_with('-')
+            || label.ends_with('-')
+        {
+            bail!(
+                "'{}' may only contain letters, digits and inner hyphens",
+                label.to_string() + APT_SUFFIX
+            );
+        }
+    }
+    Ok(name.to_string())
+}
+
+#[derive(Deserialize)]
+struct AddressResponse {
+    /// Absent if the name is not registered.
+    address: Option<String>,
+}
+
+/// Resolves names with the ANS REST API, e.g.
+/// `https://www.aptosnames.com/api/testnet/v1/address/`, which returns `{"address": "0x..."}` for
+/// `GET <url>/<name>`, or `{}` if the name is not registered.
+pub struct AnsResolver {
+    url: Url,
+    client: reqwest::Client,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, (Option<AccountAddress>, Instant)>>,
+}
+
+impl AnsResolver {
+    pub fn new(url: Url, cache_ttl: Duration) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+            cache_ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the address the name points to, or `None` if the name is not registered.
+    pub async fn resolve(&self, name: &str) -> Result<Option<AccountAddress>> {
+        let name = normalize_name(name)?;
+        if let Some(address) = self.cached(&name) {
+            return Ok(address);
+        }
+
+        let url = format!("{}/{}", self.url.as_str().trim_end_matches('/'), name);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format_err!("Failed to resolve name '{}{}': {}", name, APT_SUFFIX, e))?;
+        let address = response
+            .json::<AddressResponse>()
+            .await
+            .map_err(|e| format_err!("Invalid response from the name resolver: {}", e))?
+            .address
+            .map(|address| {
+                AccountAddress::from_hex_literal(&address).map_err(|e| {
+                    format_err!("Name resolver returned invalid address {}: {}", address, e)
+                })
+            })
+            .transpose()?;
+
+        self.insert(name, address);
+        Ok(address)
+    }
+
+    fn cached(&self, name: &str) -> Option<Option<AccountAddress>> {
+        let cache = self.cache.lock().unwrap();
+        let (address, expiration) = cache.get(name)?;
+        (*expiration > Instant::now()).then_some(*address)
+    }
+
+    fn insert(&self, name: String, address: Option<AccountAddress>) {
+        let now = Instant::now();
+        let ttl = if address.is_some() {
+            self.cache_ttl
+        } else {
+            std::cmp::min(self.cache_ttl, NOT_REGISTERED_TTL)
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.retain(|_, (_, expiration)| *expiration > now);
+        }
+        cache.insert(name, (address, now + ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_name;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("alice.apt").unwrap(), "alice");
+        assert_eq!(normalize_name(" Alice ").unwrap(), "alice");
+        assert_eq!(normalize_name("sub.alice.apt").unwrap(), "sub.alice");
+        assert_eq!(normalize_name("my-name-1").unwrap(), "my-name-1");
+
+        assert!(normalize_name("al.apt").is_err());
+        assert!(normalize_name("-alice.apt").is_err());
+        assert!(normalize_name("al_ice.apt").is_err());
+        assert!(normalize_name("a.b.alice.apt").is_err());
+        assert!(normalize_name(&"a".repeat(64)).is_err());
+    }
+}
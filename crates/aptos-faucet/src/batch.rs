@@ -0,0 +1,284 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Funding many accounts in one request, e.g. all the accounts a CI job needs. Each receiver is
+//! checked on its own, and those accepted are funded together: the faucet mints the total to
+//! itself, then sends each receiver its amount with a single `aptos_account::batch_transfer`.
+
+use crate::{
+    checkers::{is_shadow_banned, CheckerData, RejectionReason},
+    errors::{error_reply, FaucetError},
+    mint::{minter_script, sequences, submit_with_shared_sequence_number},
+    validation::parse_address,
+    webhooks::FundingEvent,
+    Service, MAX_OUTSTANDING_TRANSACTIONS,
+};
+use anyhow::Result;
+use aptos_crypto::hash::HashValue;
+use aptos_logger::info;
+use aptos_sdk::{
+    transaction_builder::aptos_stdlib,
+    types::{account_address::AccountAddress, transaction::SignedTransaction},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use warp::{http::HeaderMap, Filter, Rejection, Reply};
+
+/// Body of `POST /fund_batch`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundBatchRequest {
+    pub items: Vec<FundBatchItem>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FundBatchItem {
+    pub address: String,
+    pub amount: u64,
+}
+
+/// Reply to `POST /fund_batch`, with a result for each item, in the order of the request.
+#[derive(Debug, Serialize)]
+pub struct FundBatchResponse {
+    pub results: Vec<FundBatchItemResult>,
+    /// Transactions funding the accepted items, empty if none was.
+    pub txn_hashes: Vec<HashValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FundBatchItemResult {
+    pub address: String,
+    /// Amount sent, after capping to the maximum amount and scaling down for the funder's runway.
+    pub amount: u64,
+    pub funded: bool,
+    /// Why the item wasn't funded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<String>,
+    /// The rejections by the checkers, in full, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rejections: Vec<RejectionReason>,
+}
+
+impl FundBatchItemResult {
+    fn rejected(item: &FundBatchItem, rejection: String) -> Self {
+        Self {
+            address: item.address.clone(),
+            amount: 0,
+            funded: false,
+            rejection: Some(rejection),
+            rejections: vec![],
+        }
+    }
+}
+
+pub fn batch_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // POST /fund_batch
+    warp::path!("fund_batch")
+        .and(warp::post())
+        .and(warp::any().map(move || service.clone()))
+        .and(warp::body::content_length_limit(1024 * 1024))
+        .and(warp::body::json())
+        .and(warp::addr::remote())
+        .and(warp::header::headers_cloned())
+        .and_then(handle)
+}
+
+async fn handle(
+    service: Arc<Service>,
+    request: FundBatchRequest,
+    remote_addr: Option<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Box<dyn warp::Reply>, Infallible> {
+    if request.items.is_empty() || request.items.len() > service.max_batch_size() {
+        return Ok(error_reply(
+            &FaucetError::invalid_request(format!(
+                "A batch must have between 1 and {} items, got {}",
+                service.max_batch_size(),
+                request.items.len()
+            )),
+            &headers,
+        ));
+    }
+
+    let source_ip = service.client_ip(remote_addr, &headers);
+    let mut results = Vec::with_capacity(request.items.len());
+    // Receivers to actually fund, with their amount.
+    let mut to_fund = vec![];
+    for item in &request.items {
+        let receiver = match parse_address(&item.address) {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                results.push(FundBatchItemResult::rejected(item, err.to_string()));
+                continue;
+            },
+        };
+        if let Some(treasury) = service.treasury_for(item.amount) {
+            results.push(FundBatchItemResult::rejected(
+                item,
+                format!(
+                    "Amounts over {} are funded from the treasury, one request at a time on /mint",
+                    treasury.threshold()
+                ),
+            ));
+            continue;
+        }
+        let amount = service.grant_amount(item.amount);
+        let data = CheckerData {
+            receiver,
+            amount,
+            source_ip,
+            headers: headers.clone(),
+            time: Utc::now(),
+        };
+        match service.run_checkers(&data).await {
+            Ok(rejections) if is_shadow_banned(&rejections) => {
+                // Reported as funded, like `/mint` does.
+                info!(
+                    receiver = receiver,
+                    source_ip = source_ip,
+                    amount = amount,
+                    "shadow banned batch item, answering without funding"
+                );
+            },
+            Ok(rejections) if !rejections.is_empty() => {
+                let reasons: Vec<String> = rejections.iter().map(|r| r.to_string()).collect();
+                results.push(FundBatchItemResult {
+                    rejections,
+                    ..FundBatchItemResult::rejected(item, reasons.join("; "))
+                });
+                continue;
+            },
+            Ok(_) => to_fund.push((receiver, amount)),
+            Err(err) => return Ok(error_reply(&FaucetError::internal(err), &headers)),
+        }
+        results.push(FundBatchItemResult {
+            address: receiver.to_hex_literal(),
+            amount,
+            funded: true,
+            rejection: None,
+            rejections: vec![],
+        });
+    }
+
+    let txn_hashes = if to_fund.is_empty() {
+        vec![]
+    } else {
+        let result = process_batch(&service, to_fund.clone()).await;
+        if result.is_ok() {
+            let now = Utc::now();
+            for (receiver, _) in &to_fund {
+                service.record_funding(*receiver, now).await;
+            }
+        }
+        if let Some(webhooks) = service.webhooks() {
+            for (receiver, amount) in to_fund {
+                webhooks.notify(match &result {
+                    Ok(txns) => FundingEvent::funded(
+                        Some(receiver),
+                        amount,
+                        txns.iter().map(|txn| txn.committed_hash()).collect(),
+                    ),
+                    Err(err) => FundingEvent::failed(Some(receiver), amount, err.to_string()),
+                });
+            }
+        }
+        match result {
+            Ok(txns) => txns.iter().map(|txn| txn.committed_hash()).collect(),
+            Err(err) => return Ok(error_reply(&FaucetError::funding(err), &headers)),
+        }
+    };
+    Ok(
+        service.with_amount_scale_header(Box::new(warp::reply::json(&FundBatchResponse {
+            results,
+            txn_hashes,
+        }))),
+    )
+}
+
+/// Submits the transactions funding the receivers: a mint of the total to the faucet account,
+/// if it's not zero, followed by a batch transfer to the receivers.
+pub async fn process_batch(
+    service: &Service,
+    receivers: Vec<(AccountAddress, u64)>,
+) -> Result<Vec<SignedTransaction>> {
+    let total = receivers
+        .iter()
+        .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+        .ok_or_else(|| {
+            FaucetError::invalid_request("Total amount of the batch overflows".to_string())
+        })?;
+    if let Some(key_rotation) = service.key_rotation() {
+        key_rotation.ensure_not_paused()?;
+    }
+    let faucet_address = service.faucet_account.lock().await.address();
+
+    let (recipients, amounts) = receivers.into_iter().unzip();
+    let mut builders = vec![];
+    if total > 0 {
+        builders.push(
+            service
+                .transaction_factory
+                .script(minter_script(faucet_address, total)),
+        );
+    }
+    builders.push(
+        service
+            .transaction_factory
+            .payload(aptos_stdlib::aptos_account_batch_transfer(
+                recipients, amounts,
+            )),
+    );
+
+    if let Some(fake_funder) = service.fake_funder() {
+        return fake_funder.fund(service, builders).await;
+    }
+
+    let (faucet_seq, _, _) = sequences(service, faucet_address).await?;
+    if let Some(shared_sequence_numbers) = service.shared_sequence_numbers() {
+        let mut txns = vec![];
+        for builder in builders {
+            txns.push(
+                submit_with_shared_sequence_number(
+                    service,
+                    shared_sequence_numbers,
+                    faucet_address,
+                    builder,
+                    faucet_seq,
+                )
+                .await?,
+            );
+        }
+        return Ok(txns);
+    }
+
+    // The transfer relies on the mint committing first, so both are signed at once, with
+    // consecutive sequence numbers.
+    let txns: Vec<_> = {
+        let mut faucet_account = service.faucet_account.lock().await;
+        if faucet_seq > faucet_account.sequence_number() {
+            *faucet_account.sequence_number_mut() = faucet_seq;
+        }
+        anyhow::ensure!(
+            faucet_account.sequence_number() + (builders.len() as u64)
+                <= faucet_seq + MAX_OUTSTANDING_TRANSACTIONS,
+            "Too many outstanding transactions, try again shortly"
+        );
+        builders
+            .into_iter()
+            .map(|builder| faucet_account.sign_with_transaction_builder(builder))
+            .collect()
+    };
+    for txn in &txns {
+        // As in `mint::process`, reset to what's on chain if anything goes wrong.
+        if let Err(e) = service.client.submit(txn).await {
+            *service.faucet_account.lock().await.sequence_number_mut() = faucet_seq;
+            return Err(e.into());
+        }
+    }
+    Ok(txns)
+}
@@ -0,0 +1,167 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use anyhow::{Context, Result};
+use aptos_logger::{info, warn};
+use aptos_sdk::types::account_address::AccountAddress;
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+/// Only allows receivers listed in a file, one address per line. Blank lines and lines starting
+/// with `#` are ignored.
+///
+/// The file is reloaded when it changes, so that receivers can be added or removed without
+/// restarting the faucet. If it can't be reloaded, e.g. because it has a malformed address, the
+/// previous list stays in use.
+pub struct ReceiverAllowlistChecker {
+    path: PathBuf,
+    /// The allowed receivers, and the modification time and size of the file last loaded.
+    allowlist: Mutex<(Option<(SystemTime, u64)>, HashSet<AccountAddress>)>,
+}
+
+impl ReceiverAllowlistChecker {
+    pub fn new(path: &Path) -> Result<Self> {
+        let version = file_version(path)?;
+        let allowlist = load(path)?;
+        info!(
+            "Loaded {} receivers from allowlist {}",
+            allowlist.len(),
+            path.display()
+        );
+        Ok(Self {
+            path: path.to_path_buf(),
+            allowlist: Mutex::new((Some(version), allowlist)),
+        })
+    }
+
+    /// Reloads the file if it changed since it was last loaded. A file that fails to load isn't
+    /// retried until it changes again.
+    fn maybe_reload(&self, loaded: &mut (Option<(SystemTime, u64)>, HashSet<AccountAddress>)) {
+        let result = file_version(&self.path).and_then(|version| {
+            if loaded.0 != Some(version) {
+                loaded.0 = Some(version);
+                loaded.1 = load(&self.path)?;
+                info!(
+                    "Reloaded {} receivers from allowlist {}",
+                    loaded.1.len(),
+                    self.path.display()
+                );
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!(
+                "Failed to reload allowlist {}, keeping the previous one: {:#}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+fn file_version(path: &Path) -> Result<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read allowlist {}", path.display()))?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+fn load(path: &Path) -> Result<HashSet<AccountAddress>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read allowlist {}", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            AccountAddress::from_str(line).with_context(|| {
+                format!(
+                    "Invalid address '{}' on line {} of allowlist {}",
+                    line,
+                    i + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Checker for ReceiverAllowlistChecker {
+    fn name(&self) -> &'static str {
+        "receiver_allowlist"
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let mut allowlist = self.allowlist.lock().await;
+        self.maybe_reload(&mut allowlist);
+        if allowlist.1.contains(&data.receiver) {
+            return Ok(None);
+        }
+        Ok(Some(RejectionReason::new(
+            RejectionReasonCode::ReceiverNotAllowed,
+            format!(
+                "Account {} is not allowed to be funded by this faucet",
+                data.receiver.to_hex_literal()
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReceiverAllowlistChecker;
+    use crate::checkers::{Checker, CheckerData, RejectionReasonCode};
+    use aptos_sdk::types::account_address::AccountAddress;
+
+    #[tokio::test]
+    async fn test_allowlist_reload() {
+        let path = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(path.path(), "# CI accounts\n0x1\n\n  0xa550c18  \n").unwrap();
+        let checker = ReceiverAllowlistChecker::new(path.path()).unwrap();
+        let data = |receiver| CheckerData::for_test().receiver(receiver);
+
+        assert!(checker
+            .check(&data(AccountAddress::ONE))
+            .await
+            .unwrap()
+            .is_none());
+        let two = AccountAddress::from_hex_literal("0x2").unwrap();
+        let a550c18 = AccountAddress::from_hex_literal("0xa550c18").unwrap();
+        assert!(checker.check(&data(a550c18)).await.unwrap().is_none());
+        let rejection = checker.check(&data(two)).await.unwrap().unwrap();
+        assert_eq!(rejection.code, RejectionReasonCode::ReceiverNotAllowed);
+
+        // Changes are picked up.
+        std::fs::write(path.path(), "0x1\n0x2\n").unwrap();
+        assert!(checker.check(&data(two)).await.unwrap().is_none());
+        assert!(checker.check(&data(a550c18)).await.unwrap().is_some());
+
+        // A malformed file leaves the previous list in use.
+        std::fs::write(path.path(), "0x1\nnot an address\n0x3\n").unwrap();
+        assert!(checker.check(&data(two)).await.unwrap().is_none());
+        let three = AccountAddress::from_hex_literal("0x3").unwrap();
+        assert!(checker.check(&data(three)).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_allowlist_invalid() {
+        let path = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(path.path(), "0x1\nnot an address\n").unwrap();
+        let err = ReceiverAllowlistChecker::new(path.path())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+}
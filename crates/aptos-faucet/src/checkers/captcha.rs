@@ -0,0 +1,139 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Challenge, ChallengeKind, CheckerData};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use url::Url;
+
+/// Header carrying the token of a solved captcha, when retrying a request that was challenged.
+pub const CAPTCHA_TOKEN_HEADER: &str = "X-Aptos-Faucet-Captcha-Token";
+
+#[derive(Debug, Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+/// Verifies solved captchas through the `siteverify` API shared by hCaptcha, reCAPTCHA and
+/// Turnstile. Tokens are single use, the provider refuses those verified before.
+pub struct CaptchaVerifier {
+    client: reqwest::Client,
+    /// e.g. https://hcaptcha.com/siteverify
+    verify_url: Url,
+    secret: String,
+    /// Page where clients solve the captcha, which gives them the token.
+    challenge_url: Url,
+}
+
+impl CaptchaVerifier {
+    pub fn new(verify_url: Url, secret: String, challenge_url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            verify_url,
+            secret,
+            challenge_url,
+        }
+    }
+
+    /// What clients are told to do to get their request accepted.
+    pub fn challenge(&self) -> Challenge {
+        Challenge {
+            kind: ChallengeKind::Captcha,
+            url: Some(self.challenge_url.to_string()),
+            seed: None,
+            difficulty: None,
+            token_header: CAPTCHA_TOKEN_HEADER,
+        }
+    }
+
+    /// Whether the request carries the token of a solved captcha.
+    pub async fn verify(&self, data: &CheckerData) -> Result<bool> {
+        let token = match data
+            .headers
+            .get(CAPTCHA_TOKEN_HEADER)
+            .and_then(|token| token.to_str().ok())
+        {
+            Some(token) if !token.is_empty() => token,
+            _ => return Ok(false),
+        };
+        let mut form = vec![
+            ("secret", self.secret.clone()),
+            ("response", token.to_string()),
+        ];
+        if let Some(source_ip) = data.source_ip {
+            form.push(("remoteip", source_ip.to_string()));
+        }
+        let response: VerifyResponse = self
+            .client
+            .post(self.verify_url.clone())
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("Failed to verify the captcha")?
+            .json()
+            .await
+            .context("Failed to read the captcha verification")?;
+        Ok(response.success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptchaVerifier, CAPTCHA_TOKEN_HEADER};
+    use crate::checkers::{ChallengeKind, Checker, CheckerData, IpRateLimitChecker, LimitSchedule};
+    use std::{collections::HashMap, sync::Arc};
+    use url::Url;
+    use warp::Filter;
+
+    #[tokio::test]
+    async fn test_captcha_escalation() {
+        // Accepts "solved" from 1.1.1.1.
+        let siteverify = warp::path!("siteverify")
+            .and(warp::post())
+            .and(warp::body::form())
+            .map(|form: HashMap<String, String>| {
+                let success = form.get("secret").map(String::as_str) == Some("secret")
+                    && form.get("response").map(String::as_str) == Some("solved")
+                    && form.get("remoteip").map(String::as_str) == Some("1.1.1.1");
+                warp::reply::json(&serde_json::json!({ "success": success }))
+            });
+        let (address, future) = warp::serve(siteverify).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::task::spawn(future);
+
+        let captcha = Arc::new(CaptchaVerifier::new(
+            Url::parse(&format!("http://localhost:{}/siteverify", address.port())).unwrap(),
+            "secret".to_string(),
+            Url::parse("https://faucet.example.com/captcha").unwrap(),
+        ));
+        let checker = IpRateLimitChecker::new(1, LimitSchedule::default()).with_captcha(captcha);
+        let data = CheckerData::for_test().ip("1.1.1.1");
+
+        assert!(checker.check(&data).await.unwrap().is_none());
+        // Over the limit, the client is challenged rather than turned away.
+        let rejection = checker.check(&data).await.unwrap().unwrap();
+        let challenge = rejection.challenge.unwrap();
+        assert_eq!(challenge.kind, ChallengeKind::Captcha);
+        assert_eq!(
+            challenge.url.as_deref(),
+            Some("https://faucet.example.com/captcha")
+        );
+        assert_eq!(challenge.token_header, CAPTCHA_TOKEN_HEADER);
+        assert!(checker
+            .check(&data.clone().header(CAPTCHA_TOKEN_HEADER, "wrong"))
+            .await
+            .unwrap()
+            .unwrap()
+            .challenge
+            .is_some());
+        // And gets through once it solved it.
+        assert!(checker
+            .check(&data.clone().header(CAPTCHA_TOKEN_HEADER, "solved"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
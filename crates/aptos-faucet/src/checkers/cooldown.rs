@@ -0,0 +1,424 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use crate::metrics::COOLDOWN_STORE_MISMATCHES;
+use anyhow::{Context, Result};
+use aptos_logger::warn;
+use aptos_sdk::types::account_address::AccountAddress;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::lock::Mutex;
+use redis::{aio::MultiplexedConnection, Script};
+use std::collections::HashMap;
+
+/// Bound on the number of tracked receivers, beyond which forgotten ones are dropped.
+const MAX_TRACKED_RECEIVERS: usize = 1_000_000;
+
+/// Checks whether a receiver is cooling down.
+///
+/// KEYS[1]: receiver, ARGV[1]: now, ARGV[2]: base cooldown, ARGV[3]: factor, ARGV[4]: max
+/// cooldown, all in seconds but the factor. Returns the seconds left in the cooldown, or 0.
+const CHECK_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local times_funded = tonumber(redis.call('HGET', KEYS[1], 'times_funded') or 0)
+if times_funded > 0 then
+    local last_funded = tonumber(redis.call('HGET', KEYS[1], 'last_funded'))
+    local cooldown = math.min(
+        tonumber(ARGV[2]) * tonumber(ARGV[3]) ^ (times_funded - 1), tonumber(ARGV[4]))
+    local left = math.ceil(last_funded + cooldown - now)
+    if left > 0 then
+        return left
+    end
+end
+return 0
+"#;
+
+/// Records a funding of a receiver.
+///
+/// KEYS[1]: receiver, ARGV[1]: now, ARGV[2]: forget after, in seconds.
+const RECORD_SCRIPT: &str = r#"
+local times_funded = tonumber(redis.call('HGET', KEYS[1], 'times_funded') or 0)
+redis.call('HSET', KEYS[1], 'times_funded', times_funded + 1, 'last_funded', ARGV[1])
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 0
+"#;
+
+#[derive(Clone, Debug)]
+pub struct CooldownConfig {
+    /// Cooldown after the first funding of a receiver.
+    pub base_secs: u64,
+    /// Each funding multiplies the cooldown by this.
+    pub factor: f64,
+    /// Cap on the cooldown.
+    pub max_secs: u64,
+    /// A receiver not funded for this long starts over.
+    pub forget_after_secs: u64,
+}
+
+impl Default for CooldownConfig {
+    fn default() -> Self {
+        Self {
+            base_secs: 3600,
+            factor: 4.0,
+            max_secs: 7 * 24 * 3600,
+            forget_after_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+impl CooldownConfig {
+    /// Cooldown after a receiver was funded that many times, the last one included.
+    pub fn cooldown_secs(&self, times_funded: u64) -> u64 {
+        if times_funded == 0 {
+            return 0;
+        }
+        let cooldown = self.base_secs as f64 * self.factor.powi((times_funded - 1) as i32);
+        cooldown.min(self.max_secs as f64) as u64
+    }
+}
+
+/// How many times a receiver was funded, and when last.
+#[derive(Clone, Copy, Debug)]
+struct Fundings {
+    times_funded: u64,
+    last_funded_secs: u64,
+}
+
+enum Store {
+    Memory(Mutex<HashMap<AccountAddress, Fundings>>),
+    /// Shared by all replicas.
+    Redis(MultiplexedConnection),
+}
+
+impl Store {
+    async fn connect(redis_url: &str) -> Result<Self> {
+        let connection = redis::Client::open(redis_url)?
+            .get_multiplexed_tokio_connection()
+            .await
+            .with_context(|| format!("Failed to connect to Redis at {}", redis_url))?;
+        Ok(Store::Redis(connection))
+    }
+
+    /// Returns the seconds left in the cooldown of the receiver, if it's cooling down.
+    async fn cooldown_left(
+        &self,
+        config: &CooldownConfig,
+        receiver: AccountAddress,
+        now_secs: u64,
+    ) -> Result<Option<u64>> {
+        match self {
+            Store::Memory(fundings) => {
+                let fundings = fundings.lock().await;
+                Ok(fundings
+                    .get(&receiver)
+                    .filter(|f| f.last_funded_secs + config.forget_after_secs > now_secs)
+                    .map(|f| f.last_funded_secs + config.cooldown_secs(f.times_funded))
+                    .filter(|ends_secs| *ends_secs > now_secs)
+                    .map(|ends_secs| ends_secs - now_secs))
+            },
+            Store::Redis(connection) => {
+                let left_secs: u64 = Script::new(CHECK_SCRIPT)
+                    .key(Self::redis_key(receiver))
+                    .arg(now_secs)
+                    .arg(config.base_secs)
+                    .arg(config.factor)
+                    .arg(config.max_secs)
+                    .invoke_async(&mut connection.clone())
+                    .await?;
+                Ok((left_secs > 0).then(|| left_secs))
+            },
+        }
+    }
+
+    /// Records a funding of the receiver.
+    async fn record(
+        &self,
+        config: &CooldownConfig,
+        receiver: AccountAddress,
+        now_secs: u64,
+    ) -> Result<()> {
+        match self {
+            Store::Memory(fundings) => {
+                let mut fundings = fundings.lock().await;
+                let forget_after_secs = config.forget_after_secs;
+                if fundings.len() > MAX_TRACKED_RECEIVERS {
+                    fundings.retain(|_, f| f.last_funded_secs + forget_after_secs > now_secs);
+                }
+                let times_funded = fundings
+                    .get(&receiver)
+                    .filter(|f| f.last_funded_secs + forget_after_secs > now_secs)
+                    .map_or(0, |f| f.times_funded);
+                fundings.insert(receiver, Fundings {
+                    times_funded: times_funded + 1,
+                    last_funded_secs: now_secs,
+                });
+                Ok(())
+            },
+            Store::Redis(connection) => {
+                let _: u64 = Script::new(RECORD_SCRIPT)
+                    .key(Self::redis_key(receiver))
+                    .arg(now_secs)
+                    .arg(config.forget_after_secs)
+                    .invoke_async(&mut connection.clone())
+                    .await?;
+                Ok(())
+            },
+        }
+    }
+
+    fn redis_key(receiver: AccountAddress) -> String {
+        format!("aptos-faucet:cooldown:{}", receiver)
+    }
+}
+
+/// A store the cooldowns are being migrated to, written along with the current one.
+struct Migration {
+    target: Store,
+    /// Whether requests are answered from the target rather than the current store.
+    read_target: bool,
+}
+
+/// Makes each receiver wait longer between fundings the more it has been funded, e.g. 1h after
+/// the first funding, 4h after the second, and so on, so that a single account cycling through
+/// proxies to get around the per IP limits gets little out of it. Checking doesn't start a
+/// cooldown, only fundings do, see `Checker::record_funding`.
+pub struct ReceiverCooldownChecker {
+    config: CooldownConfig,
+    store: Store,
+    migration: Option<Migration>,
+}
+
+impl ReceiverCooldownChecker {
+    /// Keeps track of the receivers in memory.
+    pub fn new(config: CooldownConfig) -> Self {
+        Self {
+            config,
+            store: Store::Memory(Mutex::new(HashMap::new())),
+            migration: None,
+        }
+    }
+
+    /// Keeps track of the receivers in Redis, for them to be shared by all replicas.
+    pub async fn connect(config: CooldownConfig, redis_url: &str) -> Result<Self> {
+        Ok(Self {
+            config,
+            store: Store::connect(redis_url).await?,
+            migration: None,
+        })
+    }
+
+    /// Also records the fundings in the Redis at `redis_url`, for the cooldowns to be moved
+    /// there without losing their history. Requests are answered from the current store, or
+    /// from the new one once `read_target`, and the answers of both are compared, mismatches
+    /// being logged and counted.
+    pub async fn with_migration(mut self, redis_url: &str, read_target: bool) -> Result<Self> {
+        self.migration = Some(Migration {
+            target: Store::connect(redis_url).await?,
+            read_target,
+        });
+        Ok(self)
+    }
+
+    async fn cooldown_left(&self, receiver: AccountAddress, now_secs: u64) -> Result<Option<u64>> {
+        let migration = match &self.migration {
+            Some(migration) => migration,
+            None => {
+                return self
+                    .store
+                    .cooldown_left(&self.config, receiver, now_secs)
+                    .await
+            },
+        };
+        let (current, target) = futures::join!(
+            self.store.cooldown_left(&self.config, receiver, now_secs),
+            migration
+                .target
+                .cooldown_left(&self.config, receiver, now_secs)
+        );
+        let (primary, secondary) = if migration.read_target {
+            (target, current)
+        } else {
+            (current, target)
+        };
+        match (&primary, &secondary) {
+            (Ok(primary_left), Ok(secondary_left)) if primary_left != secondary_left => {
+                warn!(
+                    "Cooldown stores disagree on receiver {}: {:?} seconds left in the primary, \
+                    {:?} in the secondary",
+                    receiver, primary_left, secondary_left
+                );
+                COOLDOWN_STORE_MISMATCHES.inc();
+            },
+            (Ok(_), Err(e)) => {
+                warn!("Failed to check the secondary cooldown store: {:#}", e);
+                COOLDOWN_STORE_MISMATCHES.inc();
+            },
+            _ => (),
+        }
+        primary
+    }
+}
+
+#[async_trait]
+impl Checker for ReceiverCooldownChecker {
+    fn name(&self) -> &'static str {
+        "receiver_cooldown"
+    }
+
+    fn cost(&self) -> u64 {
+        // A round trip to Redis, when shared.
+        1_000
+    }
+
+    /// Receivers that may not be funded at all are told so, rather than to wait.
+    fn depends_on(&self) -> &'static [&'static str] {
+        &["receiver_allowlist", "on_chain_eligibility"]
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let now_secs = data.time.timestamp().max(0) as u64;
+        Ok(self
+            .cooldown_left(data.receiver, now_secs)
+            .await?
+            .map(|left_secs| {
+                RejectionReason::new(
+                    RejectionReasonCode::ReceiverCoolingDown,
+                    format!(
+                        "Account {} was funded recently, try again in {} minutes",
+                        data.receiver,
+                        (left_secs + 59) / 60
+                    ),
+                )
+                .with_retry_after_secs(left_secs)
+            }))
+    }
+
+    async fn record_funding(&self, receiver: AccountAddress, time: DateTime<Utc>) -> Result<()> {
+        let now_secs = time.timestamp().max(0) as u64;
+        let migration = match &self.migration {
+            Some(migration) => migration,
+            None => return self.store.record(&self.config, receiver, now_secs).await,
+        };
+        let (current, target) = futures::join!(
+            self.store.record(&self.config, receiver, now_secs),
+            migration.target.record(&self.config, receiver, now_secs)
+        );
+        let (primary, secondary) = if migration.read_target {
+            (target, current)
+        } else {
+            (current, target)
+        };
+        if let Err(e) = secondary {
+            warn!(
+                "Failed to record funding in the secondary cooldown store: {:#}",
+                e
+            );
+            COOLDOWN_STORE_MISMATCHES.inc();
+        }
+        primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CooldownConfig, Migration, ReceiverCooldownChecker, Store};
+    use crate::{
+        checkers::{Checker, CheckerData, RejectionReasonCode},
+        metrics::COOLDOWN_STORE_MISMATCHES,
+    };
+    use aptos_sdk::types::account_address::AccountAddress;
+    use futures::lock::Mutex;
+    use std::collections::HashMap;
+
+    /// Checks the request, and records it funded if accepted, returning how long to wait if not.
+    async fn retry_after(checker: &ReceiverCooldownChecker, data: &CheckerData) -> Option<u64> {
+        let retry_after = checker.check(data).await.unwrap().map(|rejection| {
+            assert_eq!(rejection.code, RejectionReasonCode::ReceiverCoolingDown);
+            rejection.retry_after_secs.unwrap()
+        });
+        if retry_after.is_none() {
+            checker
+                .record_funding(data.receiver, data.time)
+                .await
+                .unwrap();
+        }
+        retry_after
+    }
+
+    #[test]
+    fn test_cooldown_secs() {
+        let config = CooldownConfig::default();
+        assert_eq!(config.cooldown_secs(0), 0);
+        assert_eq!(config.cooldown_secs(1), 3600);
+        assert_eq!(config.cooldown_secs(2), 4 * 3600);
+        assert_eq!(config.cooldown_secs(3), 16 * 3600);
+        assert_eq!(config.cooldown_secs(10), 7 * 24 * 3600);
+    }
+
+    #[tokio::test]
+    async fn test_receiver_cooldown() {
+        let checker = ReceiverCooldownChecker::new(CooldownConfig::default());
+        let (alice, bob) = (AccountAddress::ONE, AccountAddress::TWO);
+        let data = |receiver, secs| CheckerData::for_test().receiver(receiver).secs(secs);
+
+        assert_eq!(retry_after(&checker, &data(alice, 0)).await, None);
+        assert_eq!(retry_after(&checker, &data(alice, 600)).await, Some(3000));
+        assert_eq!(retry_after(&checker, &data(bob, 600)).await, None);
+
+        // Checking alone doesn't start a cooldown.
+        let carol = AccountAddress::from_hex_literal("0x3").unwrap();
+        assert_eq!(checker.check(&data(carol, 600)).await.unwrap(), None);
+        assert_eq!(retry_after(&checker, &data(carol, 601)).await, None);
+
+        // Rejected requests don't extend the cooldown, which grows with every funding.
+        assert_eq!(retry_after(&checker, &data(alice, 3600)).await, None);
+        assert_eq!(
+            retry_after(&checker, &data(alice, 3600 + 3600)).await,
+            Some(3 * 3600)
+        );
+        assert_eq!(retry_after(&checker, &data(alice, 5 * 3600)).await, None);
+        assert_eq!(
+            retry_after(&checker, &data(alice, 6 * 3600)).await,
+            Some(15 * 3600)
+        );
+
+        // Long after, the receiver starts over.
+        let later = 5 * 3600 + 30 * 24 * 3600;
+        assert_eq!(retry_after(&checker, &data(alice, later)).await, None);
+        assert_eq!(
+            retry_after(&checker, &data(alice, later + 1)).await,
+            Some(3599)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migration() {
+        let mut checker = ReceiverCooldownChecker::new(CooldownConfig::default());
+        let (alice, bob) = (AccountAddress::ONE, AccountAddress::TWO);
+        let data = |receiver, secs| CheckerData::for_test().receiver(receiver).secs(secs);
+        assert_eq!(retry_after(&checker, &data(alice, 0)).await, None);
+
+        // The target has yet to learn of alice, while the current store answers.
+        checker.migration = Some(Migration {
+            target: Store::Memory(Mutex::new(HashMap::new())),
+            read_target: false,
+        });
+        let mismatches = COOLDOWN_STORE_MISMATCHES.get();
+        assert_eq!(retry_after(&checker, &data(alice, 600)).await, Some(3000));
+        assert_eq!(COOLDOWN_STORE_MISMATCHES.get(), mismatches + 1);
+
+        // Both record new fundings.
+        assert_eq!(retry_after(&checker, &data(bob, 600)).await, None);
+        assert_eq!(retry_after(&checker, &data(bob, 1200)).await, Some(3000));
+        assert_eq!(COOLDOWN_STORE_MISMATCHES.get(), mismatches + 1);
+
+        // Answered from the target, which never learnt of alice, rejected at 600.
+        checker.migration.as_mut().unwrap().read_target = true;
+        assert_eq!(retry_after(&checker, &data(alice, 1800)).await, None);
+        assert_eq!(COOLDOWN_STORE_MISMATCHES.get(), mismatches + 2);
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use anyhow::{bail, Context, Result};
+use aptos_rest_client::{
+    aptos_api_types::{EntryFunctionId, ViewRequest},
+    Client,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+
+/// Only allows receivers a Move view function deems eligible, e.g.
+/// `0xcafe::allowlist::is_eligible(address): bool`, so that who may use a private faucet is
+/// managed on chain rather than in its config.
+pub struct OnChainEligibilityChecker {
+    client: Client,
+    function: EntryFunctionId,
+}
+
+impl OnChainEligibilityChecker {
+    /// `function` takes the receiver address as its only argument and returns a `bool`.
+    pub fn new(client: Client, function: EntryFunctionId) -> Self {
+        Self { client, function }
+    }
+}
+
+/// The view function returns its values in a list, of a single bool here.
+fn parse_eligibility(function: &EntryFunctionId, values: &[Value]) -> Result<bool> {
+    match values {
+        [Value::Bool(eligible)] => Ok(*eligible),
+        _ => bail!(
+            "Expected {} to return a single bool, got {:?}",
+            function,
+            values
+        ),
+    }
+}
+
+#[async_trait]
+impl Checker for OnChainEligibilityChecker {
+    fn name(&self) -> &'static str {
+        "on_chain_eligibility"
+    }
+
+    fn cost(&self) -> u64 {
+        // A view function call to a fullnode.
+        50_000
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let request = ViewRequest {
+            function: self.function.clone(),
+            type_arguments: vec![],
+            arguments: vec![Value::String(data.receiver.to_hex_literal())],
+        };
+        let values = self
+            .client
+            .view(&request, None)
+            .await
+            .with_context(|| format!("Failed to call {}", self.function))?
+            .into_inner();
+        if parse_eligibility(&self.function, &values)? {
+            return Ok(None);
+        }
+        Ok(Some(RejectionReason::new(
+            RejectionReasonCode::ReceiverNotAllowed,
+            format!(
+                "Account {} is not eligible to be funded by this faucet",
+                data.receiver.to_hex_literal()
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_eligibility;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_eligibility() {
+        let function = "0xcafe::allowlist::is_eligible".parse().unwrap();
+        assert!(parse_eligibility(&function, &[json!(true)]).unwrap());
+        assert!(!parse_eligibility(&function, &[json!(false)]).unwrap());
+        assert!(parse_eligibility(&function, &[]).is_err());
+        assert!(parse_eligibility(&function, &[json!("true")]).is_err());
+        assert!(parse_eligibility(&function, &[json!(true), json!(1)]).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Per country policies, for the regions dominating farming traffic. The policies are read from
+//! a YAML file like:
+//!
+//! ```yaml
+//! countries:
+//!   XX: block
+//!   YY: captcha
+//!   ZZ:
+//!     max_requests_per_ip_per_day: 2
+//! ```
+//!
+//! Countries are ISO 3166-1 alpha-2 codes, as found in a GeoIP country database such as
+//! GeoLite2-Country. Clients whose country isn't listed, or can't be told, have no policy.
+
+use super::{CaptchaVerifier, Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use futures::lock::Mutex;
+use maxminddb::geoip2;
+use serde::Deserialize;
+use std::{collections::HashMap, net::IpAddr, path::Path, sync::Arc};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CountryPolicy {
+    /// Requests are rejected.
+    Block,
+    /// Requests must carry the token of a solved captcha, see `CaptchaVerifier`.
+    Captcha,
+    /// A lower daily limit per IP.
+    MaxRequestsPerIpPerDay(u64),
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeoPolicies {
+    #[serde(default)]
+    pub countries: HashMap<String, CountryPolicy>,
+}
+
+impl GeoPolicies {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read geo policies {}", path.display()))?;
+        let policies: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse geo policies {}", path.display()))?;
+        Ok(policies.normalized())
+    }
+
+    /// Country codes in upper case, as in the database.
+    fn normalized(self) -> Self {
+        Self {
+            countries: self
+                .countries
+                .into_iter()
+                .map(|(country, policy)| (country.to_ascii_uppercase(), policy))
+                .collect(),
+        }
+    }
+
+    fn requires_captcha(&self) -> bool {
+        self.countries
+            .values()
+            .any(|policy| *policy == CountryPolicy::Captcha)
+    }
+}
+
+/// Applies the policy of the country of the client IP, as told by a GeoIP database.
+pub struct GeoPolicyChecker {
+    policies: GeoPolicies,
+    /// IP to country database, e.g. GeoLite2-Country.
+    country_db: maxminddb::Reader<Vec<u8>>,
+    captcha: Option<Arc<CaptchaVerifier>>,
+    /// Number of requests per IP for the current UTC day, from countries with a lower limit.
+    usage: Mutex<(Option<NaiveDate>, HashMap<IpAddr, u64>)>,
+}
+
+impl GeoPolicyChecker {
+    /// Policies requiring a captcha need the verifier.
+    pub fn new(
+        policies: GeoPolicies,
+        country_db_path: &Path,
+        captcha: Option<Arc<CaptchaVerifier>>,
+    ) -> Result<Self> {
+        ensure!(
+            captcha.is_some() || !policies.requires_captcha(),
+            "Geo policies require a captcha, but none is configured"
+        );
+        let country_db = maxminddb::Reader::open_readfile(country_db_path).with_context(|| {
+            format!(
+                "Failed to open country database {}",
+                country_db_path.display()
+            )
+        })?;
+        Ok(Self {
+            policies: policies.normalized(),
+            country_db,
+            captcha,
+            usage: Mutex::new((None, HashMap::new())),
+        })
+    }
+
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        self.country_db
+            .lookup::<geoip2::Country>(ip)
+            .ok()?
+            .country?
+            .iso_code
+            .map(str::to_string)
+    }
+
+    async fn check_policy(
+        &self,
+        country: &str,
+        source_ip: IpAddr,
+        data: &CheckerData,
+    ) -> Result<Option<RejectionReason>> {
+        match self.policies.countries.get(country) {
+            None => Ok(None),
+            Some(CountryPolicy::Block) => Ok(Some(RejectionReason::new(
+                RejectionReasonCode::CountryBlocked,
+                format!("Requests from {} are not served", country),
+            ))),
+            Some(CountryPolicy::Captcha) => {
+                let captcha = self.captcha.as_ref().expect("Checked when created");
+                if captcha.verify(data).await? {
+                    return Ok(None);
+                }
+                Ok(Some(
+                    RejectionReason::new(
+                        RejectionReasonCode::CaptchaRequired,
+                        format!("Requests from {} must solve a captcha", country),
+                    )
+                    .with_challenge(captcha.challenge()),
+                ))
+            },
+            Some(CountryPolicy::MaxRequestsPerIpPerDay(limit)) => {
+                let today = data.time.naive_utc().date();
+                let mut usage = self.usage.lock().await;
+                let (day, counts) = &mut *usage;
+                if *day != Some(today) {
+                    *day = Some(today);
+                    counts.clear();
+                }
+                let count = counts.entry(source_ip).or_insert(0);
+                if *count >= *limit {
+                    let retry_after = today.succ().and_hms(0, 0, 0) - data.time.naive_utc();
+                    return Ok(Some(
+                        RejectionReason::new(
+                            RejectionReasonCode::UsageLimitExhausted,
+                            format!(
+                                "IP {} has exceeded the daily limit of {} requests from {}",
+                                source_ip, limit, country
+                            ),
+                        )
+                        .with_limit(*limit)
+                        .with_retry_after_secs(retry_after.num_seconds().max(0) as u64),
+                    ));
+                }
+                *count += 1;
+                Ok(None)
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Checker for GeoPolicyChecker {
+    fn name(&self) -> &'static str {
+        "geo_policy"
+    }
+
+    fn cost(&self) -> u64 {
+        // A lookup in the country database.
+        100
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let source_ip = match data.source_ip {
+            Some(source_ip) => source_ip,
+            None => return Ok(None),
+        };
+        match self.country(source_ip) {
+            Some(country) => self.check_policy(&country, source_ip, data).await,
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CountryPolicy, GeoPolicies};
+
+    #[test]
+    fn test_parse_policies() {
+        let policies: GeoPolicies = serde_yaml::from_str(
+            "countries:\n  xx: block\n  YY: captcha\n  ZZ:\n    max_requests_per_ip_per_day: 2\n",
+        )
+        .unwrap();
+        let policies = policies.normalized();
+        assert_eq!(policies.countries["XX"], CountryPolicy::Block);
+        assert_eq!(policies.countries["YY"], CountryPolicy::Captcha);
+        assert_eq!(
+            policies.countries["ZZ"],
+            CountryPolicy::MaxRequestsPerIpPerDay(2)
+        );
+        assert!(policies.requires_captcha());
+        assert!(serde_yaml::from_str::<GeoPolicies>("countries:\n  XX: allow\n").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{
+    CaptchaVerifier, Checker, CheckerData, LimitSchedule, RejectionReason, RejectionReasonCode,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use futures::lock::Mutex;
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+
+/// Limits how many requests each client IP can make per day. Days start at midnight in the
+/// timezone of the schedule, which may also raise or lower the limit at given times.
+pub struct IpRateLimitChecker {
+    max_requests_per_day: u64,
+    schedule: LimitSchedule,
+    /// Number of requests per IP for the current day.
+    usage: Mutex<(Option<NaiveDate>, HashMap<IpAddr, u64>)>,
+    /// Lets clients over the limit through if they solve a captcha.
+    captcha: Option<Arc<CaptchaVerifier>>,
+}
+
+impl IpRateLimitChecker {
+    pub fn new(max_requests_per_day: u64, schedule: LimitSchedule) -> Self {
+        Self {
+            max_requests_per_day,
+            schedule,
+            usage: Mutex::new((None, HashMap::new())),
+            captcha: None,
+        }
+    }
+
+    pub fn with_captcha(mut self, captcha: Arc<CaptchaVerifier>) -> Self {
+        self.captcha = Some(captcha);
+        self
+    }
+}
+
+#[async_trait]
+impl Checker for IpRateLimitChecker {
+    fn name(&self) -> &'static str {
+        "ip_ratelimit"
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        // Without an address, there is nothing to attribute the request to.
+        let source_ip = match data.source_ip {
+            Some(source_ip) => source_ip,
+            None => return Ok(None),
+        };
+        let limit = self.schedule.apply(self.max_requests_per_day, &data.time);
+        let today = self.schedule.local_time(&data.time).naive_local().date();
+
+        let mut usage = self.usage.lock().await;
+        let (day, counts) = &mut *usage;
+        if *day != Some(today) {
+            *day = Some(today);
+            counts.clear();
+        }
+        let count = *counts.get(&source_ip).unwrap_or(&0);
+        if count >= limit {
+            // Not holding the lock while the captcha is verified.
+            drop(usage);
+            if let Some(captcha) = &self.captcha {
+                if captcha.verify(data).await? {
+                    return Ok(None);
+                }
+            }
+            // Usage is reset at the next midnight.
+            let local_time = self.schedule.local_time(&data.time).naive_local();
+            let retry_after = today.succ().and_hms(0, 0, 0) - local_time;
+            let mut rejection = RejectionReason::new(
+                RejectionReasonCode::UsageLimitExhausted,
+                format!(
+                    "IP {} has exceeded the daily limit of {} requests",
+                    source_ip, limit
+                ),
+            )
+            .with_limit(limit)
+            .with_retry_after_secs(retry_after.num_seconds().max(0) as u64);
+            if let Some(captcha) = &self.captcha {
+                rejection = rejection.with_challenge(captcha.challenge());
+            }
+            return Ok(Some(rejection));
+        }
+        counts.insert(source_ip, count + 1);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IpRateLimitChecker;
+    use crate::checkers::{Checker, CheckerData, LimitSchedule, RejectionReasonCode};
+    use chrono::{TimeZone, Utc};
+
+    async fn allowed(checker: &IpRateLimitChecker, data: &CheckerData) -> bool {
+        match checker.check(data).await.unwrap() {
+            None => true,
+            Some(rejection) => {
+                assert_eq!(rejection.code, RejectionReasonCode::UsageLimitExhausted);
+                false
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_ratelimit() {
+        let schedule: LimitSchedule =
+            serde_yaml::from_str("windows:\n  - days: [Sat, Sun]\n    multiplier: 2.0\n").unwrap();
+        let checker = IpRateLimitChecker::new(2, schedule);
+
+        // Friday.
+        let friday = CheckerData::for_test()
+            .ip("1.1.1.1")
+            .time(Utc.ymd(2023, 3, 3).and_hms(10, 0, 0));
+        assert!(allowed(&checker, &friday).await);
+        assert!(allowed(&checker, &friday).await);
+        assert!(!allowed(&checker, &friday).await);
+        assert!(allowed(&checker, &friday.clone().ip("2.2.2.2")).await);
+
+        // The weekend starts a new day with a larger quota.
+        let saturday = CheckerData::for_test()
+            .ip("1.1.1.1")
+            .time(Utc.ymd(2023, 3, 4).and_hms(10, 0, 0));
+        for _ in 0..4 {
+            assert!(allowed(&checker, &saturday).await);
+        }
+        assert!(!allowed(&checker, &saturday).await);
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Checkers look at an incoming mint request before the faucet does any work for it, and may
+//! reject it, e.g. because the client has used up its quota.
+
+mod allowlist;
+mod captcha;
+mod cooldown;
+mod eligibility;
+mod geo;
+mod ip_ratelimit;
+mod pow;
+mod reputation;
+mod schedule;
+mod scheduler;
+mod shadow_ban;
+mod timeout;
+mod velocity;
+
+use crate::errors::accepts_json;
+pub use allowlist::ReceiverAllowlistChecker;
+use anyhow::Result;
+use aptos_sdk::types::account_address::AccountAddress;
+use async_trait::async_trait;
+pub use captcha::{CaptchaVerifier, CAPTCHA_TOKEN_HEADER};
+use chrono::{DateTime, Utc};
+pub use cooldown::{CooldownConfig, ReceiverCooldownChecker};
+pub use eligibility::OnChainEligibilityChecker;
+pub use geo::{CountryPolicy, GeoPolicies, GeoPolicyChecker};
+pub use ip_ratelimit::IpRateLimitChecker;
+pub use pow::{PowConfig, ProofOfWorkChecker, POW_SOLUTION_HEADER};
+pub use reputation::SharedReputationChecker;
+use reqwest::StatusCode;
+pub use schedule::{LimitSchedule, LimitWindow};
+pub use scheduler::CheckerScheduler;
+use serde::Serialize;
+pub use shadow_ban::ShadowBanChecker;
+use std::{fmt, net::IpAddr};
+pub use timeout::{run_checker, CheckerTimeout, CheckerTimeoutPolicy};
+pub use velocity::{VelocityChecker, VelocityConfig};
+use warp::{
+    http::{header::RETRY_AFTER, HeaderMap},
+    Reply,
+};
+
+/// Everything a checker gets to know about a request.
+#[derive(Clone, Debug)]
+pub struct CheckerData {
+    pub receiver: AccountAddress,
+    pub amount: u64,
+    /// The client address, see `client_ip::extract_client_ip`.
+    pub source_ip: Option<IpAddr>,
+    pub headers: HeaderMap,
+    /// When the request arrived.
+    pub time: DateTime<Utc>,
+}
+
+/// Builds the data of the requests checked in the tests of the checkers.
+#[cfg(test)]
+impl CheckerData {
+    /// A request for 1 to `AccountAddress::ONE`, without headers or client address, arriving now.
+    pub fn for_test() -> Self {
+        Self {
+            receiver: AccountAddress::ONE,
+            amount: 1,
+            source_ip: None,
+            headers: HeaderMap::new(),
+            time: Utc::now(),
+        }
+    }
+
+    pub fn receiver(mut self, receiver: AccountAddress) -> Self {
+        self.receiver = receiver;
+        self
+    }
+
+    pub fn amount(mut self, amount: u64) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn ip(mut self, ip: &str) -> Self {
+        self.source_ip = Some(ip.parse().unwrap());
+        self
+    }
+
+    pub fn time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Arriving `secs` after the epoch.
+    pub fn secs(self, secs: i64) -> Self {
+        use chrono::TimeZone;
+        self.time(Utc.timestamp(secs, 0))
+    }
+
+    pub fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.insert(name, value.parse().unwrap());
+        self
+    }
+}
+
+/// Stable, machine readable code of a rejection, for clients to render their own messages.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReasonCode {
+    /// The client has made too many requests.
+    UsageLimitExhausted,
+    /// Requests from the network or autonomous system of the client spiked unusually.
+    AnomalousVelocity,
+    /// The receiver isn't in the allowlist, or not eligible on chain.
+    ReceiverNotAllowed,
+    /// The receiver was funded too recently.
+    ReceiverCoolingDown,
+    /// The request must carry a solved proof of work challenge.
+    ProofOfWorkRequired,
+    /// Requests from the country of the client are not served.
+    CountryBlocked,
+    /// Requests from the country of the client must carry the token of a solved captcha.
+    CaptchaRequired,
+    /// The client is shadow banned. The request is answered as if it was accepted, but nothing
+    /// is funded.
+    ShadowBanned,
+    /// The amount is funded from the treasury, and the request must carry its approval token.
+    ApprovalRequired,
+    /// A checker didn't answer in time, see `CheckerTimeout`.
+    CheckerUnavailable,
+}
+
+impl RejectionReasonCode {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RejectionReasonCode::UsageLimitExhausted
+            | RejectionReasonCode::AnomalousVelocity
+            | RejectionReasonCode::ReceiverCoolingDown => StatusCode::TOO_MANY_REQUESTS,
+            RejectionReasonCode::ReceiverNotAllowed
+            | RejectionReasonCode::ProofOfWorkRequired
+            | RejectionReasonCode::CountryBlocked
+            | RejectionReasonCode::CaptchaRequired
+            | RejectionReasonCode::ApprovalRequired => StatusCode::FORBIDDEN,
+            RejectionReasonCode::CheckerUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            // What the client sees.
+            RejectionReasonCode::ShadowBanned => StatusCode::OK,
+        }
+    }
+
+    /// Whether the same request may be accepted later, after `retry_after_secs` if set. Requests
+    /// rejected with a challenge must be sent again with its proof instead.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RejectionReasonCode::UsageLimitExhausted
+            | RejectionReasonCode::AnomalousVelocity
+            | RejectionReasonCode::ReceiverCoolingDown
+            | RejectionReasonCode::CheckerUnavailable => true,
+            RejectionReasonCode::ReceiverNotAllowed
+            | RejectionReasonCode::ProofOfWorkRequired
+            | RejectionReasonCode::CountryBlocked
+            | RejectionReasonCode::CaptchaRequired
+            | RejectionReasonCode::ApprovalRequired
+            | RejectionReasonCode::ShadowBanned => false,
+        }
+    }
+}
+
+/// Why a request was rejected. Besides the message in English, this carries what clients need to
+/// render a localized, actionable message of their own.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct RejectionReason {
+    pub code: RejectionReasonCode,
+    pub reason: String,
+    /// Name of the checker that rejected the request, set by `Service::run_checkers`.
+    pub checker: &'static str,
+    /// Whether the same request may be accepted later, see `RejectionReasonCode::is_retryable`.
+    pub retryable: bool,
+    /// The limit that was reached, e.g. the number of requests per day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// How long until the request would be accepted again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    /// What the client can do to get the request accepted right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<Challenge>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeKind {
+    Captcha,
+    ProofOfWork,
+}
+
+/// A challenge whose proof, e.g. the token of a solved captcha, is sent in the `token_header`
+/// header when retrying the request.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Challenge {
+    pub kind: ChallengeKind,
+    /// Where to complete a captcha.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Seed of a proof of work, see `ProofOfWorkChecker`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+    /// Leading zero bits required of the hash of a proof of work.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+    pub token_header: &'static str,
+}
+
+impl RejectionReason {
+    pub fn new(code: RejectionReasonCode, reason: String) -> Self {
+        Self {
+            code,
+            reason,
+            checker: "",
+            retryable: code.is_retryable(),
+            limit: None,
+            retry_after_secs: None,
+            challenge: None,
+        }
+    }
+
+    pub fn with_checker(mut self, checker: &'static str) -> Self {
+        self.checker = checker;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_retry_after_secs(mut self, retry_after_secs: u64) -> Self {
+        self.retry_after_secs = Some(retry_after_secs);
+        self
+    }
+
+    pub fn with_challenge(mut self, challenge: Challenge) -> Self {
+        self.challenge = Some(challenge);
+        self
+    }
+}
+
+/// Body of the reply to a rejected request, when the client accepts JSON.
+#[derive(Debug, Serialize)]
+struct RejectionsBody<'a> {
+    rejections: &'a [RejectionReason],
+}
+
+/// Replies to a rejected request. Clients asking for JSON, with `Accept: application/json`, get
+/// the rejections in full, others get the messages. The status is that of the first rejection,
+/// and `Retry-After` is set to the longest wait, if any.
+pub fn rejection_reply(rejections: &[RejectionReason], headers: &HeaderMap) -> Box<dyn Reply> {
+    let status = rejections[0].code.status_code();
+    let reply: Box<dyn Reply> = if accepts_json(headers) {
+        Box::new(warp::reply::with_status(
+            warp::reply::json(&RejectionsBody { rejections }),
+            status,
+        ))
+    } else {
+        let reasons: Vec<String> = rejections.iter().map(|r| r.to_string()).collect();
+        Box::new(warp::reply::with_status(reasons.join("; "), status))
+    };
+    match rejections.iter().filter_map(|r| r.retry_after_secs).max() {
+        Some(retry_after_secs) => Box::new(warp::reply::with_header(
+            reply,
+            RETRY_AFTER,
+            retry_after_secs.to_string(),
+        )),
+        None => reply,
+    }
+}
+
+/// Whether the request must look accepted, i.e. one of the rejections is a shadow ban. This takes
+/// precedence over other rejections, which would otherwise tell the client apart.
+pub fn is_shadow_banned(rejections: &[RejectionReason]) -> bool {
+    rejections
+        .iter()
+        .any(|rejection| rejection.code == RejectionReasonCode::ShadowBanned)
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+#[async_trait]
+pub trait Checker: Send + Sync {
+    /// Name used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Rough latency of a check in microseconds, e.g. 10 for an in memory lookup and 10s of
+    /// thousands for a remote call. Stands for the latency of the checker until measured, see
+    /// `CheckerScheduler`.
+    fn cost(&self) -> u64 {
+        10
+    }
+
+    /// Names of the checkers that must run before this one, when configured.
+    fn depends_on(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether the rejections of this checker take precedence over the others, such that it
+    /// must run before any of them may stop the evaluation.
+    fn takes_precedence(&self) -> bool {
+        false
+    }
+
+    /// Returns `Some` if the request must be rejected. Errors are reported to the client as
+    /// internal errors, they are not a verdict on the request.
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>>;
+
+    /// Called once a request that passed the checkers was funded, for checkers keeping track of
+    /// fundings rather than of requests, see `Service::record_funding`.
+    async fn record_funding(&self, _receiver: AccountAddress, _time: DateTime<Utc>) -> Result<()> {
+        Ok(())
+    }
+}
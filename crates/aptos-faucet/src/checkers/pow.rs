@@ -0,0 +1,305 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Challenge, ChallengeKind, Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use anyhow::Result;
+use aptos_crypto::HashValue;
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use ipnet::IpNet;
+use rand::RngCore;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+};
+
+/// Header carrying the solution of a proof of work, as `<seed>:<nonce>`.
+pub const POW_SOLUTION_HEADER: &str = "X-Aptos-Faucet-Pow-Solution";
+
+/// Bound on the number of tracked networks and spent seeds, beyond which old ones are dropped.
+const MAX_TRACKED: usize = 100_000;
+
+#[derive(Clone, Debug)]
+pub struct PowConfig {
+    /// Leading zero bits required of the hash of a solution, for a network without recent
+    /// requests.
+    pub base_difficulty: u8,
+    /// Cap on the difficulty.
+    pub max_difficulty: u8,
+    /// The difficulty goes up a bit at this many recent requests from the network of the client,
+    /// and another one every time they double.
+    pub requests_per_extra_bit: u64,
+    /// Requests are counted over this many seconds.
+    pub window_secs: u64,
+    /// A challenge must be solved within this many seconds.
+    pub challenge_ttl_secs: u64,
+    /// Requests are counted per network of this size, e.g. /24.
+    pub ipv4_prefix_len: u8,
+    pub ipv6_prefix_len: u8,
+}
+
+impl Default for PowConfig {
+    fn default() -> Self {
+        Self {
+            base_difficulty: 16,
+            max_difficulty: 24,
+            requests_per_extra_bit: 10,
+            window_secs: 600,
+            challenge_ttl_secs: 300,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 48,
+        }
+    }
+}
+
+impl PowConfig {
+    /// Difficulty for a network with that many recent requests.
+    pub fn difficulty(&self, recent_requests: u64) -> u8 {
+        let doublings =
+            64 - (1 + recent_requests / self.requests_per_extra_bit.max(1)).leading_zeros() - 1;
+        (self.base_difficulty as u32 + doublings).min(self.max_difficulty as u32) as u8
+    }
+}
+
+/// Makes clients spend some CPU on each request, which is cheap for a person but adds up for
+/// automated farming, without requiring accounts or captchas.
+///
+/// A request without a solution is rejected with a challenge: a seed, which encodes when it was
+/// issued and its difficulty and is authenticated for the network of the client, and the number
+/// of leading zero bits the SHA3-256 hash of `<seed>:<nonce>` must have. The difficulty grows with
+/// the recent requests from the network. Each seed is good for a single request.
+pub struct ProofOfWorkChecker {
+    config: PowConfig,
+    /// Authenticates seeds, to be shared by replicas for one to accept seeds issued by another.
+    secret: Vec<u8>,
+    /// Times of the recent accepted requests per network.
+    recent: Mutex<HashMap<IpNet, VecDeque<u64>>>,
+    /// Seeds already used, with when.
+    spent: Mutex<HashMap<String, u64>>,
+}
+
+impl ProofOfWorkChecker {
+    /// Seeds are authenticated with the given secret, or one of its own otherwise.
+    pub fn new(config: PowConfig, secret: Option<&str>) -> Self {
+        let secret = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut secret = vec![0; 32];
+                rand::rngs::OsRng.fill_bytes(&mut secret);
+                secret
+            },
+        };
+        Self {
+            config,
+            secret,
+            recent: Mutex::new(HashMap::new()),
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn network(&self, ip: IpAddr) -> Result<IpNet> {
+        let prefix_len = match ip {
+            IpAddr::V4(_) => self.config.ipv4_prefix_len,
+            IpAddr::V6(_) => self.config.ipv6_prefix_len,
+        };
+        Ok(IpNet::new(ip, prefix_len)?.trunc())
+    }
+
+    fn seed(&self, network: &IpNet, issued_secs: u64, difficulty: u8) -> String {
+        let mac = HashValue::sha3_256_of(
+            &[
+                self.secret.as_slice(),
+                format!("{}.{}.{}", network, issued_secs, difficulty).as_bytes(),
+            ]
+            .concat(),
+        );
+        format!("{}.{}.{}", issued_secs, difficulty, &mac.to_hex()[..32])
+    }
+
+    /// Whether the solution is valid for the network at that time, without checking whether it
+    /// was used before.
+    fn verify(&self, solution: &str, network: &IpNet, now_secs: u64) -> Option<String> {
+        let (seed, _nonce) = solution.rsplit_once(':')?;
+        let mut parts = seed.splitn(3, '.');
+        let issued_secs: u64 = parts.next()?.parse().ok()?;
+        let difficulty: u8 = parts.next()?.parse().ok()?;
+        if seed != self.seed(network, issued_secs, difficulty)
+            || issued_secs + self.config.challenge_ttl_secs < now_secs
+            || leading_zero_bits(&HashValue::sha3_256_of(solution.as_bytes())) < difficulty as u32
+        {
+            return None;
+        }
+        Some(seed.to_string())
+    }
+}
+
+fn leading_zero_bits(hash: &HashValue) -> u32 {
+    let mut bits = 0;
+    for byte in hash.iter() {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[async_trait]
+impl Checker for ProofOfWorkChecker {
+    fn name(&self) -> &'static str {
+        "proof_of_work"
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        // Without an address, there is nothing to authenticate seeds for.
+        let network = match data.source_ip {
+            Some(source_ip) => self.network(source_ip)?,
+            None => return Ok(None),
+        };
+        let now_secs = data.time.timestamp().max(0) as u64;
+
+        let seed = data
+            .headers
+            .get(POW_SOLUTION_HEADER)
+            .and_then(|solution| solution.to_str().ok())
+            .and_then(|solution| self.verify(solution, &network, now_secs));
+        if let Some(seed) = seed {
+            let mut spent = self.spent.lock().await;
+            if spent.len() > MAX_TRACKED {
+                let ttl_secs = self.config.challenge_ttl_secs;
+                spent.retain(|_, used_secs| *used_secs + ttl_secs >= now_secs);
+            }
+            if spent.insert(seed, now_secs).is_none() {
+                let mut recent = self.recent.lock().await;
+                if recent.len() > MAX_TRACKED {
+                    let window_secs = self.config.window_secs;
+                    recent.retain(|_, times| {
+                        times.back().map_or(false, |t| t + window_secs > now_secs)
+                    });
+                }
+                recent.entry(network).or_default().push_back(now_secs);
+                return Ok(None);
+            }
+        }
+
+        let recent_requests = match self.recent.lock().await.get_mut(&network) {
+            Some(times) => {
+                while times
+                    .front()
+                    .map_or(false, |t| t + self.config.window_secs <= now_secs)
+                {
+                    times.pop_front();
+                }
+                times.len() as u64
+            },
+            None => 0,
+        };
+        let difficulty = self.config.difficulty(recent_requests);
+        Ok(Some(
+            RejectionReason::new(
+                RejectionReasonCode::ProofOfWorkRequired,
+                format!(
+                    "Solve the proof of work challenge, with {} leading zero bits, and retry",
+                    difficulty
+                ),
+            )
+            .with_challenge(Challenge {
+                kind: ChallengeKind::ProofOfWork,
+                url: None,
+                seed: Some(self.seed(&network, now_secs, difficulty)),
+                difficulty: Some(difficulty),
+                token_header: POW_SOLUTION_HEADER,
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leading_zero_bits, PowConfig, ProofOfWorkChecker, POW_SOLUTION_HEADER};
+    use crate::checkers::{Checker, CheckerData, RejectionReasonCode};
+    use aptos_crypto::HashValue;
+
+    /// Gets a challenge, and solves it.
+    async fn solve(checker: &ProofOfWorkChecker, data: &CheckerData) -> (String, u8) {
+        let rejection = checker.check(data).await.unwrap().unwrap();
+        assert_eq!(rejection.code, RejectionReasonCode::ProofOfWorkRequired);
+        let challenge = rejection.challenge.unwrap();
+        let (seed, difficulty) = (challenge.seed.unwrap(), challenge.difficulty.unwrap());
+        let solution = (0u64..)
+            .map(|nonce| format!("{}:{}", seed, nonce))
+            .find(|solution| {
+                leading_zero_bits(&HashValue::sha3_256_of(solution.as_bytes())) >= difficulty as u32
+            })
+            .unwrap();
+        (solution, difficulty)
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let config = PowConfig::default();
+        assert_eq!(config.difficulty(0), 16);
+        assert_eq!(config.difficulty(9), 16);
+        assert_eq!(config.difficulty(10), 17);
+        assert_eq!(config.difficulty(30), 18);
+        assert_eq!(config.difficulty(1_000_000), 24);
+    }
+
+    #[tokio::test]
+    async fn test_proof_of_work() {
+        let checker = ProofOfWorkChecker::new(
+            PowConfig {
+                base_difficulty: 4,
+                requests_per_extra_bit: 1,
+                ..PowConfig::default()
+            },
+            Some("secret"),
+        );
+
+        let data = |ip: &str, secs| CheckerData::for_test().ip(ip).secs(secs);
+
+        let (solution, difficulty) = solve(&checker, &data("1.1.1.1", 0)).await;
+        assert_eq!(difficulty, 4);
+        // Not from another network, nor too late.
+        assert!(checker
+            .check(&data("2.2.2.2", 10).header(POW_SOLUTION_HEADER, &solution))
+            .await
+            .unwrap()
+            .is_some());
+        assert!(checker
+            .check(&data("1.1.1.1", 1000).header(POW_SOLUTION_HEADER, &solution))
+            .await
+            .unwrap()
+            .is_some());
+        // Accepted once, from anywhere in the network.
+        assert!(checker
+            .check(&data("1.1.1.2", 10).header(POW_SOLUTION_HEADER, &solution))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(checker
+            .check(&data("1.1.1.1", 10).header(POW_SOLUTION_HEADER, &solution))
+            .await
+            .unwrap()
+            .is_some());
+
+        // The network made a request, the next challenge is harder.
+        let (solution, difficulty) = solve(&checker, &data("1.1.1.1", 20)).await;
+        assert_eq!(difficulty, 5);
+        assert!(checker
+            .check(&data("1.1.1.1", 30).header(POW_SOLUTION_HEADER, &solution))
+            .await
+            .unwrap()
+            .is_none());
+        // Forging an easier challenge doesn't work.
+        let forged = solution.replacen(".5.", ".4.", 1);
+        assert!(checker
+            .check(&data("1.1.1.1", 30).header(POW_SOLUTION_HEADER, &forged))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use crate::reputation::{AbuseSignal, IpReputation};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Treats clients from the IP prefixes flagged by other deployments as those deployments do:
+/// shadow banned if they were, and rejected as anomalous otherwise, until the prefixes expire.
+pub struct SharedReputationChecker {
+    reputation: Arc<IpReputation>,
+}
+
+impl SharedReputationChecker {
+    pub fn new(reputation: Arc<IpReputation>) -> Self {
+        Self { reputation }
+    }
+}
+
+#[async_trait]
+impl Checker for SharedReputationChecker {
+    fn name(&self) -> &'static str {
+        "shared_reputation"
+    }
+
+    /// May shadow ban, which must come before any rejection.
+    fn takes_precedence(&self) -> bool {
+        true
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let source_ip = match data.source_ip {
+            Some(source_ip) => source_ip,
+            None => return Ok(None),
+        };
+        let now_secs = data.time.timestamp().max(0) as u64;
+        Ok(self.reputation.lookup(source_ip, now_secs).map(|flagged| {
+            let deployment = flagged.deployment.as_deref().unwrap_or("another faucet");
+            match flagged.signal {
+                AbuseSignal::ShadowBanned => RejectionReason::new(
+                    RejectionReasonCode::ShadowBanned,
+                    format!(
+                        "IP {} is shadow banned by {} ({})",
+                        source_ip, deployment, flagged.prefix
+                    ),
+                ),
+                AbuseSignal::AnomalousVelocity => RejectionReason::new(
+                    RejectionReasonCode::AnomalousVelocity,
+                    format!(
+                        "Unusually many requests from network {}, try again later",
+                        flagged.prefix
+                    ),
+                )
+                .with_retry_after_secs(
+                    (flagged.last_seen_secs + self.reputation.ttl_secs()).saturating_sub(now_secs),
+                ),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedReputationChecker;
+    use crate::{
+        checkers::{Checker, CheckerData, RejectionReasonCode},
+        reputation::{IpReputation, ReputationConfig},
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_shared_reputation() {
+        let reputation = Arc::new(IpReputation::new(ReputationConfig {
+            deployment: "devnet".to_string(),
+            sync_interval_secs: 60,
+            ttl_secs: 3600,
+        }));
+        reputation.import(
+            serde_json::from_str(
+                r#"{"prefixes":[
+                    {"prefix":"192.0.2.0/24","signal":"shadow_banned","last_seen_secs":0,"deployment":"testnet"},
+                    {"prefix":"198.51.100.0/24","signal":"anomalous_velocity","last_seen_secs":0,"deployment":"testnet"}
+                ]}"#,
+            )
+            .unwrap(),
+            0,
+        );
+        let checker = SharedReputationChecker::new(reputation);
+        let check = |ip: &str| {
+            let data = CheckerData::for_test().ip(ip).secs(600);
+            let checker = &checker;
+            async move { checker.check(&data).await.unwrap() }
+        };
+
+        let rejection = check("192.0.2.1").await.unwrap();
+        assert_eq!(rejection.code, RejectionReasonCode::ShadowBanned);
+        let rejection = check("198.51.100.1").await.unwrap();
+        assert_eq!(rejection.code, RejectionReasonCode::AnomalousVelocity);
+        assert_eq!(rejection.retry_after_secs, Some(3000));
+        assert!(check("203.0.113.1").await.is_none());
+    }
+}
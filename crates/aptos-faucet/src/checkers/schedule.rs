@@ -0,0 +1,187 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Scheduled modifiers for the limits of the checkers, so that e.g. a hackathon weekend can get
+//! a higher quota without redeploying the faucet. A schedule is read from a YAML file like:
+//!
+//! ```yaml
+//! utc_offset_minutes: -420
+//! windows:
+//!   - name: hackathon
+//!     start_date: 2023-03-04
+//!     end_date: 2023-03-05
+//!     days: [Sat, Sun]
+//!     multiplier: 5.0
+//!   - name: quiet nights
+//!     start_time: "22:00:00"
+//!     end_time: "06:00:00"
+//!     multiplier: 0.5
+//! ```
+
+use anyhow::{ensure, Context, Result};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, Utc, Weekday};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitSchedule {
+    /// Offset from UTC of the timezone in which the windows and days are expressed. Daylight
+    /// saving time is not accounted for.
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    #[serde(default)]
+    pub windows: Vec<LimitWindow>,
+}
+
+/// A window during which the limits are multiplied. Unset fields don't restrict the window.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitWindow {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// First day of the window, inclusive.
+    #[serde(default)]
+    pub start_date: Option<NaiveDate>,
+    /// Last day of the window, inclusive.
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+    /// Days of the week the window applies to.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Time of the day the window opens, inclusive.
+    #[serde(default)]
+    pub start_time: Option<NaiveTime>,
+    /// Time of the day the window closes, exclusive. If earlier than `start_time`, the window
+    /// spans midnight.
+    #[serde(default)]
+    pub end_time: Option<NaiveTime>,
+    pub multiplier: f64,
+}
+
+impl LimitWindow {
+    fn contains(&self, time: &DateTime<FixedOffset>) -> bool {
+        let date = time.naive_local().date();
+        if self.start_date.map_or(false, |start| date < start)
+            || self.end_date.map_or(false, |end| date > end)
+        {
+            return false;
+        }
+        if !self.days.is_empty() && !self.days.contains(&time.weekday()) {
+            return false;
+        }
+        let t = time.time();
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end < start => t >= start || t < end,
+            (start, end) => {
+                start.map_or(true, |start| t >= start) && end.map_or(true, |end| t < end)
+            },
+        }
+    }
+}
+
+impl LimitSchedule {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read limit schedule {}", path.display()))?;
+        let schedule: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse limit schedule {}", path.display()))?;
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.utc_offset_minutes.abs() < 24 * 60,
+            "utc_offset_minutes must be within a day, got {}",
+            self.utc_offset_minutes
+        );
+        for window in &self.windows {
+            ensure!(
+                window.multiplier.is_finite() && window.multiplier >= 0.0,
+                "Invalid multiplier {} for limit window {:?}",
+                window.multiplier,
+                window.name
+            );
+        }
+        Ok(())
+    }
+
+    fn offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_minutes * 60).unwrap_or_else(|| FixedOffset::east(0))
+    }
+
+    /// The time in the timezone of the schedule.
+    pub fn local_time(&self, time: &DateTime<Utc>) -> DateTime<FixedOffset> {
+        time.with_timezone(&self.offset())
+    }
+
+    /// The multiplier in effect at `time`: the largest one of the windows containing it, or 1.
+    pub fn multiplier(&self, time: &DateTime<Utc>) -> f64 {
+        let local = self.local_time(time);
+        self.windows
+            .iter()
+            .filter(|window| window.contains(&local))
+            .map(|window| window.multiplier)
+            .reduce(f64::max)
+            .unwrap_or(1.0)
+    }
+
+    /// Applies the multiplier in effect at `time` to `limit`.
+    pub fn apply(&self, limit: u64, time: &DateTime<Utc>) -> u64 {
+        (limit as f64 * self.multiplier(time)).floor() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LimitSchedule;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        Utc.datetime_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_multiplier() {
+        let schedule: LimitSchedule = serde_yaml::from_str(
+            r#"
+utc_offset_minutes: -420
+windows:
+  - name: hackathon
+    start_date: 2023-03-04
+    end_date: 2023-03-05
+    days: [Sat, Sun]
+    multiplier: 5.0
+  - start_time: "22:00:00"
+    end_time: "06:00:00"
+    multiplier: 0.5
+"#,
+        )
+        .unwrap();
+        schedule.validate().unwrap();
+
+        // Friday noon, local time.
+        assert_eq!(schedule.apply(10, &utc("2023-03-03 19:00:00")), 10);
+        // Friday 23:00 local time is already Saturday in UTC, but not locally.
+        assert_eq!(schedule.apply(10, &utc("2023-03-04 06:00:00")), 5);
+        // Saturday 00:30 local time, both windows apply, the largest wins.
+        assert_eq!(schedule.apply(10, &utc("2023-03-04 07:30:00")), 50);
+        // The following weekend is not part of the hackathon.
+        assert_eq!(schedule.apply(10, &utc("2023-03-11 19:00:00")), 10);
+    }
+
+    #[test]
+    fn test_validate() {
+        let schedule: LimitSchedule =
+            serde_yaml::from_str("windows:\n  - multiplier: -1.0\n").unwrap();
+        assert!(schedule.validate().is_err());
+        assert!(serde_yaml::from_str::<LimitSchedule>(
+            "windows:\n  - days: [Xyz]\n    multiplier: 1.0\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Order checkers are run in. Once a request is rejected, running the remaining checkers only
+//! adds to its latency, so checkers are run by increasing expected cost of finding a rejection:
+//! their latency over the share of the requests they reject, both measured as requests come, and
+//! evaluation stops at the first rejection. Until a checker is measured, its declared
+//! `Checker::cost` stands for its latency.
+//!
+//! Checkers whose rejections take precedence, i.e. shadow bans, always run first, and checkers
+//! run after those they declare to depend on, wherever they would rank otherwise.
+
+use super::Checker;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Weight of each new measure in the moving averages.
+const ALPHA: f64 = 0.05;
+/// Share of the requests a checker is assumed to reject until measured.
+const PRIOR_REJECTION_RATE: f64 = 0.5;
+/// Floor of the rejection rates, for checkers which never reject to still be ordered by latency.
+const MIN_REJECTION_RATE: f64 = 0.001;
+
+#[derive(Clone, Copy, Debug)]
+struct CheckerStats {
+    latency_us: f64,
+    rejection_rate: f64,
+}
+
+impl CheckerStats {
+    fn new(cost_us: u64) -> Self {
+        Self {
+            latency_us: cost_us as f64,
+            rejection_rate: PRIOR_REJECTION_RATE,
+        }
+    }
+
+    /// Expected time spent per rejection found.
+    fn score(&self) -> f64 {
+        self.latency_us / self.rejection_rate.max(MIN_REJECTION_RATE)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CheckerScheduler {
+    stats: Mutex<HashMap<&'static str, CheckerStats>>,
+}
+
+impl CheckerScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `checker` took `latency` to check a request, and whether it rejected it.
+    pub fn record(&self, checker: &dyn Checker, latency: Duration, rejected: bool) {
+        let mut stats = self.stats.lock().unwrap();
+        let stats = stats
+            .entry(checker.name())
+            .or_insert_with(|| CheckerStats::new(checker.cost()));
+        stats.latency_us += ALPHA * (latency.as_micros() as f64 - stats.latency_us);
+        stats.rejection_rate += ALPHA * (if rejected { 1.0 } else { 0.0 } - stats.rejection_rate);
+    }
+
+    /// Orders the checkers to run them in: cheapest per rejection first, respecting precedence
+    /// and dependencies. Checkers in a dependency cycle keep their configured order, last.
+    pub fn order(&self, checkers: &[Arc<dyn Checker>]) -> Vec<Arc<dyn Checker>> {
+        let scores: Vec<f64> = {
+            let stats = self.stats.lock().unwrap();
+            checkers
+                .iter()
+                .map(|checker| {
+                    stats
+                        .get(checker.name())
+                        .copied()
+                        .unwrap_or_else(|| CheckerStats::new(checker.cost()))
+                        .score()
+                })
+                .collect()
+        };
+        let mut by_rank: Vec<usize> = (0..checkers.len()).collect();
+        by_rank.sort_by(|a, b| {
+            checkers[*b]
+                .takes_precedence()
+                .cmp(&checkers[*a].takes_precedence())
+                .then(scores[*a].total_cmp(&scores[*b]))
+        });
+
+        // Dependencies which aren't configured are ignored.
+        let names: HashSet<&str> = checkers.iter().map(|checker| checker.name()).collect();
+        let mut placed: HashSet<&str> = HashSet::new();
+        let mut ordered = Vec::with_capacity(checkers.len());
+        while !by_rank.is_empty() {
+            let ready = by_rank.iter().position(|i| {
+                checkers[*i]
+                    .depends_on()
+                    .iter()
+                    .all(|dependency| !names.contains(dependency) || placed.contains(dependency))
+            });
+            let i = match ready {
+                Some(position) => by_rank.remove(position),
+                None => {
+                    by_rank.sort_unstable();
+                    ordered.extend(by_rank.drain(..).map(|i| checkers[i].clone()));
+                    break;
+                },
+            };
+            placed.insert(checkers[i].name());
+            ordered.push(checkers[i].clone());
+        }
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CheckerScheduler;
+    use crate::checkers::{Checker, CheckerData, RejectionReason};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::{sync::Arc, time::Duration};
+
+    struct FakeChecker {
+        name: &'static str,
+        cost: u64,
+        depends_on: &'static [&'static str],
+        takes_precedence: bool,
+    }
+
+    #[async_trait]
+    impl Checker for FakeChecker {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn cost(&self) -> u64 {
+            self.cost
+        }
+
+        fn depends_on(&self) -> &'static [&'static str] {
+            self.depends_on
+        }
+
+        fn takes_precedence(&self) -> bool {
+            self.takes_precedence
+        }
+
+        async fn check(&self, _data: &CheckerData) -> Result<Option<RejectionReason>> {
+            Ok(None)
+        }
+    }
+
+    fn checker(
+        name: &'static str,
+        cost: u64,
+        depends_on: &'static [&'static str],
+    ) -> Arc<dyn Checker> {
+        Arc::new(FakeChecker {
+            name,
+            cost,
+            depends_on,
+            takes_precedence: false,
+        })
+    }
+
+    fn names(checkers: &[Arc<dyn Checker>]) -> Vec<&'static str> {
+        checkers.iter().map(|checker| checker.name()).collect()
+    }
+
+    #[test]
+    fn test_order() {
+        let scheduler = CheckerScheduler::new();
+        let ban: Arc<dyn Checker> = Arc::new(FakeChecker {
+            name: "ban",
+            cost: 100,
+            depends_on: &[],
+            takes_precedence: true,
+        });
+        let checkers = vec![
+            checker("remote", 50_000, &[]),
+            checker("local", 10, &[]),
+            ban,
+            checker("after_local", 1, &["local"]),
+            checker("after_missing", 20, &["missing"]),
+        ];
+        // By declared cost until measured.
+        assert_eq!(names(&scheduler.order(&checkers)), vec![
+            "ban",
+            "local",
+            "after_local",
+            "after_missing",
+            "remote"
+        ]);
+
+        // A slow checker rejecting most requests is worth running before fast ones rejecting
+        // none.
+        for _ in 0..200 {
+            scheduler.record(checkers[0].as_ref(), Duration::from_micros(1_000), true);
+            scheduler.record(checkers[1].as_ref(), Duration::from_micros(10), false);
+            scheduler.record(checkers[4].as_ref(), Duration::from_micros(20), false);
+        }
+        assert_eq!(names(&scheduler.order(&checkers)), vec![
+            "ban",
+            "remote",
+            "local",
+            "after_local",
+            "after_missing"
+        ]);
+
+        // Cycles keep their configured order.
+        let checkers = vec![
+            checker("a", 10, &["b"]),
+            checker("b", 1, &["a"]),
+            checker("c", 100, &[]),
+        ];
+        assert_eq!(names(&scheduler.order(&checkers)), vec!["c", "a", "b"]);
+    }
+}
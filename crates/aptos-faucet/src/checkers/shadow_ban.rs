@@ -0,0 +1,70 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use crate::reputation::{AbuseSignal, IpReputation};
+use anyhow::Result;
+use async_trait::async_trait;
+use ipnet::IpNet;
+use std::sync::Arc;
+
+/// Shadow bans clients from the given IP ranges: their requests look accepted, but are never
+/// funded. Unlike a plain rejection, this gives abusers probing for what gets them rejected
+/// nothing to adapt to.
+pub struct ShadowBanChecker {
+    ranges: Vec<IpNet>,
+    /// Where the ranges are reported as they are hit, to share them with other deployments.
+    reputation: Option<Arc<IpReputation>>,
+}
+
+impl ShadowBanChecker {
+    pub fn new(ranges: Vec<IpNet>) -> Self {
+        Self {
+            ranges,
+            reputation: None,
+        }
+    }
+
+    pub fn with_reputation(mut self, reputation: Arc<IpReputation>) -> Self {
+        self.reputation = Some(reputation);
+        self
+    }
+}
+
+#[async_trait]
+impl Checker for ShadowBanChecker {
+    fn name(&self) -> &'static str {
+        "shadow_ban"
+    }
+
+    fn takes_precedence(&self) -> bool {
+        true
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let source_ip = match data.source_ip {
+            Some(source_ip) => source_ip,
+            None => return Ok(None),
+        };
+        Ok(self
+            .ranges
+            .iter()
+            .find(|range| range.contains(&source_ip))
+            .map(|range| {
+                if let Some(reputation) = &self.reputation {
+                    reputation.report(
+                        *range,
+                        AbuseSignal::ShadowBanned,
+                        data.time.timestamp().max(0) as u64,
+                    );
+                }
+                RejectionReason::new(
+                    RejectionReasonCode::ShadowBanned,
+                    format!("IP {} is shadow banned ({})", source_ip, range),
+                )
+            }))
+    }
+}
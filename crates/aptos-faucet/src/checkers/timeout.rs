@@ -0,0 +1,151 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use crate::metrics::CHECKER_TIMEOUTS;
+use anyhow::{bail, Result};
+use aptos_logger::warn;
+use std::{str::FromStr, time::Duration};
+
+/// What becomes of a request whose checker didn't answer in time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckerTimeoutPolicy {
+    /// The checker is skipped, as if it accepted the request.
+    Skip,
+    /// The request is rejected, for the client to retry later.
+    Reject,
+}
+
+impl CheckerTimeoutPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckerTimeoutPolicy::Skip => "skip",
+            CheckerTimeoutPolicy::Reject => "reject",
+        }
+    }
+}
+
+impl FromStr for CheckerTimeoutPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(CheckerTimeoutPolicy::Skip),
+            "reject" => Ok(CheckerTimeoutPolicy::Reject),
+            _ => bail!("Checker timeout policy must be skip or reject, got {}", s),
+        }
+    }
+}
+
+/// Bound on how long a checker may take, so that one waiting on a hung remote service, e.g. the
+/// store of a cooldown or an HTTP API, can't stall every request.
+#[derive(Clone, Copy, Debug)]
+pub struct CheckerTimeout {
+    pub timeout: Duration,
+    pub policy: CheckerTimeoutPolicy,
+}
+
+impl CheckerTimeout {
+    pub fn new(timeout: Duration, policy: CheckerTimeoutPolicy) -> Self {
+        Self { timeout, policy }
+    }
+}
+
+/// Runs the checker against the request, giving up on it after the timeout, if any. The check is
+/// then dropped, and the request skips the checker or is rejected, per the policy.
+pub async fn run_checker(
+    checker: &dyn Checker,
+    data: &CheckerData,
+    timeout: Option<&CheckerTimeout>,
+) -> Result<Option<RejectionReason>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return checker.check(data).await,
+    };
+    match tokio::time::timeout(timeout.timeout, checker.check(data)).await {
+        Ok(result) => result,
+        Err(_) => {
+            CHECKER_TIMEOUTS
+                .with_label_values(&[checker.name(), timeout.policy.as_str()])
+                .inc();
+            warn!(
+                checker = checker.name(),
+                receiver = data.receiver,
+                timeout_ms = timeout.timeout.as_millis() as u64,
+                policy = timeout.policy.as_str(),
+                "checker timed out"
+            );
+            Ok(match timeout.policy {
+                CheckerTimeoutPolicy::Skip => None,
+                CheckerTimeoutPolicy::Reject => Some(
+                    RejectionReason::new(
+                        RejectionReasonCode::CheckerUnavailable,
+                        "The request couldn't be checked in time, try again later".to_string(),
+                    )
+                    .with_retry_after_secs(timeout.timeout.as_secs().max(1)),
+                ),
+            })
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_checker, CheckerTimeout, CheckerTimeoutPolicy};
+    use crate::checkers::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    /// Rejects every request, after the delay.
+    struct SlowChecker(Duration);
+
+    #[async_trait]
+    impl Checker for SlowChecker {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn check(&self, _data: &CheckerData) -> Result<Option<RejectionReason>> {
+            tokio::time::sleep(self.0).await;
+            Ok(Some(RejectionReason::new(
+                RejectionReasonCode::ReceiverNotAllowed,
+                "Not allowed".to_string(),
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checker_timeout() {
+        let data = CheckerData::for_test();
+        let hung = SlowChecker(Duration::from_secs(3600));
+        let fast = SlowChecker(Duration::from_millis(1));
+        let skip = CheckerTimeout::new(Duration::from_millis(50), CheckerTimeoutPolicy::Skip);
+        let reject = CheckerTimeout::new(Duration::from_millis(50), CheckerTimeoutPolicy::Reject);
+
+        assert!(run_checker(&hung, &data, Some(&skip))
+            .await
+            .unwrap()
+            .is_none());
+        let rejection = run_checker(&hung, &data, Some(&reject))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rejection.code, RejectionReasonCode::CheckerUnavailable);
+        assert_eq!(rejection.retry_after_secs, Some(1));
+        // Answers in time are kept.
+        for timeout in [None, Some(&skip), Some(&reject)] {
+            let rejection = run_checker(&fast, &data, timeout).await.unwrap().unwrap();
+            assert_eq!(rejection.code, RejectionReasonCode::ReceiverNotAllowed);
+        }
+
+        assert_eq!(
+            "reject".parse::<CheckerTimeoutPolicy>().unwrap(),
+            CheckerTimeoutPolicy::Reject
+        );
+        assert!("drop".parse::<CheckerTimeoutPolicy>().is_err());
+    }
+}
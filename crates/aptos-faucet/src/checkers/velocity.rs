@@ -0,0 +1,272 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use super::{Checker, CheckerData, RejectionReason, RejectionReasonCode};
+use crate::reputation::{AbuseSignal, IpReputation};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use ipnet::IpNet;
+use maxminddb::geoip2;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::IpAddr,
+    path::Path,
+    sync::Arc,
+};
+
+/// Bound on the number of tracked sources, beyond which idle ones are dropped.
+const MAX_TRACKED_SOURCES: usize = 100_000;
+
+#[derive(Clone, Debug)]
+pub struct VelocityConfig {
+    /// Requests are grouped by the network of this size they come from, e.g. /24.
+    pub ipv4_prefix_len: u8,
+    pub ipv6_prefix_len: u8,
+    /// The recent request rate is measured over this many minutes.
+    pub short_window_minutes: u64,
+    /// The baseline request rate is measured over this many minutes.
+    pub long_window_minutes: u64,
+    /// Requests are rejected when the recent rate is more than this many times the baseline.
+    pub multiplier: f64,
+    /// The baseline is never considered lower than this, so that sources with little history
+    /// aren't rejected on their first few requests.
+    pub min_requests_per_minute: f64,
+}
+
+impl Default for VelocityConfig {
+    fn default() -> Self {
+        Self {
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 48,
+            short_window_minutes: 1,
+            long_window_minutes: 60,
+            multiplier: 5.0,
+            min_requests_per_minute: 10.0,
+        }
+    }
+}
+
+/// What requests are grouped by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Source {
+    Network(IpNet),
+    Asn(u32),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Network(network) => write!(f, "network {}", network),
+            Source::Asn(asn) => write!(f, "AS{}", asn),
+        }
+    }
+}
+
+/// Number of accepted requests per minute, oldest first.
+#[derive(Default)]
+struct History {
+    buckets: VecDeque<(u64, u64)>,
+}
+
+impl History {
+    fn prune(&mut self, minute: u64, long_window_minutes: u64) {
+        while let Some((bucket_minute, _)) = self.buckets.front() {
+            if bucket_minute + long_window_minutes > minute {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+
+    fn count_since(&self, minute: u64) -> u64 {
+        self.buckets
+            .iter()
+            .filter(|(bucket_minute, _)| *bucket_minute >= minute)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn record(&mut self, minute: u64) {
+        match self.buckets.back_mut() {
+            Some((bucket_minute, count)) if *bucket_minute == minute => *count += 1,
+            _ => self.buckets.push_back((minute, 1)),
+        }
+    }
+}
+
+/// Catches farming distributed over many addresses, which per IP limits miss, by tracking the
+/// request rate per network and per autonomous system, and rejecting requests when the recent
+/// rate spikes well above the baseline of the source.
+pub struct VelocityChecker {
+    config: VelocityConfig,
+    /// IP to ASN database, e.g. GeoLite2-ASN.
+    asn_db: Option<maxminddb::Reader<Vec<u8>>>,
+    histories: Mutex<HashMap<Source, History>>,
+    /// Where anomalous networks are reported, to share them with other deployments.
+    reputation: Option<Arc<IpReputation>>,
+}
+
+impl VelocityChecker {
+    pub fn new(config: VelocityConfig, asn_db_path: Option<&Path>) -> Result<Self> {
+        let asn_db = asn_db_path
+            .map(|path| {
+                maxminddb::Reader::open_readfile(path)
+                    .with_context(|| format!("Failed to open ASN database {}", path.display()))
+            })
+            .transpose()?;
+        Ok(Self {
+            config,
+            asn_db,
+            histories: Mutex::new(HashMap::new()),
+            reputation: None,
+        })
+    }
+
+    pub fn with_reputation(mut self, reputation: Arc<IpReputation>) -> Self {
+        self.reputation = Some(reputation);
+        self
+    }
+
+    fn sources(&self, ip: IpAddr) -> Result<Vec<Source>> {
+        let prefix_len = match ip {
+            IpAddr::V4(_) => self.config.ipv4_prefix_len,
+            IpAddr::V6(_) => self.config.ipv6_prefix_len,
+        };
+        let mut sources = vec![Source::Network(IpNet::new(ip, prefix_len)?.trunc())];
+        if let Some(asn) = self
+            .asn_db
+            .as_ref()
+            .and_then(|db| db.lookup::<geoip2::Asn>(ip).ok())
+            .and_then(|asn| asn.autonomous_system_number)
+        {
+            sources.push(Source::Asn(asn));
+        }
+        Ok(sources)
+    }
+}
+
+#[async_trait]
+impl Checker for VelocityChecker {
+    fn name(&self) -> &'static str {
+        "velocity"
+    }
+
+    fn cost(&self) -> u64 {
+        // A lookup in the ASN database, if any.
+        100
+    }
+
+    async fn check(&self, data: &CheckerData) -> Result<Option<RejectionReason>> {
+        let source_ip = match data.source_ip {
+            Some(source_ip) => source_ip,
+            None => return Ok(None),
+        };
+        let sources = self.sources(source_ip)?;
+        let minute = data.time.timestamp() as u64 / 60;
+        let short_window = self.config.short_window_minutes.max(1);
+        let long_window = self.config.long_window_minutes.max(short_window);
+
+        let mut histories = self.histories.lock().await;
+        if histories.len() > MAX_TRACKED_SOURCES {
+            histories.retain(|_, history| {
+                history.prune(minute, long_window);
+                !history.buckets.is_empty()
+            });
+        }
+
+        for source in &sources {
+            let history = histories.entry(*source).or_default();
+            history.prune(minute, long_window);
+            // The request being checked counts towards the recent rate.
+            let recent =
+                (history.count_since(minute + 1 - short_window) + 1) as f64 / short_window as f64;
+            let baseline = (history.count_since(0) as f64 / long_window as f64)
+                .max(self.config.min_requests_per_minute);
+            if recent > self.config.multiplier * baseline {
+                if let (Source::Network(network), Some(reputation)) = (source, &self.reputation) {
+                    reputation.report(
+                        *network,
+                        AbuseSignal::AnomalousVelocity,
+                        data.time.timestamp().max(0) as u64,
+                    );
+                }
+                // By then, the requests of the current minute are out of the recent window.
+                let retry_after_secs = short_window * 60 - (data.time.timestamp() as u64 % 60);
+                return Ok(Some(
+                    RejectionReason::new(
+                        RejectionReasonCode::AnomalousVelocity,
+                        format!(
+                            "Unusually many requests from {}, try again in a few minutes",
+                            source
+                        ),
+                    )
+                    .with_retry_after_secs(retry_after_secs),
+                ));
+            }
+        }
+
+        // Only accepted requests are recorded, so a source that is being rejected is let
+        // through again at the allowed rate.
+        for source in sources {
+            histories.entry(source).or_default().record(minute);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VelocityChecker, VelocityConfig};
+    use crate::checkers::{Checker, CheckerData, RejectionReasonCode};
+    use chrono::{Duration, TimeZone, Utc};
+
+    async fn allowed(checker: &VelocityChecker, data: &CheckerData) -> bool {
+        match checker.check(data).await.unwrap() {
+            None => true,
+            Some(rejection) => {
+                assert_eq!(rejection.code, RejectionReasonCode::AnomalousVelocity);
+                false
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_velocity() {
+        let checker = VelocityChecker::new(
+            VelocityConfig {
+                long_window_minutes: 10,
+                multiplier: 2.0,
+                min_requests_per_minute: 1.0,
+                ..VelocityConfig::default()
+            },
+            None,
+        )
+        .unwrap();
+        let start = Utc.ymd(2023, 3, 3).and_hms(10, 0, 0);
+        let data = |ip: &str, minute| {
+            CheckerData::for_test()
+                .ip(ip)
+                .time(start + Duration::minutes(minute))
+        };
+
+        // A steady 2 requests per minute, spread over the /24.
+        for minute in 0..10 {
+            assert!(allowed(&checker, &data("1.1.1.1", minute)).await);
+            assert!(allowed(&checker, &data("1.1.1.2", minute)).await);
+        }
+        // The baseline is now 2 per minute, so up to 4 go through in a minute.
+        for i in 0..4 {
+            assert!(allowed(&checker, &data(&format!("1.1.1.{}", 10 + i), 10)).await);
+        }
+        assert!(!allowed(&checker, &data("1.1.1.20", 10)).await);
+        // Other networks are unaffected.
+        assert!(allowed(&checker, &data("2.2.2.2", 10)).await);
+        // Once the burst is over, requests are allowed again.
+        assert!(allowed(&checker, &data("1.1.1.20", 11)).await);
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Figures out the address of the client that sent a request.
+//!
+//! When the faucet runs behind load balancers or reverse proxies, the remote address of the TCP
+//! connection is that of the last proxy. Proxies append the address they received the request
+//! from to the `Forwarded` (RFC 7239) or `X-Forwarded-For` header, so the client address is found
+//! by walking the chain of hops from the right, skipping proxies we trust, and taking the first
+//! address we don't trust. Anything to the left of that could have been forged by the client.
+
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use warp::http::HeaderMap;
+
+const FORWARDED: &str = "forwarded";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Returns the address of the client, or `None` if it can't be determined, e.g. because there is
+/// no remote address (as in tests) or a trusted proxy forwarded a hop we can't parse.
+pub fn extract_client_ip(
+    remote_addr: Option<SocketAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let remote_ip = remote_addr?.ip();
+    if !is_trusted(&remote_ip, trusted_proxies) {
+        return Some(remote_ip);
+    }
+
+    // The request came through a proxy we trust, look at the hops it reported.
+    let hops = forwarded_hops(headers);
+    let mut client_ip = remote_ip;
+    for hop in hops.iter().rev() {
+        match hop {
+            Some(ip) => {
+                client_ip = *ip;
+                if !is_trusted(ip, trusted_proxies) {
+                    return Some(client_ip);
+                }
+            },
+            // A trusted proxy gave us something we can't make sense of, so there is no way to
+            // tell who is behind it.
+            None => return None,
+        }
+    }
+    // Every hop is trusted, the leftmost one is as close to the client as we can get.
+    Some(client_ip)
+}
+
+fn is_trusted(ip: &IpAddr, trusted_proxies: &[IpNet]) -> bool {
+    trusted_proxies.iter().any(|net| net.contains(ip))
+}
+
+/// Collects the hops from the `Forwarded` header, falling back to `X-Forwarded-For` if absent.
+/// Multiple header lines are treated as a single comma separated list, in order.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded = header_values(headers, FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(parse_forwarded_element)
+            .collect();
+    }
+    header_values(headers, X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+fn header_values(headers: &HeaderMap, name: &str) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+        .collect()
+}
+
+/// Parses one element of a `Forwarded` header, e.g. `for=192.0.2.60;proto=http;by=203.0.113.43`.
+fn parse_forwarded_element(element: &str) -> Option<IpAddr> {
+    element
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+        .and_then(|(_, value)| parse_node(value))
+}
+
+/// Parses a node as found in `X-Forwarded-For` or the `for` parameter of `Forwarded`, which may be
+/// quoted and carry a port: `192.0.2.43`, `192.0.2.43:47011`, `"[2001:db8:cafe::17]:4711"`.
+/// Obfuscated identifiers and "unknown" are not addresses and yield `None`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    // Bracketed IPv6 without a port.
+    node.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .and_then(|ip| ip.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_client_ip;
+    use ipnet::IpNet;
+    use std::net::{IpAddr, SocketAddr};
+    use warp::http::{HeaderMap, HeaderValue};
+
+    fn make_headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn remote(s: &str) -> Option<SocketAddr> {
+        Some(SocketAddr::new(ip(s), 1234))
+    }
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    #[test]
+    fn test_untrusted_remote_ignores_headers() {
+        let headers = make_headers(&[("x-forwarded-for", "1.1.1.1")]);
+        assert_eq!(
+            extract_client_ip(remote("2.2.2.2"), &headers, &trusted()),
+            Some(ip("2.2.2.2"))
+        );
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &[]),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(extract_client_ip(None, &headers, &trusted()), None);
+    }
+
+    #[test]
+    fn test_x_forwarded_for_rightmost_untrusted() {
+        // The client forged the first entry.
+        let headers = make_headers(&[("x-forwarded-for", "6.6.6.6, 1.1.1.1, 10.0.0.2")]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            Some(ip("1.1.1.1"))
+        );
+
+        let headers = make_headers(&[
+            ("x-forwarded-for", "6.6.6.6"),
+            ("x-forwarded-for", "1.1.1.1:5555"),
+        ]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            Some(ip("1.1.1.1"))
+        );
+
+        let headers = make_headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            Some(ip("10.0.0.3"))
+        );
+
+        let headers = make_headers(&[("x-forwarded-for", "1.1.1.1, garbage")]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let headers = make_headers(&[
+            (
+                "forwarded",
+                r#"for=6.6.6.6, for="[2001:db8:cafe::17]:4711";proto=https"#,
+            ),
+            ("forwarded", "for=fd00::1;by=10.0.0.1"),
+            // Forwarded takes precedence over X-Forwarded-For.
+            ("x-forwarded-for", "3.3.3.3"),
+        ]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            Some(ip("2001:db8:cafe::17"))
+        );
+
+        let headers = make_headers(&[("forwarded", "for=unknown")]);
+        assert_eq!(
+            extract_client_ip(remote("10.0.0.1"), &headers, &trusted()),
+            None
+        );
+    }
+}
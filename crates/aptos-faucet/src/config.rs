@@ -0,0 +1,204 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Layered configuration of the faucet. Each argument is taken from, in order of precedence:
+//!
+//! 1. the command line,
+//! 2. its environment variable, e.g. `FAUCET__SERVER_URL` for `--server-url`,
+//! 3. the YAML file given with `--config-file`, keyed by argument name, e.g. `server_url`,
+//! 4. the preset of `--dev-mode`, if set,
+//! 5. its default value.
+//!
+//! This way a base config can be shared between deployments, while secrets, e.g. the mint key,
+//! are mounted separately and pointed at, or passed, through the environment.
+
+use crate::FaucetArgs;
+use anyhow::{bail, ensure, format_err, Context, Result};
+use clap::{CommandFactory, Parser, ValueSource};
+use serde_yaml::Value;
+use std::{collections::BTreeMap, ffi::OsString, net::IpAddr, path::Path};
+
+/// Arguments of `--dev-mode`, for the defaults of `aptos node run-local-testnet` run from the
+/// current directory.
+const DEV_MODE_PRESET: &[(&str, &str)] = &[
+    ("server_url", "http://127.0.0.1:8080"),
+    ("chain_id", "TESTING"),
+    ("port", "8081"),
+    ("mint_key_file_path", ".aptos/testnet/mint.key"),
+];
+
+impl FaucetArgs {
+    /// Parses the arguments of the process, layered on top of the config file, if any.
+    pub fn parse_layered() -> Result<Self> {
+        Self::parse_layered_from(std::env::args_os())
+    }
+
+    pub fn parse_layered_from<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut layered_args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut faucet_args = Self::try_parse_from(&layered_args)?;
+        if let Some(config_file) = faucet_args.config_file.clone() {
+            let source = format!("config file {}", config_file.display());
+            layered_args = layer(layered_args, load_config_file(&config_file)?, &source)?;
+            faucet_args = Self::try_parse_from(&layered_args)
+                .map_err(|e| format_err!("Invalid arguments, including {}: {}", source, e))?;
+        }
+        // Checked after the config file, which may set it too.
+        if faucet_args.dev_mode {
+            let preset = DEV_MODE_PRESET
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::String(value.to_string())))
+                .collect();
+            layered_args = layer(layered_args, preset, "--dev-mode")?;
+            faucet_args = Self::try_parse_from(&layered_args)
+                .map_err(|e| format_err!("Invalid arguments for --dev-mode: {}", e))?;
+            ensure!(
+                faucet_args
+                    .address
+                    .parse::<IpAddr>()
+                    .map_or(false, |address| address.is_loopback()),
+                "--dev-mode only listens on localhost, not on {}",
+                faucet_args.address
+            );
+        }
+        Ok(faucet_args)
+    }
+}
+
+/// Appends the arguments of a layer to the command line, unless given on the command line, by
+/// an upper layer, or through the environment already.
+fn layer(
+    args: Vec<OsString>,
+    values: BTreeMap<String, Value>,
+    source: &str,
+) -> Result<Vec<OsString>> {
+    let matches = FaucetArgs::command().try_get_matches_from(&args)?;
+    let mut layered_args = args;
+    for (name, value) in values {
+        let kebab_name = name.replace('_', "-");
+        let command = FaucetArgs::command();
+        let (id, long) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == kebab_name || arg.get_id() == name)
+            .and_then(|arg| Some((arg.get_id(), arg.get_long()?)))
+            .ok_or_else(|| format_err!("Unknown argument '{}' in {}", name, source))?;
+        if matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        for value in flatten_value(&name, value)? {
+            match value {
+                Some(value) => {
+                    layered_args.push(format!("--{}", long).into());
+                    layered_args.push(value.into());
+                },
+                None => layered_args.push(format!("--{}", long).into()),
+            }
+        }
+    }
+    Ok(layered_args)
+}
+
+fn load_config_file(path: &Path) -> Result<BTreeMap<String, Value>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file {}", path.display()))
+}
+
+/// Turns a config file value into the values of one or more occurrences of the argument. `None`
+/// stands for a flag, which takes no value.
+fn flatten_value(name: &str, value: Value) -> Result<Vec<Option<String>>> {
+    Ok(match value {
+        Value::Null | Value::Bool(false) => vec![],
+        Value::Bool(true) => vec![None],
+        Value::Number(number) => vec![Some(number.to_string())],
+        Value::String(string) => vec![Some(string)],
+        Value::Sequence(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::Number(number) => Ok(Some(number.to_string())),
+                Value::String(string) => Ok(Some(string)),
+                _ => bail!("'{}' may only contain strings and numbers", name),
+            })
+            .collect::<Result<_>>()?,
+        Value::Mapping(_) => bail!("'{}' can't be a mapping", name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FaucetArgs;
+    use aptos_sdk::types::chain_id::ChainId;
+    use std::{io::Write, path::Path};
+
+    #[test]
+    fn test_config_file_layering() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "port: 8000\n\
+             maximum_amount: 100\n\
+             do_not_delegate: true\n\
+             trusted_proxies:\n  - 10.0.0.0/8\n  - 192.168.0.0/16\n"
+        )
+        .unwrap();
+        let config_file = file.path().to_str().unwrap();
+
+        let args =
+            FaucetArgs::parse_layered_from(["faucet", "--config-file", config_file, "-p", "9000"])
+                .unwrap();
+        // The command line wins over the file.
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.maximum_amount, Some(100));
+        assert!(args.do_not_delegate);
+        assert_eq!(args.trusted_proxies.len(), 2);
+        // Defaults apply to whatever is left.
+        assert_eq!(args.address, "127.0.0.1");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "no_such_argument: 1").unwrap();
+        let config_file = file.path().to_str().unwrap();
+        assert!(FaucetArgs::parse_layered_from(["faucet", "--config-file", config_file]).is_err());
+    }
+
+    #[test]
+    fn test_dev_mode() {
+        let args = FaucetArgs::parse_layered_from(["faucet", "--dev-mode", "-p", "9000"]).unwrap();
+        assert_eq!(args.server_url.as_str(), "http://127.0.0.1:8080/");
+        assert_eq!(args.chain_id, ChainId::test());
+        assert_eq!(
+            args.mint_key_file_path,
+            Path::new(".aptos/testnet/mint.key")
+        );
+        // The preset only applies to what isn't given otherwise.
+        assert_eq!(args.port, 9000);
+
+        // The config file wins over the preset, and may turn dev mode on too.
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "dev_mode: true\nchain_id: 3\n").unwrap();
+        let config_file = file.path().to_str().unwrap();
+        let args =
+            FaucetArgs::parse_layered_from(["faucet", "--config-file", config_file]).unwrap();
+        assert_eq!(args.chain_id, ChainId::new(3));
+        assert_eq!(args.port, 8081);
+
+        // Dev mode runs without checkers, and only on localhost.
+        assert!(FaucetArgs::parse_layered_from([
+            "faucet",
+            "--dev-mode",
+            "--max-requests-per-ip-per-day",
+            "10"
+        ])
+        .is_err());
+        assert!(FaucetArgs::parse_layered_from(["faucet", "--dev-mode", "-a", "0.0.0.0"]).is_err());
+    }
+}
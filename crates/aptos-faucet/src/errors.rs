@@ -0,0 +1,180 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Errors answered to clients, other than the rejections of the checkers. Like rejections, see
+//! `checkers::RejectionReason`, they tell whether sending the same request again may succeed, and
+//! when, so that clients only retry automatically what can succeed.
+
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::fmt;
+use warp::{
+    http::{
+        header::{ACCEPT, RETRY_AFTER},
+        HeaderMap,
+    },
+    Reply,
+};
+
+/// Stable, machine readable code of an error.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaucetErrorCode {
+    /// The request is malformed, or can't be served as asked, e.g. the name to fund isn't
+    /// registered.
+    InvalidRequest,
+    /// What was asked for isn't served by this faucet.
+    NotFound,
+    /// The faucet is too busy to take the request, e.g. its queue is full.
+    Overloaded,
+    /// Funding failed, e.g. the node didn't accept the transaction, or it expired.
+    FundingFailed,
+    /// The faucet failed otherwise, e.g. a checker couldn't reach its store.
+    Internal,
+    /// The faucet gave up waiting on the request, which may still get funded.
+    OutcomeUnknown,
+}
+
+impl FaucetErrorCode {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            FaucetErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            FaucetErrorCode::NotFound => StatusCode::NOT_FOUND,
+            FaucetErrorCode::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            FaucetErrorCode::FundingFailed | FaucetErrorCode::Internal => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+            FaucetErrorCode::OutcomeUnknown => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    /// Whether the same request may succeed later. Failures of the faucet or of the node are
+    /// assumed to be transient. Requests of unknown outcome aren't, as they may still be funded.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FaucetErrorCode::InvalidRequest
+            | FaucetErrorCode::NotFound
+            | FaucetErrorCode::OutcomeUnknown => false,
+            FaucetErrorCode::Overloaded
+            | FaucetErrorCode::FundingFailed
+            | FaucetErrorCode::Internal => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FaucetError {
+    pub code: FaucetErrorCode,
+    pub message: String,
+    /// Whether the same request may succeed later, see `FaucetErrorCode::is_retryable`.
+    pub retryable: bool,
+    /// How long to wait before retrying, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl FaucetError {
+    pub fn new(code: FaucetErrorCode, message: String) -> Self {
+        Self {
+            code,
+            message,
+            retryable: code.is_retryable(),
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn invalid_request(message: String) -> Self {
+        Self::new(FaucetErrorCode::InvalidRequest, message)
+    }
+
+    pub fn internal(err: anyhow::Error) -> Self {
+        Self::new(FaucetErrorCode::Internal, err.to_string())
+    }
+
+    /// An error of the funder: the `FaucetError` it failed with, if any, otherwise a funding
+    /// failure.
+    pub fn funding(err: anyhow::Error) -> Self {
+        match err.downcast::<FaucetError>() {
+            Ok(err) => err,
+            Err(err) => Self::new(FaucetErrorCode::FundingFailed, err.to_string()),
+        }
+    }
+
+    pub fn with_retry_after_secs(mut self, retry_after_secs: u64) -> Self {
+        self.retry_after_secs = Some(retry_after_secs);
+        self
+    }
+}
+
+impl fmt::Display for FaucetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FaucetError {}
+
+/// Whether the client asked for JSON, with `Accept: application/json`.
+pub fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| accept.contains("application/json"))
+}
+
+/// Body of the reply to a failed request, when the client accepts JSON.
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    error: &'a FaucetError,
+}
+
+/// Replies with an error. Clients asking for JSON get the error in full, others get the message,
+/// and `Retry-After` is set if the wait is known.
+pub fn error_reply(error: &FaucetError, headers: &HeaderMap) -> Box<dyn Reply> {
+    let status = error.code.status_code();
+    let reply: Box<dyn Reply> = if accepts_json(headers) {
+        Box::new(warp::reply::with_status(
+            warp::reply::json(&ErrorBody { error }),
+            status,
+        ))
+    } else {
+        Box::new(warp::reply::with_status(error.message.clone(), status))
+    };
+    match error.retry_after_secs {
+        Some(retry_after_secs) => Box::new(warp::reply::with_header(
+            reply,
+            RETRY_AFTER,
+            retry_after_secs.to_string(),
+        )),
+        None => reply,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaucetError, FaucetErrorCode};
+
+    #[test]
+    fn test_funding_errors() {
+        let error = FaucetError::funding(anyhow::anyhow!("Transaction expired"));
+        assert_eq!(error.code, FaucetErrorCode::FundingFailed);
+        assert!(error.retryable);
+
+        let error = FaucetError::funding(
+            FaucetError::invalid_request("Account is already created".to_string()).into(),
+        );
+        assert_eq!(error.code, FaucetErrorCode::InvalidRequest);
+        assert!(!error.retryable);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "invalid_request",
+                "message": "Account is already created",
+                "retryable": false,
+            })
+        );
+    }
+}
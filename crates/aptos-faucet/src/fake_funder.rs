@@ -0,0 +1,252 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Funding without a chain, to load test the rest of the service, e.g. the checkers and the
+//! stores behind them, in isolation. Instead of submitting the transactions, the fake funder
+//! waits as long as a submission would, then fails, or signs them with the faucet account and
+//! answers with their hashes, as configured in a YAML file like:
+//!
+//! ```yaml
+//! latency:
+//!   distribution: exponential
+//!   mean_ms: 200
+//!   max_ms: 5000
+//! failures:
+//!   - rate: 0.01
+//!     error: "Transaction expired"
+//!   - rate: 0.001
+//!     error: "Faucet account not found"
+//! ```
+//!
+//! The hashes are never found on chain, as with shadow banned requests.
+
+use crate::{metrics::FAKE_FUNDINGS, Service};
+use anyhow::{Context, Result};
+use aptos_sdk::{transaction_builder::TransactionBuilder, types::transaction::SignedTransaction};
+use rand::Rng;
+use serde::Deserialize;
+use std::{path::Path, time::Duration};
+
+/// How long fundings take, in milliseconds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Latency {
+    Fixed {
+        ms: u64,
+    },
+    Uniform {
+        min_ms: u64,
+        max_ms: u64,
+    },
+    /// Mostly short, with the long tail of a congested node, cut at `max_ms`.
+    Exponential {
+        mean_ms: u64,
+        max_ms: u64,
+    },
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::Fixed { ms: 0 }
+    }
+}
+
+impl Latency {
+    fn validate(&self) -> Result<()> {
+        match self {
+            Latency::Fixed { .. } => Ok(()),
+            Latency::Uniform { min_ms, max_ms } => {
+                anyhow::ensure!(
+                    min_ms <= max_ms,
+                    "Uniform latency min_ms {} is over max_ms {}",
+                    min_ms,
+                    max_ms
+                );
+                Ok(())
+            },
+            Latency::Exponential { mean_ms, max_ms } => {
+                anyhow::ensure!(
+                    mean_ms <= max_ms,
+                    "Exponential latency mean_ms {} is over max_ms {}",
+                    mean_ms,
+                    max_ms
+                );
+                Ok(())
+            },
+        }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        let ms = match *self {
+            Latency::Fixed { ms } => ms,
+            Latency::Uniform { min_ms, max_ms } => rng.gen_range(min_ms..=max_ms),
+            Latency::Exponential { mean_ms, max_ms } => {
+                // Inverse of the CDF, 1 - e^(-x / mean), at a uniform sample.
+                let uniform: f64 = rng.gen();
+                let ms = -(mean_ms as f64) * (1.0 - uniform).ln();
+                (ms as u64).min(max_ms)
+            },
+        };
+        Duration::from_millis(ms)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Failure {
+    /// Fraction of the fundings failing with the error, between 0 and 1.
+    pub rate: f64,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FakeFunderConfig {
+    #[serde(default)]
+    pub latency: Latency,
+    /// Errors fundings fail with, none if empty. Failed fundings take as long as the others.
+    #[serde(default)]
+    pub failures: Vec<Failure>,
+}
+
+impl FakeFunderConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fake funder config {}", path.display()))?;
+        let config: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse fake funder config {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.latency.validate()?;
+        let mut total = 0.0;
+        for failure in &self.failures {
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&failure.rate),
+                "Failure rate {} of '{}' is not between 0 and 1",
+                failure.rate,
+                failure.error
+            );
+            total += failure.rate;
+        }
+        anyhow::ensure!(total <= 1.0, "Failure rates add up to {}, over 1", total);
+        Ok(())
+    }
+}
+
+pub struct FakeFunder {
+    config: FakeFunderConfig,
+}
+
+impl FakeFunder {
+    pub fn new(config: FakeFunderConfig) -> Self {
+        Self { config }
+    }
+
+    /// Waits as long as the configured latency, then fails with one of the configured errors,
+    /// or signs the transactions with the faucet account without submitting them.
+    pub async fn fund(
+        &self,
+        service: &Service,
+        builders: Vec<TransactionBuilder>,
+    ) -> Result<Vec<SignedTransaction>> {
+        let (latency, failure) = self.sample(&mut rand::thread_rng());
+        tokio::time::sleep(latency).await;
+        if let Some(failure) = failure {
+            FAKE_FUNDINGS.with_label_values(&["failed"]).inc();
+            anyhow::bail!("{}", failure.error);
+        }
+        FAKE_FUNDINGS.with_label_values(&["funded"]).inc();
+        let mut faucet_account = service.faucet_account.lock().await;
+        Ok(builders
+            .into_iter()
+            .map(|builder| faucet_account.sign_with_transaction_builder(builder))
+            .collect())
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> (Duration, Option<&Failure>) {
+        let latency = self.config.latency.sample(rng);
+        let mut draw: f64 = rng.gen();
+        for failure in &self.config.failures {
+            if draw < failure.rate {
+                return (latency, Some(failure));
+            }
+            draw -= failure.rate;
+        }
+        (latency, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FakeFunder, FakeFunderConfig, Latency};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Duration;
+
+    #[test]
+    fn test_fake_funder_sampling() {
+        let config: FakeFunderConfig = serde_yaml::from_str(
+            r#"
+latency:
+  distribution: uniform
+  min_ms: 10
+  max_ms: 20
+failures:
+  - rate: 0.25
+    error: expired
+  - rate: 0.25
+    error: not found
+"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.latency, Latency::Uniform {
+            min_ms: 10,
+            max_ms: 20
+        });
+
+        let funder = FakeFunder::new(config);
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mut expired, mut not_found) = (0, 0);
+        for _ in 0..10_000 {
+            let (latency, failure) = funder.sample(&mut rng);
+            assert!(latency >= Duration::from_millis(10) && latency <= Duration::from_millis(20));
+            match failure.map(|failure| failure.error.as_str()) {
+                Some("expired") => expired += 1,
+                Some("not found") => not_found += 1,
+                _ => (),
+            }
+        }
+        assert!((2_000..3_000).contains(&expired));
+        assert!((2_000..3_000).contains(&not_found));
+
+        let exponential = Latency::Exponential {
+            mean_ms: 100,
+            max_ms: 150,
+        };
+        for _ in 0..1_000 {
+            assert!(exponential.sample(&mut rng) <= Duration::from_millis(150));
+        }
+
+        let config: FakeFunderConfig = serde_yaml::from_str(
+            r#"
+failures:
+  - rate: 0.75
+    error: expired
+  - rate: 0.5
+    error: not found
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+        assert_eq!(
+            FakeFunder::new(FakeFunderConfig::default()).sample(&mut rng),
+            (Duration::ZERO, None)
+        );
+    }
+}
@@ -0,0 +1,718 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Two-person approval of manual grants, e.g. for amounts above what the faucet funds on its
+//! own. An operator proposes a grant with `POST /admin/grants`, and it's only funded once another
+//! operator approves it with `POST /admin/grants/<id>/approve`, within the approval TTL. Either
+//! operator may reject it instead with `POST /admin/grants/<id>/reject`. Grants are funded from
+//! the treasury if there is one, and from the faucet account otherwise, without the caps of the
+//! automated fundings.
+//!
+//! Operators authenticate with `Authorization: Bearer <token>`, their tokens being read from a
+//! YAML file like:
+//!
+//! ```yaml
+//! - name: alice
+//!   token: s3cret
+//! - name: bob
+//!   token: t0ps3cret
+//! ```
+//!
+//! Every step of every grant, who took it and when, is appended to the audit log, a file of JSON
+//! lines which is synced before the step is acknowledged. Pending grants are only kept in memory
+//! by the replica they were proposed to, so they're dropped, never to be approved, when it
+//! restarts.
+
+use crate::{
+    batch::process_batch,
+    constant_time_eq,
+    errors::{error_reply, FaucetError},
+    metrics::GRANT_ACTIONS,
+    stats::now_secs,
+    validation::parse_address,
+    webhooks::FundingEvent,
+    Service,
+};
+use anyhow::{Context, Result};
+use aptos_crypto::hash::HashValue;
+use aptos_logger::info;
+use aptos_sdk::types::account_address::AccountAddress;
+use futures::lock::Mutex;
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Arc,
+};
+use warp::{http::HeaderMap, Filter, Rejection, Reply};
+
+/// Grants decided on are listed for this long, after which only the audit log has them.
+const DECIDED_GRANT_TTL_SECS: u64 = 24 * 3600;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Operator {
+    pub name: String,
+    pub token: String,
+}
+
+impl Operator {
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read grant operators {}", path.display()))?;
+        let operators: Vec<Self> = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse grant operators {}", path.display()))?;
+        for (idx, operator) in operators.iter().enumerate() {
+            anyhow::ensure!(
+                operators[..idx]
+                    .iter()
+                    .all(|other| other.name != operator.name && other.token != operator.token),
+                "Grant operator {} is listed twice, or shares a token with another one",
+                operator.name
+            );
+        }
+        anyhow::ensure!(
+            operators.len() >= 2,
+            "Grants need at least two operators, one to propose and one to approve"
+        );
+        Ok(operators)
+    }
+
+    /// Whether the token is that of the operator.
+    fn has_token(&self, token: &[u8]) -> bool {
+        constant_time_eq(token, self.token.as_bytes())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantStatus {
+    /// Waiting for a second operator.
+    Pending,
+    /// Approved, and being funded.
+    Funding,
+    Funded,
+    Failed,
+    Rejected,
+    /// Not approved within the TTL.
+    Expired,
+}
+
+/// Body of `POST /admin/grants`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrantProposal {
+    pub address: String,
+    pub amount: u64,
+    /// Why the grant is given, e.g. a link to the request for it.
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Grant {
+    pub grant_id: String,
+    pub receiver: AccountAddress,
+    pub amount: u64,
+    pub reason: String,
+    pub status: GrantStatus,
+    pub proposed_by: String,
+    pub proposed_at_secs: u64,
+    /// Time after which the grant can't be approved anymore.
+    pub expires_at_secs: u64,
+    /// Operator who approved or rejected the grant.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_at_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub txn_hashes: Vec<HashValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Proposed,
+    Approved,
+    Rejected,
+    Expired,
+    Funded,
+    Failed,
+}
+
+impl AuditAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Proposed => "proposed",
+            AuditAction::Approved => "approved",
+            AuditAction::Rejected => "rejected",
+            AuditAction::Expired => "expired",
+            AuditAction::Funded => "funded",
+            AuditAction::Failed => "failed",
+        }
+    }
+}
+
+/// Line of the audit log.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub time_secs: u64,
+    pub grant_id: String,
+    pub action: AuditAction,
+    /// Operator who took the action, none for the ones the faucet takes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    pub receiver: AccountAddress,
+    pub amount: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub txn_hashes: Vec<HashValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Why an operator can't act on a grant.
+#[derive(Debug, Eq, PartialEq)]
+pub enum GrantRefusal {
+    NotFound,
+    /// The approver is the proposer.
+    SelfApproval,
+    NotPending(GrantStatus),
+}
+
+impl GrantRefusal {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            GrantRefusal::NotFound => StatusCode::NOT_FOUND,
+            GrantRefusal::SelfApproval => StatusCode::FORBIDDEN,
+            GrantRefusal::NotPending(_) => StatusCode::CONFLICT,
+        }
+    }
+}
+
+impl fmt::Display for GrantRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrantRefusal::NotFound => write!(f, "Unknown grant, or decided on too long ago"),
+            GrantRefusal::SelfApproval => {
+                write!(f, "Grants must be approved by another operator")
+            },
+            GrantRefusal::NotPending(status) => {
+                write!(f, "Grant is not pending anymore, but {:?}", status)
+            },
+        }
+    }
+}
+
+pub struct Grants {
+    operators: Vec<Operator>,
+    approval_ttl_secs: u64,
+    /// Written to off the async workers, see `Grants::audit`.
+    audit_log: Arc<std::sync::Mutex<File>>,
+    grants: Mutex<HashMap<String, Grant>>,
+}
+
+impl Grants {
+    pub fn new(
+        operators: Vec<Operator>,
+        approval_ttl_secs: u64,
+        audit_path: &Path,
+    ) -> Result<Self> {
+        let audit_log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(audit_path)
+            .with_context(|| format!("Failed to open grant audit log {}", audit_path.display()))?;
+        Ok(Self {
+            operators,
+            approval_ttl_secs,
+            audit_log: Arc::new(std::sync::Mutex::new(audit_log)),
+            grants: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The operator whose token the request carries, if any.
+    fn operator(&self, authorization: Option<&str>) -> Option<&Operator> {
+        let token = authorization?.strip_prefix("Bearer ")?.as_bytes();
+        self.operators
+            .iter()
+            .find(|operator| operator.has_token(token))
+    }
+
+    pub async fn propose(
+        &self,
+        operator: &str,
+        receiver: AccountAddress,
+        amount: u64,
+        reason: String,
+        now: u64,
+    ) -> Result<Grant> {
+        let grant = Grant {
+            grant_id: format!("{:016x}", rand::rngs::OsRng.next_u64()),
+            receiver,
+            amount,
+            reason,
+            status: GrantStatus::Pending,
+            proposed_by: operator.to_string(),
+            proposed_at_secs: now,
+            expires_at_secs: now + self.approval_ttl_secs,
+            decided_by: None,
+            decided_at_secs: None,
+            txn_hashes: vec![],
+            error: None,
+        };
+        let mut grants = self.grants.lock().await;
+        self.expire(&mut grants, now).await?;
+        self.audit(AuditRecord {
+            reason: Some(grant.reason.clone()),
+            ..Self::record(&grant, AuditAction::Proposed, Some(operator), now)
+        })
+        .await?;
+        grants.insert(grant.grant_id.clone(), grant.clone());
+        Ok(grant)
+    }
+
+    /// Marks the grant approved by the operator, for it to be funded, unless the operator
+    /// proposed it, or it's not pending anymore.
+    pub async fn approve(
+        &self,
+        grant_id: &str,
+        operator: &str,
+        now: u64,
+    ) -> Result<std::result::Result<Grant, GrantRefusal>> {
+        self.decide(grant_id, operator, now, AuditAction::Approved)
+            .await
+    }
+
+    pub async fn reject(
+        &self,
+        grant_id: &str,
+        operator: &str,
+        now: u64,
+    ) -> Result<std::result::Result<Grant, GrantRefusal>> {
+        self.decide(grant_id, operator, now, AuditAction::Rejected)
+            .await
+    }
+
+    async fn decide(
+        &self,
+        grant_id: &str,
+        operator: &str,
+        now: u64,
+        action: AuditAction,
+    ) -> Result<std::result::Result<Grant, GrantRefusal>> {
+        let mut grants = self.grants.lock().await;
+        self.expire(&mut grants, now).await?;
+        let grant = match grants.get_mut(grant_id) {
+            Some(grant) => grant,
+            None => return Ok(Err(GrantRefusal::NotFound)),
+        };
+        if grant.status != GrantStatus::Pending {
+            return Ok(Err(GrantRefusal::NotPending(grant.status)));
+        }
+        if action == AuditAction::Approved && grant.proposed_by == operator {
+            return Ok(Err(GrantRefusal::SelfApproval));
+        }
+        self.audit(Self::record(grant, action, Some(operator), now))
+            .await?;
+        grant.status = match action {
+            AuditAction::Approved => GrantStatus::Funding,
+            _ => GrantStatus::Rejected,
+        };
+        grant.decided_by = Some(operator.to_string());
+        grant.decided_at_secs = Some(now);
+        Ok(Ok(grant.clone()))
+    }
+
+    /// Records the outcome of the funding of an approved grant.
+    pub async fn record_funding(
+        &self,
+        grant_id: &str,
+        result: &Result<Vec<HashValue>>,
+        now: u64,
+    ) -> Result<Grant> {
+        let mut grants = self.grants.lock().await;
+        let grant = grants
+            .get_mut(grant_id)
+            .ok_or_else(|| anyhow::format_err!("Grant {} is gone", grant_id))?;
+        match result {
+            Ok(txn_hashes) => {
+                grant.status = GrantStatus::Funded;
+                grant.txn_hashes = txn_hashes.clone();
+            },
+            Err(err) => {
+                grant.status = GrantStatus::Failed;
+                grant.error = Some(err.to_string());
+            },
+        }
+        let action = match result {
+            Ok(_) => AuditAction::Funded,
+            Err(_) => AuditAction::Failed,
+        };
+        self.audit(AuditRecord {
+            txn_hashes: grant.txn_hashes.clone(),
+            error: grant.error.clone(),
+            ..Self::record(grant, action, None, now)
+        })
+        .await?;
+        Ok(grant.clone())
+    }
+
+    /// The grants pending, and those decided on recently, the latest proposed first.
+    pub async fn list(&self, now: u64) -> Result<Vec<Grant>> {
+        let mut grants = self.grants.lock().await;
+        self.expire(&mut grants, now).await?;
+        let mut list: Vec<_> = grants.values().cloned().collect();
+        list.sort_by(|a, b| b.proposed_at_secs.cmp(&a.proposed_at_secs));
+        Ok(list)
+    }
+
+    /// Expires the pending grants past their TTL, and forgets those decided on long ago.
+    async fn expire(&self, grants: &mut HashMap<String, Grant>, now: u64) -> Result<()> {
+        for grant in grants.values_mut() {
+            if grant.status == GrantStatus::Pending && now > grant.expires_at_secs {
+                self.audit(Self::record(grant, AuditAction::Expired, None, now))
+                    .await?;
+                grant.status = GrantStatus::Expired;
+                grant.decided_at_secs = Some(now);
+            }
+        }
+        grants.retain(|_, grant| {
+            grant
+                .decided_at_secs
+                .map_or(true, |decided_at| decided_at + DECIDED_GRANT_TTL_SECS > now)
+        });
+        Ok(())
+    }
+
+    fn record(grant: &Grant, action: AuditAction, operator: Option<&str>, now: u64) -> AuditRecord {
+        AuditRecord {
+            time_secs: now,
+            grant_id: grant.grant_id.clone(),
+            action,
+            operator: operator.map(str::to_string),
+            receiver: grant.receiver,
+            amount: grant.amount,
+            reason: None,
+            txn_hashes: vec![],
+            error: None,
+        }
+    }
+
+    /// Appends the record to the audit log, synced to disk before the action takes effect. The
+    /// writes block, so they're made on the blocking threads rather than the async workers.
+    async fn audit(&self, record: AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let audit_log = self.audit_log.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut audit_log = audit_log.lock().unwrap();
+            audit_log
+                .write_all(&line)
+                .and_then(|()| audit_log.sync_data())
+        })
+        .await?
+        .context("Failed to write to the grant audit log")?;
+        GRANT_ACTIONS
+            .with_label_values(&[record.action.as_str()])
+            .inc();
+        info!(
+            grant_id = record.grant_id,
+            action = record.action.as_str(),
+            operator = record.operator,
+            receiver = record.receiver,
+            amount = record.amount,
+            "grant"
+        );
+        Ok(())
+    }
+}
+
+/// Funds the grant, from the treasury if there is one, and from the faucet account otherwise.
+async fn fund(service: &Service, receiver: AccountAddress, amount: u64) -> Result<Vec<HashValue>> {
+    match service.treasury() {
+        // As with any other request, the fake funder stands in for the treasury.
+        Some(treasury) if service.fake_funder().is_none() => {
+            let (txn, _) = treasury.fund(service, receiver, amount).await?;
+            Ok(vec![txn.committed_hash()])
+        },
+        _ => Ok(process_batch(service, vec![(receiver, amount)])
+            .await?
+            .iter()
+            .map(|txn| txn.committed_hash())
+            .collect()),
+    }
+}
+
+pub fn grant_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let list_service = service.clone();
+    let propose_service = service.clone();
+    // GET /admin/grants
+    let list = warp::path!("admin" / "grants")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(move || list_service.clone()))
+        .and_then(handle_list);
+    // POST /admin/grants
+    let propose = warp::path!("admin" / "grants")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(move || propose_service.clone()))
+        .and(warp::body::content_length_limit(64 * 1024))
+        .and(warp::body::json())
+        .and_then(handle_propose);
+    // POST /admin/grants/<id>/approve
+    // POST /admin/grants/<id>/reject
+    let decide = warp::path!("admin" / "grants" / String / String)
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(move || service.clone()))
+        .and_then(handle_decision);
+    list.or(propose).or(decide)
+}
+
+/// The grants, and the operator the request is from, or the reply if the endpoints aren't
+/// served, or the request carries no operator token.
+fn authorize<'a>(
+    service: &'a Service,
+    authorization: Option<&str>,
+) -> std::result::Result<(&'a Grants, &'a Operator), Box<dyn Reply>> {
+    let grants = service
+        .grants()
+        .ok_or_else(|| Box::new(StatusCode::NOT_FOUND) as Box<dyn Reply>)?;
+    let operator = grants
+        .operator(authorization)
+        .ok_or_else(|| Box::new(StatusCode::UNAUTHORIZED) as Box<dyn Reply>)?;
+    Ok((grants, operator))
+}
+
+async fn handle_list(
+    authorization: Option<String>,
+    headers: HeaderMap,
+    service: Arc<Service>,
+) -> std::result::Result<Box<dyn Reply>, Infallible> {
+    let (grants, _) = match authorize(&service, authorization.as_deref()) {
+        Ok(authorized) => authorized,
+        Err(reply) => return Ok(reply),
+    };
+    Ok(match grants.list(now_secs()).await {
+        Ok(list) => Box::new(warp::reply::json(&list)),
+        Err(err) => error_reply(&FaucetError::internal(err), &headers),
+    })
+}
+
+async fn handle_propose(
+    authorization: Option<String>,
+    headers: HeaderMap,
+    service: Arc<Service>,
+    proposal: GrantProposal,
+) -> std::result::Result<Box<dyn Reply>, Infallible> {
+    let (grants, operator) = match authorize(&service, authorization.as_deref()) {
+        Ok(authorized) => authorized,
+        Err(reply) => return Ok(reply),
+    };
+    let receiver = match parse_address(&proposal.address) {
+        Ok(receiver) => receiver,
+        Err(err) => {
+            return Ok(error_reply(
+                &FaucetError::invalid_request(err.to_string()),
+                &headers,
+            ))
+        },
+    };
+    if proposal.amount == 0 || proposal.reason.trim().is_empty() {
+        return Ok(error_reply(
+            &FaucetError::invalid_request("Grants must have an amount and a reason".to_string()),
+            &headers,
+        ));
+    }
+    Ok(
+        match grants
+            .propose(
+                &operator.name,
+                receiver,
+                proposal.amount,
+                proposal.reason,
+                now_secs(),
+            )
+            .await
+        {
+            Ok(grant) => Box::new(warp::reply::with_status(
+                warp::reply::json(&grant),
+                StatusCode::CREATED,
+            )),
+            Err(err) => error_reply(&FaucetError::internal(err), &headers),
+        },
+    )
+}
+
+async fn handle_decision(
+    grant_id: String,
+    decision: String,
+    authorization: Option<String>,
+    headers: HeaderMap,
+    service: Arc<Service>,
+) -> std::result::Result<Box<dyn Reply>, Infallible> {
+    let (grants, operator) = match authorize(&service, authorization.as_deref()) {
+        Ok(authorized) => authorized,
+        Err(reply) => return Ok(reply),
+    };
+    let decided = match decision.as_str() {
+        "approve" => grants.approve(&grant_id, &operator.name, now_secs()).await,
+        "reject" => grants.reject(&grant_id, &operator.name, now_secs()).await,
+        _ => return Ok(Box::new(StatusCode::NOT_FOUND)),
+    };
+    let grant = match decided {
+        Ok(Ok(grant)) => grant,
+        Ok(Err(refusal)) => {
+            return Ok(Box::new(warp::reply::with_status(
+                refusal.to_string(),
+                refusal.status_code(),
+            )))
+        },
+        Err(err) => return Ok(error_reply(&FaucetError::internal(err), &headers)),
+    };
+    if grant.status != GrantStatus::Funding {
+        return Ok(Box::new(warp::reply::json(&grant)));
+    }
+
+    let result = fund(&service, grant.receiver, grant.amount).await;
+    if let Some(webhooks) = service.webhooks() {
+        webhooks.notify(match &result {
+            Ok(txn_hashes) => {
+                FundingEvent::funded(Some(grant.receiver), grant.amount, txn_hashes.clone())
+            },
+            Err(err) => FundingEvent::failed(Some(grant.receiver), grant.amount, err.to_string()),
+        });
+    }
+    Ok(
+        match grants.record_funding(&grant_id, &result, now_secs()).await {
+            Ok(grant) if grant.status == GrantStatus::Funded => Box::new(warp::reply::json(&grant)),
+            Ok(grant) => Box::new(warp::reply::with_status(
+                warp::reply::json(&grant),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )),
+            Err(err) => error_reply(&FaucetError::internal(err), &headers),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditAction, AuditRecord, GrantRefusal, GrantStatus, Grants, Operator};
+    use aptos_sdk::types::account_address::AccountAddress;
+
+    #[tokio::test]
+    async fn test_grants() {
+        let audit_file = tempfile::NamedTempFile::new().unwrap();
+        let operators = vec![
+            Operator {
+                name: "alice".to_string(),
+                token: "s3cret".to_string(),
+            },
+            Operator {
+                name: "bob".to_string(),
+                token: "t0ps3cret".to_string(),
+            },
+        ];
+        let grants = Grants::new(operators, 100, audit_file.path()).unwrap();
+        assert_eq!(
+            grants.operator(Some("Bearer t0ps3cret")).unwrap().name,
+            "bob"
+        );
+        assert!(grants.operator(Some("Bearer s3cre")).is_none());
+        assert!(grants.operator(None).is_none());
+
+        let receiver = AccountAddress::ONE;
+        let funded = grants
+            .propose("alice", receiver, 1000, "hackathon".to_string(), 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            grants
+                .approve(&funded.grant_id, "alice", 1)
+                .await
+                .unwrap()
+                .unwrap_err(),
+            GrantRefusal::SelfApproval
+        );
+        let approved = grants
+            .approve(&funded.grant_id, "bob", 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(approved.status, GrantStatus::Funding);
+        assert_eq!(
+            grants
+                .reject(&funded.grant_id, "alice", 3)
+                .await
+                .unwrap()
+                .unwrap_err(),
+            GrantRefusal::NotPending(GrantStatus::Funding)
+        );
+        grants
+            .record_funding(&funded.grant_id, &Ok(vec![]), 3)
+            .await
+            .unwrap();
+
+        let expired = grants
+            .propose("bob", receiver, 2000, "partner".to_string(), 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            grants
+                .approve(&expired.grant_id, "alice", 111)
+                .await
+                .unwrap()
+                .unwrap_err(),
+            GrantRefusal::NotPending(GrantStatus::Expired)
+        );
+        assert_eq!(
+            grants
+                .approve("unknown", "alice", 111)
+                .await
+                .unwrap()
+                .unwrap_err(),
+            GrantRefusal::NotFound
+        );
+        let list = grants.list(111).await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].status, GrantStatus::Expired);
+        assert_eq!(list[1].status, GrantStatus::Funded);
+        assert_eq!(list[1].decided_by.as_deref(), Some("bob"));
+        // Forgotten a day after being decided on.
+        assert!(grants.list(111 + 24 * 3600).await.unwrap().is_empty());
+
+        let trail: Vec<(AuditAction, Option<String>)> = std::fs::read_to_string(audit_file.path())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let record: AuditRecord = serde_json::from_str(line).unwrap();
+                (record.action, record.operator)
+            })
+            .collect();
+        assert_eq!(trail, vec![
+            (AuditAction::Proposed, Some("alice".to_string())),
+            (AuditAction::Approved, Some("bob".to_string())),
+            (AuditAction::Funded, None),
+            (AuditAction::Proposed, Some("bob".to_string())),
+            (AuditAction::Expired, None),
+        ]);
+    }
+}
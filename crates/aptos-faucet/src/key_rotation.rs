@@ -0,0 +1,304 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Rotating the key of the faucet account while the faucet runs. The next key is read from
+//! `--next-mint-key-file`, e.g. a mounted secret updated ahead of the rotation, which then:
+//!
+//! 1. pauses funding: requests get a 503 telling them to retry shortly, and transactions being
+//!    signed are let through first,
+//! 2. submits the rotation of the authentication key of the account to that of the next key,
+//!    signed with both keys, and waits for it,
+//! 3. verifies the authentication key on chain, and swaps the next key in,
+//! 4. resumes funding, with the next key.
+//!
+//! If the account was rotated to the next key already, e.g. by another replica funding from the
+//! same account, the key is only swapped in, so the rotation can be run on every replica in turn.
+//! The mint key the faucet starts with should be updated to the next key afterwards, for restarts.
+//!
+//! Served on `POST /admin/rotate_key` to requests with `Authorization: Bearer <admin token>`, and
+//! not served at all without `--next-mint-key-file`.
+
+use crate::{
+    errors::{error_reply, FaucetError, FaucetErrorCode},
+    read_private_key, Service,
+};
+use anyhow::{bail, ensure, format_err, Context, Result};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, hash::HashValue, PrivateKey, SigningKey};
+use aptos_logger::info;
+use aptos_sdk::{
+    transaction_builder::aptos_stdlib,
+    types::{
+        account_address::AccountAddress, account_config::CORE_CODE_ADDRESS,
+        transaction::authenticator::AuthenticationKey,
+    },
+};
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use warp::{
+    http::{HeaderMap, StatusCode},
+    Filter, Rejection, Reply,
+};
+
+/// How long clients are told to wait while funding is paused. Rotations take a few seconds.
+const PAUSE_RETRY_AFTER_SECS: u64 = 10;
+
+/// The `0x1::account::RotationProofChallenge` signed by both keys, preceded by its type info, as
+/// the framework verifies it.
+#[derive(Serialize)]
+struct RotationProofChallenge {
+    account_address: AccountAddress,
+    module_name: String,
+    struct_name: String,
+    sequence_number: u64,
+    originator: AccountAddress,
+    current_auth_key: AccountAddress,
+    new_public_key: Vec<u8>,
+}
+
+/// Body of `POST /admin/rotate_key`.
+#[derive(Debug, Serialize)]
+pub struct RotationBody {
+    pub address: String,
+    pub previous_auth_key: String,
+    pub auth_key: String,
+    /// Hash of the rotation transaction, if the account wasn't rotated to the next key already.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txn_hash: Option<HashValue>,
+}
+
+pub struct KeyRotation {
+    next_key_file: PathBuf,
+    /// Whether funding from the faucet account is paused, for its key to be rotated.
+    paused: AtomicBool,
+}
+
+/// Resumes funding once dropped, however the rotation ended.
+struct Pause<'a>(&'a AtomicBool);
+
+impl Drop for Pause<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl KeyRotation {
+    pub fn new(next_key_file: PathBuf) -> Self {
+        Self {
+            next_key_file,
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Fails while funding from the faucet account is paused.
+    pub fn ensure_not_paused(&self) -> Result<(), FaucetError> {
+        if self.paused.load(Ordering::SeqCst) {
+            return Err(FaucetError::new(
+                FaucetErrorCode::Overloaded,
+                "Funding is paused while the key of the faucet account is rotated".to_string(),
+            )
+            .with_retry_after_secs(PAUSE_RETRY_AFTER_SECS));
+        }
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<Pause<'_>> {
+        if self
+            .paused
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            bail!(FaucetError::new(
+                FaucetErrorCode::Overloaded,
+                "The key of the faucet account is being rotated already".to_string(),
+            ));
+        }
+        Ok(Pause(&self.paused))
+    }
+
+    pub async fn rotate(&self, service: &Service) -> Result<RotationBody> {
+        let next_key = read_private_key(&self.next_key_file).with_context(|| {
+            format!(
+                "Failed to read the next mint key file {}",
+                self.next_key_file.display()
+            )
+        })?;
+        let next_auth_key = AuthenticationKey::ed25519(&next_key.public_key());
+
+        let _pause = self.pause()?;
+        // Held until the next key is swapped in: transactions being signed with the current key
+        // are signed before the rotation, and none after until then.
+        let mut faucet_account = service.faucet_account.lock().await;
+        let address = faucet_account.address();
+        let onchain = service.client.get_account(address).await?.into_inner();
+        let previous_auth_key = onchain.authentication_key;
+
+        let txn_hash = if previous_auth_key == next_auth_key {
+            info!(
+                "[faucet]: {} was rotated to the next key already, swapping it in",
+                address
+            );
+            None
+        } else {
+            ensure!(
+                previous_auth_key == faucet_account.authentication_key(),
+                "The authentication key of {} on chain, {}, is neither that of the current key \
+                nor that of the next key",
+                address,
+                previous_auth_key
+            );
+            // The rotation takes the next sequence number, after the transactions submitted
+            // already, which are checked against the current key.
+            let sequence_number = match service.shared_sequence_numbers() {
+                Some(shared_sequence_numbers) => shared_sequence_numbers
+                    .lease(onchain.sequence_number)
+                    .await?
+                    .ok_or_else(|| {
+                        format_err!("Too many outstanding transactions to rotate the key")
+                    })?,
+                None => std::cmp::max(onchain.sequence_number, faucet_account.sequence_number()),
+            };
+            let challenge = bcs::to_bytes(&RotationProofChallenge {
+                account_address: CORE_CODE_ADDRESS,
+                module_name: "account".to_string(),
+                struct_name: "RotationProofChallenge".to_string(),
+                sequence_number,
+                originator: address,
+                current_auth_key: previous_auth_key.derived_address(),
+                new_public_key: next_key.public_key().to_bytes().to_vec(),
+            })?;
+            let payload = aptos_stdlib::account_rotate_authentication_key(
+                0,
+                faucet_account.public_key().to_bytes().to_vec(),
+                0,
+                next_key.public_key().to_bytes().to_vec(),
+                sign(faucet_account.private_key(), &challenge),
+                sign(&next_key, &challenge),
+            );
+            *faucet_account.sequence_number_mut() = sequence_number;
+            let txn = faucet_account
+                .sign_with_transaction_builder(service.transaction_factory.payload(payload));
+            if let Err(e) = service.client.submit(&txn).await {
+                match service.shared_sequence_numbers() {
+                    Some(shared_sequence_numbers) => {
+                        shared_sequence_numbers
+                            .reset(onchain.sequence_number)
+                            .await?
+                    },
+                    None => *faucet_account.sequence_number_mut() = sequence_number,
+                }
+                return Err(e).context("Failed to submit the key rotation");
+            }
+            service
+                .client
+                .wait_for_signed_transaction(&txn)
+                .await
+                .context("The key rotation didn't go through")?;
+            Some(txn.committed_hash())
+        };
+
+        let rotated_auth_key = service
+            .client
+            .get_account(address)
+            .await?
+            .into_inner()
+            .authentication_key;
+        ensure!(
+            rotated_auth_key == next_auth_key,
+            "The authentication key of {} on chain is {} after the rotation, expected {}",
+            address,
+            rotated_auth_key,
+            next_auth_key
+        );
+        faucet_account.rotate_key(next_key);
+        info!(
+            "[faucet]: rotated the key of {} from {} to {}, resuming funding",
+            address, previous_auth_key, next_auth_key
+        );
+        Ok(RotationBody {
+            address: address.to_hex_literal(),
+            previous_auth_key: previous_auth_key.to_string(),
+            auth_key: next_auth_key.to_string(),
+            txn_hash,
+        })
+    }
+}
+
+fn sign(key: &Ed25519PrivateKey, challenge: &[u8]) -> Vec<u8> {
+    key.sign_arbitrary_message(challenge).to_bytes().to_vec()
+}
+
+pub fn key_rotation_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // POST /admin/rotate_key
+    warp::path!("admin" / "rotate_key")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(move || service.clone()))
+        .and_then(handle_rotate_key)
+}
+
+async fn handle_rotate_key(
+    authorization: Option<String>,
+    headers: HeaderMap,
+    service: Arc<Service>,
+) -> Result<Box<dyn Reply>, Infallible> {
+    // The admin token is that of the triage stats.
+    match (service.key_rotation(), service.triage_stats()) {
+        (Some(_), Some(stats)) if stats.is_authorized(authorization.as_deref()) => (),
+        (Some(_), Some(_)) => return Ok(Box::new(StatusCode::UNAUTHORIZED)),
+        _ => return Ok(Box::new(StatusCode::NOT_FOUND)),
+    }
+    // Rotated apart from the request, so that a client going away doesn't abort the rotation
+    // halfway.
+    let rotation = tokio::spawn(async move {
+        let key_rotation = service.key_rotation().expect("Checked above");
+        key_rotation.rotate(&service).await
+    })
+    .await;
+    Ok(match rotation {
+        Ok(Ok(body)) => Box::new(warp::reply::json(&body)),
+        Ok(Err(err)) => {
+            let error = match err.downcast::<FaucetError>() {
+                Ok(error) => error,
+                Err(err) => FaucetError::internal(err),
+            };
+            error_reply(&error, &headers)
+        },
+        Err(err) => error_reply(&FaucetError::internal(err.into()), &headers),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyRotation, PAUSE_RETRY_AFTER_SECS};
+    use crate::errors::FaucetErrorCode;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_pause() {
+        let rotation = KeyRotation::new(PathBuf::from("next.key"));
+        rotation.ensure_not_paused().unwrap();
+        {
+            let _pause = rotation.pause().unwrap();
+            // One rotation at once.
+            assert!(rotation.pause().is_err());
+            let error = rotation.ensure_not_paused().unwrap_err();
+            assert_eq!(error.code, FaucetErrorCode::Overloaded);
+            assert_eq!(error.retry_after_secs, Some(PAUSE_RETRY_AFTER_SECS));
+        }
+        // Resumed however the rotation ended.
+        rotation.ensure_not_paused().unwrap();
+    }
+}
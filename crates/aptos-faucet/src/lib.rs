@@ -23,70 +23,465 @@
 //! cargo run -p aptos-faucet -- -h
 //! ```
 
+use account_pool::AccountPool;
+use ans::AnsResolver;
 use anyhow::Result;
 use aptos_config::keys::ConfigKey;
-use aptos_crypto::ed25519::Ed25519PrivateKey;
-use aptos_logger::info;
-use aptos_rest_client::Client;
+use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
+use aptos_logger::{info, warn};
+use aptos_rest_client::{aptos_api_types::EntryFunctionId, Client};
 use aptos_sdk::{
     transaction_builder::{aptos_stdlib, TransactionFactory},
     types::{
         account_address::AccountAddress, account_config::aptos_test_root_address,
-        chain_id::ChainId, LocalAccount,
+        chain_id::ChainId, transaction::authenticator::AuthenticationKey, LocalAccount,
     },
 };
+use checkers::{
+    run_checker, CaptchaVerifier, Checker, CheckerData, CheckerScheduler, CheckerTimeout,
+    CheckerTimeoutPolicy, CooldownConfig, GeoPolicies, GeoPolicyChecker, IpRateLimitChecker,
+    LimitSchedule, OnChainEligibilityChecker, PowConfig, ProofOfWorkChecker,
+    ReceiverAllowlistChecker, ReceiverCooldownChecker, RejectionReason, ShadowBanChecker,
+    SharedReputationChecker, VelocityChecker, VelocityConfig,
+};
+use chrono::{DateTime, Utc};
 use clap::Parser;
+use fake_funder::{FakeFunder, FakeFunderConfig};
 use futures::lock::Mutex;
+use grants::{Grants, Operator};
+use ipnet::IpNet;
+use key_rotation::KeyRotation;
+use mint::ExplorerUrlTemplate;
+use queue::MintQueue;
+use reputation::{IpReputation, ReputationConfig};
 use reqwest::StatusCode;
-use std::{convert::Infallible, path::PathBuf, sync::Arc};
+use runway::{RunwayConfig, RunwayMonitor};
+use sequence_numbers::SharedSequenceNumbers;
+use stats::TriageStats;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use treasury::Treasury;
 use url::Url;
-use warp::{http, Filter, Rejection, Reply};
+use warp::{http, http::HeaderMap, Filter, Rejection, Reply};
+use webhooks::{WebhookConfig, Webhooks};
 
+pub mod account_pool;
+pub mod ans;
+pub mod batch;
+pub mod checkers;
+pub mod client_ip;
+pub mod config;
+pub mod errors;
+pub mod fake_funder;
+pub mod grants;
+pub mod key_rotation;
+pub mod metrics;
 pub mod mint;
+pub mod queue;
+pub mod reputation;
+pub mod runway;
+pub mod sequence_numbers;
+pub mod stats;
+#[cfg(any(test, feature = "testing"))]
+pub mod test_support;
+pub mod treasury;
+pub mod validation;
+pub mod webhooks;
+
+/// Maximum number of transactions from the faucet account waiting to be committed.
+pub(crate) const MAX_OUTSTANDING_TRANSACTIONS: u64 = 50;
+/// Shared sequence numbers expire after this long without use, the same as the transactions.
+const SHARED_SEQUENCE_NUMBER_EXPIRATION_SECS: u64 = 30;
+/// Header of the replies carrying the factor amounts were scaled down by.
+pub const AMOUNT_SCALE_HEADER: &str = "X-Aptos-Faucet-Amount-Scale";
+/// How often the balance of the funder is polled for its runway.
+const RUNWAY_POLL_INTERVAL_SECS: u64 = 60;
+
+/// Reads a private key file, holding either the BCS bytes of the key or the key as a hex string.
+fn read_private_key(path: &Path) -> Result<Ed25519PrivateKey> {
+    let bytes = std::fs::read(path)?;
+    bcs::from_bytes(&bytes)
+        .ok()
+        .or_else(|| {
+            let encoded = std::str::from_utf8(&bytes).ok()?.trim();
+            ConfigKey::<Ed25519PrivateKey>::from_encoded_string(encoded)
+                .ok()
+                .map(|key| key.private_key())
+        })
+        .ok_or_else(|| anyhow::format_err!("Failed to deserialize {}", path.display()))
+}
+
+/// Compares tokens without short-circuiting, not to leak through timing how much of a guess is
+/// right.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
 
 /// Aptos Testnet utility service for creating test accounts and minting test coins
 #[derive(Clone, Debug, Parser)]
 #[clap(name = "Aptos Faucet", author, version)]
 pub struct FaucetArgs {
     /// Faucet service listen address
-    #[clap(short = 'a', long, default_value = "127.0.0.1")]
+    #[clap(
+        short = 'a',
+        long,
+        env = "FAUCET__ADDRESS",
+        default_value = "127.0.0.1"
+    )]
     pub address: String,
     /// Faucet service listen port
-    #[clap(short = 'p', long, default_value = "80")]
+    #[clap(short = 'p', long, env = "FAUCET__PORT", default_value = "80")]
     pub port: u16,
     /// Aptos fullnode/validator server URL
-    #[clap(short = 's', long, default_value = "https://testnet.aptoslabs.com/")]
+    #[clap(
+        short = 's',
+        long,
+        env = "FAUCET__SERVER_URL",
+        default_value = "https://testnet.aptoslabs.com/"
+    )]
     pub server_url: Url,
     /// Path to the private key for creating test account and minting coins.
     /// To keep Testnet simple, we used one private key for aptos root account
     /// To manually generate a keypair, use generate-key:
     /// `cargo run -p generate-keypair -- -o <output_file_path>`
+    /// The file may hold the BCS bytes of the key, or the key as a hex string, as is convenient
+    /// for mounted secrets.
     #[clap(
         short = 'm',
         long,
+        env = "FAUCET__MINT_KEY_FILE",
         default_value = "/opt/aptos/etc/mint.key",
         parse(from_os_str)
     )]
     pub mint_key_file_path: PathBuf,
     /// Ed25519PrivateKey for minting coins
-    #[clap(long, parse(try_from_str = ConfigKey::from_encoded_string))]
+    #[clap(
+        long,
+        env = "FAUCET__MINT_KEY",
+        hide_env_values = true,
+        parse(try_from_str = ConfigKey::from_encoded_string)
+    )]
     pub mint_key: Option<ConfigKey<Ed25519PrivateKey>>,
     /// Address of the account to send transactions from.
     /// On Testnet, for example, this is a550c18.
     /// If not present, the mint key's address is used
-    #[clap(short = 't', long, parse(try_from_str = AccountAddress::from_hex_literal))]
+    #[clap(
+        short = 't',
+        long,
+        env = "FAUCET__MINT_ACCOUNT_ADDRESS",
+        parse(try_from_str = AccountAddress::from_hex_literal)
+    )]
     pub mint_account_address: Option<AccountAddress>,
     /// Chain ID of the network this client is connecting to.
     /// For mainnet: "MAINNET" or 1, testnet: "TESTNET" or 2, devnet: "DEVNET" or 3,
     /// local swarm: "TESTING" or 4
     /// Note: Chain ID of 0 is not allowed; Use number if chain id is not predefined.
-    #[clap(short = 'c', long, default_value = "2")]
+    #[clap(short = 'c', long, env = "FAUCET__CHAIN_ID", default_value = "2")]
     pub chain_id: ChainId,
     /// Maximum amount of coins to mint.
-    #[clap(long)]
+    #[clap(long, env = "FAUCET__MAXIMUM_AMOUNT")]
     pub maximum_amount: Option<u64>,
-    #[clap(long)]
+    #[clap(long, env = "FAUCET__DO_NOT_DELEGATE")]
     pub do_not_delegate: bool,
+    /// CIDR of a load balancer or reverse proxy in front of the faucet, e.g. 10.0.0.0/8.
+    /// The client address is taken from the Forwarded / X-Forwarded-For headers set by
+    /// these, rather than from the connection. Can be repeated.
+    #[clap(
+        long = "trusted-proxy",
+        env = "FAUCET__TRUSTED_PROXIES",
+        value_delimiter = ','
+    )]
+    pub trusted_proxies: Vec<IpNet>,
+    /// Maximum number of mint requests a client IP can make per day
+    #[clap(long, env = "FAUCET__MAX_REQUESTS_PER_IP_PER_DAY")]
+    pub max_requests_per_ip_per_day: Option<u64>,
+    /// YAML file describing windows (e.g. hackathon weekends) during which the rate limits are
+    /// multiplied, and the timezone they are expressed in. See `checkers::LimitSchedule`.
+    /// Only used along with `--max-requests-per-ip-per-day`.
+    #[clap(long, env = "FAUCET__RATE_LIMIT_SCHEDULE_FILE", parse(from_os_str))]
+    pub rate_limit_schedule_file: Option<PathBuf>,
+    /// `siteverify` API of the captcha provider, e.g. https://hcaptcha.com/siteverify, to let
+    /// clients over the daily IP limit through if they solve a captcha. Along with
+    /// `--captcha-secret` and `--captcha-challenge-url`, and only used along with
+    /// `--max-requests-per-ip-per-day`. If not present, clients over the limit are turned away.
+    #[clap(long, env = "FAUCET__CAPTCHA_VERIFY_URL")]
+    pub captcha_verify_url: Option<Url>,
+    /// Secret of the site with the captcha provider
+    #[clap(long, env = "FAUCET__CAPTCHA_SECRET")]
+    pub captcha_secret: Option<String>,
+    /// Page where clients over the limit solve the captcha, returned in the rejection
+    #[clap(long, env = "FAUCET__CAPTCHA_CHALLENGE_URL")]
+    pub captcha_challenge_url: Option<Url>,
+    /// Make clients solve a proof of work with this many leading zero bits before being funded,
+    /// and more as their network makes more requests, see `--pow-requests-per-extra-bit`.
+    /// If not present, no proof of work is asked for.
+    #[clap(long, env = "FAUCET__POW_DIFFICULTY")]
+    pub pow_difficulty: Option<u8>,
+    /// Cap on the proof of work difficulty
+    #[clap(long, env = "FAUCET__POW_MAX_DIFFICULTY", default_value = "24")]
+    pub pow_max_difficulty: u8,
+    /// The proof of work difficulty goes up a bit at this many requests from the network of the
+    /// client in the last 10 minutes, and another one every time they double
+    #[clap(long, env = "FAUCET__POW_REQUESTS_PER_EXTRA_BIT", default_value = "10")]
+    pub pow_requests_per_extra_bit: u64,
+    /// Secret authenticating the proof of work challenges, for replicas to accept those issued
+    /// by one another. If not present, each replica picks one of its own.
+    #[clap(long, env = "FAUCET__POW_SECRET")]
+    pub pow_secret: Option<String>,
+    /// Reject requests when the recent request rate from the network (or autonomous system) of
+    /// the client exceeds this multiple of its hourly baseline.
+    #[clap(long, env = "FAUCET__VELOCITY_MULTIPLIER")]
+    pub velocity_multiplier: Option<f64>,
+    /// Request rate per minute under which a network is never considered anomalous.
+    /// Only used along with `--velocity-multiplier`.
+    #[clap(
+        long,
+        env = "FAUCET__VELOCITY_MIN_REQUESTS_PER_MINUTE",
+        default_value = "10"
+    )]
+    pub velocity_min_requests_per_minute: f64,
+    /// MaxMind database mapping IPs to autonomous systems, e.g. GeoLite2-ASN.mmdb, to also
+    /// track velocity per autonomous system. Only used along with `--velocity-multiplier`.
+    #[clap(long, env = "FAUCET__ASN_DATABASE_FILE", parse(from_os_str))]
+    pub asn_database_file: Option<PathBuf>,
+    /// MaxMind database mapping IPs to countries, e.g. GeoLite2-Country.mmdb, to apply the
+    /// policies of `--geo-policy-file`.
+    #[clap(long, env = "FAUCET__COUNTRY_DATABASE_FILE", parse(from_os_str))]
+    pub country_database_file: Option<PathBuf>,
+    /// YAML file of per country policies: block, require a captcha, or a lower daily limit per
+    /// IP. See `checkers::GeoPolicies`. Along with `--country-database-file`.
+    #[clap(long, env = "FAUCET__GEO_POLICY_FILE", parse(from_os_str))]
+    pub geo_policy_file: Option<PathBuf>,
+    /// Make each receiver wait this many seconds after its first funding, and longer after each
+    /// one after, see `--receiver-cooldown-factor`. Tracked in Redis if `--redis-url` is given,
+    /// for all replicas to share it, and in memory otherwise. If not present, receivers can be
+    /// funded as often as the other limits allow.
+    #[clap(long, env = "FAUCET__RECEIVER_COOLDOWN_SECS")]
+    pub receiver_cooldown_secs: Option<u64>,
+    /// Each funding of a receiver multiplies its cooldown by this
+    #[clap(long, env = "FAUCET__RECEIVER_COOLDOWN_FACTOR", default_value = "4")]
+    pub receiver_cooldown_factor: f64,
+    /// Cap on the cooldown of a receiver
+    #[clap(
+        long,
+        env = "FAUCET__RECEIVER_MAX_COOLDOWN_SECS",
+        default_value = "604800"
+    )]
+    pub receiver_max_cooldown_secs: u64,
+    /// Redis server the receiver cooldowns are being moved to, e.g. redis://new-redis:6379.
+    /// Fundings are then recorded in both it and the current store, and mismatches between the
+    /// two are logged and counted, see `aptos_faucet_cooldown_store_mismatches`.
+    #[clap(long, env = "FAUCET__RECEIVER_COOLDOWN_MIGRATION_REDIS_URL")]
+    pub receiver_cooldown_migration_redis_url: Option<String>,
+    /// Answer from the store given with `--receiver-cooldown-migration-redis-url` rather than
+    /// the current one, which is still written to, to be able to go back.
+    #[clap(long, env = "FAUCET__RECEIVER_COOLDOWN_MIGRATION_READ_TARGET")]
+    pub receiver_cooldown_migration_read_target: bool,
+    /// File listing the only receivers that may be funded, one address per line, e.g. the CI
+    /// accounts of a private devnet. Reloaded when it changes.
+    #[clap(long, env = "FAUCET__RECEIVER_ALLOWLIST_FILE", parse(from_os_str))]
+    pub receiver_allowlist_file: Option<PathBuf>,
+    /// Move view function deciding whether a receiver may be funded, called with its address,
+    /// e.g. 0xcafe::allowlist::is_eligible. Receivers it returns false for are rejected.
+    #[clap(long, env = "FAUCET__ELIGIBILITY_VIEW_FUNCTION")]
+    pub eligibility_view_function: Option<EntryFunctionId>,
+    /// IP range, e.g. 203.0.113.0/24, whose requests are answered as if accepted but never
+    /// funded. Can be repeated.
+    #[clap(
+        long = "shadow-ban-cidr",
+        env = "FAUCET__SHADOW_BAN_CIDRS",
+        value_delimiter = ','
+    )]
+    pub shadow_ban_cidrs: Vec<IpNet>,
+    /// Run the checkers cheapest and most likely to reject first, as measured on the requests
+    /// so far, and stop at the first rejection, rather than running all of them in the order
+    /// configured. Clients then only learn of one rejection. See `checkers::CheckerScheduler`.
+    #[clap(long, env = "FAUCET__ADAPTIVE_CHECKER_ORDER")]
+    pub adaptive_checker_order: bool,
+    /// Gives up on a checker, e.g. one waiting on a hung remote service, after this long, and
+    /// goes on as `--checker-timeout-policy` says. If not present, checkers are waited for.
+    #[clap(long, env = "FAUCET__CHECKER_TIMEOUT_MS")]
+    pub checker_timeout_ms: Option<u64>,
+    /// What becomes of a request whose checker timed out: `skip` the checker, as if it accepted
+    /// the request, or `reject` the request with a 503, for the client to retry later.
+    #[clap(long, default_value = "skip", env = "FAUCET__CHECKER_TIMEOUT_POLICY")]
+    pub checker_timeout_policy: CheckerTimeoutPolicy,
+    /// Shared store through which deployments, e.g. the devnet and testnet faucets, exchange the
+    /// IP prefixes they shadow banned or saw spike in velocity, see `reputation`. Prefixes
+    /// flagged by other deployments are then treated as they were there. If not present, abuse
+    /// signals are not shared.
+    #[clap(long, env = "FAUCET__REPUTATION_SYNC_URL")]
+    pub reputation_sync_url: Option<Url>,
+    /// Name of this deployment in the shared store, e.g. testnet. Defaults to `--server-url`.
+    #[clap(long, env = "FAUCET__REPUTATION_DEPLOYMENT")]
+    pub reputation_deployment: Option<String>,
+    /// How often to sync with the shared store
+    #[clap(
+        long,
+        env = "FAUCET__REPUTATION_SYNC_INTERVAL_SECS",
+        default_value = "60"
+    )]
+    pub reputation_sync_interval_secs: u64,
+    /// How long a flagged prefix is shared and enforced for after it was last flagged
+    #[clap(long, env = "FAUCET__REPUTATION_TTL_SECS", default_value = "86400")]
+    pub reputation_ttl_secs: u64,
+    /// ANS REST API used to resolve names (e.g. alice.apt) given instead of an address,
+    /// e.g. https://www.aptosnames.com/api/testnet/v1/address/
+    /// If not present, funding by name is disabled.
+    #[clap(long, env = "FAUCET__ANS_RESOLVER_URL")]
+    pub ans_resolver_url: Option<Url>,
+    /// How long resolved names are cached for
+    #[clap(long, env = "FAUCET__ANS_CACHE_TTL_SECS", default_value = "300")]
+    pub ans_cache_ttl_secs: u64,
+    /// YAML file with the base configuration, keyed by argument name, e.g. `server_url`.
+    /// Arguments given on the command line or through their environment variable take
+    /// precedence. See `config`.
+    #[clap(long, env = "FAUCET__CONFIG_FILE", parse(from_os_str))]
+    pub config_file: Option<PathBuf>,
+    /// Preset for running against a local testnet, e.g. `aptos node run-local-testnet`: funds
+    /// from the mint key of the testnet, on localhost, without any checker, keeping everything
+    /// in memory. Presets only apply to arguments not given otherwise. See `config`.
+    #[clap(
+        long,
+        env = "FAUCET__DEV_MODE",
+        conflicts_with_all = &[
+            "redis-url",
+            "receiver-cooldown-migration-redis-url",
+            "max-requests-per-ip-per-day",
+            "captcha-verify-url",
+            "pow-difficulty",
+            "velocity-multiplier",
+            "country-database-file",
+            "geo-policy-file",
+            "receiver-allowlist-file",
+            "eligibility-view-function",
+            "receiver-cooldown-secs",
+            "shadow-ban-cidrs",
+            "reputation-sync-url",
+        ]
+    )]
+    pub dev_mode: bool,
+    /// Redis server through which replicas funding from the same account share its sequence
+    /// numbers, e.g. redis://redis:6379. Only used along with `--do-not-delegate`, as otherwise
+    /// each replica funds from an account of its own. Also holds the receiver cooldowns, if
+    /// enabled.
+    #[clap(long, env = "FAUCET__REDIS_URL")]
+    pub redis_url: Option<String>,
+    /// Keep this many accounts created and funded ahead of time, and hand them out, private key
+    /// included, on `POST /account`. If not present, accounts are not handed out.
+    #[clap(long, env = "FAUCET__ACCOUNT_POOL_SIZE")]
+    pub account_pool_size: Option<usize>,
+    /// Amount each account of the pool is funded with
+    #[clap(long, env = "FAUCET__ACCOUNT_POOL_AMOUNT", default_value = "100000000")]
+    pub account_pool_amount: u64,
+    /// URL of the explorer page of a transaction, with `{txn_hash}` standing for its hash, e.g.
+    /// https://explorer.aptoslabs.com/txn/{txn_hash}?network=testnet
+    /// Returned along with the hashes when the request has `detailed=true`.
+    #[clap(long, env = "FAUCET__EXPLORER_URL_TEMPLATE")]
+    pub explorer_url_template: Option<ExplorerUrlTemplate>,
+    /// Maximum number of receivers in a single `POST /fund_batch` request
+    #[clap(long, env = "FAUCET__MAX_BATCH_SIZE", default_value = "100")]
+    pub max_batch_size: usize,
+    /// Scale amounts down when the funder would run out in less than this many seconds at the
+    /// rate it spent over the last `--runway-window-secs`, e.g. 86400 for a day. The current
+    /// scale is exported as a metric and returned in a header. If not present, amounts are never
+    /// scaled down.
+    #[clap(long, env = "FAUCET__MIN_RUNWAY_SECS")]
+    pub min_runway_secs: Option<u64>,
+    /// Window the spend rate of the funder is measured over
+    #[clap(long, env = "FAUCET__RUNWAY_WINDOW_SECS", default_value = "3600")]
+    pub runway_window_secs: u64,
+    /// Factor amounts are never scaled down below, however short the runway
+    #[clap(long, env = "FAUCET__RUNWAY_MIN_SCALE", default_value = "0.1")]
+    pub runway_min_scale: f64,
+    /// Queue up to this many mint requests when the faucet is busy, rather than have them time
+    /// out, answering with their position, or with an id to poll if they ask for `async=true`.
+    /// Only requests over the capacity are turned away. If not present, requests aren't queued.
+    #[clap(long, env = "FAUCET__QUEUE_CAPACITY")]
+    pub queue_capacity: Option<usize>,
+    /// Number of queued requests processed at once
+    #[clap(long, env = "FAUCET__QUEUE_CONCURRENCY", default_value = "8")]
+    pub queue_concurrency: usize,
+    /// Queued requests processing for longer than this are marked failed, answering their
+    /// clients with an error and no longer counting as in flight
+    #[clap(long, env = "FAUCET__QUEUE_STALE_REQUEST_SECS", default_value = "300")]
+    pub queue_stale_request_secs: u64,
+    /// Fund requests for more than this from the treasury account of `--treasury-key-file`,
+    /// rather than from the mint account, only if they carry `--treasury-approval-token` and
+    /// pass the stricter treasury checkers. See `treasury`. If not present, every request is
+    /// funded from the mint account.
+    #[clap(long, env = "FAUCET__TREASURY_THRESHOLD")]
+    pub treasury_threshold: Option<u64>,
+    /// Private key of the treasury account, in the same formats as `--mint-key-file-path`
+    #[clap(long, env = "FAUCET__TREASURY_KEY_FILE", parse(from_os_str))]
+    pub treasury_key_file: Option<PathBuf>,
+    /// Address of the treasury account, if it isn't the one derived from its key
+    #[clap(
+        long,
+        env = "FAUCET__TREASURY_ACCOUNT_ADDRESS",
+        parse(try_from_str = AccountAddress::from_hex_literal)
+    )]
+    pub treasury_account_address: Option<AccountAddress>,
+    /// Token that requests funded from the treasury must carry in the X-Aptos-Faucet-Approval
+    /// header, handed out by whoever approves large grants
+    #[clap(long, env = "FAUCET__TREASURY_APPROVAL_TOKEN", hide_env_values = true)]
+    pub treasury_approval_token: Option<String>,
+    /// Maximum number of requests funded from the treasury a client IP can make per day
+    #[clap(
+        long,
+        env = "FAUCET__TREASURY_MAX_REQUESTS_PER_IP_PER_DAY",
+        default_value = "1"
+    )]
+    pub treasury_max_requests_per_ip_per_day: u64,
+    /// File listing the only receivers that may be funded from the treasury, one address per
+    /// line. If not present, any receiver accepted by the other checkers may be.
+    #[clap(
+        long,
+        env = "FAUCET__TREASURY_RECEIVER_ALLOWLIST_FILE",
+        parse(from_os_str)
+    )]
+    pub treasury_receiver_allowlist_file: Option<PathBuf>,
+    /// Serves counts of recent requests by rejection reason, funding outcome and network on
+    /// `GET /admin/stats`, to requests with `Authorization: Bearer <admin token>`. See `stats`.
+    /// If not present, the endpoint isn't served.
+    #[clap(long, env = "FAUCET__ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+    /// YAML file listing webhooks notified of fundings and their failures, with the secrets
+    /// their bodies are signed with and the events they ask for. See `webhooks`. If not present,
+    /// no notifications are sent.
+    #[clap(long, env = "FAUCET__WEBHOOKS_FILE", parse(from_os_str))]
+    pub webhooks_file: Option<PathBuf>,
+    /// Attempts at delivering a notification to a webhook, retrying with exponential backoff,
+    /// before logging it as a dead letter
+    #[clap(long, env = "FAUCET__WEBHOOK_MAX_ATTEMPTS", default_value = "5")]
+    pub webhook_max_attempts: u32,
+    /// YAML file configuring the latency and failures of a fake funder, which answers fundings
+    /// without submitting any transaction, to load test the rest of the service. See
+    /// `fake_funder`. Requires --do-not-delegate.
+    #[clap(long, env = "FAUCET__FAKE_FUNDER_CONFIG", parse(from_os_str))]
+    pub fake_funder_config: Option<PathBuf>,
+    /// YAML file listing the operators who may propose and approve manual grants, with their
+    /// tokens. Serves the `/admin/grants` endpoints, where a grant proposed by one operator is
+    /// only funded once approved by another. See `grants`. Requires --grant-audit-file.
+    #[clap(long, env = "FAUCET__GRANT_OPERATORS_FILE", parse(from_os_str))]
+    pub grant_operators_file: Option<PathBuf>,
+    /// File every step of the manual grants is appended to, as JSON lines
+    #[clap(long, env = "FAUCET__GRANT_AUDIT_FILE", parse(from_os_str))]
+    pub grant_audit_file: Option<PathBuf>,
+    /// Time a proposed grant has to be approved within, before it expires
+    #[clap(long, env = "FAUCET__GRANT_APPROVAL_TTL_SECS", default_value = "3600")]
+    pub grant_approval_ttl_secs: u64,
+    /// File to read the next mint key from, as the mint key file, to rotate the key of the
+    /// faucet account to on `POST /admin/rotate_key`, with the admin token, without restarting.
+    /// Funding is paused during the rotation. See `key_rotation`. Requires --admin-token and
+    /// --do-not-delegate.
+    #[clap(long, env = "FAUCET__NEXT_MINT_KEY_FILE", parse(from_os_str))]
+    pub next_mint_key_file: Option<PathBuf>,
 }
 
 impl FaucetArgs {
@@ -101,22 +496,286 @@ impl FaucetArgs {
             self.server_url.as_str(),
             self.maximum_amount,
         );
+        if self.dev_mode {
+            info!("[faucet]: dev mode, funding without any checker");
+        }
 
         let key = if let Some(ref key) = self.mint_key {
             key.private_key()
         } else {
-            bcs::from_bytes(
-                &std::fs::read(self.mint_key_file_path.as_path())
-                    .expect("Failed to read mint key file"),
-            )
-            .expect("Failed to deserialize mint key file")
+            read_private_key(self.mint_key_file_path.as_path())
+                .expect("Failed to read mint key file")
+        };
+
+        let schedule = match &self.rate_limit_schedule_file {
+            Some(path) => LimitSchedule::load(path).expect("Failed to load rate limit schedule"),
+            None => LimitSchedule::default(),
+        };
+        let captcha = match (
+            &self.captcha_verify_url,
+            &self.captcha_secret,
+            &self.captcha_challenge_url,
+        ) {
+            (Some(verify_url), Some(secret), Some(challenge_url)) => Some(Arc::new(
+                CaptchaVerifier::new(verify_url.clone(), secret.clone(), challenge_url.clone()),
+            )),
+            (None, None, None) => None,
+            _ => panic!(
+                "--captcha-verify-url, --captcha-secret and --captcha-challenge-url go together"
+            ),
         };
+        let reputation = self.reputation_sync_url.as_ref().map(|_| {
+            Arc::new(IpReputation::new(ReputationConfig {
+                deployment: self
+                    .reputation_deployment
+                    .clone()
+                    .unwrap_or_else(|| self.server_url.to_string()),
+                sync_interval_secs: self.reputation_sync_interval_secs,
+                ttl_secs: self.reputation_ttl_secs,
+            }))
+        });
+        let mut checkers: Vec<Arc<dyn Checker>> = self
+            .max_requests_per_ip_per_day
+            .map(|max_requests_per_day| {
+                let checker = IpRateLimitChecker::new(max_requests_per_day, schedule);
+                Arc::new(match &captcha {
+                    Some(captcha) => checker.with_captcha(captcha.clone()),
+                    None => checker,
+                }) as Arc<dyn Checker>
+            })
+            .into_iter()
+            .collect();
+        if let Some(multiplier) = self.velocity_multiplier {
+            let config = VelocityConfig {
+                multiplier,
+                min_requests_per_minute: self.velocity_min_requests_per_minute,
+                ..VelocityConfig::default()
+            };
+            let checker = VelocityChecker::new(config, self.asn_database_file.as_deref())
+                .expect("Failed to create velocity checker");
+            checkers.push(Arc::new(match &reputation {
+                Some(reputation) => checker.with_reputation(reputation.clone()),
+                None => checker,
+            }));
+        }
+        match (&self.country_database_file, &self.geo_policy_file) {
+            (Some(country_database_file), Some(geo_policy_file)) => {
+                let policies =
+                    GeoPolicies::load(geo_policy_file).expect("Failed to load geo policies");
+                checkers.push(Arc::new(
+                    GeoPolicyChecker::new(policies, country_database_file, captcha.clone())
+                        .expect("Failed to create geo policy checker"),
+                ));
+            },
+            (None, None) => (),
+            _ => panic!("--country-database-file and --geo-policy-file go together"),
+        }
+        if let Some(path) = &self.receiver_allowlist_file {
+            checkers.push(Arc::new(
+                ReceiverAllowlistChecker::new(path).expect("Failed to load receiver allowlist"),
+            ));
+        }
+        if let Some(function) = &self.eligibility_view_function {
+            checkers.push(Arc::new(OnChainEligibilityChecker::new(
+                Client::new(self.server_url.clone()),
+                function.clone(),
+            )));
+        }
+        if let Some(base_secs) = self.receiver_cooldown_secs {
+            let config = CooldownConfig {
+                base_secs,
+                factor: self.receiver_cooldown_factor,
+                max_secs: self.receiver_max_cooldown_secs,
+                ..CooldownConfig::default()
+            };
+            let checker = match &self.redis_url {
+                Some(redis_url) => ReceiverCooldownChecker::connect(config, redis_url)
+                    .await
+                    .expect("Failed to connect to Redis"),
+                None => ReceiverCooldownChecker::new(config),
+            };
+            let checker = match &self.receiver_cooldown_migration_redis_url {
+                Some(redis_url) => checker
+                    .with_migration(redis_url, self.receiver_cooldown_migration_read_target)
+                    .await
+                    .expect("Failed to connect to Redis"),
+                None => checker,
+            };
+            checkers.push(Arc::new(checker));
+        }
+        if let Some(base_difficulty) = self.pow_difficulty {
+            let config = PowConfig {
+                base_difficulty,
+                max_difficulty: self.pow_max_difficulty.max(base_difficulty),
+                requests_per_extra_bit: self.pow_requests_per_extra_bit,
+                ..PowConfig::default()
+            };
+            checkers.push(Arc::new(ProofOfWorkChecker::new(
+                config,
+                self.pow_secret.as_deref(),
+            )));
+        }
+        if !self.shadow_ban_cidrs.is_empty() {
+            let checker = ShadowBanChecker::new(self.shadow_ban_cidrs.clone());
+            checkers.push(Arc::new(match &reputation {
+                Some(reputation) => checker.with_reputation(reputation.clone()),
+                None => checker,
+            }));
+        }
+        if let Some(reputation) = &reputation {
+            checkers.push(Arc::new(SharedReputationChecker::new(reputation.clone())));
+        }
+
+        let ans_resolver = self.ans_resolver_url.clone().map(|url| {
+            Arc::new(AnsResolver::new(
+                url,
+                Duration::from_secs(self.ans_cache_ttl_secs),
+            ))
+        });
 
         let faucet_address: AccountAddress = self
             .mint_account_address
             .unwrap_or_else(aptos_test_root_address);
         let faucet_account = LocalAccount::new(faucet_address, key, 0);
 
+        let shared_sequence_numbers = match &self.redis_url {
+            Some(redis_url) if self.do_not_delegate => Some(Arc::new(
+                SharedSequenceNumbers::connect(
+                    redis_url,
+                    faucet_address,
+                    MAX_OUTSTANDING_TRANSACTIONS,
+                    SHARED_SEQUENCE_NUMBER_EXPIRATION_SECS,
+                )
+                .await
+                .expect("Failed to connect to Redis"),
+            )),
+            _ => None,
+        };
+
+        let account_pool = self
+            .account_pool_size
+            .map(|size| Arc::new(AccountPool::new(size, self.account_pool_amount)));
+
+        let runway_monitor = self.min_runway_secs.map(|min_runway_secs| {
+            Arc::new(RunwayMonitor::new(RunwayConfig {
+                min_runway_secs,
+                window_secs: self.runway_window_secs,
+                min_scale: self.runway_min_scale,
+                poll_interval_secs: RUNWAY_POLL_INTERVAL_SECS,
+            }))
+        });
+
+        let checker_scheduler = self
+            .adaptive_checker_order
+            .then(|| Arc::new(CheckerScheduler::new()));
+        let checker_timeout = self.checker_timeout_ms.map(|timeout_ms| {
+            CheckerTimeout::new(
+                Duration::from_millis(timeout_ms),
+                self.checker_timeout_policy,
+            )
+        });
+
+        let mint_queue = self.queue_capacity.map(|capacity| {
+            Arc::new(
+                MintQueue::new(capacity, self.queue_concurrency)
+                    .with_stale_after(Duration::from_secs(self.queue_stale_request_secs)),
+            )
+        });
+
+        let treasury = match (
+            self.treasury_threshold,
+            &self.treasury_key_file,
+            &self.treasury_approval_token,
+        ) {
+            (Some(threshold), Some(key_file), Some(approval_token)) => {
+                let key = read_private_key(key_file).expect("Failed to read treasury key file");
+                let address = self.treasury_account_address.unwrap_or_else(|| {
+                    AuthenticationKey::ed25519(&Ed25519PublicKey::from(&key)).derived_address()
+                });
+                let mut treasury_checkers: Vec<Arc<dyn Checker>> =
+                    vec![Arc::new(IpRateLimitChecker::new(
+                        self.treasury_max_requests_per_ip_per_day,
+                        LimitSchedule::default(),
+                    ))];
+                if let Some(path) = &self.treasury_receiver_allowlist_file {
+                    treasury_checkers.push(Arc::new(
+                        ReceiverAllowlistChecker::new(path)
+                            .expect("Failed to load treasury receiver allowlist"),
+                    ));
+                }
+                info!(
+                    "[faucet]: funding amounts over {} from treasury {}",
+                    threshold, address
+                );
+                Some(Arc::new(
+                    Treasury::new(
+                        LocalAccount::new(address, key, 0),
+                        threshold,
+                        approval_token.clone(),
+                    )
+                    .with_checkers(treasury_checkers)
+                    .with_checker_timeout(checker_timeout),
+                ))
+            },
+            (None, None, None) => None,
+            _ => panic!(
+                "--treasury-threshold, --treasury-key-file and --treasury-approval-token go \
+                together"
+            ),
+        };
+
+        let triage_stats = self
+            .admin_token
+            .clone()
+            .map(|admin_token| Arc::new(TriageStats::new(admin_token)));
+
+        let webhooks = self.webhooks_file.as_ref().map(|path| {
+            let configs = WebhookConfig::load_all(path).expect("Failed to load webhooks");
+            info!("[faucet]: notifying {} webhooks of fundings", configs.len());
+            Arc::new(Webhooks::new(
+                configs,
+                self.webhook_max_attempts,
+                Duration::from_secs(1),
+            ))
+        });
+
+        let grants = match (&self.grant_operators_file, &self.grant_audit_file) {
+            (Some(operators_file), Some(audit_file)) => {
+                let operators =
+                    Operator::load_all(operators_file).expect("Failed to load grant operators");
+                info!(
+                    "[faucet]: serving manual grants to {} operators",
+                    operators.len()
+                );
+                Some(Arc::new(
+                    Grants::new(operators, self.grant_approval_ttl_secs, audit_file)
+                        .expect("Failed to open grant audit log"),
+                ))
+            },
+            (None, None) => None,
+            _ => panic!("--grant-operators-file and --grant-audit-file go together"),
+        };
+
+        let key_rotation = self.next_mint_key_file.as_ref().map(|path| {
+            // Delegation funds from an account of its own, not from the mint key's.
+            assert!(
+                self.do_not_delegate && self.admin_token.is_some(),
+                "--next-mint-key-file requires --do-not-delegate and --admin-token"
+            );
+            Arc::new(KeyRotation::new(path.clone()))
+        });
+
+        let fake_funder = self.fake_funder_config.as_ref().map(|path| {
+            // Delegation funds the delegated account on chain, before serving anything.
+            assert!(
+                self.do_not_delegate,
+                "--fake-funder-config requires --do-not-delegate"
+            );
+            let config = FakeFunderConfig::load(path).expect("Failed to load fake funder config");
+            warn!("[faucet]: funding with a fake funder, no transaction will be submitted");
+            Arc::new(FakeFunder::new(config))
+        });
+
         // Do not use maximum amount on delegation, this allows the new delegated faucet to
         // mint a lot for themselves!
         let maximum_amount = if self.do_not_delegate {
@@ -125,20 +784,54 @@ impl FaucetArgs {
             None
         };
 
-        let service = Arc::new(Service::new(
-            self.server_url.clone(),
-            self.chain_id,
-            faucet_account,
-            maximum_amount,
-        ));
+        let service = Arc::new(
+            Service::new(
+                self.server_url,
+                self.chain_id,
+                faucet_account,
+                maximum_amount,
+            )
+            .with_trusted_proxies(self.trusted_proxies)
+            .with_checkers(checkers)
+            .with_checker_scheduler(checker_scheduler)
+            .with_checker_timeout(checker_timeout)
+            .with_ans_resolver(ans_resolver)
+            .with_shared_sequence_numbers(shared_sequence_numbers)
+            .with_account_pool(account_pool.clone())
+            .with_explorer_url_template(self.explorer_url_template)
+            .with_max_batch_size(self.max_batch_size)
+            .with_runway_monitor(runway_monitor.clone())
+            .with_mint_queue(mint_queue.clone())
+            .with_treasury(treasury)
+            .with_triage_stats(triage_stats)
+            .with_webhooks(webhooks.clone())
+            .with_fake_funder(fake_funder)
+            .with_grants(grants)
+            .with_key_rotation(key_rotation),
+        );
 
         let actual_service = if self.do_not_delegate {
             service
         } else {
-            delegate_mint_account(service, self.server_url, self.chain_id, self.maximum_amount)
-                .await
+            delegate_mint_account(service, self.maximum_amount).await
         };
 
+        if let Some(account_pool) = account_pool {
+            tokio::spawn(account_pool.refill(actual_service.clone()));
+        }
+        if let Some(runway_monitor) = runway_monitor {
+            tokio::spawn(runway_monitor.run(actual_service.clone()));
+        }
+        if let Some(mint_queue) = mint_queue {
+            tokio::spawn(mint_queue.run(actual_service.clone()));
+        }
+        if let Some(webhooks) = webhooks {
+            tokio::spawn(webhooks.run());
+        }
+        if let (Some(reputation), Some(url)) = (reputation, self.reputation_sync_url) {
+            tokio::spawn(reputation.run(url));
+        }
+
         println!("Faucet is running. Faucet endpoint: {}", address);
 
         info!(
@@ -157,8 +850,28 @@ pub struct Service {
     client: Client,
     endpoint: Url,
     maximum_amount: Option<u64>,
+    trusted_proxies: Vec<IpNet>,
+    checkers: Vec<Arc<dyn Checker>>,
+    checker_scheduler: Option<Arc<CheckerScheduler>>,
+    checker_timeout: Option<CheckerTimeout>,
+    ans_resolver: Option<Arc<AnsResolver>>,
+    shared_sequence_numbers: Option<Arc<SharedSequenceNumbers>>,
+    account_pool: Option<Arc<AccountPool>>,
+    explorer_url_template: Option<ExplorerUrlTemplate>,
+    max_batch_size: usize,
+    runway_monitor: Option<Arc<RunwayMonitor>>,
+    mint_queue: Option<Arc<MintQueue>>,
+    treasury: Option<Arc<Treasury>>,
+    triage_stats: Option<Arc<TriageStats>>,
+    webhooks: Option<Arc<Webhooks>>,
+    fake_funder: Option<Arc<FakeFunder>>,
+    grants: Option<Arc<Grants>>,
+    key_rotation: Option<Arc<KeyRotation>>,
 }
 
+/// Maximum number of receivers in a `POST /fund_batch` request, unless configured otherwise.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
 impl Service {
     pub fn new(
         endpoint: Url,
@@ -176,9 +889,151 @@ impl Service {
             client,
             endpoint,
             maximum_amount,
+            trusted_proxies: vec![],
+            checkers: vec![],
+            checker_scheduler: None,
+            checker_timeout: None,
+            ans_resolver: None,
+            shared_sequence_numbers: None,
+            account_pool: None,
+            explorer_url_template: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            runway_monitor: None,
+            mint_queue: None,
+            treasury: None,
+            triage_stats: None,
+            webhooks: None,
+            fake_funder: None,
+            grants: None,
+            key_rotation: None,
+        }
+    }
+
+    /// A service funding from `faucet_account`, which minting was delegated to, configured as
+    /// this one otherwise. What is tied to the account of this service isn't carried over: its
+    /// outstanding requests, shared sequence numbers, key rotation and fake funder.
+    fn delegated(&self, faucet_account: LocalAccount, maximum_amount: Option<u64>) -> Self {
+        Service {
+            faucet_account: Mutex::new(faucet_account),
+            transaction_factory: self.transaction_factory.clone(),
+            outstanding_requests: std::sync::RwLock::new(vec![]),
+            client: self.client.clone(),
+            endpoint: self.endpoint.clone(),
+            maximum_amount,
+            trusted_proxies: self.trusted_proxies.clone(),
+            checkers: self.checkers.clone(),
+            checker_scheduler: self.checker_scheduler.clone(),
+            checker_timeout: self.checker_timeout,
+            ans_resolver: self.ans_resolver.clone(),
+            shared_sequence_numbers: None,
+            account_pool: self.account_pool.clone(),
+            explorer_url_template: self.explorer_url_template.clone(),
+            max_batch_size: self.max_batch_size,
+            runway_monitor: self.runway_monitor.clone(),
+            mint_queue: self.mint_queue.clone(),
+            treasury: self.treasury.clone(),
+            triage_stats: self.triage_stats.clone(),
+            webhooks: self.webhooks.clone(),
+            fake_funder: None,
+            grants: self.grants.clone(),
+            key_rotation: None,
         }
     }
 
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpNet>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    pub fn with_checkers(mut self, checkers: Vec<Arc<dyn Checker>>) -> Self {
+        self.checkers = checkers;
+        self
+    }
+
+    pub fn with_checker_scheduler(
+        mut self,
+        checker_scheduler: Option<Arc<CheckerScheduler>>,
+    ) -> Self {
+        self.checker_scheduler = checker_scheduler;
+        self
+    }
+
+    pub fn with_checker_timeout(mut self, checker_timeout: Option<CheckerTimeout>) -> Self {
+        self.checker_timeout = checker_timeout;
+        self
+    }
+
+    pub fn with_ans_resolver(mut self, ans_resolver: Option<Arc<AnsResolver>>) -> Self {
+        self.ans_resolver = ans_resolver;
+        self
+    }
+
+    pub fn with_shared_sequence_numbers(
+        mut self,
+        shared_sequence_numbers: Option<Arc<SharedSequenceNumbers>>,
+    ) -> Self {
+        self.shared_sequence_numbers = shared_sequence_numbers;
+        self
+    }
+
+    pub fn with_account_pool(mut self, account_pool: Option<Arc<AccountPool>>) -> Self {
+        self.account_pool = account_pool;
+        self
+    }
+
+    pub fn with_explorer_url_template(
+        mut self,
+        explorer_url_template: Option<ExplorerUrlTemplate>,
+    ) -> Self {
+        self.explorer_url_template = explorer_url_template;
+        self
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    pub fn with_runway_monitor(mut self, runway_monitor: Option<Arc<RunwayMonitor>>) -> Self {
+        self.runway_monitor = runway_monitor;
+        self
+    }
+
+    pub fn with_mint_queue(mut self, mint_queue: Option<Arc<MintQueue>>) -> Self {
+        self.mint_queue = mint_queue;
+        self
+    }
+
+    pub fn with_treasury(mut self, treasury: Option<Arc<Treasury>>) -> Self {
+        self.treasury = treasury;
+        self
+    }
+
+    pub fn with_triage_stats(mut self, triage_stats: Option<Arc<TriageStats>>) -> Self {
+        self.triage_stats = triage_stats;
+        self
+    }
+
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<Webhooks>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    pub fn with_fake_funder(mut self, fake_funder: Option<Arc<FakeFunder>>) -> Self {
+        self.fake_funder = fake_funder;
+        self
+    }
+
+    pub fn with_grants(mut self, grants: Option<Arc<Grants>>) -> Self {
+        self.grants = grants;
+        self
+    }
+
+    pub fn with_key_rotation(mut self, key_rotation: Option<Arc<KeyRotation>>) -> Self {
+        self.key_rotation = key_rotation;
+        self
+    }
+
     // By default the path is prefixed with the version, e.g. `v1/`. The fake
     // API used in the faucet tests doesn't have a versioned API however, so
     // we just set it to `/`.
@@ -190,25 +1045,182 @@ impl Service {
     pub fn endpoint(&self) -> &Url {
         &self.endpoint
     }
+
+    pub fn checker_timeout(&self) -> Option<&CheckerTimeout> {
+        self.checker_timeout.as_ref()
+    }
+
+    pub fn ans_resolver(&self) -> Option<&AnsResolver> {
+        self.ans_resolver.as_deref()
+    }
+
+    pub fn shared_sequence_numbers(&self) -> Option<&SharedSequenceNumbers> {
+        self.shared_sequence_numbers.as_deref()
+    }
+
+    pub fn account_pool(&self) -> Option<&AccountPool> {
+        self.account_pool.as_deref()
+    }
+
+    pub fn explorer_url_template(&self) -> Option<&ExplorerUrlTemplate> {
+        self.explorer_url_template.as_ref()
+    }
+
+    pub fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    pub fn runway_monitor(&self) -> Option<&RunwayMonitor> {
+        self.runway_monitor.as_deref()
+    }
+
+    pub fn mint_queue(&self) -> Option<&MintQueue> {
+        self.mint_queue.as_deref()
+    }
+
+    pub fn treasury(&self) -> Option<&Treasury> {
+        self.treasury.as_deref()
+    }
+
+    pub fn triage_stats(&self) -> Option<&TriageStats> {
+        self.triage_stats.as_deref()
+    }
+
+    pub fn webhooks(&self) -> Option<&Webhooks> {
+        self.webhooks.as_deref()
+    }
+
+    pub fn fake_funder(&self) -> Option<&FakeFunder> {
+        self.fake_funder.as_deref()
+    }
+
+    pub fn grants(&self) -> Option<&Grants> {
+        self.grants.as_deref()
+    }
+
+    pub fn key_rotation(&self) -> Option<&KeyRotation> {
+        self.key_rotation.as_deref()
+    }
+
+    /// The treasury, if the amount requested is funded from it rather than from the faucet
+    /// account.
+    pub fn treasury_for(&self, requested: u64) -> Option<&Treasury> {
+        self.treasury()
+            .filter(|treasury| treasury.covers(requested))
+    }
+
+    /// Amount actually granted for the one requested: capped to the maximum amount, and scaled
+    /// down if the funder is running low.
+    pub fn grant_amount(&self, requested: u64) -> u64 {
+        let amount = std::cmp::min(requested, self.maximum_amount.unwrap_or(requested));
+        match self.runway_monitor() {
+            Some(runway_monitor) => runway_monitor.scale_amount(amount),
+            None => amount,
+        }
+    }
+
+    /// Tells the client the factor amounts are scaled down by, if they may be.
+    pub fn with_amount_scale_header(&self, reply: Box<dyn Reply>) -> Box<dyn Reply> {
+        match self.runway_monitor() {
+            Some(runway_monitor) => Box::new(warp::reply::with_header(
+                reply,
+                AMOUNT_SCALE_HEADER,
+                runway_monitor.scale().to_string(),
+            )),
+            None => reply,
+        }
+    }
+
+    pub fn client_ip(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        headers: &HeaderMap,
+    ) -> Option<IpAddr> {
+        client_ip::extract_client_ip(remote_addr, headers, &self.trusted_proxies)
+    }
+
+    /// Runs the checkers against the request, returning the reasons to reject it, if any. With a
+    /// scheduler, they run in the order it measures to be the fastest to find a rejection, and
+    /// stop at the first one, otherwise all of them run in the order configured. Each is given
+    /// up on after the checker timeout, if any.
+    pub async fn run_checkers(&self, data: &CheckerData) -> Result<Vec<RejectionReason>> {
+        let checkers = match &self.checker_scheduler {
+            Some(scheduler) => scheduler.order(&self.checkers),
+            None => self.checkers.clone(),
+        };
+        let mut rejections = vec![];
+        for checker in checkers {
+            let start = Instant::now();
+            let rejection = run_checker(checker.as_ref(), data, self.checker_timeout()).await?;
+            if let Some(scheduler) = &self.checker_scheduler {
+                scheduler.record(checker.as_ref(), start.elapsed(), rejection.is_some());
+            }
+            if let Some(rejection) = rejection {
+                info!(
+                    checker = checker.name(),
+                    receiver = data.receiver,
+                    source_ip = data.source_ip,
+                    reason = rejection.reason,
+                    "rejected mint request"
+                );
+                rejections.push(rejection.with_checker(checker.name()));
+                if self.checker_scheduler.is_some() {
+                    break;
+                }
+            }
+        }
+        Ok(rejections)
+    }
+
+    /// Tells the checkers the receiver of a request that passed them was funded, e.g. for its
+    /// cooldown to start. Failures are logged, the funding having happened anyway.
+    pub async fn record_funding(&self, receiver: AccountAddress, time: DateTime<Utc>) {
+        for checker in &self.checkers {
+            if let Err(err) = checker.record_funding(receiver, time).await {
+                warn!(
+                    checker = checker.name(),
+                    receiver = receiver,
+                    "failed to record funding: {:#}",
+                    err
+                );
+            }
+        }
+    }
 }
 
 pub fn routes(
     service: Arc<Service>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let mint = mint::mint_routes(service.clone());
-    let health = health_route(service);
+    let account = account_pool::account_routes(service.clone());
+    let batch = batch::batch_routes(service.clone());
+    let requests = queue::queue_routes(service.clone());
+    let stats = stats::stats_routes(service.clone());
+    let grants = grants::grant_routes(service.clone());
+    let key_rotation = key_rotation::key_rotation_routes(service.clone());
+    let health = health_route(service.clone());
+    let metrics = metrics::metrics_route();
 
     health
+        .or(metrics)
         .or(mint)
-        .with(warp::log::custom(|info| {
+        .or(account)
+        .or(batch)
+        .or(requests)
+        .or(stats)
+        .or(grants)
+        .or(key_rotation)
+        .with(warp::log::custom(move |info| {
             let forwarded_for = info
                 .request_headers()
                 .get("x-forwarded-for")
                 .map(|inner| inner.to_str().unwrap_or("-"));
+            let client_ip = service.client_ip(info.remote_addr(), info.request_headers());
 
             info!(
                 remote_addr = info.remote_addr(),
                 forwarded_for = forwarded_for,
+                client_ip = client_ip,
                 host = info.host(),
                 method = format!("{}", info.method()),
                 path = info.path(),
@@ -223,8 +1235,20 @@ pub fn routes(
         .with(
             warp::cors()
                 .allow_any_origin()
-                .allow_headers(vec![http::header::CONTENT_TYPE])
-                .allow_methods(vec!["POST"]),
+                .allow_headers(vec![
+                    http::header::CONTENT_TYPE.as_str(),
+                    checkers::CAPTCHA_TOKEN_HEADER,
+                    checkers::POW_SOLUTION_HEADER,
+                    treasury::TREASURY_APPROVAL_HEADER,
+                ])
+                // For browsers to let clients know when to retry, and by how much amounts are
+                // scaled down.
+                .expose_headers(vec![
+                    http::header::RETRY_AFTER.as_str(),
+                    AMOUNT_SCALE_HEADER,
+                    queue::QUEUE_POSITION_HEADER,
+                ])
+                .allow_methods(vec!["GET", "POST"]),
         )
 }
 
@@ -255,15 +1279,13 @@ async fn handle_health(service: Arc<Service>) -> Result<Box<dyn warp::Reply>, In
 /// succeed and the other will hit an unwrap. Eventually all faucets should get online.
 pub async fn delegate_mint_account(
     service: Arc<Service>,
-    server_url: Url,
-    chain_id: ChainId,
     maximum_amount: Option<u64>,
 ) -> Arc<Service> {
     // Create a new random account, then delegate to it
     let mut delegated_account = LocalAccount::generate(&mut rand::rngs::OsRng);
 
-    // Create the account
-    let response = mint::process(&service, mint::MintParams {
+    // Create the account, from the faucet account whatever the amount
+    let response = mint::process_from_faucet(&service, mint::MintParams {
         amount: 100_000_000_000,
         auth_key: None,
         address: Some(
@@ -274,7 +1296,9 @@ pub async fn delegate_mint_account(
                 .to_hex_literal(),
         ),
         pub_key: None,
+        name: None,
         return_txns: Some(true),
+        detailed: None,
     })
     .await
     .expect("Failed to create new account");
@@ -319,10 +1343,5 @@ pub async fn delegate_mint_account(
         .await
         .unwrap();
 
-    Arc::new(Service::new(
-        server_url,
-        chain_id,
-        delegated_account,
-        maximum_amount,
-    ))
+    Arc::new(service.delegated(delegated_account, maximum_amount))
 }
@@ -6,293 +6,66 @@
 // to this until you've spoken with the Ecosystem Platform team + dport.
 
 use aptos_faucet::FaucetArgs;
-use clap::Parser;
 
 #[tokio::main]
 async fn main() {
     aptos_logger::Logger::new().init();
-    let args: FaucetArgs = FaucetArgs::from_args();
+    let args = FaucetArgs::parse_layered().expect("Failed to load the faucet configuration");
     args.run().await
 }
 
 #[cfg(test)]
 mod tests {
     use aptos_crypto::{ed25519::Ed25519PublicKey, hash::HashValue};
-    use aptos_faucet::{routes, Service};
-    use aptos_infallible::RwLock;
-    use aptos_keygen::KeyGen;
-    use aptos_rest_client::{
-        aptos_api_types::{
-            AccountData, LedgerInfo, ModuleBundlePayload, PendingTransaction,
-            TransactionPayload as TransactionPayloadData,
+    use aptos_faucet::{
+        account_pool::AccountPool,
+        checkers::{
+            Checker, CooldownConfig, IpRateLimitChecker, LimitSchedule, ReceiverCooldownChecker,
+            ShadowBanChecker,
         },
-        FaucetClient,
+        routes,
+        test_support::{FaucetServer, MockChain, REGISTERED_NAME_ADDRESS},
+        Service,
     };
+    use aptos_rest_client::FaucetClient;
     use aptos_sdk::types::{
         account_address::AccountAddress,
-        chain_id::ChainId,
-        transaction::{
-            authenticator::AuthenticationKey, SignedTransaction, Transaction, TransactionArgument,
-            TransactionPayload::Script,
-        },
-        LocalAccount,
+        transaction::{authenticator::AuthenticationKey, SignedTransaction},
     };
-    use aptos_warp_webserver::Response;
-    use serde::Serialize;
     use std::{
-        collections::HashMap,
-        convert::{Infallible, TryFrom, TryInto},
-        sync::{Arc, Mutex},
-    };
-    use tokio::task::JoinHandle;
-    use url::Url;
-    use warp::{
-        body::BodyDeserializeError,
-        cors::CorsForbidden,
-        http::{header, HeaderValue, StatusCode},
-        reject::{LengthRequired, MethodNotAllowed, PayloadTooLarge, UnsupportedMediaType},
-        reply, Filter, Rejection, Reply,
+        convert::{TryFrom, TryInto},
+        sync::Arc,
+        time::Duration,
     };
+    use warp::http::{header, StatusCode};
 
-    type AccountStates = Arc<RwLock<HashMap<AccountAddress, AccountState>>>;
-    #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-    struct AccountState {
-        pub authentication_key: AuthenticationKey,
-        pub balance: u64,
-        pub sequence_number: u64,
-    }
-
-    impl AccountState {
-        pub fn new(balance: u64) -> Self {
-            Self {
-                authentication_key: AuthenticationKey::new([1; 32]),
-                balance,
-                sequence_number: 0,
-            }
-        }
-    }
-
-    fn setup(maximum_amount: Option<u64>) -> (AccountStates, Arc<Service>) {
-        let mut keygen = KeyGen::from_seed([0; 32]);
-        let (private_key, public_key) = keygen.generate_ed25519_keypair();
-        let account_address = AuthenticationKey::ed25519(&public_key).derived_address();
-
-        let faucet_account = LocalAccount::new(account_address, private_key, 0);
-
-        let chain_id = ChainId::test();
-
-        let accounts = AccountStates::new(aptos_infallible::RwLock::new(HashMap::new()));
-        accounts
-            .write()
-            .insert(account_address, AccountState::new(0));
-
-        let last_txn = Arc::new(Mutex::new(None));
-        let last_txn_0 = last_txn.clone();
-
-        let accounts_cloned_0 = accounts.clone();
-        let accounts_cloned_1 = accounts.clone();
-        let stub = warp::path!("accounts" / String)
-            .and(warp::any().map(move || accounts_cloned_0.clone()))
-            .and_then(handle_get_account)
-            .or(warp::path!("transactions" / "by_hash" / String)
-                .and(warp::get())
-                .and(warp::any().map(move || last_txn_0.clone()))
-                .and_then(handle_get_transaction))
-            .or(warp::path!("transactions")
-                .and(warp::post())
-                .and(warp::body::bytes())
-                .and(warp::any().map(move || (accounts_cloned_1.clone(), last_txn.clone())))
-                .and_then(handle_submit_transaction))
-            .with(
-                warp::cors()
-                    .allow_any_origin()
-                    .allow_methods(vec!["POST", "GET"])
-                    .allow_headers(vec![header::CONTENT_TYPE]),
-            )
-            .recover(handle_rejection);
-        let (address, future) = warp::serve(stub).bind_ephemeral(([127, 0, 0, 1], 0));
-        tokio::task::spawn(async move { future.await });
-
-        let service = Service::new(
-            Url::parse(&format!("http://localhost:{}/", address.port())).unwrap(),
-            chain_id,
-            faucet_account,
-            maximum_amount,
-        )
-        .configure_for_testing();
-        (accounts, Arc::new(service))
-    }
-
-    async fn handle_get_account(
-        address: String,
-        accounts: AccountStates,
-    ) -> Result<impl Reply, Rejection> {
-        let reader = accounts.read();
-        let account = match AccountAddress::try_from(address.clone())
-            .or_else(|_e| AccountAddress::from_hex(address.clone()))
-        {
-            Ok(addr) => reader.get(&addr),
-            _ => None,
-        };
-        if let Some(account) = account {
-            let auth_vec: Vec<u8> = account.authentication_key.as_ref().into();
-            let account_data = AccountData {
-                authentication_key: auth_vec.into(),
-                sequence_number: account.sequence_number.into(),
-            };
-            Ok(response(&account_data))
-        } else {
-            Err(warp::reject())
-        }
-    }
-
-    async fn handle_get_transaction(
-        _hash: String,
-        last_txn: Arc<Mutex<Option<Transaction>>>,
-    ) -> Result<impl Reply, Rejection> {
-        last_txn.lock().unwrap().as_ref().map_or_else(
-            || Err(warp::reject()),
-            |txn| {
-                let info = aptos_rest_client::aptos_api_types::TransactionInfo {
-                    version: 0.into(),
-                    hash: HashValue::zero().into(),
-                    state_change_hash: HashValue::zero().into(),
-                    event_root_hash: HashValue::zero().into(),
-                    state_checkpoint_hash: None,
-                    gas_used: 0.into(),
-                    success: true,
-                    vm_status: "Executed".to_string(),
-                    accumulator_root_hash: HashValue::zero().into(),
-                    changes: vec![],
-                    block_height: None,
-                    epoch: None,
-                };
-                let serializable_txn: aptos_rest_client::aptos_api_types::Transaction = (
-                    txn.as_signed_user_txn().unwrap(),
-                    info,
-                    dummy_payload(),
-                    Vec::new(),
-                    0,
-                )
-                    .into();
-
-                Ok(response(&serializable_txn))
-            },
-        )
-    }
-
-    async fn handle_submit_transaction(
-        txn: bytes::Bytes,
-        (accounts, last_txn): (AccountStates, Arc<Mutex<Option<Transaction>>>),
-    ) -> Result<impl Reply, Rejection> {
-        let txn: SignedTransaction = bcs::from_bytes(&txn).unwrap();
-        assert_eq!(txn.chain_id(), ChainId::test());
-
-        if let Script(script) = txn.payload() {
-            let dst_addr = if let TransactionArgument::Address(addr) = script.args()[0] {
-                addr
-            } else {
-                panic!("unexpected type of script: {:?}", script);
-            };
-            let amount = if let TransactionArgument::U64(amount) = script.args()[1] {
-                amount
-            } else {
-                panic!("unexpected type of script: {:?}", script);
-            };
-
-            accounts
-                .write()
-                .entry(dst_addr)
-                .and_modify(|account| account.balance += amount)
-                .or_insert_with(|| AccountState::new(amount));
-        }
-
-        let pending_txn = PendingTransaction {
-            hash: HashValue::zero().into(),
-            request: (&txn, dummy_payload()).into(),
-        };
-
-        *last_txn.lock().unwrap() = Some(Transaction::UserTransaction(txn));
-        Ok(response(&pending_txn))
+    fn setup(maximum_amount: Option<u64>) -> (MockChain, Arc<Service>) {
+        setup_with_checkers(maximum_amount, vec![])
     }
 
-    fn response<T: Serialize>(body: &T) -> warp::reply::Response {
-        let li = LedgerInfo {
-            chain_id: ChainId::test().id(),
-            epoch: 1.into(),
-            ledger_version: 5.into(),
-            oldest_ledger_version: 0.into(),
-            block_height: 4.into(),
-            oldest_block_height: 0.into(),
-            ledger_timestamp: 5.into(),
-        };
-        Response::new(li, body).unwrap().into_response()
-    }
-
-    fn dummy_payload() -> TransactionPayloadData {
-        TransactionPayloadData::ModuleBundlePayload(ModuleBundlePayload { modules: vec![] })
-    }
-
-    #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
-    pub struct Error {
-        pub code: u16,
-        pub message: String,
+    fn setup_with_checkers(
+        maximum_amount: Option<u64>,
+        checkers: Vec<Arc<dyn Checker>>,
+    ) -> (MockChain, Arc<Service>) {
+        setup_with_account_pool(maximum_amount, checkers, None)
     }
 
-    impl Error {
-        fn new(code: StatusCode, message: String) -> Error {
-            Error {
-                code: code.as_u16(),
-                message,
-            }
-        }
-
-        fn status_code(&self) -> StatusCode {
-            StatusCode::from_u16(self.code).unwrap()
-        }
-    }
-
-    async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
-        let code;
-        let body;
-
-        if err.is_not_found() {
-            code = StatusCode::NOT_FOUND;
-            body = reply::json(&Error::new(code, "Not Found".to_owned()));
-        } else if let Some(error) = err.find::<Error>() {
-            code = error.status_code();
-            body = reply::json(error);
-        } else if let Some(cause) = err.find::<CorsForbidden>() {
-            code = StatusCode::FORBIDDEN;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else if let Some(cause) = err.find::<BodyDeserializeError>() {
-            code = StatusCode::BAD_REQUEST;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else if let Some(cause) = err.find::<LengthRequired>() {
-            code = StatusCode::LENGTH_REQUIRED;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else if let Some(cause) = err.find::<PayloadTooLarge>() {
-            code = StatusCode::PAYLOAD_TOO_LARGE;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else if let Some(cause) = err.find::<UnsupportedMediaType>() {
-            code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else if let Some(cause) = err.find::<MethodNotAllowed>() {
-            code = StatusCode::METHOD_NOT_ALLOWED;
-            body = reply::json(&Error::new(code, cause.to_string()));
-        } else {
-            code = StatusCode::INTERNAL_SERVER_ERROR;
-            body = reply::json(&Error::new(code, format!("unexpected error: {:?}", err)));
-        }
-        let mut rep = reply::with_status(body, code).into_response();
-        rep.headers_mut()
-            .insert("access-control-allow-origin", HeaderValue::from_static("*"));
-        Ok(rep)
+    fn setup_with_account_pool(
+        maximum_amount: Option<u64>,
+        checkers: Vec<Arc<dyn Checker>>,
+        account_pool: Option<Arc<AccountPool>>,
+    ) -> (MockChain, Arc<Service>) {
+        let chain = MockChain::start();
+        let service = chain
+            .service(maximum_amount)
+            .with_checkers(checkers)
+            .with_account_pool(account_pool);
+        (chain, Arc::new(service))
     }
 
     #[tokio::test]
     async fn test_mint_auth_key() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let filter = routes(service);
         let auth_key = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
         let amount = 13345;
@@ -302,7 +75,7 @@ mod tests {
             .reply(&filter)
             .await;
         serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
-        let reader = accounts.read();
+        let reader = chain.accounts.read();
         let addr = AccountAddress::try_from(
             "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d".to_owned(),
         )
@@ -313,7 +86,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_pub_key() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let filter = routes(service);
 
         let pub_key = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
@@ -324,7 +97,7 @@ mod tests {
             .reply(&filter)
             .await;
         serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
-        let reader = accounts.read();
+        let reader = chain.accounts.read();
         let addr = AccountAddress::try_from(
             "9FF98E82355EB13098F3B1157AC018A725C62C0E0820F422000814CDBA407835".to_owned(),
         )
@@ -335,7 +108,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_address() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let filter = routes(service);
 
         let address = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
@@ -347,7 +120,7 @@ mod tests {
             .await;
 
         serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
-        let reader = accounts.read();
+        let reader = chain.accounts.read();
         let addr = AccountAddress::try_from(
             "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d".to_owned(),
         )
@@ -358,7 +131,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_address_hex() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let filter = routes(service);
 
         let address = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
@@ -370,7 +143,7 @@ mod tests {
             .await;
 
         serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
-        let reader = accounts.read();
+        let reader = chain.accounts.read();
         let addr = AccountAddress::try_from(
             "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d".to_owned(),
         )
@@ -381,7 +154,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_with_txns_response() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let filter = routes(service);
 
         let auth_key = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
@@ -401,7 +174,7 @@ mod tests {
         let bytes = hex::decode(body).expect("hex encoded response body");
         bcs::from_bytes::<Vec<SignedTransaction>>(&bytes).expect("valid bcs vec");
 
-        let reader = accounts.read();
+        let reader = chain.accounts.read();
         let addr = AccountAddress::try_from(
             "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d".to_owned(),
         )
@@ -412,7 +185,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_health() {
-        let (_accounts, service) = setup(None);
+        let (_chain, service) = setup(None);
 
         let resp = warp::test::request()
             .method("GET")
@@ -426,7 +199,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_invalid_auth_key() {
-        let (_accounts, service) = setup(None);
+        let (_chain, service) = setup(None);
         let filter = routes(service);
 
         let auth_key = "invalid-auth-key";
@@ -435,6 +208,30 @@ mod tests {
             .path(format!("/mint?auth_key={}&amount=1000000", auth_key).as_str())
             .reply(&filter)
             .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.body(),
+            "Invalid auth_key 'invalid-auth-key': 'i' at position 0 is not a hex digit (0-9, a-f)"
+        );
+
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/mint?address=Oxa550c18&amount=1000000")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.body(),
+            "Invalid address 'Oxa550c18': it starts with 'Ox' rather than '0x'. Did you mean \
+            '0xa550c18'?"
+        );
+
+        // Nothing given is still told apart.
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/mint?amount=1000000")
+            .reply(&filter)
+            .await;
         assert_eq!(
             resp.body(),
             "You must provide 'address' (preferred), 'pub_key', or 'auth_key'"
@@ -443,9 +240,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_mint_fullnode_error() {
-        let (accounts, service) = setup(None);
+        let (chain, service) = setup(None);
         let address = service.faucet_account.lock().await.address();
-        accounts.write().remove(&address);
+        chain.accounts.write().remove(&address);
         let filter = routes(service);
 
         let auth_key = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
@@ -469,33 +266,327 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_mint_ip_ratelimit() {
+        let checker: Arc<dyn Checker> =
+            Arc::new(IpRateLimitChecker::new(1, LimitSchedule::default()));
+        let (_chain, service) = setup_with_checkers(None, vec![checker]);
+        let filter = routes(service);
+
+        let address = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
+        let mint = |remote_addr: &str| {
+            warp::test::request()
+                .method("POST")
+                .remote_addr(remote_addr.parse().unwrap())
+                .path(format!("/mint?address={}&amount=1", address).as_str())
+                .reply(&filter)
+        };
+        assert_eq!(mint("1.1.1.1:1000").await.status(), StatusCode::OK);
+        assert_eq!(
+            mint("1.1.1.1:1001").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(mint("2.2.2.2:1000").await.status(), StatusCode::OK);
+
+        // Clients accepting JSON get the rejection in full.
+        let resp = warp::test::request()
+            .method("POST")
+            .remote_addr("1.1.1.1:1002".parse().unwrap())
+            .header(header::ACCEPT, "application/json")
+            .path(format!("/mint?address={}&amount=1", address).as_str())
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = resp.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after <= 24 * 3600);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let rejection = &body["rejections"][0];
+        assert_eq!(rejection["code"], "usage_limit_exhausted");
+        assert_eq!(rejection["checker"], "ip_ratelimit");
+        assert_eq!(rejection["limit"], 1);
+        assert_eq!(rejection["retry_after_secs"], retry_after);
+    }
+
+    #[tokio::test]
+    async fn test_mint_receiver_cooldown() {
+        // The cooldown is checked first, and the rate limit rejects after it.
+        let checkers: Vec<Arc<dyn Checker>> = vec![
+            Arc::new(ReceiverCooldownChecker::new(CooldownConfig::default())),
+            Arc::new(IpRateLimitChecker::new(1, LimitSchedule::default())),
+        ];
+        let (_chain, service) = setup_with_checkers(None, checkers);
+        let filter = routes(service);
+
+        let (alice, bob) = (
+            "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d",
+            "9ff98e82355eb13098f3b1157ac018a725c62c0e0820f422000814cdba407835",
+        );
+        let mint = |remote_addr: &str, address: &str| {
+            warp::test::request()
+                .method("POST")
+                .remote_addr(remote_addr.parse().unwrap())
+                .header(header::ACCEPT, "application/json")
+                .path(format!("/mint?address={}&amount=1", address).as_str())
+                .reply(&filter)
+        };
+        let rejection_code = |body: &[u8]| {
+            serde_json::from_slice::<serde_json::Value>(body).unwrap()["rejections"][0]["code"]
+                .clone()
+        };
+        assert_eq!(mint("1.1.1.1:1000", alice).await.status(), StatusCode::OK);
+        let resp = mint("1.1.1.1:1001", bob).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rejection_code(resp.body()), "usage_limit_exhausted");
+
+        // Bob was rejected, not funded, so isn't cooling down, unlike alice.
+        assert_eq!(mint("2.2.2.2:1000", bob).await.status(), StatusCode::OK);
+        for address in [alice, bob] {
+            let resp = mint("3.3.3.3:1000", address).await;
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(rejection_code(resp.body()), "receiver_cooling_down");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mint_shadow_ban() {
+        let checkers: Vec<Arc<dyn Checker>> = vec![
+            Arc::new(IpRateLimitChecker::new(1, LimitSchedule::default())),
+            Arc::new(ShadowBanChecker::new(vec!["1.1.1.0/24".parse().unwrap()])),
+        ];
+        let (chain, service) = setup_with_checkers(None, checkers);
+        let filter = routes(service);
+
+        let address = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
+        let mint = |remote_addr: &str| {
+            warp::test::request()
+                .method("POST")
+                .remote_addr(remote_addr.parse().unwrap())
+                .path(format!("/mint?address={}&amount=1", address).as_str())
+                .reply(&filter)
+        };
+        // Looks accepted, even past the rate limit, but nothing is funded.
+        for port in 1000..1003 {
+            let resp = mint(&format!("1.1.1.1:{}", port)).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
+        }
+        let receiver = AccountAddress::from_hex(address).unwrap();
+        assert!(chain.accounts.read().get(&receiver).is_none());
+
+        assert_eq!(mint("2.2.2.2:1000").await.status(), StatusCode::OK);
+        assert_eq!(chain.accounts.read().get(&receiver).unwrap().balance, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fund_batch() {
+        let checkers: Vec<Arc<dyn Checker>> = vec![Arc::new(IpRateLimitChecker::new(
+            2,
+            LimitSchedule::default(),
+        ))];
+        let (chain, service) = setup_with_checkers(Some(1000), checkers);
+        let faucet_address = service.faucet_account.lock().await.address();
+        let filter = routes(service);
+
+        let fund_batch = |body: serde_json::Value| {
+            warp::test::request()
+                .method("POST")
+                .remote_addr("1.1.1.1:1000".parse().unwrap())
+                .path("/fund_batch")
+                .json(&body)
+                .reply(&filter)
+        };
+        let resp = fund_batch(serde_json::json!({
+            "items": [
+                { "address": "0xa", "amount": 10 },
+                { "address": "not an address", "amount": 10 },
+                { "address": "b", "amount": 5000 },
+                { "address": "0xc", "amount": 30 },
+            ]
+        }))
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        // The mint of the total, then the batch transfer.
+        assert_eq!(resp["txn_hashes"].as_array().unwrap().len(), 2);
+        let funded: Vec<_> = resp["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["funded"].as_bool().unwrap(),
+                    r["amount"].as_u64().unwrap(),
+                    r.get("rejection").is_some(),
+                )
+            })
+            .collect();
+        // The third item is capped to the maximum amount, the fourth is over the rate limit.
+        assert_eq!(funded, vec![
+            (true, 10, false),
+            (false, 0, true),
+            (true, 1000, false),
+            (false, 0, true),
+        ]);
+        assert_eq!(
+            resp["results"][3]["rejections"][0]["code"],
+            "usage_limit_exhausted"
+        );
+
+        let accounts = chain.accounts.read();
+        let balance = |address: &str| {
+            accounts
+                .get(&AccountAddress::from_hex_literal(address).unwrap())
+                .map(|account| account.balance)
+        };
+        assert_eq!(balance("0xa"), Some(10));
+        assert_eq!(balance("0xb"), Some(1000));
+        assert_eq!(balance("0xc"), None);
+        assert_eq!(accounts.get(&faucet_address).unwrap().balance, 0);
+        drop(accounts);
+
+        let items: Vec<_> = (0..101)
+            .map(|i| serde_json::json!({ "address": format!("0x{:x}", i + 16), "amount": 1 }))
+            .collect();
+        let resp = fund_batch(serde_json::json!({ "items": items })).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_mint_name() {
+        let (chain, service) = setup(None);
+        let filter = routes(service);
+
+        let mint = |query: &str| {
+            warp::test::request()
+                .method("POST")
+                .path(format!("/mint?{}&amount=13345", query).as_str())
+                .reply(&filter)
+        };
+        // Pasted in the address field.
+        let resp = mint("address=Alice.apt").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        serde_json::from_slice::<Vec<HashValue>>(resp.body()).unwrap();
+        let addr = AccountAddress::from_hex_literal(REGISTERED_NAME_ADDRESS).unwrap();
+        assert_eq!(chain.accounts.read().get(&addr).unwrap().balance, 13345);
+
+        let resp = mint("name=alice").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = mint("name=bob.apt").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.body(),
+            "Name 'bob.apt' is not registered, or doesn't point to an address"
+        );
+
+        let resp = mint("name=b_b.apt").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_mint_detailed() {
+        let (_chain, service) = setup(None);
+        let faucet_address = service.faucet_account.lock().await.address();
+        let filter = routes(service);
+
+        let address = "459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
+        let resp = warp::test::request()
+            .method("POST")
+            .path(format!("/mint?address={}&amount=1&detailed=true", address).as_str())
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let txn_hash: HashValue = serde_json::from_value(body["txn_hashes"][0].clone()).unwrap();
+        assert_eq!(
+            body["explorer_urls"][0],
+            format!(
+                "https://explorer.aptoslabs.com/txn/{}?network=local",
+                txn_hash.to_hex_literal()
+            )
+        );
+        assert_eq!(body["ledger_version"], 5);
+        assert_eq!(body["funder"], faucet_address.to_hex_literal());
+    }
+
+    #[tokio::test]
+    async fn test_account_pool() {
+        let (_chain, service) = setup(None);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/account")
+            .reply(&routes(service))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let pool = Arc::new(AccountPool::new(2, 1000));
+        let (chain, service) = setup_with_account_pool(None, vec![], Some(pool.clone()));
+        let filter = routes(service.clone());
+        tokio::spawn(pool.clone().refill(service));
+        for _ in 0..100 {
+            if pool.available().await == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(pool.available().await, 2);
+
+        let mut addresses = vec![];
+        for _ in 0..2 {
+            let resp = warp::test::request()
+                .method("POST")
+                .path("/account")
+                .reply(&filter)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            let address =
+                AccountAddress::from_hex_literal(body["address"].as_str().unwrap()).unwrap();
+            let private_key = body["private_key"].as_str().unwrap();
+            assert!(private_key.starts_with("0x"));
+            assert_eq!(chain.accounts.read().get(&address).unwrap().balance, 1000);
+            addresses.push(address);
+        }
+        assert_ne!(addresses[0], addresses[1]);
+    }
+
     #[tokio::test]
     async fn create_account_with_client() {
-        let (faucet_client, _service) = get_client().await;
+        let (faucet_client, _chain, _server) = get_client();
         let address = get_address();
         faucet_client.create_account(address).await.unwrap();
     }
 
     #[tokio::test]
     async fn fund_account_with_client() {
-        let (faucet_client, _service) = get_client().await;
+        let (faucet_client, _chain, _server) = get_client();
         let address = get_address();
         faucet_client.create_account(address).await.unwrap();
         faucet_client.fund(address, 10).await.unwrap();
     }
 
-    async fn get_client() -> (FaucetClient, JoinHandle<()>) {
-        let (_accounts, service) = setup(None);
-        let endpoint = service.endpoint().clone();
-        let (address, future) = warp::serve(routes(service)).bind_ephemeral(([127, 0, 0, 1], 0));
-        let service = tokio::task::spawn(async move { future.await });
-
-        let faucet_client = FaucetClient::new_for_testing(
-            Url::parse(&format!("http://{}", address)).unwrap(),
-            endpoint,
-        );
+    #[tokio::test]
+    async fn fund_account_with_client_rate_limited() {
+        let checker: Arc<dyn Checker> =
+            Arc::new(IpRateLimitChecker::new(1, LimitSchedule::default()));
+        let (chain, service) = setup_with_checkers(None, vec![checker]);
+        let server = FaucetServer::start(service);
+        let faucet_client = server.client();
+        let address = get_address();
+        faucet_client.fund(address, 10).await.unwrap();
+        // Over HTTP, requests come from the loopback address, which is over its limit now.
+        assert!(faucet_client.fund(address, 10).await.is_err());
+        assert_eq!(chain.balance(address), Some(10));
+    }
 
-        (faucet_client, service)
+    fn get_client() -> (FaucetClient, MockChain, FaucetServer) {
+        let (chain, service) = setup(None);
+        let server = FaucetServer::start(service);
+        (server.client(), chain, server)
     }
 
     fn get_address() -> AccountAddress {
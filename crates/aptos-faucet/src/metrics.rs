@@ -0,0 +1,135 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use aptos_metrics_core::{
+    register_gauge, register_int_counter, register_int_counter_vec, register_int_gauge, Encoder,
+    Gauge, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+use once_cell::sync::Lazy;
+use std::convert::Infallible;
+use warp::{Filter, Rejection, Reply};
+
+pub static FUNDER_BALANCE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "aptos_faucet_funder_balance",
+        "Balance of the funder account, as last polled."
+    )
+    .unwrap()
+});
+
+pub static FUNDER_RUNWAY_SECS: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "aptos_faucet_funder_runway_secs",
+        "Seconds until the funder runs out at its recent spend rate, -1 if it isn't spending."
+    )
+    .unwrap()
+});
+
+pub static AMOUNT_SCALE: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "aptos_faucet_amount_scale",
+        "Factor the amounts granted are scaled down by, 1 while the runway of the funder is long \
+        enough."
+    )
+    .unwrap()
+});
+
+pub static QUEUE_LENGTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "aptos_faucet_queue_length",
+        "Number of mint requests waiting in the queue."
+    )
+    .unwrap()
+});
+
+pub static QUEUE_PROCESSING: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "aptos_faucet_queue_processing",
+        "Number of queued mint requests being processed."
+    )
+    .unwrap()
+});
+
+pub static QUEUE_REAPED_REQUESTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_faucet_queue_reaped_requests",
+        "Queued mint requests marked failed after processing for too long."
+    )
+    .unwrap()
+});
+
+pub static COOLDOWN_STORE_MISMATCHES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_faucet_cooldown_store_mismatches",
+        "Requests the receiver cooldown stores disagreed on, or the secondary one failed, while \
+        migrating them."
+    )
+    .unwrap()
+});
+
+pub static CHECKER_TIMEOUTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_faucet_checker_timeouts",
+        "Checks given up on after the checker timeout, by checker and by what became of the \
+        request: skip or reject.",
+        &["checker", "policy"]
+    )
+    .unwrap()
+});
+
+pub static TREASURY_GRANTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_faucet_treasury_grants",
+        "Grants over the treasury threshold, funded from the treasury account."
+    )
+    .unwrap()
+});
+
+pub static WEBHOOK_DELIVERIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_faucet_webhook_deliveries",
+        "Webhook notifications by kind of event and by outcome: delivered, retried or \
+        dead_lettered.",
+        &["event", "result"]
+    )
+    .unwrap()
+});
+
+pub static FAKE_FUNDINGS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_faucet_fake_fundings",
+        "Fundings answered by the fake funder, without touching the chain, by outcome: funded or \
+        failed.",
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static GRANT_ACTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_faucet_grant_actions",
+        "Steps of manual grants recorded in the audit log: proposed, approved, rejected, \
+        expired, funded or failed.",
+        &["action"]
+    )
+    .unwrap()
+});
+
+pub fn metrics_route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // GET /metrics
+    warp::path!("metrics").and(warp::get()).and_then(handle)
+}
+
+async fn handle() -> Result<Box<dyn warp::Reply>, Infallible> {
+    let mut buffer = vec![];
+    match TextEncoder::new().encode(&aptos_metrics_core::gather(), &mut buffer) {
+        Ok(()) => Ok(Box::new(buffer)),
+        Err(err) => Ok(Box::new(warp::reply::with_status(
+            err.to_string(),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ))),
+    }
+}
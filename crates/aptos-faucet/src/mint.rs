@@ -5,20 +5,35 @@
 // README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
 // to this until you've spoken with the Ecosystem Platform team + dport.
 
-use crate::Service;
+use crate::{
+    ans,
+    checkers::{is_shadow_banned, rejection_reply, CheckerData},
+    errors::{error_reply, FaucetError, FaucetErrorCode},
+    fake_funder::FakeFunder,
+    queue::{self, QueueParams},
+    sequence_numbers::SharedSequenceNumbers,
+    stats::{now_secs, FundingOutcome},
+    validation::{parse_address, parse_auth_key, InvalidAddress},
+    webhooks::FundingEvent,
+    Service, MAX_OUTSTANDING_TRANSACTIONS,
+};
 use anyhow::Result;
 use aptos_crypto::{ed25519::Ed25519PublicKey, hash::HashValue};
 use aptos_logger::{info, warn};
-use aptos_sdk::types::{
-    account_address::AccountAddress,
-    transaction::{
-        authenticator::AuthenticationKey, Script, SignedTransaction, TransactionArgument,
+use aptos_sdk::{
+    transaction_builder::TransactionBuilder,
+    types::{
+        account_address::AccountAddress,
+        transaction::{
+            authenticator::AuthenticationKey, Script, SignedTransaction, TransactionArgument,
+        },
+        AccountKey, LocalAccount,
     },
 };
-use reqwest::StatusCode;
-use serde::Deserialize;
-use std::{convert::Infallible, fmt, sync::Arc};
-use warp::{Filter, Rejection, Reply};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, fmt, net::SocketAddr, str::FromStr, sync::Arc};
+use warp::{http::HeaderMap, Filter, Rejection, Reply};
 
 static MINTER_SCRIPT: &[u8] = include_bytes!("minter.mv");
 
@@ -32,19 +47,130 @@ pub fn mint_routes(
         .and(warp::post())
         .and(warp::any().map(move || service.clone()))
         .and(warp::query().map(move |params: MintParams| params))
-        .and_then(|_, service, params| handle(service, params))
+        .and(warp::query().map(move |queue_params: QueueParams| queue_params))
+        .and(warp::addr::remote())
+        .and(warp::header::headers_cloned())
+        .and_then(|_, service, params, queue_params, remote_addr, headers| {
+            handle(service, params, queue_params, remote_addr, headers)
+        })
 }
 
 async fn handle(
     service: Arc<Service>,
     params: MintParams,
+    queue_params: QueueParams,
+    remote_addr: Option<SocketAddr>,
+    headers: HeaderMap,
 ) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let params = match resolve_name(&service, params).await {
+        Ok(params) => params,
+        Err(err) => return Ok(error_reply(&err, &headers)),
+    };
+    if let Err(err) = params.validate() {
+        return Ok(error_reply(
+            &FaucetError::invalid_request(err.to_string()),
+            &headers,
+        ));
+    }
+
+    if let Some(receiver) = params.receiver() {
+        let data = CheckerData {
+            receiver,
+            amount: params.amount,
+            source_ip: service.client_ip(remote_addr, &headers),
+            headers: headers.clone(),
+            time: Utc::now(),
+        };
+        if let Some(stats) = service.triage_stats() {
+            stats.record_request(data.source_ip, now_secs());
+        }
+        let checked = service.run_checkers(&data).await;
+        if let (Some(stats), Ok(rejections)) = (service.triage_stats(), &checked) {
+            stats.record_rejections(rejections, now_secs());
+        }
+        match checked {
+            Ok(rejections) if is_shadow_banned(&rejections) => {
+                info!(
+                    receiver = receiver,
+                    source_ip = data.source_ip,
+                    amount = params.amount,
+                    "shadow banned mint request, answering without funding"
+                );
+                return Ok(match shadow_response(&service, &params, receiver).await {
+                    Ok(body) => service.with_amount_scale_header(Box::new(body.to_string())),
+                    Err(err) => error_reply(&FaucetError::internal(err), &headers),
+                });
+            },
+            Ok(rejections) if !rejections.is_empty() => {
+                return Ok(rejection_reply(&rejections, &data.headers));
+            },
+            Ok(_) => (),
+            Err(err) => return Ok(error_reply(&FaucetError::internal(err), &headers)),
+        }
+        if let Some(treasury) = service.treasury_for(params.amount) {
+            let checked = treasury.run_checkers(&data).await;
+            if let (Some(stats), Ok(rejections)) = (service.triage_stats(), &checked) {
+                stats.record_rejections(rejections, now_secs());
+            }
+            match checked {
+                Ok(rejections) if !rejections.is_empty() => {
+                    return Ok(rejection_reply(&rejections, &data.headers));
+                },
+                Ok(_) => (),
+                Err(err) => return Ok(error_reply(&FaucetError::internal(err), &headers)),
+            }
+        }
+    }
+
+    if let Some(mint_queue) = service.mint_queue() {
+        return Ok(
+            queue::handle_queued(&service, mint_queue, params, queue_params, &headers).await,
+        );
+    }
+
     match process(&service, params).await {
-        Ok(body) => Ok(Box::new(body.to_string())),
-        Err(err) => Ok(Box::new(warp::reply::with_status(
-            err.to_string(),
-            StatusCode::INTERNAL_SERVER_ERROR,
+        Ok(body) => Ok(service.with_amount_scale_header(Box::new(body.to_string()))),
+        Err(err) => Ok(error_reply(&FaucetError::funding(err), &headers)),
+    }
+}
+
+/// If the receiver is given as a name, resolves it and replaces it with the address it points to.
+async fn resolve_name(
+    service: &Service,
+    mut params: MintParams,
+) -> Result<MintParams, FaucetError> {
+    let name = match params.name_to_resolve() {
+        Some(name) => name,
+        None => return Ok(params),
+    };
+
+    if let Err(err) = ans::normalize_name(&name) {
+        return Err(FaucetError::invalid_request(format!(
+            "Invalid name: {}",
+            err
+        )));
+    }
+    let resolver = match service.ans_resolver() {
+        Some(resolver) => resolver,
+        None => {
+            return Err(FaucetError::invalid_request(format!(
+                "This faucet can't fund names, provide the address '{}' points to instead",
+                name
+            )))
+        },
+    };
+    match resolver.resolve(&name).await {
+        Ok(Some(address)) => {
+            info!(name = name, address = address, "resolved name");
+            params.address = Some(address.to_hex_literal());
+            params.name = None;
+            Ok(params)
+        },
+        Ok(None) => Err(FaucetError::invalid_request(format!(
+            "Name '{}' is not registered, or doesn't point to an address",
+            name
         ))),
+        Err(err) => Err(FaucetError::internal(err)),
     }
 }
 
@@ -52,6 +178,7 @@ async fn handle(
 pub enum Response {
     SubmittedTxns(Vec<SignedTransaction>),
     SubmittedTxnsHashes(Vec<HashValue>),
+    SubmittedTxnsDetails(SubmittedTxnsDetails),
 }
 
 impl std::fmt::Display for Response {
@@ -63,17 +190,76 @@ impl std::fmt::Display for Response {
             Response::SubmittedTxnsHashes(value) => {
                 write!(f, "{}", serde_json::to_string(&value).unwrap())
             },
+            Response::SubmittedTxnsDetails(value) => {
+                write!(f, "{}", serde_json::to_string(&value).unwrap())
+            },
         }
     }
 }
 
+impl Response {
+    pub fn txn_hashes(&self) -> Vec<HashValue> {
+        match self {
+            Response::SubmittedTxns(txns) => txns.iter().map(|txn| txn.committed_hash()).collect(),
+            Response::SubmittedTxnsHashes(hashes) => hashes.clone(),
+            Response::SubmittedTxnsDetails(details) => details.txn_hashes.clone(),
+        }
+    }
+}
+
+/// Returned instead of the bare hashes when `detailed=true`.
+#[derive(Debug, Serialize)]
+pub struct SubmittedTxnsDetails {
+    pub txn_hashes: Vec<HashValue>,
+    /// Explorer page of each transaction, if the faucet is configured with a URL template.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explorer_urls: Option<Vec<String>>,
+    /// Latest ledger version seen by the faucet when it submitted the transactions. The
+    /// transactions commit at a later version.
+    pub ledger_version: u64,
+    /// Account the funds were sent from.
+    pub funder: String,
+}
+
+/// URL of the explorer page of a transaction, with `{txn_hash}` standing for its hash, e.g.
+/// `https://explorer.aptoslabs.com/txn/{txn_hash}?network=testnet`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExplorerUrlTemplate(String);
+
+impl ExplorerUrlTemplate {
+    const TXN_HASH: &'static str = "{txn_hash}";
+
+    pub fn url(&self, txn_hash: &HashValue) -> String {
+        self.0.replace(Self::TXN_HASH, &txn_hash.to_hex_literal())
+    }
+}
+
+impl FromStr for ExplorerUrlTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        anyhow::ensure!(
+            s.contains(Self::TXN_HASH),
+            "Explorer URL template must contain {}: {}",
+            Self::TXN_HASH,
+            s
+        );
+        Ok(Self(s.to_string()))
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
 pub struct MintParams {
     pub amount: u64,
     pub auth_key: Option<String>,
     pub address: Option<String>,
     pub pub_key: Option<Ed25519PublicKey>,
+    /// Aptos name, e.g. alice.apt, to fund the address of.
+    pub name: Option<String>,
     pub return_txns: Option<bool>,
+    /// Return a JSON object with the hashes, explorer links, ledger version and funder, rather
+    /// than the bare hashes. Ignored along with `return_txns`.
+    pub detailed: Option<bool>,
 }
 
 impl std::fmt::Display for MintParams {
@@ -83,24 +269,40 @@ impl std::fmt::Display for MintParams {
 }
 
 impl MintParams {
+    /// The name to fund, if any. People commonly paste names in the address field, so an address
+    /// that doesn't parse as one but looks like a name is treated as such.
+    fn name_to_resolve(&self) -> Option<String> {
+        if let Some(name) = self.name.as_ref() {
+            return Some(name.clone());
+        }
+        if self.auth_key.is_some() {
+            return None;
+        }
+        let address = self.address.as_ref()?;
+        if parse_address(address).is_ok() {
+            return None;
+        }
+        ans::normalize_name(address)
+            .is_ok()
+            .then(|| address.clone())
+    }
+
+    /// Rejects a malformed receiver up front, telling what is wrong with it.
+    fn validate(&self) -> std::result::Result<(), InvalidAddress> {
+        if let Some(auth_key) = self.auth_key.as_ref() {
+            parse_auth_key(auth_key)?;
+        } else if let Some(address) = self.address.as_ref() {
+            parse_address(address)?;
+        }
+        Ok(())
+    }
+
     fn receiver(&self) -> Option<AccountAddress> {
         if let Some(auth_key) = self.auth_key.as_ref() {
-            return match AccountAddress::from_hex_literal(auth_key) {
-                Ok(auth_key) => Some(auth_key),
-                Err(_) => match AccountAddress::from_hex(auth_key) {
-                    Ok(auth_key) => Some(auth_key),
-                    Err(_) => None,
-                },
-            };
+            return parse_auth_key(auth_key).ok();
         }
         if let Some(address) = self.address.as_ref() {
-            return match AccountAddress::from_hex_literal(address) {
-                Ok(address) => Some(address),
-                Err(_) => match AccountAddress::from_hex(address) {
-                    Ok(address) => Some(address),
-                    Err(_) => None,
-                },
-            };
+            return parse_address(address).ok();
         }
         if let Some(pub_key) = self.pub_key.as_ref() {
             return Some(AuthenticationKey::ed25519(pub_key).derived_address());
@@ -109,17 +311,90 @@ impl MintParams {
     }
 }
 
+/// Funds the request, from the treasury if the amount is over its threshold, and from the faucet
+/// account otherwise.
 pub async fn process(service: &Service, params: MintParams) -> Result<Response> {
-    let maybe_maximum_amount = service.maximum_amount.unwrap_or(params.amount);
-    let amount = std::cmp::min(params.amount, maybe_maximum_amount);
+    let (receiver, amount) = (params.receiver(), params.amount);
+    let result = process_routed(service, params).await;
+    if let (Ok(_), Some(receiver)) = (&result, receiver) {
+        service.record_funding(receiver, Utc::now()).await;
+    }
+    if let Some(stats) = service.triage_stats() {
+        let outcome = match result {
+            Ok(_) => FundingOutcome::Funded,
+            Err(_) => FundingOutcome::Failed,
+        };
+        stats.record_outcome(outcome, now_secs());
+    }
+    if let Some(webhooks) = service.webhooks() {
+        webhooks.notify(match &result {
+            Ok(response) => FundingEvent::funded(receiver, amount, response.txn_hashes()),
+            Err(err) => FundingEvent::failed(receiver, amount, err.to_string()),
+        });
+    }
+    result
+}
+
+async fn process_routed(service: &Service, params: MintParams) -> Result<Response> {
+    let treasury = match service.treasury_for(params.amount) {
+        Some(treasury) => treasury,
+        None => return process_from_faucet(service, params).await,
+    };
+    let receiver_address = params.receiver().ok_or_else(|| {
+        FaucetError::invalid_request(
+            "You must provide 'address' (preferred), 'pub_key', or 'auth_key'".to_string(),
+        )
+    })?;
+    // Capped like any other amount, but not scaled down with the runway of the faucet account.
+    let amount = std::cmp::min(
+        params.amount,
+        service.maximum_amount.unwrap_or(params.amount),
+    );
+    if let Some(fake_funder) = service.fake_funder() {
+        return fake_fund(service, fake_funder, &params, receiver_address, amount).await;
+    }
+    let (txn, ledger_version) = treasury.fund(service, receiver_address, amount).await?;
+    Ok(mint_response(service, &params, txn, ledger_version))
+}
+
+/// Funds the request from the faucet account, whatever the amount.
+pub(crate) async fn process_from_faucet(service: &Service, params: MintParams) -> Result<Response> {
+    let amount = service.grant_amount(params.amount);
 
     let receiver_address = params.receiver().ok_or_else(|| {
-        anyhow::format_err!("You must provide 'address' (preferred), 'pub_key', or 'auth_key'")
+        FaucetError::invalid_request(
+            "You must provide 'address' (preferred), 'pub_key', or 'auth_key'".to_string(),
+        )
     })?;
 
-    let (mut faucet_seq, mut receiver_seq) = sequences(service, receiver_address).await?;
+    if let Some(key_rotation) = service.key_rotation() {
+        key_rotation.ensure_not_paused()?;
+    }
+    if let Some(fake_funder) = service.fake_funder() {
+        return fake_fund(service, fake_funder, &params, receiver_address, amount).await;
+    }
+
+    let (mut faucet_seq, mut receiver_seq, ledger_version) =
+        sequences(service, receiver_address).await?;
     if receiver_seq.is_some() && amount == 0 {
-        anyhow::bail!("Account is already created and amount asked for is 0");
+        return Err(FaucetError::invalid_request(
+            "Account is already created and amount asked for is 0".to_string(),
+        )
+        .into());
+    }
+
+    if let Some(shared_sequence_numbers) = service.shared_sequence_numbers() {
+        let txn = submit_with_shared_sequence_number(
+            service,
+            shared_sequence_numbers,
+            receiver_address,
+            service
+                .transaction_factory
+                .script(minter_script(receiver_address, amount)),
+            faucet_seq,
+        )
+        .await?;
+        return Ok(mint_response(service, &params, txn, ledger_version));
     }
 
     let our_faucet_seq = {
@@ -136,7 +411,7 @@ pub async fn process(service: &Service, params: MintParams) -> Result<Response>
     let mut set_outstanding = false;
     // We shouldn't have too many outstanding txns
     for _ in 0..60 {
-        if our_faucet_seq < faucet_seq + 50 {
+        if our_faucet_seq < faucet_seq + MAX_OUTSTANDING_TRANSACTIONS {
             // Enforce a stronger ordering of priorities based upon the MintParams that arrived
             // first. Then put the other folks to sleep to try again until the queue fills up.
             if !set_outstanding {
@@ -164,20 +439,23 @@ pub async fn process(service: &Service, params: MintParams) -> Result<Response>
         );
 
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        let (lhs, rhs) = sequences(service, receiver_address).await?;
+        let (lhs, rhs, _) = sequences(service, receiver_address).await?;
         faucet_seq = lhs;
         receiver_seq = rhs;
 
         if receiver_seq.is_some() && amount == 0 {
-            anyhow::bail!("Account is already created and amount asked for is 0");
+            return Err(FaucetError::invalid_request(
+                "Account is already created and amount asked for is 0".to_string(),
+            )
+            .into());
         }
     }
 
     // After 30 seconds, we still have not caught up, we are likely unhealthy
-    if our_faucet_seq >= faucet_seq + 50 {
+    if our_faucet_seq >= faucet_seq + MAX_OUTSTANDING_TRANSACTIONS {
         warn!("We are unhealthy, transactions have likely expired.");
         let mut faucet_account = service.faucet_account.lock().await;
-        if faucet_account.sequence_number() >= faucet_seq + 50 {
+        if faucet_account.sequence_number() >= faucet_seq + MAX_OUTSTANDING_TRANSACTIONS {
             info!("Resetting the sequence number counter.");
             *faucet_account.sequence_number_mut() = faucet_seq;
         } else {
@@ -185,15 +463,14 @@ pub async fn process(service: &Service, params: MintParams) -> Result<Response>
         }
     }
 
-    let txn = {
-        let mut faucet_account = service.faucet_account.lock().await;
-        faucet_account.sign_with_transaction_builder(service.transaction_factory.script(
-            Script::new(MINTER_SCRIPT.to_vec(), vec![], vec![
-                TransactionArgument::Address(receiver_address),
-                TransactionArgument::U64(amount),
-            ]),
-        ))
-    };
+    let txn = sign(
+        service,
+        service
+            .transaction_factory
+            .script(minter_script(receiver_address, amount)),
+        None,
+    )
+    .await;
 
     let response = service.client.submit(&txn).await;
 
@@ -204,14 +481,151 @@ pub async fn process(service: &Service, params: MintParams) -> Result<Response>
         response?;
     }
 
+    Ok(mint_response(service, &params, txn, ledger_version))
+}
+
+/// Like the local sequence number handling above, but with sequence numbers leased from the
+/// counter shared with the other replicas. The receiver is only used to refresh the faucet's
+/// sequence number.
+pub(crate) async fn submit_with_shared_sequence_number(
+    service: &Service,
+    shared_sequence_numbers: &SharedSequenceNumbers,
+    receiver_address: AccountAddress,
+    builder: TransactionBuilder,
+    mut faucet_seq: u64,
+) -> Result<SignedTransaction> {
+    let mut leased_seq = None;
+    for _ in 0..60 {
+        leased_seq = shared_sequence_numbers.lease(faucet_seq).await?;
+        if leased_seq.is_some() {
+            break;
+        }
+        warn!("Too many outstanding transactions across replicas. Sleeping to let the system catchup.");
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        faucet_seq = sequences(service, receiver_address).await?.0;
+    }
+    let leased_seq = leased_seq.ok_or_else(|| {
+        FaucetError::new(
+            FaucetErrorCode::Overloaded,
+            "Too many outstanding transactions, transactions have likely expired".to_string(),
+        )
+    })?;
+
+    let txn = sign(service, builder, Some(leased_seq)).await;
+    if let Err(e) = service.client.submit(&txn).await {
+        shared_sequence_numbers.reset(faucet_seq).await?;
+        return Err(e.into());
+    }
+    Ok(txn)
+}
+
+/// Answers like `process`, from the fake funder. There is no ledger to report the version of, so
+/// it's 0.
+async fn fake_fund(
+    service: &Service,
+    fake_funder: &FakeFunder,
+    params: &MintParams,
+    receiver_address: AccountAddress,
+    amount: u64,
+) -> Result<Response> {
+    let mut txns = fake_funder
+        .fund(service, vec![service
+            .transaction_factory
+            .script(minter_script(receiver_address, amount))])
+        .await?;
+    Ok(mint_response(service, params, txns.remove(0), 0))
+}
+
+/// Answers like `process`, with a made up transaction that's never submitted. Its hash is never
+/// found on chain, as if it expired.
+async fn shadow_response(
+    service: &Service,
+    params: &MintParams,
+    receiver_address: AccountAddress,
+) -> Result<Response> {
+    let amount = service.grant_amount(params.amount);
+    let ledger_version = match service.fake_funder() {
+        Some(_) => 0,
+        None => sequences(service, receiver_address).await?.2,
+    };
+    let (faucet_address, faucet_seq) = {
+        let faucet_account = service.faucet_account.lock().await;
+        (faucet_account.address(), faucet_account.sequence_number())
+    };
+    let txn = shadow_transaction(
+        service
+            .transaction_factory
+            .script(minter_script(receiver_address, amount)),
+        faucet_address,
+        faucet_seq,
+    );
+    Ok(mint_response(service, params, txn, ledger_version))
+}
+
+/// A transaction from the faucet as far as the client can tell, which can't be submitted even if
+/// the client tries: it's signed with a throwaway key rather than the one of the faucet, with a
+/// sequence number the faucet already used.
+fn shadow_transaction(
+    builder: TransactionBuilder,
+    faucet_address: AccountAddress,
+    faucet_seq: u64,
+) -> SignedTransaction {
+    LocalAccount::new(
+        faucet_address,
+        AccountKey::generate(&mut rand::rngs::OsRng),
+        faucet_seq.saturating_sub(1),
+    )
+    .sign_with_transaction_builder(builder)
+}
+
+pub(crate) fn minter_script(receiver_address: AccountAddress, amount: u64) -> Script {
+    Script::new(MINTER_SCRIPT.to_vec(), vec![], vec![
+        TransactionArgument::Address(receiver_address),
+        TransactionArgument::U64(amount),
+    ])
+}
+
+async fn sign(
+    service: &Service,
+    builder: TransactionBuilder,
+    sequence_number: Option<u64>,
+) -> SignedTransaction {
+    let mut faucet_account = service.faucet_account.lock().await;
+    if let Some(sequence_number) = sequence_number {
+        *faucet_account.sequence_number_mut() = sequence_number;
+    }
+    faucet_account.sign_with_transaction_builder(builder)
+}
+
+fn mint_response(
+    service: &Service,
+    params: &MintParams,
+    txn: SignedTransaction,
+    ledger_version: u64,
+) -> Response {
     if params.return_txns.unwrap_or(false) {
-        Ok(Response::SubmittedTxns(vec![txn]))
+        Response::SubmittedTxns(vec![txn])
+    } else if params.detailed.unwrap_or(false) {
+        let txn_hashes = vec![txn.committed_hash()];
+        Response::SubmittedTxnsDetails(SubmittedTxnsDetails {
+            explorer_urls: service
+                .explorer_url_template()
+                .map(|template| txn_hashes.iter().map(|hash| template.url(hash)).collect()),
+            txn_hashes,
+            ledger_version,
+            funder: txn.sender().to_hex_literal(),
+        })
     } else {
-        Ok(Response::SubmittedTxnsHashes(vec![txn.committed_hash()]))
+        Response::SubmittedTxnsHashes(vec![txn.committed_hash()])
     }
 }
 
-async fn sequences(service: &Service, receiver: AccountAddress) -> Result<(u64, Option<u64>)> {
+/// Returns the sequence numbers of the faucet and of the receiver, if it exists, along with the
+/// latest ledger version.
+pub(crate) async fn sequences(
+    service: &Service,
+    receiver: AccountAddress,
+) -> Result<(u64, Option<u64>, u64)> {
     let faucet_address = service.faucet_account.lock().await.address();
     let f_request = service.client.get_account(faucet_address);
     let r_request = service.client.get_account(receiver);
@@ -222,11 +636,41 @@ async fn sequences(service: &Service, receiver: AccountAddress) -> Result<(u64,
         .as_ref()
         .ok()
         .map(|account| account.inner().sequence_number);
-    let faucet_seq_num = responses
+    let faucet_account = responses
         .remove(0)
-        .map_err(|e| anyhow::format_err!("Faucet account {} not found: {:#}", faucet_address, e))?
-        .inner()
-        .sequence_number;
+        .map_err(|e| anyhow::format_err!("Faucet account {} not found: {:#}", faucet_address, e))?;
+
+    Ok((
+        faucet_account.inner().sequence_number,
+        receiver_seq_num,
+        faucet_account.state().version,
+    ))
+}
 
-    Ok((faucet_seq_num, receiver_seq_num))
+#[cfg(test)]
+mod tests {
+    use super::{minter_script, shadow_transaction};
+    use aptos_sdk::{
+        transaction_builder::TransactionFactory,
+        types::{account_address::AccountAddress, chain_id::ChainId, LocalAccount},
+    };
+
+    #[test]
+    fn test_shadow_transaction_cannot_be_submitted() {
+        let faucet_account = LocalAccount::generate(&mut rand::rngs::OsRng);
+        let txn = shadow_transaction(
+            TransactionFactory::new(ChainId::test())
+                .script(minter_script(AccountAddress::random(), 100)),
+            faucet_account.address(),
+            5,
+        );
+
+        assert_eq!(txn.sender(), faucet_account.address());
+        // Rejected for the key, and for the sequence number otherwise.
+        assert_ne!(
+            txn.authenticator().sender().authentication_key(),
+            faucet_account.authentication_key()
+        );
+        assert!(txn.sequence_number() < 5);
+    }
 }
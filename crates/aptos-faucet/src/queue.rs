@@ -0,0 +1,549 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Queueing of mint requests. Bursts, e.g. a workshop where hundreds of people ask for coins
+//! within a minute, would otherwise pile up on the outstanding transaction limit and time out.
+//! Accepted requests instead wait their turn in a bounded FIFO queue, served by a fixed number of
+//! workers, and only those over its capacity are turned away.
+//!
+//! A request waits for its turn and answers as usual, with its position on arrival in a header,
+//! unless it has `async=true`: it's then answered right away with an id, position and estimated
+//! wait, and its outcome is polled with `GET /requests/<id>`.
+//!
+//! Requests processing for longer than `stale_after`, e.g. stuck on a node that stopped
+//! answering, are marked failed by a reaper, so neither their clients nor the count of requests
+//! in flight wait on them forever. They keep processing, and may still get funded, so they fail
+//! with an outcome unknown, which isn't retryable. Should they complete afterwards, their outcome
+//! is only logged.
+
+use crate::{
+    errors::{error_reply, FaucetError, FaucetErrorCode},
+    metrics::{QUEUE_LENGTH, QUEUE_PROCESSING, QUEUE_REAPED_REQUESTS},
+    mint::{self, MintParams},
+    Service,
+};
+use aptos_logger::{info, warn};
+use futures::lock::Mutex;
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{oneshot, Notify};
+use warp::{http::HeaderMap, Filter, Rejection, Reply};
+
+/// Header of the replies to queued requests, carrying their position on arrival, 1 being next.
+pub const QUEUE_POSITION_HEADER: &str = "X-Aptos-Faucet-Queue-Position";
+
+/// Outcomes of async requests are kept this long for clients to poll them.
+const OUTCOME_TTL: Duration = Duration::from_secs(600);
+/// Bound on the number of outcomes kept, beyond which the oldest are dropped.
+const MAX_OUTCOMES: usize = 10_000;
+/// Processing time assumed before any request was processed.
+const INITIAL_PROCESSING_SECS: f64 = 1.0;
+/// Requests processing for longer than this are marked failed, unless set otherwise.
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Deserialize)]
+pub struct QueueParams {
+    /// Answer right away with the id of the request, rather than once it's processed.
+    #[serde(rename = "async")]
+    pub asynchronous: Option<bool>,
+}
+
+/// Where a queued request stands.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RequestStatus {
+    Queued {
+        position: usize,
+        eta_secs: u64,
+    },
+    Processing,
+    /// `response` is what the request would have been answered with without `async=true`.
+    Done {
+        response: String,
+    },
+    Failed {
+        error: String,
+        /// Whether sending the request again may succeed, see `FaucetError`.
+        retryable: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<u64>,
+    },
+}
+
+/// Body of `GET /requests/<id>`, and of the reply to an async request.
+#[derive(Debug, Serialize)]
+pub struct RequestStatusBody {
+    pub request_id: String,
+    #[serde(flatten)]
+    pub status: RequestStatus,
+}
+
+type Outcome = Result<String, FaucetError>;
+
+struct Job {
+    id: u64,
+    params: MintParams,
+    /// The client waiting for the outcome, if it didn't ask for an async answer.
+    waiter: Option<oneshot::Sender<Outcome>>,
+}
+
+/// A request taken out of the queue by a worker.
+struct Processing {
+    start: Instant,
+    waiter: Option<oneshot::Sender<Outcome>>,
+}
+
+struct QueueState {
+    queued: VecDeque<Job>,
+    processing: HashMap<u64, Processing>,
+    /// Outcomes of async requests, with when they were known.
+    outcomes: HashMap<u64, (Instant, Outcome)>,
+    /// Moving average of the time a request takes to process.
+    avg_processing_secs: f64,
+}
+
+pub struct MintQueue {
+    capacity: usize,
+    /// Number of requests processed at once.
+    concurrency: usize,
+    /// Requests processing for longer than this are marked failed.
+    stale_after: Duration,
+    state: Mutex<QueueState>,
+    /// Wakes up a worker when a request was queued.
+    queued: Notify,
+}
+
+impl MintQueue {
+    pub fn new(capacity: usize, concurrency: usize) -> Self {
+        Self {
+            capacity,
+            concurrency: concurrency.max(1),
+            stale_after: DEFAULT_STALE_AFTER,
+            state: Mutex::new(QueueState {
+                queued: VecDeque::with_capacity(capacity),
+                processing: HashMap::new(),
+                outcomes: HashMap::new(),
+                avg_processing_secs: INITIAL_PROCESSING_SECS,
+            }),
+            queued: Notify::new(),
+        }
+    }
+
+    pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// Estimated wait, in seconds, of the request at that position.
+    fn eta_secs(&self, position: usize, avg_processing_secs: f64) -> u64 {
+        (position as f64 * avg_processing_secs / self.concurrency as f64).ceil() as u64
+    }
+
+    /// Queues the request, returning its id and position, or the estimated wait of the last
+    /// request if the queue is full.
+    async fn enqueue(
+        &self,
+        params: MintParams,
+        waiter: Option<oneshot::Sender<Outcome>>,
+    ) -> Result<(u64, usize), u64> {
+        let mut state = self.state.lock().await;
+        if state.queued.len() >= self.capacity {
+            return Err(self.eta_secs(state.queued.len(), state.avg_processing_secs));
+        }
+        let id = rand::rngs::OsRng.next_u64();
+        state.queued.push_back(Job { id, params, waiter });
+        QUEUE_LENGTH.set(state.queued.len() as i64);
+        self.queued.notify_one();
+        Ok((id, state.queued.len()))
+    }
+
+    pub async fn status(&self, id: u64) -> Option<RequestStatus> {
+        let state = self.state.lock().await;
+        if let Some(index) = state.queued.iter().position(|job| job.id == id) {
+            return Some(RequestStatus::Queued {
+                position: index + 1,
+                eta_secs: self.eta_secs(index + 1, state.avg_processing_secs),
+            });
+        }
+        if state.processing.contains_key(&id) {
+            return Some(RequestStatus::Processing);
+        }
+        state.outcomes.get(&id).map(|(_, outcome)| match outcome {
+            Ok(response) => RequestStatus::Done {
+                response: response.clone(),
+            },
+            Err(error) => RequestStatus::Failed {
+                error: error.message.clone(),
+                retryable: error.retryable,
+                retry_after_secs: error.retry_after_secs,
+            },
+        })
+    }
+
+    /// Takes the oldest request out of the queue, waiting for one if needed, returning its id
+    /// and parameters.
+    async fn next(&self) -> (u64, MintParams) {
+        loop {
+            {
+                let mut state = self.state.lock().await;
+                if let Some(job) = state.queued.pop_front() {
+                    QUEUE_LENGTH.set(state.queued.len() as i64);
+                    state.processing.insert(job.id, Processing {
+                        start: Instant::now(),
+                        waiter: job.waiter,
+                    });
+                    QUEUE_PROCESSING.set(state.processing.len() as i64);
+                    return (job.id, job.params);
+                }
+            }
+            self.queued.notified().await;
+        }
+    }
+
+    async fn finish(&self, id: u64, outcome: Outcome, processing_time: Duration) {
+        let mut state = self.state.lock().await;
+        let processing = match state.processing.remove(&id) {
+            Some(processing) => processing,
+            None => {
+                info!(
+                    request_id = request_id(id),
+                    processing_secs = processing_time.as_secs(),
+                    funded = outcome.is_ok(),
+                    "dropped the outcome of a request already marked failed"
+                );
+                return;
+            },
+        };
+        QUEUE_PROCESSING.set(state.processing.len() as i64);
+        state.avg_processing_secs =
+            0.9 * state.avg_processing_secs + 0.1 * processing_time.as_secs_f64();
+        state.deliver(id, processing.waiter, outcome);
+    }
+
+    /// Marks the requests processing since before `now - stale_after` failed, returning how many.
+    async fn reap(&self, now: Instant) -> usize {
+        let mut state = self.state.lock().await;
+        let stale: Vec<_> = state
+            .processing
+            .iter()
+            .filter(|(_, processing)| {
+                now.saturating_duration_since(processing.start) > self.stale_after
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &stale {
+            let processing = state
+                .processing
+                .remove(id)
+                .expect("Stale request must exist");
+            warn!(
+                request_id = request_id(*id),
+                processing_secs = now.saturating_duration_since(processing.start).as_secs(),
+                "marked a stale request failed"
+            );
+            state.deliver(
+                *id,
+                processing.waiter,
+                Err(FaucetError::new(
+                    FaucetErrorCode::OutcomeUnknown,
+                    format!(
+                        "Request {} is still processing after {} seconds, and may yet be funded: \
+                        check the balance of the receiver before sending it again",
+                        request_id(*id),
+                        self.stale_after.as_secs()
+                    ),
+                )),
+            );
+        }
+        QUEUE_PROCESSING.set(state.processing.len() as i64);
+        QUEUE_REAPED_REQUESTS.inc_by(stale.len() as u64);
+        stale.len()
+    }
+
+    /// Marks stale requests failed periodically. Runs forever.
+    async fn run_reaper(&self) {
+        let mut interval =
+            tokio::time::interval((self.stale_after / 10).max(Duration::from_secs(1)));
+        loop {
+            interval.tick().await;
+            self.reap(Instant::now()).await;
+        }
+    }
+
+    /// Processes queued requests through the given service. Runs forever.
+    pub async fn run(self: Arc<Self>, service: Arc<Service>) {
+        info!(
+            capacity = self.capacity,
+            concurrency = self.concurrency,
+            stale_after_secs = self.stale_after.as_secs(),
+            "processing queued mint requests"
+        );
+        let workers = futures::future::join_all((0..self.concurrency).map(|_| {
+            let queue = self.clone();
+            let service = service.clone();
+            async move {
+                loop {
+                    let (id, params) = queue.next().await;
+                    let start = Instant::now();
+                    let outcome = mint::process(&service, params)
+                        .await
+                        .map(|response| response.to_string())
+                        .map_err(FaucetError::funding);
+                    queue.finish(id, outcome, start.elapsed()).await;
+                }
+            }
+        }));
+        futures::future::join(workers, self.run_reaper()).await;
+    }
+}
+
+impl QueueState {
+    /// Hands the outcome to the client waiting for it, or keeps it for the client to poll.
+    fn deliver(&mut self, id: u64, waiter: Option<oneshot::Sender<Outcome>>, outcome: Outcome) {
+        match waiter {
+            // The client may have gone away in the meantime, nothing to do then.
+            Some(waiter) => {
+                let _ = waiter.send(outcome);
+            },
+            None => {
+                let now = Instant::now();
+                self.outcomes
+                    .retain(|_, (time, _)| now.duration_since(*time) < OUTCOME_TTL);
+                if self.outcomes.len() >= MAX_OUTCOMES {
+                    if let Some(oldest) = self
+                        .outcomes
+                        .iter()
+                        .min_by_key(|(_, (time, _))| *time)
+                        .map(|(id, _)| *id)
+                    {
+                        self.outcomes.remove(&oldest);
+                    }
+                }
+                self.outcomes.insert(id, (now, outcome));
+            },
+        }
+    }
+}
+
+fn request_id(id: u64) -> String {
+    format!("{:016x}", id)
+}
+
+/// Queues a request that passed the checkers, and answers it once processed, or right away if
+/// asked for an async answer.
+pub(crate) async fn handle_queued(
+    service: &Service,
+    queue: &MintQueue,
+    params: MintParams,
+    queue_params: QueueParams,
+    headers: &HeaderMap,
+) -> Box<dyn Reply> {
+    let asynchronous = queue_params.asynchronous.unwrap_or(false);
+    let (waiter, outcome) = if asynchronous {
+        (None, None)
+    } else {
+        let (sender, receiver) = oneshot::channel();
+        (Some(sender), Some(receiver))
+    };
+
+    let (id, position) = match queue.enqueue(params, waiter).await {
+        Ok(queued) => queued,
+        Err(eta_secs) => {
+            return error_reply(
+                &FaucetError::new(
+                    FaucetErrorCode::Overloaded,
+                    format!(
+                        "The faucet is busy with {} queued requests, try again in {} seconds",
+                        queue.capacity, eta_secs
+                    ),
+                )
+                .with_retry_after_secs(eta_secs),
+                headers,
+            )
+        },
+    };
+
+    let reply: Box<dyn Reply> = match outcome {
+        None => {
+            let eta_secs = queue.eta_secs(position, queue.state.lock().await.avg_processing_secs);
+            Box::new(warp::reply::with_status(
+                warp::reply::json(&RequestStatusBody {
+                    request_id: request_id(id),
+                    status: RequestStatus::Queued { position, eta_secs },
+                }),
+                StatusCode::ACCEPTED,
+            ))
+        },
+        Some(outcome) => match outcome.await {
+            Ok(Ok(body)) => service.with_amount_scale_header(Box::new(body)),
+            Ok(Err(err)) => error_reply(&err, headers),
+            Err(_) => error_reply(
+                &FaucetError::new(
+                    FaucetErrorCode::Internal,
+                    "The request was dropped from the queue".to_string(),
+                ),
+                headers,
+            ),
+        },
+    };
+    Box::new(warp::reply::with_header(
+        reply,
+        QUEUE_POSITION_HEADER,
+        position.to_string(),
+    ))
+}
+
+pub fn queue_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // GET /requests/<id>
+    warp::path!("requests" / String)
+        .and(warp::get())
+        .and(warp::any().map(move || service.clone()))
+        .and_then(handle_status)
+}
+
+async fn handle_status(
+    request_id: String,
+    service: Arc<Service>,
+) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let not_found = || -> Box<dyn warp::Reply> {
+        Box::new(warp::reply::with_status(
+            "Unknown request, or its outcome expired".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    };
+    let (queue, id) = match (service.mint_queue(), u64::from_str_radix(&request_id, 16)) {
+        (Some(queue), Ok(id)) => (queue, id),
+        _ => return Ok(not_found()),
+    };
+    Ok(match queue.status(id).await {
+        Some(status) => Box::new(warp::reply::json(&RequestStatusBody { request_id, status })),
+        None => not_found(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{request_id, MintQueue, RequestStatus};
+    use crate::{errors::FaucetErrorCode, mint::MintParams};
+    use std::time::{Duration, Instant};
+
+    fn params(amount: u64) -> MintParams {
+        MintParams {
+            amount,
+            auth_key: None,
+            address: Some("0x1".to_string()),
+            pub_key: None,
+            name: None,
+            return_txns: None,
+            detailed: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue() {
+        let queue = MintQueue::new(2, 2);
+        let (first, position) = queue.enqueue(params(1), None).await.unwrap();
+        assert_eq!(position, 1);
+        let (second, position) = queue.enqueue(params(2), None).await.unwrap();
+        assert_eq!(position, 2);
+        // Full, with two requests ahead processed two at a time, a second each.
+        assert_eq!(queue.enqueue(params(3), None).await, Err(1));
+        assert_eq!(
+            queue.status(second).await,
+            Some(RequestStatus::Queued {
+                position: 2,
+                eta_secs: 1
+            })
+        );
+
+        // First in, first out.
+        let (id, _) = queue.next().await;
+        assert_eq!(id, first);
+        assert_eq!(queue.status(first).await, Some(RequestStatus::Processing));
+        assert_eq!(
+            queue.status(second).await,
+            Some(RequestStatus::Queued {
+                position: 1,
+                eta_secs: 1
+            })
+        );
+        queue
+            .finish(id, Ok("[\"0xabc\"]".to_string()), Duration::from_secs(11))
+            .await;
+        assert_eq!(
+            queue.status(first).await,
+            Some(RequestStatus::Done {
+                response: "[\"0xabc\"]".to_string()
+            })
+        );
+        // Slower processing makes for longer waits.
+        let (third, position) = queue.enqueue(params(3), None).await.unwrap();
+        assert_eq!(position, 2);
+        assert_eq!(
+            queue.status(third).await,
+            Some(RequestStatus::Queued {
+                position: 2,
+                eta_secs: 2
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reap_stale_requests() {
+        let queue = MintQueue::new(2, 2).with_stale_after(Duration::from_secs(60));
+        let (first, _) = queue.enqueue(params(1), None).await.unwrap();
+        let ((second, _), outcome) = {
+            let (sender, receiver) = tokio::sync::oneshot::channel();
+            (
+                queue.enqueue(params(2), Some(sender)).await.unwrap(),
+                receiver,
+            )
+        };
+        queue.next().await;
+        queue.next().await;
+
+        assert_eq!(queue.reap(Instant::now()).await, 0);
+        assert_eq!(queue.status(first).await, Some(RequestStatus::Processing));
+        assert_eq!(
+            queue.reap(Instant::now() + Duration::from_secs(61)).await,
+            2
+        );
+        // They may still get funded, retrying could fund them twice.
+        assert!(matches!(
+            queue.status(first).await,
+            Some(RequestStatus::Failed {
+                retryable: false,
+                ..
+            })
+        ));
+        // The waiting client is answered too.
+        let error = outcome.await.unwrap().unwrap_err();
+        assert_eq!(error.code, FaucetErrorCode::OutcomeUnknown);
+        assert!(error.message.contains(&request_id(second)));
+
+        // Outcomes of requests completing after being reaped are dropped.
+        queue
+            .finish(
+                first,
+                Ok("[\"0xabc\"]".to_string()),
+                Duration::from_secs(61),
+            )
+            .await;
+        assert!(matches!(
+            queue.status(first).await,
+            Some(RequestStatus::Failed { .. })
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Sharing of abuse signals between deployments, e.g. devnet and testnet faucets, which are
+//! farmed by the same networks. Each deployment periodically posts the IP prefixes its checkers
+//! flagged recently (shadow banned, or spiking in velocity) to a shared store over HTTP, and gets
+//! back the merged list of all deployments, whose prefixes `SharedReputationChecker` then rejects.
+//!
+//! The store is expected to answer `POST <url>` with a `ReputationReport` of a deployment, and
+//! `GET <url>` with the `ReputationList` merged from the latest report of every deployment.
+
+use anyhow::{Context, Result};
+use aptos_logger::{info, warn};
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// Why a prefix was flagged.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbuseSignal {
+    ShadowBanned,
+    AnomalousVelocity,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FlaggedPrefix {
+    #[serde(
+        serialize_with = "serialize_prefix",
+        deserialize_with = "deserialize_prefix"
+    )]
+    pub prefix: IpNet,
+    pub signal: AbuseSignal,
+    /// When the prefix was last flagged, in seconds since the unix epoch.
+    pub last_seen_secs: u64,
+    /// Deployment that flagged the prefix, set by the store in the merged list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<String>,
+}
+
+fn serialize_prefix<S: Serializer>(prefix: &IpNet, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(prefix)
+}
+
+fn deserialize_prefix<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IpNet, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+/// What a deployment posts to the store.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReputationReport {
+    pub deployment: String,
+    pub prefixes: Vec<FlaggedPrefix>,
+}
+
+/// What the store answers with.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReputationList {
+    pub prefixes: Vec<FlaggedPrefix>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReputationConfig {
+    /// Name of this deployment, for its own prefixes not to be imported back.
+    pub deployment: String,
+    pub sync_interval_secs: u64,
+    /// Prefixes not flagged for this long are neither exported nor enforced.
+    pub ttl_secs: u64,
+}
+
+pub struct IpReputation {
+    config: ReputationConfig,
+    /// Prefixes flagged by the checkers of this deployment.
+    local: Mutex<HashMap<IpNet, (AbuseSignal, u64)>>,
+    /// Prefixes flagged by other deployments, as of the last sync.
+    shared: RwLock<Vec<FlaggedPrefix>>,
+}
+
+impl IpReputation {
+    pub fn new(config: ReputationConfig) -> Self {
+        Self {
+            config,
+            local: Mutex::new(HashMap::new()),
+            shared: RwLock::new(vec![]),
+        }
+    }
+
+    pub fn ttl_secs(&self) -> u64 {
+        self.config.ttl_secs
+    }
+
+    /// Records that a checker flagged the prefix, to share it with the other deployments.
+    pub fn report(&self, prefix: IpNet, signal: AbuseSignal, now_secs: u64) {
+        self.local
+            .lock()
+            .unwrap()
+            .insert(prefix.trunc(), (signal, now_secs));
+    }
+
+    /// The prefix flagged by another deployment that the address is in, if any.
+    pub fn lookup(&self, ip: IpAddr, now_secs: u64) -> Option<FlaggedPrefix> {
+        self.shared
+            .read()
+            .unwrap()
+            .iter()
+            .find(|flagged| flagged.prefix.contains(&ip) && !self.expired(flagged, now_secs))
+            .cloned()
+    }
+
+    fn expired(&self, flagged: &FlaggedPrefix, now_secs: u64) -> bool {
+        flagged.last_seen_secs + self.config.ttl_secs <= now_secs
+    }
+
+    /// The prefixes flagged locally and not expired, forgetting the expired ones.
+    pub fn export(&self, now_secs: u64) -> ReputationReport {
+        let ttl_secs = self.config.ttl_secs;
+        let mut local = self.local.lock().unwrap();
+        local.retain(|_, (_, last_seen_secs)| *last_seen_secs + ttl_secs > now_secs);
+        ReputationReport {
+            deployment: self.config.deployment.clone(),
+            prefixes: local
+                .iter()
+                .map(|(prefix, (signal, last_seen_secs))| FlaggedPrefix {
+                    prefix: *prefix,
+                    signal: *signal,
+                    last_seen_secs: *last_seen_secs,
+                    deployment: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replaces the prefixes flagged by other deployments with those of the merged list.
+    pub fn import(&self, list: ReputationList, now_secs: u64) {
+        let shared: Vec<_> = list
+            .prefixes
+            .into_iter()
+            .filter(|flagged| {
+                flagged.deployment.as_deref() != Some(self.config.deployment.as_str())
+                    && !self.expired(flagged, now_secs)
+            })
+            .collect();
+        let num_shared = shared.len();
+        let num_previous = std::mem::replace(&mut *self.shared.write().unwrap(), shared).len();
+        if num_shared != num_previous {
+            info!(
+                "IP prefixes flagged by other deployments went from {} to {}",
+                num_previous, num_shared
+            );
+        }
+    }
+
+    async fn sync(&self, client: &reqwest::Client, url: &Url) -> Result<()> {
+        let now = now_secs();
+        client
+            .post(url.clone())
+            .json(&self.export(now))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to export IP reputation")?;
+        let list: ReputationList = client
+            .get(url.clone())
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to import IP reputation")?
+            .json()
+            .await?;
+        self.import(list, now_secs());
+        Ok(())
+    }
+
+    /// Syncs with the store at the URL, forever.
+    pub async fn run(self: Arc<Self>, url: Url) {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.sync_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.sync(&client, &url).await {
+                warn!("Failed to sync IP reputation with {}: {:#}", url, e);
+            }
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AbuseSignal, FlaggedPrefix, IpReputation, ReputationConfig, ReputationList};
+
+    #[test]
+    fn test_reputation() {
+        let reputation = IpReputation::new(ReputationConfig {
+            deployment: "devnet".to_string(),
+            sync_interval_secs: 60,
+            ttl_secs: 100,
+        });
+        reputation.report(
+            "10.0.0.7/24".parse().unwrap(),
+            AbuseSignal::AnomalousVelocity,
+            0,
+        );
+        let report = reputation.export(50);
+        assert_eq!(report.deployment, "devnet");
+        assert_eq!(report.prefixes, vec![FlaggedPrefix {
+            prefix: "10.0.0.0/24".parse().unwrap(),
+            signal: AbuseSignal::AnomalousVelocity,
+            last_seen_secs: 0,
+            deployment: None,
+        }]);
+        assert_eq!(
+            serde_json::to_string(&report.prefixes[0]).unwrap(),
+            r#"{"prefix":"10.0.0.0/24","signal":"anomalous_velocity","last_seen_secs":0}"#
+        );
+        assert!(reputation.export(100).prefixes.is_empty());
+
+        let list: ReputationList = serde_json::from_str(
+            r#"{"prefixes":[
+                {"prefix":"10.0.0.0/24","signal":"anomalous_velocity","last_seen_secs":0,"deployment":"devnet"},
+                {"prefix":"192.0.2.0/24","signal":"shadow_banned","last_seen_secs":10,"deployment":"testnet"},
+                {"prefix":"198.51.100.0/24","signal":"shadow_banned","last_seen_secs":0,"deployment":"testnet"}
+            ]}"#,
+        )
+        .unwrap();
+        reputation.import(list, 50);
+        // Its own prefixes aren't imported back.
+        assert_eq!(reputation.lookup("10.0.0.1".parse().unwrap(), 50), None);
+        let flagged = reputation.lookup("192.0.2.1".parse().unwrap(), 50).unwrap();
+        assert_eq!(flagged.signal, AbuseSignal::ShadowBanned);
+        assert_eq!(flagged.deployment.as_deref(), Some("testnet"));
+        assert!(reputation
+            .lookup("198.51.100.1".parse().unwrap(), 50)
+            .is_some());
+        // Until they expire.
+        assert!(reputation
+            .lookup("198.51.100.1".parse().unwrap(), 100)
+            .is_none());
+        assert!(reputation
+            .lookup("192.0.2.1".parse().unwrap(), 100)
+            .is_some());
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Scaling down of the amounts granted as the funder account runs low. The balance of the funder
+//! is polled, and its runway is projected from how fast it went down over a recent window. When
+//! the runway drops below the configured minimum, amounts are scaled down in proportion, so that
+//! the faucet keeps serving everyone a little rather than a few people in full until it's dry.
+
+use crate::{metrics, Service};
+use aptos_logger::{info, warn};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug)]
+pub struct RunwayConfig {
+    /// Runway under which amounts are scaled down.
+    pub min_runway_secs: u64,
+    /// Window the spend rate is measured over.
+    pub window_secs: u64,
+    /// Scale amounts are never scaled down below, however short the runway.
+    pub min_scale: f64,
+    /// How often the balance of the funder is polled.
+    pub poll_interval_secs: u64,
+}
+
+pub struct RunwayMonitor {
+    config: RunwayConfig,
+    /// Balance of the funder over the window, by unix timestamp, oldest first.
+    samples: Mutex<VecDeque<(u64, u64)>>,
+    scale: Mutex<f64>,
+}
+
+impl RunwayMonitor {
+    pub fn new(config: RunwayConfig) -> Self {
+        Self {
+            config,
+            samples: Mutex::new(VecDeque::new()),
+            scale: Mutex::new(1.0),
+        }
+    }
+
+    /// Current factor amounts are multiplied by, between `min_scale` and 1.
+    pub fn scale(&self) -> f64 {
+        *self.scale.lock().unwrap()
+    }
+
+    pub fn scale_amount(&self, amount: u64) -> u64 {
+        (amount as f64 * self.scale()) as u64
+    }
+
+    /// Records the balance of the funder at the given time, and updates the scale accordingly.
+    pub fn record(&self, timestamp_secs: u64, balance: u64) {
+        let runway_secs = {
+            let mut samples = self.samples.lock().unwrap();
+            samples.push_back((timestamp_secs, balance));
+            // Keep the newest sample older than the window, for the window to be fully covered.
+            while samples.len() > 2 && samples[1].0 + self.config.window_secs <= timestamp_secs {
+                samples.pop_front();
+            }
+            runway_secs(&samples)
+        };
+        let scale = match runway_secs {
+            Some(runway_secs) if runway_secs < self.config.min_runway_secs as f64 => {
+                (runway_secs / self.config.min_runway_secs as f64).max(self.config.min_scale)
+            },
+            _ => 1.0,
+        };
+
+        let previous_scale = std::mem::replace(&mut *self.scale.lock().unwrap(), scale);
+        if scale != previous_scale {
+            info!(
+                balance = balance,
+                runway_secs = runway_secs,
+                "amount scale changed from {} to {}",
+                previous_scale,
+                scale
+            );
+        }
+        metrics::FUNDER_BALANCE.set(balance as i64);
+        metrics::FUNDER_RUNWAY_SECS.set(runway_secs.unwrap_or(-1.0));
+        metrics::AMOUNT_SCALE.set(scale);
+    }
+
+    /// Polls the balance of the funder of the service, forever.
+    pub async fn run(self: Arc<Self>, service: Arc<Service>) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.poll_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            let address = service.faucet_account.lock().await.address();
+            match service.client.get_account_balance(address).await {
+                Ok(balance) => {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("Time went backwards")
+                        .as_secs();
+                    self.record(now, balance.into_inner().get());
+                },
+                Err(e) => warn!("Failed to get the balance of funder {}: {}", address, e),
+            }
+        }
+    }
+}
+
+/// Seconds until the balance runs out at the rate it was spent over the samples, if it was spent
+/// at all. Balance going up, e.g. when the funder is topped up, doesn't count against spending.
+fn runway_secs(samples: &VecDeque<(u64, u64)>) -> Option<f64> {
+    let (first, last) = (samples.front()?, samples.back()?);
+    let elapsed_secs = last.0.checked_sub(first.0).filter(|secs| *secs > 0)?;
+    let spent: u64 = samples
+        .iter()
+        .zip(samples.iter().skip(1))
+        .map(|((_, before), (_, after))| before.saturating_sub(*after))
+        .sum();
+    if spent == 0 {
+        return None;
+    }
+    Some(last.1 as f64 / (spent as f64 / elapsed_secs as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunwayConfig, RunwayMonitor};
+
+    #[test]
+    fn test_scale() {
+        let monitor = RunwayMonitor::new(RunwayConfig {
+            min_runway_secs: 1000,
+            window_secs: 100,
+            min_scale: 0.1,
+            poll_interval_secs: 10,
+        });
+        // Not enough samples to tell.
+        monitor.record(0, 100_000);
+        assert_eq!(monitor.scale(), 1.0);
+        // 10 per second, leaving 9900 seconds.
+        monitor.record(100, 99_000);
+        assert_eq!(monitor.scale(), 1.0);
+        monitor.record(200, 5_000);
+        assert_eq!(monitor.scale(), 0.1);
+        // Only the last window counts: 10 per second, leaving 400 seconds.
+        monitor.record(300, 4_000);
+        assert!((monitor.scale() - 0.4).abs() < 1e-9);
+        assert!((399..=400).contains(&monitor.scale_amount(1000)));
+        // Topping up doesn't count as spending, and restores the runway.
+        monitor.record(400, 1_000_000);
+        assert_eq!(monitor.scale(), 1.0);
+        // Running dry bottoms out at the minimum scale.
+        monitor.record(500, 0);
+        assert_eq!(monitor.scale(), 0.1);
+    }
+}
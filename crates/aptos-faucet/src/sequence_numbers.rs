@@ -0,0 +1,96 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+use anyhow::{Context, Result};
+use aptos_sdk::types::account_address::AccountAddress;
+use redis::{aio::MultiplexedConnection, Script};
+
+/// Takes the next sequence number from the counter, unless too many are outstanding already.
+/// The counter never goes below the on-chain sequence number, and expires when unused, so a
+/// stale counter is never a problem for long.
+///
+/// KEYS[1]: counter, ARGV[1]: on-chain sequence number, ARGV[2]: max outstanding,
+/// ARGV[3]: expiration in seconds. Returns the sequence number, or -1.
+const LEASE_SCRIPT: &str = r#"
+local onchain = tonumber(ARGV[1])
+local next = tonumber(redis.call('GET', KEYS[1]) or onchain)
+if next < onchain then
+    next = onchain
+end
+if next >= onchain + tonumber(ARGV[2]) then
+    return -1
+end
+redis.call('SET', KEYS[1], next + 1, 'EX', ARGV[3])
+return next
+"#;
+
+/// Rewinds the counter to the on-chain sequence number, if it's ahead.
+///
+/// KEYS[1]: counter, ARGV[1]: on-chain sequence number.
+const RESET_SCRIPT: &str = r#"
+local next = tonumber(redis.call('GET', KEYS[1]) or ARGV[1])
+if next > tonumber(ARGV[1]) then
+    redis.call('SET', KEYS[1], ARGV[1], 'KEEPTTL')
+end
+return 0
+"#;
+
+/// Sequence numbers of the funder account, handed out from a counter in Redis shared by all
+/// faucet replicas funding from the same account, so they can submit concurrently without
+/// reusing each other's sequence numbers.
+///
+/// Sequence numbers are leased one at a time, as an unused number would hold back every
+/// transaction after it until they expire.
+pub struct SharedSequenceNumbers {
+    connection: MultiplexedConnection,
+    key: String,
+    max_outstanding: u64,
+    expiration_secs: u64,
+}
+
+impl SharedSequenceNumbers {
+    pub async fn connect(
+        redis_url: &str,
+        funder: AccountAddress,
+        max_outstanding: u64,
+        expiration_secs: u64,
+    ) -> Result<Self> {
+        let connection = redis::Client::open(redis_url)?
+            .get_multiplexed_tokio_connection()
+            .await
+            .with_context(|| format!("Failed to connect to Redis at {}", redis_url))?;
+        Ok(Self {
+            connection,
+            key: format!("aptos-faucet:sequence-number:{}", funder),
+            max_outstanding,
+            expiration_secs,
+        })
+    }
+
+    /// Leases the next sequence number, given the on-chain one. Returns `None` if too many
+    /// transactions are outstanding across all replicas.
+    pub async fn lease(&self, onchain_seq: u64) -> Result<Option<u64>> {
+        let seq: i64 = Script::new(LEASE_SCRIPT)
+            .key(&self.key)
+            .arg(onchain_seq)
+            .arg(self.max_outstanding)
+            .arg(self.expiration_secs)
+            .invoke_async(&mut self.connection.clone())
+            .await?;
+        Ok(u64::try_from(seq).ok())
+    }
+
+    /// Rewinds to the on-chain sequence number, e.g. after a transaction failed to submit, so
+    /// that no gap is left behind.
+    pub async fn reset(&self, onchain_seq: u64) -> Result<()> {
+        let _: i64 = Script::new(RESET_SCRIPT)
+            .key(&self.key)
+            .arg(onchain_seq)
+            .invoke_async(&mut self.connection.clone())
+            .await?;
+        Ok(())
+    }
+}
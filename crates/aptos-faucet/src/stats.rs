@@ -0,0 +1,299 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Counts of recent requests, for on-call to triage abuse and funding failures without going
+//! through the logs: requests rejected by reason, funded or failed, and the networks requesting
+//! the most, over the last minutes and the last hour.
+//!
+//! Served on `GET /admin/stats` to requests with `Authorization: Bearer <admin token>`, and not
+//! served at all without an admin token.
+
+use crate::{
+    checkers::{RejectionReason, RejectionReasonCode},
+    constant_time_eq, Service,
+};
+use ipnet::IpNet;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+/// Counts are kept per minute, for this many minutes.
+const MAX_MINUTES: u64 = 60;
+/// Windows the counts are summed over, in minutes.
+const WINDOWS_MINUTES: [u64; 2] = [5, 60];
+/// Bound on the networks counted per minute, beyond which requests from new ones are only
+/// counted in the total.
+const MAX_PREFIXES_PER_MINUTE: usize = 10_000;
+/// Number of networks listed per window.
+const TOP_PREFIXES: usize = 10;
+/// Requests are grouped by the network of this size they come from.
+const IPV4_PREFIX_LEN: u8 = 24;
+const IPV6_PREFIX_LEN: u8 = 48;
+
+/// How the funding of a request that passed the checkers went.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FundingOutcome {
+    Funded,
+    Failed,
+}
+
+#[derive(Default)]
+struct MinuteCounts {
+    minute: u64,
+    requests: u64,
+    rejections: HashMap<RejectionReasonCode, u64>,
+    outcomes: HashMap<FundingOutcome, u64>,
+    prefixes: HashMap<IpNet, u64>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct PrefixCount {
+    pub prefix: String,
+    pub requests: u64,
+}
+
+/// Counts over a window ending now.
+#[derive(Debug, Serialize)]
+pub struct WindowStats {
+    pub window_secs: u64,
+    pub requests: u64,
+    pub rejections: HashMap<RejectionReasonCode, u64>,
+    pub outcomes: HashMap<FundingOutcome, u64>,
+    /// The networks requesting the most, most first.
+    pub top_prefixes: Vec<PrefixCount>,
+}
+
+/// Body of `GET /admin/stats`.
+#[derive(Debug, Serialize)]
+pub struct StatsBody {
+    pub windows: Vec<WindowStats>,
+}
+
+pub struct TriageStats {
+    admin_token: String,
+    minutes: Mutex<VecDeque<MinuteCounts>>,
+}
+
+impl TriageStats {
+    pub fn new(admin_token: String) -> Self {
+        Self {
+            admin_token,
+            minutes: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Applies the update to the counts of the current minute, dropping those too old to be in
+    /// any window.
+    fn update(&self, now_secs: u64, update: impl FnOnce(&mut MinuteCounts)) {
+        let minute = now_secs / 60;
+        let mut minutes = self.minutes.lock().unwrap();
+        if minutes.back().map_or(true, |counts| counts.minute < minute) {
+            minutes.push_back(MinuteCounts {
+                minute,
+                ..Default::default()
+            });
+        }
+        while minutes
+            .front()
+            .map_or(false, |counts| counts.minute + MAX_MINUTES <= minute)
+        {
+            minutes.pop_front();
+        }
+        // Clocks going backwards are counted in the latest minute.
+        update(minutes.back_mut().expect("Current minute must exist"))
+    }
+
+    pub fn record_request(&self, source_ip: Option<IpAddr>, now_secs: u64) {
+        let prefix = source_ip.map(|ip| {
+            let prefix_len = match ip {
+                IpAddr::V4(_) => IPV4_PREFIX_LEN,
+                IpAddr::V6(_) => IPV6_PREFIX_LEN,
+            };
+            IpNet::new(ip, prefix_len)
+                .expect("Prefix length must be valid")
+                .trunc()
+        });
+        self.update(now_secs, |counts| {
+            counts.requests += 1;
+            if let Some(prefix) = prefix {
+                if counts.prefixes.len() < MAX_PREFIXES_PER_MINUTE
+                    || counts.prefixes.contains_key(&prefix)
+                {
+                    *counts.prefixes.entry(prefix).or_default() += 1;
+                }
+            }
+        });
+    }
+
+    pub fn record_rejections(&self, rejections: &[RejectionReason], now_secs: u64) {
+        self.update(now_secs, |counts| {
+            for rejection in rejections {
+                *counts.rejections.entry(rejection.code).or_default() += 1;
+            }
+        });
+    }
+
+    pub fn record_outcome(&self, outcome: FundingOutcome, now_secs: u64) {
+        self.update(now_secs, |counts| {
+            *counts.outcomes.entry(outcome).or_default() += 1;
+        });
+    }
+
+    /// The counts over each window ending at the current minute.
+    pub fn summary(&self, now_secs: u64) -> StatsBody {
+        let minute = now_secs / 60;
+        let minutes = self.minutes.lock().unwrap();
+        let windows = WINDOWS_MINUTES
+            .iter()
+            .map(|window| {
+                let mut requests = 0;
+                let mut rejections = HashMap::new();
+                let mut outcomes = HashMap::new();
+                let mut prefixes: HashMap<IpNet, u64> = HashMap::new();
+                for counts in minutes
+                    .iter()
+                    .filter(|counts| counts.minute + window > minute)
+                {
+                    requests += counts.requests;
+                    for (code, count) in &counts.rejections {
+                        *rejections.entry(*code).or_default() += count;
+                    }
+                    for (outcome, count) in &counts.outcomes {
+                        *outcomes.entry(*outcome).or_default() += count;
+                    }
+                    for (prefix, count) in &counts.prefixes {
+                        *prefixes.entry(*prefix).or_default() += count;
+                    }
+                }
+                let mut top_prefixes: Vec<_> = prefixes.into_iter().collect();
+                top_prefixes.sort_by(|(a_prefix, a), (b_prefix, b)| {
+                    b.cmp(a).then_with(|| a_prefix.cmp(b_prefix))
+                });
+                WindowStats {
+                    window_secs: window * 60,
+                    requests,
+                    rejections,
+                    outcomes,
+                    top_prefixes: top_prefixes
+                        .into_iter()
+                        .take(TOP_PREFIXES)
+                        .map(|(prefix, requests)| PrefixCount {
+                            prefix: prefix.to_string(),
+                            requests,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        StatsBody { windows }
+    }
+
+    /// Whether the request carries the admin token.
+    pub(crate) fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |token| {
+                constant_time_eq(token.as_bytes(), self.admin_token.as_bytes())
+            })
+    }
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+pub fn stats_routes(
+    service: Arc<Service>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    // GET /admin/stats
+    warp::path!("admin" / "stats")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::any().map(move || service.clone()))
+        .and_then(handle_stats)
+}
+
+async fn handle_stats(
+    authorization: Option<String>,
+    service: Arc<Service>,
+) -> Result<Box<dyn Reply>, Infallible> {
+    Ok(match service.triage_stats() {
+        None => Box::new(StatusCode::NOT_FOUND),
+        Some(stats) if !stats.is_authorized(authorization.as_deref()) => {
+            Box::new(StatusCode::UNAUTHORIZED)
+        },
+        Some(stats) => Box::new(warp::reply::json(&stats.summary(now_secs()))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FundingOutcome, PrefixCount, TriageStats};
+    use crate::checkers::{RejectionReason, RejectionReasonCode};
+
+    #[test]
+    fn test_triage_stats() {
+        let stats = TriageStats::new("s3cret".to_string());
+        // An hour ago, only in the one hour window.
+        stats.record_request(Some("10.0.0.1".parse().unwrap()), 0);
+        stats.record_outcome(FundingOutcome::Failed, 0);
+        let now = 3599;
+        for ip in ["10.0.0.2", "10.0.0.3", "192.0.2.1"] {
+            stats.record_request(Some(ip.parse().unwrap()), now);
+        }
+        stats.record_request(None, now);
+        stats.record_rejections(
+            &[RejectionReason::new(
+                RejectionReasonCode::UsageLimitExhausted,
+                "Too many requests".to_string(),
+            )],
+            now,
+        );
+        stats.record_outcome(FundingOutcome::Funded, now);
+
+        let summary = stats.summary(now);
+        let (last_minutes, last_hour) = (&summary.windows[0], &summary.windows[1]);
+        assert_eq!(last_minutes.window_secs, 300);
+        assert_eq!(last_minutes.requests, 4);
+        assert_eq!(
+            last_minutes.rejections[&RejectionReasonCode::UsageLimitExhausted],
+            1
+        );
+        assert_eq!(last_minutes.outcomes[&FundingOutcome::Funded], 1);
+        assert!(!last_minutes.outcomes.contains_key(&FundingOutcome::Failed));
+        assert_eq!(last_minutes.top_prefixes, vec![
+            PrefixCount {
+                prefix: "10.0.0.0/24".to_string(),
+                requests: 2
+            },
+            PrefixCount {
+                prefix: "192.0.2.0/24".to_string(),
+                requests: 1
+            },
+        ]);
+        assert_eq!(last_hour.requests, 5);
+        assert_eq!(last_hour.outcomes[&FundingOutcome::Failed], 1);
+        assert_eq!(last_hour.top_prefixes[0].requests, 3);
+        // Dropped once older than the longest window.
+        stats.record_request(None, 3600);
+        assert_eq!(stats.summary(3600).windows[1].requests, 5);
+
+        assert!(stats.is_authorized(Some("Bearer s3cret")));
+        assert!(!stats.is_authorized(Some("Bearer s3cre")));
+        assert!(!stats.is_authorized(Some("s3cret")));
+        assert!(!stats.is_authorized(None));
+    }
+}
@@ -0,0 +1,397 @@
+// Copyright © Aptos Foundation
+// Parts of the project are originally copyright © Meta Platforms, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Running the faucet end to end without external infrastructure, for the integration tests of
+//! the checkers and the funders.
+//!
+//! `MockChain` serves, in process, the few node API endpoints the faucet calls: accounts, the
+//! submission of transactions, which it applies to the balances of the accounts right away, their
+//! lookup by hash, and a name service. `FaucetServer` serves the full faucet over HTTP on an
+//! ephemeral port, so requests go through the same filters, client IP extraction and clients as
+//! in production:
+//!
+//! ```ignore
+//! let chain = MockChain::start();
+//! let service = chain.service(None).with_checkers(checkers);
+//! let server = FaucetServer::start(Arc::new(service));
+//! server.client().fund(address, 100).await?;
+//! assert_eq!(chain.balance(address), Some(100));
+//! ```
+//!
+//! Tests against a real node start a local network with the swarm of the smoke tests instead.
+
+use crate::{ans::AnsResolver, routes, Service};
+use aptos_crypto::hash::HashValue;
+use aptos_infallible::RwLock;
+use aptos_keygen::KeyGen;
+use aptos_rest_client::{
+    aptos_api_types::{
+        AccountData, LedgerInfo, ModuleBundlePayload, PendingTransaction,
+        TransactionInfo as TransactionInfoData, TransactionPayload as TransactionPayloadData,
+    },
+    FaucetClient,
+};
+use aptos_sdk::{
+    transaction_builder::aptos_stdlib::EntryFunctionCall,
+    types::{
+        account_address::AccountAddress,
+        chain_id::ChainId,
+        transaction::{
+            authenticator::AuthenticationKey, SignedTransaction, Transaction, TransactionArgument,
+            TransactionPayload::Script,
+        },
+        LocalAccount,
+    },
+};
+use aptos_warp_webserver::Response;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    convert::{Infallible, TryFrom},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use url::Url;
+use warp::{
+    body::BodyDeserializeError,
+    cors::CorsForbidden,
+    http::{header, HeaderValue, StatusCode},
+    reject::{LengthRequired, MethodNotAllowed, PayloadTooLarge, UnsupportedMediaType},
+    reply, Filter, Rejection, Reply,
+};
+
+pub type AccountStates = Arc<RwLock<HashMap<AccountAddress, AccountState>>>;
+
+/// Name registered with the name service of the mock chain.
+pub const REGISTERED_NAME: &str = "alice";
+pub const REGISTERED_NAME_ADDRESS: &str =
+    "0x459c77a38803bd53f3adee52703810e3a74fd7c46952c497e75afb0a7932586d";
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AccountState {
+    pub authentication_key: AuthenticationKey,
+    pub balance: u64,
+    pub sequence_number: u64,
+}
+
+impl AccountState {
+    pub fn new(balance: u64) -> Self {
+        Self {
+            authentication_key: AuthenticationKey::new([1; 32]),
+            balance,
+            sequence_number: 0,
+        }
+    }
+}
+
+/// The node API of a chain, served in process on an ephemeral port.
+pub struct MockChain {
+    pub accounts: AccountStates,
+    url: Url,
+    handle: JoinHandle<()>,
+}
+
+impl MockChain {
+    pub fn start() -> Self {
+        let accounts = AccountStates::new(RwLock::new(HashMap::new()));
+        let last_txn = Arc::new(Mutex::new(None));
+        let last_txn_0 = last_txn.clone();
+
+        let accounts_cloned_0 = accounts.clone();
+        let accounts_cloned_1 = accounts.clone();
+        let stub = warp::path!("accounts" / String)
+            .and(warp::any().map(move || accounts_cloned_0.clone()))
+            .and_then(handle_get_account)
+            .or(warp::path!("transactions" / "by_hash" / String)
+                .and(warp::get())
+                .and(warp::any().map(move || last_txn_0.clone()))
+                .and_then(handle_get_transaction))
+            .or(warp::path!("transactions")
+                .and(warp::post())
+                .and(warp::body::bytes())
+                .and(warp::any().map(move || (accounts_cloned_1.clone(), last_txn.clone())))
+                .and_then(handle_submit_transaction))
+            .or(warp::path!("ans" / String)
+                .and(warp::get())
+                .map(|name: String| {
+                    if name == REGISTERED_NAME {
+                        reply::json(&serde_json::json!({ "address": REGISTERED_NAME_ADDRESS }))
+                    } else {
+                        reply::json(&serde_json::json!({}))
+                    }
+                }))
+            .with(
+                warp::cors()
+                    .allow_any_origin()
+                    .allow_methods(vec!["POST", "GET"])
+                    .allow_headers(vec![header::CONTENT_TYPE]),
+            )
+            .recover(handle_rejection);
+        let (address, future) = warp::serve(stub).bind_ephemeral(([127, 0, 0, 1], 0));
+        let handle = tokio::task::spawn(async move { future.await });
+
+        Self {
+            accounts,
+            url: Url::parse(&format!("http://localhost:{}/", address.port())).unwrap(),
+            handle,
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn balance(&self, address: AccountAddress) -> Option<u64> {
+        self.accounts
+            .read()
+            .get(&address)
+            .map(|account| account.balance)
+    }
+
+    /// A faucet funding from a new account of the chain, resolving names with its name service.
+    pub fn service(&self, maximum_amount: Option<u64>) -> Service {
+        let mut keygen = KeyGen::from_seed([0; 32]);
+        let (private_key, public_key) = keygen.generate_ed25519_keypair();
+        let account_address = AuthenticationKey::ed25519(&public_key).derived_address();
+        let faucet_account = LocalAccount::new(account_address, private_key, 0);
+        self.accounts
+            .write()
+            .insert(account_address, AccountState::new(0));
+
+        Service::new(
+            self.url.clone(),
+            ChainId::test(),
+            faucet_account,
+            maximum_amount,
+        )
+        .with_ans_resolver(Some(Arc::new(AnsResolver::new(
+            self.url.join("ans/").unwrap(),
+            Duration::from_secs(60),
+        ))))
+        .with_explorer_url_template(Some(
+            "https://explorer.aptoslabs.com/txn/{txn_hash}?network=local"
+                .parse()
+                .unwrap(),
+        ))
+        .configure_for_testing()
+    }
+}
+
+impl Drop for MockChain {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// The faucet served over HTTP on an ephemeral port.
+pub struct FaucetServer {
+    url: Url,
+    node_url: Url,
+    handle: JoinHandle<()>,
+}
+
+impl FaucetServer {
+    pub fn start(service: Arc<Service>) -> Self {
+        let node_url = service.endpoint().clone();
+        let (address, future) = warp::serve(routes(service)).bind_ephemeral(([127, 0, 0, 1], 0));
+        let handle = tokio::task::spawn(async move { future.await });
+        Self {
+            url: Url::parse(&format!("http://{}", address)).unwrap(),
+            node_url,
+            handle,
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// A client of the faucet, waiting for the transactions on the node it funds from.
+    pub fn client(&self) -> FaucetClient {
+        FaucetClient::new_for_testing(self.url.clone(), self.node_url.clone())
+    }
+}
+
+impl Drop for FaucetServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_get_account(
+    address: String,
+    accounts: AccountStates,
+) -> Result<impl Reply, Rejection> {
+    let reader = accounts.read();
+    let account = match AccountAddress::try_from(address.clone())
+        .or_else(|_e| AccountAddress::from_hex(address.clone()))
+    {
+        Ok(addr) => reader.get(&addr),
+        _ => None,
+    };
+    if let Some(account) = account {
+        let auth_vec: Vec<u8> = account.authentication_key.as_ref().into();
+        let account_data = AccountData {
+            authentication_key: auth_vec.into(),
+            sequence_number: account.sequence_number.into(),
+        };
+        Ok(response(&account_data))
+    } else {
+        Err(warp::reject())
+    }
+}
+
+async fn handle_get_transaction(
+    _hash: String,
+    last_txn: Arc<Mutex<Option<Transaction>>>,
+) -> Result<impl Reply, Rejection> {
+    last_txn.lock().unwrap().as_ref().map_or_else(
+        || Err(warp::reject()),
+        |txn| {
+            let info = TransactionInfoData {
+                version: 0.into(),
+                hash: HashValue::zero().into(),
+                state_change_hash: HashValue::zero().into(),
+                event_root_hash: HashValue::zero().into(),
+                state_checkpoint_hash: None,
+                gas_used: 0.into(),
+                success: true,
+                vm_status: "Executed".to_string(),
+                accumulator_root_hash: HashValue::zero().into(),
+                changes: vec![],
+                block_height: None,
+                epoch: None,
+            };
+            let serializable_txn: aptos_rest_client::aptos_api_types::Transaction = (
+                txn.as_signed_user_txn().unwrap(),
+                info,
+                dummy_payload(),
+                Vec::new(),
+                0,
+            )
+                .into();
+
+            Ok(response(&serializable_txn))
+        },
+    )
+}
+
+async fn handle_submit_transaction(
+    txn: bytes::Bytes,
+    (accounts, last_txn): (AccountStates, Arc<Mutex<Option<Transaction>>>),
+) -> Result<impl Reply, Rejection> {
+    let txn: SignedTransaction = bcs::from_bytes(&txn).unwrap();
+    assert_eq!(txn.chain_id(), ChainId::test());
+
+    if let Script(script) = txn.payload() {
+        let dst_addr = if let TransactionArgument::Address(addr) = script.args()[0] {
+            addr
+        } else {
+            panic!("unexpected type of script: {:?}", script);
+        };
+        let amount = if let TransactionArgument::U64(amount) = script.args()[1] {
+            amount
+        } else {
+            panic!("unexpected type of script: {:?}", script);
+        };
+
+        accounts
+            .write()
+            .entry(dst_addr)
+            .and_modify(|account| account.balance += amount)
+            .or_insert_with(|| AccountState::new(amount));
+    } else if let Some(EntryFunctionCall::AptosAccountBatchTransfer {
+        recipients,
+        amounts,
+    }) = EntryFunctionCall::decode(txn.payload())
+    {
+        let mut accounts = accounts.write();
+        for (dst_addr, amount) in recipients.into_iter().zip(amounts) {
+            accounts.get_mut(&txn.sender()).unwrap().balance -= amount;
+            accounts
+                .entry(dst_addr)
+                .and_modify(|account| account.balance += amount)
+                .or_insert_with(|| AccountState::new(amount));
+        }
+    }
+
+    let pending_txn = PendingTransaction {
+        hash: HashValue::zero().into(),
+        request: (&txn, dummy_payload()).into(),
+    };
+
+    *last_txn.lock().unwrap() = Some(Transaction::UserTransaction(txn));
+    Ok(response(&pending_txn))
+}
+
+fn response<T: Serialize>(body: &T) -> warp::reply::Response {
+    let li = LedgerInfo {
+        chain_id: ChainId::test().id(),
+        epoch: 1.into(),
+        ledger_version: 5.into(),
+        oldest_ledger_version: 0.into(),
+        block_height: 4.into(),
+        oldest_block_height: 0.into(),
+        ledger_timestamp: 5.into(),
+    };
+    Response::new(li, body).unwrap().into_response()
+}
+
+fn dummy_payload() -> TransactionPayloadData {
+    TransactionPayloadData::ModuleBundlePayload(ModuleBundlePayload { modules: vec![] })
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+struct Error {
+    pub code: u16,
+    pub message: String,
+}
+
+impl Error {
+    fn new(code: StatusCode, message: String) -> Error {
+        Error {
+            code: code.as_u16(),
+            message,
+        }
+    }
+}
+
+async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let code;
+    let body;
+
+    if err.is_not_found() {
+        code = StatusCode::NOT_FOUND;
+        body = reply::json(&Error::new(code, "Not Found".to_owned()));
+    } else if let Some(cause) = err.find::<CorsForbidden>() {
+        code = StatusCode::FORBIDDEN;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<BodyDeserializeError>() {
+        code = StatusCode::BAD_REQUEST;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<LengthRequired>() {
+        code = StatusCode::LENGTH_REQUIRED;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<PayloadTooLarge>() {
+        code = StatusCode::PAYLOAD_TOO_LARGE;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<UnsupportedMediaType>() {
+        code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<MethodNotAllowed>() {
+        code = StatusCode::METHOD_NOT_ALLOWED;
+        body = reply::json(&Error::new(code, cause.to_string()));
+    } else {
+        code = StatusCode::INTERNAL_SERVER_ERROR;
+        body = reply::json(&Error::new(code, format!("unexpected error: {:?}", err)));
+    }
+    let mut rep = reply::with_status(body, code).into_response();
+    rep.headers_mut()
+        .insert("access-control-allow-origin", HeaderValue::from_static("*"));
+    Ok(rep)
+}
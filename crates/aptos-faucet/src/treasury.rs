@@ -0,0 +1,208 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Funding of large grants from a treasury account, rather than from the hot wallet the faucet
+//! otherwise funds from, so that the hot wallet, whose key sits with every replica, only ever
+//! needs to hold enough for small grants.
+//!
+//! Requests for more than the threshold are only accepted if they carry the approval token in
+//! `TREASURY_APPROVAL_HEADER`, and pass the checkers of the treasury on top of the usual ones.
+//! They are then funded with a transfer from the treasury account.
+
+use crate::{
+    checkers::{
+        run_checker, Checker, CheckerData, CheckerTimeout, RejectionReason, RejectionReasonCode,
+    },
+    constant_time_eq,
+    metrics::TREASURY_GRANTS,
+    Service,
+};
+use anyhow::Result;
+use aptos_logger::info;
+use aptos_sdk::{
+    transaction_builder::aptos_stdlib,
+    types::{account_address::AccountAddress, transaction::SignedTransaction, LocalAccount},
+};
+use futures::lock::Mutex;
+use std::sync::Arc;
+use warp::http::HeaderMap;
+
+/// Header carrying the approval token, for requests for more than the treasury threshold.
+pub const TREASURY_APPROVAL_HEADER: &str = "X-Aptos-Faucet-Approval";
+
+pub struct Treasury {
+    account: Mutex<LocalAccount>,
+    /// Requests for more than this are funded from the treasury.
+    threshold: u64,
+    approval_token: String,
+    /// Run on the requests funded from the treasury, after the checkers of the service.
+    checkers: Vec<Arc<dyn Checker>>,
+    checker_timeout: Option<CheckerTimeout>,
+}
+
+impl Treasury {
+    pub fn new(account: LocalAccount, threshold: u64, approval_token: String) -> Self {
+        Self {
+            account: Mutex::new(account),
+            threshold,
+            approval_token,
+            checkers: vec![],
+            checker_timeout: None,
+        }
+    }
+
+    pub fn with_checkers(mut self, checkers: Vec<Arc<dyn Checker>>) -> Self {
+        self.checkers = checkers;
+        self
+    }
+
+    pub fn with_checker_timeout(mut self, checker_timeout: Option<CheckerTimeout>) -> Self {
+        self.checker_timeout = checker_timeout;
+        self
+    }
+
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    /// Whether a request for the amount is funded from the treasury.
+    pub fn covers(&self, amount: u64) -> bool {
+        amount > self.threshold
+    }
+
+    /// Whether the request carries the approval token.
+    fn is_approved(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(TREASURY_APPROVAL_HEADER)
+            .map_or(false, |token| {
+                constant_time_eq(token.as_bytes(), self.approval_token.as_bytes())
+            })
+    }
+
+    /// Checks a request funded from the treasury, returning the reasons to reject it, if any:
+    /// a missing or wrong approval token, or the rejections of the checkers of the treasury.
+    pub async fn run_checkers(&self, data: &CheckerData) -> Result<Vec<RejectionReason>> {
+        if !self.is_approved(&data.headers) {
+            info!(
+                receiver = data.receiver,
+                source_ip = data.source_ip,
+                amount = data.amount,
+                "rejected treasury request without approval"
+            );
+            return Ok(vec![RejectionReason::new(
+                RejectionReasonCode::ApprovalRequired,
+                format!(
+                    "Amounts over {} must be approved, with the token in the {} header",
+                    self.threshold, TREASURY_APPROVAL_HEADER
+                ),
+            )
+            .with_checker("treasury_approval")]);
+        }
+        let mut rejections = vec![];
+        for checker in &self.checkers {
+            if let Some(rejection) =
+                run_checker(checker.as_ref(), data, self.checker_timeout.as_ref()).await?
+            {
+                info!(
+                    checker = checker.name(),
+                    receiver = data.receiver,
+                    source_ip = data.source_ip,
+                    reason = rejection.reason,
+                    "rejected treasury request"
+                );
+                rejections.push(rejection.with_checker(checker.name()));
+            }
+        }
+        Ok(rejections)
+    }
+
+    /// Sends the amount to the receiver, creating its account if needed, returning the
+    /// transaction and the latest ledger version seen. Grants are rare enough to be submitted
+    /// one at a time, with the sequence number refreshed from chain before each.
+    pub async fn fund(
+        &self,
+        service: &Service,
+        receiver: AccountAddress,
+        amount: u64,
+    ) -> Result<(SignedTransaction, u64)> {
+        let mut account = self.account.lock().await;
+        let on_chain = service
+            .client
+            .get_account(account.address())
+            .await
+            .map_err(|e| {
+                anyhow::format_err!("Treasury account {} not found: {:#}", account.address(), e)
+            })?;
+        let seq = on_chain.inner().sequence_number;
+        let ledger_version = on_chain.state().version;
+        if seq > account.sequence_number() {
+            *account.sequence_number_mut() = seq;
+        }
+
+        let txn = account.sign_with_transaction_builder(
+            service
+                .transaction_factory
+                .payload(aptos_stdlib::aptos_account_transfer(receiver, amount)),
+        );
+        if let Err(e) = service.client.submit(&txn).await {
+            *account.sequence_number_mut() = seq;
+            return Err(e.into());
+        }
+        TREASURY_GRANTS.inc();
+        info!(
+            receiver = receiver,
+            amount = amount,
+            treasury = account.address(),
+            "funded from the treasury"
+        );
+        Ok((txn, ledger_version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Treasury, TREASURY_APPROVAL_HEADER};
+    use crate::checkers::{CheckerData, IpRateLimitChecker, LimitSchedule, RejectionReasonCode};
+    use aptos_sdk::types::LocalAccount;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_treasury_checks() {
+        let treasury = Treasury::new(
+            LocalAccount::generate(&mut rand::rngs::OsRng),
+            1000,
+            "s3cret".to_string(),
+        )
+        .with_checkers(vec![Arc::new(IpRateLimitChecker::new(
+            1,
+            LimitSchedule::default(),
+        ))]);
+        assert!(!treasury.covers(1000));
+        assert!(treasury.covers(1001));
+
+        let data = |token: Option<&str>| {
+            let data = CheckerData::for_test().amount(5000).ip("1.2.3.4");
+            match token {
+                Some(token) => data.header(TREASURY_APPROVAL_HEADER, token),
+                None => data,
+            }
+        };
+
+        for token in [None, Some("s3cre"), Some("s3cret!"), Some("S3cret")] {
+            let rejections = treasury.run_checkers(&data(token)).await.unwrap();
+            assert_eq!(rejections.len(), 1);
+            assert_eq!(rejections[0].code, RejectionReasonCode::ApprovalRequired);
+        }
+        assert!(treasury
+            .run_checkers(&data(Some("s3cret")))
+            .await
+            .unwrap()
+            .is_empty());
+        // The stricter limits of the treasury apply on top of the approval.
+        let rejections = treasury.run_checkers(&data(Some("s3cret"))).await.unwrap();
+        assert_eq!(rejections[0].code, RejectionReasonCode::UsageLimitExhausted);
+    }
+}
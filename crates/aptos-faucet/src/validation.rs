@@ -0,0 +1,239 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Validation of the addresses given to the faucet endpoints. Rather than failing to parse a
+//! malformed address, requests are rejected with what is wrong with it, and how to fix it when
+//! the mistake is a common one: a mistyped `0x` prefix, a name pasted instead of an address, a
+//! letter standing for a digit, etc.
+
+use crate::ans;
+use aptos_sdk::types::account_address::AccountAddress;
+use std::fmt;
+
+/// Number of hex digits of an address.
+const ADDRESS_HEX_LENGTH: usize = AccountAddress::LENGTH * 2;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidAddress {
+    /// Name of the field the address was given in, e.g. `address` or `auth_key`.
+    pub field: &'static str,
+    pub input: String,
+    pub reason: String,
+    pub suggestion: Option<String>,
+}
+
+impl InvalidAddress {
+    fn new(field: &'static str, input: &str, reason: String) -> Self {
+        Self {
+            field,
+            input: input.to_string(),
+            reason,
+            suggestion: None,
+        }
+    }
+
+    fn with_suggestion(mut self, suggestion: String) -> Self {
+        self.suggestion = Some(suggestion);
+        self
+    }
+}
+
+impl fmt::Display for InvalidAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid {} '{}': {}",
+            self.field, self.input, self.reason
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ". {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidAddress {}
+
+/// Parses the address of a receiver, given as hex with or without the `0x` prefix, leading
+/// zeros optional.
+pub fn parse_address(input: &str) -> Result<AccountAddress, InvalidAddress> {
+    parse("address", input, true)
+}
+
+/// Parses an authentication key, which the account address is derived from, as hex.
+pub fn parse_auth_key(input: &str) -> Result<AccountAddress, InvalidAddress> {
+    parse("auth_key", input, false)
+}
+
+fn parse(
+    field: &'static str,
+    input: &str,
+    may_be_name: bool,
+) -> Result<AccountAddress, InvalidAddress> {
+    let trimmed = input.trim();
+    let (has_prefix, digits) = match trimmed.strip_prefix("0x") {
+        Some(digits) => (true, digits),
+        None => (false, trimmed),
+    };
+    if !digits.is_empty()
+        && digits.len() <= ADDRESS_HEX_LENGTH
+        && digits.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        let padded = format!("{:0>width$}", digits, width = ADDRESS_HEX_LENGTH);
+        return AccountAddress::from_hex(padded)
+            .map_err(|e| InvalidAddress::new(field, input, e.to_string()));
+    }
+    Err(diagnose(
+        field,
+        input,
+        trimmed,
+        has_prefix,
+        digits,
+        may_be_name,
+    ))
+}
+
+/// Tells what is wrong with an address that doesn't parse.
+fn diagnose(
+    field: &'static str,
+    input: &str,
+    trimmed: &str,
+    has_prefix: bool,
+    digits: &str,
+    may_be_name: bool,
+) -> InvalidAddress {
+    let invalid = |reason: String| InvalidAddress::new(field, input, reason);
+
+    if trimmed.is_empty() {
+        return invalid("it is empty".to_string());
+    }
+    if digits.is_empty() {
+        return invalid("it has no hex digits after the 0x prefix".to_string());
+    }
+    if let Some(rest) = digits.strip_prefix("0x") {
+        return invalid("it has the 0x prefix twice".to_string())
+            .with_suggestion(format!("Did you mean '0x{}'?", rest));
+    }
+    if !has_prefix {
+        for prefix in ["0X", "Ox", "OX", "ox"] {
+            if let Some(rest) = trimmed.strip_prefix(prefix) {
+                if !rest.is_empty() && rest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return invalid(format!("it starts with '{}' rather than '0x'", prefix))
+                        .with_suggestion(format!("Did you mean '0x{}'?", rest));
+                }
+            }
+        }
+    }
+    if may_be_name && !has_prefix {
+        let lowercase = trimmed.to_lowercase();
+        match ans::normalize_name(trimmed) {
+            Ok(name) => {
+                return invalid("it looks like an Aptos name rather than an address".to_string())
+                    .with_suggestion(format!(
+                        "Fund it by name with 'name={}.apt' where names are supported, or \
+                        provide the address it points to",
+                        name
+                    ))
+            },
+            Err(err) if lowercase.ends_with(".apt") => {
+                return invalid(format!("it looks like an Aptos name, but {}", err))
+            },
+            Err(_) => (),
+        }
+    }
+    if digits.chars().any(char::is_whitespace) {
+        return invalid("it contains whitespace".to_string()).with_suggestion(format!(
+            "Did you mean '0x{}'?",
+            digits.split_whitespace().collect::<String>()
+        ));
+    }
+    if let Some((position, c)) = digits.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        let invalid = invalid(format!(
+            "'{}' at position {} is not a hex digit (0-9, a-f)",
+            c,
+            position + if has_prefix { 2 } else { 0 },
+        ));
+        return match c {
+            'o' | 'O' => invalid.with_suggestion("The letter 'o' may stand for '0'".to_string()),
+            'l' | 'I' => invalid.with_suggestion(format!("The letter '{}' may stand for '1'", c)),
+            _ => invalid,
+        };
+    }
+    invalid(format!(
+        "it has {} hex digits, but an address has at most {}",
+        digits.len(),
+        ADDRESS_HEX_LENGTH
+    ))
+    .with_suggestion("Check that it wasn't pasted twice or along with something else".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_address, parse_auth_key};
+    use aptos_sdk::types::account_address::AccountAddress;
+
+    #[test]
+    fn test_parse_address() {
+        let address = AccountAddress::from_hex_literal("0xa550c18").unwrap();
+        for input in [
+            "0xa550c18",
+            "a550c18",
+            " 0xa550c18\n",
+            "0x000000000000000000000000000000000000000000000000000000000a550c18",
+        ] {
+            assert_eq!(parse_address(input).unwrap(), address, "{}", input);
+        }
+
+        let reason = |input: &str| parse_address(input).unwrap_err().reason;
+        let suggestion = |input: &str| parse_address(input).unwrap_err().suggestion;
+        assert_eq!(reason(""), "it is empty");
+        assert_eq!(reason("0x"), "it has no hex digits after the 0x prefix");
+        assert_eq!(
+            suggestion("0x0xa550c18").unwrap(),
+            "Did you mean '0xa550c18'?"
+        );
+        assert_eq!(
+            suggestion("Oxa550c18").unwrap(),
+            "Did you mean '0xa550c18'?"
+        );
+        assert_eq!(reason("0Xa550c18"), "it starts with '0X' rather than '0x'");
+        assert_eq!(
+            reason("alice.apt"),
+            "it looks like an Aptos name rather than an address"
+        );
+        assert!(suggestion("Alice").unwrap().contains("name=alice.apt"));
+        assert!(reason("al.apt").starts_with("it looks like an Aptos name, but"));
+        assert_eq!(
+            suggestion("0xa550 c18").unwrap(),
+            "Did you mean '0xa550c18'?"
+        );
+        assert_eq!(
+            reason("0xa55Oc18"),
+            "'O' at position 5 is not a hex digit (0-9, a-f)"
+        );
+        assert_eq!(
+            suggestion("0xa55Oc18").unwrap(),
+            "The letter 'o' may stand for '0'"
+        );
+        assert_eq!(
+            reason(&format!("0x{}", "a".repeat(65))),
+            "it has 65 hex digits, but an address has at most 64"
+        );
+        assert_eq!(
+            parse_address("0xzz").unwrap_err().to_string(),
+            "Invalid address '0xzz': 'z' at position 2 is not a hex digit (0-9, a-f)"
+        );
+    }
+
+    #[test]
+    fn test_parse_auth_key() {
+        // Names make no sense for keys.
+        assert_eq!(
+            parse_auth_key("invalid-auth-key").unwrap_err().to_string(),
+            "Invalid auth_key 'invalid-auth-key': 'i' at position 0 is not a hex digit (0-9, a-f)"
+        );
+    }
+}
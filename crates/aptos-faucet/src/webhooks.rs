@@ -0,0 +1,357 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// README: The aptos-faucet is deprecated in favor of the tap. Do not add new code
+// to this until you've spoken with the Ecosystem Platform team + dport.
+
+//! Notifications of fundings pushed to downstream systems, e.g. Discord bots or analytics, so
+//! they don't have to poll. The webhooks are read from a YAML file like:
+//!
+//! ```yaml
+//! - url: https://bot.example.com/faucet
+//!   secret: s3cret
+//!   events: [failed]
+//! - url: https://analytics.example.com/faucet
+//! ```
+//!
+//! Each webhook gets a JSON `FundingEvent` POSTed for every funding it asks for, all of them if
+//! `events` is left out. With a secret, the body is signed with HMAC-SHA256, the hex digest being
+//! sent as `X-Aptos-Faucet-Signature: sha256=<digest>`. Deliveries are retried with exponential
+//! backoff, and given up on after the last attempt, being logged as dead letters with their body,
+//! for them to be replayed by hand.
+
+use crate::metrics::WEBHOOK_DELIVERIES;
+use anyhow::{Context, Result};
+use aptos_crypto::hash::HashValue;
+use aptos_logger::{error, warn};
+use aptos_sdk::types::account_address::AccountAddress;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use url::Url;
+
+/// Header of the signature of the body, if the webhook has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Aptos-Faucet-Signature";
+/// Header of the kind of the event, for receivers to route on without parsing the body.
+pub const EVENT_HEADER: &str = "X-Aptos-Faucet-Event";
+/// Number of events waiting to be delivered to a webhook, beyond which new ones are dropped,
+/// logged as dead letters.
+const QUEUE_CAPACITY: usize = 1000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FundingEventKind {
+    Funded,
+    Failed,
+}
+
+impl FundingEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FundingEventKind::Funded => "funded",
+            FundingEventKind::Failed => "failed",
+        }
+    }
+}
+
+/// Body of the notifications.
+#[derive(Clone, Debug, Serialize)]
+pub struct FundingEvent {
+    pub kind: FundingEventKind,
+    /// Receiver of the funds, if the request named one.
+    pub receiver: Option<AccountAddress>,
+    /// Amount requested.
+    pub amount: u64,
+    /// Transactions submitted, if funded.
+    pub txn_hashes: Vec<HashValue>,
+    /// Why the funding failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp of the funding, for receivers to reject replayed notifications.
+    pub timestamp_secs: u64,
+}
+
+impl FundingEvent {
+    pub fn funded(
+        receiver: Option<AccountAddress>,
+        amount: u64,
+        txn_hashes: Vec<HashValue>,
+    ) -> Self {
+        Self {
+            kind: FundingEventKind::Funded,
+            receiver,
+            amount,
+            txn_hashes,
+            error: None,
+            timestamp_secs: crate::stats::now_secs(),
+        }
+    }
+
+    pub fn failed(receiver: Option<AccountAddress>, amount: u64, error: String) -> Self {
+        Self {
+            kind: FundingEventKind::Failed,
+            receiver,
+            amount,
+            txn_hashes: vec![],
+            error: Some(error),
+            timestamp_secs: crate::stats::now_secs(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: Url,
+    /// Key the bodies are signed with, if any.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Kinds of events sent, all of them if empty.
+    #[serde(default)]
+    pub events: Vec<FundingEventKind>,
+}
+
+impl WebhookConfig {
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read webhooks {}", path.display()))?;
+        serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse webhooks {}", path.display()))
+    }
+
+    fn wants(&self, kind: FundingEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    /// Hex HMAC-SHA256 of the body, if the webhook has a secret.
+    fn signature(&self, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        Some(format!(
+            "sha256={}",
+            hex::encode(hmac::sign(&key, body).as_ref())
+        ))
+    }
+}
+
+struct Webhook {
+    config: WebhookConfig,
+    sender: mpsc::Sender<FundingEvent>,
+    /// Taken by the task delivering the events.
+    receiver: Mutex<Option<mpsc::Receiver<FundingEvent>>>,
+}
+
+pub struct Webhooks {
+    webhooks: Vec<Webhook>,
+    client: reqwest::Client,
+    /// Attempts at delivering an event, including the first.
+    max_attempts: u32,
+    /// Wait before the first retry, doubled for each of the next ones.
+    initial_backoff: Duration,
+}
+
+impl Webhooks {
+    pub fn new(configs: Vec<WebhookConfig>, max_attempts: u32, initial_backoff: Duration) -> Self {
+        let webhooks = configs
+            .into_iter()
+            .map(|config| {
+                let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+                Webhook {
+                    config,
+                    sender,
+                    receiver: Mutex::new(Some(receiver)),
+                }
+            })
+            .collect();
+        Self {
+            webhooks,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build webhook client"),
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+        }
+    }
+
+    /// Queues the event for delivery to the webhooks asking for it.
+    pub fn notify(&self, event: FundingEvent) {
+        for webhook in &self.webhooks {
+            if !webhook.config.wants(event.kind) {
+                continue;
+            }
+            if let Err(err) = webhook.sender.try_send(event.clone()) {
+                self.dead_letter(&webhook.config, &event, "queue full or closed");
+                warn!(webhook = webhook.config.url.as_str(), "{}", err);
+            }
+        }
+    }
+
+    /// Delivers the queued events, each webhook in order and independently of the others.
+    pub async fn run(self: Arc<Self>) {
+        let mut tasks = Vec::new();
+        for idx in 0..self.webhooks.len() {
+            let receiver = self.webhooks[idx].receiver.lock().unwrap().take();
+            if let Some(mut receiver) = receiver {
+                let webhooks = self.clone();
+                tasks.push(tokio::spawn(async move {
+                    while let Some(event) = receiver.recv().await {
+                        webhooks
+                            .deliver(&webhooks.webhooks[idx].config, &event)
+                            .await;
+                    }
+                }));
+            }
+        }
+        futures::future::join_all(tasks).await;
+    }
+
+    async fn deliver(&self, config: &WebhookConfig, event: &FundingEvent) {
+        let body = serde_json::to_vec(event).expect("Events must serialize");
+        let mut backoff = self.initial_backoff;
+        for attempt in 1..=self.max_attempts {
+            let mut request = self
+                .client
+                .post(config.url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event.kind.as_str())
+                .body(body.clone());
+            if let Some(signature) = config.signature(&body) {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+            let failure = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    WEBHOOK_DELIVERIES
+                        .with_label_values(&[event.kind.as_str(), "delivered"])
+                        .inc();
+                    return;
+                },
+                Ok(response) => format!("status {}", response.status()),
+                Err(err) => err.to_string(),
+            };
+            warn!(
+                webhook = config.url.as_str(),
+                attempt = attempt,
+                "webhook delivery failed: {}",
+                failure
+            );
+            if attempt < self.max_attempts {
+                WEBHOOK_DELIVERIES
+                    .with_label_values(&[event.kind.as_str(), "retried"])
+                    .inc();
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+        self.dead_letter(config, event, "out of attempts");
+    }
+
+    fn dead_letter(&self, config: &WebhookConfig, event: &FundingEvent, reason: &str) {
+        WEBHOOK_DELIVERIES
+            .with_label_values(&[event.kind.as_str(), "dead_lettered"])
+            .inc();
+        error!(
+            webhook = config.url.as_str(),
+            reason = reason,
+            event = serde_json::to_string(event).expect("Events must serialize"),
+            "webhook dead letter"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FundingEvent, FundingEventKind, WebhookConfig, Webhooks, EVENT_HEADER, SIGNATURE_HEADER,
+    };
+    use aptos_sdk::types::account_address::AccountAddress;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    use url::Url;
+    use warp::{http::StatusCode, hyper::body::Bytes, Filter};
+
+    #[tokio::test]
+    async fn test_webhooks() {
+        // Fails the first attempt at each delivery, records the others.
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let received: Arc<Mutex<Vec<(String, Option<String>, Bytes)>>> = Arc::default();
+        let hook = {
+            let (attempts, received) = (attempts.clone(), received.clone());
+            warp::path!("hook")
+                .and(warp::post())
+                .and(warp::header::<String>(EVENT_HEADER))
+                .and(warp::header::optional::<String>(SIGNATURE_HEADER))
+                .and(warp::body::bytes())
+                .map(move |kind, signature, body| {
+                    if attempts.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+                        return StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    received.lock().unwrap().push((kind, signature, body));
+                    StatusCode::OK
+                })
+        };
+        let (address, future) = warp::serve(hook).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::task::spawn(future);
+
+        let url = Url::parse(&format!("http://localhost:{}/hook", address.port())).unwrap();
+        let signed = WebhookConfig {
+            url: url.clone(),
+            secret: Some("s3cret".to_string()),
+            events: vec![FundingEventKind::Failed],
+        };
+        let webhooks = Arc::new(Webhooks::new(
+            vec![signed.clone()],
+            2,
+            Duration::from_millis(10),
+        ));
+        tokio::spawn(webhooks.clone().run());
+
+        // Not asked for.
+        webhooks.notify(FundingEvent::funded(Some(AccountAddress::ONE), 10, vec![]));
+        webhooks.notify(FundingEvent::failed(
+            Some(AccountAddress::ONE),
+            10,
+            "out of gas".to_string(),
+        ));
+        for _ in 0..100 {
+            if !received.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let (kind, signature, body) = &received[0];
+        assert_eq!(kind, "failed");
+        assert_eq!(signature.as_deref(), signed.signature(body).as_deref());
+        let event: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(event["kind"], "failed");
+        assert_eq!(event["error"], "out of gas");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // HMAC-SHA256 test vector of RFC 4231, case 2.
+        let config = WebhookConfig {
+            url,
+            secret: Some("Jefe".to_string()),
+            events: vec![],
+        };
+        assert_eq!(
+            config.signature(b"what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert!(config.wants(FundingEventKind::Funded));
+    }
+}
@@ -111,12 +111,29 @@ impl MetricsPusher {
             Err(_) => DEFAULT_PUSH_FREQUENCY_SECS,
         };
         let push_metrics_api_token = env::var("PUSH_METRICS_API_TOKEN").ok();
+
+        Some(Self::spawn_worker_thread(
+            quit_receiver,
+            push_metrics_endpoint,
+            push_metrics_frequency_secs,
+            push_metrics_api_token,
+            push_metrics_extra_labels,
+        ))
+    }
+
+    fn spawn_worker_thread(
+        quit_receiver: mpsc::Receiver<()>,
+        push_metrics_endpoint: String,
+        push_metrics_frequency_secs: u64,
+        push_metrics_api_token: Option<String>,
+        push_metrics_extra_labels: Vec<String>,
+    ) -> JoinHandle<()> {
         info!(
             "Starting push metrics loop. Sending metrics to {} with a frequency of {} seconds",
             push_metrics_endpoint, push_metrics_frequency_secs
         );
 
-        Some(thread::spawn(move || {
+        thread::spawn(move || {
             Self::worker(
                 quit_receiver,
                 push_metrics_endpoint,
@@ -124,7 +141,7 @@ impl MetricsPusher {
                 push_metrics_api_token,
                 push_metrics_extra_labels,
             )
-        }))
+        })
     }
 
     fn get_dashboard_link(chain_name: &str, namespace: &str) -> Url {
@@ -179,6 +196,29 @@ impl MetricsPusher {
         }
     }
 
+    /// Same as `start`, but with the pushgateway endpoint and settings given explicitly rather
+    /// than read from the environment.
+    pub fn start_with_endpoint(
+        push_metrics_endpoint: String,
+        push_metrics_frequency_secs: u64,
+        push_metrics_api_token: Option<String>,
+        push_metrics_labels: Vec<String>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker_thread = Self::spawn_worker_thread(
+            rx,
+            push_metrics_endpoint,
+            push_metrics_frequency_secs,
+            push_metrics_api_token,
+            push_metrics_labels,
+        );
+
+        Self {
+            worker_thread: Some(worker_thread),
+            quit_sender: tx,
+        }
+    }
+
     pub fn start_for_local_run(chain_name: &str) -> Self {
         let namespace = env::var("PUSH_METRICS_NAMESPACE").unwrap_or_else(|_| {
             SystemTime::now()
@@ -4,6 +4,7 @@
 use crate::State;
 use aptos_api_types::AptosError;
 use reqwest::StatusCode;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug)]
@@ -161,12 +162,40 @@ pub enum RestError {
     Http(StatusCode, reqwest::Error),
 }
 
+impl RestError {
+    /// HTTP status code the server answered with, if it answered.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            RestError::Api(inner) => Some(inner.status_code),
+            RestError::Http(status_code, _) => Some(*status_code),
+            _ => None,
+        }
+    }
+
+    /// Whether the server, or a proxy in front of it, asked to slow down.
+    pub fn is_throttled(&self) -> bool {
+        matches!(
+            self.status_code(),
+            Some(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+        )
+    }
+
+    /// How long the server asked to wait before retrying, if it said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RestError::Api(inner) => inner.retry_after,
+            _ => None,
+        }
+    }
+}
+
 impl From<(AptosError, Option<State>, StatusCode)> for RestError {
     fn from((error, state, status_code): (AptosError, Option<State>, StatusCode)) -> Self {
         Self::Api(AptosErrorResponse {
             error,
             state,
             status_code,
+            retry_after: None,
         })
     }
 }
@@ -210,6 +239,8 @@ pub struct AptosErrorResponse {
     pub error: AptosError,
     pub state: Option<State>,
     pub status_code: StatusCode,
+    /// The `Retry-After` header of the response, in seconds.
+    pub retry_after: Option<Duration>,
 }
 
 impl std::fmt::Display for AptosErrorResponse {
@@ -15,7 +15,7 @@ pub mod types;
 
 use crate::{
     aptos::{AptosVersion, Balance},
-    error::RestError,
+    error::{AptosErrorResponse, RestError},
 };
 use anyhow::{anyhow, Result};
 pub use aptos_api_types::{
@@ -24,9 +24,9 @@ pub use aptos_api_types::{
 use aptos_api_types::{
     deserialize_from_string,
     mime_types::{BCS, BCS_SIGNED_TRANSACTION as BCS_CONTENT_TYPE, JSON},
-    AptosError, BcsBlock, Block, GasEstimation, HexEncodedBytes, IndexResponse, MoveModuleId,
-    TransactionData, TransactionOnChainData, TransactionsBatchSubmissionResult, UserTransaction,
-    VersionedEvent, ViewRequest,
+    AptosError, AptosErrorCode, BcsBlock, Block, GasEstimation, HexEncodedBytes, IndexResponse,
+    MoveModuleId, TransactionData, TransactionOnChainData, TransactionsBatchSubmissionResult,
+    UserTransaction, VersionedEvent, ViewRequest,
 };
 use aptos_crypto::HashValue;
 use aptos_logger::{debug, info, sample, sample::SampleRate};
@@ -1603,11 +1603,40 @@ fn parse_state_optional(response: &reqwest::Response) -> Option<State> {
         .unwrap_or(None)
 }
 
+fn parse_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
 async fn parse_error(response: reqwest::Response) -> RestError {
     let status_code = response.status();
     let maybe_state = parse_state_optional(&response);
+    let retry_after = parse_retry_after(&response);
+    if status_code == StatusCode::TOO_MANY_REQUESTS {
+        // Rate limits are mostly enforced by proxies in front of the node, whose bodies aren't
+        // API errors, so keep what they said along with how long to wait.
+        let body = response.text().await.unwrap_or_default();
+        let error = serde_json::from_str::<AptosError>(&body).unwrap_or_else(|_| {
+            AptosError::new_with_error_code(body, AptosErrorCode::WebFrameworkError)
+        });
+        return RestError::Api(AptosErrorResponse {
+            error,
+            state: maybe_state,
+            status_code,
+            retry_after,
+        });
+    }
     match response.json::<AptosError>().await {
-        Ok(error) => (error, maybe_state, status_code).into(),
+        Ok(error) => RestError::Api(AptosErrorResponse {
+            error,
+            state: maybe_state,
+            status_code,
+            retry_after,
+        }),
         Err(e) => RestError::Http(status_code, e),
     }
 }
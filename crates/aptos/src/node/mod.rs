@@ -23,7 +23,10 @@ use aptos_backup_cli::{
     coordinators::restore::{RestoreCoordinator, RestoreCoordinatorOpt},
     metadata::cache::MetadataCacheOpt,
     storage::command_adapter::{config::CommandAdapterConfig, CommandAdapter},
-    utils::{ConcurrentDownloadsOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt, RocksdbOpt},
+    utils::{
+        ConcurrentDownloadsOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt, RocksdbOpt,
+        TargetDbOpt,
+    },
 };
 use aptos_cached_packages::aptos_stdlib;
 use aptos_config::config::NodeConfig;
@@ -1194,6 +1197,65 @@ impl CliCommand<()> for RunLocalTestnet {
                     chain_id: ChainId::test(),
                     maximum_amount: None,
                     do_not_delegate: self.do_not_delegate,
+                    trusted_proxies: vec![],
+                    max_requests_per_ip_per_day: None,
+                    rate_limit_schedule_file: None,
+                    captcha_verify_url: None,
+                    captcha_secret: None,
+                    captcha_challenge_url: None,
+                    pow_difficulty: None,
+                    pow_max_difficulty: 24,
+                    pow_requests_per_extra_bit: 10,
+                    pow_secret: None,
+                    velocity_multiplier: None,
+                    velocity_min_requests_per_minute: 10.0,
+                    asn_database_file: None,
+                    country_database_file: None,
+                    geo_policy_file: None,
+                    receiver_cooldown_secs: None,
+                    receiver_cooldown_factor: 4.0,
+                    receiver_max_cooldown_secs: 604_800,
+                    receiver_cooldown_migration_redis_url: None,
+                    receiver_cooldown_migration_read_target: false,
+                    receiver_allowlist_file: None,
+                    eligibility_view_function: None,
+                    shadow_ban_cidrs: vec![],
+                    adaptive_checker_order: false,
+                    checker_timeout_ms: None,
+                    checker_timeout_policy: aptos_faucet::checkers::CheckerTimeoutPolicy::Skip,
+                    reputation_sync_url: None,
+                    reputation_deployment: None,
+                    reputation_sync_interval_secs: 60,
+                    reputation_ttl_secs: 86400,
+                    ans_resolver_url: None,
+                    ans_cache_ttl_secs: 300,
+                    config_file: None,
+                    dev_mode: false,
+                    redis_url: None,
+                    account_pool_size: None,
+                    account_pool_amount: 100_000_000,
+                    explorer_url_template: None,
+                    max_batch_size: aptos_faucet::DEFAULT_MAX_BATCH_SIZE,
+                    min_runway_secs: None,
+                    runway_window_secs: 3600,
+                    runway_min_scale: 0.1,
+                    queue_capacity: None,
+                    queue_concurrency: 8,
+                    queue_stale_request_secs: 300,
+                    treasury_threshold: None,
+                    treasury_key_file: None,
+                    treasury_account_address: None,
+                    treasury_approval_token: None,
+                    treasury_max_requests_per_ip_per_day: 1,
+                    treasury_receiver_allowlist_file: None,
+                    admin_token: None,
+                    webhooks_file: None,
+                    webhook_max_attempts: 5,
+                    fake_funder_config: None,
+                    grant_operators_file: None,
+                    grant_audit_file: None,
+                    grant_approval_ttl_secs: 3600,
+                    next_mint_key_file: None,
                 }
                 .run(),
             )
@@ -1512,6 +1574,9 @@ impl CliCommand<()> for BootstrapDbFromBackup {
             replay_all: false,
             ledger_history_start_version: None,
             skip_epoch_endings: false,
+            catch_up: false,
+            epoch_history_only: false,
+            priority_state: Default::default(),
         };
         let global_opt = GlobalRestoreOpt {
             dry_run: false,
@@ -1519,6 +1584,7 @@ impl CliCommand<()> for BootstrapDbFromBackup {
             target_version: None,
             trusted_waypoints: Default::default(),
             rocksdb_opt: RocksdbOpt::default(),
+            target_db: TargetDbOpt::default(),
             concurrent_downloads: self.concurrent_downloads,
             replay_concurrency_level: self.replay_concurrency_level,
         }
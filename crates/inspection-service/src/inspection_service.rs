@@ -13,11 +13,13 @@ use prometheus::{
     proto::{MetricFamily, MetricType},
     Encoder, TextEncoder,
 };
+use serde_json::json;
 use std::{
     collections::HashMap,
     convert::Infallible,
-    net::{SocketAddr, ToSocketAddrs},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
     thread,
+    time::Duration,
 };
 
 // TODO: we need to write tests for this service!
@@ -31,6 +33,9 @@ const HEADER_CONTENT_TYPE: &str = "Content-Type";
 const INVALID_ENDPOINT_MESSAGE: &str = "The requested endpoint is invalid!";
 const UNEXPECTED_ERROR_MESSAGE: &str = "An unexpected error was encountered!";
 
+// Timeout when querying the backup service of the node
+const BACKUP_SERVICE_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub fn encode_metrics(encoder: impl Encoder) -> Vec<u8> {
     let metric_families = gather_metrics();
     let mut buffer = vec![];
@@ -89,12 +94,73 @@ pub fn get_all_metrics() -> HashMap<String, String> {
     get_metrics(all_metric_families)
 }
 
+/// Address to reach the backup service at from the node, which is localhost if the service
+/// listens on all interfaces.
+fn local_backup_service_address(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), address.port())
+        },
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), address.port())
+        },
+        _ => address,
+    }
+}
+
+/// Returns whether the backup service of the node is available, along with its status (i.e.,
+/// the endpoints it serves and the versions they serve) if it is.
+pub async fn get_backup_service_status(address: SocketAddr) -> serde_json::Value {
+    let url = format!("http://{}/status", local_backup_service_address(address));
+    let status = async {
+        reqwest::Client::builder()
+            .timeout(BACKUP_SERVICE_TIMEOUT)
+            .build()?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await
+    };
+    match status.await {
+        Ok(status) => json!({
+            "available": true,
+            "address": address.to_string(),
+            "status": status,
+        }),
+        Err(error) => json!({
+            "available": false,
+            "address": address.to_string(),
+            "error": error.to_string(),
+        }),
+    }
+}
+
 async fn serve_requests(
     req: Request<Body>,
     node_config: NodeConfig,
 ) -> Result<Response<Body>, hyper::Error> {
     // Process the request and get the response components
     let (status_code, body, content_type) = match req.uri().path() {
+        "/backup_service" => {
+            // Exposes the availability of the backup service and the versions it serves
+            if node_config.inspection_service.expose_backup_service {
+                let status =
+                    get_backup_service_status(node_config.storage.backup_service_address).await;
+                (
+                    StatusCode::OK,
+                    Body::from(status.to_string()),
+                    CONTENT_TYPE_JSON,
+                )
+            } else {
+                (
+                    StatusCode::FORBIDDEN,
+                    Body::from(DISABLED_ENDPOINT_MESSAGE),
+                    CONTENT_TYPE_TEXT,
+                )
+            }
+        },
         "/configuration" => {
             // Exposes the node configuration
             if node_config.inspection_service.expose_configuration {
@@ -1,7 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::inspection_service::get_all_metrics;
+use crate::inspection_service::{get_all_metrics, get_backup_service_status};
 use assert_approx_eq::assert_approx_eq;
 use once_cell::sync::Lazy;
 use prometheus::{proto::MetricFamily, register_int_counter, Counter, IntCounter, Opts, Registry};
@@ -70,3 +70,12 @@ fn get_all_metrics_test() {
     }
 }
 }
+
+#[tokio::test]
+async fn backup_service_unavailable_test() {
+    // Nothing listens on the port, as it's reserved
+    let status = get_backup_service_status("0.0.0.0:0".parse().unwrap()).await;
+    assert_eq!(status["available"], false);
+    assert_eq!(status["address"], "0.0.0.0:0");
+    assert!(status["error"].is_string());
+}
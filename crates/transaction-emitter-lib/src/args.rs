@@ -8,7 +8,11 @@ use aptos_crypto::ed25519::Ed25519PrivateKey;
 use aptos_sdk::types::chain_id::ChainId;
 use clap::{ArgEnum, ArgGroup, Parser};
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, path::Path};
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 use url::Url;
 
 const DEFAULT_API_PORT: u16 = 8080;
@@ -66,8 +70,10 @@ pub struct ClusterArgs {
     #[clap(long)]
     pub reuse_accounts: bool,
 
-    #[clap(long, default_value = "TESTING")]
-    pub chain_id: ChainId,
+    /// Chain the targets run. If not given, it's discovered from the targets, which must then
+    /// all run the same chain.
+    #[clap(long)]
+    pub chain_id: Option<ChainId>,
 
     #[clap(flatten)]
     pub coin_source_args: CoinSourceArgs,
@@ -79,10 +85,13 @@ pub enum TransactionTypeArg {
     AccountGeneration,
     AccountGenerationLargePool,
     NftMintAndTransfer,
+    FungibleTokenTransfer,
     PublishPackage,
     CustomFunctionLargeModuleWorkingSet,
     CreateNewResource,
     NoOp,
+    StakePoolOperations,
+    StakingContractOperations,
 }
 
 impl Default for TransactionTypeArg {
@@ -131,12 +140,33 @@ pub struct EmitArgs {
     #[clap(long, min_values = 0)]
     pub transaction_weights: Vec<usize>,
 
+    /// Collections the tokens of nft-mint-and-transfer are spread over, and each account mints
+    /// into.
+    #[clap(long, default_value = "1")]
+    pub nft_num_collections: usize,
+
+    /// Tokens of each collection nft-mint-and-transfer transfers.
+    #[clap(long, default_value = "1")]
+    pub nft_tokens_per_collection: usize,
+
+    /// Size in bytes of the description of the tokens nft-mint-and-transfer creates.
+    #[clap(long, default_value = "0")]
+    pub nft_metadata_size: usize,
+
+    /// Percentage of the rounds of an account in nft-mint-and-transfer minting new tokens,
+    /// rather than transferring existing ones.
+    #[clap(long, default_value = "0")]
+    pub nft_mint_percentage: usize,
+
     #[clap(long, min_values = 0)]
     pub transaction_phases: Vec<usize>,
 
+    /// Gas unit price of the transactions. If not given, the highest estimate of the targets.
     #[clap(long)]
     pub gas_price: Option<u64>,
 
+    /// Max gas of the transactions. If not given, the default, or the max allowed by the
+    /// on-chain gas schedule if lower.
     #[clap(long)]
     pub max_gas_per_txn: Option<u64>,
 
@@ -158,6 +188,182 @@ pub struct EmitArgs {
     // loadtest puts significant load, you can add a delay here.
     #[clap(long)]
     pub delay_after_minting: Option<u64>,
+
+    /// Record the generated transactions and their timing to this file, so the same workload
+    /// can be replayed later with --replay-transactions.
+    #[clap(long, parse(from_os_str))]
+    pub record_transactions: Option<PathBuf>,
+
+    /// Replay transactions recorded with --record-transactions instead of generating new ones.
+    /// The other arguments (targets, mode, transaction types, ...) should match the recorded run,
+    /// so that the same accounts are created.
+    #[clap(long, parse(from_os_str))]
+    pub replay_transactions: Option<PathBuf>,
+
+    /// Shed load (pause workers) while more than this fraction of the transactions expire, and
+    /// resume it gradually once well under, so the measured throughput reflects what the network
+    /// can sustain. The adjustments are logged, and summarized at the end.
+    #[clap(long)]
+    pub max_expired_ratio: Option<f64>,
+
+    /// Minimum committed TPS of each phase after its warmup, either one for all phases or one
+    /// per phase. The emitter aborts with a failure report as soon as a phase can't meet its
+    /// criteria, rather than running for the full duration.
+    #[clap(long, min_values = 0)]
+    pub phase_min_tps: Vec<u64>,
+
+    /// Maximum p99 latency in milliseconds of each phase, either one for all phases or one per
+    /// phase.
+    #[clap(long, min_values = 0)]
+    pub phase_max_p99_latency_ms: Vec<u64>,
+
+    /// Maximum percentage of transactions expiring in each phase, either one for all phases or
+    /// one per phase.
+    #[clap(long, min_values = 0)]
+    pub phase_max_expired_percent: Vec<f64>,
+
+    /// Time each phase is given to ramp up before it's judged against its criteria.
+    #[clap(long, default_value = "60")]
+    pub phase_warmup_secs: u64,
+
+    /// Abort once a phase fails this many checks (every 30 seconds) in a row.
+    #[clap(long, default_value = "3")]
+    pub phase_abort_after_failed_checks: usize,
+
+    /// Serve an API on this port of localhost to steer the run without restarting it: adjust
+    /// the target TPS (POST /tps/<tps>), pause and resume (POST /pause, POST /resume), dump the
+    /// stats (POST /stats), and see where it's at (GET /status).
+    #[clap(long)]
+    pub control_port: Option<u16>,
+
+    /// Report the gas spent at the end of the run, per phase and per transaction type, along with
+    /// the balance of the coin source account before and after. Fetching the committed
+    /// transactions back adds a read per account and batch.
+    #[clap(long)]
+    pub gas_report: bool,
+
+    /// Spill the accounts created by account generation, for other workloads to use, to a
+    /// temporary directory under this one once there are more than
+    /// --accounts-pool-max-in-memory, so that pools of millions of accounts fit on one host.
+    #[clap(long, parse(from_os_str))]
+    pub accounts_pool_spill_dir: Option<PathBuf>,
+
+    /// Number of accounts of the pool kept in memory, the most recently created ones, along with
+    /// --accounts-pool-spill-dir.
+    #[clap(long, default_value = "1000000")]
+    pub accounts_pool_max_in_memory: usize,
+
+    /// Latency SLO tracked over a sliding window as the run goes, e.g. p99<2s or p99.9<1500ms.
+    /// Each interval it's violated for is logged as it starts and ends, and summarized at the
+    /// end. Can be repeated.
+    #[clap(long, min_values = 0)]
+    pub latency_slo: Vec<String>,
+
+    /// Window the latency SLOs are evaluated over.
+    #[clap(long, default_value = "60")]
+    pub slo_window_secs: u64,
+
+    /// How often the latency SLOs are evaluated.
+    #[clap(long, default_value = "10")]
+    pub slo_check_interval_secs: u64,
+
+    /// Prometheus endpoint of a node, e.g. http://node:9101/metrics, whose metrics are logged
+    /// when a latency SLO starts being violated. Can be repeated.
+    #[clap(long, min_values = 0)]
+    pub node_metrics_url: Vec<Url>,
+
+    /// Name of a node metric to log along with the SLO violations, instead of the defaults
+    /// (mempool size, consensus rounds and state sync version). Can be repeated.
+    #[clap(long, min_values = 0)]
+    pub slo_node_metric: Vec<String>,
+
+    /// Create accounts in batches that start small, and grow while they commit within
+    /// --account-creation-target-latency-ms, or shrink when slower or retried, instead of
+    /// batches of a fixed size.
+    #[clap(long)]
+    pub adaptive_account_creation: bool,
+
+    /// Latency account creation batches are sized for, along with --adaptive-account-creation.
+    #[clap(long, default_value = "5000")]
+    pub account_creation_target_latency_ms: u64,
+
+    /// Largest account creation batch, along with --adaptive-account-creation.
+    #[clap(long, default_value = "500")]
+    pub account_creation_max_batch_size: usize,
+
+    /// Tenant to split the load among, as NAME:WEIGHT:MIX[:COIN_SOURCE_FILE], where MIX lists
+    /// transaction types with optional weights, e.g. nft-mint-and-transfer=3,coin-transfer=1.
+    /// Each tenant gets workers in proportion to its weight, with accounts funded from the
+    /// coin source in the file (BCS encoded key) or the coin source of the run otherwise, and the
+    /// same mix in every phase. Its stats are reported apart. Can be repeated.
+    #[clap(long, min_values = 0)]
+    pub tenant: Vec<String>,
+}
+
+/// A tenant given with --tenant.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TenantArg {
+    pub name: String,
+    pub weight: usize,
+    pub transaction_mix: Vec<(TransactionTypeArg, usize)>,
+    pub coin_source_file: Option<PathBuf>,
+}
+
+impl TenantArg {
+    pub fn load_coin_source_key(&self) -> Result<Option<Ed25519PrivateKey>> {
+        self.coin_source_file
+            .as_ref()
+            .map(|path| {
+                EncodingType::BCS.load_key::<Ed25519PrivateKey>("tenant coin source key", path)
+            })
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
+impl FromStr for TenantArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            format_err!(
+                "Invalid tenant {}, expected NAME:WEIGHT:MIX[:COIN_SOURCE_FILE], e.g. dapp:2:coin-transfer=1,no-op=1",
+                s
+            )
+        };
+        let mut parts = s.splitn(4, ':');
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(invalid)?;
+        let weight = parts
+            .next()
+            .and_then(|weight| weight.parse().ok())
+            .ok_or_else(invalid)?;
+        let transaction_mix = parts
+            .next()
+            .ok_or_else(invalid)?
+            .split(',')
+            .map(|entry| {
+                let (transaction_type, weight) = match entry.split_once('=') {
+                    Some((transaction_type, weight)) => (
+                        transaction_type,
+                        weight.parse::<usize>().map_err(|_| invalid())?,
+                    ),
+                    None => (entry, 1),
+                };
+                let transaction_type = TransactionTypeArg::from_str(transaction_type, true)
+                    .map_err(|e| format_err!("Invalid transaction type of tenant {}: {}", s, e))?;
+                Ok((transaction_type, weight))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            name: name.to_string(),
+            weight,
+            transaction_mix,
+            coin_source_file: parts.next().map(PathBuf::from),
+        })
+    }
 }
 
 fn parse_target(target: &str) -> Result<Url> {
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{emitter::query_sequence_number, instance::Instance, ClusterArgs};
-use anyhow::{anyhow, bail, format_err, Result};
+use anyhow::{anyhow, bail, ensure, format_err, Context, Result};
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
     test_utils::KeyPair,
@@ -10,10 +10,13 @@ use aptos_crypto::{
 use aptos_logger::{info, warn};
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::types::{
-    account_config::aptos_test_root_address, chain_id::ChainId, AccountKey, LocalAccount,
+    account_config::{aptos_test_root_address, CORE_CODE_ADDRESS},
+    chain_id::ChainId,
+    on_chain_config::GasScheduleV2,
+    AccountKey, LocalAccount,
 };
 use rand::seq::SliceRandom;
-use std::convert::TryFrom;
+use std::{collections::BTreeSet, convert::TryFrom};
 use url::Url;
 
 #[derive(Debug)]
@@ -24,6 +27,61 @@ pub struct Cluster {
     pub chain_id: ChainId,
 }
 
+/// Gas parameters of the chain, as reported by the targets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChainGasParams {
+    /// The highest gas unit price estimate among the targets.
+    pub gas_estimate: u64,
+    pub min_gas_price: u64,
+    pub max_gas_price: u64,
+    pub max_gas_per_txn: u64,
+}
+
+impl ChainGasParams {
+    fn from_gas_schedule(gas_estimate: u64, gas_schedule: GasScheduleV2) -> Result<Self> {
+        let entries = gas_schedule.to_btree_map();
+        let get = |key: &str| {
+            entries
+                .get(key)
+                .copied()
+                .ok_or_else(|| format_err!("{} missing from the on-chain gas schedule", key))
+        };
+        Ok(Self {
+            gas_estimate,
+            min_gas_price: get("txn.min_price_per_gas_unit")?,
+            max_gas_price: get("txn.max_price_per_gas_unit")?,
+            max_gas_per_txn: get("txn.maximum_number_of_gas_units")?,
+        })
+    }
+
+    /// Returns the gas unit price and max gas per transaction to use, the given ones if any,
+    /// otherwise the estimate and the default (or the chain max, if lower). Fails if a given one
+    /// is out of what the chain accepts.
+    pub fn resolve(
+        &self,
+        gas_price: Option<u64>,
+        max_gas_per_txn: Option<u64>,
+    ) -> Result<(u64, u64)> {
+        let gas_price = gas_price.unwrap_or_else(|| self.gas_estimate.max(self.min_gas_price));
+        ensure!(
+            (self.min_gas_price..=self.max_gas_price).contains(&gas_price),
+            "Gas price {} out of the range accepted by the chain, [{}, {}]",
+            gas_price,
+            self.min_gas_price,
+            self.max_gas_price,
+        );
+        let max_gas_per_txn = max_gas_per_txn
+            .unwrap_or_else(|| aptos_global_constants::MAX_GAS_AMOUNT.min(self.max_gas_per_txn));
+        ensure!(
+            max_gas_per_txn <= self.max_gas_per_txn,
+            "Max gas per transaction {} over the chain maximum {}",
+            max_gas_per_txn,
+            self.max_gas_per_txn,
+        );
+        Ok((gas_price, max_gas_per_txn))
+    }
+}
+
 fn clone(key: &Ed25519PrivateKey) -> Ed25519PrivateKey {
     let serialized: &[u8] = &(key.to_bytes());
     Ed25519PrivateKey::try_from(serialized).unwrap()
@@ -32,11 +90,14 @@ fn clone(key: &Ed25519PrivateKey) -> Ed25519PrivateKey {
 impl Cluster {
     /// We assume the URLs have been validated at this point, specifically to
     /// confirm that they have a host and port set.
+    ///
+    /// If no chain id is given, it's taken from the endpoints, which must all agree on it.
+    /// Otherwise endpoints running another chain are left out.
     pub async fn from_host_port(
         peers: Vec<Url>,
         coin_source_key: Ed25519PrivateKey,
         coin_source_is_root: bool,
-        chain_id: ChainId,
+        chain_id: Option<ChainId>,
     ) -> Result<Self> {
         let num_peers = peers.len();
 
@@ -65,6 +126,38 @@ impl Cluster {
             );
         }
 
+        let chain_id = match chain_id {
+            Some(chain_id) => chain_id,
+            None => {
+                let chain_ids: BTreeSet<u8> =
+                    instance_states.iter().map(|(_, s)| s.chain_id).collect();
+                match chain_ids.len() {
+                    0 => bail!(
+                        "None of the rest endpoints provided are reachable: {:?}",
+                        errors
+                    ),
+                    1 => {
+                        let chain_id = ChainId::new(*chain_ids.iter().next().unwrap());
+                        info!("Discovered chain id {} from the endpoints", chain_id);
+                        chain_id
+                    },
+                    _ => bail!(
+                        "Endpoints run different chains: {}. Pass --chain-id to only use the \
+                        ones running a given chain.",
+                        instance_states
+                            .iter()
+                            .map(|(instance, s)| format!(
+                                "{}: {}",
+                                instance.peer_name(),
+                                s.chain_id
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            },
+        };
+
         let mut instances = Vec::new();
         let max_version = instance_states
             .iter()
@@ -158,6 +251,54 @@ impl Cluster {
         Ok(LocalAccount::new(address, account_key, sequence_number))
     }
 
+    /// Queries all endpoints for their gas estimate and the on-chain gas schedule. Fails if the
+    /// gas schedules differ, e.g. because some endpoints lag behind a gas schedule upgrade.
+    pub async fn discover_gas_params(&self) -> Result<ChainGasParams> {
+        let mut discovered: Option<(String, ChainGasParams)> = None;
+        for instance in &self.instances {
+            let client = instance.rest_client();
+            let gas_estimate = client
+                .estimate_gas_price()
+                .await
+                .with_context(|| {
+                    format!("Failed to estimate gas price on {}", instance.peer_name())
+                })?
+                .into_inner()
+                .gas_estimate;
+            let gas_schedule: GasScheduleV2 = client
+                .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::gas_schedule::GasScheduleV2")
+                .await
+                .with_context(|| {
+                    format!("Failed to get gas schedule from {}", instance.peer_name())
+                })?
+                .into_inner();
+            let params = ChainGasParams::from_gas_schedule(gas_estimate, gas_schedule)?;
+            discovered = Some(match discovered {
+                None => (instance.peer_name().to_string(), params),
+                Some((name, prev)) => {
+                    ensure!(
+                        ChainGasParams {
+                            gas_estimate: prev.gas_estimate,
+                            ..params
+                        } == prev,
+                        "Gas schedules differ between {} ({:?}) and {} ({:?})",
+                        name,
+                        prev,
+                        instance.peer_name(),
+                        params,
+                    );
+                    (name, ChainGasParams {
+                        gas_estimate: prev.gas_estimate.max(params.gas_estimate),
+                        ..prev
+                    })
+                },
+            });
+        }
+        let (_, params) = discovered.ok_or_else(|| anyhow!("No endpoint in the cluster"))?;
+        info!("Discovered gas parameters from the endpoints: {:?}", params);
+        Ok(params)
+    }
+
     pub fn random_instance(&self) -> Instance {
         let mut rnd = rand::thread_rng();
         self.instances
@@ -170,3 +311,27 @@ impl Cluster {
         self.instances.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ChainGasParams;
+
+    #[test]
+    fn test_resolve_gas_params() {
+        let params = ChainGasParams {
+            gas_estimate: 150,
+            min_gas_price: 100,
+            max_gas_price: 10_000_000_000,
+            max_gas_per_txn: 1_000_000,
+        };
+        // Defaults to the estimate, and the default max gas capped by the chain.
+        assert_eq!(
+            params.resolve(None, None).unwrap(),
+            (150, aptos_global_constants::MAX_GAS_AMOUNT.min(1_000_000))
+        );
+        // Given values take precedence, but must be accepted by the chain.
+        assert_eq!(params.resolve(Some(200), Some(5000)).unwrap(), (200, 5000));
+        assert!(params.resolve(Some(50), None).is_err());
+        assert!(params.resolve(None, Some(2_000_000)).is_err());
+    }
+}
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    emitter::MAX_RETRIES,
+    emitter::{adaptive_batch::AdaptiveBatchSize, MAX_RETRIES},
     transaction_generator::{TransactionExecutor, SEND_AMOUNT},
     EmitJobRequest, EmitModeParams,
 };
@@ -20,13 +20,14 @@ use aptos_sdk::{
         AccountKey, LocalAccount,
     },
 };
-use core::{
-    cmp::min,
-    result::Result::{Err, Ok},
-};
+use core::result::Result::{Err, Ok};
 use futures::StreamExt;
 use rand::{rngs::StdRng, SeedableRng};
-use std::{path::Path, sync::atomic::AtomicUsize, time::Instant};
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
 
 #[derive(Debug)]
 pub struct AccountMinter<'t> {
@@ -155,6 +156,11 @@ impl<'t> AccountMinter<'t> {
         let failed_requests = std::iter::repeat_with(|| AtomicUsize::new(0))
             .take(MAX_RETRIES * 2)
             .collect::<Vec<_>>();
+        // Shared by seed and new accounts, as the network is the same.
+        let batch_sizes = match &req.adaptive_account_creation {
+            Some(config) => AdaptiveBatchSize::new(config.clone()),
+            None => AdaptiveBatchSize::fixed(mode_params.max_submit_batch_size),
+        };
 
         // Create seed accounts with which we can create actual accounts concurrently. Adding
         // additional fund for paying gas fees later.
@@ -163,7 +169,7 @@ impl<'t> AccountMinter<'t> {
                 txn_executor,
                 expected_num_seed_accounts,
                 coins_per_seed_account,
-                mode_params.max_submit_batch_size,
+                &batch_sizes,
                 &failed_requests,
             )
             .await?;
@@ -198,7 +204,7 @@ impl<'t> AccountMinter<'t> {
                     seed_account,
                     num_new_child_accounts,
                     coins_per_account,
-                    mode_params.max_submit_batch_size,
+                    &batch_sizes,
                     txn_executor,
                     &txn_factory,
                     req.reuse_accounts,
@@ -261,14 +267,14 @@ impl<'t> AccountMinter<'t> {
         txn_executor: &dyn TransactionExecutor,
         seed_account_num: usize,
         coins_per_seed_account: u64,
-        max_submit_batch_size: usize,
+        batch_sizes: &AdaptiveBatchSize,
         failed_requests: &[AtomicUsize],
     ) -> Result<Vec<LocalAccount>> {
         info!("Creating and funding seeds accounts");
         let mut i = 0;
         let mut seed_accounts = vec![];
         while i < seed_account_num {
-            let batch_size = min(max_submit_batch_size, seed_account_num - i);
+            let batch_size = batch_sizes.next(seed_account_num - i);
             let mut rng = StdRng::from_rng(self.rng()).unwrap();
             let mut batch = gen_random_accounts(batch_size, &mut rng);
             let source_account = &mut self.source_account;
@@ -284,9 +290,9 @@ impl<'t> AccountMinter<'t> {
                     )
                 })
                 .collect();
-            txn_executor
-                .execute_transactions_with_counter(&create_requests, failed_requests)
-                .await?;
+            let batch_start = Instant::now();
+            let failures = execute_batch(txn_executor, &create_requests, failed_requests).await?;
+            batch_sizes.record(batch_size, batch_start.elapsed(), failures);
 
             i += batch_size;
             seed_accounts.append(&mut batch);
@@ -335,6 +341,28 @@ fn failed_requests_to_trimmed_vec(failed_requests: Vec<AtomicUsize>) -> Vec<usiz
     result
 }
 
+/// Executes a batch of account creations, returning how many times its transactions had to be
+/// retried, also added to `failed_requests`.
+async fn execute_batch(
+    txn_executor: &dyn TransactionExecutor,
+    txns: &[SignedTransaction],
+    failed_requests: &[AtomicUsize],
+) -> Result<usize> {
+    let batch_failures = std::iter::repeat_with(|| AtomicUsize::new(0))
+        .take(failed_requests.len())
+        .collect::<Vec<_>>();
+    let result = txn_executor
+        .execute_transactions_with_counter(txns, &batch_failures)
+        .await;
+    let mut failures = 0;
+    for (total, batch) in failed_requests.iter().zip(batch_failures) {
+        let batch = batch.into_inner();
+        total.fetch_add(batch, Ordering::Relaxed);
+        failures += batch;
+    }
+    result.map(|()| failures)
+}
+
 fn gen_rng_for_reusable_account(count: usize) -> Vec<StdRng> {
     // use same seed for reuse account creation and reuse
     // TODO: Investigate why we use the same seed and then consider changing
@@ -358,7 +386,7 @@ async fn create_and_fund_new_accounts<R>(
     mut source_account: LocalAccount,
     num_new_accounts: usize,
     coins_per_new_account: u64,
-    max_num_accounts_per_batch: usize,
+    batch_sizes: &AdaptiveBatchSize,
     txn_executor: &dyn TransactionExecutor,
     txn_factory: &TransactionFactory,
     reuse_account: bool,
@@ -372,7 +400,7 @@ where
     let mut accounts = vec![];
 
     while i < num_new_accounts {
-        let batch_size = min(max_num_accounts_per_batch, num_new_accounts - i);
+        let batch_size = batch_sizes.next(num_new_accounts - i);
         let mut batch = if reuse_account {
             info!("Loading {} accounts if they exist", batch_size);
             gen_reusable_accounts(txn_executor, batch_size, &mut rng).await?
@@ -391,10 +419,11 @@ where
                 })
                 .collect();
 
-            txn_executor
-                .execute_transactions_with_counter(&creation_requests, failed_requests)
+            let batch_start = Instant::now();
+            let failures = execute_batch(txn_executor, &creation_requests, failed_requests)
                 .await
                 .with_context(|| format!("Account {} couldn't mint", source_account.address()))?;
+            batch_sizes.record(batch_size, batch_start.elapsed(), failures);
 
             batch
         };
@@ -413,26 +442,23 @@ async fn gen_reusable_accounts<R>(
 where
     R: rand_core::RngCore + ::rand_core::CryptoRng,
 {
-    let mut vasp_accounts = vec![];
-    let mut i = 0;
-    while i < num_accounts {
-        vasp_accounts.push(gen_reusable_account(txn_executor, rng).await?);
-        i += 1;
-    }
-    Ok(vasp_accounts)
-}
-
-async fn gen_reusable_account<R>(
-    txn_executor: &dyn TransactionExecutor,
-    rng: &mut R,
-) -> Result<LocalAccount>
-where
-    R: ::rand_core::RngCore + ::rand_core::CryptoRng,
-{
-    let account_key = AccountKey::generate(rng);
-    let address = account_key.authentication_key().derived_address();
-    let sequence_number = txn_executor.query_sequence_number(address).await?;
-    Ok(LocalAccount::new(address, account_key, sequence_number))
+    let account_keys: Vec<_> = (0..num_accounts)
+        .map(|_| AccountKey::generate(rng))
+        .collect();
+    let addresses: Vec<_> = account_keys
+        .iter()
+        .map(|account_key| account_key.authentication_key().derived_address())
+        .collect();
+    // One bulk sync rather than a query per account, which takes minutes on large pools.
+    let sequence_numbers = txn_executor.query_sequence_numbers(&addresses).await?;
+    Ok(account_keys
+        .into_iter()
+        .zip(addresses)
+        .zip(sequence_numbers)
+        .map(|((account_key, address), sequence_number)| {
+            LocalAccount::new(address, account_key, sequence_number)
+        })
+        .collect())
 }
 
 fn gen_random_accounts<R>(num_accounts: usize, rng: &mut R) -> Vec<LocalAccount>
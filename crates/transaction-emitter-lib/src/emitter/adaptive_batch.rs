@@ -0,0 +1,139 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Adaptive sizing of the batches accounts are created in.
+//!
+//! A fixed batch size either overloads small networks, whose batches then time out and are
+//! retried, or underuses big ones. Instead, batches start small, and after each batch the size
+//! shared by all seed accounts grows if the batch committed well within the target latency, and
+//! shrinks if it was slower or too many of its transactions had to be retried.
+
+use aptos_infallible::Mutex;
+use aptos_logger::info;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct AdaptiveBatchConfig {
+    pub initial_batch_size: usize,
+    pub min_batch_size: usize,
+    pub max_batch_size: usize,
+    /// Batches committed in under half of this grow, those slower than this shrink.
+    pub target_latency: Duration,
+    /// Batches with more retried transactions than this fraction of their size shrink.
+    pub max_failure_ratio: f64,
+    pub increase_factor: f64,
+    pub decrease_factor: f64,
+}
+
+impl Default for AdaptiveBatchConfig {
+    fn default() -> Self {
+        Self {
+            initial_batch_size: 10,
+            min_batch_size: 1,
+            max_batch_size: 500,
+            target_latency: Duration::from_secs(5),
+            max_failure_ratio: 0.05,
+            increase_factor: 2.0,
+            decrease_factor: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AdaptiveBatchSize {
+    config: AdaptiveBatchConfig,
+    size: Mutex<usize>,
+}
+
+impl AdaptiveBatchSize {
+    pub fn new(config: AdaptiveBatchConfig) -> Self {
+        let size = config
+            .initial_batch_size
+            .clamp(config.min_batch_size.max(1), config.max_batch_size.max(1));
+        Self {
+            config,
+            size: Mutex::new(size),
+        }
+    }
+
+    /// Always the same size, as when batches aren't adaptive.
+    pub fn fixed(batch_size: usize) -> Self {
+        Self::new(AdaptiveBatchConfig {
+            initial_batch_size: batch_size,
+            min_batch_size: batch_size,
+            max_batch_size: batch_size,
+            ..AdaptiveBatchConfig::default()
+        })
+    }
+
+    /// Size of the next batch, out of the remaining accounts.
+    pub fn next(&self, remaining: usize) -> usize {
+        std::cmp::min(*self.size.lock(), remaining)
+    }
+
+    /// Adjusts the size to how long a batch took to commit and how many of its transactions had
+    /// to be retried, returning the new size.
+    pub fn record(&self, batch_size: usize, latency: Duration, failures: usize) -> usize {
+        let mut size = self.size.lock();
+        let previous = *size;
+        if latency > self.config.target_latency
+            || failures as f64 > self.config.max_failure_ratio * batch_size as f64
+        {
+            *size = ((previous as f64 * self.config.decrease_factor) as usize)
+                .max(self.config.min_batch_size)
+                .max(1);
+        } else if latency * 2 <= self.config.target_latency && batch_size >= previous {
+            // Only full batches tell whether a larger one would commit in time.
+            *size = ((previous as f64 * self.config.increase_factor).ceil() as usize)
+                .min(self.config.max_batch_size)
+                .max(1);
+        }
+        if *size != previous {
+            info!(
+                "Account creation batch size {} -> {}, after a batch of {} committed in {}ms with {} retries",
+                previous,
+                *size,
+                batch_size,
+                latency.as_millis(),
+                failures,
+            );
+        }
+        *size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveBatchConfig, AdaptiveBatchSize};
+    use std::time::Duration;
+
+    #[test]
+    fn test_adaptive_batch_size() {
+        let batch_size = AdaptiveBatchSize::new(AdaptiveBatchConfig {
+            initial_batch_size: 10,
+            min_batch_size: 2,
+            max_batch_size: 30,
+            ..AdaptiveBatchConfig::default()
+        });
+        let fast = Duration::from_secs(1);
+        assert_eq!(batch_size.next(100), 10);
+        assert_eq!(batch_size.next(3), 3);
+
+        assert_eq!(batch_size.record(10, fast, 0), 20);
+        // A partial batch doesn't grow it.
+        assert_eq!(batch_size.record(5, fast, 0), 20);
+        assert_eq!(batch_size.record(20, fast, 0), 30);
+        assert_eq!(batch_size.record(30, fast, 0), 30);
+        // Neither fast nor slow.
+        assert_eq!(batch_size.record(30, Duration::from_secs(4), 0), 30);
+        assert_eq!(batch_size.record(30, Duration::from_secs(6), 0), 15);
+        // Too many retries.
+        assert_eq!(batch_size.record(15, fast, 1), 7);
+        assert_eq!(batch_size.record(7, fast, 2), 3);
+        assert_eq!(batch_size.record(3, fast, 3), 2);
+
+        let fixed = AdaptiveBatchSize::fixed(100);
+        assert_eq!(fixed.record(100, fast, 0), 100);
+        assert_eq!(fixed.record(100, Duration::from_secs(60), 50), 100);
+    }
+}
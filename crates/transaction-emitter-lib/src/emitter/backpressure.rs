@@ -0,0 +1,244 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shedding load when too many transactions expire.
+//!
+//! Once the network can't keep up, pushing the same load only makes transactions expire, and the
+//! measured throughput says little about capacity. The controller watches the fraction of
+//! expired transactions, and pauses workers (i.e. lowers the number of transactions in flight)
+//! while it's over the threshold, resuming them gradually once it's well under.
+
+use crate::emitter::stats::DynamicStatsTracking;
+use aptos_infallible::Mutex;
+use aptos_logger::{info, warn};
+use std::{
+    cmp::{max, min},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug)]
+pub struct BackpressureConfig {
+    /// Load is shed while the fraction of expired transactions, among those that finished in an
+    /// interval, is over this.
+    pub max_expired_ratio: f64,
+    /// How often the expiration rate is evaluated. Should be longer than the transaction
+    /// expiration time, as the effect of a change only shows once the transactions submitted
+    /// after it have expired.
+    pub check_interval: Duration,
+    /// Fraction of the active workers kept when shedding load.
+    pub decrease_factor: f64,
+    /// Fraction of all workers resumed at a time, when expiration is under half the threshold.
+    pub increase_fraction: f64,
+    /// Intervals with fewer finished transactions than this are ignored.
+    pub min_samples: u64,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            max_expired_ratio: 0.05,
+            check_interval: Duration::from_secs(60),
+            decrease_factor: 0.75,
+            increase_fraction: 0.05,
+            min_samples: 100,
+        }
+    }
+}
+
+/// A change of the number of active workers.
+#[derive(Clone, Debug)]
+pub struct BackpressureEvent {
+    /// Time since the start of the job.
+    pub at: Duration,
+    pub phase: usize,
+    pub committed: u64,
+    pub expired: u64,
+    pub previous_active_workers: usize,
+    pub active_workers: usize,
+}
+
+impl fmt::Display for BackpressureEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at {}s (phase {}): {} committed, {} expired, active workers {} -> {}",
+            self.at.as_secs(),
+            self.phase,
+            self.committed,
+            self.expired,
+            self.previous_active_workers,
+            self.active_workers,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct BackpressureController {
+    config: BackpressureConfig,
+    total_workers: usize,
+    active_workers: AtomicUsize,
+    events: Mutex<Vec<BackpressureEvent>>,
+}
+
+impl BackpressureController {
+    pub fn new(config: BackpressureConfig, total_workers: usize) -> Self {
+        Self {
+            config,
+            total_workers,
+            active_workers: AtomicUsize::new(total_workers),
+            events: Mutex::new(vec![]),
+        }
+    }
+
+    /// Workers with the highest indices are paused first. Workers are assigned to endpoints
+    /// round robin, so the load is shed evenly across endpoints.
+    pub fn is_worker_active(&self, worker_index: usize) -> bool {
+        worker_index < self.active_workers.load(Ordering::Relaxed)
+    }
+
+    pub fn active_workers(&self) -> usize {
+        self.active_workers.load(Ordering::Relaxed)
+    }
+
+    pub fn events(&self) -> Vec<BackpressureEvent> {
+        self.events.lock().clone()
+    }
+
+    /// Evaluates the transactions that finished in the last interval until `stop` is set.
+    pub async fn run(
+        self: Arc<Self>,
+        stats: Arc<DynamicStatsTracking>,
+        stop: Arc<AtomicBool>,
+        job_start: Instant,
+    ) {
+        let mut prev = (stats.get_cur_phase(), 0, 0);
+        let mut next_check = Instant::now() + self.config.check_interval;
+        while !stop.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if Instant::now() < next_check {
+                continue;
+            }
+            next_check += self.config.check_interval;
+
+            let phase = stats.get_cur_phase();
+            let cur = stats.get_cur();
+            let committed = cur.committed.load(Ordering::Relaxed);
+            let expired = cur.expired.load(Ordering::Relaxed);
+            if phase != prev.0 {
+                // Counters restart with each phase.
+                prev = (phase, 0, 0);
+            }
+            let changed = self
+                .adjust(
+                    job_start.elapsed(),
+                    phase,
+                    committed - prev.1,
+                    expired - prev.2,
+                )
+                .is_some();
+            prev = (phase, committed, expired);
+            if changed {
+                // Transactions in flight during the change still reflect the previous load.
+                next_check += self.config.check_interval;
+            }
+        }
+    }
+
+    /// Adjusts the number of active workers, given the transactions that finished in the last
+    /// interval. Returns the event, if it changed.
+    fn adjust(
+        &self,
+        at: Duration,
+        phase: usize,
+        committed: u64,
+        expired: u64,
+    ) -> Option<BackpressureEvent> {
+        let finished = committed + expired;
+        if finished < self.config.min_samples {
+            return None;
+        }
+        let expired_ratio = expired as f64 / finished as f64;
+        let previous_active_workers = self.active_workers();
+        let active_workers = if expired_ratio > self.config.max_expired_ratio {
+            max(
+                1,
+                (previous_active_workers as f64 * self.config.decrease_factor) as usize,
+            )
+        } else if expired_ratio < self.config.max_expired_ratio / 2.0 {
+            min(
+                self.total_workers,
+                previous_active_workers
+                    + max(
+                        1,
+                        (self.total_workers as f64 * self.config.increase_fraction) as usize,
+                    ),
+            )
+        } else {
+            previous_active_workers
+        };
+        if active_workers == previous_active_workers {
+            return None;
+        }
+
+        self.active_workers.store(active_workers, Ordering::Relaxed);
+        let event = BackpressureEvent {
+            at,
+            phase,
+            committed,
+            expired,
+            previous_active_workers,
+            active_workers,
+        };
+        if active_workers < previous_active_workers {
+            warn!("Too many transactions expired, shedding load: {}", event);
+        } else {
+            info!("Expiration back under control, resuming load: {}", event);
+        }
+        self.events.lock().push(event.clone());
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackpressureConfig, BackpressureController};
+    use std::time::Duration;
+
+    #[test]
+    fn test_adjust() {
+        let controller = BackpressureController::new(BackpressureConfig::default(), 100);
+        let at = Duration::from_secs(60);
+
+        // Too few samples to judge.
+        assert!(controller.adjust(at, 0, 10, 10).is_none());
+        assert_eq!(controller.active_workers(), 100);
+
+        // 10% expired, shed a quarter.
+        let event = controller.adjust(at, 0, 900, 100).unwrap();
+        assert_eq!(
+            (event.previous_active_workers, event.active_workers),
+            (100, 75)
+        );
+        assert!(controller.is_worker_active(74));
+        assert!(!controller.is_worker_active(75));
+
+        // Between half the threshold and the threshold, hold.
+        assert!(controller.adjust(at, 0, 970, 30).is_none());
+
+        // Well under, resume 5% of all workers at a time, up to all of them.
+        assert_eq!(
+            controller.adjust(at, 0, 1000, 0).unwrap().active_workers,
+            80
+        );
+        for _ in 0..10 {
+            controller.adjust(at, 0, 1000, 0);
+        }
+        assert_eq!(controller.active_workers(), 100);
+        assert_eq!(controller.events().len(), 6);
+    }
+}
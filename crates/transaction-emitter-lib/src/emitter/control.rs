@@ -0,0 +1,257 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Steering a running job, e.g. a long soak run, without restarting it.
+//!
+//! A small HTTP API, meant to listen on localhost only:
+//! * `GET /status`: whether the job is paused, the current phase and how many workers are active.
+//! * `POST /tps/<tps>`: pauses or resumes workers for the job to emit that many transactions per
+//!   second, up to its initial target. Only in constant TPS mode.
+//! * `POST /pause`, `POST /resume`: stops and resumes emitting. The clock of the current phase
+//!   stops while paused, i.e. the phase still emits for its full duration.
+//! * `POST /stats`: logs the stats of every phase so far, and returns them.
+
+use crate::emitter::stats::DynamicStatsTracking;
+use anyhow::{format_err, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::info;
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+use warp::{http::StatusCode, Filter};
+
+#[derive(Debug, Serialize)]
+pub struct ControlStatus {
+    pub paused: bool,
+    pub phase: usize,
+    pub active_workers: usize,
+    pub total_workers: usize,
+    /// Only in constant TPS mode.
+    pub target_tps: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct EmitterControl {
+    total_workers: usize,
+    /// Transactions per second emitted by each worker, in constant TPS mode.
+    tps_per_worker: Option<f64>,
+    active_workers: AtomicUsize,
+    paused: AtomicBool,
+    /// Total time spent paused, and since when it's paused if it is.
+    paused_for: Mutex<(Duration, Option<Instant>)>,
+    stats: Arc<DynamicStatsTracking>,
+    phase_starts: Arc<Mutex<Vec<Instant>>>,
+}
+
+impl EmitterControl {
+    pub fn new(
+        total_workers: usize,
+        tps_per_worker: Option<f64>,
+        stats: Arc<DynamicStatsTracking>,
+        phase_starts: Arc<Mutex<Vec<Instant>>>,
+    ) -> Self {
+        Self {
+            total_workers,
+            tps_per_worker,
+            active_workers: AtomicUsize::new(total_workers),
+            paused: AtomicBool::new(false),
+            paused_for: Mutex::new((Duration::ZERO, None)),
+            stats,
+            phase_starts,
+        }
+    }
+
+    /// Like with backpressure, workers with the highest indices are paused first.
+    pub fn is_worker_active(&self, worker_index: usize) -> bool {
+        !self.is_paused() && worker_index < self.active_workers.load(Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> ControlStatus {
+        let active_workers = self.active_workers.load(Ordering::Relaxed);
+        ControlStatus {
+            paused: self.is_paused(),
+            phase: self.stats.get_cur_phase(),
+            active_workers,
+            total_workers: self.total_workers,
+            target_tps: self
+                .tps_per_worker
+                .map(|tps_per_worker| active_workers as f64 * tps_per_worker),
+        }
+    }
+
+    pub fn set_target_tps(&self, tps: f64) -> Result<()> {
+        let tps_per_worker = self.tps_per_worker.ok_or_else(|| {
+            format_err!("The target TPS can only be changed in constant TPS mode")
+        })?;
+        let max_tps = self.total_workers as f64 * tps_per_worker;
+        if !(0.0..=max_tps).contains(&tps) {
+            return Err(format_err!(
+                "The target TPS must be between 0 and {}, the initial one",
+                max_tps
+            ));
+        }
+        let active_workers = (tps / tps_per_worker).round() as usize;
+        self.active_workers.store(active_workers, Ordering::Relaxed);
+        info!(
+            "Target TPS set to {}, with {} active workers",
+            tps, active_workers
+        );
+        Ok(())
+    }
+
+    pub fn pause(&self) {
+        let mut paused_for = self.paused_for.lock();
+        if !self.paused.swap(true, Ordering::Relaxed) {
+            paused_for.1 = Some(Instant::now());
+            info!("Paused emitting");
+        }
+    }
+
+    pub fn resume(&self) {
+        let mut paused_for = self.paused_for.lock();
+        if self.paused.swap(false, Ordering::Relaxed) {
+            if let Some(since) = paused_for.1.take() {
+                paused_for.0 += since.elapsed();
+            }
+            info!("Resumed emitting");
+        }
+    }
+
+    /// Total time spent paused so far.
+    pub fn paused_for(&self) -> Duration {
+        let paused_for = self.paused_for.lock();
+        paused_for.0 + paused_for.1.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// Waits for as long as the job was paused since it had been paused for `paused_before`, and
+    /// until it's resumed, so that phases emit for their full duration.
+    pub async fn make_up_for_pauses(&self, paused_before: Duration) {
+        let mut made_up = paused_before;
+        loop {
+            let paused_for = self.paused_for();
+            if paused_for > made_up {
+                tokio::time::sleep(paused_for - made_up).await;
+                made_up = paused_for;
+            } else if self.is_paused() {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Logs the stats of every phase so far, and returns them.
+    pub fn dump_stats(&self) -> String {
+        let stats = self.stats.accumulate(&self.phase_starts.lock());
+        let lines: Vec<_> = stats
+            .iter()
+            .enumerate()
+            .map(|(phase, stats)| format!("phase {}: {}, {}", phase, stats, stats.rate()))
+            .collect();
+        for line in &lines {
+            info!("{}", line);
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Serves the control API on `address`, until the returned task is aborted.
+    pub fn serve(self: Arc<Self>, address: SocketAddr) -> Result<(SocketAddr, JoinHandle<()>)> {
+        let control = warp::any().map(move || self.clone());
+        let status = warp::path!("status")
+            .and(warp::get())
+            .and(control.clone())
+            .map(|control: Arc<Self>| warp::reply::json(&control.status()));
+        let tps = warp::path!("tps" / f64)
+            .and(warp::post())
+            .and(control.clone())
+            .map(
+                |tps: f64, control: Arc<Self>| match control.set_target_tps(tps) {
+                    Ok(()) => warp::reply::with_status(String::new(), StatusCode::OK),
+                    Err(e) => warp::reply::with_status(format!("{}\n", e), StatusCode::BAD_REQUEST),
+                },
+            );
+        let pause = warp::path!("pause")
+            .and(warp::post())
+            .and(control.clone())
+            .map(|control: Arc<Self>| {
+                control.pause();
+                warp::reply()
+            });
+        let resume = warp::path!("resume")
+            .and(warp::post())
+            .and(control.clone())
+            .map(|control: Arc<Self>| {
+                control.resume();
+                warp::reply()
+            });
+        let stats = warp::path!("stats")
+            .and(warp::post())
+            .and(control)
+            .map(|control: Arc<Self>| control.dump_stats());
+        let (address, server) = warp::serve(status.or(tps).or(pause).or(resume).or(stats))
+            .try_bind_ephemeral(address)
+            .map_err(|e| format_err!("Failed to bind the control API to {}: {}", address, e))?;
+        info!("Control API listening on http://{}", address);
+        Ok((address, tokio::spawn(server)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmitterControl;
+    use crate::emitter::stats::DynamicStatsTracking;
+    use aptos_infallible::Mutex;
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    fn control(tps_per_worker: Option<f64>) -> EmitterControl {
+        EmitterControl::new(
+            10,
+            tps_per_worker,
+            Arc::new(DynamicStatsTracking::new(1)),
+            Arc::new(Mutex::new(vec![Instant::now()])),
+        )
+    }
+
+    #[test]
+    fn test_target_tps() {
+        let control = control(Some(50.0));
+        assert_eq!(control.status().target_tps, Some(500.0));
+        control.set_target_tps(120.0).unwrap();
+        assert_eq!(control.status().active_workers, 2);
+        assert!(control.is_worker_active(1));
+        assert!(!control.is_worker_active(2));
+        // Workers can only be paused, not added.
+        assert!(control.set_target_tps(600.0).is_err());
+        assert!(self::control(None).set_target_tps(100.0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let control = control(Some(50.0));
+        control.pause();
+        assert!(!control.is_worker_active(0));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        control.resume();
+        assert!(control.is_worker_active(0));
+        let paused_for = control.paused_for();
+        assert!(paused_for >= Duration::from_millis(100));
+
+        let start = Instant::now();
+        control.make_up_for_pauses(Duration::ZERO).await;
+        assert!(start.elapsed() >= paused_for);
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! What a run costs: the gas spent by the committed transactions, per phase and per type, for
+//! runs to be budgeted and unexpectedly expensive workloads to stand out.
+
+use aptos_infallible::Mutex;
+use aptos_sdk::types::transaction::{SignedTransaction, TransactionPayload};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TypeCost {
+    pub committed: u64,
+    /// Committed, but aborted or otherwise failed to execute, which costs gas all the same.
+    pub failed: u64,
+    pub gas_units: u64,
+    pub octas: u64,
+}
+
+impl TypeCost {
+    pub fn avg_gas_units(&self) -> u64 {
+        self.gas_units.checked_div(self.committed).unwrap_or(0)
+    }
+
+    fn add(&mut self, other: &TypeCost) {
+        self.committed += other.committed;
+        self.failed += other.failed;
+        self.gas_units += other.gas_units;
+        self.octas += other.octas;
+    }
+}
+
+/// Gas spent per phase and per type of transaction, as the entry function called, e.g.
+/// `0x1::aptos_account::transfer`, or `script`, `module_bundle` and `multisig`.
+#[derive(Debug)]
+pub struct GasAccounting {
+    per_phase: Mutex<Vec<BTreeMap<String, TypeCost>>>,
+}
+
+impl GasAccounting {
+    pub fn new(phases: usize) -> Self {
+        Self {
+            per_phase: Mutex::new(vec![BTreeMap::new(); phases]),
+        }
+    }
+
+    pub fn txn_type(txn: &SignedTransaction) -> String {
+        match txn.payload() {
+            TransactionPayload::EntryFunction(entry_function) => format!(
+                "0x{}::{}::{}",
+                entry_function.module().address().short_str_lossless(),
+                entry_function.module().name(),
+                entry_function.function()
+            ),
+            TransactionPayload::Script(_) => "script".to_string(),
+            TransactionPayload::ModuleBundle(_) => "module_bundle".to_string(),
+            TransactionPayload::Multisig(_) => "multisig".to_string(),
+        }
+    }
+
+    pub fn record(&self, phase: usize, txn: &SignedTransaction, gas_used: u64, success: bool) {
+        let mut per_phase = self.per_phase.lock();
+        let cost = per_phase[phase].entry(Self::txn_type(txn)).or_default();
+        cost.committed += 1;
+        if !success {
+            cost.failed += 1;
+        }
+        cost.gas_units += gas_used;
+        cost.octas += gas_used * txn.gas_unit_price();
+    }
+
+    pub fn report(
+        &self,
+        expected_gas_per_txn: u64,
+        source_initial_balance: Option<u64>,
+        source_final_balance: Option<u64>,
+    ) -> GasReport {
+        GasReport {
+            per_phase: self.per_phase.lock().clone(),
+            expected_gas_per_txn,
+            source_initial_balance,
+            source_final_balance,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GasReport {
+    pub per_phase: Vec<BTreeMap<String, TypeCost>>,
+    /// Types using more gas units than this per transaction, on average, are flagged.
+    pub expected_gas_per_txn: u64,
+    /// Balance of the account the accounts of the run are funded from, before they were.
+    pub source_initial_balance: Option<u64>,
+    pub source_final_balance: Option<u64>,
+}
+
+impl GasReport {
+    pub fn per_type(&self) -> BTreeMap<String, TypeCost> {
+        let mut per_type: BTreeMap<String, TypeCost> = BTreeMap::new();
+        for phase in &self.per_phase {
+            for (txn_type, cost) in phase {
+                per_type.entry(txn_type.clone()).or_default().add(cost);
+            }
+        }
+        per_type
+    }
+
+    pub fn total(&self) -> TypeCost {
+        let mut total = TypeCost::default();
+        for cost in self.per_type().values() {
+            total.add(cost);
+        }
+        total
+    }
+
+    /// Types more expensive than expected, on average.
+    pub fn expensive_types(&self) -> Vec<String> {
+        self.per_type()
+            .into_iter()
+            .filter(|(_, cost)| cost.avg_gas_units() > self.expected_gas_per_txn)
+            .map(|(txn_type, _)| txn_type)
+            .collect()
+    }
+}
+
+impl fmt::Display for GasReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        writeln!(
+            f,
+            "Gas spent: {} octas, {} gas units by {} committed transactions",
+            total.octas, total.gas_units, total.committed
+        )?;
+        for (phase, per_type) in self.per_phase.iter().enumerate() {
+            for (txn_type, cost) in per_type {
+                let flag = if cost.avg_gas_units() > self.expected_gas_per_txn {
+                    format!(
+                        " [more than the expected {} gas units]",
+                        self.expected_gas_per_txn
+                    )
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    f,
+                    "  phase {}: {}: {} octas, {} txns ({} failed), {} gas units per txn{}",
+                    phase,
+                    txn_type,
+                    cost.octas,
+                    cost.committed,
+                    cost.failed,
+                    cost.avg_gas_units(),
+                    flag,
+                )?;
+            }
+        }
+        match (self.source_initial_balance, self.source_final_balance) {
+            (Some(initial), Some(last)) => write!(
+                f,
+                "Source account balance: {} octas initially, {} at the end, {} spent on funding and gas",
+                initial,
+                last,
+                initial as i128 - last as i128,
+            ),
+            _ => write!(f, "Source account balance unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GasAccounting;
+    use aptos_sdk::{
+        transaction_builder::{aptos_stdlib, TransactionFactory},
+        types::{account_address::AccountAddress, chain_id::ChainId, LocalAccount},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_gas_report() {
+        let mut rng = StdRng::from_seed([0; 32]);
+        let mut account = LocalAccount::generate(&mut rng);
+        let factory = TransactionFactory::new(ChainId::test()).with_gas_unit_price(100);
+        let transfer = account.sign_with_transaction_builder(
+            factory.payload(aptos_stdlib::aptos_coin_transfer(AccountAddress::ONE, 1)),
+        );
+        let accounting = GasAccounting::new(2);
+        assert_eq!(GasAccounting::txn_type(&transfer), "0x1::coin::transfer");
+
+        accounting.record(0, &transfer, 10, true);
+        accounting.record(1, &transfer, 30, false);
+        let report = accounting.report(15, Some(10_000), Some(5_000));
+        let total = report.total();
+        assert_eq!(total.committed, 2);
+        assert_eq!(total.failed, 1);
+        assert_eq!(total.gas_units, 40);
+        assert_eq!(total.octas, 4_000);
+        // 20 gas units per transfer on average, over the expected 15.
+        assert_eq!(report.expensive_types(), vec![
+            "0x1::coin::transfer".to_string()
+        ]);
+        assert!(report.to_string().contains("5000 spent on funding and gas"));
+    }
+}
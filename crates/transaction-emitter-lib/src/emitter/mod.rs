@@ -2,22 +2,45 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod account_minter;
+pub mod adaptive_batch;
+pub mod backpressure;
+pub mod control;
+pub mod gas;
+pub mod recording;
+pub mod slo;
 pub mod stats;
 pub mod submission_worker;
+pub mod success_criteria;
+pub mod tenants;
+pub mod throttling;
 pub mod transaction_executor;
 
 use crate::{
     emitter::{
         account_minter::AccountMinter,
+        adaptive_batch::AdaptiveBatchConfig,
+        backpressure::{BackpressureConfig, BackpressureController, BackpressureEvent},
+        control::EmitterControl,
+        gas::{GasAccounting, GasReport},
+        recording::{Recording, ReplayPlan, TransactionRecorder},
+        slo::{SloConfig, SloMonitor, SloViolation},
         stats::{DynamicStatsTracking, TxnStats},
         submission_worker::SubmissionWorker,
+        success_criteria::{PhaseFailure, PhaseJudge, PhaseSuccessCriteria},
+        tenants::{split_workers, TenantConfig, TenantStats, TenantsStatsTracking},
+        throttling::{TargetThrottling, TargetThrottlingStats},
         transaction_executor::RestApiTransactionExecutor,
     },
-    transaction_generator::{create_txn_generator_creator, EntryPoints},
+    transaction_generator::{
+        accounts_pool::AccountsPool, create_txn_generator_creator,
+        nft_mint_and_transfer::NftWorkload, staking::StakingWorkload, EntryPoints,
+        TransactionExecutor,
+    },
 };
 use again::RetryPolicy;
 use anyhow::{ensure, format_err, Result};
 use aptos_config::config::DEFAULT_MAX_SUBMIT_TRANSACTION_BATCH_SIZE;
+use aptos_infallible::Mutex;
 use aptos_logger::{debug, error, info, sample, sample::SampleRate, warn};
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::{
@@ -32,6 +55,8 @@ use rand_core::SeedableRng;
 use std::{
     cmp::{max, min},
     collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -67,7 +92,11 @@ pub enum TransactionType {
         max_account_working_set: usize,
         creation_balance: u64,
     },
-    NftMintAndTransfer,
+    NftMintAndTransfer {
+        workload: NftWorkload,
+    },
+    /// Transfers of a fungible token of the token standard.
+    FungibleTokenTransfer,
     PublishPackage {
         use_account_pool: bool,
     },
@@ -76,6 +105,9 @@ pub enum TransactionType {
         num_modules: usize,
         use_account_pool: bool,
     },
+    Staking {
+        workload: StakingWorkload,
+    },
 }
 
 impl TransactionType {
@@ -94,6 +126,12 @@ impl TransactionType {
         }
     }
 
+    pub fn default_nft_mint_and_transfer() -> Self {
+        Self::NftMintAndTransfer {
+            workload: NftWorkload::default(),
+        }
+    }
+
     pub fn default_call_custom_module() -> Self {
         Self::CallCustomModules {
             entry_point: EntryPoints::Nop,
@@ -198,6 +236,25 @@ pub struct EmitJobRequest {
     prompt_before_spending: bool,
 
     delay_after_minting: Duration,
+
+    /// Where to record the generated transactions, along with the seed of the emitter.
+    record_transactions: Option<(PathBuf, [u8; 32])>,
+    replay_recording: Option<Arc<Recording>>,
+    expiration_backpressure: Option<BackpressureConfig>,
+    success_criteria_per_phase: Vec<PhaseSuccessCriteria>,
+    /// Where to serve the control API, see `control`.
+    control_address: Option<SocketAddr>,
+    /// Report the gas spent, see `gas`.
+    gas_report: bool,
+    /// Where to spill the accounts pool beyond how many accounts it may hold in memory, see
+    /// `AccountsPool`.
+    accounts_pool_spill: Option<(PathBuf, usize)>,
+    /// Latency SLOs tracked over a sliding window, see `slo`.
+    latency_slos: Option<SloConfig>,
+    /// Size the account creation batches to the network, see `adaptive_batch`.
+    adaptive_account_creation: Option<AdaptiveBatchConfig>,
+    /// Tenants the workers are split among, see `tenants`.
+    tenants: Vec<TenantConfig>,
 }
 
 impl Default for EmitJobRequest {
@@ -219,6 +276,16 @@ impl Default for EmitJobRequest {
             expected_gas_per_txn: aptos_global_constants::MAX_GAS_AMOUNT,
             prompt_before_spending: false,
             delay_after_minting: Duration::from_secs(0),
+            record_transactions: None,
+            replay_recording: None,
+            expiration_backpressure: None,
+            success_criteria_per_phase: Vec::new(),
+            control_address: None,
+            gas_report: false,
+            accounts_pool_spill: None,
+            latency_slos: None,
+            adaptive_account_creation: None,
+            tenants: Vec::new(),
         }
     }
 }
@@ -310,6 +377,104 @@ impl EmitJobRequest {
         self
     }
 
+    /// Records the generated transactions to `path` when the job stops. `emitter_seed` must be the
+    /// seed of the rng the `TxnEmitter` was created with, so that a replay creates the same
+    /// accounts.
+    pub fn record_transactions(mut self, path: PathBuf, emitter_seed: [u8; 32]) -> Self {
+        self.record_transactions = Some((path, emitter_seed));
+        self
+    }
+
+    /// Submits the recorded transactions, at the recorded times, instead of generating new ones.
+    /// The `TxnEmitter` must be created with an rng seeded with `recording.emitter_seed`, and the
+    /// rest of the request must match the recorded run.
+    pub fn replay_recording(mut self, recording: Arc<Recording>) -> Self {
+        self.replay_recording = Some(recording);
+        self
+    }
+
+    /// Pauses workers while too many transactions expire, see `BackpressureController`.
+    /// Ignored when replaying a recording, which must keep the recorded pace.
+    pub fn expiration_backpressure(mut self, config: BackpressureConfig) -> Self {
+        self.expiration_backpressure = Some(config);
+        self
+    }
+
+    /// Aborts the job with a `PhaseFailure` as soon as a phase can't meet its criteria, instead of
+    /// running for the full duration. A single criteria applies to every phase.
+    pub fn success_criteria_per_phase(
+        mut self,
+        success_criteria_per_phase: Vec<PhaseSuccessCriteria>,
+    ) -> Self {
+        self.success_criteria_per_phase = success_criteria_per_phase;
+        self
+    }
+
+    /// Serves an API to adjust the target TPS, pause and resume the job, and dump its stats
+    /// while it runs, see `control`.
+    pub fn control_address(mut self, address: SocketAddr) -> Self {
+        self.control_address = Some(address);
+        self
+    }
+
+    /// Fetches back the committed transactions to report the gas they spent, per phase and type,
+    /// at the end of the job. This adds a read per account and batch to the load.
+    pub fn gas_report(mut self) -> Self {
+        self.gas_report = true;
+        self
+    }
+
+    /// Keeps at most `max_in_memory` of the accounts created for other workloads to use in
+    /// memory, spilling the others to a temporary directory under `dir`.
+    pub fn accounts_pool_spill(mut self, dir: PathBuf, max_in_memory: usize) -> Self {
+        self.accounts_pool_spill = Some((dir, max_in_memory));
+        self
+    }
+
+    /// Tracks latency SLOs over a sliding window, logging each interval they are violated for,
+    /// with a snapshot of the metrics of the nodes, see `slo`.
+    pub fn latency_slos(mut self, config: SloConfig) -> Self {
+        self.latency_slos = Some(config);
+        self
+    }
+
+    /// Creates accounts in batches that start small, and grow or shrink with how fast they
+    /// commit and how many of their transactions have to be retried, see `adaptive_batch`.
+    pub fn adaptive_account_creation(mut self, config: AdaptiveBatchConfig) -> Self {
+        self.adaptive_account_creation = Some(config);
+        self
+    }
+
+    /// Splits the workers among tenants, each with its own coin source, transaction mix and share
+    /// of the load, whose stats are reported apart, see `tenants`. The transaction mix of the
+    /// request is then only used for its number of phases, which the tenants must match.
+    pub fn tenants(mut self, tenants: Vec<TenantConfig>) -> Self {
+        self.tenants = tenants;
+        self
+    }
+
+    /// The request for the accounts and transactions of a tenant with `num_workers` out of
+    /// `total_workers`.
+    fn for_tenant(&self, tenant: &TenantConfig, num_workers: usize, total_workers: usize) -> Self {
+        Self {
+            transaction_mix_per_phase: tenant.transaction_mix_per_phase.clone(),
+            expected_max_txns: self.expected_max_txns * num_workers as u64 / total_workers as u64,
+            // Only the root account can mint.
+            mint_to_root: self.mint_to_root && tenant.coin_source_key.is_none(),
+            tenants: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    fn success_criteria_for_phase(&self, phase: usize) -> Option<&PhaseSuccessCriteria> {
+        let criteria = if self.success_criteria_per_phase.len() == 1 {
+            self.success_criteria_per_phase.first()
+        } else {
+            self.success_criteria_per_phase.get(phase)
+        };
+        criteria.filter(|criteria| !criteria.is_empty())
+    }
+
     pub fn calculate_mode_params(&self) -> EmitModeParams {
         let clients_count = self.rest_clients.len();
 
@@ -511,35 +676,154 @@ pub struct EmitJob {
     workers: Vec<Worker>,
     stop: Arc<AtomicBool>,
     stats: Arc<DynamicStatsTracking>,
-    phase_starts: Vec<Instant>,
+    phase_starts: Arc<Mutex<Vec<Instant>>>,
+    recorder: Option<Arc<TransactionRecorder>>,
+    backpressure: Option<Arc<BackpressureController>>,
+    rest_clients: Vec<RestClient>,
+    throttling: Arc<TargetThrottling>,
+    /// The control API and the task serving it.
+    control: Option<(Arc<EmitterControl>, JoinHandle<()>)>,
+    /// Gas spent so far, along with the source account and its balance before the job, and the
+    /// gas per transaction expected.
+    gas_accounting: Option<(Arc<GasAccounting>, AccountAddress, Option<u64>, u64)>,
+    slo_monitor: Option<Arc<SloMonitor>>,
+    tenants_stats: TenantsStatsTracking,
 }
 
 impl EmitJob {
     pub fn start_next_phase(&mut self) {
         let cur_phase = self.stats.start_next_phase();
+        self.tenants_stats.start_next_phase();
 
-        assert!(self.phase_starts.len() == cur_phase);
-        self.phase_starts.push(Instant::now());
+        let mut phase_starts = self.phase_starts.lock();
+        assert!(phase_starts.len() == cur_phase);
+        phase_starts.push(Instant::now());
+    }
+
+    pub fn control(&self) -> Option<&Arc<EmitterControl>> {
+        self.control.as_ref().map(|(control, _)| control)
     }
 
     pub fn get_cur_phase(&self) -> usize {
         self.stats.get_cur_phase()
     }
 
-    pub async fn stop_and_accumulate(self) -> Vec<TxnStats> {
+    pub async fn stop_and_accumulate(mut self) -> Vec<TxnStats> {
         self.stop.store(true, Ordering::Relaxed);
-        for worker in self.workers {
-            let _accounts = worker
-                .join_handle
-                .await
-                .expect("TxnEmitter worker thread failed");
+        if let Some((_, server)) = &self.control {
+            server.abort();
+        }
+        let mut accounts = vec![];
+        for worker in std::mem::take(&mut self.workers) {
+            accounts.append(
+                &mut worker
+                    .join_handle
+                    .await
+                    .expect("TxnEmitter worker thread failed"),
+            );
+        }
+        report_out_of_sync_accounts(self.rest_clients.clone(), &accounts).await;
+        if let Some(recorder) = &self.recorder {
+            match recorder.save() {
+                Ok(()) => info!("Saved recording of the generated transactions"),
+                Err(e) => error!(
+                    "Failed to save recording of the generated transactions: {:?}",
+                    e
+                ),
+            }
+        }
+
+        if let Some(backpressure) = &self.backpressure {
+            let events = backpressure.events();
+            info!(
+                "Load was adjusted {} times because of expired transactions, ending with {} active workers",
+                events.len(),
+                backpressure.active_workers(),
+            );
+            for event in events {
+                info!("  {}", event);
+            }
+        }
+
+        let throttled_targets = self.throttling_stats();
+        if !throttled_targets.is_empty() {
+            warn!(
+                "{} of {} targets rate limited the emitter",
+                throttled_targets.len(),
+                self.rest_clients.len()
+            );
+            for stats in throttled_targets {
+                warn!("  {}", stats);
+            }
         }
 
-        self.stats.accumulate(&self.phase_starts)
+        if let Some(slo_monitor) = &self.slo_monitor {
+            let violations = slo_monitor.violations();
+            info!("Latency SLOs were violated {} times", violations.len());
+            for violation in violations {
+                info!("  {}", violation);
+            }
+        }
+
+        if !self.tenants_stats.is_empty() {
+            info!("Stats per tenant:");
+            for tenant_stats in self.tenant_stats() {
+                info!("  {}", tenant_stats);
+            }
+        }
+
+        if let Some(report) = self.gas_report().await {
+            info!("{}", report);
+            let expensive_types = report.expensive_types();
+            if !expensive_types.is_empty() {
+                warn!(
+                    "Transaction types using more than the expected {} gas units on average: {:?}",
+                    report.expected_gas_per_txn, expensive_types
+                );
+            }
+        }
+
+        self.stats.accumulate(&self.phase_starts.lock())
+    }
+
+    /// Gas spent so far, if reported on, with the current balance of the source account.
+    pub async fn gas_report(&self) -> Option<GasReport> {
+        let (gas_accounting, source, initial_balance, expected_gas_per_txn) =
+            self.gas_accounting.as_ref()?;
+        let final_balance = query_balance(&self.rest_clients, *source).await;
+        Some(gas_accounting.report(*expected_gas_per_txn, *initial_balance, final_balance))
+    }
+
+    /// Intervals the latency SLOs were violated for so far, if tracked.
+    pub fn slo_violations(&self) -> Vec<SloViolation> {
+        self.slo_monitor
+            .as_ref()
+            .map_or_else(Vec::new, |slo_monitor| slo_monitor.violations())
+    }
+
+    /// How much each target that rate limited the emitter did so far.
+    pub fn throttling_stats(&self) -> Vec<TargetThrottlingStats> {
+        self.throttling
+            .stats()
+            .into_iter()
+            .filter(|stats| stats.throttled_responses > 0)
+            .collect()
+    }
+
+    /// Stats of each tenant so far, per phase, if the job has tenants.
+    pub fn tenant_stats(&self) -> Vec<TenantStats> {
+        self.tenants_stats.accumulate(&self.phase_starts.lock())
+    }
+
+    /// Changes of the load made because of expired transactions, if enabled.
+    pub fn backpressure_events(&self) -> Vec<BackpressureEvent> {
+        self.backpressure
+            .as_ref()
+            .map_or_else(Vec::new, |backpressure| backpressure.events())
     }
 
     pub fn accumulate(&self) -> Vec<TxnStats> {
-        self.stats.accumulate(&self.phase_starts)
+        self.stats.accumulate(&self.phase_starts.lock())
     }
 }
 
@@ -581,6 +865,28 @@ impl TxnEmitter {
             "Will use {} workers per endpoint for a total of {} endpoint clients and {} accounts",
             workers_per_endpoint, num_workers, num_accounts
         );
+        ensure!(
+            req.tenants
+                .iter()
+                .all(|tenant| tenant.transaction_mix_per_phase.len()
+                    == req.transaction_mix_per_phase.len()),
+            "Tenants need a transaction mix for each of the {} phases",
+            req.transaction_mix_per_phase.len()
+        );
+        // Without tenants, the workers are all of one group.
+        let workers_per_tenant = if req.tenants.is_empty() {
+            vec![num_workers]
+        } else {
+            split_workers(&req.tenants, num_workers)?
+        };
+        for (tenant, tenant_num_workers) in req.tenants.iter().zip(&workers_per_tenant) {
+            info!(
+                "Tenant {} will use {} workers and {} accounts",
+                tenant.name,
+                tenant_num_workers,
+                tenant_num_workers * mode_params.accounts_per_worker
+            );
+        }
 
         let txn_factory = self
             .txn_factory
@@ -591,37 +897,89 @@ impl TxnEmitter {
         let init_txn_factory = txn_factory
             .clone()
             .with_gas_unit_price(req.gas_price * req.init_gas_price_multiplier);
-        let seed = self.rng.gen();
-        info!(
-            "AccountMinter Seed (can be passed in to reuse accounts): {:?}",
-            seed
-        );
-        let mut account_minter = AccountMinter::new(
-            root_account,
-            init_txn_factory.clone(),
-            StdRng::from_seed(seed),
-        );
+        let gas_accounting = if req.gas_report {
+            let initial_balance = query_balance(&req.rest_clients, root_account.address()).await;
+            Some((
+                Arc::new(GasAccounting::new(stats_tracking_phases)),
+                root_account.address(),
+                initial_balance,
+                req.expected_gas_per_txn,
+            ))
+        } else {
+            None
+        };
         let txn_executor = RestApiTransactionExecutor {
             rest_clients: req.rest_clients.clone(),
             max_retries: MAX_RETRIES,
         };
-        let mut all_accounts = account_minter
-            .create_accounts(&txn_executor, &req, &mode_params, num_accounts)
-            .await?;
         let stop = Arc::new(AtomicBool::new(false));
         let stats = Arc::new(DynamicStatsTracking::new(stats_tracking_phases));
+        let tenants_stats = TenantsStatsTracking::new(&req.tenants, stats_tracking_phases);
         let tokio_handle = Handle::current();
 
-        let mut txn_generator_creator = create_txn_generator_creator(
-            &req.transaction_mix_per_phase,
-            num_workers,
-            &mut all_accounts,
-            &txn_executor,
-            &txn_factory,
-            &init_txn_factory,
-            stats.clone(),
-        )
-        .await;
+        // The accounts and the transaction generator creator of each tenant.
+        let mut worker_groups = vec![];
+        for (tenant_index, tenant_num_workers) in workers_per_tenant.iter().copied().enumerate() {
+            let tenant = req.tenants.get(tenant_index);
+            let tenant_req =
+                tenant.map(|tenant| req.for_tenant(tenant, tenant_num_workers, num_workers));
+            let group_req = tenant_req.as_ref().unwrap_or(&req);
+            let mut tenant_source_account = match tenant {
+                Some(tenant) => {
+                    tenant
+                        .load_coin_source_account(&req.rest_clients[0])
+                        .await?
+                },
+                None => None,
+            };
+            let source_account = tenant_source_account.as_mut().unwrap_or(&mut *root_account);
+
+            let seed = self.rng.gen();
+            if let Some(tenant) = tenant {
+                info!(
+                    "Creating the accounts of tenant {} from {}",
+                    tenant.name,
+                    source_account.address()
+                );
+            }
+            info!(
+                "AccountMinter Seed (can be passed in to reuse accounts): {:?}",
+                seed
+            );
+            let mut account_minter = AccountMinter::new(
+                source_account,
+                init_txn_factory.clone(),
+                StdRng::from_seed(seed),
+            );
+            let mut accounts = account_minter
+                .create_accounts(
+                    &txn_executor,
+                    group_req,
+                    &mode_params,
+                    tenant_num_workers * mode_params.accounts_per_worker,
+                )
+                .await?;
+
+            let accounts_pool = match &req.accounts_pool_spill {
+                Some((dir, max_in_memory)) => AccountsPool::with_spill(dir, *max_in_memory)
+                    .map_err(|e| {
+                        format_err!("Failed to create accounts pool in {:?}: {}", dir, e)
+                    })?,
+                None => AccountsPool::new(),
+            };
+            let txn_generator_creator = create_txn_generator_creator(
+                &group_req.transaction_mix_per_phase,
+                tenant_num_workers,
+                &mut accounts,
+                &txn_executor,
+                &txn_factory,
+                &init_txn_factory,
+                stats.clone(),
+                accounts_pool,
+            )
+            .await;
+            worker_groups.push((accounts.into_iter(), txn_generator_creator));
+        }
 
         if !req.delay_after_minting.is_zero() {
             info!(
@@ -649,19 +1007,80 @@ impl TxnEmitter {
         );
 
         let all_start_sleep_durations = mode_params.get_all_start_sleep_durations(self.from_rng());
-        let mut all_accounts_iter = all_accounts.into_iter();
+        let job_start = Instant::now();
+        let recorder = req
+            .record_transactions
+            .as_ref()
+            .map(|(path, emitter_seed)| {
+                Arc::new(TransactionRecorder::new(
+                    path.clone(),
+                    *emitter_seed,
+                    job_start,
+                ))
+            });
+        let backpressure = match (&req.expiration_backpressure, &req.replay_recording) {
+            (Some(config), None) => {
+                let backpressure =
+                    Arc::new(BackpressureController::new(config.clone(), total_workers));
+                tokio_handle.spawn(backpressure.clone().run(
+                    stats.clone(),
+                    stop.clone(),
+                    job_start,
+                ));
+                Some(backpressure)
+            },
+            _ => None,
+        };
+        let slo_monitor = req.latency_slos.as_ref().map(|config| {
+            let slo_monitor = Arc::new(SloMonitor::new(config.clone()));
+            tokio_handle.spawn(
+                slo_monitor
+                    .clone()
+                    .run(stats.clone(), stop.clone(), job_start),
+            );
+            slo_monitor
+        });
+        let phase_starts = Arc::new(Mutex::new(vec![Instant::now()]));
+        let control = match req.control_address {
+            Some(address) => {
+                // In constant TPS mode, each worker submits its batch once per wait.
+                let tps_per_worker = (mode_params.wait_millis > 0).then(|| {
+                    (mode_params.transactions_per_account * mode_params.accounts_per_worker) as f64
+                        * 1000.0
+                        / mode_params.wait_millis as f64
+                });
+                let control = Arc::new(EmitterControl::new(
+                    total_workers,
+                    tps_per_worker,
+                    stats.clone(),
+                    phase_starts.clone(),
+                ));
+                let (_, server) = control.clone().serve(address)?;
+                Some((control, server))
+            },
+            None => None,
+        };
+        let throttling = Arc::new(TargetThrottling::new(req.rest_clients.clone()));
+        // The workers of each tenant are the next ones in order.
+        let worker_tenants: Vec<usize> = workers_per_tenant
+            .iter()
+            .enumerate()
+            .flat_map(|(tenant_index, num)| std::iter::repeat(tenant_index).take(*num))
+            .collect();
         let mut workers = vec![];
         for _ in 0..workers_per_endpoint {
-            for client in &req.rest_clients {
-                let accounts = (&mut all_accounts_iter)
+            for (target, client) in req.rest_clients.iter().enumerate() {
+                let worker_index = workers.len();
+                let tenant_index = worker_tenants[worker_index];
+                let (accounts_iter, txn_generator_creator) = &mut worker_groups[tenant_index];
+                let accounts = accounts_iter
                     .take(mode_params.accounts_per_worker)
                     .collect::<Vec<_>>();
                 let stop = stop.clone();
                 let stats = Arc::clone(&stats);
                 let txn_generator = txn_generator_creator.create_transaction_generator().await;
-                let worker_index = workers.len();
 
-                let worker = SubmissionWorker::new(
+                let mut worker = SubmissionWorker::new(
                     accounts,
                     client.clone(),
                     stop,
@@ -671,7 +1090,30 @@ impl TxnEmitter {
                     all_start_sleep_durations[worker_index],
                     check_account_sequence_only_once_for.contains(&worker_index),
                     self.from_rng(),
-                );
+                )
+                .with_throttling(target, throttling.clone());
+                if let Some(recorder) = &recorder {
+                    worker = worker.with_recorder(worker_index, recorder.clone());
+                }
+                if let Some(backpressure) = &backpressure {
+                    worker = worker.with_backpressure(worker_index, backpressure.clone());
+                }
+                if let Some((control, _)) = &control {
+                    worker = worker.with_control(worker_index, control.clone());
+                }
+                if let Some((gas_accounting, ..)) = &gas_accounting {
+                    worker = worker.with_gas_accounting(gas_accounting.clone());
+                }
+                if !tenants_stats.is_empty() {
+                    worker = worker.with_tenant_stats(tenants_stats.get(tenant_index).clone());
+                }
+                if let Some(recording) = &req.replay_recording {
+                    worker = worker.with_replay(ReplayPlan {
+                        txn_factory: txn_factory.clone(),
+                        start: job_start,
+                        batches: recording.batches_for_worker(worker_index),
+                    });
+                }
                 let join_handle = tokio_handle.spawn(worker.run().boxed());
                 workers.push(Worker { join_handle });
             }
@@ -682,7 +1124,15 @@ impl TxnEmitter {
             workers,
             stop,
             stats,
-            phase_starts: vec![Instant::now()],
+            phase_starts,
+            recorder,
+            backpressure,
+            rest_clients: req.rest_clients.clone(),
+            throttling,
+            control,
+            gas_accounting,
+            slo_monitor,
+            tenants_stats,
         })
     }
 
@@ -713,6 +1163,43 @@ impl TxnEmitter {
         }
     }
 
+    /// Waits for the phase to last `duration`, judging it as it goes. On failure, the job is left
+    /// for the caller to stop.
+    async fn watch_phase(
+        &mut self,
+        job: &EmitJob,
+        phase: usize,
+        duration: Duration,
+        criteria: &PhaseSuccessCriteria,
+        print_stats_interval: Option<u64>,
+    ) -> Result<(), PhaseFailure> {
+        let deadline = Instant::now() + duration;
+        let window = match print_stats_interval {
+            Some(interval_secs) => min(
+                criteria.check_interval,
+                Duration::from_secs(max(interval_secs, 1)),
+            ),
+            None => criteria.check_interval,
+        };
+        let mut judge = PhaseJudge::new(criteria, phase);
+        let mut prev_stats: Option<TxnStats> = None;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            tokio::time::sleep(min(window, deadline - now)).await;
+            let stats = self.peek_job_stats(job).swap_remove(phase);
+            if print_stats_interval.is_some() {
+                let delta = &stats - prev_stats.as_ref().unwrap_or(&TxnStats::default());
+                info!("phase {}: {}", phase, delta.rate());
+            }
+            judge.check(&stats)?;
+            prev_stats = Some(stats);
+        }
+        judge.finish(&self.peek_job_stats(job).swap_remove(phase))
+    }
+
     async fn emit_txn_for_impl(
         mut self,
         source_account: &mut LocalAccount,
@@ -721,6 +1208,9 @@ impl TxnEmitter {
         print_stats_interval: Option<u64>,
     ) -> Result<TxnStats> {
         let phases = emit_job_request.transaction_mix_per_phase.len();
+        let success_criteria: Vec<_> = (0..phases)
+            .map(|phase| emit_job_request.success_criteria_for_phase(phase).cloned())
+            .collect();
 
         let mut job = self
             .start_job(source_account, emit_job_request, phases)
@@ -737,12 +1227,33 @@ impl TxnEmitter {
                 info!("Starting next phase");
                 job.start_next_phase();
             }
-            if let Some(interval_secs) = print_stats_interval {
+            let paused_before = job
+                .control()
+                .map_or(Duration::ZERO, |control| control.paused_for());
+            if let Some(criteria) = &success_criteria[phase] {
+                if let Err(failure) = self
+                    .watch_phase(
+                        &job,
+                        phase,
+                        per_phase_duration,
+                        criteria,
+                        print_stats_interval,
+                    )
+                    .await
+                {
+                    error!("Aborting the job: {}", failure);
+                    self.stop_job(job).await;
+                    return Err(failure.into());
+                }
+            } else if let Some(interval_secs) = print_stats_interval {
                 self.periodic_stat(&job, per_phase_duration, interval_secs)
                     .await;
             } else {
                 time::sleep(per_phase_duration).await;
             }
+            if let Some(control) = job.control() {
+                control.make_up_for_pauses(paused_before).await;
+            }
         }
         info!("Ran for {} secs, stopping job...", duration.as_secs());
         let stats = self.stop_job(job).await;
@@ -938,6 +1449,50 @@ fn update_seq_num_and_get_num_expired(
         )
 }
 
+/// Reports the accounts whose sequence numbers the workers lost track of, e.g. because
+/// transactions they counted on expired. Their sequence numbers are fetched from the chain in bulk
+/// at the end of a job only to compare, the accounts aren't updated as they aren't used after it.
+async fn report_out_of_sync_accounts(rest_clients: Vec<RestClient>, accounts: &[LocalAccount]) {
+    let txn_executor = RestApiTransactionExecutor {
+        rest_clients,
+        max_retries: MAX_RETRIES,
+    };
+    let addresses: Vec<_> = accounts.iter().map(|account| account.address()).collect();
+    match txn_executor.query_sequence_numbers(&addresses).await {
+        Ok(sequence_numbers) => {
+            let out_of_sync = accounts
+                .iter()
+                .zip(sequence_numbers)
+                .filter(|(account, sequence_number)| account.sequence_number() != *sequence_number)
+                .count();
+            if out_of_sync > 0 {
+                warn!(
+                    "{} out of {} accounts have a different sequence number on chain than the workers tracked",
+                    out_of_sync,
+                    accounts.len()
+                );
+            } else {
+                info!("All {} accounts are in sync with the chain", accounts.len());
+            }
+        },
+        Err(e) => warn!(
+            "Failed to check the accounts against the chain at the end of the job: {:?}",
+            e
+        ),
+    }
+}
+
+/// Balance of the account, from the first client that answers.
+async fn query_balance(rest_clients: &[RestClient], address: AccountAddress) -> Option<u64> {
+    for client in rest_clients {
+        match client.get_account_balance(address).await {
+            Ok(balance) => return Some(balance.into_inner().get()),
+            Err(e) => warn!("Failed to query the balance of {}: {:?}", address, e),
+        }
+    }
+    None
+}
+
 pub async fn query_sequence_number(client: &RestClient, address: AccountAddress) -> Result<u64> {
     Ok(query_sequence_numbers(client, [address].iter()).await?.0[0].1)
 }
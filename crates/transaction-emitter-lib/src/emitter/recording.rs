@@ -0,0 +1,206 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recording of the transactions generated by a run, so the exact same workload can be replayed
+//! against another network build.
+//!
+//! Accounts are derived from the emitter seed, which is part of the recording. As long as the
+//! replay uses the same job configuration (endpoints, mode, etc), the same accounts are created,
+//! so the recorded payloads (including the accounts they refer to) are valid again, and are
+//! re-signed with fresh sequence numbers and expiration times.
+
+use anyhow::{Context, Result};
+use aptos_infallible::Mutex;
+use aptos_sdk::{
+    move_types::account_address::AccountAddress,
+    transaction_builder::TransactionFactory,
+    types::{
+        transaction::{SignedTransaction, TransactionPayload},
+        LocalAccount,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedTransaction {
+    pub sender: AccountAddress,
+    pub payload: TransactionPayload,
+}
+
+/// Transactions generated at once by a worker.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedBatch {
+    pub worker_index: usize,
+    /// Time since the start of the job at which the batch was generated.
+    pub offset_millis: u64,
+    pub transactions: Vec<RecordedTransaction>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Recording {
+    /// Seed of the `TxnEmitter` rng, from which all accounts are derived.
+    pub emitter_seed: [u8; 32],
+    pub batches: Vec<RecordedBatch>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        bcs::from_bytes(&bytes)
+            .with_context(|| format!("Failed to deserialize recording {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, bcs::to_bytes(self)?)
+            .with_context(|| format!("Failed to write recording {}", path.display()))
+    }
+
+    /// Batches of the given worker, in the order they were generated.
+    pub fn batches_for_worker(&self, worker_index: usize) -> VecDeque<RecordedBatch> {
+        let mut batches: Vec<_> = self
+            .batches
+            .iter()
+            .filter(|batch| batch.worker_index == worker_index)
+            .cloned()
+            .collect();
+        batches.sort_by_key(|batch| batch.offset_millis);
+        batches.into()
+    }
+}
+
+/// Collects the batches generated by all workers, and writes them out when the job stops.
+#[derive(Debug)]
+pub struct TransactionRecorder {
+    path: PathBuf,
+    emitter_seed: [u8; 32],
+    start: Instant,
+    batches: Mutex<Vec<RecordedBatch>>,
+}
+
+impl TransactionRecorder {
+    pub fn new(path: PathBuf, emitter_seed: [u8; 32], start: Instant) -> Self {
+        Self {
+            path,
+            emitter_seed,
+            start,
+            batches: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, worker_index: usize, txns: &[SignedTransaction]) {
+        let batch = RecordedBatch {
+            worker_index,
+            offset_millis: self.start.elapsed().as_millis() as u64,
+            transactions: txns
+                .iter()
+                .map(|txn| RecordedTransaction {
+                    sender: txn.sender(),
+                    payload: txn.payload().clone(),
+                })
+                .collect(),
+        };
+        self.batches.lock().push(batch);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let recording = Recording {
+            emitter_seed: self.emitter_seed,
+            batches: std::mem::take(&mut *self.batches.lock()),
+        };
+        recording.save(&self.path)
+    }
+}
+
+/// What a worker replays instead of generating transactions.
+#[derive(Debug)]
+pub struct ReplayPlan {
+    pub txn_factory: TransactionFactory,
+    pub start: Instant,
+    pub batches: VecDeque<RecordedBatch>,
+}
+
+impl ReplayPlan {
+    /// Signs the recorded payloads with the matching accounts. Transactions from senders the
+    /// worker doesn't own are skipped, which only happens if the job configuration changed.
+    pub fn sign(
+        &self,
+        batch: &RecordedBatch,
+        accounts: &mut [LocalAccount],
+    ) -> Vec<SignedTransaction> {
+        batch
+            .transactions
+            .iter()
+            .filter_map(|recorded| {
+                accounts
+                    .iter_mut()
+                    .find(|account| account.address() == recorded.sender)
+                    .map(|account| {
+                        account.sign_with_transaction_builder(
+                            self.txn_factory.payload(recorded.payload.clone()),
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recording, ReplayPlan, TransactionRecorder};
+    use aptos_sdk::{
+        transaction_builder::{aptos_stdlib, TransactionFactory},
+        types::{chain_id::ChainId, LocalAccount},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Instant;
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut rng = StdRng::from_seed([1; 32]);
+        let txn_factory = TransactionFactory::new(ChainId::test());
+        let mut sender = LocalAccount::generate(&mut rng);
+        let receiver = LocalAccount::generate(&mut rng);
+
+        let recorder = TransactionRecorder::new("unused".into(), [7; 32], Instant::now());
+        let txns: Vec<_> = (0..3)
+            .map(|amount| {
+                sender.sign_with_transaction_builder(txn_factory.payload(
+                    aptos_stdlib::aptos_coin_transfer(receiver.address(), amount),
+                ))
+            })
+            .collect();
+        recorder.record(1, &txns[..2]);
+        recorder.record(0, &txns[2..]);
+
+        let recording = Recording {
+            emitter_seed: recorder.emitter_seed,
+            batches: recorder.batches.lock().clone(),
+        };
+        let recording: Recording = bcs::from_bytes(&bcs::to_bytes(&recording).unwrap()).unwrap();
+        assert_eq!(recording.emitter_seed, [7; 32]);
+
+        // The same account, derived from the same seed, starts over on the new network.
+        let mut accounts = vec![LocalAccount::generate(&mut StdRng::from_seed([1; 32]))];
+        assert_eq!(accounts[0].address(), sender.address());
+        let plan = ReplayPlan {
+            txn_factory,
+            start: Instant::now(),
+            batches: recording.batches_for_worker(1),
+        };
+        assert_eq!(plan.batches.len(), 1);
+        let replayed = plan.sign(&plan.batches[0], &mut accounts);
+        assert_eq!(replayed.len(), 2);
+        for (replayed, original) in replayed.iter().zip(&txns) {
+            assert_eq!(replayed.sender(), original.sender());
+            assert_eq!(replayed.payload(), original.payload());
+            assert_eq!(replayed.sequence_number(), original.sequence_number());
+        }
+        assert_eq!(accounts[0].sequence_number(), 2);
+    }
+}
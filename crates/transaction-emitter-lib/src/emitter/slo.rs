@@ -0,0 +1,447 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Latency SLOs, e.g. p99 under 2s, tracked over a sliding window while the job runs.
+//!
+//! Unlike the success criteria of a phase, which judge its averages since the warmup, SLOs look
+//! at the last window only, so that short latency spikes show up rather than being averaged
+//! away. Each interval an SLO is violated for is logged when it starts, along with a snapshot of
+//! the metrics of the nodes, if their URLs are given, to correlate the spike with what the nodes
+//! were doing, and again when it ends. The intervals are summarized at the end of the job.
+
+use crate::emitter::stats::{DynamicStatsTracking, TxnStats};
+use anyhow::{bail, format_err, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::{info, warn};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use url::Url;
+
+/// Node metrics snapshotted when an SLO starts being violated, unless configured otherwise.
+pub const DEFAULT_NODE_METRICS: &[&str] = &[
+    "aptos_core_mempool_index_size",
+    "aptos_consensus_current_round",
+    "aptos_consensus_last_committed_round",
+    "aptos_state_sync_version",
+];
+
+/// A latency percentile and the latency it must stay under, e.g. `p99<2s` or `p99.9<1500ms`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencySlo {
+    /// Percentile, between 0 and 100, with at most one decimal.
+    pub percentile: f64,
+    pub max_latency: Duration,
+}
+
+impl LatencySlo {
+    /// The latency at the percentile, in milliseconds.
+    fn observed_ms(&self, stats: &TxnStats) -> u64 {
+        let permille = (self.percentile * 10.0).round() as u64;
+        stats.latency_buckets.percentile(permille, 1000)
+    }
+
+    fn is_violated(&self, stats: &TxnStats) -> Option<u64> {
+        let observed_ms = self.observed_ms(stats);
+        (observed_ms > self.max_latency.as_millis() as u64).then_some(observed_ms)
+    }
+}
+
+impl FromStr for LatencySlo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || format_err!("Invalid latency SLO {}, expected e.g. p99<2s", s);
+        let (percentile, max_latency) = s
+            .trim()
+            .strip_prefix('p')
+            .and_then(|rest| rest.split_once('<'))
+            .ok_or_else(invalid)?;
+        let percentile: f64 = percentile.trim().parse().map_err(|_| invalid())?;
+        if percentile.is_nan() || percentile <= 0.0 || percentile > 100.0 {
+            bail!("Percentile of latency SLO {} must be in (0, 100]", s);
+        }
+        let max_latency = max_latency.trim();
+        let max_latency = if let Some(millis) = max_latency.strip_suffix("ms") {
+            Duration::from_millis(millis.trim().parse().map_err(|_| invalid())?)
+        } else if let Some(secs) = max_latency.strip_suffix('s') {
+            let secs: f64 = secs.trim().parse().map_err(|_| invalid())?;
+            if !secs.is_finite() || secs < 0.0 {
+                return Err(invalid());
+            }
+            Duration::from_secs_f64(secs)
+        } else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            percentile,
+            max_latency,
+        })
+    }
+}
+
+impl fmt::Display for LatencySlo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p{} < {} ms",
+            self.percentile,
+            self.max_latency.as_millis()
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SloConfig {
+    pub slos: Vec<LatencySlo>,
+    /// The SLOs are evaluated on the transactions that finished in the last window.
+    pub window: Duration,
+    /// How often the SLOs are evaluated.
+    pub check_interval: Duration,
+    /// Windows with fewer latency samples than this are ignored.
+    pub min_samples: u64,
+    /// Prometheus endpoints of the nodes, e.g. http://node:9101/metrics, snapshotted when an SLO
+    /// starts being violated.
+    pub node_metrics_urls: Vec<Url>,
+    /// Names of the metrics kept in the snapshots, with all their labels.
+    pub node_metrics: Vec<String>,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            slos: vec![],
+            window: Duration::from_secs(60),
+            check_interval: Duration::from_secs(10),
+            min_samples: 10,
+            node_metrics_urls: vec![],
+            node_metrics: DEFAULT_NODE_METRICS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The metrics of a node at some point, or why they couldn't be fetched.
+#[derive(Clone, Debug)]
+pub struct NodeMetricsSnapshot {
+    pub url: Url,
+    /// Metric, with its labels, and its value.
+    pub metrics: Result<Vec<(String, f64)>, String>,
+}
+
+impl fmt::Display for NodeMetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.metrics {
+            Ok(metrics) => {
+                let metrics: Vec<_> = metrics
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                write!(f, "{}: {}", self.url, metrics.join(", "))
+            },
+            Err(e) => write!(f, "{}: failed to fetch: {}", self.url, e),
+        }
+    }
+}
+
+/// The samples of the metrics with the names, out of a Prometheus text exposition.
+fn parse_metrics(text: &str, names: &[String]) -> Vec<(String, f64)> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (metric, value) = line.trim().rsplit_once(' ')?;
+            let name = metric.split('{').next()?;
+            if !names.iter().any(|wanted| wanted == name) {
+                return None;
+            }
+            Some((metric.to_string(), value.parse().ok()?))
+        })
+        .collect()
+}
+
+/// An interval during which an SLO was violated.
+#[derive(Clone, Debug)]
+pub struct SloViolation {
+    pub slo: LatencySlo,
+    pub phase: usize,
+    /// Time since the start of the job.
+    pub start: Duration,
+    /// When the SLO was met again, or its phase or the job ended. `None` while ongoing.
+    pub end: Option<Duration>,
+    /// Highest latency at the percentile over the interval, in milliseconds.
+    pub worst_latency_ms: u64,
+    /// Metrics of the nodes when the violation started.
+    pub node_metrics: Vec<NodeMetricsSnapshot>,
+}
+
+impl fmt::Display for SloViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} violated in phase {} from {}s",
+            self.slo,
+            self.phase,
+            self.start.as_secs()
+        )?;
+        match self.end {
+            Some(end) => write!(f, " to {}s", end.as_secs())?,
+            None => write!(f, " to the end")?,
+        }
+        write!(f, ", worst {} ms", self.worst_latency_ms)?;
+        for snapshot in &self.node_metrics {
+            write!(f, "\n    {}", snapshot)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct SloMonitor {
+    config: SloConfig,
+    violations: Mutex<Vec<SloViolation>>,
+    /// Index in `violations` of the ongoing violation of each SLO, by index of the SLO.
+    ongoing: Mutex<HashMap<usize, usize>>,
+}
+
+impl SloMonitor {
+    pub fn new(config: SloConfig) -> Self {
+        Self {
+            config,
+            violations: Mutex::new(vec![]),
+            ongoing: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn violations(&self) -> Vec<SloViolation> {
+        self.violations.lock().clone()
+    }
+
+    /// Evaluates the SLOs on the last window until `stop` is set.
+    pub async fn run(
+        self: Arc<Self>,
+        stats: Arc<DynamicStatsTracking>,
+        stop: Arc<AtomicBool>,
+        job_start: Instant,
+    ) {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let mut phase = stats.get_cur_phase();
+        // Stats of the phase over the window, the oldest first.
+        let mut samples: VecDeque<TxnStats> = VecDeque::new();
+        let mut next_check = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if Instant::now() < next_check {
+                continue;
+            }
+            next_check += self.config.check_interval;
+
+            let at = job_start.elapsed();
+            if stats.get_cur_phase() != phase {
+                // Counters restart with each phase.
+                phase = stats.get_cur_phase();
+                samples.clear();
+                self.end_ongoing(at);
+            }
+            samples.push_back(stats.get_cur().accumulate(at));
+            while samples.len() > 2 && at - samples[1].lasted >= self.config.window {
+                samples.pop_front();
+            }
+            if samples.len() < 2 {
+                continue;
+            }
+            let window = &samples[samples.len() - 1] - &samples[0];
+            for index in self.update(at, phase, &window) {
+                let node_metrics = self.snapshot_node_metrics(&client).await;
+                let mut violations = self.violations.lock();
+                violations[index].node_metrics = node_metrics;
+                warn!("Latency SLO violated: {}", violations[index]);
+            }
+        }
+        self.end_ongoing(job_start.elapsed());
+    }
+
+    /// Updates the violations with the stats of the last window, returning the indices of those
+    /// that just started.
+    fn update(&self, at: Duration, phase: usize, window: &TxnStats) -> Vec<usize> {
+        if window.latency_samples < self.config.min_samples {
+            return vec![];
+        }
+        let mut violations = self.violations.lock();
+        let mut ongoing = self.ongoing.lock();
+        let mut started = vec![];
+        for (slo_index, slo) in self.config.slos.iter().enumerate() {
+            match (slo.is_violated(window), ongoing.get(&slo_index)) {
+                (Some(observed_ms), Some(&index)) => {
+                    let violation = &mut violations[index];
+                    violation.worst_latency_ms = violation.worst_latency_ms.max(observed_ms);
+                },
+                (Some(observed_ms), None) => {
+                    ongoing.insert(slo_index, violations.len());
+                    started.push(violations.len());
+                    violations.push(SloViolation {
+                        slo: slo.clone(),
+                        phase,
+                        start: at,
+                        end: None,
+                        worst_latency_ms: observed_ms,
+                        node_metrics: vec![],
+                    });
+                },
+                (None, Some(&index)) => {
+                    ongoing.remove(&slo_index);
+                    violations[index].end = Some(at);
+                    info!("Latency SLO met again: {}", violations[index]);
+                },
+                (None, None) => (),
+            }
+        }
+        started
+    }
+
+    fn end_ongoing(&self, at: Duration) {
+        let mut violations = self.violations.lock();
+        for (_, index) in self.ongoing.lock().drain() {
+            violations[index].end = Some(at);
+        }
+    }
+
+    async fn snapshot_node_metrics(&self, client: &reqwest::Client) -> Vec<NodeMetricsSnapshot> {
+        let snapshots = self.config.node_metrics_urls.iter().map(|url| async move {
+            let metrics = async {
+                let text = client
+                    .get(url.clone())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                Ok::<_, reqwest::Error>(parse_metrics(&text, &self.config.node_metrics))
+            }
+            .await
+            .map_err(|e| e.to_string());
+            NodeMetricsSnapshot {
+                url: url.clone(),
+                metrics,
+            }
+        });
+        futures::future::join_all(snapshots).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_metrics, LatencySlo, SloConfig, SloMonitor};
+    use crate::emitter::stats::{AtomicHistogramAccumulator, TxnStats};
+    use std::time::Duration;
+
+    fn window(latencies_ms: &[(u64, u64)]) -> TxnStats {
+        let histogram = AtomicHistogramAccumulator::default();
+        let mut samples = 0;
+        for (latency_ms, count) in latencies_ms {
+            histogram.record_data_point(*latency_ms, *count);
+            samples += count;
+        }
+        TxnStats {
+            latency_samples: samples,
+            latency_buckets: histogram.snapshot(),
+            ..TxnStats::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_slo() {
+        assert_eq!("p99<2s".parse::<LatencySlo>().unwrap(), LatencySlo {
+            percentile: 99.0,
+            max_latency: Duration::from_secs(2),
+        });
+        let slo: LatencySlo = "p99.9 < 1500ms".parse().unwrap();
+        assert_eq!(slo.percentile, 99.9);
+        assert_eq!(slo.max_latency, Duration::from_millis(1500));
+        assert_eq!(slo.to_string(), "p99.9 < 1500 ms");
+        for invalid in ["99<2s", "p99", "p0<1s", "p101<1s", "p99<2", "p99<fast"] {
+            assert!(invalid.parse::<LatencySlo>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_violation_intervals() {
+        let monitor = SloMonitor::new(SloConfig {
+            slos: vec!["p99<2s".parse().unwrap(), "p50<1s".parse().unwrap()],
+            ..SloConfig::default()
+        });
+        let at = Duration::from_secs;
+
+        assert!(monitor.update(at(10), 0, &window(&[(500, 100)])).is_empty());
+        // 5% of the transactions at 3s violate p99, not p50.
+        assert_eq!(
+            monitor.update(at(20), 0, &window(&[(500, 95), (3000, 5)])),
+            vec![0]
+        );
+        // Worse, and p50 is violated too.
+        assert_eq!(
+            monitor.update(at(30), 0, &window(&[(1500, 50), (5000, 50)])),
+            vec![1]
+        );
+        // Too few samples to judge, the violations go on.
+        assert!(monitor.update(at(40), 0, &window(&[(100, 5)])).is_empty());
+        assert!(monitor.update(at(50), 0, &window(&[(500, 100)])).is_empty());
+
+        let violations = monitor.violations();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            (violations[0].start, violations[0].end),
+            (at(20), Some(at(50)))
+        );
+        assert_eq!(violations[0].worst_latency_ms, 5000);
+        assert_eq!(
+            (violations[1].start, violations[1].end),
+            (at(30), Some(at(50)))
+        );
+
+        // A new interval when violated again.
+        assert_eq!(monitor.update(at(60), 0, &window(&[(3000, 100)])), vec![
+            2, 3
+        ]);
+        monitor.end_ongoing(at(65));
+        assert_eq!(monitor.violations()[2].end, Some(at(65)));
+    }
+
+    #[test]
+    fn test_parse_metrics() {
+        let text = "\
+# HELP aptos_core_mempool_index_size Index size
+# TYPE aptos_core_mempool_index_size gauge
+aptos_core_mempool_index_size{index=\"system_ttl\"} 1234
+aptos_core_mempool_index_size{index=\"timeline\"} 56
+aptos_consensus_current_round 42
+aptos_other_metric 7
+";
+        let names = vec![
+            "aptos_core_mempool_index_size".to_string(),
+            "aptos_consensus_current_round".to_string(),
+        ];
+        assert_eq!(parse_metrics(text, &names), vec![
+            (
+                "aptos_core_mempool_index_size{index=\"system_ttl\"}".to_string(),
+                1234.0
+            ),
+            (
+                "aptos_core_mempool_index_size{index=\"timeline\"}".to_string(),
+                56.0
+            ),
+            ("aptos_consensus_current_round".to_string(), 42.0),
+        ]);
+    }
+}
@@ -17,6 +17,8 @@ pub struct TxnStats {
     pub committed: u64,
     pub expired: u64,
     pub failed_submission: u64,
+    /// Not accepted because every target was rate limiting.
+    pub throttled: u64,
     pub latency: u64,
     pub latency_samples: u64,
     pub latency_buckets: AtomicHistogramSnapshot,
@@ -29,6 +31,7 @@ pub struct TxnStatsRate {
     pub committed: u64,
     pub expired: u64,
     pub failed_submission: u64,
+    pub throttled: u64,
     pub latency: u64,
     pub latency_samples: u64,
     pub p50_latency: u64,
@@ -40,8 +43,8 @@ impl fmt::Display for TxnStatsRate {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "submitted: {} txn/s, committed: {} txn/s, expired: {} txn/s, failed submission: {} tnx/s, latency: {} ms, (p50: {} ms, p90: {} ms, p99: {} ms), latency samples: {}",
-            self.submitted, self.committed, self.expired, self.failed_submission, self.latency, self.p50_latency, self.p90_latency, self.p99_latency, self.latency_samples,
+            "submitted: {} txn/s, committed: {} txn/s, expired: {} txn/s, failed submission: {} tnx/s, throttled: {} txn/s, latency: {} ms, (p50: {} ms, p90: {} ms, p99: {} ms), latency samples: {}",
+            self.submitted, self.committed, self.expired, self.failed_submission, self.throttled, self.latency, self.p50_latency, self.p90_latency, self.p99_latency, self.latency_samples,
         )
     }
 }
@@ -57,6 +60,7 @@ impl TxnStats {
             committed: self.committed / window_secs,
             expired: self.expired / window_secs,
             failed_submission: self.failed_submission / window_secs,
+            throttled: self.throttled / window_secs,
             latency: if self.latency_samples == 0 {
                 0u64
             } else {
@@ -74,8 +78,8 @@ impl fmt::Display for TxnStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "submitted: {}, committed: {}, expired: {}, failed submission: {}",
-            self.submitted, self.committed, self.expired, self.failed_submission,
+            "submitted: {}, committed: {}, expired: {}, failed submission: {}, throttled: {}",
+            self.submitted, self.committed, self.expired, self.failed_submission, self.throttled,
         )
     }
 }
@@ -89,6 +93,7 @@ impl Sub for &TxnStats {
             committed: self.committed - other.committed,
             expired: self.expired - other.expired,
             failed_submission: self.failed_submission - other.failed_submission,
+            throttled: self.throttled - other.throttled,
             latency: self.latency - other.latency,
             latency_samples: self.latency_samples - other.latency_samples,
             latency_buckets: &self.latency_buckets - &other.latency_buckets,
@@ -106,6 +111,7 @@ impl Add for &TxnStats {
             committed: self.committed + other.committed,
             expired: self.expired + other.expired,
             failed_submission: self.failed_submission + other.failed_submission,
+            throttled: self.throttled + other.throttled,
             latency: self.latency + other.latency,
             latency_samples: self.latency_samples + other.latency_samples,
             latency_buckets: &self.latency_buckets + &other.latency_buckets,
@@ -120,6 +126,7 @@ pub struct StatsAccumulator {
     pub committed: AtomicU64,
     pub expired: AtomicU64,
     pub failed_submission: AtomicU64,
+    pub throttled: AtomicU64,
     pub latency: AtomicU64,
     pub latency_samples: AtomicU64,
     pub latencies: Arc<AtomicHistogramAccumulator>,
@@ -132,12 +139,29 @@ impl StatsAccumulator {
             committed: self.committed.load(Ordering::Relaxed),
             expired: self.expired.load(Ordering::Relaxed),
             failed_submission: self.failed_submission.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
             latency: self.latency.load(Ordering::Relaxed),
             latency_samples: self.latency_samples.load(Ordering::Relaxed),
             latency_buckets: self.latencies.snapshot(),
             lasted,
         }
     }
+
+    /// Adds what was recorded here to `other`.
+    pub fn add_to(&self, other: &StatsAccumulator) {
+        for (from, to) in [
+            (&self.submitted, &other.submitted),
+            (&self.committed, &other.committed),
+            (&self.expired, &other.expired),
+            (&self.failed_submission, &other.failed_submission),
+            (&self.throttled, &other.throttled),
+            (&self.latency, &other.latency),
+            (&self.latency_samples, &other.latency_samples),
+        ] {
+            to.fetch_add(from.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.latencies.add_to(&other.latencies);
+    }
 }
 
 // have more slots than generally used txn expiration. (240s)
@@ -195,6 +219,17 @@ impl AtomicHistogramAccumulator {
         let bucket_num = self.get_bucket_num(data_value);
         self.buckets[bucket_num].fetch_add(data_num, Ordering::Relaxed);
     }
+
+    /// Adds the data points recorded here to `other`, which must have the same buckets.
+    pub fn add_to(&self, other: &AtomicHistogramAccumulator) {
+        assert_eq!(self.step_width, other.step_width);
+        for (from, to) in self.buckets.iter().zip(&other.buckets) {
+            let data_num = from.load(Ordering::Relaxed);
+            if data_num > 0 {
+                to.fetch_add(data_num, Ordering::Relaxed);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -304,6 +339,10 @@ impl DynamicStatsTracking {
         self.stats.get(self.get_cur_phase()).unwrap()
     }
 
+    pub fn get_phase(&self, phase: usize) -> &StatsAccumulator {
+        self.stats.get(phase).unwrap()
+    }
+
     pub fn accumulate(&self, phase_starts: &[Instant]) -> Vec<TxnStats> {
         let now = Instant::now();
         self.stats
@@ -394,6 +433,7 @@ mod test {
             committed: 10,
             expired: 0,
             failed_submission: 0,
+            throttled: 0,
             latency: 0,
             latency_samples: 0,
             latency_buckets: histogram.snapshot(),
@@ -3,17 +3,29 @@
 
 use crate::{
     emitter::{
+        backpressure::BackpressureController,
+        control::EmitterControl,
+        gas::GasAccounting,
+        recording::{ReplayPlan, TransactionRecorder},
         stats::{DynamicStatsTracking, StatsAccumulator},
+        throttling::TargetThrottling,
         update_seq_num_and_get_num_expired, wait_for_accounts_sequence,
     },
     transaction_generator::TransactionGenerator,
     EmitModeParams,
 };
 use aptos_logger::{sample, sample::SampleRate, warn};
-use aptos_rest_client::Client as RestClient;
+use aptos_rest_client::{
+    aptos_api_types::TransactionsBatchSubmissionResult, error::RestError, Client as RestClient,
+    Response,
+};
 use aptos_sdk::{
     move_types::account_address::AccountAddress,
-    types::{transaction::SignedTransaction, vm_status::StatusCode, LocalAccount},
+    types::{
+        transaction::{SignedTransaction, Transaction},
+        vm_status::StatusCode,
+        LocalAccount,
+    },
 };
 use core::{
     cmp::{max, min},
@@ -41,6 +53,21 @@ pub struct SubmissionWorker {
     start_sleep_duration: Duration,
     skip_latency_stats: bool,
     rng: ::rand::rngs::StdRng,
+    /// Index of the worker and where to record the generated transactions.
+    recorder: Option<(usize, Arc<TransactionRecorder>)>,
+    /// Recorded transactions to submit instead of generating them.
+    replay: Option<ReplayPlan>,
+    /// Index of the worker and what decides whether it's paused to shed load.
+    backpressure: Option<(usize, Arc<BackpressureController>)>,
+    /// Index of the worker and the control API, which may pause it too.
+    control: Option<(usize, Arc<EmitterControl>)>,
+    /// Where to record the gas spent by the committed transactions, if reported on.
+    gas_accounting: Option<Arc<GasAccounting>>,
+    /// Index of the target of the worker and the throttling of all targets, to submit to another
+    /// target while its own is rate limiting.
+    throttling: Option<(usize, Arc<TargetThrottling>)>,
+    /// Stats of the tenant of the worker, if the job has tenants, see `tenants`.
+    tenant_stats: Option<Arc<DynamicStatsTracking>>,
 }
 
 impl SubmissionWorker {
@@ -65,9 +92,61 @@ impl SubmissionWorker {
             start_sleep_duration,
             skip_latency_stats,
             rng,
+            recorder: None,
+            replay: None,
+            backpressure: None,
+            control: None,
+            gas_accounting: None,
+            throttling: None,
+            tenant_stats: None,
         }
     }
 
+    pub fn with_recorder(
+        mut self,
+        worker_index: usize,
+        recorder: Arc<TransactionRecorder>,
+    ) -> Self {
+        self.recorder = Some((worker_index, recorder));
+        self
+    }
+
+    pub fn with_backpressure(
+        mut self,
+        worker_index: usize,
+        backpressure: Arc<BackpressureController>,
+    ) -> Self {
+        self.backpressure = Some((worker_index, backpressure));
+        self
+    }
+
+    pub fn with_control(mut self, worker_index: usize, control: Arc<EmitterControl>) -> Self {
+        self.control = Some((worker_index, control));
+        self
+    }
+
+    pub fn with_gas_accounting(mut self, gas_accounting: Arc<GasAccounting>) -> Self {
+        self.gas_accounting = Some(gas_accounting);
+        self
+    }
+
+    pub fn with_replay(mut self, replay: ReplayPlan) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    pub fn with_throttling(mut self, target: usize, throttling: Arc<TargetThrottling>) -> Self {
+        self.throttling = Some((target, throttling));
+        self
+    }
+
+    /// Records the stats of each batch to the stats of the tenant of the worker too, once done
+    /// with the batch.
+    pub fn with_tenant_stats(mut self, tenant_stats: Arc<DynamicStatsTracking>) -> Self {
+        self.tenant_stats = Some(tenant_stats);
+        self
+    }
+
     #[allow(clippy::collapsible_if)]
     pub(crate) async fn run(mut self) -> Vec<LocalAccount> {
         let start_time = Instant::now() + self.start_sleep_duration;
@@ -78,8 +157,12 @@ impl SubmissionWorker {
         let mut wait_until = start_time;
 
         while !self.stop.load(Ordering::Relaxed) {
+            if self.replay.is_some() && !self.wait_for_next_replayed_batch().await {
+                break;
+            }
             let stats_clone = self.stats.clone();
             let loop_stats = stats_clone.get_cur();
+            let loop_phase = stats_clone.get_cur_phase();
 
             let loop_start_time = Arc::new(Instant::now());
             if wait_duration.as_secs() > 0
@@ -97,7 +180,21 @@ impl SubmissionWorker {
             // always add expected cycle duration, to not drift from expected pace.
             wait_until += wait_duration;
 
+            if !self.is_active() {
+                // Keep the pace, so the worker resumes in its slot.
+                let now = Instant::now();
+                self.sleep_check_done(max(
+                    wait_until.saturating_duration_since(now),
+                    Duration::from_secs(1),
+                ))
+                .await;
+                continue;
+            }
+
             let requests = self.gen_requests();
+            if requests.is_empty() {
+                continue;
+            }
 
             let mut account_to_start_and_end_seq_num = HashMap::new();
             for req in requests.iter() {
@@ -121,7 +218,18 @@ impl SubmissionWorker {
                 .max()
                 .unwrap_or(0);
 
+            // The stats of the batch are kept apart to add them to the stats of the tenant too.
+            let tenant_batch_stats = self
+                .tenant_stats
+                .as_ref()
+                .map(|_| StatsAccumulator::default());
+            let batch_stats = tenant_batch_stats.as_ref().unwrap_or(loop_stats);
+
             let txn_offset_time = Arc::new(AtomicU64::new(0));
+            let gas_accounting_ranges = self
+                .gas_accounting
+                .is_some()
+                .then(|| account_to_start_and_end_seq_num.clone());
 
             join_all(
                 requests
@@ -132,7 +240,10 @@ impl SubmissionWorker {
                             reqs,
                             loop_start_time.clone(),
                             txn_offset_time.clone(),
-                            loop_stats,
+                            batch_stats,
+                            self.throttling
+                                .as_ref()
+                                .map(|(target, throttling)| (*target, throttling.as_ref())),
                         )
                     }),
             )
@@ -163,12 +274,24 @@ impl SubmissionWorker {
                     if self.skip_latency_stats { 10 } else { 1 }
                         * self.params.check_account_sequence_sleep_millis,
                 ),
-                loop_stats,
+                batch_stats,
             )
             .await;
 
+            if let (Some(tenant_stats), Some(batch_stats)) =
+                (&self.tenant_stats, &tenant_batch_stats)
+            {
+                batch_stats.add_to(loop_stats);
+                batch_stats.add_to(tenant_stats.get_phase(loop_phase));
+            }
+
+            if let Some(ranges) = gas_accounting_ranges {
+                self.record_gas(loop_phase, &ranges).await;
+            }
+
+            // When replaying, the recorded offsets set the pace.
             let now = Instant::now();
-            if wait_until > now {
+            if wait_until > now && self.replay.is_none() {
                 self.sleep_check_done(wait_until - now).await;
             }
         }
@@ -176,6 +299,45 @@ impl SubmissionWorker {
         self.accounts
     }
 
+    /// Sleeps until the next recorded batch is due. Returns false if there is nothing left to
+    /// replay or the job was stopped.
+    async fn wait_for_next_replayed_batch(&self) -> bool {
+        let due = match self.replay.as_ref().and_then(|replay| {
+            replay
+                .batches
+                .front()
+                .map(|batch| replay.start + Duration::from_millis(batch.offset_millis))
+        }) {
+            Some(due) => due,
+            None => return false,
+        };
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= due {
+                return true;
+            }
+            sleep(min(due - now, Duration::from_secs(1))).await;
+        }
+    }
+
+    /// Whether neither backpressure nor the control API paused the worker.
+    fn is_active(&self) -> bool {
+        self.backpressure
+            .as_ref()
+            .map_or(true, |(worker_index, backpressure)| {
+                backpressure.is_worker_active(*worker_index)
+            })
+            && self
+                .control
+                .as_ref()
+                .map_or(true, |(worker_index, control)| {
+                    control.is_worker_active(*worker_index)
+                })
+    }
+
     // returns true if it returned early
     async fn sleep_check_done(&self, duration: Duration) {
         let start_time = Instant::now();
@@ -263,7 +425,63 @@ impl SubmissionWorker {
         }
     }
 
+    /// Records the gas spent by the transactions of the batch that committed, fetching them back
+    /// from the account transactions of the senders.
+    async fn record_gas(&self, phase: usize, ranges: &HashMap<AccountAddress, (u64, u64)>) {
+        let gas_accounting = match &self.gas_accounting {
+            Some(gas_accounting) => gas_accounting,
+            None => return,
+        };
+        let committed_up_to: HashMap<_, _> = self
+            .accounts
+            .iter()
+            .map(|account| (account.address(), account.sequence_number()))
+            .collect();
+        join_all(ranges.iter().map(|(address, (start, end))| {
+            let end = min(*end, committed_up_to.get(address).copied().unwrap_or(*start));
+            async move {
+                if end <= *start {
+                    return;
+                }
+                match self
+                    .client
+                    .get_account_transactions_bcs(*address, Some(*start), Some((end - start) as u16))
+                    .await
+                {
+                    Ok(txns) => {
+                        for txn in txns.into_inner() {
+                            if let Transaction::UserTransaction(signed) = &txn.transaction {
+                                gas_accounting.record(
+                                    phase,
+                                    signed,
+                                    txn.info.gas_used(),
+                                    txn.info.status().is_success(),
+                                );
+                            }
+                        }
+                    },
+                    Err(e) => sample!(
+                        SampleRate::Duration(Duration::from_secs(60)),
+                        warn!(
+                            "[{:?}] Failed to fetch committed transactions for the gas report: {:?}",
+                            self.client.path_prefix_string(),
+                            e
+                        )
+                    ),
+                }
+            }
+        }))
+        .await;
+    }
+
     fn gen_requests(&mut self) -> Vec<SignedTransaction> {
+        if let Some(replay) = self.replay.as_mut() {
+            return match replay.batches.pop_front() {
+                Some(batch) => replay.sign(&batch, &mut self.accounts),
+                None => vec![],
+            };
+        }
+
         let batch_size = max(
             1,
             min(
@@ -275,8 +493,69 @@ impl SubmissionWorker {
             .accounts
             .iter_mut()
             .choose_multiple(&mut self.rng, batch_size);
-        self.txn_generator
-            .generate_transactions(accounts, self.params.transactions_per_account)
+        let requests = self
+            .txn_generator
+            .generate_transactions(accounts, self.params.transactions_per_account);
+        if let Some((worker_index, recorder)) = &self.recorder {
+            recorder.record(*worker_index, &requests);
+        }
+        requests
+    }
+}
+
+type BatchSubmissionResult = Result<Response<TransactionsBatchSubmissionResult>, RestError>;
+
+/// Longest a submission waits for a throttled target, so that it's not attempted long after the
+/// transactions were generated.
+const MAX_THROTTLED_WAIT: Duration = Duration::from_secs(10);
+
+/// Submits the transactions to `client`, or, given the throttling of the targets, to the target
+/// of the worker unless it's rate limiting, in which case to another one, waiting for one to be
+/// available if all are. Transactions that every target refused that way are counted as
+/// throttled rather than as failed.
+/// Submits to the target `preferred`, or to another one while it's throttled, trying each target
+/// at most once more. Returns `None` if all were throttled for longer than is worth waiting.
+async fn submit_avoiding_throttled<'a>(
+    txns: &[SignedTransaction],
+    preferred: usize,
+    throttling: &'a TargetThrottling,
+) -> Option<(&'a RestClient, BatchSubmissionResult)> {
+    let mut attempts_left = throttling.num_targets();
+    loop {
+        let target = match throttling.pick(preferred) {
+            Ok(target) => target,
+            Err(wait) if attempts_left > 0 && wait <= MAX_THROTTLED_WAIT => {
+                attempts_left -= 1;
+                sleep(wait).await;
+                continue;
+            },
+            Err(_) => return None,
+        };
+        let client = throttling.client(target);
+        let result = client.submit_batch_bcs(txns).await;
+        match &result {
+            Err(e) if e.is_throttled() => {
+                let backoff = throttling.record_throttled(target, e.retry_after(), txns.len());
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(60)),
+                    warn!(
+                        "[{:?}] Throttled, avoiding it for {:?}: {:?}",
+                        client.path_prefix_string(),
+                        backoff,
+                        e
+                    )
+                );
+                if attempts_left == 0 {
+                    return Some((client, result));
+                }
+                attempts_left -= 1;
+            },
+            Ok(_) => {
+                throttling.record_accepted(preferred, target, txns.len());
+                return Some((client, result));
+            },
+            Err(_) => return Some((client, result)),
+        }
     }
 }
 
@@ -286,6 +565,7 @@ pub async fn submit_transactions(
     loop_start_time: Arc<Instant>,
     txn_offset_time: Arc<AtomicU64>,
     stats: &StatsAccumulator,
+    throttling: Option<(usize, &TargetThrottling)>,
 ) {
     let cur_time = Instant::now();
     let offset = cur_time - *loop_start_time;
@@ -297,7 +577,35 @@ pub async fn submit_transactions(
         .submitted
         .fetch_add(txns.len() as u64, Ordering::Relaxed);
 
-    match client.submit_batch_bcs(txns).await {
+    let (client, result) = match throttling {
+        Some((preferred, throttling)) => {
+            match submit_avoiding_throttled(txns, preferred, throttling).await {
+                Some(submitted) => submitted,
+                None => {
+                    stats
+                        .throttled
+                        .fetch_add(txns.len() as u64, Ordering::Relaxed);
+                    return;
+                },
+            }
+        },
+        None => (client, client.submit_batch_bcs(txns).await),
+    };
+
+    match result {
+        Err(e) if e.is_throttled() => {
+            stats
+                .throttled
+                .fetch_add(txns.len() as u64, Ordering::Relaxed);
+            sample!(
+                SampleRate::Duration(Duration::from_secs(60)),
+                warn!(
+                    "[{:?}] Batch request throttled: {:?}",
+                    client.path_prefix_string(),
+                    e
+                )
+            );
+        },
         Err(e) => {
             stats
                 .failed_submission
@@ -0,0 +1,244 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Success criteria of the phases of a job, and aborting it early when one can't be met.
+//!
+//! A phase is judged on what it achieved since its warmup ended, re-evaluated at every check.
+//! The averages being cumulative, a phase failing several checks in a row is very unlikely to
+//! recover, and the job is aborted with a report rather than left running for its full duration.
+
+use crate::emitter::stats::{TxnStats, TxnStatsRate};
+use std::{fmt, time::Duration};
+
+#[derive(Clone, Debug)]
+pub struct PhaseSuccessCriteria {
+    /// Minimum committed transactions per second.
+    pub min_tps: Option<u64>,
+    /// Maximum p99 latency, in milliseconds.
+    pub max_p99_latency_ms: Option<u64>,
+    /// Maximum percentage of transactions expiring, among those that finished.
+    pub max_expired_percent: Option<f64>,
+    /// Time the phase is given to ramp up before it's judged.
+    pub warmup: Duration,
+    /// How often the phase is judged.
+    pub check_interval: Duration,
+    /// The job is aborted once this many checks in a row fail.
+    pub abort_after_failed_checks: usize,
+}
+
+impl Default for PhaseSuccessCriteria {
+    fn default() -> Self {
+        Self {
+            min_tps: None,
+            max_p99_latency_ms: None,
+            max_expired_percent: None,
+            warmup: Duration::from_secs(60),
+            check_interval: Duration::from_secs(30),
+            abort_after_failed_checks: 3,
+        }
+    }
+}
+
+impl PhaseSuccessCriteria {
+    pub fn is_empty(&self) -> bool {
+        self.min_tps.is_none()
+            && self.max_p99_latency_ms.is_none()
+            && self.max_expired_percent.is_none()
+    }
+
+    /// The criteria the stats don't meet, described.
+    pub fn violations(&self, stats: &TxnStats) -> Vec<String> {
+        let rate = stats.rate();
+        let mut violations = vec![];
+        if let Some(min_tps) = self.min_tps {
+            if rate.committed < min_tps {
+                violations.push(format!(
+                    "committed {} txn/s, less than the {} required",
+                    rate.committed, min_tps
+                ));
+            }
+        }
+        if let Some(max_p99_latency_ms) = self.max_p99_latency_ms {
+            if rate.p99_latency > max_p99_latency_ms {
+                violations.push(format!(
+                    "p99 latency {} ms, more than the {} allowed",
+                    rate.p99_latency, max_p99_latency_ms
+                ));
+            }
+        }
+        if let Some(max_expired_percent) = self.max_expired_percent {
+            let expired_percent = expired_percent(stats);
+            if expired_percent > max_expired_percent {
+                violations.push(format!(
+                    "{:.2}% expired, more than the {}% allowed",
+                    expired_percent, max_expired_percent
+                ));
+            }
+        }
+        violations
+    }
+}
+
+fn expired_percent(stats: &TxnStats) -> f64 {
+    let finished = stats.committed + stats.expired;
+    if finished == 0 {
+        0.0
+    } else {
+        100.0 * stats.expired as f64 / finished as f64
+    }
+}
+
+/// Why a job was aborted: a phase that didn't meet its success criteria.
+#[derive(Clone, Debug)]
+pub struct PhaseFailure {
+    pub phase: usize,
+    /// Time since the start of the phase.
+    pub at: Duration,
+    pub violations: Vec<String>,
+    /// Rate of the phase since its warmup ended.
+    pub rate: TxnStatsRate,
+}
+
+impl fmt::Display for PhaseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "phase {} failed its success criteria after {}s: {}. Rate after warmup: {}",
+            self.phase,
+            self.at.as_secs(),
+            self.violations.join(", "),
+            self.rate,
+        )
+    }
+}
+
+impl std::error::Error for PhaseFailure {}
+
+/// Judges a phase as its stats come in.
+pub(crate) struct PhaseJudge<'a> {
+    criteria: &'a PhaseSuccessCriteria,
+    phase: usize,
+    /// Stats of the phase when the warmup ended.
+    baseline: Option<TxnStats>,
+    failed_checks_in_a_row: usize,
+}
+
+impl<'a> PhaseJudge<'a> {
+    pub fn new(criteria: &'a PhaseSuccessCriteria, phase: usize) -> Self {
+        Self {
+            criteria,
+            phase,
+            baseline: None,
+            failed_checks_in_a_row: 0,
+        }
+    }
+
+    /// Takes the stats of the phase so far, and fails if it should be aborted.
+    pub fn check(&mut self, stats: &TxnStats) -> Result<(), PhaseFailure> {
+        if stats.lasted < self.criteria.warmup {
+            return Ok(());
+        }
+        let baseline = match &self.baseline {
+            Some(baseline) => baseline,
+            None => {
+                self.baseline = Some(stats.clone());
+                return Ok(());
+            },
+        };
+        let judged = stats - baseline;
+        let violations = self.criteria.violations(&judged);
+        if violations.is_empty() {
+            self.failed_checks_in_a_row = 0;
+            return Ok(());
+        }
+        self.failed_checks_in_a_row += 1;
+        if self.failed_checks_in_a_row < self.criteria.abort_after_failed_checks {
+            return Ok(());
+        }
+        Err(PhaseFailure {
+            phase: self.phase,
+            at: stats.lasted,
+            violations,
+            rate: judged.rate(),
+        })
+    }
+
+    /// Takes the stats of the phase at its end, and fails if they don't meet the criteria, however
+    /// many checks failed before.
+    pub fn finish(&self, stats: &TxnStats) -> Result<(), PhaseFailure> {
+        let judged = match &self.baseline {
+            Some(baseline) => stats - baseline,
+            // Shorter than the warmup, judge it all.
+            None => stats.clone(),
+        };
+        let violations = self.criteria.violations(&judged);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(PhaseFailure {
+                phase: self.phase,
+                at: stats.lasted,
+                violations,
+                rate: judged.rate(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhaseJudge, PhaseSuccessCriteria};
+    use crate::emitter::stats::TxnStats;
+    use std::time::Duration;
+
+    fn stats(secs: u64, committed: u64, expired: u64) -> TxnStats {
+        TxnStats {
+            submitted: committed + expired,
+            committed,
+            expired,
+            lasted: Duration::from_secs(secs),
+            ..TxnStats::default()
+        }
+    }
+
+    #[test]
+    fn test_violations() {
+        let criteria = PhaseSuccessCriteria {
+            min_tps: Some(100),
+            max_expired_percent: Some(5.0),
+            ..PhaseSuccessCriteria::default()
+        };
+        assert!(criteria.violations(&stats(10, 1000, 50)).is_empty());
+        assert_eq!(criteria.violations(&stats(10, 990, 10)).len(), 1);
+        assert_eq!(criteria.violations(&stats(10, 900, 100)).len(), 2);
+        assert!(PhaseSuccessCriteria::default()
+            .violations(&stats(10, 0, 0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_judge() {
+        let criteria = PhaseSuccessCriteria {
+            min_tps: Some(100),
+            warmup: Duration::from_secs(60),
+            abort_after_failed_checks: 2,
+            ..PhaseSuccessCriteria::default()
+        };
+        let mut judge = PhaseJudge::new(&criteria, 1);
+        // Slow during the warmup doesn't count.
+        judge.check(&stats(30, 0, 0)).unwrap();
+        judge.check(&stats(60, 10, 0)).unwrap();
+        // 100 txn/s since the warmup.
+        judge.check(&stats(90, 3010, 0)).unwrap();
+        // A single failed check is tolerated.
+        judge.check(&stats(150, 6010, 0)).unwrap();
+        judge.check(&stats(180, 12010, 0)).unwrap();
+        // Nothing committed after, the average falls under for two checks in a row.
+        judge.check(&stats(210, 12010, 0)).unwrap();
+        let failure = judge.check(&stats(240, 12010, 0)).unwrap_err();
+        assert_eq!(failure.phase, 1);
+        assert_eq!(failure.at, Duration::from_secs(240));
+        assert!(judge.finish(&stats(240, 12010, 0)).is_err());
+        assert!(judge.finish(&stats(180, 12010, 0)).is_ok());
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Emitting the load of several tenants at once, e.g. independent dApps sharing the network.
+//!
+//! Each tenant gets its own workers, whose accounts are funded from its own coin source and which
+//! generate its own transaction mix out of its own accounts pool. The workers of the job are split
+//! among the tenants in proportion to their weights, so each gets that share of the TPS (or of the
+//! mempool backlog). The stats of each tenant are tracked on top of the stats of the job. The
+//! workers of a tenant add the stats of a batch once done with it, so while the job runs its stats
+//! lag behind by a batch.
+
+use crate::{
+    emitter::{
+        query_sequence_number,
+        stats::{DynamicStatsTracking, TxnStats},
+    },
+    TransactionType,
+};
+use anyhow::{ensure, format_err, Result};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, PrivateKey, ValidCryptoMaterial};
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::types::{transaction::authenticator::AuthenticationKey, AccountKey, LocalAccount};
+use std::{convert::TryFrom, fmt, sync::Arc, time::Instant};
+
+#[derive(Clone, Debug)]
+pub struct TenantConfig {
+    pub name: String,
+    /// Key of the account funding the accounts of the tenant. The source account of the job funds
+    /// them if not set.
+    pub coin_source_key: Option<Arc<Ed25519PrivateKey>>,
+    /// Mix of each phase, like `EmitJobRequest::transaction_mix_per_phase`, for as many phases.
+    pub transaction_mix_per_phase: Vec<Vec<(TransactionType, usize)>>,
+    /// Share of the load of the tenant, relative to the weights of the other tenants.
+    pub tps_weight: usize,
+}
+
+impl TenantConfig {
+    pub fn new(
+        name: String,
+        transaction_mix_per_phase: Vec<Vec<(TransactionType, usize)>>,
+        tps_weight: usize,
+    ) -> Self {
+        Self {
+            name,
+            coin_source_key: None,
+            transaction_mix_per_phase,
+            tps_weight,
+        }
+    }
+
+    pub fn coin_source_key(mut self, coin_source_key: Ed25519PrivateKey) -> Self {
+        self.coin_source_key = Some(Arc::new(coin_source_key));
+        self
+    }
+
+    /// The account of the coin source key of the tenant, if it has one, at its current sequence
+    /// number.
+    pub(crate) async fn load_coin_source_account(
+        &self,
+        client: &RestClient,
+    ) -> Result<Option<LocalAccount>> {
+        let key = match &self.coin_source_key {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let address = AuthenticationKey::ed25519(&key.public_key()).derived_address();
+        let sequence_number = query_sequence_number(client, address).await.map_err(|e| {
+            format_err!(
+                "Failed to query the coin source account {} of tenant {}: {:?}",
+                address,
+                self.name,
+                e
+            )
+        })?;
+        let key = Ed25519PrivateKey::try_from(key.to_bytes().as_slice())?;
+        Ok(Some(LocalAccount::new(
+            address,
+            AccountKey::from_private_key(key),
+            sequence_number,
+        )))
+    }
+}
+
+/// Splits `num_workers` among the tenants in proportion to their weights, by largest remainder.
+/// Each tenant gets at least one worker.
+pub fn split_workers(tenants: &[TenantConfig], num_workers: usize) -> Result<Vec<usize>> {
+    ensure!(
+        num_workers >= tenants.len(),
+        "{} workers are too few for {} tenants, each needs at least one",
+        num_workers,
+        tenants.len()
+    );
+    ensure!(
+        tenants.iter().all(|tenant| tenant.tps_weight > 0),
+        "Tenants need a weight above 0"
+    );
+    let total_weight: usize = tenants.iter().map(|tenant| tenant.tps_weight).sum();
+    // Each tenant first gets its one worker, then its share of the others.
+    let to_split = num_workers - tenants.len();
+    let mut split: Vec<usize> = tenants
+        .iter()
+        .map(|tenant| 1 + to_split * tenant.tps_weight / total_weight)
+        .collect();
+    let mut by_remainder: Vec<usize> = (0..tenants.len()).collect();
+    by_remainder.sort_by_key(|&index| {
+        std::cmp::Reverse(to_split * tenants[index].tps_weight % total_weight)
+    });
+    let left = num_workers - split.iter().sum::<usize>();
+    for index in by_remainder.into_iter().take(left) {
+        split[index] += 1;
+    }
+    Ok(split)
+}
+
+/// Stats of a tenant, per phase.
+#[derive(Clone, Debug)]
+pub struct TenantStats {
+    pub name: String,
+    pub stats: Vec<TxnStats>,
+}
+
+impl fmt::Display for TenantStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        for (phase, stats) in self.stats.iter().enumerate() {
+            write!(f, "\n    phase {}: {}, {}", phase, stats, stats.rate())?;
+        }
+        Ok(())
+    }
+}
+
+/// The stats of the tenants of a job.
+#[derive(Debug)]
+pub struct TenantsStatsTracking {
+    tenants: Vec<(String, Arc<DynamicStatsTracking>)>,
+}
+
+impl TenantsStatsTracking {
+    pub fn new(tenants: &[TenantConfig], num_phases: usize) -> Self {
+        Self {
+            tenants: tenants
+                .iter()
+                .map(|tenant| {
+                    (
+                        tenant.name.clone(),
+                        Arc::new(DynamicStatsTracking::new(num_phases)),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+
+    pub fn get(&self, tenant: usize) -> &Arc<DynamicStatsTracking> {
+        &self.tenants[tenant].1
+    }
+
+    pub fn start_next_phase(&self) {
+        for (_, stats) in &self.tenants {
+            stats.start_next_phase();
+        }
+    }
+
+    pub fn accumulate(&self, phase_starts: &[Instant]) -> Vec<TenantStats> {
+        self.tenants
+            .iter()
+            .map(|(name, stats)| TenantStats {
+                name: name.clone(),
+                stats: stats.accumulate(phase_starts),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_workers, TenantConfig};
+    use crate::TransactionType;
+
+    fn tenant(tps_weight: usize) -> TenantConfig {
+        TenantConfig::new(
+            format!("weight-{}", tps_weight),
+            vec![vec![(TransactionType::default(), 1)]],
+            tps_weight,
+        )
+    }
+
+    #[test]
+    fn test_split_workers() {
+        let tenants = vec![tenant(1), tenant(1)];
+        assert_eq!(split_workers(&tenants, 4).unwrap(), vec![2, 2]);
+
+        let tenants = vec![tenant(3), tenant(1)];
+        assert_eq!(split_workers(&tenants, 10).unwrap(), vec![7, 3]);
+        assert_eq!(split_workers(&tenants, 2).unwrap(), vec![1, 1]);
+
+        // The smallest tenant still gets a worker.
+        let tenants = vec![tenant(100), tenant(1), tenant(1)];
+        let split = split_workers(&tenants, 8).unwrap();
+        assert_eq!(split, vec![6, 1, 1]);
+        assert_eq!(split.iter().sum::<usize>(), 8);
+
+        assert!(split_workers(&tenants, 2).is_err());
+        assert!(split_workers(&[tenant(0)], 2).is_err());
+    }
+}
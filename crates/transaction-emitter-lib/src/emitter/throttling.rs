@@ -0,0 +1,195 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backing off targets that rate limit the emitter.
+//!
+//! A target answering 429 or 503 is asking for less load, not failing the transactions, so
+//! workers stop submitting to it until its `Retry-After` has passed (or, when it doesn't say, for
+//! a backoff doubling with each throttled response in a row), and submit to the other targets in
+//! the meantime. Throttled submissions are counted apart from failed ones, per target, so that a
+//! run limited by the proxies in front of the nodes isn't mistaken for one limited by the nodes.
+
+use aptos_infallible::Mutex;
+use aptos_rest_client::Client as RestClient;
+use std::{
+    cmp::min,
+    fmt,
+    time::{Duration, Instant},
+};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct TargetState {
+    throttled_until: Option<Instant>,
+    /// Throttled responses since the last accepted submission.
+    consecutive: u32,
+    throttled_responses: u64,
+    throttled_txns: u64,
+    rerouted_txns: u64,
+}
+
+/// How much a target throttled the emitter.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TargetThrottlingStats {
+    pub target: String,
+    pub throttled_responses: u64,
+    pub throttled_txns: u64,
+    /// Transactions submitted to another target while this one was throttled.
+    pub rerouted_txns: u64,
+}
+
+impl fmt::Display for TargetThrottlingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} throttled responses for {} txns, {} txns rerouted",
+            self.target, self.throttled_responses, self.throttled_txns, self.rerouted_txns,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct TargetThrottling {
+    clients: Vec<RestClient>,
+    states: Mutex<Vec<TargetState>>,
+}
+
+impl TargetThrottling {
+    pub fn new(clients: Vec<RestClient>) -> Self {
+        let states = clients.iter().map(|_| TargetState::default()).collect();
+        Self {
+            clients,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub fn num_targets(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn client(&self, target: usize) -> &RestClient {
+        &self.clients[target]
+    }
+
+    /// The target to submit to instead of `preferred`: `preferred` itself unless it's throttled,
+    /// otherwise the next one that isn't, or how long until the first one stops being throttled.
+    pub fn pick(&self, preferred: usize) -> Result<usize, Duration> {
+        let now = Instant::now();
+        let mut states = self.states.lock();
+        let num_targets = states.len();
+        let mut earliest: Option<Instant> = None;
+        for offset in 0..num_targets {
+            let index = (preferred + offset) % num_targets;
+            match states[index].throttled_until {
+                Some(until) if until > now => {
+                    earliest = Some(earliest.map_or(until, |earliest| min(earliest, until)));
+                },
+                _ => {
+                    states[index].throttled_until = None;
+                    return Ok(index);
+                },
+            }
+        }
+        Err(earliest.map_or(Duration::ZERO, |earliest| earliest - now))
+    }
+
+    /// Records that `target` throttled a submission of `num_txns` transactions, returning how long
+    /// it's avoided for.
+    pub fn record_throttled(
+        &self,
+        target: usize,
+        retry_after: Option<Duration>,
+        num_txns: usize,
+    ) -> Duration {
+        let mut states = self.states.lock();
+        let state = &mut states[target];
+        let backoff = retry_after.unwrap_or_else(|| {
+            min(
+                MIN_BACKOFF * 2u32.saturating_pow(min(state.consecutive, 16)),
+                MAX_BACKOFF,
+            )
+        });
+        state.consecutive += 1;
+        state.throttled_responses += 1;
+        state.throttled_txns += num_txns as u64;
+        let until = Instant::now() + backoff;
+        state.throttled_until = Some(state.throttled_until.map_or(until, |cur| cur.max(until)));
+        backoff
+    }
+
+    /// Records that `target` accepted a submission, on behalf of `preferred`.
+    pub fn record_accepted(&self, preferred: usize, target: usize, num_txns: usize) {
+        let mut states = self.states.lock();
+        states[target].consecutive = 0;
+        if preferred != target {
+            states[preferred].rerouted_txns += num_txns as u64;
+        }
+    }
+
+    pub fn stats(&self) -> Vec<TargetThrottlingStats> {
+        self.states
+            .lock()
+            .iter()
+            .zip(&self.clients)
+            .map(|(state, client)| TargetThrottlingStats {
+                target: client.path_prefix_string(),
+                throttled_responses: state.throttled_responses,
+                throttled_txns: state.throttled_txns,
+                rerouted_txns: state.rerouted_txns,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TargetThrottling;
+    use aptos_rest_client::Client as RestClient;
+    use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn test_throttling() {
+        let throttling = TargetThrottling::new(
+            ["http://a:8080", "http://b:8080"]
+                .iter()
+                .map(|url| RestClient::new(Url::parse(url).unwrap()))
+                .collect(),
+        );
+        assert_eq!(throttling.pick(1), Ok(1));
+
+        assert_eq!(
+            throttling.record_throttled(0, Some(Duration::from_secs(60)), 10),
+            Duration::from_secs(60)
+        );
+        assert_eq!(throttling.pick(0), Ok(1));
+        throttling.record_accepted(0, 1, 10);
+
+        // Without Retry-After, the backoff doubles with each throttled response in a row.
+        assert_eq!(
+            throttling.record_throttled(1, None, 5),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            throttling.record_throttled(1, None, 5),
+            Duration::from_secs(2)
+        );
+        let wait = throttling.pick(1).unwrap_err();
+        assert!(wait > Duration::from_secs(1) && wait <= Duration::from_secs(2));
+        throttling.record_accepted(1, 1, 5);
+        assert_eq!(
+            throttling.record_throttled(1, None, 5),
+            Duration::from_secs(1)
+        );
+
+        let stats = throttling.stats();
+        assert_eq!(stats[0].throttled_responses, 1);
+        assert_eq!(stats[0].throttled_txns, 10);
+        assert_eq!(stats[0].rerouted_txns, 10);
+        assert_eq!(stats[1].throttled_responses, 3);
+        assert_eq!(stats[1].throttled_txns, 15);
+        assert_eq!(stats[1].rerouted_txns, 0);
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use super::RETRY_POLICY;
+use super::{query_sequence_numbers, RETRY_POLICY};
 use crate::transaction_generator::TransactionExecutor;
 use anyhow::Result;
 use aptos_logger::{sample, sample::SampleRate, warn};
@@ -10,10 +10,15 @@ use aptos_sdk::{
     move_types::account_address::AccountAddress, types::transaction::SignedTransaction,
 };
 use async_trait::async_trait;
-use futures::future::join_all;
+use futures::{future::join_all, stream, StreamExt, TryStreamExt};
 use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
 use std::{sync::atomic::AtomicUsize, time::Duration};
 
+/// Number of accounts whose sequence numbers are fetched together when syncing in bulk.
+const SEQUENCE_NUMBER_SYNC_BATCH_SIZE: usize = 100;
+/// Number of such batches in flight at once, spread over the endpoints.
+const SEQUENCE_NUMBER_SYNC_PARALLEL_BATCHES: usize = 10;
+
 // Reliable/retrying transaction executor, used for initializing
 pub struct RestApiTransactionExecutor {
     pub rest_clients: Vec<RestClient>,
@@ -135,6 +140,30 @@ impl TransactionExecutor for RestApiTransactionExecutor {
             .sequence_number())
     }
 
+    async fn query_sequence_numbers(&self, addresses: &[AccountAddress]) -> Result<Vec<u64>> {
+        let batches = addresses
+            .chunks(SEQUENCE_NUMBER_SYNC_BATCH_SIZE)
+            .enumerate()
+            .map(|(i, batch)| {
+                query_sequence_numbers(
+                    &self.rest_clients[i % self.rest_clients.len()],
+                    batch.iter(),
+                )
+            });
+        let sequence_numbers: Vec<_> = stream::iter(batches)
+            .buffered(SEQUENCE_NUMBER_SYNC_PARALLEL_BATCHES)
+            .try_collect()
+            .await?;
+        Ok(sequence_numbers
+            .into_iter()
+            .flat_map(|(batch, _)| {
+                batch
+                    .into_iter()
+                    .map(|(_, sequence_number)| sequence_number)
+            })
+            .collect())
+    }
+
     async fn execute_transactions(&self, txns: &[SignedTransaction]) -> Result<()> {
         self.execute_transactions_with_counter(txns, &[AtomicUsize::new(0)])
             .await
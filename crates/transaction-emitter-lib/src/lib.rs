@@ -13,11 +13,13 @@ mod wrappers;
 // These are the top level things you should need to run the emitter.
 pub use args::{ClusterArgs, CoinSourceArgs, EmitArgs, TransactionTypeArg};
 // We export these if you want finer grained control.
-pub use cluster::Cluster;
+pub use cluster::{ChainGasParams, Cluster};
 pub use emitter::{
     query_sequence_number, query_sequence_numbers,
     stats::{TxnStats, TxnStatsRate},
     EmitJob, EmitJobMode, EmitJobRequest, EmitModeParams, TransactionType, TxnEmitter,
 };
-pub use transaction_generator::EntryPoints;
+pub use transaction_generator::{
+    nft_mint_and_transfer::NftWorkload, staking::StakingWorkload, EntryPoints,
+};
 pub use wrappers::{emit_transactions, emit_transactions_with_cluster};
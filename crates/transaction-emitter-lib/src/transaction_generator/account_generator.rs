@@ -1,6 +1,8 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
-use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use crate::transaction_generator::{
+    accounts_pool::AccountsPool, TransactionGenerator, TransactionGeneratorCreator,
+};
 use aptos_infallible::RwLock;
 use aptos_logger::{info, sample, sample::SampleRate};
 use aptos_sdk::{
@@ -16,7 +18,7 @@ pub struct AccountGenerator {
     rng: StdRng,
     txn_factory: TransactionFactory,
     addresses_pool: Arc<RwLock<Vec<AccountAddress>>>,
-    accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+    accounts_pool: Arc<RwLock<AccountsPool>>,
     add_created_accounts_to_pool: bool,
     max_working_set: usize,
     creation_balance: u64,
@@ -27,7 +29,7 @@ impl AccountGenerator {
         rng: StdRng,
         txn_factory: TransactionFactory,
         addresses_pool: Arc<RwLock<Vec<AccountAddress>>>,
-        accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+        accounts_pool: Arc<RwLock<AccountsPool>>,
         add_created_accounts_to_pool: bool,
         max_working_set: usize,
         creation_balance: u64,
@@ -110,12 +112,9 @@ impl TransactionGenerator for AccountGenerator {
         }
 
         if self.add_created_accounts_to_pool {
-            add_to_sized_pool(
-                self.accounts_pool.as_ref(),
-                new_accounts,
-                self.max_working_set,
-                &mut self.rng,
-            );
+            self.accounts_pool
+                .write()
+                .add(new_accounts, self.max_working_set, &mut self.rng);
             add_to_sized_pool(
                 self.addresses_pool.as_ref(),
                 new_account_addresses,
@@ -130,7 +129,7 @@ impl TransactionGenerator for AccountGenerator {
 pub struct AccountGeneratorCreator {
     txn_factory: TransactionFactory,
     addresses_pool: Arc<RwLock<Vec<AccountAddress>>>,
-    accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+    accounts_pool: Arc<RwLock<AccountsPool>>,
     add_created_accounts_to_pool: bool,
     max_working_set: usize,
     creation_balance: u64,
@@ -140,7 +139,7 @@ impl AccountGeneratorCreator {
     pub fn new(
         txn_factory: TransactionFactory,
         addresses_pool: Arc<RwLock<Vec<AccountAddress>>>,
-        accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+        accounts_pool: Arc<RwLock<AccountsPool>>,
         add_created_accounts_to_pool: bool,
         max_working_set: usize,
         creation_balance: u64,
@@ -0,0 +1,267 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pool of the accounts created by account generation, for other workloads to use once. Pools of
+//! millions of accounts, with their keys, don't fit in the memory of an emitter host, so beyond a
+//! bound the oldest accounts are spilled to disk, in segments, and loaded back once the accounts
+//! in memory run out. Accounts are taken newest first, so the ones in memory are the hot ones.
+
+use anyhow::{format_err, Context, Result};
+use aptos_logger::{info, sample, sample::SampleRate, warn};
+use aptos_sdk::{
+    crypto::ed25519::Ed25519PrivateKey, move_types::account_address::AccountAddress,
+    types::LocalAccount,
+};
+use rand::{rngs::StdRng, Rng};
+use std::{fs, path::Path, time::Duration};
+use tempfile::TempDir;
+
+/// Segments of spilled accounts, the last one holding the newest.
+struct Spill {
+    /// Removed, along with the segments, when the pool is dropped.
+    dir: TempDir,
+    segments: Vec<(u64, usize)>,
+    next_segment: u64,
+    len: usize,
+}
+
+impl Spill {
+    fn segment_path(&self, segment: u64) -> std::path::PathBuf {
+        self.dir.path().join(format!("accounts-{}.bcs", segment))
+    }
+
+    fn push(&mut self, accounts: &[LocalAccount]) -> Result<()> {
+        let spilled: Vec<(AccountAddress, &Ed25519PrivateKey, u64)> = accounts
+            .iter()
+            .map(|account| {
+                (
+                    account.address(),
+                    account.private_key(),
+                    account.sequence_number(),
+                )
+            })
+            .collect();
+        let segment = self.next_segment;
+        let bytes = bcs::to_bytes(&spilled).context("Failed to serialize accounts")?;
+        fs::write(self.segment_path(segment), bytes).context("Failed to spill accounts to disk")?;
+        self.next_segment += 1;
+        self.len += spilled.len();
+        self.segments.push((segment, spilled.len()));
+        Ok(())
+    }
+
+    /// Loads the newest segment back, which stays spilled if it can't be loaded.
+    fn pop(&mut self) -> Result<Option<Vec<LocalAccount>>> {
+        let (segment, len) = match self.segments.last() {
+            Some(&last) => last,
+            None => return Ok(None),
+        };
+        let path = self.segment_path(segment);
+        let bytes = fs::read(&path).context("Failed to load spilled accounts")?;
+        let spilled: Vec<(AccountAddress, Ed25519PrivateKey, u64)> =
+            bcs::from_bytes(&bytes).context("Failed to deserialize spilled accounts")?;
+        let _ = fs::remove_file(&path);
+        self.segments.pop();
+        self.len -= len;
+        Ok(Some(
+            spilled
+                .into_iter()
+                .map(|(address, private_key, sequence_number)| {
+                    LocalAccount::new(address, private_key, sequence_number)
+                })
+                .collect(),
+        ))
+    }
+}
+
+pub struct AccountsPool {
+    /// Newest accounts, the last ones taken first.
+    hot: Vec<LocalAccount>,
+    max_in_memory: usize,
+    spill: Option<Spill>,
+}
+
+impl Default for AccountsPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccountsPool {
+    /// A pool holding all of its accounts in memory.
+    pub fn new() -> Self {
+        Self {
+            hot: Vec::new(),
+            max_in_memory: usize::MAX,
+            spill: None,
+        }
+    }
+
+    /// A pool holding up to `max_in_memory` accounts in memory, and the others in a temporary
+    /// directory under `dir`.
+    pub fn with_spill(dir: &Path, max_in_memory: usize) -> std::io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            hot: Vec::new(),
+            max_in_memory: max_in_memory.max(1),
+            spill: Some(Spill {
+                dir: tempfile::tempdir_in(dir)?,
+                segments: Vec::new(),
+                next_segment: 0,
+                len: 0,
+            }),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.hot.len() + self.spill.as_ref().map_or(0, |spill| spill.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.hot
+            .reserve(additional.min(self.max_in_memory.saturating_sub(self.hot.len())));
+    }
+
+    /// Adds the accounts to the pool, or once it holds `max_working_set` of them, swaps them
+    /// with random accounts in memory.
+    pub fn add(
+        &mut self,
+        mut addition: Vec<LocalAccount>,
+        max_working_set: usize,
+        rng: &mut StdRng,
+    ) {
+        if self.len() < max_working_set {
+            self.hot.append(&mut addition);
+            if self.hot.len() > self.max_in_memory {
+                // Spill the oldest half, not to go back and forth with the disk.
+                let oldest = self.hot.len() / 2;
+                if let Some(spill) = self.spill.as_mut() {
+                    match spill.push(&self.hot[..oldest]) {
+                        Ok(()) => {
+                            self.hot.drain(..oldest);
+                            info!(
+                                "Spilled accounts to disk, {} in memory, {} on disk",
+                                self.hot.len(),
+                                spill.len
+                            );
+                        },
+                        Err(err) => sample!(
+                            SampleRate::Duration(Duration::from_secs(120)),
+                            warn!(
+                                "Keeping {} accounts in memory, as spilling them failed: {:?}",
+                                self.hot.len(),
+                                err
+                            )
+                        ),
+                    }
+                }
+            }
+            sample!(
+                SampleRate::Duration(Duration::from_secs(120)),
+                info!("Accounts working set increased to {}", self.len())
+            );
+        } else if self.hot.len() >= addition.len() {
+            let start = rng.gen_range(0, self.hot.len() - addition.len() + 1);
+            self.hot[start..start + addition.len()].swap_with_slice(&mut addition);
+            sample!(
+                SampleRate::Duration(Duration::from_secs(120)),
+                info!(
+                    "Already at limit {} > {}, so exchanged accounts in working set",
+                    self.len(),
+                    max_working_set
+                )
+            );
+        }
+    }
+
+    /// Takes the `num` newest accounts, loading spilled ones back as needed, unless there aren't
+    /// as many. Fails if spilled accounts can't be loaded back, which stay in the pool then.
+    pub fn take(&mut self, num: usize) -> Result<Option<Vec<LocalAccount>>> {
+        if self.len() < num {
+            return Ok(None);
+        }
+        while self.hot.len() < num {
+            let older = match self.spill.as_mut() {
+                Some(spill) => spill.pop()?,
+                None => None,
+            }
+            .ok_or_else(|| format_err!("Spilled accounts are counted in the length"))?;
+            self.hot.splice(0..0, older);
+        }
+        Ok(Some(self.hot.drain(self.hot.len() - num..).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountsPool;
+    use aptos_sdk::types::LocalAccount;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_spill() {
+        let mut rng = StdRng::from_seed([0; 32]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = AccountsPool::with_spill(dir.path(), 4).unwrap();
+        let mut addresses = vec![];
+        for _ in 0..5 {
+            let chunk: Vec<LocalAccount> = (0..2)
+                .map(|_| {
+                    let mut account = LocalAccount::generate(&mut rng);
+                    *account.sequence_number_mut() = 7;
+                    addresses.push(account.address());
+                    account
+                })
+                .collect();
+            pool.add(chunk, 100, &mut rng);
+        }
+        assert_eq!(pool.len(), 10);
+        assert!(pool.hot.len() <= 4);
+
+        // Newest first, whether in memory or spilled.
+        let taken = pool.take(7).unwrap().unwrap();
+        assert_eq!(
+            taken.iter().map(|a| a.address()).collect::<Vec<_>>(),
+            addresses[3..].to_vec()
+        );
+        assert!(taken.iter().all(|a| a.sequence_number() == 7));
+        assert!(pool.take(4).unwrap().is_none());
+        assert_eq!(
+            pool.take(3)
+                .unwrap()
+                .unwrap()
+                .iter()
+                .map(|a| a.address())
+                .collect::<Vec<_>>(),
+            addresses[..3].to_vec()
+        );
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_spill_failures() {
+        let mut rng = StdRng::from_seed([0; 32]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut pool = AccountsPool::with_spill(dir.path(), 4).unwrap();
+        let generate = |rng: &mut StdRng, num| -> Vec<LocalAccount> {
+            (0..num).map(|_| LocalAccount::generate(rng)).collect()
+        };
+
+        // Spilled segments that can't be loaded back stay in the pool.
+        pool.add(generate(&mut rng, 6), 100, &mut rng);
+        assert_eq!(pool.hot.len(), 3);
+        std::fs::remove_dir_all(dir.path()).unwrap();
+        assert!(pool.take(5).is_err());
+        assert_eq!(pool.len(), 6);
+
+        // Accounts that can't be spilled stay in memory.
+        pool.add(generate(&mut rng, 4), 100, &mut rng);
+        assert_eq!(pool.len(), 10);
+        assert_eq!(pool.hot.len(), 7);
+        assert_eq!(pool.take(7).unwrap().unwrap().len(), 7);
+    }
+}
@@ -1,7 +1,9 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use crate::transaction_generator::{
+    accounts_pool::AccountsPool, TransactionGenerator, TransactionGeneratorCreator,
+};
 use aptos_infallible::RwLock;
 use aptos_logger::{sample, sample::SampleRate, warn};
 use aptos_sdk::types::{transaction::SignedTransaction, LocalAccount};
@@ -15,13 +17,13 @@ use std::{sync::Arc, time::Duration};
 /// (we cannot use more as sequence number is not updated on failure)
 pub struct AccountsPoolWrapperGenerator {
     creator: Box<dyn TransactionGenerator>,
-    accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+    accounts_pool: Arc<RwLock<AccountsPool>>,
 }
 
 impl AccountsPoolWrapperGenerator {
     pub fn new(
         creator: Box<dyn TransactionGenerator>,
-        accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+        accounts_pool: Arc<RwLock<AccountsPool>>,
     ) -> Self {
         Self {
             creator,
@@ -41,16 +43,24 @@ impl TransactionGenerator for AccountsPoolWrapperGenerator {
 
         let mut accounts_pool = self.accounts_pool.write();
         let num_in_pool = accounts_pool.len();
-        if num_in_pool < needed {
-            sample!(
-                SampleRate::Duration(Duration::from_secs(10)),
-                warn!("Cannot fetch enough accounts from pool, left in pool {}, needed {}", num_in_pool, needed);
-            );
-            return Vec::new();
-        }
-        let mut accounts_to_burn = accounts_pool
-            .drain((num_in_pool - needed)..)
-            .collect::<Vec<_>>();
+        let mut accounts_to_burn = match accounts_pool.take(needed) {
+            Ok(Some(accounts)) => accounts,
+            Ok(None) => {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(10)),
+                    warn!("Cannot fetch enough accounts from pool, left in pool {}, needed {}", num_in_pool, needed);
+                );
+                return Vec::new();
+            },
+            Err(err) => {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(10)),
+                    warn!("Cannot load spilled accounts from pool: {:?}", err);
+                );
+                return Vec::new();
+            },
+        };
+        drop(accounts_pool);
 
         self.creator
             .generate_transactions(accounts_to_burn.iter_mut().collect(), 1)
@@ -59,13 +69,13 @@ impl TransactionGenerator for AccountsPoolWrapperGenerator {
 
 pub struct AccountsPoolWrapperCreator {
     creator: Box<dyn TransactionGeneratorCreator>,
-    accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+    accounts_pool: Arc<RwLock<AccountsPool>>,
 }
 
 impl AccountsPoolWrapperCreator {
     pub fn new(
         creator: Box<dyn TransactionGeneratorCreator>,
-        accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+        accounts_pool: Arc<RwLock<AccountsPool>>,
     ) -> Self {
         Self {
             creator,
@@ -0,0 +1,197 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transfers of a fungible token between the accounts, so that the token standard can be
+//! characterized apart from coin transfers. The framework has no fungible asset standard of its
+//! own, so the token is one of the token standard with a supply, moved with `token` transfers.
+
+use super::{
+    nft_mint_and_transfer::{
+        collection_name, create_nft_collection_request, create_nft_token_request,
+        create_nft_transfer_request, create_token_accounts, execute_per_sender_batches, token_name,
+    },
+    TransactionExecutor,
+};
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use aptos_logger::info;
+use aptos_sdk::{
+    transaction_builder::{aptos_stdlib::aptos_token_stdlib, TransactionFactory},
+    types::{account_address::AccountAddress, transaction::SignedTransaction, LocalAccount},
+};
+use async_trait::async_trait;
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, SeedableRng};
+use std::collections::HashSet;
+
+/// Balance the creator starts with, and hands to the distribution accounts.
+const SUPPLY: u64 = 1 << 62;
+/// Balance each account is given by the distribution account of its worker.
+const INITIAL_BALANCE: u64 = 1_000_000;
+
+pub struct FungibleTokenTransfer {
+    rng: StdRng,
+    txn_factory: TransactionFactory,
+    creator_address: AccountAddress,
+    distribution_account: LocalAccount,
+    /// Accounts funded and opted in to receive the token.
+    set_up: HashSet<AccountAddress>,
+    /// Accounts set up in a previous round, so that their opt-in committed, to send to.
+    receivers: Vec<AccountAddress>,
+    /// Accounts set up in the last round, receivers from the next one.
+    newly_set_up: Vec<AccountAddress>,
+}
+
+impl FungibleTokenTransfer {
+    pub fn new(
+        rng: StdRng,
+        txn_factory: TransactionFactory,
+        creator_address: AccountAddress,
+        distribution_account: LocalAccount,
+    ) -> Self {
+        Self {
+            rng,
+            txn_factory,
+            creator_address,
+            distribution_account,
+            set_up: HashSet::new(),
+            receivers: vec![],
+            newly_set_up: vec![],
+        }
+    }
+}
+
+impl TransactionGenerator for FungibleTokenTransfer {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        self.receivers.append(&mut self.newly_set_up);
+        let mut requests = Vec::with_capacity(accounts.len() * transactions_per_account);
+        for account in accounts {
+            if self.set_up.insert(account.address()) {
+                requests.push(
+                    account.sign_with_transaction_builder(
+                        self.txn_factory
+                            .payload(aptos_token_stdlib::token_opt_in_direct_transfer(true)),
+                    ),
+                );
+                requests.push(create_nft_transfer_request(
+                    &mut self.distribution_account,
+                    account,
+                    self.creator_address,
+                    &collection_name(0),
+                    &token_name(0),
+                    &self.txn_factory,
+                    INITIAL_BALANCE,
+                ));
+                self.newly_set_up.push(account.address());
+                continue;
+            }
+            for _ in 0..transactions_per_account {
+                // The account itself is a receiver, at worst it sends to itself.
+                let receiver = *self.receivers.choose(&mut self.rng).unwrap();
+                requests.push(
+                    account.sign_with_transaction_builder(self.txn_factory.payload(
+                        aptos_token_stdlib::token_transfer_with_opt_in(
+                            self.creator_address,
+                            collection_name(0),
+                            token_name(0),
+                            0,
+                            receiver,
+                            1,
+                        ),
+                    )),
+                );
+            }
+        }
+        requests
+    }
+}
+
+pub struct FungibleTokenTransferGeneratorCreator {
+    txn_factory: TransactionFactory,
+    creator_address: AccountAddress,
+    distribution_accounts: Vec<LocalAccount>,
+}
+
+impl FungibleTokenTransferGeneratorCreator {
+    pub async fn new(
+        txn_factory: TransactionFactory,
+        init_txn_factory: TransactionFactory,
+        root_account: &mut LocalAccount,
+        txn_executor: &dyn TransactionExecutor,
+        num_workers: usize,
+    ) -> Self {
+        let mut creator_account = LocalAccount::generate(&mut StdRng::from_entropy());
+        let creator_address = creator_account.address();
+        let distribution_accounts: Vec<_> = (0..num_workers)
+            .map(|_| LocalAccount::generate(&mut thread_rng()))
+            .collect();
+        create_token_accounts(
+            txn_executor,
+            root_account,
+            &creator_account,
+            &distribution_accounts,
+            2 + num_workers,
+            &init_txn_factory,
+        )
+        .await;
+
+        let txns = vec![
+            create_nft_collection_request(
+                &mut creator_account,
+                &collection_name(0),
+                &init_txn_factory,
+            ),
+            create_nft_token_request(
+                &mut creator_account,
+                &collection_name(0),
+                &token_name(0),
+                SUPPLY,
+                u64::MAX,
+                0,
+                &init_txn_factory,
+            ),
+        ];
+        execute_per_sender_batches(txn_executor, vec![txns]).await;
+
+        let share = SUPPLY / num_workers.max(1) as u64;
+        let txns = distribution_accounts
+            .iter()
+            .map(|distribution_account| {
+                create_nft_transfer_request(
+                    &mut creator_account,
+                    distribution_account,
+                    creator_address,
+                    &collection_name(0),
+                    &token_name(0),
+                    &init_txn_factory,
+                    share,
+                )
+            })
+            .collect();
+        execute_per_sender_batches(txn_executor, vec![txns]).await;
+        info!(
+            "Distributed the fungible token to {} accounts",
+            distribution_accounts.len()
+        );
+
+        Self {
+            txn_factory,
+            creator_address,
+            distribution_accounts,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for FungibleTokenTransferGeneratorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(FungibleTokenTransfer::new(
+            StdRng::from_entropy(),
+            self.txn_factory.clone(),
+            self.creator_address,
+            self.distribution_accounts.pop().unwrap(),
+        ))
+    }
+}
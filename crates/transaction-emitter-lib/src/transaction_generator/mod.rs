@@ -12,18 +12,23 @@ use async_trait::async_trait;
 use std::sync::{atomic::AtomicUsize, Arc};
 
 pub mod account_generator;
+pub mod accounts_pool;
 pub mod accounts_pool_wrapper;
 pub mod call_custom_modules;
+pub mod fungible_token_transfer;
 pub mod nft_mint_and_transfer;
 pub mod p2p_transaction_generator;
 pub mod publish_modules;
 mod publishing;
+pub mod staking;
 pub mod transaction_mix_generator;
 use self::{
-    account_generator::AccountGeneratorCreator, call_custom_modules::CallCustomModulesCreator,
+    account_generator::AccountGeneratorCreator, accounts_pool::AccountsPool,
+    call_custom_modules::CallCustomModulesCreator,
+    fungible_token_transfer::FungibleTokenTransferGeneratorCreator,
     nft_mint_and_transfer::NFTMintAndTransferGeneratorCreator,
     p2p_transaction_generator::P2PTransactionGeneratorCreator,
-    publish_modules::PublishPackageCreator,
+    publish_modules::PublishPackageCreator, staking::StakingGeneratorCreator,
     transaction_mix_generator::PhasedTxnMixGeneratorCreator,
 };
 use crate::{
@@ -53,6 +58,9 @@ pub trait TransactionExecutor: Sync + Send {
 
     async fn query_sequence_number(&self, account_address: AccountAddress) -> Result<u64>;
 
+    /// Sequence numbers of many accounts, in the order of the addresses, fetched in bulk.
+    async fn query_sequence_numbers(&self, addresses: &[AccountAddress]) -> Result<Vec<u64>>;
+
     async fn execute_transactions(&self, txns: &[SignedTransaction]) -> Result<()>;
 
     async fn execute_transactions_with_counter(
@@ -70,11 +78,12 @@ pub async fn create_txn_generator_creator(
     txn_factory: &TransactionFactory,
     init_txn_factory: &TransactionFactory,
     stats: Arc<DynamicStatsTracking>,
+    accounts_pool: AccountsPool,
 ) -> Box<dyn TransactionGeneratorCreator> {
     let all_addresses = Arc::new(RwLock::new(
         all_accounts.iter().map(|d| d.address()).collect::<Vec<_>>(),
     ));
-    let accounts_pool = Arc::new(RwLock::new(Vec::new()));
+    let accounts_pool = Arc::new(RwLock::new(accounts_pool));
 
     let mut txn_generator_creator_mix_per_phase: Vec<
         Vec<(Box<dyn TransactionGeneratorCreator>, usize)>,
@@ -83,7 +92,7 @@ pub async fn create_txn_generator_creator(
     fn wrap_accounts_pool(
         inner: Box<dyn TransactionGeneratorCreator>,
         use_account_pool: bool,
-        accounts_pool: Arc<RwLock<Vec<LocalAccount>>>,
+        accounts_pool: Arc<RwLock<AccountsPool>>,
     ) -> Box<dyn TransactionGeneratorCreator> {
         if use_account_pool {
             Box::new(AccountsPoolWrapperCreator::new(inner, accounts_pool))
@@ -123,13 +132,24 @@ pub async fn create_txn_generator_creator(
                     *max_account_working_set,
                     *creation_balance,
                 )),
-                TransactionType::NftMintAndTransfer => Box::new(
+                TransactionType::NftMintAndTransfer { workload } => Box::new(
                     NFTMintAndTransferGeneratorCreator::new(
                         txn_factory.clone(),
                         init_txn_factory.clone(),
                         all_accounts.get_mut(0).unwrap(),
                         txn_executor,
                         num_workers,
+                        *workload,
+                    )
+                    .await,
+                ),
+                TransactionType::FungibleTokenTransfer => Box::new(
+                    FungibleTokenTransferGeneratorCreator::new(
+                        txn_factory.clone(),
+                        init_txn_factory.clone(),
+                        all_accounts.get_mut(0).unwrap(),
+                        txn_executor,
+                        num_workers,
                     )
                     .await,
                 ),
@@ -157,6 +177,11 @@ pub async fn create_txn_generator_creator(
                     *use_account_pool,
                     accounts_pool.clone(),
                 ),
+                TransactionType::Staking { workload } => Box::new(StakingGeneratorCreator::new(
+                    txn_factory.clone(),
+                    *workload,
+                    all_addresses.clone(),
+                )),
             };
             txn_generator_creator_mix.push((txn_generator_creator, *weight));
         }
@@ -12,37 +12,125 @@ use aptos_sdk::{
     types::{account_address::AccountAddress, transaction::SignedTransaction, LocalAccount},
 };
 use async_trait::async_trait;
-use rand::{rngs::StdRng, thread_rng, SeedableRng};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
 use std::collections::HashMap;
 
 const INITIAL_NFT_BALANCE: u64 = 50_000;
+/// Balance of each token the creator hands to the distribution account of each worker.
+const DISTRIBUTION_BALANCE: u64 = 1_000_000_000;
+/// Coins the creator is given for each of the transactions it sends to set up the workload.
+const CREATOR_FUNDING_PER_TXN: u64 = 1_000_000;
+/// Transactions of an account are accepted by mempool in batches up to this size.
+const MAX_TXNS_PER_ACCOUNT: usize = 100;
+
+/// Shape of the NFT workload, so that token performance can be characterized by how spread the
+/// tokens are, how large they are, and how often they're minted rather than transferred.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NftWorkload {
+    /// Collections the transferred tokens are spread over, and each account creates to mint into.
+    pub num_collections: usize,
+    /// Tokens of each collection the transfers pick from.
+    pub tokens_per_collection: usize,
+    /// Size in bytes of the description of the tokens created, standing for their metadata.
+    pub metadata_size: usize,
+    /// Percentage of the rounds of an account minting new tokens rather than transferring them.
+    pub mint_percentage: usize,
+}
+
+impl NftWorkload {
+    fn num_tokens(&self) -> usize {
+        self.num_collections * self.tokens_per_collection
+    }
+
+    /// Collection and name of the token of the creator at the index.
+    fn token(&self, idx: usize) -> (Vec<u8>, Vec<u8>) {
+        (
+            collection_name(idx / self.tokens_per_collection),
+            token_name((idx % self.tokens_per_collection) as u64),
+        )
+    }
+}
+
+impl Default for NftWorkload {
+    fn default() -> Self {
+        Self {
+            num_collections: 1,
+            tokens_per_collection: 1,
+            metadata_size: 0,
+            mint_percentage: 0,
+        }
+    }
+}
+
+pub(crate) fn collection_name(idx: usize) -> Vec<u8> {
+    format!("collection {}", idx).into_bytes()
+}
+
+pub(crate) fn token_name(idx: u64) -> Vec<u8> {
+    format!("token {}", idx).into_bytes()
+}
+
+/// Collections an account created to mint into, and the tokens it minted.
+#[derive(Default)]
+struct Minter {
+    num_collections: usize,
+    num_minted: u64,
+}
 
 pub struct NFTMintAndTransfer {
+    rng: StdRng,
     txn_factory: TransactionFactory,
     creator_address: AccountAddress,
     distribution_account: LocalAccount,
-    collection_name: Vec<u8>,
-    token_name: Vec<u8>,
-    account_funded: HashMap<AccountAddress, bool>,
+    workload: NftWorkload,
+    /// Index of the token each account holds, handed back to the distribution account in the
+    /// next round of the account.
+    holding: HashMap<AccountAddress, usize>,
+    minters: HashMap<AccountAddress, Minter>,
 }
 
 impl NFTMintAndTransfer {
     pub async fn new(
+        rng: StdRng,
         txn_factory: TransactionFactory,
         creator_address: AccountAddress,
         distribution_account: LocalAccount,
-        collection_name: Vec<u8>,
-        token_name: Vec<u8>,
+        workload: NftWorkload,
     ) -> Self {
         Self {
+            rng,
             txn_factory,
             distribution_account,
             creator_address,
-            collection_name,
-            token_name,
-            account_funded: Default::default(),
+            workload,
+            holding: Default::default(),
+            minters: Default::default(),
         }
     }
+
+    /// Next transaction of the account minting into its collections, creating them first.
+    fn mint_request(&mut self, account: &mut LocalAccount) -> SignedTransaction {
+        let minter = self.minters.entry(account.address()).or_default();
+        if minter.num_collections < self.workload.num_collections {
+            minter.num_collections += 1;
+            return create_nft_collection_request(
+                account,
+                &collection_name(minter.num_collections - 1),
+                &self.txn_factory,
+            );
+        }
+        minter.num_minted += 1;
+        let collection_idx = (minter.num_minted % self.workload.num_collections as u64) as usize;
+        create_nft_token_request(
+            account,
+            &collection_name(collection_idx),
+            &token_name(minter.num_minted),
+            1,
+            1,
+            self.workload.metadata_size,
+            &self.txn_factory,
+        )
+    }
 }
 
 impl TransactionGenerator for NFTMintAndTransfer {
@@ -53,87 +141,189 @@ impl TransactionGenerator for NFTMintAndTransfer {
     ) -> Vec<SignedTransaction> {
         let mut requests = Vec::with_capacity(accounts.len() * transactions_per_account);
         for account in accounts {
-            let account_funded = self
-                .account_funded
-                .get(&account.address())
-                .cloned()
-                .unwrap_or(false);
+            // Accounts holding a token hand it back before minting.
+            if !self.holding.contains_key(&account.address())
+                && self.rng.gen_range(0..100) < self.workload.mint_percentage
+            {
+                for _ in 0..transactions_per_account {
+                    requests.push(self.mint_request(account));
+                }
+                continue;
+            }
+
+            // Each round moves INITIAL_NFT_BALANCE of a token to the account, or back.
+            let (token_idx, account_funded) = match self.holding.remove(&account.address()) {
+                Some(token_idx) => (token_idx, true),
+                None => {
+                    let token_idx = self.rng.gen_range(0..self.workload.num_tokens());
+                    self.holding.insert(account.address(), token_idx);
+                    (token_idx, false)
+                },
+            };
+            let (collection_name, token_name) = self.workload.token(token_idx);
             for i in 0..transactions_per_account {
+                let amount = if i != transactions_per_account - 1 {
+                    1
+                } else {
+                    INITIAL_NFT_BALANCE + 1 - transactions_per_account as u64
+                };
                 requests.push(
                     if account_funded {
                         create_nft_transfer_request(
                             account,
                             &self.distribution_account,
                             self.creator_address,
-                            &self.collection_name,
-                            &self.token_name,
+                            &collection_name,
+                            &token_name,
                             &self.txn_factory,
-                            if i != transactions_per_account - 1 {
-                                1
-                            } else {
-                                INITIAL_NFT_BALANCE + 1 - transactions_per_account as u64
-                            },
+                            amount,
                         )
                     } else {
                         create_nft_transfer_request(
                             &mut self.distribution_account,
                             account,
                             self.creator_address,
-                            &self.collection_name,
-                            &self.token_name,
+                            &collection_name,
+                            &token_name,
                             &self.txn_factory,
-                            if i != transactions_per_account - 1 {
-                                1
-                            } else {
-                                INITIAL_NFT_BALANCE + 1 - transactions_per_account as u64
-                            },
+                            amount,
                         )
                     },
                 );
             }
-            self.account_funded
-                .insert(account.address(), !account_funded);
         }
         requests
     }
 }
 
-pub async fn initialize_nft_collection(
+/// Executes the transactions, in batches of at most `MAX_TXNS_PER_ACCOUNT` of each sender.
+pub(crate) async fn execute_per_sender_batches(
+    txn_executor: &dyn TransactionExecutor,
+    txns_per_sender: Vec<Vec<SignedTransaction>>,
+) {
+    let num_batches = txns_per_sender
+        .iter()
+        .map(|txns| (txns.len() + MAX_TXNS_PER_ACCOUNT - 1) / MAX_TXNS_PER_ACCOUNT)
+        .max()
+        .unwrap_or(0);
+    for batch in 0..num_batches {
+        let txns: Vec<_> = txns_per_sender
+            .iter()
+            .flat_map(|txns| {
+                txns.iter()
+                    .skip(batch * MAX_TXNS_PER_ACCOUNT)
+                    .take(MAX_TXNS_PER_ACCOUNT)
+                    .cloned()
+            })
+            .collect();
+        txn_executor.execute_transactions(&txns).await.unwrap();
+    }
+}
+
+/// Creates the creator of the tokens, funded for the transactions it sends to set up the
+/// workload, and the distribution accounts, sharing half of the coins left to the root account.
+pub(crate) async fn create_token_accounts(
     txn_executor: &dyn TransactionExecutor,
     root_account: &mut LocalAccount,
-    creator_account: &mut LocalAccount,
+    creator_account: &LocalAccount,
+    distribution_accounts: &[LocalAccount],
+    num_creator_txns: usize,
     txn_factory: &TransactionFactory,
-    collection_name: &[u8],
-    token_name: &[u8],
 ) {
-    // Create and mint the owner account first
-    let create_account_txn = create_and_fund_account_request(
-        root_account,
-        10_000_000,
-        creator_account.public_key(),
-        txn_factory,
-    );
-
-    txn_executor
-        .execute_transactions(&[create_account_txn])
+    let creator_funding = CREATOR_FUNDING_PER_TXN * num_creator_txns as u64;
+    let root_balance = txn_executor
+        .get_account_balance(root_account.address())
         .await
         .unwrap();
+    // The distribution accounts send about half of the transfers.
+    let distribution_funding = root_balance.saturating_sub(creator_funding)
+        / 2
+        / distribution_accounts.len().max(1) as u64;
 
-    let collection_txn =
-        create_nft_collection_request(creator_account, collection_name, txn_factory);
+    let mut txns = vec![create_and_fund_account_request(
+        root_account,
+        creator_funding,
+        creator_account.public_key(),
+        txn_factory,
+    )];
+    for distribution_account in distribution_accounts {
+        txns.push(create_and_fund_account_request(
+            root_account,
+            distribution_funding,
+            distribution_account.public_key(),
+            txn_factory,
+        ));
+    }
+    execute_per_sender_batches(txn_executor, vec![txns]).await;
+}
 
-    txn_executor
-        .execute_transactions(&[collection_txn])
-        .await
-        .unwrap();
+/// Sets up the collections and tokens of the workload, and hands `DISTRIBUTION_BALANCE` of each
+/// token to each distribution account.
+pub async fn initialize_nft_collection(
+    txn_executor: &dyn TransactionExecutor,
+    root_account: &mut LocalAccount,
+    creator_account: &mut LocalAccount,
+    distribution_accounts: &[LocalAccount],
+    txn_factory: &TransactionFactory,
+    workload: &NftWorkload,
+) {
+    let num_creator_txns =
+        workload.num_collections + workload.num_tokens() * (1 + distribution_accounts.len());
+    create_token_accounts(
+        txn_executor,
+        root_account,
+        creator_account,
+        distribution_accounts,
+        num_creator_txns,
+        txn_factory,
+    )
+    .await;
 
-    let token_txn =
-        create_nft_token_request(creator_account, collection_name, token_name, txn_factory);
+    let mut txns = Vec::with_capacity(num_creator_txns);
+    for collection_idx in 0..workload.num_collections {
+        txns.push(create_nft_collection_request(
+            creator_account,
+            &collection_name(collection_idx),
+            txn_factory,
+        ));
+    }
+    for token_idx in 0..workload.num_tokens() {
+        let (collection_name, token_name) = workload.token(token_idx);
+        txns.push(create_nft_token_request(
+            creator_account,
+            &collection_name,
+            &token_name,
+            100_000_000_000,
+            u64::MAX,
+            workload.metadata_size,
+            txn_factory,
+        ));
+    }
+    // The tokens must exist before they're transferred.
+    execute_per_sender_batches(txn_executor, vec![txns]).await;
+    info!(
+        "Created {} collections of {} tokens",
+        workload.num_collections, workload.tokens_per_collection
+    );
 
-    txn_executor
-        .execute_transactions(&[token_txn])
-        .await
-        .unwrap();
+    let mut txns = Vec::with_capacity(workload.num_tokens() * distribution_accounts.len());
+    let creator_address = creator_account.address();
+    for distribution_account in distribution_accounts {
+        for token_idx in 0..workload.num_tokens() {
+            let (collection_name, token_name) = workload.token(token_idx);
+            txns.push(create_nft_transfer_request(
+                creator_account,
+                distribution_account,
+                creator_address,
+                &collection_name,
+                &token_name,
+                txn_factory,
+                DISTRIBUTION_BALANCE,
+            ));
+        }
+    }
+    info!("Distributing {} NFTs", txns.len());
+    execute_per_sender_batches(txn_executor, vec![txns]).await;
 
     info!("initialize_nft_collection complete");
 }
@@ -154,19 +344,24 @@ pub fn create_nft_collection_request(
     ))
 }
 
+/// Creates the token with `balance` of it for its creator, its description `metadata_size`
+/// bytes long.
 pub fn create_nft_token_request(
     creation_account: &mut LocalAccount,
     collection_name: &[u8],
     token_name: &[u8],
+    balance: u64,
+    maximum: u64,
+    metadata_size: usize,
     txn_factory: &TransactionFactory,
 ) -> SignedTransaction {
     creation_account.sign_with_transaction_builder(txn_factory.payload(
         aptos_token_stdlib::token_create_token_script(
             collection_name.to_vec(),
             token_name.to_vec(),
-            "collection description".to_owned().into_bytes(),
-            100_000_000_000,
-            u64::MAX,
+            vec![b'm'; metadata_size],
+            balance,
+            maximum,
             "uri".to_owned().into_bytes(),
             creation_account.address(),
             1,
@@ -204,8 +399,7 @@ pub struct NFTMintAndTransferGeneratorCreator {
     txn_factory: TransactionFactory,
     creator_address: AccountAddress,
     distribution_accounts: Vec<LocalAccount>,
-    collection_name: Vec<u8>,
-    token_name: Vec<u8>,
+    workload: NftWorkload,
 }
 
 impl NFTMintAndTransferGeneratorCreator {
@@ -215,52 +409,33 @@ impl NFTMintAndTransferGeneratorCreator {
         root_account: &mut LocalAccount,
         txn_executor: &dyn TransactionExecutor,
         num_workers: usize,
+        workload: NftWorkload,
     ) -> Self {
+        assert!(
+            workload.num_collections > 0 && workload.tokens_per_collection > 0,
+            "NFT workload needs at least one collection of one token"
+        );
         let mut rng = StdRng::from_entropy();
         let mut creator_account = LocalAccount::generate(&mut rng);
         let creator_address = creator_account.address();
-        let collection_name = "collection name".to_owned().into_bytes();
-        let token_name = "token name".to_owned().into_bytes();
+        let distribution_accounts: Vec<_> = (0..num_workers)
+            .map(|_| LocalAccount::generate(&mut thread_rng()))
+            .collect();
         initialize_nft_collection(
             txn_executor,
             root_account,
             &mut creator_account,
+            &distribution_accounts,
             &init_txn_factory,
-            &collection_name,
-            &token_name,
+            &workload,
         )
         .await;
 
-        let mut distribution_accounts = Vec::new();
-        let mut txns = Vec::new();
-
-        for _ in 0..num_workers {
-            let distribution_account = LocalAccount::generate(&mut thread_rng());
-            txns.push(create_nft_transfer_request(
-                &mut creator_account,
-                &distribution_account,
-                creator_address,
-                &collection_name,
-                &token_name,
-                &init_txn_factory,
-                1_000_000_000,
-            ));
-            distribution_accounts.push(distribution_account);
-        }
-
-        info!("Creating {} NFTs", txns.len());
-        // per account limit is 100
-        for chunk in txns.chunks(100) {
-            txn_executor.execute_transactions(chunk).await.unwrap();
-        }
-        info!("Done creating {} NFTs", txns.len());
-
         Self {
             txn_factory,
             creator_address,
             distribution_accounts,
-            collection_name,
-            token_name,
+            workload,
         }
     }
 }
@@ -270,13 +445,82 @@ impl TransactionGeneratorCreator for NFTMintAndTransferGeneratorCreator {
     async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
         Box::new(
             NFTMintAndTransfer::new(
+                StdRng::from_entropy(),
                 self.txn_factory.clone(),
                 self.creator_address,
                 self.distribution_accounts.pop().unwrap(),
-                self.collection_name.clone(),
-                self.token_name.clone(),
+                self.workload,
             )
             .await,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{NFTMintAndTransfer, NftWorkload};
+    use crate::transaction_generator::TransactionGenerator;
+    use aptos_sdk::{
+        transaction_builder::TransactionFactory,
+        types::{
+            chain_id::ChainId,
+            transaction::{SignedTransaction, TransactionPayload},
+            LocalAccount,
+        },
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn function_names(txns: &[SignedTransaction]) -> Vec<String> {
+        txns.iter()
+            .map(|txn| match txn.payload() {
+                TransactionPayload::EntryFunction(entry_function) => {
+                    entry_function.function().to_string()
+                },
+                _ => panic!("Expected entry functions"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_nft_workload() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut accounts: Vec<_> = (0..4).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let creator = LocalAccount::generate(&mut rng);
+        let workload = NftWorkload {
+            num_collections: 3,
+            tokens_per_collection: 2,
+            metadata_size: 100,
+            mint_percentage: 100,
+        };
+        let mut generator = NFTMintAndTransfer::new(
+            rng,
+            TransactionFactory::new(ChainId::test()),
+            creator.address(),
+            LocalAccount::generate(&mut StdRng::seed_from_u64(1)),
+            workload,
+        )
+        .await;
+
+        // Minters create their collections first, then mint across them.
+        let txns = generator.generate_transactions(accounts.iter_mut().collect(), 5);
+        assert_eq!(txns.len(), 20);
+        let names = function_names(&txns[..5]);
+        assert_eq!(names[..3], ["create_collection_script"; 3]);
+        assert_eq!(names[3..], ["create_token_script"; 2]);
+        let txns = generator.generate_transactions(accounts.iter_mut().collect(), 5);
+        assert!(function_names(&txns)
+            .iter()
+            .all(|name| name == "create_token_script"));
+
+        // Transfers go to the accounts, and back.
+        generator.workload.mint_percentage = 0;
+        for _ in 0..2 {
+            let txns = generator.generate_transactions(accounts.iter_mut().collect(), 5);
+            assert_eq!(txns.len(), 20);
+            assert!(function_names(&txns)
+                .iter()
+                .all(|name| name == "direct_transfer_script"));
+        }
+        assert!(generator.holding.is_empty());
+    }
+}
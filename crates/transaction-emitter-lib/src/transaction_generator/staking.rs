@@ -0,0 +1,207 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Workloads going through the staking modules of the framework, which are much heavier than coin
+//! transfers. Each account first sets up its own stake pool or staking contract, then keeps adding
+//! stake, unlocking and withdrawing it, in proportions resembling those seen on mainnet.
+
+use crate::transaction_generator::{TransactionGenerator, TransactionGeneratorCreator};
+use aptos_infallible::RwLock;
+use aptos_sdk::{
+    move_types::account_address::AccountAddress,
+    transaction_builder::{aptos_stdlib, TransactionFactory},
+    types::{
+        transaction::{SignedTransaction, TransactionPayload},
+        LocalAccount,
+    },
+};
+use async_trait::async_trait;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// Stake each account sets its pool or contract up with.
+const INITIAL_STAKE: u64 = 10_000;
+/// Bounds of the amounts added, unlocked and withdrawn by each transaction.
+const MIN_AMOUNT: u64 = 10;
+const MAX_AMOUNT: u64 = 1_000;
+/// Weights of the operations once set up: adding stake is the most common, withdrawing the least.
+const ADD_STAKE_WEIGHT: u32 = 5;
+const UNLOCK_WEIGHT: u32 = 3;
+const WITHDRAW_WEIGHT: u32 = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StakingWorkload {
+    /// Each account owns a stake pool, and operates it itself: `stake::add_stake`,
+    /// `stake::unlock` and `stake::withdraw`.
+    StakePool,
+    /// Each account stakes through a staking contract with another account as the operator, as
+    /// vesting contracts do: `staking_contract::add_stake`, `staking_contract::unlock_stake` and
+    /// `staking_contract::distribute` to withdraw.
+    StakingContract,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum StakingOperation {
+    AddStake,
+    Unlock,
+    Withdraw,
+}
+
+impl StakingOperation {
+    fn sample(rng: &mut StdRng) -> Self {
+        let roll = rng.gen_range(0..ADD_STAKE_WEIGHT + UNLOCK_WEIGHT + WITHDRAW_WEIGHT);
+        if roll < ADD_STAKE_WEIGHT {
+            Self::AddStake
+        } else if roll < ADD_STAKE_WEIGHT + UNLOCK_WEIGHT {
+            Self::Unlock
+        } else {
+            Self::Withdraw
+        }
+    }
+}
+
+pub struct StakingGenerator {
+    rng: StdRng,
+    txn_factory: TransactionFactory,
+    workload: StakingWorkload,
+    all_addresses: Arc<RwLock<Vec<AccountAddress>>>,
+    /// Accounts whose stake pool or staking contract is set up.
+    set_up: HashSet<AccountAddress>,
+    /// Operator of the staking contract of each account.
+    operators: HashMap<AccountAddress, AccountAddress>,
+}
+
+impl StakingGenerator {
+    pub fn new(
+        rng: StdRng,
+        txn_factory: TransactionFactory,
+        workload: StakingWorkload,
+        all_addresses: Arc<RwLock<Vec<AccountAddress>>>,
+    ) -> Self {
+        Self {
+            rng,
+            txn_factory,
+            workload,
+            all_addresses,
+            set_up: HashSet::new(),
+            operators: Default::default(),
+        }
+    }
+
+    fn operator(&mut self, staker: AccountAddress) -> AccountAddress {
+        if let Some(operator) = self.operators.get(&staker) {
+            return *operator;
+        }
+        let operator = {
+            let addresses = self.all_addresses.read();
+            if addresses.len() < 2 {
+                AccountAddress::ONE
+            } else {
+                loop {
+                    let address = addresses[self.rng.gen_range(0..addresses.len())];
+                    if address != staker {
+                        break address;
+                    }
+                }
+            }
+        };
+        self.operators.insert(staker, operator);
+        operator
+    }
+
+    fn payload(&mut self, staker: AccountAddress) -> TransactionPayload {
+        let amount = self.rng.gen_range(MIN_AMOUNT..=MAX_AMOUNT);
+        if self.set_up.insert(staker) {
+            return match self.workload {
+                StakingWorkload::StakePool => {
+                    aptos_stdlib::stake_initialize_stake_owner(INITIAL_STAKE, staker, staker)
+                },
+                StakingWorkload::StakingContract => {
+                    let operator = self.operator(staker);
+                    aptos_stdlib::staking_contract_create_staking_contract(
+                        operator,
+                        staker,
+                        INITIAL_STAKE,
+                        10, /* commission percentage */
+                        vec![],
+                    )
+                },
+            };
+        }
+        let operation = StakingOperation::sample(&mut self.rng);
+        match self.workload {
+            StakingWorkload::StakePool => match operation {
+                StakingOperation::AddStake => aptos_stdlib::stake_add_stake(amount),
+                StakingOperation::Unlock => aptos_stdlib::stake_unlock(amount),
+                StakingOperation::Withdraw => aptos_stdlib::stake_withdraw(amount),
+            },
+            StakingWorkload::StakingContract => {
+                let operator = self.operator(staker);
+                match operation {
+                    StakingOperation::AddStake => {
+                        aptos_stdlib::staking_contract_add_stake(operator, amount)
+                    },
+                    StakingOperation::Unlock => {
+                        aptos_stdlib::staking_contract_unlock_stake(operator, amount)
+                    },
+                    StakingOperation::Withdraw => {
+                        aptos_stdlib::staking_contract_distribute(staker, operator)
+                    },
+                }
+            },
+        }
+    }
+}
+
+impl TransactionGenerator for StakingGenerator {
+    fn generate_transactions(
+        &mut self,
+        accounts: Vec<&mut LocalAccount>,
+        transactions_per_account: usize,
+    ) -> Vec<SignedTransaction> {
+        let mut requests = Vec::with_capacity(accounts.len() * transactions_per_account);
+        for account in accounts {
+            for _ in 0..transactions_per_account {
+                let payload = self.payload(account.address());
+                requests
+                    .push(account.sign_with_transaction_builder(self.txn_factory.payload(payload)));
+            }
+        }
+        requests
+    }
+}
+
+pub struct StakingGeneratorCreator {
+    txn_factory: TransactionFactory,
+    workload: StakingWorkload,
+    all_addresses: Arc<RwLock<Vec<AccountAddress>>>,
+}
+
+impl StakingGeneratorCreator {
+    pub fn new(
+        txn_factory: TransactionFactory,
+        workload: StakingWorkload,
+        all_addresses: Arc<RwLock<Vec<AccountAddress>>>,
+    ) -> Self {
+        Self {
+            txn_factory,
+            workload,
+            all_addresses,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionGeneratorCreator for StakingGeneratorCreator {
+    async fn create_transaction_generator(&mut self) -> Box<dyn TransactionGenerator> {
+        Box::new(StakingGenerator::new(
+            StdRng::from_entropy(),
+            self.txn_factory.clone(),
+            self.workload,
+            self.all_addresses.clone(),
+        ))
+    }
+}
@@ -2,16 +2,25 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    args::{ClusterArgs, EmitArgs},
+    args::{ClusterArgs, EmitArgs, TenantArg},
     cluster::Cluster,
-    emitter::{stats::TxnStats, EmitJobMode, EmitJobRequest, TxnEmitter},
+    emitter::{
+        adaptive_batch::AdaptiveBatchConfig,
+        backpressure::BackpressureConfig,
+        recording::Recording,
+        slo::{LatencySlo, SloConfig},
+        stats::TxnStats,
+        success_criteria::PhaseSuccessCriteria,
+        tenants::TenantConfig,
+        EmitJobMode, EmitJobRequest, TxnEmitter,
+    },
     instance::Instance,
-    EntryPoints, TransactionType, TransactionTypeArg,
+    EntryPoints, NftWorkload, StakingWorkload, TransactionType, TransactionTypeArg,
 };
 use anyhow::{Context, Result};
 use aptos_sdk::transaction_builder::TransactionFactory;
-use rand::{rngs::StdRng, SeedableRng};
-use std::time::Duration;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 pub async fn emit_transactions(
     cluster_args: &ClusterArgs,
@@ -31,53 +40,36 @@ pub async fn emit_transactions_with_cluster(
     let emitter_mode = EmitJobMode::create(args.mempool_backlog, args.target_tps);
 
     let duration = Duration::from_secs(args.duration);
+    // Gas flags take precedence over what the chain reports, but are checked against it before
+    // spending anything.
+    let (gas_price, max_gas_per_txn) = cluster
+        .discover_gas_params()
+        .await?
+        .resolve(args.gas_price, args.max_gas_per_txn)?;
     let client = cluster.random_instance().rest_client();
     let mut coin_source_account = cluster.load_coin_source_account(&client).await?;
+    let recording = args
+        .replay_transactions
+        .as_deref()
+        .map(Recording::load)
+        .transpose()?
+        .map(Arc::new);
+    // Accounts are derived from the emitter seed, a replay must use the recorded one.
+    let emitter_seed: [u8; 32] = match &recording {
+        Some(recording) => recording.emitter_seed,
+        None => StdRng::from_entropy().gen(),
+    };
     let emitter = TxnEmitter::new(
         TransactionFactory::new(cluster.chain_id)
             .with_transaction_expiration_time(args.txn_expiration_time_secs)
             .with_gas_unit_price(aptos_global_constants::GAS_UNIT_PRICE),
-        StdRng::from_entropy(),
+        StdRng::from_seed(emitter_seed),
     );
 
     let arg_transaction_types = args
         .transaction_type
         .iter()
-        .map(|t| match t {
-            TransactionTypeArg::CoinTransfer => TransactionType::CoinTransfer {
-                invalid_transaction_ratio: args.invalid_tx,
-                sender_use_account_pool: false,
-            },
-            TransactionTypeArg::AccountGeneration => TransactionType::default_account_generation(),
-            TransactionTypeArg::AccountGenerationLargePool => TransactionType::AccountGeneration {
-                add_created_accounts_to_pool: true,
-                max_account_working_set: 50_000_000,
-                creation_balance: 200_000_000,
-            },
-            TransactionTypeArg::NftMintAndTransfer => TransactionType::NftMintAndTransfer,
-            TransactionTypeArg::PublishPackage => TransactionType::PublishPackage {
-                use_account_pool: false,
-            },
-            TransactionTypeArg::CustomFunctionLargeModuleWorkingSet => {
-                TransactionType::CallCustomModules {
-                    entry_point: EntryPoints::Nop,
-                    num_modules: 1000,
-                    use_account_pool: false,
-                }
-            },
-            TransactionTypeArg::CreateNewResource => TransactionType::CallCustomModules {
-                entry_point: EntryPoints::BytesMakeOrChange {
-                    data_length: Some(32),
-                },
-                num_modules: 1,
-                use_account_pool: true,
-            },
-            TransactionTypeArg::NoOp => TransactionType::CallCustomModules {
-                entry_point: EntryPoints::Nop,
-                num_modules: 1,
-                use_account_pool: false,
-            },
-        })
+        .map(|t| transaction_type(args, *t))
         .collect::<Vec<_>>();
 
     let arg_transaction_weights = if args.transaction_weights.is_empty() {
@@ -121,26 +113,97 @@ pub async fn emit_transactions_with_cluster(
             .push((transaction_type, weight));
     }
 
+    let success_criteria_per_phase = phase_success_criteria(args, transaction_mix_per_phase.len());
     let mut emit_job_request =
         EmitJobRequest::new(cluster.all_instances().map(Instance::rest_client).collect())
             .mode(emitter_mode)
+            .success_criteria_per_phase(success_criteria_per_phase)
             .transaction_mix_per_phase(transaction_mix_per_phase)
             .txn_expiration_time_secs(args.txn_expiration_time_secs)
+            .gas_price(gas_price)
+            .max_gas_per_txn(max_gas_per_txn)
             .delay_after_minting(Duration::from_secs(args.delay_after_minting.unwrap_or(0)));
     if reuse_accounts {
         emit_job_request = emit_job_request.reuse_accounts();
     }
-    if let Some(max_transactions_per_account) = args.max_transactions_per_account {
+    if let Some(path) = &args.record_transactions {
+        emit_job_request = emit_job_request.record_transactions(path.clone(), emitter_seed);
+    }
+    if let Some(recording) = recording {
+        emit_job_request = emit_job_request.replay_recording(recording);
+    }
+    if let Some(max_expired_ratio) = args.max_expired_ratio {
+        emit_job_request = emit_job_request.expiration_backpressure(BackpressureConfig {
+            max_expired_ratio,
+            // Leave time for the transactions submitted after a change to expire.
+            check_interval: Duration::from_secs(2 * args.txn_expiration_time_secs + 30),
+            ..BackpressureConfig::default()
+        });
+    }
+    if let Some(control_port) = args.control_port {
         emit_job_request =
-            emit_job_request.max_transactions_per_account(max_transactions_per_account);
+            emit_job_request.control_address(SocketAddr::from(([127, 0, 0, 1], control_port)));
     }
-
-    if let Some(gas_price) = args.gas_price {
-        emit_job_request = emit_job_request.gas_price(gas_price);
+    if args.gas_report {
+        emit_job_request = emit_job_request.gas_report();
     }
-
-    if let Some(max_gas_per_txn) = args.max_gas_per_txn {
-        emit_job_request = emit_job_request.max_gas_per_txn(max_gas_per_txn);
+    if let Some(dir) = &args.accounts_pool_spill_dir {
+        emit_job_request =
+            emit_job_request.accounts_pool_spill(dir.clone(), args.accounts_pool_max_in_memory);
+    }
+    if !args.latency_slo.is_empty() {
+        let slos = args
+            .latency_slo
+            .iter()
+            .map(|slo| slo.parse::<LatencySlo>())
+            .collect::<Result<Vec<_>>>()?;
+        let mut config = SloConfig {
+            slos,
+            window: Duration::from_secs(args.slo_window_secs),
+            check_interval: Duration::from_secs(args.slo_check_interval_secs.max(1)),
+            node_metrics_urls: args.node_metrics_url.clone(),
+            ..SloConfig::default()
+        };
+        if !args.slo_node_metric.is_empty() {
+            config.node_metrics = args.slo_node_metric.clone();
+        }
+        emit_job_request = emit_job_request.latency_slos(config);
+    }
+    if args.adaptive_account_creation {
+        emit_job_request = emit_job_request.adaptive_account_creation(AdaptiveBatchConfig {
+            target_latency: Duration::from_millis(args.account_creation_target_latency_ms),
+            max_batch_size: args.account_creation_max_batch_size,
+            ..AdaptiveBatchConfig::default()
+        });
+    }
+    if !args.tenant.is_empty() {
+        let phases = emit_job_request.get_num_phases();
+        let tenants = args
+            .tenant
+            .iter()
+            .map(|tenant| {
+                let tenant = tenant.parse::<TenantArg>()?;
+                let transaction_mix: Vec<_> = tenant
+                    .transaction_mix
+                    .iter()
+                    .map(|(t, weight)| (transaction_type(args, *t), *weight))
+                    .collect();
+                let mut config = TenantConfig::new(
+                    tenant.name.clone(),
+                    vec![transaction_mix; phases],
+                    tenant.weight,
+                );
+                if let Some(key) = tenant.load_coin_source_key()? {
+                    config = config.coin_source_key(key);
+                }
+                Ok(config)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        emit_job_request = emit_job_request.tenants(tenants);
+    }
+    if let Some(max_transactions_per_account) = args.max_transactions_per_account {
+        emit_job_request =
+            emit_job_request.max_transactions_per_account(max_transactions_per_account);
     }
 
     if let Some(init_gas_price_multiplier) = args.init_gas_price_multiplier {
@@ -166,3 +229,94 @@ pub async fn emit_transactions_with_cluster(
         .await?;
     Ok(stats)
 }
+
+/// The workload of a transaction type given on the command line.
+fn transaction_type(args: &EmitArgs, transaction_type: TransactionTypeArg) -> TransactionType {
+    match transaction_type {
+        TransactionTypeArg::CoinTransfer => TransactionType::CoinTransfer {
+            invalid_transaction_ratio: args.invalid_tx,
+            sender_use_account_pool: false,
+        },
+        TransactionTypeArg::AccountGeneration => TransactionType::default_account_generation(),
+        TransactionTypeArg::AccountGenerationLargePool => TransactionType::AccountGeneration {
+            add_created_accounts_to_pool: true,
+            max_account_working_set: 50_000_000,
+            creation_balance: 200_000_000,
+        },
+        TransactionTypeArg::NftMintAndTransfer => TransactionType::NftMintAndTransfer {
+            workload: NftWorkload {
+                num_collections: args.nft_num_collections,
+                tokens_per_collection: args.nft_tokens_per_collection,
+                metadata_size: args.nft_metadata_size,
+                mint_percentage: args.nft_mint_percentage,
+            },
+        },
+        TransactionTypeArg::FungibleTokenTransfer => TransactionType::FungibleTokenTransfer,
+        TransactionTypeArg::PublishPackage => TransactionType::PublishPackage {
+            use_account_pool: false,
+        },
+        TransactionTypeArg::CustomFunctionLargeModuleWorkingSet => {
+            TransactionType::CallCustomModules {
+                entry_point: EntryPoints::Nop,
+                num_modules: 1000,
+                use_account_pool: false,
+            }
+        },
+        TransactionTypeArg::CreateNewResource => TransactionType::CallCustomModules {
+            entry_point: EntryPoints::BytesMakeOrChange {
+                data_length: Some(32),
+            },
+            num_modules: 1,
+            use_account_pool: true,
+        },
+        TransactionTypeArg::NoOp => TransactionType::CallCustomModules {
+            entry_point: EntryPoints::Nop,
+            num_modules: 1,
+            use_account_pool: false,
+        },
+        TransactionTypeArg::StakePoolOperations => TransactionType::Staking {
+            workload: StakingWorkload::StakePool,
+        },
+        TransactionTypeArg::StakingContractOperations => TransactionType::Staking {
+            workload: StakingWorkload::StakingContract,
+        },
+    }
+}
+
+/// Success criteria of each phase, from flags given either once for all phases or once per phase.
+fn phase_success_criteria(args: &EmitArgs, phases: usize) -> Vec<PhaseSuccessCriteria> {
+    fn for_phase<T: Copy>(values: &[T], phase: usize, name: &str, phases: usize) -> Option<T> {
+        assert!(
+            values.len() <= 1 || values.len() == phases,
+            "{} needs either one value or one per phase ({})",
+            name,
+            phases
+        );
+        if values.len() == 1 {
+            values.first().copied()
+        } else {
+            values.get(phase).copied()
+        }
+    }
+
+    (0..phases)
+        .map(|phase| PhaseSuccessCriteria {
+            min_tps: for_phase(&args.phase_min_tps, phase, "--phase-min-tps", phases),
+            max_p99_latency_ms: for_phase(
+                &args.phase_max_p99_latency_ms,
+                phase,
+                "--phase-max-p99-latency-ms",
+                phases,
+            ),
+            max_expired_percent: for_phase(
+                &args.phase_max_expired_percent,
+                phase,
+                "--phase-max-expired-percent",
+                phases,
+            ),
+            warmup: Duration::from_secs(args.phase_warmup_secs),
+            abort_after_failed_checks: args.phase_abort_after_failed_checks,
+            ..PhaseSuccessCriteria::default()
+        })
+        .collect()
+}
@@ -3,15 +3,21 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod diag;
+mod runtime;
 
 use anyhow::{Context, Result};
-use aptos_logger::{Level, Logger};
+use aptos_logger::{warn, Level, Logger};
 use aptos_transaction_emitter_lib::{emit_transactions, Cluster, ClusterArgs, EmitArgs};
 use clap::{Parser, Subcommand};
 use diag::diag;
+use runtime::{CpuUsageReporter, RuntimeArgs};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 struct Args {
+    #[clap(flatten)]
+    runtime_args: RuntimeArgs,
+
     #[clap(subcommand)]
     command: TxnEmitterCommand,
 }
@@ -43,24 +49,42 @@ struct Diag {
     cluster_args: ClusterArgs,
 }
 
-#[tokio::main]
-pub async fn main() -> Result<()> {
+pub fn main() -> Result<()> {
     Logger::builder().level(Level::Info).build();
 
     let args = Args::parse();
+    let runtime = args
+        .runtime_args
+        .build_runtime()
+        .context("Failed to build the runtime")?;
+    runtime.block_on(run(args))
+}
 
+async fn run(args: Args) -> Result<()> {
     // TODO: Check if I need DisplayChain here in the error case.
     match args.command {
-        TxnEmitterCommand::EmitTx(args) => {
-            let stats = emit_transactions(&args.cluster_args, &args.emit_args)
+        TxnEmitterCommand::EmitTx(emit_tx) => {
+            let cpu_usage_reporter = CpuUsageReporter::start(
+                Duration::from_secs(args.runtime_args.cpu_report_interval_secs),
+                args.runtime_args.usable_cores(),
+            )?;
+            let stats = emit_transactions(&emit_tx.cluster_args, &emit_tx.emit_args)
                 .await
                 .context("Emit transactions failed")?;
+            let cpu_usage = cpu_usage_reporter.stop();
             println!("Total stats: {}", stats);
             println!("Average rate: {}", stats.rate());
+            println!("Emitter CPU usage: {}", cpu_usage);
+            if cpu_usage.is_saturated() {
+                warn!(
+                    "The emitter was CPU bound, the rate may be limited by the emitter rather than \
+                    the network. Consider a bigger host, or tuning --worker-threads."
+                );
+            }
             Ok(())
         },
-        TxnEmitterCommand::Diag(args) => {
-            let cluster = Cluster::try_from_cluster_args(&args.cluster_args)
+        TxnEmitterCommand::Diag(diag_args) => {
+            let cluster = Cluster::try_from_cluster_args(&diag_args.cluster_args)
                 .await
                 .context("Failed to build cluster")?;
             diag(&cluster).await.context("Diag failed")?;
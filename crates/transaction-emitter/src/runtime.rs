@@ -0,0 +1,200 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tuning of the emitter's own runtime. On small load generator hosts the emitter itself can be
+//! the bottleneck, so the runtime can be sized and pinned to cores, and the CPU usage of the
+//! process is reported along with the results.
+
+use anyhow::{bail, Context, Result};
+use aptos_logger::{info, warn};
+use clap::Parser;
+use core_affinity::CoreId;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+use sysinfo::{ProcessExt, System, SystemExt};
+
+#[derive(Parser, Debug)]
+pub struct RuntimeArgs {
+    /// Number of worker threads of the runtime. Defaults to the number of cores.
+    #[clap(long)]
+    pub worker_threads: Option<usize>,
+
+    /// Maximum number of threads of the runtime's blocking pool, e.g. for signing.
+    #[clap(long)]
+    pub max_blocking_threads: Option<usize>,
+
+    /// Cores to pin the runtime threads to, round robin, e.g. "0-3" or "0,2,4,6".
+    #[clap(long, parse(try_from_str = parse_core_list))]
+    pub pin_cores: Option<CoreList>,
+
+    /// How often the CPU usage of the emitter is logged.
+    #[clap(long, default_value = "10")]
+    pub cpu_report_interval_secs: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CoreList(Vec<usize>);
+
+fn parse_core_list(s: &str) -> Result<CoreList> {
+    let mut cores = vec![];
+    for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().context("Invalid core range")?;
+                let last: usize = last.trim().parse().context("Invalid core range")?;
+                if first > last {
+                    bail!("Invalid core range {}", part);
+                }
+                cores.extend(first..=last);
+            },
+            None => cores.push(part.parse().context("Invalid core")?),
+        }
+    }
+    if cores.is_empty() {
+        bail!("No core given");
+    }
+    Ok(CoreList(cores))
+}
+
+impl RuntimeArgs {
+    pub fn build_runtime(&self) -> Result<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(worker_threads) = self.worker_threads {
+            builder.worker_threads(worker_threads);
+        }
+        if let Some(max_blocking_threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(max_blocking_threads);
+        }
+        if let Some(CoreList(cores)) = &self.pin_cores {
+            let available: Vec<usize> = core_affinity::get_core_ids()
+                .context("Failed to list the cores")?
+                .into_iter()
+                .map(|core| core.id)
+                .collect();
+            if let Some(core) = cores.iter().find(|core| !available.contains(core)) {
+                bail!("Core {} is not available, cores are {:?}", core, available);
+            }
+            let cores = Arc::new(cores.clone());
+            let next = Arc::new(AtomicUsize::new(0));
+            builder.on_thread_start(move || {
+                let core = cores[next.fetch_add(1, Ordering::Relaxed) % cores.len()];
+                if !core_affinity::set_for_current(CoreId { id: core }) {
+                    warn!("Failed to pin runtime thread to core {}", core);
+                }
+            });
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Number of cores the emitter can keep busy.
+    pub fn usable_cores(&self) -> usize {
+        let cores = match &self.pin_cores {
+            Some(CoreList(cores)) => cores.len(),
+            None => num_cores(),
+        };
+        match self.worker_threads {
+            Some(worker_threads) => cores.min(worker_threads),
+            None => cores,
+        }
+    }
+}
+
+fn num_cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// CPU usage of the emitter process, where 100% is one core fully used.
+#[derive(Debug)]
+pub struct CpuUsage {
+    pub average_percent: f32,
+    pub max_percent: f32,
+    pub usable_cores: usize,
+}
+
+impl CpuUsage {
+    /// Whether the emitter was mostly busy, in which case it may be what limits the load.
+    pub fn is_saturated(&self) -> bool {
+        self.average_percent > 90.0 * self.usable_cores as f32
+    }
+}
+
+impl fmt::Display for CpuUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "average {:.0}%, max {:.0}%, of {} usable cores",
+            self.average_percent, self.max_percent, self.usable_cores
+        )
+    }
+}
+
+/// Samples the CPU usage of the process on a thread of its own, outside of the runtime.
+pub struct CpuUsageReporter {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<(f32, f32)>,
+    usable_cores: usize,
+}
+
+impl CpuUsageReporter {
+    pub fn start(interval: Duration, usable_cores: usize) -> Result<Self> {
+        let pid = sysinfo::get_current_pid().map_err(anyhow::Error::msg)?;
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut system = System::new();
+            // The first refresh only sets the base usage is measured from.
+            system.refresh_process(pid);
+            let (mut sum, mut max, mut count) = (0.0, 0.0_f32, 0);
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                system.refresh_process(pid);
+                if let Some(process) = system.process(pid) {
+                    let usage = process.cpu_usage();
+                    info!("Emitter CPU usage: {:.0}%", usage);
+                    sum += usage;
+                    max = max.max(usage);
+                    count += 1;
+                }
+            }
+            (if count > 0 { sum / count as f32 } else { 0.0 }, max)
+        });
+        Ok(Self {
+            stop,
+            handle,
+            usable_cores,
+        })
+    }
+
+    pub fn stop(self) -> CpuUsage {
+        let _ = self.stop.send(());
+        let (average_percent, max_percent) = self.handle.join().unwrap_or((0.0, 0.0));
+        CpuUsage {
+            average_percent,
+            max_percent,
+            usable_cores: self.usable_cores,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_core_list, CoreList};
+
+    #[test]
+    fn test_parse_core_list() {
+        assert_eq!(parse_core_list("0-3").unwrap(), CoreList(vec![0, 1, 2, 3]));
+        assert_eq!(
+            parse_core_list("0,2, 4-5").unwrap(),
+            CoreList(vec![0, 2, 4, 5])
+        );
+        assert!(parse_core_list("3-1").is_err());
+        assert!(parse_core_list("a").is_err());
+        assert!(parse_core_list("").is_err());
+    }
+}
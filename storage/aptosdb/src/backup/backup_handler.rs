@@ -9,6 +9,7 @@ use crate::{
         BACKUP_EPOCH_ENDING_EPOCH, BACKUP_STATE_SNAPSHOT_LEAF_IDX, BACKUP_STATE_SNAPSHOT_VERSION,
         BACKUP_TXN_VERSION,
     },
+    pruner::{ledger_pruner_manager::LedgerPrunerManager, pruner_manager::PrunerManager},
     state_store::StateStore,
     transaction_store::TransactionStore,
 };
@@ -32,6 +33,7 @@ pub struct BackupHandler {
     transaction_store: Arc<TransactionStore>,
     state_store: Arc<StateStore>,
     event_store: Arc<EventStore>,
+    ledger_pruner: Arc<LedgerPrunerManager>,
 }
 
 impl BackupHandler {
@@ -40,12 +42,14 @@ impl BackupHandler {
         transaction_store: Arc<TransactionStore>,
         state_store: Arc<StateStore>,
         event_store: Arc<EventStore>,
+        ledger_pruner: Arc<LedgerPrunerManager>,
     ) -> Self {
         Self {
             ledger_store,
             transaction_store,
             state_store,
             event_store,
+            ledger_pruner,
         }
     }
 
@@ -133,6 +137,12 @@ impl BackupHandler {
         Ok(Box::new(iterator))
     }
 
+    /// Gets the number of items in the state tree at the version, if the DB tracks it.
+    pub fn get_state_item_count(&self, version: Version) -> Result<Option<usize>> {
+        let usage = self.state_store.get_usage(Some(version))?;
+        Ok((!usage.is_untracked()).then(|| usage.items()))
+    }
+
     /// Gets the proof that proves a range of accounts.
     pub fn get_account_state_range_proof(
         &self,
@@ -154,6 +164,28 @@ impl BackupHandler {
             }))
     }
 
+    /// Gets the latest ledger info along with the range of data available, which is what backups
+    /// are planned on. The ledger info is read before the pruner progress, so everything between
+    /// the reported first versions and the ledger info was readable at the time of the call, give
+    /// or take pruning that happened since.
+    pub fn get_db_metadata(&self) -> Result<Option<DbMetadata>> {
+        let ledger_info = match self.ledger_store.get_latest_ledger_info_option() {
+            Some(ledger_info) => ledger_info,
+            None => return Ok(None),
+        };
+        let state_db = &self.state_store.state_db;
+
+        Ok(Some(DbMetadata {
+            epoch: ledger_info.ledger_info().epoch(),
+            first_version: self.ledger_pruner.get_min_readable_version(),
+            last_version: ledger_info.ledger_info().version(),
+            ledger_pruner: PrunerWindow::new(self.ledger_pruner.as_ref()),
+            state_merkle_pruner: PrunerWindow::new(&state_db.state_pruner),
+            epoch_snapshot_pruner: PrunerWindow::new(&state_db.epoch_snapshot_pruner),
+            ledger_info,
+        }))
+    }
+
     /// Gets the proof of the state root at specified version.
     /// N.B. the `LedgerInfo` returned will always be in the same epoch of the version.
     pub fn get_state_root_proof(
@@ -191,6 +223,53 @@ pub struct DbState {
     pub committed_version: Version,
 }
 
+/// Progress and configuration of a pruner.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PrunerWindow {
+    pub enabled: bool,
+    /// Number of versions kept.
+    pub prune_window: Version,
+    /// Versions older than this are (or are being) pruned.
+    pub min_readable_version: Version,
+}
+
+impl PrunerWindow {
+    fn new<P: PrunerManager>(pruner: &P) -> Self {
+        Self {
+            enabled: pruner.is_pruner_enabled(),
+            prune_window: pruner.get_prune_window(),
+            min_readable_version: pruner.get_min_readable_version(),
+        }
+    }
+}
+
+/// A consistent view of what a DB holds, see `BackupHandler::get_db_metadata()`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DbMetadata {
+    pub epoch: u64,
+    /// First version of which transactions are available.
+    pub first_version: Version,
+    /// Latest committed version.
+    pub last_version: Version,
+    pub ledger_pruner: PrunerWindow,
+    /// State snapshots are available at versions no older than this pruner's
+    /// `min_readable_version`.
+    pub state_merkle_pruner: PrunerWindow,
+    /// State snapshots at epoch endings are available no older than this pruner's
+    /// `min_readable_version`.
+    pub epoch_snapshot_pruner: PrunerWindow,
+    pub ledger_info: LedgerInfoWithSignatures,
+}
+
+impl DbMetadata {
+    pub fn db_state(&self) -> DbState {
+        DbState {
+            epoch: self.epoch,
+            committed_version: self.last_version,
+        }
+    }
+}
+
 impl fmt::Display for DbState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -5,6 +5,7 @@
 pub mod backup_handler;
 pub mod restore_handler;
 pub mod restore_utils;
+mod sst_ingest;
 
 #[cfg(test)]
 mod test;
@@ -3,10 +3,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backup::restore_utils,
+    backup::{restore_utils, sst_ingest::SstIngestingStateStore},
     db_metadata::{DbMetadataKey, DbMetadataSchema},
     event_store::EventStore,
     ledger_store::LedgerStore,
+    schema::{ledger_info::LedgerInfoSchema, state_value::StateValueSchema},
     state_restore::StateSnapshotRestore,
     state_store::StateStore,
     transaction_store::TransactionStore,
@@ -14,7 +15,7 @@ use crate::{
 };
 use anyhow::Result;
 use aptos_crypto::HashValue;
-use aptos_schemadb::DB;
+use aptos_schemadb::{SchemaBatch, DB};
 use aptos_storage_interface::DbReader;
 use aptos_types::{
     contract_event::ContractEvent,
@@ -23,7 +24,7 @@ use aptos_types::{
     state_store::{state_key::StateKey, state_value::StateValue},
     transaction::{Transaction, TransactionInfo, Version},
 };
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 /// Provides functionalities for AptosDB data restore.
 #[derive(Clone)]
@@ -34,6 +35,9 @@ pub struct RestoreHandler {
     transaction_store: Arc<TransactionStore>,
     state_store: Arc<StateStore>,
     event_store: Arc<EventStore>,
+    /// Where to build the SST files state snapshots are ingested from, if they are restored by
+    /// ingestion rather than through the normal write path.
+    state_snapshot_sst_dir: Option<PathBuf>,
 }
 
 impl RestoreHandler {
@@ -52,14 +56,36 @@ impl RestoreHandler {
             transaction_store,
             state_store,
             event_store,
+            state_snapshot_sst_dir: None,
         }
     }
 
+    /// Restores state snapshots by building SST files in `sst_dir` and ingesting them into the
+    /// DB, which is much faster for large states. `sst_dir` is best on the file system of the DB,
+    /// for the files to be moved into it rather than copied.
+    pub fn with_state_snapshot_ingestion(mut self, sst_dir: Option<PathBuf>) -> Self {
+        self.state_snapshot_sst_dir = sst_dir;
+        self
+    }
+
     pub fn get_state_restore_receiver(
         &self,
         version: Version,
         expected_root_hash: HashValue,
     ) -> Result<StateSnapshotRestore<StateKey, StateValue>> {
+        if let Some(sst_dir) = &self.state_snapshot_sst_dir {
+            let store = Arc::new(SstIngestingStateStore::new(
+                self.state_store.clone(),
+                sst_dir.clone(),
+            ));
+            return StateSnapshotRestore::new(
+                &store,
+                &store,
+                version,
+                expected_root_hash,
+                true, /* async_commit */
+            );
+        }
         StateSnapshotRestore::new(
             &self.state_store.state_merkle_db,
             &self.state_store,
@@ -69,6 +95,22 @@ impl RestoreHandler {
         )
     }
 
+    /// Writes state values of the snapshot at `version` ahead of the restore of its tree, so that
+    /// they can be read before the restore completes. The restore writes them again when it gets
+    /// to them.
+    pub fn save_state_values_ahead(
+        &self,
+        version: Version,
+        values: &[(StateKey, StateValue)],
+    ) -> Result<()> {
+        let batch = SchemaBatch::new();
+        values.iter().try_for_each(|(key, value)| {
+            batch.put::<StateValueSchema>(&(key.clone(), version), &Some(value.clone()))
+        })?;
+        // Where the restore writes the values, see `StateValueWriter` for `StateStore`.
+        self.state_store.ledger_db.write_schemas(batch)
+    }
+
     pub fn reset_state_store(&self) {
         self.state_store.reset();
     }
@@ -122,6 +164,11 @@ impl RestoreHandler {
             .map_or(0, |(ver, _txn_info)| ver + 1))
     }
 
+    /// The ledger info ending the genesis epoch, if the DB has it.
+    pub fn get_genesis_ledger_info(&self) -> Result<Option<LedgerInfoWithSignatures>> {
+        self.ledger_db.get::<LedgerInfoSchema>(&0)
+    }
+
     pub fn get_in_progress_state_snapshot_version(&self) -> Result<Option<Version>> {
         let mut iter = self
             .aptosdb
@@ -0,0 +1,108 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Writes of a state snapshot restore by SST ingestion. The chunks of a snapshot are written
+//! through the normal write path otherwise, whose memtables, WAL and compactions dominate the
+//! restores of large states.
+
+use crate::{
+    db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+    schema::{jellyfish_merkle_node::JellyfishMerkleNodeSchema, state_value::StateValueSchema},
+    state_restore::{StateSnapshotProgress, StateValueBatch, StateValueWriter},
+    state_store::StateStore,
+    OTHER_TIMERS_SECONDS,
+};
+use anyhow::Result;
+use aptos_jellyfish_merkle::{
+    node_type::{LeafNode, Node, NodeKey},
+    NodeBatch, TreeReader, TreeWriter,
+};
+use aptos_schemadb::SchemaBatch;
+use aptos_types::{
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+    },
+    transaction::Version,
+};
+use std::{path::PathBuf, sync::Arc};
+
+/// Restores the tree and the values of a state snapshot into the state store like the store
+/// itself does, except that each batch is built into SST files under `sst_dir` and ingested.
+/// Progress is written after the values it covers, so a restore resumed after a crash adds the
+/// last chunk again at worst, which is harmless.
+pub(crate) struct SstIngestingStateStore {
+    state_store: Arc<StateStore>,
+    sst_dir: PathBuf,
+}
+
+impl SstIngestingStateStore {
+    pub fn new(state_store: Arc<StateStore>, sst_dir: PathBuf) -> Self {
+        Self {
+            state_store,
+            sst_dir,
+        }
+    }
+}
+
+impl TreeReader<StateKey> for SstIngestingStateStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node<StateKey>>> {
+        self.state_store.state_merkle_db.get_node_option(node_key)
+    }
+
+    fn get_rightmost_leaf(
+        &self,
+        version: Version,
+    ) -> Result<Option<(NodeKey, LeafNode<StateKey>)>> {
+        self.state_store.state_merkle_db.get_rightmost_leaf(version)
+    }
+}
+
+impl TreeWriter<StateKey> for SstIngestingStateStore {
+    fn write_node_batch(&self, node_batch: &NodeBatch<StateKey>) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS
+            .with_label_values(&["tree_writer_ingest_batch"])
+            .start_timer();
+        let batch = SchemaBatch::new();
+        node_batch.iter().try_for_each(|(node_key, node)| {
+            batch.put::<JellyfishMerkleNodeSchema>(node_key, node)
+        })?;
+        self.state_store
+            .state_merkle_db
+            .ingest_schemas(batch, &self.sst_dir)
+    }
+}
+
+impl StateValueWriter<StateKey, StateValue> for SstIngestingStateStore {
+    fn write_kv_batch(
+        &self,
+        version: Version,
+        kv_batch: &StateValueBatch<StateKey, Option<StateValue>>,
+        progress: StateSnapshotProgress,
+    ) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS
+            .with_label_values(&["state_value_writer_ingest_chunk"])
+            .start_timer();
+        let batch = SchemaBatch::new();
+        kv_batch
+            .iter()
+            .try_for_each(|(k, v)| batch.put::<StateValueSchema>(k, v))?;
+        self.state_store
+            .ledger_db
+            .ingest_schemas(batch, &self.sst_dir)?;
+
+        let batch = SchemaBatch::new();
+        batch.put::<DbMetadataSchema>(
+            &DbMetadataKey::StateSnapshotRestoreProgress(version),
+            &DbMetadataValue::StateSnapshotProgress(progress),
+        )?;
+        self.state_store.ledger_db.write_schemas(batch)
+    }
+
+    fn write_usage(&self, version: Version, usage: StateStorageUsage) -> Result<()> {
+        self.state_store.write_usage(version, usage)
+    }
+
+    fn get_progress(&self, version: Version) -> Result<Option<StateSnapshotProgress>> {
+        self.state_store.get_progress(version)
+    }
+}
@@ -257,7 +257,7 @@ pub struct AptosDB {
     ledger_store: Arc<LedgerStore>,
     state_store: Arc<StateStore>,
     transaction_store: Arc<TransactionStore>,
-    ledger_pruner: LedgerPrunerManager,
+    ledger_pruner: Arc<LedgerPrunerManager>,
     _rocksdb_property_reporter: RocksdbPropertyReporter,
     ledger_commit_lock: std::sync::Mutex<()>,
     indexer: Option<Indexer>,
@@ -299,11 +299,11 @@ impl AptosDB {
             hack_for_tests,
         ));
         // TODO(grao): Handle state kv db pruning.
-        let ledger_pruner = LedgerPrunerManager::new(
+        let ledger_pruner = Arc::new(LedgerPrunerManager::new(
             Arc::clone(&arc_ledger_rocksdb),
             Arc::clone(&state_store),
             pruner_config.ledger_pruner_config,
-        );
+        ));
 
         AptosDB {
             ledger_db: Arc::clone(&arc_ledger_rocksdb),
@@ -718,6 +718,7 @@ impl AptosDB {
             Arc::clone(&self.transaction_store),
             Arc::clone(&self.state_store),
             Arc::clone(&self.event_store),
+            Arc::clone(&self.ledger_pruner),
         )
     }
 
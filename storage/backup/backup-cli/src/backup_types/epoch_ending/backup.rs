@@ -3,9 +3,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backup_types::epoch_ending::manifest::{EpochEndingBackup, EpochEndingChunk},
+    backup_types::{
+        epoch_ending::manifest::{EpochEndingBackup, EpochEndingChunk},
+        integrity::IntegrityManifest,
+    },
     metadata::Metadata,
-    storage::{BackupHandleRef, BackupStorage, FileHandle, ShellSafeName},
+    storage::{
+        tags::{ObjectTags, TaggedStorage},
+        BackupHandleRef, BackupStorage, FileHandle, ShellSafeName,
+    },
     utils::{
         backup_service_client::BackupServiceClient, read_record_bytes::ReadRecordBytes,
         should_cut_chunk, storage_ext::BackupStorageExt, GlobalBackupOpt,
@@ -51,7 +57,12 @@ impl EpochEndingBackupController {
             end_epoch: opt.end_epoch,
             max_chunk_size: global_opt.max_chunk_size,
             client,
-            storage,
+            storage: Arc::new(TaggedStorage::new(
+                storage,
+                ObjectTags::for_backup("epoch_ending", global_opt.chain_id)
+                    .with("first_epoch", opt.start_epoch)
+                    .with("last_epoch", opt.end_epoch.saturating_sub(1)),
+            )),
         }
     }
 
@@ -78,6 +89,7 @@ impl EpochEndingBackupController {
             .await?;
 
         let mut chunks = Vec::new();
+        let mut integrity = IntegrityManifest::default();
         let mut waypoints = Vec::new();
         let mut chunk_bytes = Vec::new();
 
@@ -96,6 +108,7 @@ impl EpochEndingBackupController {
                         &chunk_bytes,
                         chunk_first_epoch,
                         current_epoch - 1,
+                        &mut integrity,
                     )
                     .await?;
                 chunks.push(chunk);
@@ -117,24 +130,26 @@ impl EpochEndingBackupController {
                 &chunk_bytes,
                 chunk_first_epoch,
                 current_epoch - 1,
+                &mut integrity,
             )
             .await?;
         chunks.push(chunk);
 
-        self.write_manifest(&backup_handle, waypoints, chunks).await
+        self.write_manifest(&backup_handle, waypoints, chunks, integrity)
+            .await
     }
 
     fn backup_name(&self) -> String {
         format!("epoch_ending_{}-", self.start_epoch)
     }
 
-    fn manifest_name() -> &'static ShellSafeName {
+    pub(crate) fn manifest_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("epoch_ending.manifest").unwrap());
         &NAME
     }
 
-    fn chunk_name(first_epoch: u64) -> ShellSafeName {
+    pub(crate) fn chunk_name(first_epoch: u64) -> ShellSafeName {
         format!("{}-.chunk", first_epoch).try_into().unwrap()
     }
 
@@ -155,6 +170,7 @@ impl EpochEndingBackupController {
         chunk_bytes: &[u8],
         first_epoch: u64,
         last_epoch: u64,
+        integrity: &mut IntegrityManifest,
     ) -> Result<EpochEndingChunk> {
         let (chunk_handle, mut chunk_file) = self
             .storage
@@ -162,6 +178,7 @@ impl EpochEndingBackupController {
             .await?;
         chunk_file.write_all(chunk_bytes).await?;
         chunk_file.shutdown().await?;
+        integrity.add(&chunk_handle, chunk_bytes);
         Ok(EpochEndingChunk {
             first_epoch,
             last_epoch,
@@ -174,15 +191,18 @@ impl EpochEndingBackupController {
         backup_handle: &BackupHandleRef,
         waypoints: Vec<Waypoint>,
         chunks: Vec<EpochEndingChunk>,
+        integrity: IntegrityManifest,
     ) -> Result<FileHandle> {
         let first_epoch = self.start_epoch;
         let last_epoch = self.end_epoch - 1;
 
+        let integrity_handle = integrity.write(&self.storage, backup_handle).await?;
         let manifest = EpochEndingBackup {
             first_epoch,
             last_epoch,
             waypoints,
             chunks,
+            integrity: Some(integrity_handle),
         };
         let (manifest_handle, mut manifest_file) = self
             .storage
@@ -24,6 +24,9 @@ pub struct EpochEndingBackup {
     pub last_epoch: u64,
     pub waypoints: Vec<Waypoint>,
     pub chunks: Vec<EpochEndingChunk>,
+    /// `IntegrityManifest` covering all chunk files, absent in backups taken by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<FileHandle>,
 }
 
 impl EpochEndingBackup {
@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backup_types::epoch_ending::manifest::EpochEndingBackup,
+    backup_types::{epoch_ending::manifest::EpochEndingBackup, integrity::BackupIntegrity},
     metrics::{
         restore::{EPOCH_ENDING_EPOCH, EPOCH_ENDING_VERSION},
         verify::{VERIFY_EPOCH_ENDING_EPOCH, VERIFY_EPOCH_ENDING_VERSION},
@@ -82,6 +82,12 @@ impl EpochEndingRestoreController {
         let manifest: EpochEndingBackup =
             self.storage.load_json_file(&self.manifest_handle).await?;
         manifest.verify()?;
+        let integrity = BackupIntegrity::load(
+            &self.storage,
+            manifest.integrity.as_deref(),
+            manifest.chunks.iter().map(|c| c.ledger_infos.as_str()),
+        )
+        .await?;
 
         let mut next_epoch = manifest.first_epoch;
         let mut waypoint_iter = manifest.waypoints.iter();
@@ -95,7 +101,7 @@ impl EpochEndingRestoreController {
                 break;
             }
 
-            let lis = self.read_chunk(&chunk.ledger_infos).await?;
+            let lis = self.read_chunk(&integrity, &chunk.ledger_infos).await?;
             ensure!(
                 chunk.first_epoch + lis.len() as u64 == chunk.last_epoch + 1,
                 "Number of items in chunks doesn't match that in manifest. \
@@ -160,9 +166,10 @@ impl EpochEndingRestoreController {
 
     async fn read_chunk(
         &self,
+        integrity: &BackupIntegrity,
         file_handle: &FileHandleRef,
     ) -> Result<Vec<LedgerInfoWithSignatures>> {
-        let mut file = self.storage.open_for_read(file_handle).await?;
+        let mut file = integrity.open_for_read(&self.storage, file_handle).await?;
         let mut chunk = vec![];
 
         while let Some(record_bytes) = file.read_record_bytes().await? {
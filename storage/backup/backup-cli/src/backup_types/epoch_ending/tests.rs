@@ -9,11 +9,11 @@ use crate::{
             EpochEndingRestoreController, EpochEndingRestoreOpt, EpochHistoryRestoreController,
         },
     },
-    storage::{local_fs::LocalFs, BackupStorage},
+    storage::{local_fs::LocalFs, tags::ObjectTags, BackupStorage},
     utils::{
         backup_service_client::BackupServiceClient, test_utils::tmp_db_with_random_content,
         ConcurrentDownloadsOpt, GlobalBackupOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt,
-        RocksdbOpt, TrustedWaypointOpt,
+        RocksdbOpt, TargetDbOpt, TrustedWaypointOpt,
     },
 };
 use aptos_backup_service::start_backup_service;
@@ -23,6 +23,7 @@ use aptos_storage_interface::DbReader;
 use aptos_temppath::TempPath;
 use aptos_types::{
     aggregate_signature::AggregateSignature,
+    chain_id::ChainId,
     ledger_info::LedgerInfoWithSignatures,
     proptest_types::{AccountInfoUniverse, LedgerInfoWithSignaturesGen},
     waypoint::Waypoint,
@@ -68,6 +69,7 @@ fn end_to_end() {
                 },
                 GlobalBackupOpt {
                     max_chunk_size: 1024,
+                    chain_id: Some(ChainId::test()),
                 },
                 client,
                 Arc::clone(&store),
@@ -75,6 +77,16 @@ fn end_to_end() {
             .run(),
         )
         .unwrap();
+    let tags = rt
+        .block_on(store.read_tags(&manifest_handle))
+        .unwrap()
+        .unwrap();
+    assert_eq!(tags.get(ObjectTags::BACKUP_TYPE), Some("epoch_ending"));
+    assert_eq!(tags.get(ObjectTags::CHAIN_ID), Some("testing"));
+    assert_eq!(
+        tags.get("last_epoch"),
+        Some((latest_epoch - 1).to_string().as_str())
+    );
 
     rt.block_on(
         EpochEndingRestoreController::new(
@@ -85,6 +97,7 @@ fn end_to_end() {
                 target_version: Some(target_version),
                 trusted_waypoints: TrustedWaypointOpt::default(),
                 rocksdb_opt: RocksdbOpt::default(),
+                target_db: TargetDbOpt::default(),
                 concurrent_downloads: ConcurrentDownloadsOpt::default(),
                 replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
             }
@@ -198,6 +211,7 @@ async fn test_trusted_waypoints_impl(
             },
             GlobalBackupOpt {
                 max_chunk_size: 1024,
+                chain_id: None,
             },
             client.clone(),
             Arc::clone(&store),
@@ -217,6 +231,7 @@ async fn test_trusted_waypoints_impl(
             target_version: None,
             trusted_waypoints: TrustedWaypointOpt::default(),
             rocksdb_opt: RocksdbOpt::default(),
+            target_db: TargetDbOpt::default(),
             concurrent_downloads: ConcurrentDownloadsOpt::default(),
             replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
         }
@@ -238,6 +253,7 @@ async fn test_trusted_waypoints_impl(
                 trust_waypoint: trusted_waypoints,
             },
             rocksdb_opt: RocksdbOpt::default(),
+            target_db: TargetDbOpt::default(),
             concurrent_downloads: ConcurrentDownloadsOpt::default(),
             replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
         }
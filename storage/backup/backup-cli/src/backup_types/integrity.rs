@@ -0,0 +1,336 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    storage::{BackupHandleRef, BackupStorage, FileHandle, FileHandleRef, ShellSafeName},
+    utils::storage_ext::BackupStorageExt,
+};
+use anyhow::{anyhow, ensure, Result};
+use aptos_crypto::HashValue;
+use aptos_logger::prelude::*;
+use futures::ready;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
+
+/// Size and hash of a single file written into a backup.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FileChecksum {
+    pub file_handle: FileHandle,
+    pub size: u64,
+    pub sha3_256: HashValue,
+}
+
+/// Enumerates every data file of a backup together with its size and hash.
+///
+/// It is written by the backup controller right before the backup manifest, and the manifest
+/// refers to it, so a restore or verify can find missing or truncated uploads before it starts
+/// consuming the data.
+#[derive(Default, Deserialize, Serialize)]
+pub struct IntegrityManifest {
+    pub files: Vec<FileChecksum>,
+}
+
+impl IntegrityManifest {
+    fn file_name() -> &'static ShellSafeName {
+        static NAME: Lazy<ShellSafeName> =
+            Lazy::new(|| ShellSafeName::from_str("integrity.manifest").unwrap());
+        &NAME
+    }
+
+    pub fn add(&mut self, file_handle: &FileHandleRef, content: &[u8]) {
+        self.add_checksum(
+            file_handle,
+            content.len() as u64,
+            HashValue::sha3_256_of(content),
+        )
+    }
+
+    /// Like `add`, for a file streamed into the storage, see `HashingReader`.
+    pub fn add_checksum(&mut self, file_handle: &FileHandleRef, size: u64, sha3_256: HashValue) {
+        self.files.push(FileChecksum {
+            file_handle: file_handle.to_string(),
+            size,
+            sha3_256,
+        })
+    }
+
+    pub async fn write(
+        &self,
+        storage: &Arc<dyn BackupStorage>,
+        backup_handle: &BackupHandleRef,
+    ) -> Result<FileHandle> {
+        let (handle, mut file) = storage
+            .create_for_write(backup_handle, Self::file_name())
+            .await?;
+        file.write_all(&serde_json::to_vec(self)?).await?;
+        file.shutdown().await?;
+        Ok(handle)
+    }
+
+    /// Makes sure every file referred to by a backup manifest is covered.
+    pub fn ensure_covers<'a>(
+        &self,
+        file_handles: impl IntoIterator<Item = &'a FileHandleRef>,
+    ) -> Result<()> {
+        let covered: HashSet<&str> = self.files.iter().map(|f| f.file_handle.as_str()).collect();
+        for file_handle in file_handles {
+            ensure!(
+                covered.contains(file_handle),
+                "File {} is not listed in the integrity manifest.",
+                file_handle,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks the files of a backup against its integrity manifest as a restore reads them, rather
+/// than downloading them all beforehand, see `open_for_read`.
+#[derive(Clone, Default)]
+pub struct BackupIntegrity {
+    /// `None` for backups created without an integrity manifest.
+    files: Option<Arc<HashMap<FileHandle, FileChecksum>>>,
+}
+
+impl BackupIntegrity {
+    /// Loads the integrity manifest referred to by a backup manifest, if any, making sure it lists
+    /// all files the backup manifest refers to.
+    pub async fn load<'a>(
+        storage: &Arc<dyn BackupStorage>,
+        integrity: Option<&FileHandleRef>,
+        file_handles: impl IntoIterator<Item = &'a FileHandleRef>,
+    ) -> Result<Self> {
+        let handle = match integrity {
+            Some(handle) => handle,
+            None => {
+                warn!("Backup created without an integrity manifest, skipping integrity check.");
+                return Ok(Self::default());
+            },
+        };
+        let manifest: IntegrityManifest = storage.load_json_file(handle).await?;
+        manifest.ensure_covers(file_handles)?;
+        Ok(Self {
+            files: Some(Arc::new(
+                manifest
+                    .files
+                    .into_iter()
+                    .map(|file| (file.file_handle.clone(), file))
+                    .collect(),
+            )),
+        })
+    }
+
+    /// Opens the file for read, the read failing at the end of the file if it doesn't match the
+    /// integrity manifest, so that a truncated or corrupted file fails the restore before what's
+    /// read from it is used.
+    pub async fn open_for_read(
+        &self,
+        storage: &Arc<dyn BackupStorage>,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let file = storage.open_for_read(file_handle).await?;
+        Ok(match &self.files {
+            Some(files) => {
+                let expected = files.get(file_handle).cloned().ok_or_else(|| {
+                    anyhow!(
+                        "File {} is not listed in the integrity manifest.",
+                        file_handle
+                    )
+                })?;
+                Box::new(HashingReader::verifying(file, expected))
+            },
+            None => file,
+        })
+    }
+
+    pub async fn load_bcs_file<T: DeserializeOwned>(
+        &self,
+        storage: &Arc<dyn BackupStorage>,
+        file_handle: &FileHandleRef,
+    ) -> Result<T> {
+        let mut bytes = Vec::new();
+        self.open_for_read(storage, file_handle)
+            .await?
+            .read_to_end(&mut bytes)
+            .await?;
+        Ok(bcs::from_bytes(&bytes)?)
+    }
+}
+
+/// Hashes a file as it's read, for its checksum to be known without buffering it, and checks it
+/// against the expected one at its end, if given.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha3_256,
+    size: u64,
+    /// `None` once checked.
+    expected: Option<FileChecksum>,
+}
+
+impl<R> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha3_256::new(),
+            size: 0,
+            expected: None,
+        }
+    }
+
+    fn verifying(inner: R, expected: FileChecksum) -> Self {
+        Self {
+            expected: Some(expected),
+            ..Self::new(inner)
+        }
+    }
+
+    /// Size and hash of what was read so far.
+    pub fn checksum(&self) -> (u64, HashValue) {
+        let hash = HashValue::from_slice(self.hasher.clone().finalize().as_slice())
+            .expect("SHA3-256 hashes are 32 bytes.");
+        (self.size, hash)
+    }
+
+    fn verify(&self, expected: &FileChecksum) -> Result<()> {
+        let (size, hash) = self.checksum();
+        ensure!(
+            size == expected.size,
+            "File {} is truncated or overwritten. expected size: {}, actual: {}",
+            expected.file_handle,
+            expected.size,
+            size,
+        );
+        ensure!(
+            hash == expected.sha3_256,
+            "File {} is corrupted. expected hash: {}, actual: {}",
+            expected.file_handle,
+            expected.sha3_256,
+            hash,
+        );
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let at_capacity = buf.remaining() == 0;
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[filled..];
+        if !read.is_empty() || at_capacity {
+            this.hasher.update(read);
+            this.size += read.len() as u64;
+            return Poll::Ready(Ok(()));
+        }
+
+        // End of the file.
+        let result = match this.expected.take() {
+            Some(expected) => this.verify(&expected),
+            None => Ok(()),
+        };
+        Poll::Ready(result.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backup_types::integrity::{BackupIntegrity, HashingReader, IntegrityManifest},
+        storage::{local_fs::LocalFs, BackupStorage},
+    };
+    use aptos_crypto::HashValue;
+    use aptos_temppath::TempPath;
+    use std::{str::FromStr, sync::Arc};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        runtime::Runtime,
+    };
+
+    #[test]
+    fn test_integrity_manifest() {
+        Runtime::new().unwrap().block_on(async {
+            let tmpdir = TempPath::new();
+            tmpdir.create_as_dir().unwrap();
+            let storage: Arc<dyn BackupStorage> =
+                Arc::new(LocalFs::new(tmpdir.path().to_path_buf()));
+            let backup_handle = storage
+                .create_backup(&FromStr::from_str("backup").unwrap())
+                .await
+                .unwrap();
+
+            let mut manifest = IntegrityManifest::default();
+            let mut handles = vec![];
+            for (name, content) in [("a.chunk", b"aaaa".to_vec()), ("b.chunk", vec![1u8; 100])] {
+                let (handle, mut file) = storage
+                    .create_for_write(&backup_handle, &FromStr::from_str(name).unwrap())
+                    .await
+                    .unwrap();
+                file.write_all(&content).await.unwrap();
+                file.shutdown().await.unwrap();
+                manifest.add(&handle, &content);
+                handles.push(handle);
+            }
+            let integrity = manifest.write(&storage, &backup_handle).await.unwrap();
+
+            let checked = BackupIntegrity::load(
+                &storage,
+                Some(&integrity),
+                handles.iter().map(String::as_str),
+            )
+            .await
+            .unwrap();
+            let read = |handle: String| {
+                let storage = storage.clone();
+                let checked = checked.clone();
+                async move {
+                    let mut bytes = Vec::new();
+                    checked
+                        .open_for_read(&storage, &handle)
+                        .await?
+                        .read_to_end(&mut bytes)
+                        .await?;
+                    anyhow::Result::<_>::Ok(bytes)
+                }
+            };
+            assert_eq!(read(handles[0].clone()).await.unwrap(), b"aaaa");
+
+            // a file unknown to the integrity manifest
+            assert!(
+                BackupIntegrity::load(&storage, Some(&integrity), ["backup/c.chunk"])
+                    .await
+                    .is_err()
+            );
+
+            // truncated upload, caught at the end of the read
+            std::fs::write(tmpdir.path().join(&handles[1]), [1u8; 99]).unwrap();
+            assert!(read(handles[1].clone()).await.is_err());
+            // corrupted, of the same size
+            std::fs::write(tmpdir.path().join(&handles[1]), [2u8; 100]).unwrap();
+            assert!(read(handles[1].clone()).await.is_err());
+
+            // streamed into the storage, hashed on the way
+            let mut reader = HashingReader::new(&[3u8; 1000][..]);
+            tokio::io::copy(&mut reader, &mut tokio::io::sink())
+                .await
+                .unwrap();
+            assert_eq!(
+                reader.checksum(),
+                (1000, HashValue::sha3_256_of(&[3u8; 1000]))
+            );
+        })
+    }
+}
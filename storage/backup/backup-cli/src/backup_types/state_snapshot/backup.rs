@@ -3,9 +3,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backup_types::state_snapshot::manifest::{StateSnapshotBackup, StateSnapshotChunk},
+    backup_types::{
+        integrity::{HashingReader, IntegrityManifest},
+        state_snapshot::manifest::{StateSnapshotBackup, StateSnapshotChunk},
+    },
     metadata::Metadata,
-    storage::{BackupHandleRef, BackupStorage, FileHandle, ShellSafeName},
+    storage::{
+        tags::{ObjectTags, TaggedStorage},
+        BackupHandleRef, BackupStorage, FileHandle, ShellSafeName,
+    },
     utils::{
         backup_service_client::BackupServiceClient, read_record_bytes::ReadRecordBytes,
         should_cut_chunk, storage_ext::BackupStorageExt, GlobalBackupOpt,
@@ -55,7 +61,11 @@ impl StateSnapshotBackupController {
             version: None,
             max_chunk_size: global_opt.max_chunk_size,
             client,
-            storage,
+            storage: Arc::new(TaggedStorage::new(
+                storage,
+                ObjectTags::for_backup("state_snapshot", global_opt.chain_id)
+                    .with("epoch", opt.epoch),
+            )),
         }
     }
 
@@ -77,6 +87,7 @@ impl StateSnapshotBackupController {
             .await?;
 
         let mut chunks = vec![];
+        let mut integrity = IntegrityManifest::default();
 
         let mut state_snapshot_file = self.client.get_state_snapshot(self.version()).await?;
         let mut prev_record_bytes = state_snapshot_file
@@ -100,6 +111,7 @@ impl StateSnapshotBackupController {
                         current_idx,
                         chunk_first_key,
                         Self::parse_key(&prev_record_bytes)?,
+                        &mut integrity,
                     )
                     .await?;
                 chunks.push(chunk);
@@ -130,11 +142,12 @@ impl StateSnapshotBackupController {
                 current_idx,
                 chunk_first_key,
                 Self::parse_key(&prev_record_bytes)?,
+                &mut integrity,
             )
             .await?;
         chunks.push(chunk);
 
-        self.write_manifest(&backup_handle, chunks).await
+        self.write_manifest(&backup_handle, chunks, integrity).await
     }
 }
 
@@ -147,23 +160,23 @@ impl StateSnapshotBackupController {
         format!("state_epoch_{}_ver_{}", self.epoch, self.version())
     }
 
-    fn manifest_name() -> &'static ShellSafeName {
+    pub(crate) fn manifest_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("state.manifest").unwrap());
         &NAME
     }
 
-    fn proof_name() -> &'static ShellSafeName {
+    pub(crate) fn proof_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("state.proof").unwrap());
         &NAME
     }
 
-    fn chunk_name(first_idx: usize) -> ShellSafeName {
+    pub(crate) fn chunk_name(first_idx: usize) -> ShellSafeName {
         format!("{}-.chunk", first_idx).try_into().unwrap()
     }
 
-    fn chunk_proof_name(first_idx: usize, last_idx: usize) -> ShellSafeName {
+    pub(crate) fn chunk_proof_name(first_idx: usize, last_idx: usize) -> ShellSafeName {
         format!("{}-{}.proof", first_idx, last_idx)
             .try_into()
             .unwrap()
@@ -197,6 +210,7 @@ impl StateSnapshotBackupController {
         last_idx: usize,
         first_key: HashValue,
         last_key: HashValue,
+        integrity: &mut IntegrityManifest,
     ) -> Result<StateSnapshotChunk> {
         let (chunk_handle, mut chunk_file) = self
             .storage
@@ -204,19 +218,20 @@ impl StateSnapshotBackupController {
             .await?;
         chunk_file.write_all(chunk_bytes).await?;
         chunk_file.shutdown().await?;
+        integrity.add(&chunk_handle, chunk_bytes);
         let (proof_handle, mut proof_file) = self
             .storage
             .create_for_write(backup_handle, &Self::chunk_proof_name(first_idx, last_idx))
             .await?;
-        tokio::io::copy(
-            &mut self
-                .client
+        let mut proof = HashingReader::new(
+            self.client
                 .get_account_range_proof(last_key, self.version())
                 .await?,
-            &mut proof_file,
-        )
-        .await?;
+        );
+        tokio::io::copy(&mut proof, &mut proof_file).await?;
         proof_file.shutdown().await?;
+        let (size, hash) = proof.checksum();
+        integrity.add_checksum(&proof_handle, size, hash);
 
         Ok(StateSnapshotChunk {
             first_idx,
@@ -232,6 +247,7 @@ impl StateSnapshotBackupController {
         &self,
         backup_handle: &BackupHandleRef,
         chunks: Vec<StateSnapshotChunk>,
+        mut integrity: IntegrityManifest,
     ) -> Result<FileHandle> {
         let proof_bytes = self.client.get_state_root_proof(self.version()).await?;
         let (txn_info, _): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
@@ -243,13 +259,16 @@ impl StateSnapshotBackupController {
             .await?;
         proof_file.write_all(&proof_bytes).await?;
         proof_file.shutdown().await?;
+        integrity.add(&proof_handle, &proof_bytes);
 
+        let integrity_handle = integrity.write(&self.storage, backup_handle).await?;
         let manifest = StateSnapshotBackup {
             epoch: self.epoch,
             version: self.version(),
             root_hash: txn_info.transaction_info().ensure_state_checkpoint_hash()?,
             chunks,
             proof: proof_handle,
+            integrity: Some(integrity_handle),
         };
 
         let (manifest_handle, mut manifest_file) = self
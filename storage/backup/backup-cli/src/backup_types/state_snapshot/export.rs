@@ -0,0 +1,287 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    backup_types::{
+        integrity::BackupIntegrity,
+        state_snapshot::{
+            manifest::StateSnapshotBackup,
+            restore::{verify_root_hash, StateSnapshotRestoreController},
+        },
+    },
+    storage::{BackupStorage, FileHandle},
+    utils::{storage_ext::BackupStorageExt, stream::StreamX},
+};
+use anyhow::{anyhow, Result};
+use aptos_db::AptosDB;
+use aptos_logger::prelude::*;
+use aptos_types::{
+    access_path::Path,
+    state_store::{
+        state_key::{StateKey, StateKeyInner},
+        state_value::StateValue,
+    },
+    transaction::Version,
+};
+use futures::{stream, TryStreamExt};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateItemKind {
+    Resource,
+    ResourceGroup,
+    Module,
+    TableItem,
+    /// A key that isn't a well formed access path or table item.
+    Raw,
+}
+
+/// A state item as exported, one per line of JSON. Values are the BCS bytes in hex, to be decoded
+/// with the Move type they are tagged with.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ExportedStateItem {
+    pub kind: StateItemKind,
+    /// Account holding the resource, resource group or module.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Move type of the resource, or of the resource group, e.g.
+    /// `0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_tag: Option<String>,
+    /// Module id, e.g. `0x1::coin`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_handle: Option<String>,
+    /// BCS bytes of the key of the table item, in hex.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_key: Option<String>,
+    /// BCS bytes of the key, in hex, for raw keys only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_key: Option<String>,
+    pub value: String,
+}
+
+impl ExportedStateItem {
+    pub fn new(key: &StateKey, value: &StateValue) -> Self {
+        let mut item = Self {
+            kind: StateItemKind::Raw,
+            address: None,
+            type_tag: None,
+            module: None,
+            table_handle: None,
+            table_key: None,
+            raw_key: None,
+            value: hex::encode(value.bytes()),
+        };
+        match key.inner() {
+            StateKeyInner::AccessPath(access_path) => {
+                match bcs::from_bytes::<Path>(&access_path.path) {
+                    Ok(path) => {
+                        item.address = Some(access_path.address.to_hex_literal());
+                        match path {
+                            Path::Resource(struct_tag) => {
+                                item.kind = StateItemKind::Resource;
+                                item.type_tag = Some(struct_tag.to_string());
+                            },
+                            Path::ResourceGroup(struct_tag) => {
+                                item.kind = StateItemKind::ResourceGroup;
+                                item.type_tag = Some(struct_tag.to_string());
+                            },
+                            Path::Code(module_id) => {
+                                item.kind = StateItemKind::Module;
+                                item.module = Some(module_id.short_str_lossless());
+                            },
+                        }
+                    },
+                    Err(_) => item.raw_key = key.encode().ok().map(hex::encode),
+                }
+            },
+            StateKeyInner::TableItem { handle, key } => {
+                item.kind = StateItemKind::TableItem;
+                item.table_handle = Some(handle.0.to_hex_literal());
+                item.table_key = Some(hex::encode(key));
+            },
+            StateKeyInner::Raw(raw_key) => item.raw_key = Some(hex::encode(raw_key)),
+        }
+        item
+    }
+}
+
+/// Writes the state items of a snapshot to a file, one JSON line each, for them to be consumed
+/// without running a node, e.g. loaded into an analytics warehouse.
+pub struct StateSnapshotExportController {
+    output: PathBuf,
+}
+
+impl StateSnapshotExportController {
+    pub fn new(output: PathBuf) -> Self {
+        Self { output }
+    }
+
+    /// Exports a state snapshot backup, as is, without restoring it. Its root hash is checked
+    /// against its proof, but not against the ledger infos of the epoch ending backups.
+    pub async fn export_backup(
+        self,
+        storage: Arc<dyn BackupStorage>,
+        manifest_handle: FileHandle,
+        concurrent_downloads: usize,
+    ) -> Result<()> {
+        let start = Instant::now();
+        info!(
+            "State snapshot export started. Manifest: {}",
+            manifest_handle
+        );
+        let manifest: StateSnapshotBackup = storage.load_json_file(&manifest_handle).await?;
+        let integrity = BackupIntegrity::load(
+            &storage,
+            manifest.integrity.as_deref(),
+            manifest
+                .chunks
+                .iter()
+                .map(|c| c.blobs.as_str())
+                .chain([manifest.proof.as_str()]),
+        )
+        .await?;
+        verify_root_hash(&storage, &integrity, &manifest, None).await?;
+
+        let mut writer = self.writer()?;
+        let num_chunks = manifest.chunks.len();
+        let futs_iter = manifest.chunks.into_iter().map(|chunk| {
+            let storage = storage.clone();
+            let integrity = integrity.clone();
+            async move {
+                tokio::spawn(async move {
+                    StateSnapshotRestoreController::read_state_value(
+                        &storage,
+                        &integrity,
+                        chunk.blobs,
+                    )
+                    .await
+                })
+                .await?
+            }
+        });
+        let mut futs_stream =
+            stream::iter(futs_iter).buffered_x(concurrent_downloads * 2, concurrent_downloads);
+        let mut chunk_idx = 0;
+        while let Some(values) = futs_stream.try_next().await? {
+            for (key, value) in &values {
+                writer.write(key, value)?;
+            }
+            info!(
+                chunk = chunk_idx,
+                total_chunks = num_chunks,
+                items = writer.num_items,
+                "State chunk exported."
+            );
+            chunk_idx += 1;
+        }
+        let num_items = writer.finish()?;
+        info!(
+            version = manifest.version,
+            items = num_items,
+            time = start.elapsed().as_secs(),
+            "State snapshot exported."
+        );
+        Ok(())
+    }
+
+    /// Exports the state at the version from a DB, e.g. a restored one.
+    pub fn export_db(self, db: &AptosDB, version: Version) -> Result<()> {
+        let start = Instant::now();
+        info!(version = version, "State export started.");
+        let mut writer = self.writer()?;
+        for item in db.get_backup_handler().get_account_iter(version)? {
+            let (key, value) = item?;
+            writer.write(&key, &value)?;
+            if writer.num_items % 1_000_000 == 0 {
+                info!(items = writer.num_items, "Exporting state.");
+            }
+        }
+        let num_items = writer.finish()?;
+        info!(
+            version = version,
+            items = num_items,
+            time = start.elapsed().as_secs(),
+            "State exported."
+        );
+        Ok(())
+    }
+
+    fn writer(&self) -> Result<JsonLinesWriter> {
+        let file = File::create(&self.output)
+            .map_err(|e| anyhow!("Failed to create {}: {}", self.output.display(), e))?;
+        Ok(JsonLinesWriter {
+            inner: BufWriter::new(file),
+            num_items: 0,
+        })
+    }
+}
+
+struct JsonLinesWriter {
+    inner: BufWriter<File>,
+    num_items: usize,
+}
+
+impl JsonLinesWriter {
+    fn write(&mut self, key: &StateKey, value: &StateValue) -> Result<()> {
+        serde_json::to_writer(&mut self.inner, &ExportedStateItem::new(key, value))?;
+        self.inner.write_all(b"\n")?;
+        self.num_items += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize> {
+        self.inner.flush()?;
+        Ok(self.num_items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportedStateItem, StateItemKind};
+    use aptos_types::{
+        access_path::AccessPath,
+        account_address::AccountAddress,
+        account_config::AccountResource,
+        state_store::{state_key::StateKey, state_value::StateValue, table::TableHandle},
+    };
+    use move_core_types::move_resource::MoveStructType;
+
+    #[test]
+    fn test_exported_state_item() {
+        let resource_key = StateKey::access_path(
+            AccessPath::resource_access_path(AccountAddress::ONE, AccountResource::struct_tag())
+                .unwrap(),
+        );
+        let item = ExportedStateItem::new(&resource_key, &StateValue::new_legacy(vec![1, 2]));
+        assert_eq!(item.kind, StateItemKind::Resource);
+        assert_eq!(item.address.as_deref(), Some("0x1"));
+        assert_eq!(item.type_tag.as_deref(), Some("0x1::account::Account"));
+        assert_eq!(item.value, "0102");
+        assert_eq!(
+            serde_json::to_string(&item).unwrap(),
+            r#"{"kind":"resource","address":"0x1","type_tag":"0x1::account::Account","value":"0102"}"#
+        );
+
+        let table_key = StateKey::table_item(TableHandle(AccountAddress::TWO), vec![0xAB]);
+        let item = ExportedStateItem::new(&table_key, &StateValue::new_legacy(vec![]));
+        assert_eq!(item.kind, StateItemKind::TableItem);
+        assert_eq!(item.table_handle.as_deref(), Some("0x2"));
+        assert_eq!(item.table_key.as_deref(), Some("ab"));
+        assert_eq!(item.address, None);
+
+        let item = ExportedStateItem::new(&StateKey::raw(vec![7]), &StateValue::new_legacy(vec![]));
+        assert_eq!(item.kind, StateItemKind::Raw);
+        assert_eq!(item.raw_key.as_deref(), Some("07"));
+    }
+}
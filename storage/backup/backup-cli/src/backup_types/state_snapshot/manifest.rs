@@ -49,4 +49,8 @@ pub struct StateSnapshotBackup {
     /// `EpochStateBackup` recovered prior to this to the DB; Requiring it to be in the same epoch
     /// limits the requirement on such `EpochStateBackup` to no older than the same epoch.
     pub proof: FileHandle,
+    /// `IntegrityManifest` covering all chunk and proof files, absent in backups taken by older
+    /// versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<FileHandle>,
 }
@@ -4,11 +4,14 @@
 
 use crate::{
     backup_types::{
-        epoch_ending::restore::EpochHistory, state_snapshot::manifest::StateSnapshotBackup,
+        epoch_ending::restore::EpochHistory,
+        integrity::BackupIntegrity,
+        state_snapshot::manifest::{StateSnapshotBackup, StateSnapshotChunk},
     },
     metrics::{
         restore::{
-            STATE_SNAPSHOT_LEAF_INDEX, STATE_SNAPSHOT_TARGET_LEAF_INDEX, STATE_SNAPSHOT_VERSION,
+            STATE_SNAPSHOT_LEAF_INDEX, STATE_SNAPSHOT_PRIORITY_VALUES,
+            STATE_SNAPSHOT_TARGET_LEAF_INDEX, STATE_SNAPSHOT_VERSION,
         },
         verify::{
             VERIFY_STATE_SNAPSHOT_LEAF_INDEX, VERIFY_STATE_SNAPSHOT_TARGET_LEAF_INDEX,
@@ -23,11 +26,13 @@ use crate::{
     },
 };
 use anyhow::{anyhow, ensure, Result};
+use aptos_crypto::hash::CryptoHash;
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
 use aptos_storage_interface::StateSnapshotReceiver;
 use aptos_types::{
     access_path::Path,
+    account_address::AccountAddress,
     ledger_info::LedgerInfoWithSignatures,
     on_chain_config::{TimedFeatureOverride, TimedFeatures},
     proof::TransactionInfoWithProof,
@@ -53,6 +58,57 @@ pub struct StateSnapshotRestoreOpt {
     pub version: Version,
     #[clap(long)]
     pub validate_modules: bool,
+    #[clap(flatten)]
+    pub priority_state: PriorityStateOpt,
+}
+
+/// State restored ahead of the rest of a snapshot. The tree of a snapshot can only be restored in
+/// the order of the hashes of the keys, which scatters the state of any account over the whole
+/// restore, so the values of the keys matched are written in a first pass over the snapshot, for
+/// them to be read from the DB, e.g. by a read-only instance, long before the restore completes.
+#[derive(Clone, Default, Parser)]
+pub struct PriorityStateOpt {
+    #[clap(
+        long = "priority-account",
+        help = "(multiple) Account whose state is restored ahead of the rest of the state \
+        snapshot, for incident recovery. The snapshot is read twice, and the values restored \
+        ahead are only verified against the root hash once the rest of the restore gets to them."
+    )]
+    pub accounts: Vec<AccountAddress>,
+    #[clap(
+        long = "priority-resource-prefix",
+        help = "(multiple) Prefix of the types of the resources restored ahead of the rest of \
+        the state snapshot, whichever accounts hold them, e.g. 0x1::coin::CoinStore."
+    )]
+    pub resource_prefixes: Vec<String>,
+}
+
+impl PriorityStateOpt {
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.resource_prefixes.is_empty()
+    }
+
+    pub fn matches(&self, key: &StateKey) -> bool {
+        let access_path = match key.inner() {
+            StateKeyInner::AccessPath(access_path) => access_path,
+            StateKeyInner::TableItem { .. } | StateKeyInner::Raw(_) => return false,
+        };
+        if self.accounts.contains(&access_path.address) {
+            return true;
+        }
+        if self.resource_prefixes.is_empty() {
+            return false;
+        }
+        match bcs::from_bytes::<Path>(&access_path.path) {
+            Ok(Path::Resource(struct_tag)) | Ok(Path::ResourceGroup(struct_tag)) => {
+                let type_ = struct_tag.to_string();
+                self.resource_prefixes
+                    .iter()
+                    .any(|prefix| type_.starts_with(prefix.as_str()))
+            },
+            Ok(Path::Code(_)) | Err(_) => false,
+        }
+    }
 }
 
 pub struct StateSnapshotRestoreController {
@@ -67,6 +123,7 @@ pub struct StateSnapshotRestoreController {
     epoch_history: Option<Arc<EpochHistory>>,
     concurrent_downloads: usize,
     validate_modules: bool,
+    priority_state: PriorityStateOpt,
 }
 
 impl StateSnapshotRestoreController {
@@ -85,6 +142,7 @@ impl StateSnapshotRestoreController {
             epoch_history,
             concurrent_downloads: global_opt.concurrent_downloads,
             validate_modules: opt.validate_modules,
+            priority_state: opt.priority_state,
         }
     }
 
@@ -117,21 +175,23 @@ impl StateSnapshotRestoreController {
 
         let manifest: StateSnapshotBackup =
             self.storage.load_json_file(&self.manifest_handle).await?;
-        let (txn_info_with_proof, li): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
-            self.storage.load_bcs_file(&manifest.proof).await?;
-        txn_info_with_proof.verify(li.ledger_info(), manifest.version)?;
-        let state_root_hash = txn_info_with_proof
-            .transaction_info()
-            .ensure_state_checkpoint_hash()?;
-        ensure!(
-            state_root_hash == manifest.root_hash,
-            "Root hash mismatch with that in proof. root hash: {}, expected: {}",
-            manifest.root_hash,
-            state_root_hash,
-        );
-        if let Some(epoch_history) = self.epoch_history.as_ref() {
-            epoch_history.verify_ledger_info(&li)?;
-        }
+        let integrity = BackupIntegrity::load(
+            &self.storage,
+            manifest.integrity.as_deref(),
+            manifest
+                .chunks
+                .iter()
+                .flat_map(|c| [c.blobs.as_str(), c.proof.as_str()])
+                .chain([manifest.proof.as_str()]),
+        )
+        .await?;
+        verify_root_hash(
+            &self.storage,
+            &integrity,
+            &manifest,
+            self.epoch_history.as_ref(),
+        )
+        .await?;
 
         let receiver = Arc::new(Mutex::new(Some(
             self.run_mode
@@ -157,6 +217,14 @@ impl StateSnapshotRestoreController {
         let total_chunks = manifest.chunks.len();
 
         let resume_point_opt = receiver.lock().as_mut().unwrap().previous_key_hash()?;
+        // A resumed restore has written the priority values already.
+        if resume_point_opt.is_none()
+            && !self.priority_state.is_empty()
+            && !self.run_mode.is_verify()
+        {
+            self.restore_priority_state(&integrity, &manifest.chunks)
+                .await?;
+        }
         let chunks = if let Some(resume_point) = resume_point_opt {
             manifest
                 .chunks
@@ -180,10 +248,12 @@ impl StateSnapshotRestoreController {
         let storage = self.storage.clone();
         let futs_iter = chunks.into_iter().enumerate().map(|(chunk_idx, chunk)| {
             let storage = storage.clone();
+            let integrity = integrity.clone();
             async move {
                 tokio::spawn(async move {
-                    let blobs = Self::read_state_value(&storage, chunk.blobs.clone()).await?;
-                    let proof = storage.load_bcs_file(&chunk.proof).await?;
+                    let blobs =
+                        Self::read_state_value(&storage, &integrity, chunk.blobs.clone()).await?;
+                    let proof = integrity.load_bcs_file(&storage, &chunk.proof).await?;
                     Result::<_>::Ok((chunk_idx, chunk, blobs, proof))
                 })
                 .await?
@@ -222,6 +292,55 @@ impl StateSnapshotRestoreController {
         Ok(())
     }
 
+    /// Writes the values of the priority keys ahead of the restore of the snapshot, which writes
+    /// them again as it gets to them.
+    async fn restore_priority_state(
+        &self,
+        integrity: &BackupIntegrity,
+        chunks: &[StateSnapshotChunk],
+    ) -> Result<()> {
+        let start = Instant::now();
+        let priority_state = Arc::new(self.priority_state.clone());
+        let futs_iter = chunks.iter().map(|chunk| {
+            let storage = self.storage.clone();
+            let integrity = integrity.clone();
+            let priority_state = priority_state.clone();
+            let blobs = chunk.blobs.clone();
+            async move {
+                tokio::spawn(async move {
+                    let values = Self::read_state_value(&storage, &integrity, blobs).await?;
+                    Result::<_>::Ok(
+                        values
+                            .into_iter()
+                            .filter(|(key, _)| priority_state.matches(key))
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .await?
+            }
+        });
+        let con = self.concurrent_downloads;
+        let mut futs_stream = stream::iter(futs_iter).buffered_x(con * 2, con);
+        let mut num_values = 0;
+        while let Some(values) = futs_stream.try_next().await? {
+            if values.is_empty() {
+                continue;
+            }
+            num_values += values.len();
+            let run_mode = self.run_mode.clone();
+            let version = self.version;
+            tokio::task::spawn_blocking(move || run_mode.save_state_values_ahead(version, &values))
+                .await??;
+            STATE_SNAPSHOT_PRIORITY_VALUES.set(num_values as i64);
+        }
+        info!(
+            num_values = num_values,
+            time = start.elapsed().as_secs(),
+            "Priority state values restored ahead of the state snapshot.",
+        );
+        Ok(())
+    }
+
     fn validate_modules(blob: &[(StateKey, StateValue)]) {
         let config = verifier_config(
             false,
@@ -243,11 +362,12 @@ impl StateSnapshotRestoreController {
         }
     }
 
-    async fn read_state_value(
+    pub(crate) async fn read_state_value(
         storage: &Arc<dyn BackupStorage>,
+        integrity: &BackupIntegrity,
         file_handle: FileHandle,
     ) -> Result<Vec<(StateKey, StateValue)>> {
-        let mut file = storage.open_for_read(&file_handle).await?;
+        let mut file = integrity.open_for_read(storage, &file_handle).await?;
 
         let mut chunk = vec![];
 
@@ -258,3 +378,61 @@ impl StateSnapshotRestoreController {
         Ok(chunk)
     }
 }
+
+/// Checks the root hash of the snapshot is that of the state at its version, as proven by a
+/// ledger info signed by the validators of the epoch, if the epoch history is known.
+pub(crate) async fn verify_root_hash(
+    storage: &Arc<dyn BackupStorage>,
+    integrity: &BackupIntegrity,
+    manifest: &StateSnapshotBackup,
+    epoch_history: Option<&Arc<EpochHistory>>,
+) -> Result<()> {
+    let (txn_info_with_proof, li): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
+        integrity.load_bcs_file(storage, &manifest.proof).await?;
+    txn_info_with_proof.verify(li.ledger_info(), manifest.version)?;
+    let state_root_hash = txn_info_with_proof
+        .transaction_info()
+        .ensure_state_checkpoint_hash()?;
+    ensure!(
+        state_root_hash == manifest.root_hash,
+        "Root hash mismatch with that in proof. root hash: {}, expected: {}",
+        manifest.root_hash,
+        state_root_hash,
+    );
+    if let Some(epoch_history) = epoch_history {
+        epoch_history.verify_ledger_info(&li)?;
+    }
+    Ok(())
+}
+
+/// Checks a chunk holds the values the manifest says, in key order. Unlike adding it to a restore,
+/// this doesn't check the chunk adds up to the root hash, which needs all the chunks before it.
+pub(crate) async fn verify_chunk_content(
+    storage: &Arc<dyn BackupStorage>,
+    integrity: &BackupIntegrity,
+    chunk: &StateSnapshotChunk,
+) -> Result<()> {
+    let values =
+        StateSnapshotRestoreController::read_state_value(storage, integrity, chunk.blobs.clone())
+            .await?;
+    ensure!(
+        values.len() == chunk.last_idx + 1 - chunk.first_idx,
+        "Number of values in chunk {} doesn't match that in manifest. first_idx: {}, last_idx: {}, values in chunk: {}",
+        chunk.blobs,
+        chunk.first_idx,
+        chunk.last_idx,
+        values.len(),
+    );
+    let key_hashes = values.iter().map(|(key, _)| key.hash()).collect::<Vec<_>>();
+    ensure!(
+        key_hashes.first() == Some(&chunk.first_key) && key_hashes.last() == Some(&chunk.last_key),
+        "Keys of chunk {} don't match those in manifest.",
+        chunk.blobs,
+    );
+    ensure!(
+        key_hashes.windows(2).all(|pair| pair[0] < pair[1]),
+        "Keys of chunk {} are not in order.",
+        chunk.blobs,
+    );
+    Ok(())
+}
@@ -5,24 +5,84 @@
 use crate::{
     backup_types::state_snapshot::{
         backup::{StateSnapshotBackupController, StateSnapshotBackupOpt},
-        restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
+        restore::{PriorityStateOpt, StateSnapshotRestoreController, StateSnapshotRestoreOpt},
     },
     storage::{local_fs::LocalFs, BackupStorage},
     utils::{
         backup_service_client::BackupServiceClient,
         test_utils::{start_local_backup_service, tmp_db_with_random_content},
         ConcurrentDownloadsOpt, GlobalBackupOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt,
-        RocksdbOpt, TrustedWaypointOpt,
+        RocksdbOpt, TargetDbOpt, TrustedWaypointOpt,
     },
 };
 use aptos_db::AptosDB;
 use aptos_storage_interface::DbReader;
 use aptos_temppath::TempPath;
-use std::{convert::TryInto, sync::Arc};
+use aptos_types::{
+    access_path::AccessPath, account_address::AccountAddress, state_store::state_key::StateKey,
+};
+use clap::Parser;
+use move_core_types::language_storage::{ModuleId, StructTag};
+use std::{convert::TryInto, str::FromStr, sync::Arc};
 use tokio::time::Duration;
 
 #[test]
 fn end_to_end() {
+    end_to_end_impl(RocksdbOpt::default(), PriorityStateOpt::default())
+}
+
+#[test]
+fn end_to_end_with_sst_ingestion() {
+    let sst_dir = TempPath::new();
+    end_to_end_impl(
+        RocksdbOpt::from_iter(vec![
+            "exe",
+            "--state-snapshot-sst-dir",
+            sst_dir.path().to_str().unwrap(),
+        ]),
+        PriorityStateOpt::default(),
+    )
+}
+
+#[test]
+fn end_to_end_with_priority_state() {
+    // All the resources, ahead of the rest.
+    end_to_end_impl(
+        RocksdbOpt::default(),
+        PriorityStateOpt::from_iter(vec!["exe", "--priority-resource-prefix", "0x"]),
+    )
+}
+
+#[test]
+fn priority_state_matches() {
+    let account = AccountAddress::from_hex_literal("0xcafe").unwrap();
+    let resource = |address, type_: &str| {
+        StateKey::access_path(
+            AccessPath::resource_access_path(address, StructTag::from_str(type_).unwrap()).unwrap(),
+        )
+    };
+    let coin_store = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>";
+    let priority_state = PriorityStateOpt::from_iter(vec![
+        "exe",
+        "--priority-account",
+        "0xcafe",
+        "--priority-resource-prefix",
+        "0x1::coin::CoinStore",
+    ]);
+    assert!(!priority_state.is_empty());
+    assert!(priority_state.matches(&resource(account, "0x1::account::Account")));
+    assert!(
+        priority_state.matches(&StateKey::access_path(AccessPath::code_access_path(
+            ModuleId::from_str("0xcafe::game").unwrap()
+        )))
+    );
+    assert!(priority_state.matches(&resource(AccountAddress::ONE, coin_store)));
+    assert!(!priority_state.matches(&resource(AccountAddress::ONE, "0x1::account::Account")));
+    assert!(!priority_state.matches(&StateKey::raw(b"0xcafe".to_vec())));
+    assert!(PriorityStateOpt::default().is_empty());
+}
+
+fn end_to_end_impl(rocksdb_opt: RocksdbOpt, priority_state: PriorityStateOpt) {
     let (_src_db_dir, src_db, _blocks) = tmp_db_with_random_content();
     let tgt_db_dir = TempPath::new();
     tgt_db_dir.create_as_dir().unwrap();
@@ -64,6 +124,7 @@ fn end_to_end() {
                 StateSnapshotBackupOpt { epoch },
                 GlobalBackupOpt {
                     max_chunk_size: 500,
+                    chain_id: None,
                 },
                 client,
                 Arc::clone(&store),
@@ -78,13 +139,15 @@ fn end_to_end() {
                 manifest_handle,
                 version,
                 validate_modules: false,
+                priority_state,
             },
             GlobalRestoreOpt {
                 dry_run: false,
                 db_dir: Some(tgt_db_dir.path().to_path_buf()),
                 target_version: None, // max
                 trusted_waypoints: TrustedWaypointOpt::default(),
-                rocksdb_opt: RocksdbOpt::default(),
+                rocksdb_opt,
+                target_db: TargetDbOpt::default(),
                 concurrent_downloads: ConcurrentDownloadsOpt::default(),
                 replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
             }
@@ -4,6 +4,7 @@
 
 use crate::{
     backup_types::{
+        epoch_ending::backup::{EpochEndingBackupController, EpochEndingBackupOpt},
         state_snapshot::{
             backup::{StateSnapshotBackupController, StateSnapshotBackupOpt},
             restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
@@ -13,11 +14,13 @@ use crate::{
             restore::{TransactionRestoreController, TransactionRestoreOpt},
         },
     },
+    coordinators::restore::{RestoreCoordinator, RestoreCoordinatorOpt},
+    metadata::cache::MetadataCacheOpt,
     storage::{local_fs::LocalFs, BackupStorage},
     utils::{
         backup_service_client::BackupServiceClient, test_utils::start_local_backup_service,
         ConcurrentDownloadsOpt, GlobalBackupOpt, GlobalRestoreOpt, GlobalRestoreOptions,
-        ReplayConcurrencyLevelOpt, RocksdbOpt, TrustedWaypointOpt,
+        ReplayConcurrencyLevelOpt, RocksdbOpt, TargetDbOpt, TrustedWaypointOpt,
     },
 };
 use aptos_db::AptosDB;
@@ -88,6 +91,7 @@ fn test_end_to_end_impl(d: TestData) {
     // Backup
     let global_backup_opt = GlobalBackupOpt {
         max_chunk_size: 2048,
+        chain_id: None,
     };
     let state_snapshot_manifest = d.state_snapshot_epoch.map(|epoch| {
         rt.block_on(
@@ -123,6 +127,7 @@ fn test_end_to_end_impl(d: TestData) {
         target_version: Some(d.target_ver),
         trusted_waypoints: TrustedWaypointOpt::default(),
         rocksdb_opt: RocksdbOpt::default(),
+        target_db: TargetDbOpt::default(),
         concurrent_downloads: ConcurrentDownloadsOpt::default(),
         replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
     }
@@ -135,6 +140,7 @@ fn test_end_to_end_impl(d: TestData) {
                     manifest_handle: state_snapshot_manifest.unwrap(),
                     version,
                     validate_modules: false,
+                    priority_state: Default::default(),
                 },
                 global_restore_opt.clone(),
                 Arc::clone(&store),
@@ -205,3 +211,147 @@ proptest! {
         test_end_to_end_impl(d)
     }
 }
+
+fn restore_opt(db_dir: &TempPath, target_version: Version) -> GlobalRestoreOptions {
+    GlobalRestoreOpt {
+        dry_run: false,
+        db_dir: Some(db_dir.path().to_path_buf()),
+        target_version: Some(target_version),
+        trusted_waypoints: TrustedWaypointOpt::default(),
+        rocksdb_opt: RocksdbOpt::default(),
+        target_db: TargetDbOpt::default(),
+        concurrent_downloads: ConcurrentDownloadsOpt::default(),
+        replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
+    }
+    .try_into()
+    .unwrap()
+}
+
+#[test]
+#[cfg_attr(feature = "consensus-only-perf-test", ignore)]
+fn test_catch_up() {
+    let db = test_execution_with_storage_impl();
+    let latest_ver = db.get_latest_version().unwrap();
+    let latest_epoch = db.get_latest_epoch_state().unwrap().epoch;
+    let epoch_ending_lis = db
+        .get_epoch_ending_ledger_infos(0, latest_epoch)
+        .unwrap()
+        .ledger_info_with_sigs;
+    // Restored first from a snapshot at the end of an epoch, then caught up to the latest version.
+    let snapshot_li = epoch_ending_lis[1].ledger_info();
+    let (snapshot_epoch, snapshot_ver) = (snapshot_li.epoch(), snapshot_li.version());
+    assert!(snapshot_ver < latest_ver);
+
+    let tgt_db_dir = TempPath::new();
+    tgt_db_dir.create_as_dir().unwrap();
+    let backup_dir = TempPath::new();
+    backup_dir.create_as_dir().unwrap();
+    let metadata_cache_dir = TempPath::new();
+    metadata_cache_dir.create_as_dir().unwrap();
+    let store: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(backup_dir.path().to_path_buf()));
+    let (rt, port) = start_local_backup_service(Arc::clone(&db));
+    let client = Arc::new(BackupServiceClient::new(format!(
+        "http://localhost:{}",
+        port
+    )));
+    let global_backup_opt = GlobalBackupOpt {
+        max_chunk_size: 2048,
+        chain_id: None,
+    };
+    let backup_epoch_endings = |start_epoch, end_epoch| {
+        rt.block_on(
+            EpochEndingBackupController::new(
+                EpochEndingBackupOpt {
+                    start_epoch,
+                    end_epoch,
+                },
+                global_backup_opt.clone(),
+                Arc::clone(&client),
+                Arc::clone(&store),
+            )
+            .run(),
+        )
+        .unwrap();
+    };
+    let backup_transactions = |start_version, last_version: Version| {
+        rt.block_on(
+            TransactionBackupController::new(
+                TransactionBackupOpt {
+                    start_version,
+                    num_transactions: (last_version - start_version + 1) as usize,
+                },
+                global_backup_opt.clone(),
+                Arc::clone(&client),
+                Arc::clone(&store),
+            )
+            .run(),
+        )
+        .unwrap();
+    };
+    let restore = |target_version, catch_up| {
+        rt.block_on(
+            RestoreCoordinator::new(
+                RestoreCoordinatorOpt {
+                    metadata_cache_opt: MetadataCacheOpt::new(Some(metadata_cache_dir.path())),
+                    replay_all: false,
+                    ledger_history_start_version: None,
+                    skip_epoch_endings: false,
+                    catch_up,
+                    epoch_history_only: false,
+                    priority_state: Default::default(),
+                },
+                restore_opt(&tgt_db_dir, target_version),
+                Arc::clone(&store),
+            )
+            .run(),
+        )
+        .unwrap();
+    };
+    let latest_txn_info = || {
+        AptosDB::new_readonly_for_test(&tgt_db_dir)
+            .get_latest_transaction_info_option()
+            .unwrap()
+            .unwrap()
+    };
+
+    // Restore up to the snapshot.
+    backup_epoch_endings(0, snapshot_epoch + 1);
+    rt.block_on(
+        StateSnapshotBackupController::new(
+            StateSnapshotBackupOpt {
+                epoch: snapshot_epoch,
+            },
+            global_backup_opt.clone(),
+            Arc::clone(&client),
+            Arc::clone(&store),
+        )
+        .run(),
+    )
+    .unwrap();
+    backup_transactions(0, snapshot_ver);
+    restore(latest_ver, false);
+    assert_eq!(latest_txn_info().0, snapshot_ver);
+
+    // Back up the rest, and catch up with it.
+    if snapshot_epoch + 1 < latest_epoch {
+        backup_epoch_endings(snapshot_epoch + 1, latest_epoch);
+    }
+    backup_transactions(snapshot_ver + 1, latest_ver);
+    restore(latest_ver, true);
+    assert_eq!(
+        latest_txn_info(),
+        db.get_latest_transaction_info_option().unwrap().unwrap()
+    );
+    assert_eq!(
+        AptosDB::new_readonly_for_test(&tgt_db_dir)
+            .get_accumulator_root_hash(latest_ver)
+            .unwrap(),
+        db.get_accumulator_root_hash(latest_ver).unwrap(),
+    );
+
+    // Nothing more to catch up with.
+    restore(latest_ver, true);
+    assert_eq!(latest_txn_info().0, latest_ver);
+
+    rt.shutdown_timeout(Duration::from_secs(1));
+}
@@ -3,9 +3,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backup_types::transaction::manifest::{TransactionBackup, TransactionChunk},
+    backup_types::{
+        integrity::{HashingReader, IntegrityManifest},
+        transaction::manifest::{TransactionBackup, TransactionChunk},
+    },
     metadata::Metadata,
-    storage::{BackupHandleRef, BackupStorage, FileHandle, ShellSafeName},
+    storage::{
+        tags::{ObjectTags, TaggedStorage},
+        BackupHandleRef, BackupStorage, FileHandle, ShellSafeName,
+    },
     utils::{
         backup_service_client::BackupServiceClient, read_record_bytes::ReadRecordBytes,
         should_cut_chunk, storage_ext::BackupStorageExt, GlobalBackupOpt,
@@ -48,7 +54,15 @@ impl TransactionBackupController {
             num_transactions: opt.num_transactions,
             max_chunk_size: global_opt.max_chunk_size,
             client,
-            storage,
+            storage: Arc::new(TaggedStorage::new(
+                storage,
+                ObjectTags::for_backup("transaction", global_opt.chain_id)
+                    .with("first_version", opt.start_version)
+                    .with(
+                        "last_version",
+                        opt.start_version + (opt.num_transactions as u64).saturating_sub(1),
+                    ),
+            )),
         }
     }
 
@@ -74,6 +88,7 @@ impl TransactionBackupController {
             .await?;
 
         let mut chunks = Vec::new();
+        let mut integrity = IntegrityManifest::default();
         let mut chunk_bytes = Vec::new();
 
         let mut transactions_file = self
@@ -91,6 +106,7 @@ impl TransactionBackupController {
                         &chunk_bytes,
                         chunk_first_ver,
                         current_ver - 1,
+                        &mut integrity,
                     )
                     .await?;
                 chunks.push(chunk);
@@ -117,29 +133,36 @@ impl TransactionBackupController {
                 &chunk_bytes,
                 chunk_first_ver,
                 current_ver - 1,
+                &mut integrity,
             )
             .await?;
         chunks.push(chunk);
 
-        self.write_manifest(&backup_handle, self.start_version, current_ver - 1, chunks)
-            .await
+        self.write_manifest(
+            &backup_handle,
+            self.start_version,
+            current_ver - 1,
+            chunks,
+            integrity,
+        )
+        .await
     }
 
     fn backup_name(&self) -> String {
         format!("transaction_{}-", self.start_version)
     }
 
-    fn manifest_name() -> &'static ShellSafeName {
+    pub(crate) fn manifest_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("transaction.manifest").unwrap());
         &NAME
     }
 
-    fn chunk_name(first_ver: Version) -> ShellSafeName {
+    pub(crate) fn chunk_name(first_ver: Version) -> ShellSafeName {
         format!("{}-.chunk", first_ver).try_into().unwrap()
     }
 
-    fn chunk_proof_name(first_ver: u64, last_ver: Version) -> ShellSafeName {
+    pub(crate) fn chunk_proof_name(first_ver: u64, last_ver: Version) -> ShellSafeName {
         format!("{}-{}.proof", first_ver, last_ver)
             .try_into()
             .unwrap()
@@ -151,6 +174,7 @@ impl TransactionBackupController {
         chunk_bytes: &[u8],
         first_version: u64,
         last_version: u64,
+        integrity: &mut IntegrityManifest,
     ) -> Result<TransactionChunk> {
         let (proof_handle, mut proof_file) = self
             .storage
@@ -159,15 +183,15 @@ impl TransactionBackupController {
                 &Self::chunk_proof_name(first_version, last_version),
             )
             .await?;
-        tokio::io::copy(
-            &mut self
-                .client
+        let mut proof = HashingReader::new(
+            self.client
                 .get_transaction_range_proof(first_version, last_version)
                 .await?,
-            &mut proof_file,
-        )
-        .await?;
+        );
+        tokio::io::copy(&mut proof, &mut proof_file).await?;
         proof_file.shutdown().await?;
+        let (size, hash) = proof.checksum();
+        integrity.add_checksum(&proof_handle, size, hash);
 
         let (chunk_handle, mut chunk_file) = self
             .storage
@@ -175,6 +199,7 @@ impl TransactionBackupController {
             .await?;
         chunk_file.write_all(chunk_bytes).await?;
         chunk_file.shutdown().await?;
+        integrity.add(&chunk_handle, chunk_bytes);
 
         Ok(TransactionChunk {
             first_version,
@@ -190,11 +215,14 @@ impl TransactionBackupController {
         first_version: Version,
         last_version: Version,
         chunks: Vec<TransactionChunk>,
+        integrity: IntegrityManifest,
     ) -> Result<FileHandle> {
+        let integrity_handle = integrity.write(&self.storage, backup_handle).await?;
         let manifest = TransactionBackup {
             first_version,
             last_version,
             chunks,
+            integrity: Some(integrity_handle),
         };
         let (manifest_handle, mut manifest_file) = self
             .storage
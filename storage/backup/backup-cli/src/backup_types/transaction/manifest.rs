@@ -31,6 +31,10 @@ pub struct TransactionBackup {
     pub first_version: Version,
     pub last_version: Version,
     pub chunks: Vec<TransactionChunk>,
+    /// `IntegrityManifest` covering all chunk and proof files, absent in backups taken by older
+    /// versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<FileHandle>,
 }
 
 impl TransactionBackup {
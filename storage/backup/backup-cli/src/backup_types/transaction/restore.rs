@@ -5,6 +5,7 @@
 use crate::{
     backup_types::{
         epoch_ending::restore::EpochHistory,
+        integrity::BackupIntegrity,
         transaction::manifest::{TransactionBackup, TransactionChunk},
     },
     metrics::{
@@ -92,9 +93,14 @@ impl LoadedChunk {
     async fn load(
         manifest: TransactionChunk,
         storage: &Arc<dyn BackupStorage>,
+        integrity: &BackupIntegrity,
         epoch_history: Option<&Arc<EpochHistory>>,
     ) -> Result<Self> {
-        let mut file = BufReader::new(storage.open_for_read(&manifest.transactions).await?);
+        let mut file = BufReader::new(
+            integrity
+                .open_for_read(storage, &manifest.transactions)
+                .await?,
+        );
         let mut txns = Vec::new();
         let mut txn_infos = Vec::new();
         let mut event_vecs = Vec::new();
@@ -117,8 +123,9 @@ impl LoadedChunk {
             txns.len(),
         );
 
-        let (range_proof, ledger_info) = storage
+        let (range_proof, ledger_info) = integrity
             .load_bcs_file::<(TransactionAccumulatorRangeProof, LedgerInfoWithSignatures)>(
+                storage,
                 &manifest.proof,
             )
             .await?;
@@ -154,6 +161,18 @@ impl LoadedChunk {
     }
 }
 
+/// Checks a chunk of transactions against the ledger info its proof comes with, and the ledger info
+/// against the epoch history, if known.
+pub(crate) async fn verify_chunk(
+    chunk: TransactionChunk,
+    storage: &Arc<dyn BackupStorage>,
+    integrity: &BackupIntegrity,
+    epoch_history: Option<&Arc<EpochHistory>>,
+) -> Result<()> {
+    LoadedChunk::load(chunk, storage, integrity, epoch_history).await?;
+    Ok(())
+}
+
 impl TransactionRestoreController {
     pub fn new(
         opt: TransactionRestoreOpt,
@@ -259,19 +278,41 @@ impl TransactionRestoreBatchController {
         let manifest_stream = manifest_handle_stream
             .map(move |hdl| {
                 let storage = storage.clone();
-                async move { storage.load_json_file(&hdl).await.err_notes(&hdl) }
+                async move {
+                    let manifest: TransactionBackup =
+                        storage.load_json_file(&hdl).await.err_notes(&hdl)?;
+                    let integrity = BackupIntegrity::load(
+                        &storage,
+                        manifest.integrity.as_deref(),
+                        manifest
+                            .chunks
+                            .iter()
+                            .flat_map(|c| [c.transactions.as_str(), c.proof.as_str()]),
+                    )
+                    .await
+                    .err_notes(&hdl)?;
+                    Result::<_>::Ok((manifest, integrity))
+                }
             })
             .buffered_x(con * 3, con)
-            .and_then(|m: TransactionBackup| future::ready(m.verify().map(|_| m)));
+            .and_then(|(m, integrity): (TransactionBackup, BackupIntegrity)| {
+                future::ready(m.verify().map(|_| (m, integrity)))
+            });
 
         let target_version = self.global_opt.target_version;
         let chunk_manifest_stream = manifest_stream
-            .map_ok(|m| stream::iter(m.chunks.into_iter().map(Result::<_>::Ok)))
+            .map_ok(|(m, integrity)| {
+                stream::iter(
+                    m.chunks
+                        .into_iter()
+                        .map(move |c| Result::<_>::Ok((integrity.clone(), c))),
+                )
+            })
             .try_flatten()
-            .try_take_while(move |c| future::ready(Ok(c.first_version <= target_version)))
+            .try_take_while(move |(_, c)| future::ready(Ok(c.first_version <= target_version)))
             .scan(0, |last_chunk_last_version, chunk_res| {
                 let res = match &chunk_res {
-                    Ok(chunk) => {
+                    Ok((_, chunk)) => {
                         if *last_chunk_last_version != 0
                             && chunk.first_version != *last_chunk_last_version + 1
                         {
@@ -293,12 +334,12 @@ impl TransactionRestoreBatchController {
         let storage = self.storage.clone();
         let epoch_history = self.epoch_history.clone();
         chunk_manifest_stream
-            .and_then(move |chunk| {
+            .and_then(move |(integrity, chunk)| {
                 let storage = storage.clone();
                 let epoch_history = epoch_history.clone();
                 future::ok(async move {
                     tokio::task::spawn(async move {
-                        LoadedChunk::load(chunk, &storage, epoch_history.as_ref()).await
+                        LoadedChunk::load(chunk, &storage, &integrity, epoch_history.as_ref()).await
                     })
                     .err_into::<anyhow::Error>()
                     .await
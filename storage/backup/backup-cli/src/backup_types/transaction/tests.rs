@@ -12,7 +12,7 @@ use crate::{
         backup_service_client::BackupServiceClient,
         test_utils::{start_local_backup_service, tmp_db_with_random_content},
         ConcurrentDownloadsOpt, GlobalBackupOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt,
-        RocksdbOpt, TrustedWaypointOpt,
+        RocksdbOpt, TargetDbOpt, TrustedWaypointOpt,
     },
 };
 use aptos_db::AptosDB;
@@ -63,7 +63,10 @@ fn end_to_end() {
                     start_version: 0,
                     num_transactions: first_ver_to_backup as usize,
                 },
-                GlobalBackupOpt { max_chunk_size },
+                GlobalBackupOpt {
+                    max_chunk_size,
+                    chain_id: None,
+                },
                 client.clone(),
                 Arc::clone(&store),
             )
@@ -78,7 +81,10 @@ fn end_to_end() {
                     start_version: first_ver_to_backup,
                     num_transactions: num_txns_to_backup,
                 },
-                GlobalBackupOpt { max_chunk_size },
+                GlobalBackupOpt {
+                    max_chunk_size,
+                    chain_id: None,
+                },
                 client,
                 Arc::clone(&store),
             )
@@ -94,6 +100,7 @@ fn end_to_end() {
                 target_version: Some(target_version),
                 trusted_waypoints: TrustedWaypointOpt::default(),
                 rocksdb_opt: RocksdbOpt::default(),
+                target_db: TargetDbOpt::default(),
                 concurrent_downloads: ConcurrentDownloadsOpt::default(),
                 replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
             }
@@ -4,13 +4,13 @@
 
 use anyhow::Result;
 use aptos_backup_cli::{
-    coordinators::verify::VerifyCoordinator,
+    coordinators::verify::{VerifyCoordinator, VerifySamplingOpt},
     metadata::cache::MetadataCacheOpt,
+    metrics::output::MetricsOutputOpt,
     storage::StorageOpt,
     utils::{ConcurrentDownloadsOpt, TrustedWaypointOpt},
 };
 use aptos_logger::{prelude::*, Level, Logger};
-use aptos_push_metrics::MetricsPusher;
 use clap::Parser;
 
 #[derive(Parser)]
@@ -23,6 +23,10 @@ struct Opt {
     storage: StorageOpt,
     #[clap(flatten)]
     concurrent_downloads: ConcurrentDownloadsOpt,
+    #[clap(flatten)]
+    metrics_output: MetricsOutputOpt,
+    #[clap(flatten)]
+    sampling_opt: VerifySamplingOpt,
 }
 
 #[tokio::main]
@@ -36,14 +40,15 @@ async fn main() -> Result<()> {
 async fn main_impl() -> Result<()> {
     Logger::new().level(Level::Info).init();
 
-    let _mp = MetricsPusher::start(vec![]);
-
     let opt = Opt::from_args();
+    let _mo = opt.metrics_output.start();
+
     VerifyCoordinator::new(
         opt.storage.init_storage().await?,
         opt.metadata_cache_opt,
         opt.trusted_waypoints_opt,
         opt.concurrent_downloads.get(),
+        opt.sampling_opt,
     )?
     .run()
     .await
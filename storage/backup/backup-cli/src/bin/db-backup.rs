@@ -10,20 +10,33 @@ use aptos_backup_cli::{
         transaction::backup::{TransactionBackupController, TransactionBackupOpt},
     },
     coordinators::backup::{BackupCoordinator, BackupCoordinatorOpt},
+    doctor::{Doctor, DoctorOpt},
     metadata::{cache, cache::MetadataCacheOpt},
-    storage::StorageOpt,
+    metrics::output::MetricsOutputOpt,
+    storage::{
+        dry_run::{DryRunReport, DryRunStorage},
+        BackupStorage, StorageOpt,
+    },
     utils::{
         backup_service_client::{BackupServiceClient, BackupServiceClientOpt},
         ConcurrentDownloadsOpt, GlobalBackupOpt,
     },
 };
+use aptos_infallible::Mutex;
 use aptos_logger::{prelude::*, Level, Logger};
-use aptos_push_metrics::MetricsPusher;
 use clap::Parser;
 use std::sync::Arc;
 
 #[derive(Parser)]
 #[clap(about = "Ledger backup tool.")]
+struct Opt {
+    #[clap(flatten)]
+    metrics_output: MetricsOutputOpt,
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Parser)]
 pub enum Command {
     #[clap(subcommand, about = "Manually run one shot commands.")]
     OneShot(OneShotCommand),
@@ -32,6 +45,11 @@ pub enum Command {
         about = "Long running process backing up the chain continuously."
     )]
     Coordinator(CoordinatorCommand),
+    #[clap(
+        about = "Check the backup storage credentials and permissions, the backup service, the \
+        local disk space and the metadata cache, printing what to fix for anything failing."
+    )]
+    Doctor(DoctorCommandOpt),
 }
 
 #[derive(Parser)]
@@ -82,6 +100,14 @@ pub struct OneShotBackupOpt {
     #[clap(flatten)]
     client: BackupServiceClientOpt,
 
+    #[clap(
+        long,
+        help = "Stream the data from the backup service and cut it into chunks as usual, but \
+        instead of writing anything to the backup storage, print the backups, files and metadata \
+        that would have been written, along with their sizes."
+    )]
+    dry_run: bool,
+
     #[clap(subcommand)]
     backup_type: BackupType,
 }
@@ -132,6 +158,24 @@ pub struct CoordinatorRunOpt {
     storage: StorageOpt,
 }
 
+#[derive(Parser)]
+pub struct DoctorCommandOpt {
+    #[clap(flatten)]
+    doctor: DoctorOpt,
+
+    #[clap(flatten)]
+    client: BackupServiceClientOpt,
+
+    #[clap(flatten)]
+    metadata_cache: MetadataCacheOpt,
+
+    #[clap(flatten)]
+    concurrent_downloads: ConcurrentDownloadsOpt,
+
+    #[clap(subcommand)]
+    storage: StorageOpt,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     main_impl().await.map_err(|e| {
@@ -142,10 +186,10 @@ async fn main() -> Result<()> {
 
 async fn main_impl() -> Result<()> {
     Logger::new().level(Level::Info).init();
-    let _mp = MetricsPusher::start(vec![]);
+    let opt = Opt::from_args();
+    let _mo = opt.metrics_output.start();
 
-    let cmd = Command::from_args();
-    match cmd {
+    match opt.cmd {
         Command::OneShot(one_shot_cmd) => match one_shot_cmd {
             OneShotCommand::Query(typ) => match typ {
                 OneShotQueryType::NodeState(opt) => {
@@ -169,38 +213,33 @@ async fn main_impl() -> Result<()> {
             OneShotCommand::Backup(opt) => {
                 let client = Arc::new(BackupServiceClient::new_with_opt(opt.client));
                 let global_opt = opt.global;
+                let dry_run = opt.dry_run;
 
-                match opt.backup_type {
+                let dry_run_report = match opt.backup_type {
                     BackupType::EpochEnding { opt, storage } => {
-                        EpochEndingBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
+                        let (storage, report) = init_storage(storage, dry_run).await?;
+                        EpochEndingBackupController::new(opt, global_opt, client, storage)
+                            .run()
+                            .await?;
+                        report
                     },
                     BackupType::StateSnapshot { opt, storage } => {
-                        StateSnapshotBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
+                        let (storage, report) = init_storage(storage, dry_run).await?;
+                        StateSnapshotBackupController::new(opt, global_opt, client, storage)
+                            .run()
+                            .await?;
+                        report
                     },
                     BackupType::Transaction { opt, storage } => {
-                        TransactionBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
+                        let (storage, report) = init_storage(storage, dry_run).await?;
+                        TransactionBackupController::new(opt, global_opt, client, storage)
+                            .run()
+                            .await?;
+                        report
                     },
+                };
+                if let Some(report) = dry_run_report {
+                    println!("{}", report.lock());
                 }
             },
         },
@@ -216,6 +255,32 @@ async fn main_impl() -> Result<()> {
                 .await?;
             },
         },
+        Command::Doctor(opt) => {
+            Doctor::new(
+                opt.doctor,
+                opt.metadata_cache,
+                opt.concurrent_downloads.get(),
+                Arc::new(BackupServiceClient::new_with_opt(opt.client)),
+            )
+            .run(opt.storage)
+            .await?;
+        },
     }
     Ok(())
 }
+
+/// In a dry run, the storage is wrapped so that nothing is written, and the report of what would
+/// have been written is returned along with it.
+async fn init_storage(
+    storage: StorageOpt,
+    dry_run: bool,
+) -> Result<(Arc<dyn BackupStorage>, Option<Arc<Mutex<DryRunReport>>>)> {
+    let storage = storage.init_storage().await?;
+    if dry_run {
+        let dry_run_storage = DryRunStorage::new(storage);
+        let report = dry_run_storage.report();
+        Ok((Arc::new(dry_run_storage), Some(report)))
+    } else {
+        Ok((storage, None))
+    }
+}
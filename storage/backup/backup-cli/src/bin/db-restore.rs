@@ -10,12 +10,12 @@ use aptos_backup_cli::{
         transaction::restore::{TransactionRestoreController, TransactionRestoreOpt},
     },
     coordinators::restore::{RestoreCoordinator, RestoreCoordinatorOpt},
+    metrics::output::MetricsOutputOpt,
     storage::StorageOpt,
     utils::{GlobalRestoreOpt, GlobalRestoreOptions},
 };
 use aptos_executor_types::VerifyExecutionMode;
 use aptos_logger::{prelude::*, Level, Logger};
-use aptos_push_metrics::MetricsPusher;
 use clap::Parser;
 use std::convert::TryInto;
 
@@ -24,6 +24,9 @@ struct Opt {
     #[clap(flatten)]
     global: GlobalRestoreOpt,
 
+    #[clap(flatten)]
+    metrics_output: MetricsOutputOpt,
+
     #[clap(subcommand)]
     restore_type: RestoreType,
 }
@@ -66,9 +69,8 @@ async fn main() -> Result<()> {
 
 async fn main_impl() -> Result<()> {
     Logger::new().level(Level::Info).init();
-    let _mp = MetricsPusher::start(vec![]);
-
     let opt = Opt::from_args();
+    let _mo = opt.metrics_output.start();
     let global_opt: GlobalRestoreOptions = opt.global.clone().try_into()?;
 
     match opt.restore_type {
@@ -9,20 +9,23 @@ use crate::{
         transaction::backup::{TransactionBackupController, TransactionBackupOpt},
     },
     metadata,
-    metadata::cache::MetadataCacheOpt,
+    metadata::{cache::MetadataCacheOpt, view::MetadataView, ChainIdentity, Metadata},
     metrics::backup::{
         EPOCH_ENDING_EPOCH, HEARTBEAT_TS, STATE_SNAPSHOT_EPOCH, TRANSACTION_VERSION,
     },
     storage::BackupStorage,
     utils::{
-        backup_service_client::BackupServiceClient, unix_timestamp_sec, ConcurrentDownloadsOpt,
-        GlobalBackupOpt,
+        alert_hooks::{Alert, AlertHooks, AlertKind},
+        backup_service_client::BackupServiceClient,
+        read_record_bytes::ReadRecordBytes,
+        unix_timestamp_sec, ConcurrentDownloadsOpt, GlobalBackupOpt,
     },
 };
-use anyhow::{anyhow, ensure, Result};
-use aptos_db::backup::backup_handler::DbState;
+use anyhow::{anyhow, ensure, Context, Result};
+use aptos_db::backup::backup_handler::{DbMetadata, DbState};
+use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
-use aptos_types::transaction::Version;
+use aptos_types::{ledger_info::LedgerInfoWithSignatures, transaction::Version};
 use clap::Parser;
 use futures::{stream, Future, StreamExt};
 use std::{fmt::Debug, sync::Arc};
@@ -51,6 +54,22 @@ pub struct BackupCoordinatorOpt {
         is already at 19, then snapshot at 15 will be taken instead of at 10 (not at 18)."
     )]
     pub state_snapshot_interval_epochs: usize,
+    #[clap(
+        long,
+        help = "Also take a state snapshot at the latest epoch ending version once the chain \
+        advanced this many versions since the last snapshot, even if fewer epochs than \
+        --state-snapshot-interval-epochs passed, so that snapshots are more frequent when the \
+        chain is busy and restoring never needs to replay many more transactions than this. \
+        [default: snapshots are only scheduled by epochs]"
+    )]
+    pub state_snapshot_max_versions_between: Option<u64>,
+    #[clap(
+        long,
+        default_value = "1",
+        help = "Minimum number of epochs between two state snapshots taken because of \
+        --state-snapshot-max-versions-between, for short epochs not to each get a snapshot."
+    )]
+    pub state_snapshot_min_interval_epochs: usize,
     // Defaulting to 1M, which converts to a 20 minutes delay of a transaction showing up in a backup,
     // from a 1K TPS chain, and a few minutes replay time.
     #[clap(
@@ -71,8 +90,10 @@ pub struct BackupCoordinatorOpt {
 impl BackupCoordinatorOpt {
     fn validate(&self) -> Result<()> {
         ensure!(
-            self.state_snapshot_interval_epochs > 0 && self.transaction_batch_size > 0,
-            "Backup interval and batch size must be greater than 0."
+            self.state_snapshot_interval_epochs > 0
+                && self.state_snapshot_min_interval_epochs > 0
+                && self.transaction_batch_size > 0,
+            "Backup intervals and batch size must be greater than 0."
         );
         Ok(())
     }
@@ -83,9 +104,13 @@ pub struct BackupCoordinator {
     storage: Arc<dyn BackupStorage>,
     global_opt: GlobalBackupOpt,
     metadata_cache_opt: MetadataCacheOpt,
-    state_snapshot_interval_epochs: usize,
+    snapshot_schedule: SnapshotSchedule,
     transaction_batch_size: usize,
     concurrent_downloads: usize,
+    /// Range of data available on the node, as of the last refresh, if the node serves it.
+    db_metadata: Mutex<Option<DbMetadata>>,
+    /// Notified of failed backup runs, if any.
+    alert_hooks: Option<AlertHooks>,
 }
 
 impl BackupCoordinator {
@@ -101,21 +126,35 @@ impl BackupCoordinator {
             storage,
             global_opt,
             metadata_cache_opt: opt.metadata_cache_opt,
-            state_snapshot_interval_epochs: opt.state_snapshot_interval_epochs,
+            snapshot_schedule: SnapshotSchedule {
+                interval_epochs: opt.state_snapshot_interval_epochs,
+                max_versions_between: opt.state_snapshot_max_versions_between,
+                min_interval_epochs: opt.state_snapshot_min_interval_epochs,
+            },
             transaction_batch_size: opt.transaction_batch_size,
             concurrent_downloads: opt.concurrent_downloads.get(),
+            db_metadata: Mutex::new(None),
+            alert_hooks: None,
         }
     }
 
+    pub fn with_alert_hooks(mut self, alert_hooks: AlertHooks) -> Self {
+        self.alert_hooks = Some(alert_hooks);
+        self
+    }
+
     pub async fn run(&self) -> Result<()> {
         // Connect to both the local node and the backup storage.
-        let backup_state = metadata::cache::sync_and_load(
+        let metadata_view = metadata::cache::sync_and_load(
             &self.metadata_cache_opt,
             Arc::clone(&self.storage),
             self.concurrent_downloads,
         )
-        .await?
-        .get_storage_state()?;
+        .await?;
+        self.verify_chain_identity(&metadata_view)
+            .await
+            .context("Failed to verify the chain of the node against the backup storage.")?;
+        let backup_state = metadata_view.get_storage_state()?;
 
         // On new DbState retrieved:
         // `watch_db_state` informs `backup_epoch_endings` via channel 1,
@@ -141,7 +180,10 @@ impl BackupCoordinator {
             .boxed_local();
         let backup_state_snapshots = self
             .backup_work_stream(
-                backup_state.latest_state_snapshot_epoch,
+                SnapshotProgress {
+                    epoch: backup_state.latest_state_snapshot_epoch,
+                    version: backup_state.latest_state_snapshot_version,
+                },
                 &rx2,
                 Self::backup_state_snapshot,
             )
@@ -172,6 +214,35 @@ impl BackupCoordinator {
 }
 
 impl BackupCoordinator {
+    /// Refuses to back up a node of another chain than the one the backup storage holds the
+    /// backups of, and records the chain of the node in the storage if it isn't yet.
+    async fn verify_chain_identity(&self, metadata_view: &MetadataView) -> Result<()> {
+        if self.client.get_db_state().await?.is_none() {
+            warn!("DB not bootstrapped, its chain is not verified against the backup storage.");
+            return Ok(());
+        }
+        let mut ledger_infos = self.client.get_epoch_ending_ledger_infos(0, 1).await?;
+        let genesis: LedgerInfoWithSignatures = bcs::from_bytes(
+            &ledger_infos
+                .read_record_bytes()
+                .await?
+                .ok_or_else(|| anyhow!("The node didn't serve its genesis ledger info."))?,
+        )?;
+        let node = ChainIdentity::from_genesis(genesis.ledger_info(), self.global_opt.chain_id)?;
+
+        match metadata_view.chain_identity()? {
+            Some(recorded) => recorded.ensure_same_chain(&node, "The node"),
+            None => {
+                let metadata = Metadata::new_chain_identity(node);
+                self.storage
+                    .save_metadata_line(&metadata.name(), &metadata.to_text_line()?)
+                    .await?;
+                info!(chain = %node, "Recorded the chain of the node in the backup storage.");
+                Ok(())
+            },
+        }
+    }
+
     async fn try_refresh_db_state(&self, db_state_broadcast: &watch::Sender<Option<DbState>>) {
         match self.client.get_db_state().await {
             Ok(s) => {
@@ -179,6 +250,7 @@ impl BackupCoordinator {
                 if s.is_none() {
                     warn!("DB not bootstrapped.");
                 } else {
+                    self.refresh_db_metadata().await;
                     db_state_broadcast
                         .send(s)
                         .map_err(|e| anyhow!("Receivers should not be cancelled: {}", e))
@@ -192,6 +264,29 @@ impl BackupCoordinator {
         };
     }
 
+    async fn refresh_db_metadata(&self) {
+        // Nodes predating `DbMetadata` don't serve it, in which case we just go without.
+        match self.client.get_db_metadata().await {
+            Ok(metadata) => *self.db_metadata.lock() = metadata,
+            Err(e) => debug!("Failed pulling DbMetadata from local node: {}.", e),
+        }
+    }
+
+    /// Fails if transactions from `first_version` on are no longer available on the node, in
+    /// which case retrying is pointless.
+    fn ensure_transactions_available(&self, first_version: Version) -> Result<()> {
+        if let Some(metadata) = self.db_metadata.lock().as_ref() {
+            ensure!(
+                first_version >= metadata.first_version,
+                "Transactions from version {} on are needed, but the node pruned those before \
+                version {}. The transaction backup can't continue from this node.",
+                first_version,
+                metadata.first_version,
+            );
+        }
+        Ok(())
+    }
+
     async fn backup_epoch_endings(
         &self,
         mut last_epoch_ending_epoch_in_backup: Option<u64>,
@@ -232,22 +327,20 @@ impl BackupCoordinator {
 
     async fn backup_state_snapshot(
         &self,
-        last_snapshot_epoch_in_backup: Option<Version>,
+        last_snapshot_in_backup: SnapshotProgress,
         db_state: DbState,
-    ) -> Result<Option<Version>> {
-        if let Some(epoch) = last_snapshot_epoch_in_backup {
+    ) -> Result<SnapshotProgress> {
+        if let Some(epoch) = last_snapshot_in_backup.epoch {
             STATE_SNAPSHOT_EPOCH.set(epoch as i64);
         }
-        let epoch = get_next_snapshot(
-            last_snapshot_epoch_in_backup,
-            db_state,
-            self.state_snapshot_interval_epochs,
-        );
+        let epoch = self
+            .snapshot_schedule
+            .next_snapshot(last_snapshot_in_backup, db_state);
 
         // <= becuse db_state.epoch is still open
         if db_state.epoch <= epoch {
             // wait for the next db_state update
-            return Ok(last_snapshot_epoch_in_backup);
+            return Ok(last_snapshot_in_backup);
         }
 
         StateSnapshotBackupController::new(
@@ -259,7 +352,12 @@ impl BackupCoordinator {
         .run()
         .await?;
 
-        Ok(Some(epoch))
+        Ok(SnapshotProgress {
+            epoch: Some(epoch),
+            // The snapshot is at the end of the epoch, at or before this version. Close enough to
+            // measure the activity since.
+            version: Some(db_state.committed_version),
+        })
     }
 
     async fn backup_transactions(
@@ -280,6 +378,7 @@ impl BackupCoordinator {
                 // wait for the next db_state update
                 return Ok(last_transaction_version_in_backup);
             }
+            self.ensure_transactions_available(first)?;
 
             TransactionBackupController::new(
                 TransactionBackupOpt {
@@ -314,10 +413,21 @@ impl BackupCoordinator {
                 rx.changed().await.unwrap();
                 let db_state = *rx.borrow();
                 if let Some(db_state) = db_state {
-                    let next_state = worker(self, s, db_state).await.unwrap_or_else(|e| {
-                        warn!("backup failed: {}. Keep trying with state {:?}.", e, s);
-                        s
-                    });
+                    let next_state = match worker(self, s, db_state).await {
+                        Ok(next_state) => next_state,
+                        Err(e) => {
+                            warn!("backup failed: {}. Keep trying with state {:?}.", e, s);
+                            if let Some(alert_hooks) = &self.alert_hooks {
+                                alert_hooks
+                                    .fire(Alert::new(
+                                        AlertKind::BackupFailed,
+                                        format!("Backup failed: {:#}", e),
+                                    ))
+                                    .await;
+                            }
+                            s
+                        },
+                    };
                     Some(((), (next_state, rx)))
                 } else {
                     // initial state
@@ -352,6 +462,48 @@ fn get_batch_range(last_in_backup: Option<u64>, batch_size: usize) -> (u64, u64)
     })
 }
 
+/// The last state snapshot in the backup, if any.
+#[derive(Clone, Copy, Debug)]
+struct SnapshotProgress {
+    epoch: Option<u64>,
+    version: Option<Version>,
+}
+
+/// When to take state snapshots, which are always at epoch ending versions: every
+/// `interval_epochs` epochs, and in between if the chain is busy.
+#[derive(Clone, Copy, Debug)]
+struct SnapshotSchedule {
+    interval_epochs: usize,
+    /// Versions after which a snapshot is taken, however few epochs passed.
+    max_versions_between: Option<u64>,
+    /// Epochs between two snapshots taken because of `max_versions_between`, at least.
+    min_interval_epochs: usize,
+}
+
+impl SnapshotSchedule {
+    /// Epoch at the end of which to take the next snapshot, due once `db_state.epoch` is past it.
+    fn next_snapshot(&self, last_in_backup: SnapshotProgress, db_state: DbState) -> u64 {
+        let scheduled = get_next_snapshot(last_in_backup.epoch, db_state, self.interval_epochs);
+        if scheduled < db_state.epoch {
+            return scheduled;
+        }
+        if let (Some(max_versions_between), Some(last_epoch), Some(last_version)) = (
+            self.max_versions_between,
+            last_in_backup.epoch,
+            last_in_backup.version,
+        ) {
+            // Notice that db_state.epoch is not closed yet.
+            let latest_closed_epoch = db_state.epoch.saturating_sub(1);
+            if db_state.committed_version.saturating_sub(last_version) >= max_versions_between
+                && latest_closed_epoch >= last_epoch + self.min_interval_epochs as u64
+            {
+                return latest_closed_epoch;
+            }
+        }
+        scheduled
+    }
+}
+
 fn get_next_snapshot(last_in_backup: Option<u64>, db_state: DbState, interval: usize) -> u64 {
     // We don't try to guarantee snapshots are taken at each applicable interval: when the backup
     // progress can't keep up with the ledger growth, we favor timeliness over completeness.
@@ -371,7 +523,9 @@ fn get_next_snapshot(last_in_backup: Option<u64>, db_state: DbState, interval: u
 
 #[cfg(test)]
 mod tests {
-    use crate::coordinators::backup::{get_batch_range, get_next_snapshot};
+    use crate::coordinators::backup::{
+        get_batch_range, get_next_snapshot, SnapshotProgress, SnapshotSchedule,
+    };
     use aptos_db::backup::backup_handler::DbState;
 
     #[test]
@@ -401,4 +555,56 @@ mod tests {
         assert_eq!(get_next_snapshot(Some(0), _state(250), 100), 200);
         assert_eq!(get_next_snapshot(Some(200), _state(250), 100), 300);
     }
+
+    #[test]
+    fn test_snapshot_schedule() {
+        let schedule = SnapshotSchedule {
+            interval_epochs: 100,
+            max_versions_between: Some(1_000_000),
+            min_interval_epochs: 2,
+        };
+        let state = |epoch, committed_version| DbState {
+            epoch,
+            committed_version,
+        };
+        let last = |epoch, version| SnapshotProgress {
+            epoch: Some(epoch),
+            version: Some(version),
+        };
+
+        // Quiet chain, every 100 epochs.
+        assert_eq!(
+            schedule.next_snapshot(last(100, 5_000_000), state(150, 5_500_000)),
+            200
+        );
+        assert_eq!(
+            schedule.next_snapshot(last(100, 5_000_000), state(201, 5_900_000)),
+            200
+        );
+        // Busy chain, at the latest epoch ending.
+        assert_eq!(
+            schedule.next_snapshot(last(100, 5_000_000), state(150, 6_000_000)),
+            149
+        );
+        // But not right after the last one.
+        assert_eq!(
+            schedule.next_snapshot(last(100, 5_000_000), state(102, 6_000_000)),
+            200
+        );
+        assert_eq!(
+            schedule.next_snapshot(last(100, 5_000_000), state(103, 6_000_000)),
+            102
+        );
+        // Unknown last version, by epochs only.
+        assert_eq!(
+            schedule.next_snapshot(
+                SnapshotProgress {
+                    epoch: Some(100),
+                    version: None
+                },
+                state(150, 6_000_000)
+            ),
+            200
+        );
+    }
 }
@@ -0,0 +1,140 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    backup_types::{
+        epoch_ending::backup::{EpochEndingBackupController, EpochEndingBackupOpt},
+        transaction::backup::{TransactionBackupController, TransactionBackupOpt},
+    },
+    metadata,
+    metadata::cache::MetadataCacheOpt,
+    storage::BackupStorage,
+    utils::{backup_service_client::BackupServiceClient, GlobalBackupOpt},
+};
+use anyhow::{bail, Result};
+use aptos_logger::prelude::*;
+use std::sync::Arc;
+
+/// Finds the epochs and versions missing from the backups, which otherwise only surface when a
+/// restore fails, and optionally backs them up from the backup service.
+pub struct GapRepairCoordinator {
+    storage: Arc<dyn BackupStorage>,
+    metadata_cache_opt: MetadataCacheOpt,
+    concurrent_downloads: usize,
+    /// Where to back the gaps up from, if they are to be repaired.
+    repair: Option<(GlobalBackupOpt, Arc<BackupServiceClient>)>,
+}
+
+impl GapRepairCoordinator {
+    pub fn new(
+        storage: Arc<dyn BackupStorage>,
+        metadata_cache_opt: MetadataCacheOpt,
+        concurrent_downloads: usize,
+    ) -> Self {
+        Self {
+            storage,
+            metadata_cache_opt,
+            concurrent_downloads,
+            repair: None,
+        }
+    }
+
+    pub fn with_repair(
+        mut self,
+        global_opt: GlobalBackupOpt,
+        client: Arc<BackupServiceClient>,
+    ) -> Self {
+        self.repair = Some((global_opt, client));
+        self
+    }
+
+    /// Reports the gaps, failing if there are any left, i.e. not repaired.
+    pub async fn run(self) -> Result<()> {
+        let metadata_view = metadata::cache::sync_and_load(
+            &self.metadata_cache_opt,
+            Arc::clone(&self.storage),
+            self.concurrent_downloads,
+        )
+        .await?;
+        let gaps = metadata_view.find_gaps();
+        if gaps.is_empty() {
+            info!("No gaps in the backups.");
+            return Ok(());
+        }
+        print!("{}", gaps);
+        let (global_opt, client) = match self.repair {
+            Some(repair) => repair,
+            None => bail!(
+                "Found {} gaps in the epoch ending backups and {} in the transaction backups.",
+                gaps.epoch_ending.len(),
+                gaps.transaction.len(),
+            ),
+        };
+
+        let num_gaps = gaps.epoch_ending.len() + gaps.transaction.len();
+        let mut num_failed = 0;
+        for epochs in gaps.epoch_ending {
+            info!(
+                first_epoch = *epochs.start(),
+                last_epoch = *epochs.end(),
+                "Backing up missing epoch endings."
+            );
+            let res = EpochEndingBackupController::new(
+                EpochEndingBackupOpt {
+                    start_epoch: *epochs.start(),
+                    end_epoch: *epochs.end() + 1,
+                },
+                global_opt.clone(),
+                client.clone(),
+                Arc::clone(&self.storage),
+            )
+            .run()
+            .await;
+            if let Err(e) = res {
+                error!(
+                    "Failed to back up epochs {} to {}: {:#}",
+                    epochs.start(),
+                    epochs.end(),
+                    e
+                );
+                num_failed += 1;
+            }
+        }
+        for versions in gaps.transaction {
+            info!(
+                first_version = *versions.start(),
+                last_version = *versions.end(),
+                "Backing up missing transactions."
+            );
+            let res = TransactionBackupController::new(
+                TransactionBackupOpt {
+                    start_version: *versions.start(),
+                    num_transactions: (*versions.end() - *versions.start() + 1) as usize,
+                },
+                global_opt.clone(),
+                client.clone(),
+                Arc::clone(&self.storage),
+            )
+            .run()
+            .await;
+            if let Err(e) = res {
+                error!(
+                    "Failed to back up versions {} to {}: {:#}",
+                    versions.start(),
+                    versions.end(),
+                    e
+                );
+                num_failed += 1;
+            }
+        }
+        if num_failed > 0 {
+            bail!(
+                "{} of the {} gaps could not be backed up, the backup service may have pruned them.",
+                num_failed,
+                num_gaps,
+            );
+        }
+        info!(num_gaps = num_gaps, "Backed up all the gaps.");
+        Ok(())
+    }
+}
@@ -0,0 +1,724 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    backup_types::{
+        epoch_ending::{
+            backup::EpochEndingBackupController,
+            manifest::{EpochEndingBackup, EpochEndingChunk},
+        },
+        integrity::IntegrityManifest,
+        state_snapshot::{
+            backup::StateSnapshotBackupController,
+            manifest::{StateSnapshotBackup, StateSnapshotChunk},
+        },
+        transaction::{
+            backup::TransactionBackupController,
+            manifest::{TransactionBackup, TransactionChunk},
+        },
+    },
+    metadata,
+    metadata::{
+        cache::MetadataCacheOpt, view::MetadataView, EpochEndingBackupMeta, Metadata,
+        StateSnapshotBackupMeta, TransactionBackupMeta,
+    },
+    storage::{
+        tags::{ObjectTags, TaggedStorage},
+        BackupHandleRef, BackupStorage, FileHandle, FileHandleRef, ShellSafeName,
+    },
+    utils::storage_ext::BackupStorageExt,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use aptos_crypto::HashValue;
+use aptos_logger::prelude::*;
+use itertools::Itertools;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+/// Merges the backups of a source storage into a destination storage, e.g. when consolidating
+/// historical buckets, so that the destination alone can be restored from.
+///
+/// The backups of the source not in the destination are copied, files and all, and their metadata
+/// saved in the destination. Those whose range the destination already covers are left out, and so
+/// are those partially overlapping it, since restores expect the ranges of the backups not to
+/// overlap. The latter are reported, and make the merge fail after the rest is copied, for the
+/// ranges they leave missing to be backed up again, see `GapRepairCoordinator`.
+pub struct MetadataMergeCoordinator {
+    source: Arc<dyn BackupStorage>,
+    destination: Arc<dyn BackupStorage>,
+    metadata_cache_opt: MetadataCacheOpt,
+    concurrent_downloads: usize,
+    /// Only report what would be copied.
+    dry_run: bool,
+}
+
+impl MetadataMergeCoordinator {
+    pub fn new(
+        source: Arc<dyn BackupStorage>,
+        destination: Arc<dyn BackupStorage>,
+        metadata_cache_opt: MetadataCacheOpt,
+        concurrent_downloads: usize,
+    ) -> Self {
+        Self {
+            source,
+            destination,
+            metadata_cache_opt,
+            concurrent_downloads,
+            dry_run: false,
+        }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub async fn run(self) -> Result<()> {
+        let source_view = metadata::cache::sync_and_load(
+            &self.metadata_cache_opt.for_storage("source"),
+            Arc::clone(&self.source),
+            self.concurrent_downloads,
+        )
+        .await?;
+        let destination_view = metadata::cache::sync_and_load(
+            &self.metadata_cache_opt.for_storage("destination"),
+            Arc::clone(&self.destination),
+            self.concurrent_downloads,
+        )
+        .await?;
+        if let (Some(source_chain), Some(destination_chain)) = (
+            source_view.chain_identity()?,
+            destination_view.chain_identity()?,
+        ) {
+            destination_chain.ensure_same_chain(&source_chain, "The source")?;
+        }
+        let plan = MergePlan::new(&source_view, &destination_view);
+        info!(
+            epoch_ending_backups = plan.epoch_ending.copy.len(),
+            state_snapshot_backups = plan.state_snapshot.len(),
+            transaction_backups = plan.transaction.copy.len(),
+            already_present = plan.num_present(),
+            dry_run = self.dry_run,
+            "Backups to copy into the destination."
+        );
+        for backup in &plan.epoch_ending.conflicting {
+            warn!(
+                first_epoch = backup.first_epoch,
+                last_epoch = backup.last_epoch,
+                manifest = backup.manifest,
+                "Epoch ending backup partially overlaps the destination, left out."
+            );
+        }
+        for backup in &plan.transaction.conflicting {
+            warn!(
+                first_version = backup.first_version,
+                last_version = backup.last_version,
+                manifest = backup.manifest,
+                "Transaction backup partially overlaps the destination, left out."
+            );
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+
+        for backup in &plan.epoch_ending.copy {
+            self.copy_epoch_ending_backup(backup).await?;
+        }
+        for backup in &plan.state_snapshot {
+            self.copy_state_snapshot_backup(backup).await?;
+        }
+        for backup in &plan.transaction.copy {
+            self.copy_transaction_backup(backup).await?;
+        }
+        let num_conflicting =
+            plan.epoch_ending.conflicting.len() + plan.transaction.conflicting.len();
+        if num_conflicting > 0 {
+            bail!(
+                "{} backups of the source partially overlap the destination and were left out, \
+                check the destination for gaps.",
+                num_conflicting,
+            );
+        }
+        info!("Merged the backups of the source into the destination.");
+        Ok(())
+    }
+
+    /// The destination, attaching the tags the source has for the backup to the files copied, or
+    /// tags like those of a new backup if the source has none.
+    async fn tagged_destination(
+        &self,
+        manifest: &FileHandleRef,
+        default_tags: ObjectTags,
+    ) -> Result<Arc<dyn BackupStorage>> {
+        let tags = match self.source.read_tags(manifest).await? {
+            Some(tags) if !tags.is_empty() => tags,
+            _ => default_tags,
+        };
+        Ok(Arc::new(TaggedStorage::new(
+            Arc::clone(&self.destination),
+            tags,
+        )))
+    }
+
+    async fn copy_epoch_ending_backup(&self, backup: &EpochEndingBackupMeta) -> Result<()> {
+        info!(
+            first_epoch = backup.first_epoch,
+            last_epoch = backup.last_epoch,
+            "Copying epoch ending backup."
+        );
+        let manifest: EpochEndingBackup = self.source.load_json_file(&backup.manifest).await?;
+        manifest.verify()?;
+        let destination = self
+            .tagged_destination(
+                &backup.manifest,
+                ObjectTags::for_backup("epoch_ending", None)
+                    .with("first_epoch", backup.first_epoch)
+                    .with("last_epoch", backup.last_epoch),
+            )
+            .await?;
+        let backup_handle = destination
+            .create_backup_with_random_suffix(&format!("epoch_ending_{}-", backup.first_epoch))
+            .await?;
+        let mut copier = FileCopier::new(
+            &self.source,
+            &destination,
+            &backup_handle,
+            manifest.integrity.as_deref(),
+        )
+        .await?;
+        let mut chunks = Vec::new();
+        for chunk in manifest.chunks {
+            let ledger_infos = copier
+                .copy(
+                    &chunk.ledger_infos,
+                    &EpochEndingBackupController::chunk_name(chunk.first_epoch),
+                )
+                .await?;
+            chunks.push(EpochEndingChunk {
+                ledger_infos,
+                ..chunk
+            });
+        }
+        let manifest = EpochEndingBackup {
+            chunks,
+            integrity: Some(copier.finish().await?),
+            ..manifest
+        };
+        let manifest_handle = write_manifest(
+            &destination,
+            &backup_handle,
+            EpochEndingBackupController::manifest_name(),
+            &manifest,
+        )
+        .await?;
+        self.save_metadata(Metadata::new_epoch_ending_backup(
+            backup.first_epoch,
+            backup.last_epoch,
+            backup.first_version,
+            backup.last_version,
+            manifest_handle,
+        ))
+        .await
+    }
+
+    async fn copy_state_snapshot_backup(&self, backup: &StateSnapshotBackupMeta) -> Result<()> {
+        info!(
+            epoch = backup.epoch,
+            version = backup.version,
+            "Copying state snapshot backup."
+        );
+        let manifest: StateSnapshotBackup = self.source.load_json_file(&backup.manifest).await?;
+        let destination = self
+            .tagged_destination(
+                &backup.manifest,
+                ObjectTags::for_backup("state_snapshot", None).with("epoch", backup.epoch),
+            )
+            .await?;
+        let backup_handle = destination
+            .create_backup_with_random_suffix(&format!(
+                "state_epoch_{}_ver_{}",
+                backup.epoch, backup.version
+            ))
+            .await?;
+        let mut copier = FileCopier::new(
+            &self.source,
+            &destination,
+            &backup_handle,
+            manifest.integrity.as_deref(),
+        )
+        .await?;
+        let mut chunks = Vec::new();
+        for chunk in manifest.chunks {
+            let blobs = copier
+                .copy(
+                    &chunk.blobs,
+                    &StateSnapshotBackupController::chunk_name(chunk.first_idx),
+                )
+                .await?;
+            let proof = copier
+                .copy(
+                    &chunk.proof,
+                    &StateSnapshotBackupController::chunk_proof_name(
+                        chunk.first_idx,
+                        chunk.last_idx,
+                    ),
+                )
+                .await?;
+            chunks.push(StateSnapshotChunk {
+                blobs,
+                proof,
+                ..chunk
+            });
+        }
+        let proof = copier
+            .copy(&manifest.proof, StateSnapshotBackupController::proof_name())
+            .await?;
+        let manifest = StateSnapshotBackup {
+            chunks,
+            proof,
+            integrity: Some(copier.finish().await?),
+            ..manifest
+        };
+        let manifest_handle = write_manifest(
+            &destination,
+            &backup_handle,
+            StateSnapshotBackupController::manifest_name(),
+            &manifest,
+        )
+        .await?;
+        self.save_metadata(Metadata::new_state_snapshot_backup(
+            backup.epoch,
+            backup.version,
+            manifest_handle,
+        ))
+        .await
+    }
+
+    async fn copy_transaction_backup(&self, backup: &TransactionBackupMeta) -> Result<()> {
+        info!(
+            first_version = backup.first_version,
+            last_version = backup.last_version,
+            "Copying transaction backup."
+        );
+        let manifest: TransactionBackup = self.source.load_json_file(&backup.manifest).await?;
+        manifest.verify()?;
+        let destination = self
+            .tagged_destination(
+                &backup.manifest,
+                ObjectTags::for_backup("transaction", None)
+                    .with("first_version", backup.first_version)
+                    .with("last_version", backup.last_version),
+            )
+            .await?;
+        let backup_handle = destination
+            .create_backup_with_random_suffix(&format!("transaction_{}-", backup.first_version))
+            .await?;
+        let mut copier = FileCopier::new(
+            &self.source,
+            &destination,
+            &backup_handle,
+            manifest.integrity.as_deref(),
+        )
+        .await?;
+        let mut chunks = Vec::new();
+        for chunk in manifest.chunks {
+            let proof = copier
+                .copy(
+                    &chunk.proof,
+                    &TransactionBackupController::chunk_proof_name(
+                        chunk.first_version,
+                        chunk.last_version,
+                    ),
+                )
+                .await?;
+            let transactions = copier
+                .copy(
+                    &chunk.transactions,
+                    &TransactionBackupController::chunk_name(chunk.first_version),
+                )
+                .await?;
+            chunks.push(TransactionChunk {
+                transactions,
+                proof,
+                ..chunk
+            });
+        }
+        let manifest = TransactionBackup {
+            chunks,
+            integrity: Some(copier.finish().await?),
+            ..manifest
+        };
+        let manifest_handle = write_manifest(
+            &destination,
+            &backup_handle,
+            TransactionBackupController::manifest_name(),
+            &manifest,
+        )
+        .await?;
+        self.save_metadata(Metadata::new_transaction_backup(
+            backup.first_version,
+            backup.last_version,
+            manifest_handle,
+        ))
+        .await
+    }
+
+    async fn save_metadata(&self, metadata: Metadata) -> Result<()> {
+        self.destination
+            .save_metadata_line(&metadata.name(), &metadata.to_text_line()?)
+            .await
+    }
+}
+
+async fn write_manifest(
+    storage: &Arc<dyn BackupStorage>,
+    backup_handle: &BackupHandleRef,
+    name: &ShellSafeName,
+    manifest: &impl Serialize,
+) -> Result<FileHandle> {
+    let (manifest_handle, mut manifest_file) =
+        storage.create_for_write(backup_handle, name).await?;
+    manifest_file
+        .write_all(&serde_json::to_vec(manifest)?)
+        .await?;
+    manifest_file.shutdown().await?;
+    Ok(manifest_handle)
+}
+
+/// Copies the files of a backup into a new backup, checking them against the integrity manifest
+/// of the original if it has one, and collecting the integrity manifest of the copy.
+struct FileCopier<'a> {
+    source: &'a Arc<dyn BackupStorage>,
+    destination: &'a Arc<dyn BackupStorage>,
+    backup_handle: &'a BackupHandleRef,
+    expected: Option<IntegrityManifest>,
+    integrity: IntegrityManifest,
+}
+
+impl<'a> FileCopier<'a> {
+    async fn new(
+        source: &'a Arc<dyn BackupStorage>,
+        destination: &'a Arc<dyn BackupStorage>,
+        backup_handle: &'a BackupHandleRef,
+        integrity: Option<&FileHandleRef>,
+    ) -> Result<FileCopier<'a>> {
+        let expected = match integrity {
+            Some(handle) => Some(source.load_json_file(handle).await?),
+            None => None,
+        };
+        Ok(Self {
+            source,
+            destination,
+            backup_handle,
+            expected,
+            integrity: IntegrityManifest::default(),
+        })
+    }
+
+    async fn copy(
+        &mut self,
+        file_handle: &FileHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<FileHandle> {
+        let content = self.source.read_all(file_handle).await?;
+        if let Some(expected) = &self.expected {
+            let checksum = expected
+                .files
+                .iter()
+                .find(|f| f.file_handle == file_handle)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "File {} is not listed in the integrity manifest.",
+                        file_handle
+                    )
+                })?;
+            ensure!(
+                checksum.size == content.len() as u64
+                    && checksum.sha3_256 == HashValue::sha3_256_of(&content),
+                "File {} doesn't match the integrity manifest of its backup.",
+                file_handle,
+            );
+        }
+        let (handle, mut file) = self
+            .destination
+            .create_for_write(self.backup_handle, name)
+            .await?;
+        file.write_all(&content).await?;
+        file.shutdown().await?;
+        self.integrity.add(&handle, &content);
+        Ok(handle)
+    }
+
+    async fn finish(self) -> Result<FileHandle> {
+        self.integrity
+            .write(self.destination, self.backup_handle)
+            .await
+    }
+}
+
+/// Backups of the source with ranges, i.e. epoch ending or transaction backups, split by whether
+/// to copy them.
+pub struct RangePartition<T> {
+    /// Not overlapping the destination, nor each other.
+    pub copy: Vec<T>,
+    /// Whose range the destination already covers.
+    pub num_covered: usize,
+    /// Partially overlapping the destination, or backups to copy, left out.
+    pub conflicting: Vec<T>,
+}
+
+impl<T: Clone + Ord> RangePartition<T> {
+    fn new(source: &[T], destination: &[T], range: impl Fn(&T) -> (u64, u64)) -> Self {
+        let mut taken: Vec<(u64, u64)> = destination.iter().map(&range).collect();
+        let mut partition = Self {
+            copy: Vec::new(),
+            num_covered: 0,
+            conflicting: Vec::new(),
+        };
+        for backup in source.iter().sorted() {
+            let (first, last) = range(backup);
+            let overlapping: Vec<_> = taken
+                .iter()
+                .filter(|(f, l)| *f <= last && first <= *l)
+                .copied()
+                .sorted()
+                .collect();
+            if overlapping.is_empty() {
+                partition.copy.push(backup.clone());
+                taken.push((first, last));
+            } else if covers(&overlapping, first, last) {
+                partition.num_covered += 1;
+            } else {
+                partition.conflicting.push(backup.clone());
+            }
+        }
+        partition
+    }
+}
+
+/// Whether the sorted ranges cover `first` to `last`.
+fn covers(ranges: &[(u64, u64)], first: u64, last: u64) -> bool {
+    let mut next = first;
+    for (f, l) in ranges {
+        if *f > next {
+            return false;
+        }
+        if *l >= last {
+            return true;
+        }
+        next = next.max(l + 1);
+    }
+    false
+}
+
+/// What to copy from the source into the destination.
+pub struct MergePlan {
+    pub epoch_ending: RangePartition<EpochEndingBackupMeta>,
+    /// Snapshots at versions the destination has none at.
+    pub state_snapshot: Vec<StateSnapshotBackupMeta>,
+    pub num_state_snapshots_present: usize,
+    pub transaction: RangePartition<TransactionBackupMeta>,
+}
+
+impl MergePlan {
+    pub fn new(source: &MetadataView, destination: &MetadataView) -> Self {
+        let (state_snapshot, present): (Vec<_>, Vec<_>) = source
+            .state_snapshot_backups()
+            .iter()
+            .sorted()
+            .cloned()
+            .partition(|backup| {
+                !destination
+                    .state_snapshot_backups()
+                    .iter()
+                    .any(|b| b.version == backup.version)
+            });
+        Self {
+            epoch_ending: RangePartition::new(
+                source.epoch_ending_backups(),
+                destination.epoch_ending_backups(),
+                |b| (b.first_epoch, b.last_epoch),
+            ),
+            state_snapshot,
+            num_state_snapshots_present: present.len(),
+            transaction: RangePartition::new(
+                source.transaction_backups(),
+                destination.transaction_backups(),
+                |b| (b.first_version, b.last_version),
+            ),
+        }
+    }
+
+    /// Number of backups of the source the destination already has.
+    pub fn num_present(&self) -> usize {
+        self.epoch_ending.num_covered
+            + self.num_state_snapshots_present
+            + self.transaction.num_covered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MergePlan, MetadataMergeCoordinator};
+    use crate::{
+        backup_types::transaction::manifest::{TransactionBackup, TransactionChunk},
+        metadata::{cache, cache::MetadataCacheOpt, view::MetadataView, Metadata},
+        storage::{local_fs::LocalFs, BackupStorage},
+        utils::storage_ext::BackupStorageExt,
+    };
+    use aptos_temppath::TempPath;
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_merge_plan() {
+        let source = MetadataView::from(vec![
+            Metadata::new_epoch_ending_backup(0, 9, 0, 999, "e0".to_string()),
+            Metadata::new_epoch_ending_backup(10, 19, 1000, 1999, "e10".to_string()),
+            Metadata::new_epoch_ending_backup(20, 29, 2000, 2999, "e20".to_string()),
+            Metadata::new_state_snapshot_backup(5, 500, "s500".to_string()),
+            Metadata::new_state_snapshot_backup(15, 1500, "s1500".to_string()),
+            Metadata::new_transaction_backup(0, 99, "t0".to_string()),
+            Metadata::new_transaction_backup(100, 199, "t100".to_string()),
+            Metadata::new_transaction_backup(150, 249, "t150".to_string()),
+            Metadata::new_transaction_backup(300, 399, "t300".to_string()),
+        ]);
+        let destination = MetadataView::from(vec![
+            Metadata::new_epoch_ending_backup(0, 4, 0, 499, "de0".to_string()),
+            Metadata::new_epoch_ending_backup(5, 14, 500, 1499, "de5".to_string()),
+            Metadata::new_state_snapshot_backup(15, 1500, "ds1500".to_string()),
+            Metadata::new_transaction_backup(100, 199, "dt100".to_string()),
+            Metadata::new_transaction_backup(200, 299, "dt200".to_string()),
+        ]);
+        let plan = MergePlan::new(&source, &destination);
+
+        // Covered by two backups of the destination.
+        assert_eq!(plan.epoch_ending.num_covered, 1);
+        assert_eq!(
+            manifests(&plan.epoch_ending.conflicting, |b| &b.manifest),
+            vec!["e10"]
+        );
+        assert_eq!(manifests(&plan.epoch_ending.copy, |b| &b.manifest), vec![
+            "e20"
+        ]);
+        assert_eq!(manifests(&plan.state_snapshot, |b| &b.manifest), vec![
+            "s500"
+        ]);
+        assert_eq!(manifests(&plan.transaction.copy, |b| &b.manifest), vec![
+            "t0", "t300"
+        ]);
+        // Covered by one, and by two backups of the destination.
+        assert_eq!(plan.transaction.num_covered, 2);
+        assert!(plan.transaction.conflicting.is_empty());
+        assert_eq!(plan.num_present(), 4);
+    }
+
+    fn manifests<T>(backups: &[T], manifest: impl Fn(&T) -> &String) -> Vec<&str> {
+        backups.iter().map(|b| manifest(b).as_str()).collect()
+    }
+
+    async fn write_transaction_backup(
+        storage: &Arc<dyn BackupStorage>,
+        first_version: u64,
+        last_version: u64,
+    ) {
+        let backup_handle = storage
+            .create_backup_with_random_suffix(&format!("transaction_{}-", first_version))
+            .await
+            .unwrap();
+        let write = |name: String, content: Vec<u8>| {
+            let storage = storage.clone();
+            let backup_handle = backup_handle.clone();
+            async move {
+                let (handle, mut file) = storage
+                    .create_for_write(&backup_handle, &name.try_into().unwrap())
+                    .await
+                    .unwrap();
+                file.write_all(&content).await.unwrap();
+                file.shutdown().await.unwrap();
+                handle
+            }
+        };
+        let chunk = TransactionChunk {
+            first_version,
+            last_version,
+            transactions: write(format!("{}-.chunk", first_version), vec![1, 2, 3]).await,
+            proof: write(format!("{}-{}.proof", first_version, last_version), vec![4]).await,
+        };
+        let manifest = TransactionBackup {
+            first_version,
+            last_version,
+            chunks: vec![chunk],
+            integrity: None,
+        };
+        let manifest_handle = write(
+            "transaction.manifest".to_string(),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .await;
+        let metadata =
+            Metadata::new_transaction_backup(first_version, last_version, manifest_handle);
+        storage
+            .save_metadata_line(&metadata.name(), &metadata.to_text_line().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_merge() {
+        let dir = TempPath::new();
+        dir.create_as_dir().unwrap();
+        let source: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(dir.path().join("source")));
+        let destination: Arc<dyn BackupStorage> =
+            Arc::new(LocalFs::new(dir.path().join("destination")));
+        write_transaction_backup(&source, 0, 99).await;
+        write_transaction_backup(&source, 100, 199).await;
+        write_transaction_backup(&destination, 100, 199).await;
+        let cache_opt = MetadataCacheOpt::new(Some(dir.path().join("cache")));
+
+        MetadataMergeCoordinator::new(
+            source.clone(),
+            destination.clone(),
+            MetadataCacheOpt::new(Some(dir.path().join("cache"))),
+            4,
+        )
+        .with_dry_run(true)
+        .run()
+        .await
+        .unwrap();
+        let view = cache::sync_and_load(&cache_opt.for_storage("check"), destination.clone(), 4)
+            .await
+            .unwrap();
+        assert_eq!(view.transaction_backups().len(), 1);
+
+        MetadataMergeCoordinator::new(
+            source.clone(),
+            destination.clone(),
+            MetadataCacheOpt::new(Some(dir.path().join("cache"))),
+            4,
+        )
+        .run()
+        .await
+        .unwrap();
+        let view = cache::sync_and_load(&cache_opt.for_storage("check"), destination.clone(), 4)
+            .await
+            .unwrap();
+        assert!(view.find_gaps().is_empty());
+        let backups = view.select_transaction_backups(0, 199).unwrap();
+        assert_eq!(backups.len(), 2);
+        let manifest: TransactionBackup = destination
+            .load_json_file(&backups[0].manifest)
+            .await
+            .unwrap();
+        assert_eq!(
+            destination
+                .read_all(&manifest.chunks[0].transactions)
+                .await
+                .unwrap(),
+            vec![1, 2, 3]
+        );
+        // The copy comes with an integrity manifest.
+        assert!(manifest.integrity.is_some());
+    }
+}
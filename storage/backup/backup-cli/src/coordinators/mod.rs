@@ -3,6 +3,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod backup;
+pub mod gap_repair;
+pub mod metadata_merge;
 pub mod replay_verify;
 pub mod restore;
+pub mod staleness_check;
 pub mod verify;
@@ -142,6 +142,7 @@ impl ReplayVerifyCoordinator {
                     manifest_handle: backup.manifest,
                     version: backup.version,
                     validate_modules: self.validate_modules,
+                    priority_state: Default::default(),
                 },
                 global_opt.clone(),
                 Arc::clone(&self.storage),
@@ -4,22 +4,29 @@
 
 use crate::{
     backup_types::{
-        epoch_ending::restore::EpochHistoryRestoreController,
-        state_snapshot::restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
+        epoch_ending::restore::{EpochHistory, EpochHistoryRestoreController},
+        integrity::BackupIntegrity,
+        state_snapshot::{
+            manifest::StateSnapshotBackup,
+            restore::{
+                verify_root_hash, PriorityStateOpt, StateSnapshotRestoreController,
+                StateSnapshotRestoreOpt,
+            },
+        },
         transaction::restore::TransactionRestoreBatchController,
     },
     metadata,
-    metadata::{cache::MetadataCacheOpt, TransactionBackupMeta},
+    metadata::{cache::MetadataCacheOpt, view::MetadataView, ChainIdentity, TransactionBackupMeta},
     metrics::restore::{
         COORDINATOR_FAIL_TS, COORDINATOR_START_TS, COORDINATOR_SUCC_TS, COORDINATOR_TARGET_VERSION,
     },
     storage::BackupStorage,
-    utils::{unix_timestamp_sec, GlobalRestoreOptions},
+    utils::{storage_ext::BackupStorageExt, unix_timestamp_sec, GlobalRestoreOptions},
 };
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use aptos_executor_types::VerifyExecutionMode;
 use aptos_logger::prelude::*;
-use aptos_types::transaction::Version;
+use aptos_types::{transaction::Version, waypoint::Waypoint};
 use clap::Parser;
 use std::sync::Arc;
 
@@ -41,6 +48,23 @@ pub struct RestoreCoordinatorOpt {
     pub ledger_history_start_version: Option<Version>,
     #[clap(long, help = "Skip restoring epoch ending info, used for debugging.")]
     pub skip_epoch_endings: bool,
+    #[clap(
+        long,
+        help = "If the DB already has data, e.g. from a previous restore, extend it forward by \
+        replaying the transactions backed up since, up to --target-version, instead of leaving \
+        it for the node to catch up."
+    )]
+    pub catch_up: bool,
+    #[clap(
+        long,
+        conflicts_with_all = &["catch-up", "skip-epoch-endings"],
+        help = "Only restore the epoch ending ledger infos up to --target-version, and verify the \
+        header of the latest state snapshot before it against them, for a DB small enough to \
+        bootstrap a node syncing its state from the waypoint of the latest epoch."
+    )]
+    pub epoch_history_only: bool,
+    #[clap(flatten)]
+    pub priority_state: PriorityStateOpt,
 }
 
 pub struct RestoreCoordinator {
@@ -50,6 +74,9 @@ pub struct RestoreCoordinator {
     replay_all: bool,
     ledger_history_start_version: Option<Version>,
     skip_epoch_endings: bool,
+    catch_up: bool,
+    epoch_history_only: bool,
+    priority_state: PriorityStateOpt,
 }
 
 impl RestoreCoordinator {
@@ -65,6 +92,9 @@ impl RestoreCoordinator {
             replay_all: opt.replay_all,
             ledger_history_start_version: opt.ledger_history_start_version,
             skip_epoch_endings: opt.skip_epoch_endings,
+            catch_up: opt.catch_up,
+            epoch_history_only: opt.epoch_history_only,
+            priority_state: opt.priority_state,
         }
     }
 
@@ -88,6 +118,37 @@ impl RestoreCoordinator {
         ret
     }
 
+    /// Refuses to restore backups of another chain than the one of the target DB, or of the
+    /// trusted genesis waypoint.
+    fn verify_chain_identity(&self, metadata_view: &MetadataView) -> Result<()> {
+        let recorded = match metadata_view.chain_identity()? {
+            Some(recorded) => recorded,
+            None => {
+                warn!(
+                    "The backup storage doesn't record which chain its backups are of, the target \
+                    DB is not verified against it."
+                );
+                return Ok(());
+            },
+        };
+        if let Some(waypoint) = self.global_opt.trusted_waypoints.get(&0) {
+            ensure!(
+                waypoint.value() == recorded.genesis_hash,
+                "The trusted genesis waypoint {} is of another chain than the backup storage, \
+                which holds the backups of chain {}.",
+                waypoint,
+                recorded,
+            );
+        }
+        if let Some(genesis) = self.global_opt.run_mode.get_genesis_ledger_info()? {
+            recorded.ensure_same_chain(
+                &ChainIdentity::from_genesis(genesis.ledger_info(), None)?,
+                "The target DB",
+            )?;
+        }
+        Ok(())
+    }
+
     async fn run_impl(mut self) -> Result<()> {
         // N.b.
         // The coordinator now focuses on doing one procedure, ignoring the combination of options
@@ -108,12 +169,20 @@ impl RestoreCoordinator {
             self.global_opt.concurrent_downloads,
         )
         .await?;
+        self.verify_chain_identity(&metadata_view)?;
+
+        if self.epoch_history_only {
+            return self.restore_epoch_history_only(metadata_view).await;
+        }
 
         let next_txn_version = self
             .global_opt
             .run_mode
             .get_next_expected_transaction_version()?;
         if next_txn_version != 0 {
+            if self.catch_up {
+                return self.catch_up(metadata_view, next_txn_version).await;
+            }
             // DB is already in workable state
             info!(
                 next_txn_version = next_txn_version,
@@ -141,34 +210,19 @@ impl RestoreCoordinator {
             };
         let version = state_snapshot_backup.version;
         self.global_opt.target_version = version;
-        let epoch_ending_backups = metadata_view.select_epoch_ending_backups(version)?;
         let transaction_backups = metadata_view
             .select_transaction_backups(self.ledger_history_start_version(), version)?;
         COORDINATOR_TARGET_VERSION.set(version as i64);
         info!(version = version, "Restore target decided.");
 
-        let epoch_history = if !self.skip_epoch_endings {
-            Some(Arc::new(
-                EpochHistoryRestoreController::new(
-                    epoch_ending_backups
-                        .into_iter()
-                        .map(|backup| backup.manifest)
-                        .collect(),
-                    self.global_opt.clone(),
-                    self.storage.clone(),
-                )
-                .run()
-                .await?,
-            ))
-        } else {
-            None
-        };
+        let epoch_history = self.restore_epoch_history(&metadata_view, version).await?;
 
         StateSnapshotRestoreController::new(
             StateSnapshotRestoreOpt {
                 manifest_handle: state_snapshot_backup.manifest,
                 version,
                 validate_modules: false,
+                priority_state: self.priority_state.clone(),
             },
             self.global_opt.clone(),
             Arc::clone(&self.storage),
@@ -194,6 +248,127 @@ impl RestoreCoordinator {
 
         Ok(())
     }
+
+    /// Extends a DB that's already in workable state, i.e. whose state is at its latest
+    /// transaction, by replaying the transactions backed up after it.
+    async fn catch_up(
+        mut self,
+        metadata_view: MetadataView,
+        next_txn_version: Version,
+    ) -> Result<()> {
+        let max_txn_ver = metadata_view
+            .max_transaction_version()?
+            .ok_or_else(|| anyhow!("No transaction backup found."))?;
+        let version = std::cmp::min(self.target_version(), max_txn_ver);
+        if version < next_txn_version {
+            info!(
+                next_txn_version = next_txn_version,
+                target_version = version,
+                "DB is already caught up with the backups.",
+            );
+            return Ok(());
+        }
+        self.global_opt.target_version = version;
+        // The first backup can start before the DB ends, transactions already in the DB are
+        // saved again, but not replayed.
+        let transaction_backups =
+            metadata_view.select_transaction_backups(next_txn_version, version)?;
+        COORDINATOR_TARGET_VERSION.set(version as i64);
+        info!(
+            next_txn_version = next_txn_version,
+            version = version,
+            "Catching up DB in place."
+        );
+
+        // Epoch endings after the DB are only in the backups, and the ones before are needed to
+        // verify them.
+        let epoch_history = self.restore_epoch_history(&metadata_view, version).await?;
+
+        let txn_manifests = transaction_backups
+            .into_iter()
+            .map(|e| e.manifest)
+            .collect();
+        TransactionRestoreBatchController::new(
+            self.global_opt,
+            self.storage,
+            txn_manifests,
+            Some(next_txn_version), /* replay_from_version */
+            epoch_history,
+            VerifyExecutionMode::NoVerify,
+        )
+        .run()
+        .await?;
+
+        Ok(())
+    }
+
+    /// Restores the epoch history alone, leaving the state and the ledger history for the node to
+    /// sync, and checks the latest state snapshot against it, so the node can sync to it.
+    async fn restore_epoch_history_only(self, metadata_view: MetadataView) -> Result<()> {
+        let version = self.target_version();
+        let epoch_history = self
+            .restore_epoch_history(&metadata_view, version)
+            .await?
+            .ok_or_else(|| anyhow!("Epoch endings skipped, nothing to restore."))?;
+        let latest = epoch_history
+            .epoch_endings
+            .last()
+            .ok_or_else(|| anyhow!("No epoch ending backup found."))?;
+        let waypoint = Waypoint::new_epoch_boundary(latest)?;
+
+        match metadata_view.select_state_snapshot(version)? {
+            Some(state_snapshot) => {
+                let manifest: StateSnapshotBackup = self
+                    .storage
+                    .load_json_file(&state_snapshot.manifest)
+                    .await?;
+                let integrity =
+                    BackupIntegrity::load(&self.storage, manifest.integrity.as_deref(), [manifest
+                        .proof
+                        .as_str()])
+                    .await?;
+                verify_root_hash(&self.storage, &integrity, &manifest, Some(&epoch_history))
+                    .await?;
+                info!(
+                    version = manifest.version,
+                    epoch = manifest.epoch,
+                    root_hash = %manifest.root_hash,
+                    "Latest state snapshot verified against the epoch history.",
+                );
+            },
+            None => warn!("No state snapshot to verify against the epoch history."),
+        }
+        info!(
+            epoch = latest.epoch(),
+            version = latest.version(),
+            waypoint = %waypoint,
+            "Epoch history restored, start the node with this waypoint to sync the rest.",
+        );
+        Ok(())
+    }
+
+    async fn restore_epoch_history(
+        &self,
+        metadata_view: &MetadataView,
+        version: Version,
+    ) -> Result<Option<Arc<EpochHistory>>> {
+        if self.skip_epoch_endings {
+            return Ok(None);
+        }
+        let epoch_ending_backups = metadata_view.select_epoch_ending_backups(version)?;
+        Ok(Some(Arc::new(
+            EpochHistoryRestoreController::new(
+                epoch_ending_backups
+                    .into_iter()
+                    .map(|backup| backup.manifest)
+                    .collect(),
+                self.global_opt.clone(),
+                self.storage.clone(),
+            )
+            .run()
+            .await?,
+        )))
+    }
 }
 
 impl RestoreCoordinator {
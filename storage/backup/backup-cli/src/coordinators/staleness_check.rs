@@ -0,0 +1,131 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    backup_types::transaction::manifest::TransactionBackup,
+    metadata,
+    metadata::cache::MetadataCacheOpt,
+    storage::BackupStorage,
+    utils::{
+        alert_hooks::{Alert, AlertHooks, AlertKind},
+        storage_ext::BackupStorageExt,
+        unix_timestamp_sec,
+    },
+};
+use anyhow::{anyhow, bail, Result};
+use aptos_logger::prelude::*;
+use aptos_types::{ledger_info::LedgerInfoWithSignatures, proof::TransactionAccumulatorRangeProof};
+use std::sync::Arc;
+
+/// Checks that the newest transaction backup is recent enough, alerting otherwise.
+///
+/// The age of a backup is measured by the ledger timestamp of the `LedgerInfo` proving its last
+/// chunk, which is at least as recent as the last transaction in it.
+pub struct StalenessCheckCoordinator {
+    storage: Arc<dyn BackupStorage>,
+    metadata_cache_opt: MetadataCacheOpt,
+    concurrent_downloads: usize,
+    max_age_secs: u64,
+    alert_hooks: AlertHooks,
+}
+
+impl StalenessCheckCoordinator {
+    pub fn new(
+        storage: Arc<dyn BackupStorage>,
+        metadata_cache_opt: MetadataCacheOpt,
+        concurrent_downloads: usize,
+        max_age_secs: u64,
+        alert_hooks: AlertHooks,
+    ) -> Self {
+        Self {
+            storage,
+            metadata_cache_opt,
+            concurrent_downloads,
+            max_age_secs,
+            alert_hooks,
+        }
+    }
+
+    /// Fails if the backup is stale, after firing the alert hooks.
+    pub async fn run(self) -> Result<()> {
+        let timestamp_usecs = self.latest_backup_timestamp_usecs().await?;
+        let now_secs = unix_timestamp_sec();
+        match staleness_message(timestamp_usecs, now_secs, self.max_age_secs) {
+            Some(message) => {
+                error!("{}", message);
+                self.alert_hooks
+                    .fire(Alert::new(AlertKind::BackupStale, message.clone()))
+                    .await;
+                bail!(message)
+            },
+            None => {
+                info!(
+                    "Newest transaction backup is {} seconds old.",
+                    now_secs - (timestamp_usecs.unwrap_or_default() / 1_000_000) as i64
+                );
+                Ok(())
+            },
+        }
+    }
+
+    async fn latest_backup_timestamp_usecs(&self) -> Result<Option<u64>> {
+        let metadata_view = metadata::cache::sync_and_load(
+            &self.metadata_cache_opt,
+            Arc::clone(&self.storage),
+            self.concurrent_downloads,
+        )
+        .await?;
+        let backup = match metadata_view.latest_transaction_backup() {
+            Some(backup) => backup,
+            None => return Ok(None),
+        };
+        let manifest: TransactionBackup = self.storage.load_json_file(&backup.manifest).await?;
+        let chunk = manifest
+            .chunks
+            .last()
+            .ok_or_else(|| anyhow!("No chunks in {}.", backup.manifest))?;
+        let (_, ledger_info) = self
+            .storage
+            .load_bcs_file::<(TransactionAccumulatorRangeProof, LedgerInfoWithSignatures)>(
+                &chunk.proof,
+            )
+            .await?;
+        Ok(Some(ledger_info.ledger_info().timestamp_usecs()))
+    }
+}
+
+/// Describes why the backup is stale, if it is.
+fn staleness_message(
+    latest_timestamp_usecs: Option<u64>,
+    now_secs: i64,
+    max_age_secs: u64,
+) -> Option<String> {
+    let timestamp_secs = match latest_timestamp_usecs {
+        Some(usecs) => (usecs / 1_000_000) as i64,
+        None => return Some("No transaction backup found.".to_string()),
+    };
+    let age_secs = now_secs - timestamp_secs;
+    if age_secs > max_age_secs as i64 {
+        Some(format!(
+            "Newest transaction backup is {} seconds old, more than the {} allowed.",
+            age_secs, max_age_secs,
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::staleness_message;
+
+    #[test]
+    fn test_staleness_message() {
+        let now = 1_700_000_000;
+        assert!(staleness_message(None, now, 3600).is_some());
+        assert!(staleness_message(Some((now as u64 - 3600) * 1_000_000), now, 3600).is_none());
+        assert!(staleness_message(Some((now as u64 - 3601) * 1_000_000), now, 3600).is_some());
+        // Clocks being slightly off doesn't make a backup stale.
+        assert!(staleness_message(Some((now as u64 + 5) * 1_000_000), now, 3600).is_none());
+    }
+}
@@ -4,29 +4,119 @@
 
 use crate::{
     backup_types::{
-        epoch_ending::restore::EpochHistoryRestoreController,
-        state_snapshot::restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
-        transaction::restore::TransactionRestoreBatchController,
+        epoch_ending::restore::{EpochHistory, EpochHistoryRestoreController},
+        integrity::BackupIntegrity,
+        state_snapshot::{
+            manifest::StateSnapshotBackup,
+            restore::{
+                verify_chunk_content, verify_root_hash, StateSnapshotRestoreController,
+                StateSnapshotRestoreOpt,
+            },
+        },
+        transaction::{
+            manifest::TransactionBackup,
+            restore::{verify_chunk, TransactionRestoreBatchController},
+        },
     },
     metadata,
     metadata::cache::MetadataCacheOpt,
     metrics::verify::{
         VERIFY_COORDINATOR_FAIL_TS, VERIFY_COORDINATOR_START_TS, VERIFY_COORDINATOR_SUCC_TS,
     },
-    storage::BackupStorage,
-    utils::{unix_timestamp_sec, GlobalRestoreOptions, RestoreRunMode, TrustedWaypointOpt},
+    storage::{BackupStorage, FileHandle, FileHandleRef},
+    utils::{
+        storage_ext::BackupStorageExt, stream::StreamX, unix_timestamp_sec, GlobalRestoreOptions,
+        RestoreRunMode, TrustedWaypointOpt,
+    },
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
+use aptos_crypto::HashValue;
 use aptos_executor_types::VerifyExecutionMode;
 use aptos_logger::prelude::*;
 use aptos_types::transaction::Version;
+use clap::Parser;
+use futures::{stream, TryStreamExt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::sync::Arc;
 
+#[derive(Clone, Default, Parser)]
+pub struct VerifySamplingOpt {
+    #[clap(
+        long,
+        help = "Verify about this percentage of the chunks of each transaction and state snapshot \
+        backup, picked at random, rather than all of them, for a quick integrity check. The \
+        manifests, the proofs of the state snapshots and the epoch ending backups are verified in \
+        full all the same. A sampled state snapshot chunk is checked against its hash and the \
+        manifest, but not against the root hash, which needs all the chunks before it."
+    )]
+    pub sample_percent: Option<f64>,
+    #[clap(
+        long,
+        help = "Seed of the chunk sampling, to verify the same chunks again. [Defaults to a random \
+        seed, which is logged]"
+    )]
+    pub sample_seed: Option<u64>,
+}
+
+impl VerifySamplingOpt {
+    fn sampler(&self) -> Result<Option<ChunkSampler>> {
+        let percent = match self.sample_percent {
+            Some(percent) => percent,
+            None => return Ok(None),
+        };
+        ensure!(
+            percent > 0.0 && percent <= 100.0,
+            "--sample-percent must be within (0, 100], got {}",
+            percent,
+        );
+        let seed = self.sample_seed.unwrap_or_else(|| rand::thread_rng().gen());
+        info!(
+            sample_percent = percent,
+            sample_seed = seed,
+            "Verifying a sample of the chunks."
+        );
+        Ok(Some(ChunkSampler { percent, seed }))
+    }
+}
+
+/// Picks the chunks of a backup to verify. The picks only depend on the seed and the backup, so
+/// the same seed picks the same chunks of a backup as long as it exists.
+pub struct ChunkSampler {
+    percent: f64,
+    seed: u64,
+}
+
+impl ChunkSampler {
+    /// Indices of the chunks to verify, at least one if there are any.
+    pub fn sample(&self, manifest_handle: &FileHandleRef, num_chunks: usize) -> Vec<usize> {
+        let manifest_hash = HashValue::sha3_256_of(manifest_handle.as_bytes());
+        let mut seed = [0u8; 32];
+        for (byte, (seed_byte, hash_byte)) in seed.iter_mut().zip(
+            self.seed
+                .to_le_bytes()
+                .iter()
+                .cycle()
+                .zip(manifest_hash.as_ref()),
+        ) {
+            *byte = seed_byte ^ hash_byte;
+        }
+        let mut rng = StdRng::from_seed(seed);
+        let mut sampled = (0..num_chunks)
+            .filter(|_| rng.gen_bool(self.percent / 100.0))
+            .collect::<Vec<_>>();
+        if sampled.is_empty() && num_chunks > 0 {
+            sampled.push(rng.gen_range(0..num_chunks));
+        }
+        sampled
+    }
+}
+
 pub struct VerifyCoordinator {
     storage: Arc<dyn BackupStorage>,
     metadata_cache_opt: MetadataCacheOpt,
     trusted_waypoints_opt: TrustedWaypointOpt,
     concurrent_downloads: usize,
+    sampling_opt: VerifySamplingOpt,
 }
 
 impl VerifyCoordinator {
@@ -35,12 +125,14 @@ impl VerifyCoordinator {
         metadata_cache_opt: MetadataCacheOpt,
         trusted_waypoints_opt: TrustedWaypointOpt,
         concurrent_downloads: usize,
+        sampling_opt: VerifySamplingOpt,
     ) -> Result<Self> {
         Ok(Self {
             storage,
             metadata_cache_opt,
             trusted_waypoints_opt,
             concurrent_downloads,
+            sampling_opt,
         })
     }
 
@@ -64,6 +156,7 @@ impl VerifyCoordinator {
     }
 
     async fn run_impl(self) -> Result<()> {
+        let sampler = self.sampling_opt.sampler()?;
         let metadata_view = metadata::cache::sync_and_load(
             &self.metadata_cache_opt,
             Arc::clone(&self.storage),
@@ -74,6 +167,14 @@ impl VerifyCoordinator {
         let state_snapshot = metadata_view.select_state_snapshot(ver_max)?;
         let transactions = metadata_view.select_transaction_backups(0, ver_max)?;
         let epoch_endings = metadata_view.select_epoch_ending_backups(ver_max)?;
+        self.log_tags(
+            epoch_endings
+                .iter()
+                .map(|b| &b.manifest)
+                .chain(state_snapshot.iter().map(|b| &b.manifest))
+                .chain(transactions.iter().map(|b| &b.manifest)),
+        )
+        .await;
 
         let global_opt = GlobalRestoreOptions {
             target_version: ver_max,
@@ -96,12 +197,25 @@ impl VerifyCoordinator {
             .await?,
         );
 
+        if let Some(sampler) = sampler {
+            if let Some(backup) = state_snapshot {
+                self.verify_state_snapshot_sample(&sampler, &backup.manifest, &epoch_history)
+                    .await?;
+            }
+            for backup in transactions {
+                self.verify_transactions_sample(&sampler, &backup.manifest, &epoch_history)
+                    .await?;
+            }
+            return Ok(());
+        }
+
         if let Some(backup) = state_snapshot {
             StateSnapshotRestoreController::new(
                 StateSnapshotRestoreOpt {
                     manifest_handle: backup.manifest,
                     version: backup.version,
                     validate_modules: false,
+                    priority_state: Default::default(),
                 },
                 global_opt.clone(),
                 Arc::clone(&self.storage),
@@ -125,4 +239,133 @@ impl VerifyCoordinator {
 
         Ok(())
     }
+
+    /// Logs the tags attached to the manifests of the backups to verify, if the storage keeps
+    /// tags, so the lifecycle rules keyed on them can be checked against what's verified. Tags
+    /// failing to be read don't fail the verification.
+    async fn log_tags(&self, manifests: impl Iterator<Item = &FileHandle>) {
+        for manifest in manifests {
+            match self.storage.read_tags(manifest).await {
+                Ok(Some(tags)) => info!(manifest = manifest, tags = %tags, "Backup tags."),
+                Ok(None) => {
+                    info!("Backup storage doesn't keep tags.");
+                    break;
+                },
+                Err(e) => warn!(
+                    manifest = manifest,
+                    error = ?e,
+                    "Failed to read backup tags."
+                ),
+            }
+        }
+    }
+
+    async fn verify_state_snapshot_sample(
+        &self,
+        sampler: &ChunkSampler,
+        manifest_handle: &FileHandle,
+        epoch_history: &Arc<EpochHistory>,
+    ) -> Result<()> {
+        let manifest: StateSnapshotBackup = self.storage.load_json_file(manifest_handle).await?;
+        // Sampled chunks are checked against the integrity manifest as they're read.
+        let integrity = BackupIntegrity::load(
+            &self.storage,
+            manifest.integrity.as_deref(),
+            manifest
+                .chunks
+                .iter()
+                .flat_map(|c| [c.blobs.as_str(), c.proof.as_str()])
+                .chain([manifest.proof.as_str()]),
+        )
+        .await?;
+        verify_root_hash(&self.storage, &integrity, &manifest, Some(epoch_history)).await?;
+        let sampled = sampler
+            .sample(manifest_handle, manifest.chunks.len())
+            .into_iter()
+            .map(|idx| &manifest.chunks[idx])
+            .collect::<Vec<_>>();
+
+        let con = self.concurrent_downloads;
+        let futs = sampled
+            .iter()
+            .map(|chunk| verify_chunk_content(&self.storage, &integrity, chunk));
+        stream::iter(futs)
+            .buffered_x(con * 2, con)
+            .try_collect::<Vec<_>>()
+            .await?;
+        info!(
+            manifest = manifest_handle,
+            sampled_chunks = sampled.len(),
+            total_chunks = manifest.chunks.len(),
+            "State snapshot sample verified."
+        );
+        Ok(())
+    }
+
+    async fn verify_transactions_sample(
+        &self,
+        sampler: &ChunkSampler,
+        manifest_handle: &FileHandle,
+        epoch_history: &Arc<EpochHistory>,
+    ) -> Result<()> {
+        let manifest: TransactionBackup = self.storage.load_json_file(manifest_handle).await?;
+        manifest.verify()?;
+        let integrity = BackupIntegrity::load(
+            &self.storage,
+            manifest.integrity.as_deref(),
+            manifest
+                .chunks
+                .iter()
+                .flat_map(|c| [c.transactions.as_str(), c.proof.as_str()]),
+        )
+        .await?;
+        let sampled = sampler
+            .sample(manifest_handle, manifest.chunks.len())
+            .into_iter()
+            .map(|idx| manifest.chunks[idx].clone())
+            .collect::<Vec<_>>();
+
+        let con = self.concurrent_downloads;
+        let num_sampled = sampled.len();
+        let futs = sampled
+            .into_iter()
+            .map(|chunk| verify_chunk(chunk, &self.storage, &integrity, Some(epoch_history)));
+        stream::iter(futs)
+            .buffered_x(con * 2, con)
+            .try_collect::<Vec<_>>()
+            .await?;
+        info!(
+            manifest = manifest_handle,
+            sampled_chunks = num_sampled,
+            total_chunks = manifest.chunks.len(),
+            "Transaction sample verified."
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkSampler;
+
+    #[test]
+    fn test_chunk_sampler() {
+        let sampler = ChunkSampler {
+            percent: 10.0,
+            seed: 42,
+        };
+        let sampled = sampler.sample("backup/transaction.manifest", 1000);
+        assert!(
+            sampled.len() > 50 && sampled.len() < 150,
+            "{}",
+            sampled.len()
+        );
+        assert!(sampled.windows(2).all(|pair| pair[0] < pair[1]));
+        // Reproducible for the same seed and backup.
+        assert_eq!(sampled, sampler.sample("backup/transaction.manifest", 1000));
+        assert_ne!(sampled, sampler.sample("other/transaction.manifest", 1000));
+        // At least a chunk, if any.
+        assert_eq!(sampler.sample("backup/transaction.manifest", 1).len(), 1);
+        assert!(sampler.sample("backup/transaction.manifest", 0).is_empty());
+    }
 }
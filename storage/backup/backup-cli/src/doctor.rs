@@ -0,0 +1,447 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Diagnoses the environment of the backup tools: the backup storage, the backup service, the
+//! local disk and the metadata cache, printing what to fix for each check that fails.
+
+use crate::{
+    metadata::cache::{self, MetadataCacheOpt},
+    storage::{BackupStorage, ShellSafeName, StorageOpt},
+    utils::backup_service_client::BackupServiceClient,
+};
+use anyhow::{ensure, Result};
+use clap::Parser;
+use std::{
+    fmt,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use sysinfo::{DiskExt, System, SystemExt};
+use tokio::{
+    fs::{create_dir_all, remove_file},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+#[derive(Parser)]
+pub struct DoctorOpt {
+    #[clap(
+        long,
+        help = "Only check that the backup storage can be listed and read from, without writing \
+        a probe backup to it."
+    )]
+    pub read_only: bool,
+
+    #[clap(
+        long,
+        default_value = "10",
+        help = "Warn when the disk of the metadata cache has less than this many GiB free."
+    )]
+    pub min_free_space_gib: u64,
+
+    #[clap(
+        long,
+        help = "Don't check the backup service, e.g. when only restoring from the backup storage."
+    )]
+    pub skip_backup_service: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What the operator can do about it, if not ok.
+    pub fix: Option<&'static str>,
+}
+
+impl CheckResult {
+    fn ok(name: &'static str, detail: String) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail,
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: String, fix: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warning,
+            detail,
+            fix: Some(fix),
+        }
+    }
+
+    fn failed(name: &'static str, detail: String, fix: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Failed,
+            detail,
+            fix: Some(fix),
+        }
+    }
+
+    fn skipped(name: &'static str, detail: String) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skipped,
+            detail,
+            fix: None,
+        }
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.status {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARN",
+            CheckStatus::Failed => "FAIL",
+            CheckStatus::Skipped => "SKIP",
+        };
+        write!(f, "[{:>4}] {}: {}", status, self.name, self.detail)?;
+        if let Some(fix) = self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Doctor {
+    opt: DoctorOpt,
+    metadata_cache_opt: MetadataCacheOpt,
+    concurrent_downloads: usize,
+    client: Arc<BackupServiceClient>,
+}
+
+impl Doctor {
+    pub fn new(
+        opt: DoctorOpt,
+        metadata_cache_opt: MetadataCacheOpt,
+        concurrent_downloads: usize,
+        client: Arc<BackupServiceClient>,
+    ) -> Self {
+        Self {
+            opt,
+            metadata_cache_opt,
+            concurrent_downloads,
+            client,
+        }
+    }
+
+    /// Runs all the checks, printing each as it completes. Fails if any of them failed.
+    pub async fn run(self, storage: StorageOpt) -> Result<()> {
+        let results = self.check_all(storage).await;
+        let num_failed = results
+            .iter()
+            .filter(|r| r.status == CheckStatus::Failed)
+            .count();
+        ensure!(num_failed == 0, "{} check(s) failed.", num_failed);
+        Ok(())
+    }
+
+    pub async fn check_all(&self, storage: StorageOpt) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+        let mut report = |result: CheckResult| {
+            println!("{}", result);
+            results.push(result);
+        };
+
+        match storage.init_storage().await {
+            Ok(storage) => {
+                report(CheckResult::ok(
+                    "storage init",
+                    "Backup storage configured.".to_string(),
+                ));
+                let (list_result, num_metadata_files) = Self::check_list(&storage).await;
+                report(list_result);
+                if self.opt.read_only {
+                    report(CheckResult::skipped(
+                        "storage write",
+                        "--read-only given.".to_string(),
+                    ));
+                } else {
+                    report(Self::check_write_and_read(&storage).await);
+                }
+                report(CheckResult::skipped(
+                    "storage delete",
+                    "The backup tools never delete from the backup storage, no need for the \
+                    permission."
+                        .to_string(),
+                ));
+                match num_metadata_files {
+                    Some(0) if self.opt.read_only => report(CheckResult::warning(
+                        "metadata cache",
+                        "The backup storage has no metadata yet, and --read-only keeps the \
+                        identity metadata from being written."
+                            .to_string(),
+                        "Point at the storage holding the backups, or run without --read-only.",
+                    )),
+                    Some(_) => report(self.check_metadata_cache(storage).await),
+                    None => report(CheckResult::skipped(
+                        "metadata cache",
+                        "The backup storage can't be listed.".to_string(),
+                    )),
+                }
+            },
+            Err(e) => {
+                report(CheckResult::failed(
+                    "storage init",
+                    format!("{:#}", e),
+                    "Check the storage subcommand arguments, e.g. that the command adapter \
+                    config or the SFTP key file exists and parses.",
+                ));
+            },
+        }
+
+        report(self.check_cache_dir().await);
+        report(self.check_free_space());
+        if self.opt.skip_backup_service {
+            report(CheckResult::skipped(
+                "backup service",
+                "--skip-backup-service given.".to_string(),
+            ));
+        } else {
+            report(Self::check_backup_service(&self.client).await);
+        }
+        results
+    }
+
+    async fn check_list(storage: &Arc<dyn BackupStorage>) -> (CheckResult, Option<usize>) {
+        match storage.list_metadata_files().await {
+            Ok(files) => (
+                CheckResult::ok(
+                    "storage list",
+                    format!("{} metadata files listed.", files.len()),
+                ),
+                Some(files.len()),
+            ),
+            Err(e) => (
+                CheckResult::failed(
+                    "storage list",
+                    format!("{:#}", e),
+                    "Check the credentials of the storage and that they grant listing the \
+                    bucket or directory, e.g. s3:ListBucket or storage.objects.list.",
+                ),
+                None,
+            ),
+        }
+    }
+
+    /// Writes a small file in a backup of its own, not referred to by any metadata, and reads it
+    /// back.
+    async fn check_write_and_read(storage: &Arc<dyn BackupStorage>) -> CheckResult {
+        const NAME: &str = "storage write";
+        const FIX: &str = "Check that the credentials of the storage grant creating and reading \
+            objects, e.g. s3:PutObject and s3:GetObject, and that the commands of the command \
+            adapter config exit with 0.";
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match write_and_read_probe(storage, now).await {
+            Ok(file_handle) => CheckResult::ok(
+                NAME,
+                format!(
+                    "Wrote and read back {}, which is referred to by nothing and can be removed.",
+                    file_handle
+                ),
+            ),
+            Err(e) => CheckResult::failed(NAME, format!("{:#}", e), FIX),
+        }
+    }
+
+    async fn check_metadata_cache(&self, storage: Arc<dyn BackupStorage>) -> CheckResult {
+        match cache::sync_and_load(&self.metadata_cache_opt, storage, self.concurrent_downloads)
+            .await
+            .and_then(|view| view.get_storage_state())
+        {
+            Ok(state) => CheckResult::ok("metadata cache", format!("Synced. {}", state)),
+            Err(e) => CheckResult::failed(
+                "metadata cache",
+                format!("{:#}", e),
+                "If a cached file fails to parse, remove the metadata cache dir for it to be \
+                downloaded again. Never share a cache dir across networks.",
+            ),
+        }
+    }
+
+    async fn check_cache_dir(&self) -> CheckResult {
+        const NAME: &str = "metadata cache dir";
+
+        let dir = self.metadata_cache_opt.cache_dir();
+        match write_probe_file(&dir).await {
+            Ok(()) => CheckResult::ok(NAME, format!("{} is writable.", dir.display())),
+            Err(e) => CheckResult::failed(
+                NAME,
+                format!("{}: {:#}", dir.display(), e),
+                "Pass a --metadata-cache-dir the user running the tools can write to.",
+            ),
+        }
+    }
+
+    fn check_free_space(&self) -> CheckResult {
+        const NAME: &str = "free disk space";
+
+        let dir = self.metadata_cache_opt.cache_dir();
+        match available_space(&dir) {
+            Some(available) => {
+                let detail = format!(
+                    "{:.1} GiB free on the disk of {}.",
+                    available as f64 / (1u64 << 30) as f64,
+                    dir.display()
+                );
+                if available < self.opt.min_free_space_gib << 30 {
+                    CheckResult::warning(
+                        NAME,
+                        detail,
+                        "Free up space, or put the metadata cache and restore targets on a \
+                        larger disk; the metadata of a long chain takes gigabytes.",
+                    )
+                } else {
+                    CheckResult::ok(NAME, detail)
+                }
+            },
+            None => {
+                CheckResult::skipped(NAME, format!("Can't tell the disk of {}.", dir.display()))
+            },
+        }
+    }
+
+    async fn check_backup_service(client: &BackupServiceClient) -> CheckResult {
+        const NAME: &str = "backup service";
+
+        let db_state = match client.get_db_state().await {
+            Ok(Some(db_state)) => db_state,
+            Ok(None) => {
+                return CheckResult::warning(
+                    NAME,
+                    "Reachable, but the DB of the node isn't bootstrapped.".to_string(),
+                    "Wait for the node to sync, or point at another node.",
+                )
+            },
+            Err(e) => {
+                return CheckResult::failed(
+                    NAME,
+                    format!("{:#}", e),
+                    "Check that the node runs with the backup service enabled, and that its \
+                    address (storage.backup_service_address in the node config, localhost:6186 \
+                    by default) is reachable from here.",
+                )
+            },
+        };
+        // Only recent backup services tell the range of data they hold.
+        match client.get_db_metadata().await {
+            Ok(_) => CheckResult::ok(NAME, format!("Reachable. {}", db_state)),
+            Err(_) => CheckResult::warning(
+                NAME,
+                format!("Reachable, but doesn't serve the DB metadata. {}", db_state),
+                "Upgrade the node, older backup services can't tell whether the data asked for \
+                was pruned.",
+            ),
+        }
+    }
+}
+
+async fn write_and_read_probe(storage: &Arc<dyn BackupStorage>, now: u64) -> Result<String> {
+    let content = format!("aptos backup doctor probe {}\n", now);
+    let backup_name: ShellSafeName = format!("doctor_probe_{}", now).parse()?;
+    let backup_handle = storage.create_backup(&backup_name).await?;
+    let (file_handle, mut file) = storage
+        .create_for_write(&backup_handle, &"probe".parse()?)
+        .await?;
+    file.write_all(content.as_bytes()).await?;
+    file.shutdown().await?;
+
+    let mut read_back = String::new();
+    storage
+        .open_for_read(&file_handle)
+        .await?
+        .read_to_string(&mut read_back)
+        .await?;
+    ensure!(
+        read_back == content,
+        "Read back {:?} from {}, written {:?}.",
+        read_back,
+        file_handle,
+        content,
+    );
+    Ok(file_handle)
+}
+
+async fn write_probe_file(dir: &Path) -> Result<()> {
+    let probe = dir.join(".doctor_probe");
+    create_dir_all(dir).await?;
+    tokio::fs::write(&probe, b"probe").await?;
+    remove_file(&probe).await?;
+    Ok(())
+}
+
+/// Available space of the disk holding `path`, i.e. the one with the longest mount point it's
+/// under.
+fn available_space(path: &Path) -> Option<u64> {
+    let path = path.canonicalize().ok()?;
+    let mut system = System::new();
+    system.refresh_disks_list();
+    system
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckStatus, Doctor, DoctorOpt};
+    use crate::{
+        metadata::cache::MetadataCacheOpt,
+        storage::{local_fs::LocalFsOpt, StorageOpt},
+        utils::backup_service_client::BackupServiceClient,
+    };
+    use aptos_temppath::TempPath;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_doctor_local_fs() {
+        let backup_dir = TempPath::new();
+        backup_dir.create_as_dir().unwrap();
+        let cache_dir = TempPath::new();
+
+        let doctor = Doctor::new(
+            DoctorOpt {
+                read_only: false,
+                min_free_space_gib: 0,
+                skip_backup_service: true,
+            },
+            MetadataCacheOpt::new(Some(cache_dir.path())),
+            4,
+            Arc::new(BackupServiceClient::new(
+                "http://localhost:6186".to_string(),
+            )),
+        );
+        let results = doctor
+            .check_all(StorageOpt::LocalFs(LocalFsOpt {
+                dir: backup_dir.path().to_path_buf(),
+            }))
+            .await;
+        let status = |name: &str| results.iter().find(|r| r.name == name).unwrap().status;
+        assert_eq!(status("storage list"), CheckStatus::Ok);
+        assert_eq!(status("storage write"), CheckStatus::Ok);
+        assert_eq!(status("metadata cache"), CheckStatus::Ok);
+        assert_eq!(status("metadata cache dir"), CheckStatus::Ok);
+        assert_eq!(status("backup service"), CheckStatus::Skipped);
+        assert!(results.iter().all(|r| r.status != CheckStatus::Failed));
+    }
+}
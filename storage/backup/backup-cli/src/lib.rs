@@ -6,6 +6,7 @@
 
 pub mod backup_types;
 pub mod coordinators;
+pub mod doctor;
 pub mod metadata;
 pub mod metrics;
 pub mod storage;
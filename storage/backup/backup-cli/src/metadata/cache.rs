@@ -22,7 +22,7 @@ use std::{
     time::Instant,
 };
 use tokio::{
-    fs::{create_dir_all, read_dir, remove_file, OpenOptions},
+    fs::{create_dir_all, read_dir, remove_file, DirEntry, OpenOptions},
     io::{AsyncRead, AsyncReadExt},
 };
 use tokio_stream::StreamExt;
@@ -57,7 +57,18 @@ impl MetadataCacheOpt {
         }
     }
 
-    fn cache_dir(&self) -> PathBuf {
+    /// A cache of its own, in a subdirectory, for the metadata of another storage. The caches of
+    /// different storages can't be shared, since files not in the storage are removed from it.
+    pub fn for_storage(&self, name: &str) -> Self {
+        Self::new(Some(
+            self.dir
+                .clone()
+                .unwrap_or_else(|| TEMP_METADATA_CACHE_DIR.path().to_path_buf())
+                .join(name),
+        ))
+    }
+
+    pub(crate) fn cache_dir(&self) -> PathBuf {
         self.dir
             .clone()
             .unwrap_or_else(|| TEMP_METADATA_CACHE_DIR.path().to_path_buf())
@@ -65,6 +76,94 @@ impl MetadataCacheOpt {
     }
 }
 
+/// Number of leading characters of a file handle hash naming the shard its cache file is in.
+///
+/// With hundreds of thousands of metadata files, listing a single flat directory is slow on some
+/// filesystems, so cached files live in `<cache_dir>/<first two chars of the hash>/<hash>`.
+const SHARD_PREFIX_LEN: usize = 2;
+
+fn shard_name(hash: &str) -> &str {
+    hash.get(..SHARD_PREFIX_LEN).unwrap_or(hash)
+}
+
+fn is_shard_name(name: &str) -> bool {
+    name.len() <= SHARD_PREFIX_LEN && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn cached_file_path(cache_dir: &Path, hash: &str) -> PathBuf {
+    cache_dir.join(shard_name(hash)).join(hash)
+}
+
+async fn list_dir(dir: &Path) -> Result<Vec<DirEntry>> {
+    let mut read_dir = read_dir(dir).await.err_notes(dir)?;
+    Ok(poll_fn(|ctx| {
+        ::std::task::Poll::Ready(match futures::ready!(read_dir.poll_next_entry(ctx)) {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        })
+    })
+    .collect::<tokio::io::Result<Vec<_>>>()
+    .await
+    .err_notes(dir)?)
+}
+
+fn entry_name(entry: &DirEntry) -> Result<String> {
+    entry
+        .file_name()
+        .into_string()
+        .map_err(|s| anyhow!("into_string() failed for file name {:?}", s))
+}
+
+/// Lists the hashes of the files in the cache, moving files cached by older versions directly in
+/// the cache dir into their shards on the way. Leftover temporary files of failed downloads are
+/// removed.
+async fn list_cached_hashes(cache_dir: &Path) -> Result<HashSet<String>> {
+    let mut hashes = HashSet::new();
+    let mut num_migrated = 0;
+    let mut shards = Vec::new();
+    for entry in list_dir(cache_dir).await? {
+        let name = entry_name(&entry)?;
+        let path = entry.path();
+        if entry.file_type().await.err_notes(&path)?.is_dir() {
+            if is_shard_name(&name) {
+                shards.push(path);
+            } else {
+                warn!("Unexpected dir in metadata cache: {:?}", path);
+            }
+        } else if name.starts_with('.') {
+            remove_file(&path).await.err_notes(&path)?;
+        } else {
+            let target = cached_file_path(cache_dir, &name);
+            create_dir_all(target.parent().expect("Has shard dir."))
+                .await
+                .err_notes(&target)?;
+            tokio::fs::rename(&path, &target).await.err_notes(&target)?;
+            hashes.insert(name);
+            num_migrated += 1;
+        }
+    }
+    if num_migrated > 0 {
+        info!(
+            num_migrated = num_migrated,
+            "Moved metadata files in unsharded cache into shards."
+        );
+    }
+
+    for shard in shards {
+        for entry in list_dir(&shard).await? {
+            let name = entry_name(&entry)?;
+            if name.starts_with('.') {
+                let path = entry.path();
+                remove_file(&path).await.err_notes(&path)?;
+            } else {
+                hashes.insert(name);
+            }
+        }
+    }
+    Ok(hashes)
+}
+
 /// Try to load the identity metadata, if not present, try to write one in.
 pub async fn initialize_identity(storage: &Arc<dyn BackupStorage>) -> Result<()> {
     let metadata = Metadata::new_random_identity();
@@ -84,24 +183,7 @@ pub async fn sync_and_load(
     create_dir_all(&cache_dir).await.err_notes(&cache_dir)?; // create if not present already
 
     // List cached metadata files.
-    let mut dir = read_dir(&cache_dir).await.err_notes(&cache_dir)?;
-    let local_entries = poll_fn(|ctx| {
-        ::std::task::Poll::Ready(match futures::ready!(dir.poll_next_entry(ctx)) {
-            Ok(Some(entry)) => Some(Ok(entry)),
-            Ok(None) => None,
-            Err(err) => Some(Err(err)),
-        })
-    })
-    .collect::<tokio::io::Result<Vec<_>>>()
-    .await?;
-    let local_hashes = local_entries
-        .iter()
-        .map(|e| {
-            e.file_name()
-                .into_string()
-                .map_err(|s| anyhow!("into_string() failed for file name {:?}", s))
-        })
-        .collect::<Result<HashSet<_>>>()?;
+    let local_hashes = list_cached_hashes(&cache_dir).await?;
 
     // List remote metadata files.
     let mut remote_file_handles = storage.list_metadata_files().await?;
@@ -128,7 +210,7 @@ pub async fn sync_and_load(
     let up_to_date_local_hashes = local_hashes.intersection(&remote_hashes);
 
     for h in stale_local_hashes {
-        let file = cache_dir.join(h);
+        let file = cached_file_path(&cache_dir, h);
         remove_file(&file).await.err_notes(&file)?;
         info!(file_name = h, "Deleted stale metadata file in cache.");
     }
@@ -143,8 +225,10 @@ pub async fn sync_and_load(
 
         async move {
             let file_handle = fh_by_h_ref.get(*h).expect("In map.");
-            let local_file = cache_dir_ref.join(*h);
-            let local_tmp_file = cache_dir_ref.join(format!(".{}", *h));
+            let local_file = cached_file_path(cache_dir_ref, h);
+            let shard_dir = local_file.parent().expect("Has shard dir.");
+            create_dir_all(shard_dir).await.err_notes(shard_dir)?;
+            let local_tmp_file = shard_dir.join(format!(".{}", *h));
             // download to tmp file ".xxxxxx"
             tokio::io::copy(
                 &mut storage_ref
@@ -182,21 +266,32 @@ pub async fn sync_and_load(
 
     info!("Loading all metadata files to memory.");
     // Load metadata from synced cache files.
-    let mut metadata_vec = Vec::new();
-    for h in new_remote_hashes.into_iter().chain(up_to_date_local_hashes) {
-        let cached_file = cache_dir.join(h);
-        metadata_vec.extend(
-            OpenOptions::new()
-                .read(true)
-                .open(&cached_file)
-                .await
-                .err_notes(&cached_file)?
-                .load_metadata_lines()
-                .await
-                .err_notes(&cached_file)?
-                .into_iter(),
+    let futs = new_remote_hashes
+        .into_iter()
+        .chain(up_to_date_local_hashes)
+        .map(|h| {
+            let cached_file = cached_file_path(&cache_dir, h);
+            async move {
+                OpenOptions::new()
+                    .read(true)
+                    .open(&cached_file)
+                    .await
+                    .err_notes(&cached_file)?
+                    .load_metadata_lines()
+                    .await
+                    .err_notes(&cached_file)
+            }
+        });
+    let metadata_vec = futures::stream::iter(futs)
+        .buffered_x(
+            concurrent_downloads * 2, /* buffer size */
+            concurrent_downloads,     /* concurrency */
         )
-    }
+        .collect::<Result<Vec<_>>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
     info!(
         total_time = timer.elapsed().as_secs(),
         "Metadata cache loaded.",
@@ -236,3 +331,60 @@ impl<R: AsyncRead + Send + Unpin> LoadMetadataLines for R {
             .collect::<Result<_, serde_json::error::Error>>()?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_file_path, sync_and_load, FileHandleHash, MetadataCacheOpt};
+    use crate::{
+        metadata::Metadata,
+        storage::{local_fs::LocalFs, BackupStorage},
+    };
+    use aptos_temppath::TempPath;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_migrate_unsharded_cache() {
+        let backup_dir = TempPath::new();
+        backup_dir.create_as_dir().unwrap();
+        let storage: Arc<dyn BackupStorage> =
+            Arc::new(LocalFs::new(backup_dir.path().to_path_buf()));
+        for i in 0..10 {
+            let metadata =
+                Metadata::new_transaction_backup(i * 100, i * 100 + 99, format!("manifest_{}", i));
+            storage
+                .save_metadata_line(&metadata.name(), &metadata.to_text_line().unwrap())
+                .await
+                .unwrap();
+        }
+        let cache_root = TempPath::new();
+        cache_root.create_as_dir().unwrap();
+        let opt = MetadataCacheOpt::new(Some(cache_root.path()));
+        let cache_dir = opt.cache_dir();
+
+        // Lay out the cache the way older versions did: all files directly in the cache dir.
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let file_handles = storage.list_metadata_files().await.unwrap();
+        for file_handle in &file_handles[..5] {
+            let content = std::fs::read(backup_dir.path().join(file_handle)).unwrap();
+            std::fs::write(cache_dir.join(file_handle.file_handle_hash()), content).unwrap();
+        }
+        std::fs::write(cache_dir.join(".interrupted"), "").unwrap();
+
+        let view = sync_and_load(&opt, storage.clone(), 2).await.unwrap();
+        assert_eq!(view.max_transaction_version().unwrap(), Some(999));
+
+        let top_level_files = std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|e| e.unwrap())
+            .filter(|e| !e.file_type().unwrap().is_dir())
+            .count();
+        assert_eq!(top_level_files, 0);
+        for file_handle in &file_handles {
+            assert!(cached_file_path(&cache_dir, &file_handle.file_handle_hash()).exists());
+        }
+
+        // Loading again from the sharded cache yields the same.
+        let view = sync_and_load(&opt, storage, 2).await.unwrap();
+        assert_eq!(view.max_transaction_version().unwrap(), Some(999));
+    }
+}
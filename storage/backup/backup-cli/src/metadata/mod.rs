@@ -6,11 +6,13 @@ pub mod cache;
 pub mod view;
 
 use crate::storage::{FileHandle, ShellSafeName, TextLine};
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use aptos_crypto::HashValue;
-use aptos_types::transaction::Version;
+use aptos_types::{
+    chain_id::ChainId, ledger_info::LedgerInfo, transaction::Version, waypoint::Waypoint,
+};
 use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
+use std::{convert::TryInto, fmt};
 
 #[derive(Deserialize, Serialize)]
 #[allow(clippy::enum_variant_names)] // to introduce: BackupperId, etc
@@ -61,6 +63,14 @@ impl Metadata {
     pub fn new_random_identity() -> Self {
         Self::Identity(IdentityMeta {
             id: HashValue::random(),
+            chain: None,
+        })
+    }
+
+    pub fn new_chain_identity(chain: ChainIdentity) -> Self {
+        Self::Identity(IdentityMeta {
+            id: HashValue::random(),
+            chain: Some(chain),
         })
     }
 
@@ -73,7 +83,14 @@ impl Metadata {
             Self::TransactionBackup(t) => {
                 format!("transaction_{}-{}.meta", t.first_version, t.last_version,)
             },
-            Metadata::Identity(_) => "identity.meta".into(),
+            // Storages initialized before the chain was recorded already have an identity, so the
+            // chain is recorded in a file of its own.
+            Metadata::Identity(i) => if i.chain.is_some() {
+                "chain_identity.meta"
+            } else {
+                "identity.meta"
+            }
+            .into(),
         }
         .try_into()
         .unwrap()
@@ -107,7 +124,63 @@ pub struct TransactionBackupMeta {
     pub manifest: FileHandle,
 }
 
-#[derive(Clone, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct IdentityMeta {
     pub id: HashValue,
+    /// The chain the backups in the storage are of, recorded by the backup coordinator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<ChainIdentity>,
+}
+
+/// Which chain backups are of, so that backups, nodes and DBs of different networks aren't mixed
+/// up.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct ChainIdentity {
+    /// Only known if given to the backup coordinator, with `--chain-id`.
+    pub chain_id: Option<ChainId>,
+    /// Hash of the genesis ledger info, as in the genesis waypoint `0:<genesis_hash>`.
+    pub genesis_hash: HashValue,
+}
+
+impl ChainIdentity {
+    pub fn from_genesis(genesis: &LedgerInfo, chain_id: Option<ChainId>) -> Result<Self> {
+        ensure!(
+            genesis.epoch() == 0,
+            "Expected the genesis ledger info, got one of epoch {}.",
+            genesis.epoch()
+        );
+        Ok(Self {
+            chain_id,
+            genesis_hash: Waypoint::new_epoch_boundary(genesis)?.value(),
+        })
+    }
+
+    /// Fails if `other`, e.g. the identity of a node or a DB, named `other_name`, is of another
+    /// chain than this one, recorded in the backup storage. The chain IDs are only compared if
+    /// both are known.
+    pub fn ensure_same_chain(&self, other: &ChainIdentity, other_name: &str) -> Result<()> {
+        let same_chain_id = match (self.chain_id, other.chain_id) {
+            (Some(chain_id), Some(other_chain_id)) => chain_id == other_chain_id,
+            _ => true,
+        };
+        ensure!(
+            self.genesis_hash == other.genesis_hash && same_chain_id,
+            "{} is of chain {}, but the backup storage holds the backups of chain {}. Check that \
+            the backup storage, and the metadata cache dir, are the ones of this network.",
+            other_name,
+            other,
+            self,
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChainIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "with genesis {}", self.genesis_hash)?;
+        if let Some(chain_id) = self.chain_id {
+            write!(f, " and chain ID {}", chain_id)?;
+        }
+        Ok(())
+    }
 }
@@ -2,22 +2,43 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::metadata::{
-    EpochEndingBackupMeta, IdentityMeta, Metadata, StateSnapshotBackupMeta, TransactionBackupMeta,
+use crate::{
+    metadata::{
+        ChainIdentity, EpochEndingBackupMeta, IdentityMeta, Metadata, StateSnapshotBackupMeta,
+        TransactionBackupMeta,
+    },
+    storage::FileHandle,
 };
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use aptos_types::transaction::Version;
 use itertools::Itertools;
-use std::{fmt, str::FromStr};
+use std::{fmt, ops::RangeInclusive, str::FromStr};
 
 pub struct MetadataView {
     epoch_ending_backups: Vec<EpochEndingBackupMeta>,
     state_snapshot_backups: Vec<StateSnapshotBackupMeta>,
     transaction_backups: Vec<TransactionBackupMeta>,
-    _identity: Option<IdentityMeta>,
+    identities: Vec<IdentityMeta>,
 }
 
 impl MetadataView {
+    /// The chain the backups are of, if recorded.
+    pub fn chain_identity(&self) -> Result<Option<ChainIdentity>> {
+        let mut chain: Option<ChainIdentity> = None;
+        for recorded in self.identities.iter().filter_map(|identity| identity.chain) {
+            match chain {
+                Some(chain) if chain != recorded => bail!(
+                    "The backup storage records backups of two chains, {} and {}. Backups of \
+                    different networks were mixed up in it.",
+                    chain,
+                    recorded,
+                ),
+                _ => chain = Some(recorded),
+            }
+        }
+        Ok(chain)
+    }
+
     pub fn get_storage_state(&self) -> Result<BackupStorageState> {
         let latest_epoch_ending_epoch =
             self.epoch_ending_backups.iter().map(|e| e.last_epoch).max();
@@ -101,6 +122,44 @@ impl MetadataView {
             .map(|backup| backup.last_version))
     }
 
+    /// Manifests of all the backups, epoch endings first, then state snapshots and transactions,
+    /// each in order.
+    pub fn manifests(&self) -> Vec<FileHandle> {
+        self.epoch_ending_backups
+            .iter()
+            .sorted()
+            .map(|b| b.manifest.clone())
+            .chain(
+                self.state_snapshot_backups
+                    .iter()
+                    .sorted()
+                    .map(|b| b.manifest.clone()),
+            )
+            .chain(
+                self.transaction_backups
+                    .iter()
+                    .sorted()
+                    .map(|b| b.manifest.clone()),
+            )
+            .collect()
+    }
+
+    pub(crate) fn epoch_ending_backups(&self) -> &[EpochEndingBackupMeta] {
+        &self.epoch_ending_backups
+    }
+
+    pub(crate) fn state_snapshot_backups(&self) -> &[StateSnapshotBackupMeta] {
+        &self.state_snapshot_backups
+    }
+
+    pub(crate) fn transaction_backups(&self) -> &[TransactionBackupMeta] {
+        &self.transaction_backups
+    }
+
+    pub fn latest_transaction_backup(&self) -> Option<TransactionBackupMeta> {
+        self.transaction_backups.iter().sorted().last().cloned()
+    }
+
     pub fn select_epoch_ending_backups(
         &self,
         target_version: Version,
@@ -127,6 +186,73 @@ impl MetadataView {
 
         Ok(res)
     }
+
+    /// Ranges missing from the backups, from genesis to the latest backed up, which restores
+    /// would fail on: epochs missing from the epoch ending backups, and versions missing from the
+    /// transaction backups.
+    pub fn find_gaps(&self) -> BackupGaps {
+        BackupGaps {
+            epoch_ending: missing_ranges(
+                self.epoch_ending_backups
+                    .iter()
+                    .map(|b| (b.first_epoch, b.last_epoch)),
+            ),
+            transaction: missing_ranges(
+                self.transaction_backups
+                    .iter()
+                    .map(|b| (b.first_version, b.last_version)),
+            ),
+        }
+    }
+}
+
+/// Ranges between 0 and the end of the last of the given ranges that none of them cover.
+fn missing_ranges(ranges: impl Iterator<Item = (u64, u64)>) -> Vec<RangeInclusive<u64>> {
+    let mut next = 0;
+    let mut missing = Vec::new();
+    for (first, last) in ranges.sorted() {
+        if first > next {
+            missing.push(next..=first - 1);
+        }
+        next = next.max(last + 1);
+    }
+    missing
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct BackupGaps {
+    /// Epochs missing from the epoch ending backups.
+    pub epoch_ending: Vec<RangeInclusive<u64>>,
+    /// Versions missing from the transaction backups.
+    pub transaction: Vec<RangeInclusive<Version>>,
+}
+
+impl BackupGaps {
+    pub fn is_empty(&self) -> bool {
+        self.epoch_ending.is_empty() && self.transaction.is_empty()
+    }
+}
+
+impl fmt::Display for BackupGaps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for epochs in &self.epoch_ending {
+            writeln!(
+                f,
+                "Epoch ending backups missing epochs {} to {}.",
+                epochs.start(),
+                epochs.end()
+            )?;
+        }
+        for versions in &self.transaction {
+            writeln!(
+                f,
+                "Transaction backups missing versions {} to {}.",
+                versions.start(),
+                versions.end()
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl From<Vec<Metadata>> for MetadataView {
@@ -134,14 +260,14 @@ impl From<Vec<Metadata>> for MetadataView {
         let mut epoch_ending_backups = Vec::new();
         let mut state_snapshot_backups = Vec::new();
         let mut transaction_backups = Vec::new();
-        let mut identity = None;
+        let mut identities = Vec::new();
 
         for meta in metadata_vec {
             match meta {
                 Metadata::EpochEndingBackup(e) => epoch_ending_backups.push(e),
                 Metadata::StateSnapshotBackup(s) => state_snapshot_backups.push(s),
                 Metadata::TransactionBackup(t) => transaction_backups.push(t),
-                Metadata::Identity(i) => identity = Some(i),
+                Metadata::Identity(i) => identities.push(i),
             }
         }
 
@@ -149,7 +275,7 @@ impl From<Vec<Metadata>> for MetadataView {
             epoch_ending_backups,
             state_snapshot_backups,
             transaction_backups,
-            _identity: identity,
+            identities,
         }
     }
 }
@@ -205,3 +331,79 @@ impl FromStr for BackupStorageState {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BackupGaps, MetadataView};
+    use crate::metadata::{ChainIdentity, Metadata};
+    use aptos_crypto::HashValue;
+    use aptos_types::chain_id::ChainId;
+
+    #[test]
+    fn test_find_gaps() {
+        let view = MetadataView::from(vec![
+            Metadata::new_epoch_ending_backup(0, 9, 0, 999, "e0".to_string()),
+            Metadata::new_epoch_ending_backup(20, 29, 2000, 2999, "e20".to_string()),
+            Metadata::new_epoch_ending_backup(15, 21, 1500, 2099, "e15".to_string()),
+            Metadata::new_transaction_backup(100, 199, "t100".to_string()),
+            Metadata::new_transaction_backup(200, 299, "t200".to_string()),
+            Metadata::new_transaction_backup(500, 599, "t500".to_string()),
+            Metadata::new_state_snapshot_backup(2, 1000, "s1000".to_string()),
+        ]);
+        // Overlapping backups cover each other, and versions are missing from genesis on.
+        assert_eq!(view.find_gaps(), BackupGaps {
+            epoch_ending: vec![10..=14],
+            transaction: vec![0..=99, 300..=499],
+        });
+        assert!(view
+            .find_gaps()
+            .to_string()
+            .contains("missing versions 300 to 499"));
+
+        let view = MetadataView::from(vec![
+            Metadata::new_epoch_ending_backup(0, 9, 0, 999, "e0".to_string()),
+            Metadata::new_transaction_backup(0, 99, "t0".to_string()),
+            Metadata::new_transaction_backup(100, 199, "t100".to_string()),
+        ]);
+        assert!(view.find_gaps().is_empty());
+    }
+
+    #[test]
+    fn test_chain_identity() {
+        let chain = ChainIdentity {
+            chain_id: Some(ChainId::test()),
+            genesis_hash: HashValue::random(),
+        };
+        let view = MetadataView::from(vec![Metadata::new_random_identity()]);
+        assert_eq!(view.chain_identity().unwrap(), None);
+
+        let view = MetadataView::from(vec![
+            Metadata::new_random_identity(),
+            Metadata::new_chain_identity(chain),
+        ]);
+        assert_eq!(view.chain_identity().unwrap(), Some(chain));
+
+        // Chain IDs are only compared if both are known.
+        let node = ChainIdentity {
+            chain_id: None,
+            ..chain
+        };
+        chain.ensure_same_chain(&node, "The node").unwrap();
+        let other_node = ChainIdentity {
+            chain_id: Some(ChainId::new(1)),
+            ..chain
+        };
+        assert!(chain.ensure_same_chain(&other_node, "The node").is_err());
+        let other_genesis = ChainIdentity {
+            genesis_hash: HashValue::random(),
+            ..node
+        };
+        assert!(chain.ensure_same_chain(&other_genesis, "The DB").is_err());
+
+        let view = MetadataView::from(vec![
+            Metadata::new_chain_identity(chain),
+            Metadata::new_chain_identity(other_genesis),
+        ]);
+        assert!(view.chain_identity().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Getting metrics out of backup and restore runs that are too short lived to be scraped, e.g.
+//! cron jobs.
+
+use anyhow::Result;
+use aptos_logger::{error, info};
+use aptos_metrics_core::{Encoder, TextEncoder};
+use aptos_push_metrics::MetricsPusher;
+use clap::Parser;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+#[derive(Clone, Parser)]
+pub struct MetricsOutputOpt {
+    #[clap(
+        long,
+        global = true,
+        env = "PUSH_METRICS_ENDPOINT",
+        help = "Pushgateway endpoint to push metrics to, periodically and when the process exits. \
+        e.g. http://pushgateway:9091/metrics/job/db_backup"
+    )]
+    pub push_metrics_endpoint: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        env = "PUSH_METRICS_API_TOKEN",
+        hide_env_values = true,
+        help = "Bearer token to authenticate to the pushgateway with."
+    )]
+    pub push_metrics_api_token: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        help = "Extra label to attach to the pushed metrics, in the form of name=value. \
+        Can be repeated."
+    )]
+    pub push_metrics_extra_label: Vec<String>,
+    #[clap(
+        long,
+        global = true,
+        parse(from_os_str),
+        help = "File to write metrics to in the Prometheus text format, periodically and when \
+        the process exits, for the node exporter textfile collector to pick up. The file should \
+        be named *.prom, and is replaced atomically."
+    )]
+    pub metrics_textfile: Option<PathBuf>,
+    #[clap(
+        long,
+        global = true,
+        env = "PUSH_METRICS_FREQUENCY_SECS",
+        default_value = "15",
+        help = "How often metrics are pushed or written out."
+    )]
+    pub metrics_output_frequency_secs: u64,
+}
+
+impl MetricsOutputOpt {
+    /// Starts pushing and / or writing out metrics, until the returned value is dropped, at which
+    /// point the final values are pushed and written.
+    pub fn start(&self) -> MetricsOutput {
+        let pusher = self.push_metrics_endpoint.as_ref().map(|endpoint| {
+            MetricsPusher::start_with_endpoint(
+                endpoint.clone(),
+                self.metrics_output_frequency_secs,
+                self.push_metrics_api_token.clone(),
+                self.push_metrics_extra_label.clone(),
+            )
+        });
+        let textfile_writer = self.metrics_textfile.as_ref().map(|path| {
+            MetricsTextfileWriter::start(
+                path.clone(),
+                Duration::from_secs(self.metrics_output_frequency_secs),
+            )
+        });
+
+        MetricsOutput {
+            _pusher: pusher,
+            _textfile_writer: textfile_writer,
+        }
+    }
+}
+
+pub struct MetricsOutput {
+    _pusher: Option<MetricsPusher>,
+    _textfile_writer: Option<MetricsTextfileWriter>,
+}
+
+/// Periodically writes all metrics to a file, in the format expected by the textfile collector of
+/// the Prometheus node exporter.
+#[must_use = "Assign the constructed writer to a variable, \
+              otherwise the worker thread is joined immediately."]
+pub struct MetricsTextfileWriter {
+    worker_thread: Option<JoinHandle<()>>,
+    quit_sender: mpsc::Sender<()>,
+}
+
+impl MetricsTextfileWriter {
+    pub fn start(path: PathBuf, frequency: Duration) -> Self {
+        info!(
+            path = path.to_string_lossy(),
+            frequency_secs = frequency.as_secs(),
+            "Writing metrics to textfile."
+        );
+        let (quit_sender, quit_receiver) = mpsc::channel();
+        let worker_thread = thread::spawn(move || {
+            while quit_receiver.recv_timeout(frequency).is_err() {
+                Self::write_or_log(&path);
+            }
+            // final write
+            Self::write_or_log(&path);
+        });
+
+        Self {
+            worker_thread: Some(worker_thread),
+            quit_sender,
+        }
+    }
+
+    fn write_or_log(path: &Path) {
+        if let Err(e) = Self::write(path) {
+            error!("Failed to write metrics to {}: {:#}", path.display(), e);
+        }
+    }
+
+    /// Writes to a temporary file first and renames it, so the collector never sees a partial
+    /// file.
+    fn write(path: &Path) -> Result<()> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&aptos_metrics_core::gather(), &mut buffer)?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, buffer)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl Drop for MetricsTextfileWriter {
+    fn drop(&mut self) {
+        if let Some(worker_thread) = self.worker_thread.take() {
+            if let Err(e) = self.quit_sender.send(()) {
+                error!(
+                    "Failed to send quit signal to metrics textfile writer thread: {:?}",
+                    e
+                );
+            }
+            if let Err(e) = worker_thread.join() {
+                error!("Failed to join metrics textfile writer thread: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsTextfileWriter;
+    use crate::metrics::OTHER_TIMERS_SECONDS;
+    use aptos_temppath::TempPath;
+    use std::time::Duration;
+
+    #[test]
+    fn test_final_write_on_drop() {
+        let dir = TempPath::new();
+        dir.create_as_dir().unwrap();
+        let path = dir.path().join("backup.prom");
+
+        let writer = MetricsTextfileWriter::start(path.clone(), Duration::from_secs(3600));
+        OTHER_TIMERS_SECONDS
+            .with_label_values(&["textfile_test"])
+            .observe(1.0);
+        drop(writer);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("textfile_test"));
+    }
+}
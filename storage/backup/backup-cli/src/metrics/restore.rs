@@ -53,6 +53,14 @@ pub static STATE_SNAPSHOT_LEAF_INDEX: Lazy<IntGauge> = Lazy::new(|| {
     .unwrap()
 });
 
+pub static STATE_SNAPSHOT_PRIORITY_VALUES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "aptos_db_restore_state_snapshot_priority_values",
+        "Number of priority state values restored ahead of the state snapshot."
+    )
+    .unwrap()
+});
+
 pub static TRANSACTION_SAVE_VERSION: Lazy<IntGauge> = Lazy::new(|| {
     register_int_gauge!(
         "aptos_db_restore_transaction_save_version",
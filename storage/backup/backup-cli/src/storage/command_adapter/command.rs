@@ -18,6 +18,7 @@ use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
     macros::support::Pin,
     process::{Child, ChildStdin, ChildStdout},
+    sync::OwnedSemaphorePermit,
 };
 
 pub(super) struct Command {
@@ -60,6 +61,9 @@ impl Debug for Command {
 pub(super) struct SpawnedCommand {
     command: Command,
     child: Child,
+    /// Slot taken in the concurrency limit of the storage, released once the command is joined
+    /// or dropped.
+    _permit: Option<OwnedSemaphorePermit>,
 }
 
 impl SpawnedCommand {
@@ -90,7 +94,16 @@ impl SpawnedCommand {
             &command,
         );
 
-        Ok(Self { command, child })
+        Ok(Self {
+            command,
+            child,
+            _permit: None,
+        })
+    }
+
+    pub fn with_permit(mut self, permit: Option<OwnedSemaphorePermit>) -> Self {
+        self._permit = permit;
+        self
     }
 
     pub fn stdout(&mut self) -> &mut ChildStdout {
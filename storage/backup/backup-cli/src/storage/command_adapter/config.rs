@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    storage::{BackupHandle, FileHandle},
+    storage::{lifecycle::StorageClass, BackupHandle, FileHandle},
     utils::error_notes::ErrorNotes,
 };
 use anyhow::Result;
@@ -34,6 +34,18 @@ impl EnvVar {
         Self::new("BACKUP_HANDLE".to_string(), value)
     }
 
+    pub fn object_tags(value: String) -> Self {
+        Self::new("OBJECT_TAGS".to_string(), value)
+    }
+
+    pub fn storage_class(value: StorageClass) -> Self {
+        Self::new("STORAGE_CLASS".to_string(), value.to_string())
+    }
+
+    pub fn multipart_part_size(value: u64) -> Self {
+        Self::new("MULTIPART_PART_SIZE".to_string(), value.to_string())
+    }
+
     pub fn new(key: String, value: String) -> Self {
         Self { key, value }
     }
@@ -51,6 +63,8 @@ pub struct Commands {
     /// input env vars:
     ///     $BACKUP_HANDLE returned from the previous command
     ///     $FILE_NAME
+    ///     $OBJECT_TAGS tags to attach to the file, like "key=value&key=value", possibly empty
+    ///     $STORAGE_CLASS hot, cold or archive, see `LifecycleConfig`
     /// stdin will be fed with byte stream.
     /// expected output on stdout:
     ///     FileHandle, trailing newline
@@ -63,11 +77,89 @@ pub struct Commands {
     /// Command line to save a line of metadata
     /// input env vars:
     ///     $FILE_NAME
+    ///     $STORAGE_CLASS always hot
     /// stdin will be fed with a line of text with a trailing newline.
     pub save_metadata_line: String,
     /// Command line to list all existing metadata file handles.
     /// expected stdout to stream out lines of file handles.
     pub list_metadata_files: String,
+    /// Optional command line to read the tags attached to a file.
+    /// input env vars:
+    ///     $FILE_HANDLE
+    /// expected stdout to stream out lines of "key=value".
+    pub read_object_tags: Option<String>,
+    /// Optional command line to tell whether a file can be read, checked before opening it.
+    /// input env vars:
+    ///     $FILE_HANDLE
+    /// expected output on stdout:
+    ///     available, archived (in an archive tier, and not restored) or restoring
+    pub object_status: Option<String>,
+    /// Optional command line to start restoring a file from an archive tier, along with
+    /// `object_status`.
+    /// input env vars:
+    ///     $FILE_HANDLE
+    pub restore_from_archive: Option<String>,
+}
+
+/// Tuning of the requests made to the storage, which depends a lot on what's behind the commands:
+/// an object store like S3 copes with many more requests in flight than a slow NFS mount.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum number of commands running at the same time, e.g. files being downloaded, on top
+    /// of the `--concurrent-downloads` of the restore. No limit if not set.
+    pub max_concurrent_requests: Option<usize>,
+    /// Maximum number of commands spawned per second. No limit if not set.
+    pub max_requests_per_sec: Option<f64>,
+    /// Exposed to the commands as $MULTIPART_PART_SIZE, to be passed on to the storage CLI, e.g.
+    /// `aws configure set default.s3.multipart_chunksize $MULTIPART_PART_SIZE`.
+    pub multipart_part_size_bytes: Option<u64>,
+}
+
+/// Storage classes of the files, see `storage::lifecycle`, and what to do when reading archived
+/// ones.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LifecycleConfig {
+    /// Exposed to the commands as $STORAGE_CLASS when writing chunks, the other files being hot.
+    pub chunk_storage_class: StorageClass,
+    /// How long to wait for an archived file to be restored before failing, 0 to fail as soon as
+    /// its restore has started.
+    pub max_archive_restore_wait_secs: u64,
+    /// How often to check whether an archived file was restored while waiting.
+    pub archive_restore_poll_interval_secs: u64,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        Self {
+            chunk_storage_class: StorageClass::Cold,
+            max_archive_restore_wait_secs: 0,
+            archive_restore_poll_interval_secs: 60,
+        }
+    }
+}
+
+/// Temporary credentials for the commands, e.g. from the instance metadata or a web identity token,
+/// refreshed for long runs to outlive them.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CredentialsConfig {
+    /// Command line fetching the credentials, with the env vars of the config set.
+    /// expected output on stdout:
+    ///     env vars to set for the other commands, one `KEY=VALUE` per line, e.g.
+    ///     `AWS_SESSION_TOKEN=...`
+    pub refresh: String,
+    /// How long the credentials are used before being refreshed, which should be well within
+    /// their lifetime.
+    #[serde(default = "CredentialsConfig::default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+impl CredentialsConfig {
+    fn default_refresh_interval_secs() -> u64 {
+        900
+    }
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -76,6 +168,15 @@ pub struct CommandAdapterConfig {
     pub commands: Commands,
     /// Additional environment variables to be set when command lines are spawned.
     pub env_vars: Vec<EnvVar>,
+    /// Limits on the requests made to the storage.
+    #[serde(default)]
+    pub limits: Limits,
+    /// Temporary credentials, if the commands don't get long lived ones themselves.
+    #[serde(default)]
+    pub credentials: Option<CredentialsConfig>,
+    /// Storage classes of the files and the handling of archived ones.
+    #[serde(default)]
+    pub lifecycle: LifecycleConfig,
 }
 
 impl CommandAdapterConfig {
@@ -0,0 +1,84 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    storage::command_adapter::{
+        command::Command,
+        config::{CredentialsConfig, EnvVar},
+    },
+    utils::error_notes::ErrorNotes,
+};
+use anyhow::{format_err, Result};
+use aptos_logger::prelude::*;
+use std::time::{Duration, Instant};
+use tokio::{io::AsyncReadExt, sync::Mutex};
+
+/// Credentials fetched by the refresh command, handed to the other commands as env vars.
+pub(super) struct Credentials {
+    config: CredentialsConfig,
+    /// Env vars of the config, for the refresh command.
+    config_env_vars: Vec<EnvVar>,
+    /// The credentials and when they were fetched. Locked while refreshing, so that commands
+    /// spawned meanwhile wait for a single refresh.
+    current: Mutex<Option<(Vec<EnvVar>, Instant)>>,
+}
+
+impl Credentials {
+    pub fn new(config: CredentialsConfig, config_env_vars: Vec<EnvVar>) -> Self {
+        Self {
+            config,
+            config_env_vars,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// The credentials, refreshed first if they're due.
+    pub async fn env_vars(&self) -> Result<Vec<EnvVar>> {
+        let mut current = self.current.lock().await;
+        let refresh_interval = Duration::from_secs(self.config.refresh_interval_secs);
+        if let Some((env_vars, fetched_at)) = &*current {
+            if fetched_at.elapsed() < refresh_interval {
+                return Ok(env_vars.clone());
+            }
+        }
+        let env_vars = self.fetch().await?;
+        *current = Some((env_vars.clone(), Instant::now()));
+        Ok(env_vars)
+    }
+
+    /// Drops the credentials, e.g. after they were refused, for the next command to fetch new
+    /// ones.
+    pub async fn expire(&self) {
+        *self.current.lock().await = None;
+    }
+
+    async fn fetch(&self) -> Result<Vec<EnvVar>> {
+        info!("Refreshing the credentials of the backup storage.");
+        let mut child =
+            Command::new(&self.config.refresh, vec![], self.config_env_vars.clone()).spawn()?;
+        let mut output = String::new();
+        child
+            .stdout()
+            .read_to_string(&mut output)
+            .await
+            .err_notes((file!(), line!()))?;
+        child.join().await?;
+        parse_env_vars(&output)
+    }
+}
+
+/// Parses `KEY=VALUE` lines, ignoring empty ones.
+fn parse_env_vars(output: &str) -> Result<Vec<EnvVar>> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            // The values are secrets, not to end up in the logs.
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                format_err!("Credentials refresh output a line that's not KEY=VALUE.")
+            })?;
+            Ok(EnvVar::new(key.to_string(), value.to_string()))
+        })
+        .collect()
+}
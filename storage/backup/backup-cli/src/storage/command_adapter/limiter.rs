@@ -0,0 +1,57 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::storage::command_adapter::config::Limits;
+use anyhow::Result;
+use aptos_infallible::Mutex;
+use std::{
+    cmp::max,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Enforces the `Limits` of a storage on the commands spawned for it.
+pub(super) struct RequestLimiter {
+    /// `None` if there is no concurrency limit.
+    semaphore: Option<Arc<Semaphore>>,
+    /// `None` if there is no rate limit.
+    min_interval: Option<Duration>,
+    /// Earliest time the next command can be spawned.
+    next_slot: Mutex<Instant>,
+}
+
+impl RequestLimiter {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            semaphore: limits
+                .max_concurrent_requests
+                .filter(|max| *max > 0)
+                .map(|max| Arc::new(Semaphore::new(max))),
+            min_interval: limits
+                .max_requests_per_sec
+                .filter(|rate| *rate > 0.0)
+                .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until a command can be spawned. The returned permit, if any, is to be held until the
+    /// command finishes.
+    pub async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>> {
+        let permit = match &self.semaphore {
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await?),
+            None => None,
+        };
+        if let Some(min_interval) = self.min_interval {
+            let slot = {
+                let mut next_slot = self.next_slot.lock();
+                let slot = max(*next_slot, Instant::now());
+                *next_slot = slot + min_interval;
+                slot
+            };
+            tokio::time::sleep_until(slot.into()).await;
+        }
+        Ok(permit)
+    }
+}
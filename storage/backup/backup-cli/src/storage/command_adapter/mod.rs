@@ -4,6 +4,8 @@
 
 mod command;
 pub mod config;
+mod credentials;
+mod limiter;
 
 #[cfg(test)]
 mod tests;
@@ -11,19 +13,30 @@ mod tests;
 use crate::{
     storage::{
         command_adapter::{
-            command::Command,
+            command::{Command, SpawnedCommand},
             config::{CommandAdapterConfig, EnvVar},
+            credentials::Credentials,
+            limiter::RequestLimiter,
         },
+        lifecycle::{ObjectStatus, StorageClass},
+        tags::ObjectTags,
         BackupHandle, BackupHandleRef, BackupStorage, FileHandle, FileHandleRef, ShellSafeName,
         TextLine,
     },
     utils::error_notes::ErrorNotes,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
+use aptos_logger::prelude::*;
 use async_trait::async_trait;
 use clap::Parser;
+use futures::Future;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    cmp::min,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[derive(Parser, Debug, Serialize, Deserialize)]
@@ -49,11 +62,22 @@ impl FromStr for CommandAdapterOpt {
 /// see `CommandAdapterConfig`.
 pub struct CommandAdapter {
     config: CommandAdapterConfig,
+    limiter: RequestLimiter,
+    credentials: Option<Credentials>,
 }
 
 impl CommandAdapter {
     pub fn new(config: CommandAdapterConfig) -> Self {
-        Self { config }
+        let limiter = RequestLimiter::new(&config.limits);
+        let credentials = config
+            .credentials
+            .clone()
+            .map(|c| Credentials::new(c, config.env_vars.clone()));
+        Self {
+            config,
+            limiter,
+            credentials,
+        }
     }
 
     pub async fn new_with_opt(opt: CommandAdapterOpt) -> Result<Self> {
@@ -62,19 +86,55 @@ impl CommandAdapter {
         Ok(Self::new(config))
     }
 
-    fn cmd(&self, cmd_str: &str, env_vars: Vec<EnvVar>) -> Command {
-        Command::new(cmd_str, env_vars, self.config.env_vars.clone())
+    async fn cmd(&self, cmd_str: &str, mut env_vars: Vec<EnvVar>) -> Result<Command> {
+        if let Some(part_size) = self.config.limits.multipart_part_size_bytes {
+            env_vars.push(EnvVar::multipart_part_size(part_size));
+        }
+        // Credentials go with the config env vars, which are kept out of the logs.
+        let mut config_env_vars = self.config.env_vars.clone();
+        if let Some(credentials) = &self.credentials {
+            config_env_vars.extend(credentials.env_vars().await?);
+        }
+        Ok(Command::new(cmd_str, env_vars, config_env_vars))
     }
-}
 
-#[async_trait]
-impl BackupStorage for CommandAdapter {
-    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+    /// Spawns the command once the limits of the storage allow it.
+    async fn spawn(&self, cmd_str: &str, env_vars: Vec<EnvVar>) -> Result<SpawnedCommand> {
+        let permit = self.limiter.acquire().await?;
+        Ok(self
+            .cmd(cmd_str, env_vars)
+            .await?
+            .spawn()?
+            .with_permit(permit))
+    }
+
+    /// Runs a request that's safe to repeat, and if it fails while credentials are refreshed,
+    /// possibly because they expired, repeats it once with fresh ones. Streaming requests can't be
+    /// repeated, and rely on the credentials being refreshed ahead of their expiry instead.
+    async fn with_fresh_credentials_on_failure<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        match (request().await, &self.credentials) {
+            (Err(e), Some(credentials)) => {
+                warn!(
+                    error = ?e,
+                    "Backup storage request failed, retrying with fresh credentials."
+                );
+                credentials.expire().await;
+                request().await
+            },
+            (res, _) => res,
+        }
+    }
+
+    async fn try_create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
         let mut child = self
-            .cmd(&self.config.commands.create_backup, vec![
+            .spawn(&self.config.commands.create_backup, vec![
                 EnvVar::backup_name(name.to_string()),
             ])
-            .spawn()?;
+            .await?;
         let mut backup_handle = BackupHandle::new();
         child
             .stdout()
@@ -87,17 +147,171 @@ impl BackupStorage for CommandAdapter {
         Ok(backup_handle)
     }
 
+    async fn try_save_metadata_line(&self, name: &ShellSafeName, content: &TextLine) -> Result<()> {
+        let mut child = self
+            .spawn(&self.config.commands.save_metadata_line, vec![
+                EnvVar::file_name(name.to_string()),
+                EnvVar::storage_class(StorageClass::Hot),
+            ])
+            .await?;
+
+        child
+            .stdin()
+            .write_all(content.as_ref().as_bytes())
+            .await
+            .err_notes(name)?;
+        child.join().await?;
+        Ok(())
+    }
+
+    async fn try_list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        let child = self
+            .spawn(&self.config.commands.list_metadata_files, vec![])
+            .await?;
+
+        let mut buf = FileHandle::new();
+        child
+            .into_data_source()
+            .read_to_string(&mut buf)
+            .await
+            .err_notes((file!(), line!(), &buf))?;
+        Ok(buf.lines().map(str::to_string).collect())
+    }
+
+    async fn try_read_tags(&self, cmd: &str, file_handle: &FileHandleRef) -> Result<ObjectTags> {
+        let child = self
+            .spawn(cmd, vec![EnvVar::file_handle(file_handle.to_string())])
+            .await?;
+
+        let mut buf = String::new();
+        child
+            .into_data_source()
+            .read_to_string(&mut buf)
+            .await
+            .err_notes((file!(), line!(), file_handle))?;
+        buf.parse()
+    }
+
+    async fn try_object_status(
+        &self,
+        cmd: &str,
+        file_handle: &FileHandleRef,
+    ) -> Result<ObjectStatus> {
+        let child = self
+            .spawn(cmd, vec![EnvVar::file_handle(file_handle.to_string())])
+            .await?;
+
+        let mut buf = String::new();
+        child
+            .into_data_source()
+            .read_to_string(&mut buf)
+            .await
+            .err_notes((file!(), line!(), file_handle))?;
+        buf.parse()
+    }
+
+    async fn try_restore_from_archive(&self, cmd: &str, file_handle: &FileHandleRef) -> Result<()> {
+        self.spawn(cmd, vec![EnvVar::file_handle(file_handle.to_string())])
+            .await?
+            .join()
+            .await
+    }
+
+    /// Makes sure the file can be read, if the storage tells: starts restoring it if it's
+    /// archived, and waits for it to be restored up to `max_archive_restore_wait_secs`.
+    async fn ensure_readable(&self, cmd: &str, file_handle: &FileHandleRef) -> Result<()> {
+        let status = self
+            .with_fresh_credentials_on_failure(|| self.try_object_status(cmd, file_handle))
+            .await?;
+        match status {
+            ObjectStatus::Available => return Ok(()),
+            ObjectStatus::Archived => match &self.config.commands.restore_from_archive {
+                Some(restore_cmd) => {
+                    self.with_fresh_credentials_on_failure(|| {
+                        self.try_restore_from_archive(restore_cmd, file_handle)
+                    })
+                    .await?;
+                    info!(
+                        file_handle = file_handle,
+                        "Started restoring the file from the archive storage class."
+                    );
+                },
+                None => bail!(
+                    "{} is in an archive storage class and can't be read until it's restored. \
+                    Restore it with the tools of the storage, or set commands.restore_from_archive \
+                    in the config to have it restored, then retry.",
+                    file_handle,
+                ),
+            },
+            ObjectStatus::Restoring => (),
+        }
+
+        let lifecycle = &self.config.lifecycle;
+        let deadline =
+            Instant::now() + Duration::from_secs(lifecycle.max_archive_restore_wait_secs);
+        let poll_interval =
+            Duration::from_secs(lifecycle.archive_restore_poll_interval_secs.max(1));
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                bail!(
+                    "{} is being restored from an archive storage class, which can take hours. \
+                    Retry once it's restored, or set lifecycle.max_archive_restore_wait_secs in \
+                    the config to wait for it.",
+                    file_handle,
+                );
+            }
+            tokio::time::sleep(min(poll_interval, deadline - now)).await;
+            if self
+                .with_fresh_credentials_on_failure(|| self.try_object_status(cmd, file_handle))
+                .await?
+                == ObjectStatus::Available
+            {
+                info!(
+                    file_handle = file_handle,
+                    "The file was restored from the archive storage class."
+                );
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl BackupStorage for CommandAdapter {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        self.with_fresh_credentials_on_failure(|| self.try_create_backup(name))
+            .await
+    }
+
     async fn create_for_write(
         &self,
         backup_handle: &BackupHandleRef,
         name: &ShellSafeName,
     ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        self.create_for_write_with_tags(backup_handle, name, &ObjectTags::default())
+            .await
+    }
+
+    async fn create_for_write_with_tags(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+        tags: &ObjectTags,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        let storage_class = if StorageClass::is_chunk(name) {
+            self.config.lifecycle.chunk_storage_class
+        } else {
+            StorageClass::Hot
+        };
         let mut child = self
-            .cmd(&self.config.commands.create_for_write, vec![
+            .spawn(&self.config.commands.create_for_write, vec![
                 EnvVar::backup_handle(backup_handle.to_string()),
                 EnvVar::file_name(name.to_string()),
+                EnvVar::object_tags(tags.to_string()),
+                EnvVar::storage_class(storage_class),
             ])
-            .spawn()?;
+            .await?;
         let mut file_handle = FileHandle::new();
         child
             .stdout()
@@ -112,41 +326,34 @@ impl BackupStorage for CommandAdapter {
         &self,
         file_handle: &FileHandleRef,
     ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        if let Some(cmd) = &self.config.commands.object_status {
+            self.ensure_readable(cmd, file_handle).await?;
+        }
         let child = self
-            .cmd(&self.config.commands.open_for_read, vec![
+            .spawn(&self.config.commands.open_for_read, vec![
                 EnvVar::file_handle(file_handle.to_string()),
             ])
-            .spawn()?;
+            .await?;
         Ok(Box::new(child.into_data_source()))
     }
 
     async fn save_metadata_line(&self, name: &ShellSafeName, content: &TextLine) -> Result<()> {
-        let mut child = self
-            .cmd(&self.config.commands.save_metadata_line, vec![
-                EnvVar::file_name(name.to_string()),
-            ])
-            .spawn()?;
-
-        child
-            .stdin()
-            .write_all(content.as_ref().as_bytes())
+        self.with_fresh_credentials_on_failure(|| self.try_save_metadata_line(name, content))
             .await
-            .err_notes(name)?;
-        child.join().await?;
-        Ok(())
     }
 
     async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
-        let child = self
-            .cmd(&self.config.commands.list_metadata_files, vec![])
-            .spawn()?;
-
-        let mut buf = FileHandle::new();
-        child
-            .into_data_source()
-            .read_to_string(&mut buf)
+        self.with_fresh_credentials_on_failure(|| self.try_list_metadata_files())
             .await
-            .err_notes((file!(), line!(), &buf))?;
-        Ok(buf.lines().map(str::to_string).collect())
+    }
+
+    async fn read_tags(&self, file_handle: &FileHandleRef) -> Result<Option<ObjectTags>> {
+        match &self.config.commands.read_object_tags {
+            Some(cmd) => self
+                .with_fresh_credentials_on_failure(|| self.try_read_tags(cmd, file_handle))
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
     }
 }
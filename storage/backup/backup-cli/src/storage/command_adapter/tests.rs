@@ -4,7 +4,9 @@
 
 use super::*;
 use crate::storage::{
-    command_adapter::config::Commands,
+    command_adapter::config::{Commands, CredentialsConfig, EnvVar, LifecycleConfig, Limits},
+    lifecycle::StorageClass,
+    tags::ObjectTags,
     test_util::{
         arb_backups, arb_metadata_files, test_save_and_list_metadata_files_impl,
         test_write_and_read_impl,
@@ -13,7 +15,7 @@ use crate::storage::{
 use aptos_temppath::TempPath;
 use futures::Future;
 use proptest::prelude::*;
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 use tokio::runtime::Runtime;
 
 fn get_store(tmpdir: &TempPath) -> Box<dyn BackupStorage> {
@@ -61,6 +63,10 @@ proptest! {
 }
 
 fn dummy_store(cmd: &str) -> CommandAdapter {
+    dummy_store_with_limits(cmd, Limits::default())
+}
+
+fn dummy_store_with_limits(cmd: &str, limits: Limits) -> CommandAdapter {
     CommandAdapter::new(CommandAdapterConfig {
         commands: Commands {
             create_backup: cmd.to_string(),
@@ -68,8 +74,14 @@ fn dummy_store(cmd: &str) -> CommandAdapter {
             open_for_read: cmd.to_string(),
             save_metadata_line: cmd.to_string(),
             list_metadata_files: cmd.to_string(),
+            read_object_tags: None,
+            object_status: None,
+            restore_from_archive: None,
         },
         env_vars: Vec::new(),
+        limits,
+        credentials: None,
+        lifecycle: LifecycleConfig::default(),
     })
 }
 
@@ -195,3 +207,210 @@ fn test_pipe_fail() {
         "echo okay | (cat; true) | cat; exec 1>&-; cat | (cat; true) | cat > /dev/null",
     ));
 }
+
+#[test]
+fn test_limits() {
+    block_on(async {
+        let store = dummy_store_with_limits("echo $MULTIPART_PART_SIZE", Limits {
+            max_concurrent_requests: Some(1),
+            max_requests_per_sec: None,
+            multipart_part_size_bytes: Some(1 << 20),
+        });
+
+        let mut first = store.open_for_read("handle").await.unwrap();
+        // The only slot is taken until the first command finishes.
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), store.open_for_read("handle"))
+                .await
+                .is_err()
+        );
+
+        let mut buf = String::new();
+        first.read_to_string(&mut buf).await.unwrap();
+        assert_eq!(&buf, "1048576\n");
+
+        let mut buf = String::new();
+        tokio::time::timeout(Duration::from_secs(10), store.open_for_read("handle"))
+            .await
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(&buf, "1048576\n");
+    })
+}
+
+#[test]
+fn test_credentials_refresh() {
+    block_on(async {
+        let tmpdir = TempPath::new();
+        tmpdir.create_as_dir().unwrap();
+        let counter = tmpdir.path().join("refreshes");
+        // The first credentials are refused.
+        let cmd = r#"test "$TOKEN" -ge 2; echo okay"#;
+        let store = CommandAdapter::new(CommandAdapterConfig {
+            commands: Commands {
+                create_backup: cmd.to_string(),
+                create_for_write: cmd.to_string(),
+                open_for_read: cmd.to_string(),
+                save_metadata_line: cmd.to_string(),
+                list_metadata_files: cmd.to_string(),
+                read_object_tags: None,
+                object_status: None,
+                restore_from_archive: None,
+            },
+            env_vars: vec![EnvVar::new(
+                "COUNTER".to_string(),
+                counter.to_str().unwrap().to_string(),
+            )],
+            limits: Limits::default(),
+            credentials: Some(CredentialsConfig {
+                refresh: r#"N=$(( $(cat "$COUNTER" 2>/dev/null || echo 0) + 1 )); echo $N > "$COUNTER"; echo "TOKEN=$N""#.to_string(),
+                refresh_interval_secs: 3600,
+            }),
+            lifecycle: LifecycleConfig::default(),
+        });
+
+        let name = ShellSafeName::from_str("name").unwrap();
+        assert_eq!(&store.create_backup(&name).await.unwrap(), "okay");
+        // The fresh credentials are reused.
+        assert_eq!(store.list_metadata_files().await.unwrap(), vec!["okay"]);
+        let mut buf = String::new();
+        store
+            .open_for_read("handle")
+            .await
+            .unwrap()
+            .read_to_string(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(&buf, "okay\n");
+        assert_eq!(std::fs::read_to_string(&counter).unwrap(), "2\n");
+    })
+}
+
+#[test]
+fn test_object_tags() {
+    block_on(async {
+        let tmpdir = TempPath::new();
+        tmpdir.create_as_dir().unwrap();
+        let store = CommandAdapter::new(CommandAdapterConfig {
+            commands: Commands {
+                create_for_write: r#"cd "$FOLDER" && echo $FILE_NAME && echo "$OBJECT_TAGS" > $FILE_NAME.tags && exec >&- && cat > $FILE_NAME"#.to_string(),
+                read_object_tags: Some(r#"tr '&' '\n' < "$FOLDER/$FILE_HANDLE.tags""#.to_string()),
+                ..Commands::default()
+            },
+            env_vars: vec![EnvVar::new(
+                "FOLDER".to_string(),
+                tmpdir.path().to_str().unwrap().to_string(),
+            )],
+            limits: Limits::default(),
+            credentials: None,
+            lifecycle: LifecycleConfig::default(),
+        });
+
+        let tags = ObjectTags::for_backup("transaction", None).with("first_version", 100);
+        let (file_handle, mut file) = store
+            .create_for_write_with_tags("backup", &ShellSafeName::from_str("file").unwrap(), &tags)
+            .await
+            .unwrap();
+        file.shutdown().await.unwrap();
+        assert_eq!(store.read_tags(&file_handle).await.unwrap(), Some(tags));
+        // Untagged files get an empty $OBJECT_TAGS.
+        let (file_handle, mut file) = store
+            .create_for_write("backup", &ShellSafeName::from_str("untagged").unwrap())
+            .await
+            .unwrap();
+        file.shutdown().await.unwrap();
+        assert_eq!(
+            store.read_tags(&file_handle).await.unwrap(),
+            Some(ObjectTags::default())
+        );
+        // Not supported without the command.
+        assert_eq!(dummy_store("true").read_tags("file").await.unwrap(), None);
+    })
+}
+
+#[test]
+fn test_lifecycle() {
+    block_on(async {
+        let tmpdir = TempPath::new();
+        tmpdir.create_as_dir().unwrap();
+        let folder = tmpdir.path().to_path_buf();
+        let store = |restore_from_archive: Option<&str>, max_archive_restore_wait_secs| {
+            CommandAdapter::new(CommandAdapterConfig {
+                commands: Commands {
+                    create_for_write: r#"cd "$FOLDER" && echo $FILE_NAME && echo "$STORAGE_CLASS" > $FILE_NAME.class && echo available > $FILE_NAME.status && exec >&- && cat > $FILE_NAME"#.to_string(),
+                    open_for_read: r#"cat "$FOLDER/$FILE_HANDLE""#.to_string(),
+                    object_status: Some(r#"cat "$FOLDER/$FILE_HANDLE.status""#.to_string()),
+                    restore_from_archive: restore_from_archive.map(str::to_string),
+                    ..Commands::default()
+                },
+                env_vars: vec![EnvVar::new(
+                    "FOLDER".to_string(),
+                    folder.to_str().unwrap().to_string(),
+                )],
+                limits: Limits::default(),
+                credentials: None,
+                lifecycle: LifecycleConfig {
+                    chunk_storage_class: StorageClass::Archive,
+                    max_archive_restore_wait_secs,
+                    archive_restore_poll_interval_secs: 1,
+                },
+            })
+        };
+        let read = |store: CommandAdapter, file_handle: &'static str| async move {
+            let mut buf = String::new();
+            store
+                .open_for_read(file_handle)
+                .await?
+                .read_to_string(&mut buf)
+                .await?;
+            Result::<String>::Ok(buf)
+        };
+        let status = |file_handle: &str, status: &str| {
+            std::fs::write(folder.join(format!("{}.status", file_handle)), status).unwrap()
+        };
+
+        for name in ["0-.chunk", "transaction.manifest"] {
+            let (_, mut file) = store(None, 0)
+                .create_for_write("backup", &ShellSafeName::from_str(name).unwrap())
+                .await
+                .unwrap();
+            file.write_all(b"content").await.unwrap();
+            file.shutdown().await.unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(folder.join("0-.chunk.class")).unwrap(),
+            "archive\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(folder.join("transaction.manifest.class")).unwrap(),
+            "hot\n"
+        );
+        assert_eq!(read(store(None, 0), "0-.chunk").await.unwrap(), "content");
+
+        // Archived files fail to be read, telling how to get them restored.
+        status("0-.chunk", "archived");
+        let err = read(store(None, 0), "0-.chunk").await.unwrap_err();
+        assert!(err.to_string().contains("restore_from_archive"));
+
+        // Their restore is started, and they can be read once it's done.
+        let restore = r#"echo restoring > "$FOLDER/$FILE_HANDLE.status""#;
+        let err = read(store(Some(restore), 0), "0-.chunk").await.unwrap_err();
+        assert!(err.to_string().contains("being restored"));
+        status("0-.chunk", "available");
+        assert_eq!(
+            read(store(Some(restore), 0), "0-.chunk").await.unwrap(),
+            "content"
+        );
+
+        // Or waited for.
+        status("0-.chunk", "archived");
+        let restore = r#"echo available > "$FOLDER/$FILE_HANDLE.status""#;
+        assert_eq!(
+            read(store(Some(restore), 10), "0-.chunk").await.unwrap(),
+            "content"
+        );
+    })
+}
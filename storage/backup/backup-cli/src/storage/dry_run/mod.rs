@@ -0,0 +1,157 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests;
+
+use super::{BackupHandle, BackupHandleRef, FileHandle, FileHandleRef};
+use crate::storage::{tags::ObjectTags, BackupStorage, ShellSafeName, TextLine};
+use anyhow::Result;
+use aptos_infallible::Mutex;
+use async_trait::async_trait;
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// What a backup would have written, had it not been a dry run.
+#[derive(Default)]
+pub struct DryRunReport {
+    pub backups: Vec<BackupHandle>,
+    /// Files and their sizes in bytes, before any compression done by the storage.
+    pub files: Vec<(FileHandle, u64)>,
+    pub metadata_lines: Vec<String>,
+}
+
+impl DryRunReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|(_, size)| size).sum()
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dry run, nothing was written to the backup storage.")?;
+        for backup in &self.backups {
+            writeln!(f, "Would create backup: {}", backup)?;
+        }
+        for (file_handle, size) in &self.files {
+            writeln!(f, "Would write file: {} ({} bytes)", file_handle, size)?;
+        }
+        for line in &self.metadata_lines {
+            writeln!(f, "Would save metadata: {}", line)?;
+        }
+        write!(
+            f,
+            "Total: {} file(s), {} bytes before compression.",
+            self.files.len(),
+            self.total_bytes(),
+        )
+    }
+}
+
+/// Wraps a storage so that reads go through to it, while writes are only recorded in a
+/// `DryRunReport`, for validating backup settings against a real node without touching the
+/// backup storage.
+pub struct DryRunStorage {
+    inner: Arc<dyn BackupStorage>,
+    report: Arc<Mutex<DryRunReport>>,
+}
+
+impl DryRunStorage {
+    const FILE_HANDLE_PREFIX: &'static str = "dry-run://";
+
+    pub fn new(inner: Arc<dyn BackupStorage>) -> Self {
+        Self {
+            inner,
+            report: Arc::new(Mutex::new(DryRunReport::default())),
+        }
+    }
+
+    pub fn report(&self) -> Arc<Mutex<DryRunReport>> {
+        Arc::clone(&self.report)
+    }
+}
+
+#[async_trait]
+impl BackupStorage for DryRunStorage {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        self.report.lock().backups.push(name.to_string());
+        Ok(name.to_string())
+    }
+
+    async fn create_for_write(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        let file_handle = format!(
+            "{}{}/{}",
+            Self::FILE_HANDLE_PREFIX,
+            backup_handle,
+            name.as_str()
+        );
+        let index = {
+            let mut report = self.report.lock();
+            report.files.push((file_handle.clone(), 0));
+            report.files.len() - 1
+        };
+        let writer = SizeCountingWriter {
+            report: Arc::clone(&self.report),
+            index,
+        };
+        Ok((file_handle, Box::new(writer)))
+    }
+
+    async fn open_for_read(
+        &self,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.inner.open_for_read(file_handle).await
+    }
+
+    async fn save_metadata_line(&self, name: &ShellSafeName, content: &TextLine) -> Result<()> {
+        self.report.lock().metadata_lines.push(format!(
+            "{}: {}",
+            name.as_str(),
+            content.as_ref().trim_end()
+        ));
+        Ok(())
+    }
+
+    async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        self.inner.list_metadata_files().await
+    }
+
+    async fn read_tags(&self, file_handle: &FileHandleRef) -> Result<Option<ObjectTags>> {
+        self.inner.read_tags(file_handle).await
+    }
+}
+
+/// Discards what's written, only adding up its size in the report.
+struct SizeCountingWriter {
+    report: Arc<Mutex<DryRunReport>>,
+    index: usize,
+}
+
+impl AsyncWrite for SizeCountingWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.report.lock().files[self.index].1 += buf.len() as u64;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::storage::local_fs::LocalFs;
+use aptos_temppath::TempPath;
+use std::str::FromStr;
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn test_dry_run_writes_nothing() {
+    let tmpdir = TempPath::new();
+    tmpdir.create_as_dir().unwrap();
+    let store = DryRunStorage::new(Arc::new(LocalFs::new(tmpdir.path().to_path_buf())));
+
+    let backup_handle = store
+        .create_backup(&ShellSafeName::from_str("transaction_0-").unwrap())
+        .await
+        .unwrap();
+    let (file_handle, mut file) = store
+        .create_for_write(
+            &backup_handle,
+            &ShellSafeName::from_str("0-.chunk").unwrap(),
+        )
+        .await
+        .unwrap();
+    file.write_all(&[0u8; 100]).await.unwrap();
+    file.write_all(&[0u8; 20]).await.unwrap();
+    file.shutdown().await.unwrap();
+    store
+        .save_metadata_line(
+            &ShellSafeName::from_str("transaction_0-99.meta").unwrap(),
+            &TextLine::new("{}").unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let report = store.report();
+    let report = report.lock();
+    assert_eq!(report.backups, vec!["transaction_0-".to_string()]);
+    assert_eq!(report.files, vec![(file_handle, 120)]);
+    assert_eq!(report.total_bytes(), 120);
+    assert_eq!(report.metadata_lines.len(), 1);
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 0);
+    assert!(store.list_metadata_files().await.unwrap().is_empty());
+}
@@ -0,0 +1,88 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage classes of the files of a backup, for storages with cheaper but slower tiers.
+//!
+//! Manifests, proofs and metadata are small and read by every restore and verification, so
+//! they're hinted hot. Chunks are the bulk of a backup and only read by the restores that need
+//! them, so they're hinted cold, and lifecycle rules of the storage can move them to an archive
+//! tier as they age. A file in an archive tier can't be read until it's restored from the archive,
+//! which takes hours, see `ObjectStatus`.
+
+use crate::storage::ShellSafeName;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageClass {
+    Hot,
+    Cold,
+    Archive,
+}
+
+impl StorageClass {
+    /// Whether the file is a chunk, as opposed to a manifest, proof or metadata file.
+    pub fn is_chunk(name: &ShellSafeName) -> bool {
+        name.ends_with(".chunk")
+    }
+}
+
+impl fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            StorageClass::Hot => "hot",
+            StorageClass::Cold => "cold",
+            StorageClass::Archive => "archive",
+        })
+    }
+}
+
+/// Whether a file can be read right away, as printed by the storage commands.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ObjectStatus {
+    Available,
+    /// In an archive tier, and not restored.
+    Archived,
+    /// Being restored from an archive tier.
+    Restoring,
+}
+
+impl FromStr for ObjectStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim() {
+            "available" => ObjectStatus::Available,
+            "archived" => ObjectStatus::Archived,
+            "restoring" => ObjectStatus::Restoring,
+            other => bail!(
+                "Unknown object status {:?}, expected available, archived or restoring.",
+                other
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectStatus, StorageClass};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_storage_classes() {
+        assert!(StorageClass::is_chunk(&"0-.chunk".parse().unwrap()));
+        assert!(!StorageClass::is_chunk(
+            &"transaction.manifest".parse().unwrap()
+        ));
+        assert!(!StorageClass::is_chunk(&"0-99.proof".parse().unwrap()));
+        assert_eq!(StorageClass::Archive.to_string(), "archive");
+
+        assert_eq!(
+            ObjectStatus::from_str("restoring\n").unwrap(),
+            ObjectStatus::Restoring
+        );
+        assert!(ObjectStatus::from_str("GLACIER").is_err());
+    }
+}
@@ -7,7 +7,7 @@ mod tests;
 
 use super::{BackupHandle, BackupHandleRef, FileHandle, FileHandleRef};
 use crate::{
-    storage::{BackupStorage, ShellSafeName, TextLine},
+    storage::{tags::ObjectTags, BackupStorage, ShellSafeName, TextLine},
     utils::{error_notes::ErrorNotes, path_exists, PathToString},
 };
 use anyhow::Result;
@@ -19,7 +19,7 @@ use std::{
     str::FromStr,
 };
 use tokio::{
-    fs::{create_dir_all, read_dir, OpenOptions},
+    fs::{create_dir_all, read_dir, read_to_string, write, OpenOptions},
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
 };
 
@@ -51,6 +51,8 @@ pub struct LocalFs {
 
 impl LocalFs {
     const METADATA_DIR: &'static str = "metadata";
+    /// Tags of a file are kept next to it, in a file with this suffix.
+    const TAGS_SUFFIX: &'static str = ".tags";
 
     pub fn new(dir: PathBuf) -> Self {
         Self { dir }
@@ -63,6 +65,11 @@ impl LocalFs {
     pub fn metadata_dir(&self) -> PathBuf {
         self.dir.join(Self::METADATA_DIR)
     }
+
+    fn tags_path(&self, file_handle: &FileHandleRef) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", file_handle, Self::TAGS_SUFFIX))
+    }
 }
 
 #[async_trait]
@@ -92,6 +99,20 @@ impl BackupStorage for LocalFs {
         Ok((file_handle, Box::new(file)))
     }
 
+    async fn create_for_write_with_tags(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+        tags: &ObjectTags,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        let (file_handle, file) = self.create_for_write(backup_handle, name).await?;
+        if !tags.is_empty() {
+            let path = self.tags_path(&file_handle);
+            write(&path, tags.to_string()).await.err_notes(&path)?;
+        }
+        Ok((file_handle, file))
+    }
+
     async fn open_for_read(
         &self,
         file_handle: &FileHandleRef,
@@ -137,4 +158,12 @@ impl BackupStorage for LocalFs {
         }
         Ok(res)
     }
+
+    async fn read_tags(&self, file_handle: &FileHandleRef) -> Result<Option<ObjectTags>> {
+        let path = self.tags_path(file_handle);
+        if !path_exists(&path).await {
+            return Ok(Some(ObjectTags::default()));
+        }
+        Ok(Some(read_to_string(&path).await.err_notes(&path)?.parse()?))
+    }
 }
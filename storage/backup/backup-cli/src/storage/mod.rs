@@ -3,7 +3,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod command_adapter;
+pub mod dry_run;
+pub mod lifecycle;
 pub mod local_fs;
+pub mod sftp;
+pub mod tags;
 
 #[cfg(test)]
 mod test_util;
@@ -13,6 +17,8 @@ mod tests;
 use crate::storage::{
     command_adapter::{CommandAdapter, CommandAdapterOpt},
     local_fs::{LocalFs, LocalFsOpt},
+    sftp::{Sftp, SftpOpt},
+    tags::ObjectTags,
 };
 use anyhow::{ensure, Result};
 use async_trait::async_trait;
@@ -147,6 +153,16 @@ pub trait BackupStorage: Send + Sync {
         backup_handle: &BackupHandleRef,
         name: &ShellSafeName,
     ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)>;
+    /// Like `create_for_write`, attaching the tags to the file. Storages that don't support tags
+    /// ignore them.
+    async fn create_for_write_with_tags(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+        _tags: &ObjectTags,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        self.create_for_write(backup_handle, name).await
+    }
     /// Open file for reading.
     async fn open_for_read(
         &self,
@@ -168,6 +184,10 @@ pub trait BackupStorage: Send + Sync {
     ///   2. But the cache does expect the content stays the same for a file handle, so when
     /// reorganising metadata files, give them new unique names.
     async fn list_metadata_files(&self) -> Result<Vec<FileHandle>>;
+    /// The tags attached to a file when it was created, or None if the storage doesn't keep tags.
+    async fn read_tags(&self, _file_handle: &FileHandleRef) -> Result<Option<ObjectTags>> {
+        Ok(None)
+    }
 }
 
 #[derive(Parser)]
@@ -181,6 +201,11 @@ pub enum StorageOpt {
     https://github.com/aptos-labs/aptos-core/tree/main/storage/backup/backup-cli/src/storage/command_adapter/sample_configs/"
     )]
     CommandAdapter(CommandAdapterOpt),
+    #[clap(
+        about = "Select the Sftp backup storage type, which keeps backups in a directory of an SFTP \
+    server, authenticating with a private key."
+    )]
+    Sftp(SftpOpt),
 }
 
 impl StorageOpt {
@@ -188,6 +213,7 @@ impl StorageOpt {
         Ok(match self {
             StorageOpt::LocalFs(opt) => Arc::new(LocalFs::new_with_opt(opt)),
             StorageOpt::CommandAdapter(opt) => Arc::new(CommandAdapter::new_with_opt(opt).await?),
+            StorageOpt::Sftp(opt) => Arc::new(Sftp::new_with_opt(opt).await?),
         })
     }
 }
@@ -196,7 +222,7 @@ impl StorageOpt {
 #[clap(group(
     ArgGroup::new("storage")
     .required(true)
-    .args(&["local-fs-dir", "command-adapter-config"]),
+    .args(&["local-fs-dir", "command-adapter-config", "sftp-config"]),
 ))]
 pub struct DBToolStorageOpt {
     #[clap(
@@ -212,14 +238,57 @@ pub struct DBToolStorageOpt {
     https://github.com/aptos-labs/aptos-networks/tree/main/testnet/backups "
     )]
     command_adapter_config: Option<CommandAdapterOpt>,
+    #[clap(
+        long,
+        help = "Select the Sftp backup storage type, which keeps backups in a directory of an SFTP \
+    server, authenticating with a private key. Takes the path to its config file."
+    )]
+    sftp_config: Option<SftpOpt>,
 }
 
 impl DBToolStorageOpt {
     pub async fn init_storage(self) -> Result<Arc<dyn BackupStorage>> {
         Ok(if self.local_fs_dir.is_some() {
             Arc::new(LocalFs::new_with_opt(self.local_fs_dir.unwrap()))
+        } else if let Some(sftp_config) = self.sftp_config {
+            Arc::new(Sftp::new_with_opt(sftp_config).await?)
         } else {
             Arc::new(CommandAdapter::new_with_opt(self.command_adapter_config.unwrap()).await?)
         })
     }
 }
+
+/// A second storage next to the one of `DBToolStorageOpt`, for commands copying backups between
+/// them, selected the same way with options prefixed by "source-".
+#[derive(Parser)]
+#[clap(group(
+    ArgGroup::new("source-storage")
+    .required(true)
+    .args(&["source-local-fs-dir", "source-command-adapter-config", "source-sftp-config"]),
+))]
+pub struct DBToolSourceStorageOpt {
+    #[clap(long, help = "Select the LocalFs backup storage type for the source.")]
+    source_local_fs_dir: Option<LocalFsOpt>,
+    #[clap(
+        long,
+        help = "Select the CommandAdapter backup storage type for the source, taking its config."
+    )]
+    source_command_adapter_config: Option<CommandAdapterOpt>,
+    #[clap(
+        long,
+        help = "Select the Sftp backup storage type for the source, taking its config."
+    )]
+    source_sftp_config: Option<SftpOpt>,
+}
+
+impl DBToolSourceStorageOpt {
+    pub async fn init_storage(self) -> Result<Arc<dyn BackupStorage>> {
+        DBToolStorageOpt {
+            local_fs_dir: self.source_local_fs_dir,
+            command_adapter_config: self.source_command_adapter_config,
+            sftp_config: self.source_sftp_config,
+        }
+        .init_storage()
+        .await
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::error_notes::ErrorNotes;
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "SftpConfig::default_port")]
+    pub port: u16,
+    pub user: String,
+    /// Private key to authenticate with, e.g. ~/.ssh/id_ed25519.
+    pub private_key: PathBuf,
+    /// Passphrase of the private key, if it's encrypted.
+    #[serde(default)]
+    pub private_key_passphrase: Option<String>,
+    /// File in the OpenSSH known_hosts format the key of the server is checked against, e.g.
+    /// ~/.ssh/known_hosts. The server is not verified if not set.
+    #[serde(default)]
+    pub known_hosts: Option<PathBuf>,
+    /// Remote directory holding the backups.
+    pub dir: PathBuf,
+    /// Maximum number of files transferred at the same time, each over a connection of its own.
+    #[serde(default = "SftpConfig::default_max_connections")]
+    pub max_connections: usize,
+    /// Number of times in a row a read is resumed from where it broke off, before giving up.
+    #[serde(default = "SftpConfig::default_read_retries")]
+    pub read_retries: usize,
+}
+
+impl SftpConfig {
+    fn default_port() -> u16 {
+        22
+    }
+
+    fn default_max_connections() -> usize {
+        8
+    }
+
+    fn default_read_retries() -> usize {
+        3
+    }
+
+    pub async fn load_from_file(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read(path).await.err_notes(path)?;
+        Ok(serde_yaml::from_slice(&content)?)
+    }
+
+    pub fn load_from_str(content: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+}
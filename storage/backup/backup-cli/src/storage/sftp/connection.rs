@@ -0,0 +1,213 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::storage::sftp::config::SftpConfig;
+use anyhow::{bail, ensure, Context, Result};
+use aptos_infallible::Mutex;
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub(super) struct Connection {
+    // Kept alive along with the SFTP channel opened on it.
+    _session: Session,
+    sftp: ssh2::Sftp,
+}
+
+impl Connection {
+    fn open(config: &SftpConfig) -> Result<Self> {
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))
+            .with_context(|| format!("Failed to connect to {}:{}", config.host, config.port))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake()?;
+        check_host_key(&session, config)?;
+        session
+            .userauth_pubkey_file(
+                &config.user,
+                None,
+                &config.private_key,
+                config.private_key_passphrase.as_deref(),
+            )
+            .with_context(|| format!("Failed to authenticate as {}", config.user))?;
+        ensure!(
+            session.authenticated(),
+            "Failed to authenticate as {}",
+            config.user
+        );
+        let sftp = session.sftp()?;
+        Ok(Self {
+            _session: session,
+            sftp,
+        })
+    }
+}
+
+fn check_host_key(session: &Session, config: &SftpConfig) -> Result<()> {
+    let known_hosts_file = match &config.known_hosts {
+        Some(known_hosts_file) => known_hosts_file,
+        None => return Ok(()),
+    };
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts
+        .read_file(known_hosts_file, KnownHostFileKind::OpenSSH)
+        .with_context(|| format!("Failed to read {:?}", known_hosts_file))?;
+    let (key, _) = session
+        .host_key()
+        .context("The server didn't present a host key")?;
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => bail!(
+            "Host key of {} not found in {:?}",
+            config.host,
+            known_hosts_file
+        ),
+        CheckResult::Mismatch => bail!(
+            "Host key of {} doesn't match the one in {:?}",
+            config.host,
+            known_hosts_file
+        ),
+        CheckResult::Failure => bail!("Failed to check the host key of {}", config.host),
+    }
+}
+
+/// Connections to the server, each transferring one file at a time. Connections are opened on
+/// demand and reused, and one that errored is dropped rather than reused.
+pub(super) struct ConnectionPool {
+    config: SftpConfig,
+    idle: Mutex<Vec<Connection>>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl ConnectionPool {
+    pub fn new(config: SftpConfig) -> Self {
+        let semaphore = Arc::new(Semaphore::new(config.max_connections.max(1)));
+        Self {
+            config,
+            idle: Mutex::new(vec![]),
+            semaphore,
+        }
+    }
+
+    /// Waits for a connection slot, to be held until the transfer finishes.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        Ok(self.semaphore.clone().acquire_owned().await?)
+    }
+
+    /// Runs a short operation on a blocking thread.
+    pub async fn run<T, F>(self: &Arc<Self>, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ssh2::Sftp) -> Result<T> + Send + 'static,
+    {
+        let permit = self.acquire().await?;
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            pool.with_connection(f)
+        })
+        .await?
+    }
+
+    /// Blocking, to be called with a slot acquired.
+    pub fn with_connection<T>(&self, f: impl FnOnce(&ssh2::Sftp) -> Result<T>) -> Result<T> {
+        let connection = self.checkout()?;
+        let res = f(&connection.sftp);
+        if res.is_ok() {
+            self.checkin(connection);
+        }
+        res
+    }
+
+    fn checkout(&self) -> Result<Connection> {
+        match self.idle.lock().pop() {
+            Some(connection) => Ok(connection),
+            None => Connection::open(&self.config),
+        }
+    }
+
+    fn checkin(&self, connection: Connection) {
+        self.idle.lock().push(connection);
+    }
+}
+
+/// A remote file being read, reopened where it broke off after a failure.
+pub(super) struct Source<'a> {
+    pool: &'a ConnectionPool,
+    path: PathBuf,
+    offset: u64,
+    open: Option<(Connection, ssh2::File)>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(pool: &'a ConnectionPool, path: PathBuf) -> Self {
+        Self {
+            pool,
+            path,
+            offset: 0,
+            open: None,
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the next bytes, 0 meaning the end of the file. After an error, the next read
+    /// reopens the file over a new connection.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let res = self.read_impl(buf);
+        match &res {
+            Ok(n) => self.offset += *n as u64,
+            Err(_) => self.open = None,
+        }
+        res
+    }
+
+    fn read_impl(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.open.is_none() {
+            let connection = self.pool.checkout()?;
+            let mut file = connection
+                .sftp
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {:?}", self.path))?;
+            file.seek(SeekFrom::Start(self.offset))?;
+            self.open = Some((connection, file));
+        }
+        let (_, file) = self.open.as_mut().expect("Opened above.");
+        Ok(file.read(buf)?)
+    }
+
+    /// Returns the connection to the pool once done.
+    pub fn finish(mut self) {
+        if let Some((connection, file)) = self.open.take() {
+            drop(file);
+            self.pool.checkin(connection);
+        }
+    }
+}
+
+/// Creates the directory and its missing parents.
+pub(super) fn create_dir_all(sftp: &ssh2::Sftp, dir: &Path) -> Result<()> {
+    let mut missing = vec![];
+    for ancestor in dir.ancestors() {
+        if ancestor.as_os_str().is_empty() || sftp.stat(ancestor).is_ok() {
+            break;
+        }
+        missing.push(ancestor);
+    }
+    for dir in missing.into_iter().rev() {
+        // Possibly created concurrently in the meantime.
+        if let Err(e) = sftp.mkdir(dir, 0o755) {
+            if !sftp.stat(dir).map_or(false, |stat| stat.is_dir()) {
+                return Err(e).with_context(|| format!("Failed to create {:?}", dir));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,218 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+mod config;
+mod connection;
+mod stream;
+
+#[cfg(test)]
+mod tests;
+
+pub use crate::storage::sftp::config::SftpConfig;
+use crate::{
+    storage::{
+        sftp::{
+            connection::{ConnectionPool, Source},
+            stream::{SftpReader, SftpWriter},
+        },
+        BackupHandle, BackupHandleRef, BackupStorage, FileHandle, FileHandleRef, ShellSafeName,
+        TextLine,
+    },
+    utils::PathToString,
+};
+use anyhow::{Context, Result};
+use aptos_logger::prelude::*;
+use async_trait::async_trait;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use ssh2::{OpenFlags, OpenType};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::io::SyncIoBridge;
+
+/// Size of the buffer between the SFTP transfer, done on a blocking thread, and its reader or
+/// writer.
+const PIPE_BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct SftpOpt {
+    #[clap(long = "config", help = "Config file for the SFTP backup storage.")]
+    config: PathBuf,
+}
+
+impl FromStr for SftpOpt {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SftpOpt {
+            config: PathBuf::from(s),
+        })
+    }
+}
+
+/// A BackupStorage keeping files in a directory of an SFTP server, laid out like `LocalFs`.
+/// See `SftpConfig`.
+pub struct Sftp {
+    dir: PathBuf,
+    read_retries: usize,
+    pool: Arc<ConnectionPool>,
+}
+
+impl Sftp {
+    const METADATA_DIR: &'static str = "metadata";
+
+    pub fn new(config: SftpConfig) -> Self {
+        if config.known_hosts.is_none() {
+            warn!(
+                host = config.host,
+                "No known_hosts file configured, the identity of the SFTP server is not verified."
+            );
+        }
+        Self {
+            dir: config.dir.clone(),
+            read_retries: config.read_retries,
+            pool: Arc::new(ConnectionPool::new(config)),
+        }
+    }
+
+    pub async fn new_with_opt(opt: SftpOpt) -> Result<Self> {
+        Ok(Self::new(SftpConfig::load_from_file(&opt.config).await?))
+    }
+
+    fn metadata_dir(&self) -> PathBuf {
+        self.dir.join(Self::METADATA_DIR)
+    }
+}
+
+#[async_trait]
+impl BackupStorage for Sftp {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        let dir = self.dir.join(name.as_ref());
+        self.pool
+            .run(move |sftp| connection::create_dir_all(sftp, &dir))
+            .await?;
+        Ok(name.to_string())
+    }
+
+    async fn create_for_write(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        let file_handle = Path::new(backup_handle)
+            .join(name.as_ref())
+            .path_to_string()?;
+        let path = self.dir.join(&file_handle);
+        let (writer, reader) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+        let mut reader = SyncIoBridge::new(reader);
+        let permit = self.pool.acquire().await?;
+        let pool = self.pool.clone();
+        let upload = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            pool.with_connection(|sftp| {
+                let mut file = sftp
+                    .open_mode(
+                        &path,
+                        OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                        0o644,
+                        OpenType::File,
+                    )
+                    .with_context(|| format!("Failed to create {:?}", path))?;
+                std::io::copy(&mut reader, &mut file)
+                    .with_context(|| format!("Failed to upload {:?}", path))?;
+                Ok(())
+            })
+        });
+        Ok((file_handle, Box::new(SftpWriter::new(writer, upload))))
+    }
+
+    async fn open_for_read(
+        &self,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let path = self.dir.join(file_handle);
+        let (writer, reader) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+        let mut writer = SyncIoBridge::new(writer);
+        let permit = self.pool.acquire().await?;
+        let pool = self.pool.clone();
+        let read_retries = self.read_retries;
+        let download = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let mut source = Source::new(&pool, path.clone());
+            let mut buf = vec![0; 64 * 1024];
+            let mut failures = 0;
+            loop {
+                match source.read(&mut buf) {
+                    Ok(0) => {
+                        source.finish();
+                        break;
+                    },
+                    Ok(n) => {
+                        writer.write_all(&buf[..n])?;
+                        failures = 0;
+                    },
+                    Err(e) if failures < read_retries => {
+                        failures += 1;
+                        warn!(
+                            "Failed reading {:?} at offset {}, resuming ({}/{}): {:#}",
+                            path,
+                            source.offset(),
+                            failures,
+                            read_retries,
+                            e
+                        );
+                        std::thread::sleep(Duration::from_secs(1 << failures.min(5)));
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+            writer.shutdown()?;
+            Ok(())
+        });
+        Ok(Box::new(SftpReader::new(reader, download)))
+    }
+
+    async fn save_metadata_line(&self, name: &ShellSafeName, content: &TextLine) -> Result<()> {
+        let dir = self.metadata_dir();
+        let path = dir.join(name.as_ref());
+        let content = content.as_ref().to_string();
+        self.pool
+            .run(move |sftp| {
+                connection::create_dir_all(sftp, &dir)?;
+                sftp.create(&path)
+                    .with_context(|| format!("Failed to create {:?}", path))?
+                    .write_all(content.as_bytes())
+                    .with_context(|| format!("Failed to write {:?}", path))?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        let dir = self.metadata_dir();
+        self.pool
+            .run(move |sftp| {
+                if sftp.stat(&dir).is_err() {
+                    return Ok(vec![]);
+                }
+                let rel_path = Path::new(Self::METADATA_DIR);
+                sftp.readdir(&dir)
+                    .with_context(|| format!("Failed to list {:?}", dir))?
+                    .into_iter()
+                    .filter(|(_, stat)| stat.is_file())
+                    .map(|(path, _)| {
+                        rel_path
+                            .join(path.file_name().context("No file name")?)
+                            .path_to_string()
+                    })
+                    .collect()
+            })
+            .await
+    }
+}
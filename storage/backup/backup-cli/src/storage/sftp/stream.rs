@@ -0,0 +1,103 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transfers run on blocking threads, exchanging bytes with the backup through in-memory pipes.
+//! These wrap the ends of the pipes handed out, so that the outcome of the transfer is reported:
+//! a read only ends once the download succeeded, and a write only shuts down once the upload did.
+
+use anyhow::Result;
+use futures::ready;
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf},
+    task::JoinHandle,
+};
+
+fn poll_transfer(
+    transfer: &mut Option<JoinHandle<Result<()>>>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    let res = match transfer.as_mut() {
+        Some(handle) => ready!(Pin::new(handle).poll(cx)),
+        None => return Poll::Ready(Ok(())),
+    };
+    *transfer = None;
+    Poll::Ready(match res {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(io::Error::new(io::ErrorKind::Other, format!("{:#}", e))),
+        Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+    })
+}
+
+pub(super) struct SftpReader {
+    inner: DuplexStream,
+    download: Option<JoinHandle<Result<()>>>,
+}
+
+impl SftpReader {
+    pub fn new(inner: DuplexStream, download: JoinHandle<Result<()>>) -> Self {
+        Self {
+            inner,
+            download: Some(download),
+        }
+    }
+}
+
+impl AsyncRead for SftpReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled_before_poll = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        if buf.filled().len() > filled_before_poll {
+            return Poll::Ready(Ok(()));
+        }
+        // Hit EOF, which is only genuine if the download succeeded.
+        poll_transfer(&mut self.download, cx)
+    }
+}
+
+pub(super) struct SftpWriter {
+    inner: DuplexStream,
+    upload: Option<JoinHandle<Result<()>>>,
+    shut_down: bool,
+}
+
+impl SftpWriter {
+    pub fn new(inner: DuplexStream, upload: JoinHandle<Result<()>>) -> Self {
+        Self {
+            inner,
+            upload: Some(upload),
+            shut_down: false,
+        }
+    }
+}
+
+impl AsyncWrite for SftpWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.shut_down {
+            ready!(Pin::new(&mut self.inner).poll_shutdown(cx))?;
+            self.shut_down = true;
+        }
+        poll_transfer(&mut self.upload, cx)
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::SftpConfig;
+
+#[test]
+fn test_load_config() {
+    let config = SftpConfig::load_from_str(
+        r#"
+host: backup.example.com
+user: backup
+private_key: /etc/backup/id_ed25519
+dir: /srv/backups/testnet
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.port, 22);
+    assert_eq!(config.max_connections, 8);
+    assert_eq!(config.read_retries, 3);
+    assert!(config.known_hosts.is_none());
+
+    let config = SftpConfig::load_from_str(include_str!("sample.yaml")).unwrap();
+    assert!(config.known_hosts.is_some());
+    assert_eq!(config.max_connections, 16);
+
+    // Typos are caught rather than silently ignored.
+    assert!(SftpConfig::load_from_str(
+        r#"
+host: backup.example.com
+user: backup
+private_key: /etc/backup/id_ed25519
+dir: /srv/backups/testnet
+max_conections: 2
+"#,
+    )
+    .is_err());
+}
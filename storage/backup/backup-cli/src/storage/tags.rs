@@ -0,0 +1,207 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::storage::{
+    BackupHandle, BackupHandleRef, BackupStorage, FileHandle, FileHandleRef, ShellSafeName,
+    TextLine,
+};
+use anyhow::{anyhow, Result};
+use aptos_types::chain_id::ChainId;
+use async_trait::async_trait;
+use std::{collections::BTreeMap, fmt, str::FromStr, sync::Arc};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Key-value tags attached to the uploaded files of a backup, in storages that support it, e.g.
+/// for bucket lifecycle rules keyed on the backup type.
+///
+/// Keys and values are limited to "[a-zA-Z0-9._-]", other characters being replaced with '_', so
+/// they're safe in shell commands and accepted by the object stores. Printed as
+/// "key=value&key=value", the form `aws s3api put-object --tagging` takes.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ObjectTags(BTreeMap<String, String>);
+
+impl ObjectTags {
+    pub const BACKUP_TYPE: &'static str = "backup_type";
+    pub const CHAIN_ID: &'static str = "chain_id";
+    pub const TOOL_VERSION: &'static str = "tool_version";
+
+    /// Tags common to all files of a backup of the type.
+    pub fn for_backup(backup_type: &str, chain_id: Option<ChainId>) -> Self {
+        let mut tags = Self::default()
+            .with(Self::BACKUP_TYPE, backup_type)
+            .with(Self::TOOL_VERSION, env!("CARGO_PKG_VERSION"));
+        if let Some(chain_id) = chain_id {
+            tags = tags.with(Self::CHAIN_ID, chain_id);
+        }
+        tags
+    }
+
+    pub fn with(mut self, key: &str, value: impl fmt::Display) -> Self {
+        self.0
+            .insert(Self::sanitize(key), Self::sanitize(&value.to_string()));
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn sanitize(s: &str) -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ObjectTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (key, value)) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "&")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses tags separated by either '&' or newlines, which is how storage commands print them.
+impl FromStr for ObjectTags {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.split(|c| c == '&' || c == '\n')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .try_fold(Self::default(), |tags, tag| {
+                let (key, value) = tag
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Tag not in the form of key=value: {}", tag))?;
+                Ok(tags.with(key, value))
+            })
+    }
+}
+
+/// Attaches the tags to all the files created through it, leaving the rest to the inner storage.
+pub struct TaggedStorage {
+    inner: Arc<dyn BackupStorage>,
+    tags: ObjectTags,
+}
+
+impl TaggedStorage {
+    pub fn new(inner: Arc<dyn BackupStorage>, tags: ObjectTags) -> Self {
+        Self { inner, tags }
+    }
+}
+
+#[async_trait]
+impl BackupStorage for TaggedStorage {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        self.inner.create_backup(name).await
+    }
+
+    async fn create_for_write(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        self.inner
+            .create_for_write_with_tags(backup_handle, name, &self.tags)
+            .await
+    }
+
+    async fn open_for_read(
+        &self,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        self.inner.open_for_read(file_handle).await
+    }
+
+    async fn save_metadata_line(&self, name: &ShellSafeName, content: &TextLine) -> Result<()> {
+        self.inner.save_metadata_line(name, content).await
+    }
+
+    async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        self.inner.list_metadata_files().await
+    }
+
+    async fn read_tags(&self, file_handle: &FileHandleRef) -> Result<Option<ObjectTags>> {
+        self.inner.read_tags(file_handle).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectTags, TaggedStorage};
+    use crate::storage::{local_fs::LocalFs, BackupStorage};
+    use aptos_temppath::TempPath;
+    use aptos_types::chain_id::ChainId;
+    use std::{str::FromStr, sync::Arc};
+    use tokio::io::AsyncWriteExt;
+
+    #[test]
+    fn test_object_tags_format() {
+        let tags = ObjectTags::for_backup("epoch_ending", Some(ChainId::test()))
+            .with("first_epoch", 0)
+            .with("odd key", "a/b=c");
+        assert_eq!(tags.get(ObjectTags::CHAIN_ID), Some("testing"));
+        assert_eq!(tags.get("odd_key"), Some("a_b_c"));
+        assert_eq!(
+            tags.to_string(),
+            format!(
+                "backup_type=epoch_ending&chain_id=testing&first_epoch=0&odd_key=a_b_c&tool_version={}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(ObjectTags::from_str(&tags.to_string()).unwrap(), tags);
+        assert_eq!(
+            ObjectTags::from_str("backup_type=transaction\nfirst_version=10\n").unwrap(),
+            ObjectTags::default()
+                .with("backup_type", "transaction")
+                .with("first_version", 10)
+        );
+        assert!(ObjectTags::from_str("").unwrap().is_empty());
+        assert!(ObjectTags::from_str("backup_type").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tagged_storage() {
+        let tmpdir = TempPath::new();
+        tmpdir.create_as_dir().unwrap();
+        let store: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(tmpdir.path().to_path_buf()));
+        let tags = ObjectTags::for_backup("state_snapshot", None).with("epoch", 5);
+        let tagged = TaggedStorage::new(store.clone(), tags.clone());
+
+        let backup_handle = tagged
+            .create_backup(&"backup".parse().unwrap())
+            .await
+            .unwrap();
+        let (tagged_file, mut file) = tagged
+            .create_for_write(&backup_handle, &"tagged".parse().unwrap())
+            .await
+            .unwrap();
+        file.write_all(b"content").await.unwrap();
+        file.shutdown().await.unwrap();
+        let (untagged_file, mut file) = store
+            .create_for_write(&backup_handle, &"untagged".parse().unwrap())
+            .await
+            .unwrap();
+        file.shutdown().await.unwrap();
+
+        assert_eq!(store.read_tags(&tagged_file).await.unwrap(), Some(tags));
+        assert_eq!(
+            tagged.read_tags(&untagged_file).await.unwrap(),
+            Some(ObjectTags::default())
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hooks notifying monitoring of a backup failing or falling behind, either by posting to a
+//! webhook or by running a command, so that it doesn't need to scrape the logs.
+
+use crate::utils::unix_timestamp_sec;
+use anyhow::{ensure, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::prelude::*;
+use clap::Parser;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+use tokio::io::AsyncWriteExt;
+
+#[derive(Clone, Default, Parser)]
+pub struct AlertHooksOpt {
+    #[clap(
+        long,
+        help = "URL to POST an alert to, as JSON, when a backup fails or is found stale."
+    )]
+    pub alert_webhook_url: Option<String>,
+    #[clap(
+        long,
+        help = "Command run by bash when a backup fails or is found stale. The alert is passed \
+        as JSON on stdin, and its kind and message in the ALERT_KIND and ALERT_MESSAGE \
+        environment variables."
+    )]
+    pub alert_command: Option<String>,
+    #[clap(
+        long,
+        default_value = "3600",
+        help = "Minimum number of seconds between two alerts of the same kind, so that a backup \
+        failing over and over alerts once in a while rather than on every retry."
+    )]
+    pub alert_min_interval_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// A backup run failed.
+    BackupFailed,
+    /// The newest backup is older than allowed.
+    BackupStale,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BackupFailed => write!(f, "backup_failed"),
+            Self::BackupStale => write!(f, "backup_stale"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub message: String,
+    pub timestamp_secs: i64,
+}
+
+impl Alert {
+    pub fn new(kind: AlertKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            timestamp_secs: unix_timestamp_sec(),
+        }
+    }
+}
+
+pub struct AlertHooks {
+    webhook_url: Option<String>,
+    command: Option<String>,
+    min_interval: Duration,
+    last_fired: Mutex<HashMap<AlertKind, Instant>>,
+    client: reqwest::Client,
+}
+
+impl AlertHooks {
+    pub fn new(opt: AlertHooksOpt) -> Self {
+        Self {
+            webhook_url: opt.alert_webhook_url,
+            command: opt.alert_command,
+            min_interval: Duration::from_secs(opt.alert_min_interval_secs),
+            last_fired: Mutex::new(HashMap::new()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.webhook_url.is_none() && self.command.is_none()
+    }
+
+    /// Notifies all hooks, unless an alert of the same kind was fired recently. Failing hooks are
+    /// logged, not to fail what is being alerted on on top.
+    pub async fn fire(&self, alert: Alert) {
+        if self.is_empty() || !self.should_fire(alert.kind) {
+            return;
+        }
+        info!("Firing {} alert: {}", alert.kind, alert.message);
+        if let Some(url) = &self.webhook_url {
+            if let Err(e) = self.post(url, &alert).await {
+                warn!("Alert webhook {} failed: {:#}", url, e);
+            }
+        }
+        if let Some(command) = &self.command {
+            if let Err(e) = run_command(command, &alert).await {
+                warn!("Alert command `{}` failed: {:#}", command, e);
+            }
+        }
+    }
+
+    fn should_fire(&self, kind: AlertKind) -> bool {
+        let now = Instant::now();
+        let mut last_fired = self.last_fired.lock();
+        match last_fired.get(&kind) {
+            Some(last) if now.duration_since(*last) < self.min_interval => false,
+            _ => {
+                last_fired.insert(kind, now);
+                true
+            },
+        }
+    }
+
+    async fn post(&self, url: &str, alert: &Alert) -> Result<()> {
+        self.client
+            .post(url)
+            .timeout(Duration::from_secs(10))
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+async fn run_command(command: &str, alert: &Alert) -> Result<()> {
+    let mut child = tokio::process::Command::new("bash")
+        .args(["-c", command])
+        .env("ALERT_KIND", alert.kind.to_string())
+        .env("ALERT_MESSAGE", &alert.message)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped.");
+    // The command is free not to read its input.
+    let _ = stdin.write_all(&serde_json::to_vec(alert)?).await;
+    drop(stdin);
+    let status = child.wait().await?;
+    ensure!(status.success(), "Exited with {}", status);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alert, AlertHooks, AlertHooksOpt, AlertKind};
+    use aptos_temppath::TempPath;
+
+    #[tokio::test]
+    async fn test_alert_command() {
+        let out = TempPath::new();
+        let hooks = AlertHooks::new(AlertHooksOpt {
+            alert_command: Some(format!(
+                "(echo $ALERT_KIND; cat; echo) >> {}",
+                out.path().display()
+            )),
+            alert_min_interval_secs: 3600,
+            ..Default::default()
+        });
+
+        hooks
+            .fire(Alert::new(AlertKind::BackupFailed, "first".to_string()))
+            .await;
+        // Too soon after the previous one of the same kind.
+        hooks
+            .fire(Alert::new(AlertKind::BackupFailed, "second".to_string()))
+            .await;
+        hooks
+            .fire(Alert::new(AlertKind::BackupStale, "stale".to_string()))
+            .await;
+
+        let lines = std::fs::read_to_string(out.path()).unwrap();
+        let lines: Vec<_> = lines.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "backup_failed");
+        let alert: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(alert["kind"], "backup_failed");
+        assert_eq!(alert["message"], "first");
+        assert_eq!(lines[2], "backup_stale");
+    }
+}
@@ -2,15 +2,41 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::utils::error_notes::ErrorNotes;
-use anyhow::Result;
+use crate::utils::{error_notes::ErrorNotes, read_record_bytes::ReadRecordBytes};
+use anyhow::{bail, ensure, Context as _, Result};
 use aptos_crypto::HashValue;
-use aptos_db::backup::backup_handler::DbState;
+use aptos_db::backup::backup_handler::{DbMetadata, DbState};
+use aptos_infallible::Mutex;
+use aptos_logger::{info, warn};
 use aptos_types::transaction::Version;
+use bytes::{Buf, Bytes, BytesMut};
 use clap::Parser;
-use futures::TryStreamExt;
-use tokio::io::{AsyncRead, AsyncReadExt};
+use futures::{future::join_all, ready, Future, TryStreamExt};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Read,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, ReadBuf},
+    task::JoinHandle,
+};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
+use zstd::dict::DecoderDictionary;
+
+/// Header carrying the id of the zstd dictionary of the backup service.
+const DICTIONARY_ID_HEADER: &str = "x-backup-zstd-dictionary";
+/// Header of the streaming replies telling the version of their framing, see `unframe_records`.
+const FRAMING_HEADER: &str = "x-backup-framing";
+/// Latest framing of streamed records known, asked for with `?framing=`.
+const CHECKED_FRAMING: u32 = 2;
+/// Length of the frame ending a stream with checked framing.
+const END_OF_STREAM: u32 = u32::MAX;
 
 #[derive(Parser)]
 pub struct BackupServiceClientOpt {
@@ -18,69 +44,407 @@ pub struct BackupServiceClientOpt {
         long = "backup-service-address",
         default_value = "http://localhost:6186",
         help = "Backup service address. By default a Aptos Node runs the backup service serving \
-        on tcp port 6186 to localhost only."
+        on tcp port 6186 to localhost only. Can be repeated to specify multiple nodes, in which \
+        case their health and latest version are probed and requests fail over between them."
+    )]
+    pub addresses: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Ask for transactions and epoch ending ledger infos compressed with the zstd \
+        dictionary of the backup service, if it serves one, which cuts the bandwidth of \
+        transaction backups. What's written to the backup storage is the same either way."
+    )]
+    pub zstd_dictionary: bool,
+
+    #[clap(
+        long,
+        help = "Check each stream of records downloaded against the SHA-256 the backup service \
+        computes for it on its side, failing the download on mismatch. Nodes not serving \
+        checksums yet are trusted as is."
     )]
-    pub address: String,
+    pub verify_checksums: bool,
+}
+
+/// Dictionary the records of a node are compressed with.
+struct ZstdDictionary {
+    id: String,
+    decoder: DecoderDictionary<'static>,
+}
+
+type BoxedAsyncRead = Box<dyn AsyncRead + Send + Unpin>;
+
+/// What we know about one of the backup service nodes.
+#[derive(Clone, Copy, Debug, Default)]
+struct SourceStatus {
+    healthy: bool,
+    /// Latest committed version, as reported by the last successful probe.
+    committed_version: Option<Version>,
 }
 
 pub struct BackupServiceClient {
-    address: String,
+    addresses: Vec<String>,
+    status: Mutex<Vec<SourceStatus>>,
+    /// Index of the node tried first.
+    preferred: Mutex<usize>,
     client: reqwest::Client,
+    use_zstd_dictionary: bool,
+    /// Dictionary of each node, `None` if it serves none, fetched on first use.
+    dictionaries: Mutex<HashMap<usize, Option<Arc<ZstdDictionary>>>>,
+    verify_checksums: bool,
 }
 
 impl BackupServiceClient {
     pub fn new_with_opt(opt: BackupServiceClientOpt) -> Self {
-        Self::new(opt.address)
+        let mut client = Self::new_with_addresses(opt.addresses);
+        if opt.zstd_dictionary {
+            client = client.with_zstd_dictionary();
+        }
+        if opt.verify_checksums {
+            client = client.with_checksum_verification();
+        }
+        client
     }
 
     pub fn new(address: String) -> Self {
+        Self::new_with_addresses(vec![address])
+    }
+
+    pub fn new_with_addresses(addresses: Vec<String>) -> Self {
+        assert!(
+            !addresses.is_empty(),
+            "At least one backup service address is required."
+        );
+        let status = vec![
+            SourceStatus {
+                healthy: true,
+                committed_version: None,
+            };
+            addresses.len()
+        ];
         Self {
-            address,
+            addresses,
+            status: Mutex::new(status),
+            preferred: Mutex::new(0),
             client: reqwest::Client::builder()
                 .no_proxy()
                 .build()
                 .expect("Http client should build."),
+            use_zstd_dictionary: false,
+            dictionaries: Mutex::new(HashMap::new()),
+            verify_checksums: false,
+        }
+    }
+
+    /// Asks for transactions and epoch ending ledger infos compressed with the zstd dictionary of
+    /// the nodes serving one. The records read are the same.
+    pub fn with_zstd_dictionary(mut self) -> Self {
+        self.use_zstd_dictionary = true;
+        self
+    }
+
+    /// Checks the streams of records against the checksums the nodes compute for them, see
+    /// `ChecksummedReader`.
+    pub fn with_checksum_verification(mut self) -> Self {
+        self.verify_checksums = true;
+        self
+    }
+
+    async fn get_from(&self, address: &str, path: &str) -> Result<impl AsyncRead> {
+        Ok(into_async_read(
+            self.send(&format!("{}/{}", address, path)).await?,
+        ))
+    }
+
+    async fn send(&self, url: &str) -> Result<reqwest::Response> {
+        let response = self.client.get(url).send().await.err_notes(url)?;
+        if response.status() == reqwest::StatusCode::GONE {
+            let err = match response.json::<VersionPruned>().await {
+                Ok(pruned) => pruned.into(),
+                Err(err) => anyhow::Error::from(err).context("Unexpected 410 reply."),
+            };
+            return Err(err).err_notes(url);
         }
+        response.error_for_status().err_notes(url)
     }
 
-    async fn get(&self, path: &str) -> Result<impl AsyncRead> {
-        let url = format!("{}/{}", self.address, path);
-        Ok(self
-            .client
-            .get(&url)
-            .send()
+    /// Order in which the nodes are tried for a request needing data up to `min_version`: the
+    /// preferred node first, then the healthy ones, then the rest. Nodes known to be behind
+    /// `min_version` go last.
+    fn candidates(&self, min_version: Option<Version>) -> Vec<usize> {
+        let status = self.status.lock().clone();
+        let preferred = *self.preferred.lock();
+        let mut candidates: Vec<usize> = (0..self.addresses.len()).collect();
+        candidates.sort_by_key(|&idx| {
+            let lagging = match (min_version, status[idx].committed_version) {
+                (Some(min_version), Some(committed)) => committed < min_version,
+                _ => false,
+            };
+            (lagging, !status[idx].healthy, idx != preferred)
+        });
+        candidates
+    }
+
+    fn mark(&self, idx: usize, healthy: bool) {
+        self.status.lock()[idx].healthy = healthy;
+        if healthy {
+            *self.preferred.lock() = idx;
+        }
+    }
+
+    async fn get(&self, path: &str, min_version: Option<Version>) -> Result<impl AsyncRead> {
+        self.get_with(min_version, |idx| self.get_from(&self.addresses[idx], path))
             .await
-            .err_notes(&url)?
-            .error_for_status()
-            .err_notes(&url)?
-            .bytes_stream()
-            .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
-            .into_async_read()
-            .compat())
     }
 
-    pub async fn get_db_state(&self) -> Result<Option<DbState>> {
+    /// Like `get`, for a stream of size prefixed records. The records are checked against their
+    /// CRC and sequence number if the node frames them so, see `unframe_records`, and compressed
+    /// with the dictionary of the node if `compressible` and asked for.
+    async fn get_records(
+        &self,
+        path: &str,
+        min_version: Option<Version>,
+        compressible: bool,
+    ) -> Result<BoxedAsyncRead> {
+        self.get_with(min_version, |idx| {
+            self.get_records_from(idx, path, compressible)
+        })
+        .await
+    }
+
+    async fn get_records_from(
+        &self,
+        idx: usize,
+        path: &str,
+        compressible: bool,
+    ) -> Result<BoxedAsyncRead> {
+        let dictionary = if compressible {
+            self.dictionary(idx).await?
+        } else {
+            None
+        };
+        let mut query = format!("framing={}", CHECKED_FRAMING);
+        if let Some(dictionary) = &dictionary {
+            query = format!("{}&zstd_dictionary={}", query, dictionary.id);
+        }
+        let url = format!("{}/{}?{}", self.addresses[idx], path, query);
+        let response = match self.send(&url).await {
+            Ok(response) => response,
+            Err(err) => {
+                if dictionary.is_some() {
+                    // E.g. the node was given another dictionary, to be fetched again.
+                    self.dictionaries.lock().remove(&idx);
+                }
+                return Err(err);
+            },
+        };
+        // Nodes predating the framing don't say, and send size prefixed records.
+        let checked = response
+            .headers()
+            .get(FRAMING_HEADER)
+            .and_then(|version| version.to_str().ok())
+            .map_or(false, |version| version == CHECKED_FRAMING.to_string());
+
+        let mut reader: BoxedAsyncRead = Box::new(into_async_read(response));
+        if self.verify_checksums {
+            // Computed by the node while the stream downloads.
+            let checksum_url = format!("{}/checksum/{}?{}", self.addresses[idx], path, query);
+            let expected = tokio::spawn(get_checksum(self.client.clone(), checksum_url));
+            reader = Box::new(ChecksummedReader::new(reader, url, expected));
+        }
+        if checked {
+            reader = Box::new(unframe_records(reader));
+        }
+        if let Some(dictionary) = dictionary {
+            reader = Box::new(decompress_records(reader, dictionary));
+        }
+        Ok(reader)
+    }
+
+    /// The dictionary of the node, if asked for and it serves one.
+    async fn dictionary(&self, idx: usize) -> Result<Option<Arc<ZstdDictionary>>> {
+        if !self.use_zstd_dictionary {
+            return Ok(None);
+        }
+        if let Some(dictionary) = self.dictionaries.lock().get(&idx) {
+            return Ok(dictionary.clone());
+        }
+
+        let url = format!("{}/zstd_dictionary", self.addresses[idx]);
+        let response = self.client.get(&url).send().await.err_notes(&url)?;
+        let dictionary = if response.status() == reqwest::StatusCode::NOT_FOUND {
+            None
+        } else {
+            let response = response.error_for_status().err_notes(&url)?;
+            let id = response
+                .headers()
+                .get(DICTIONARY_ID_HEADER)
+                .and_then(|id| id.to_str().ok())
+                .context("No dictionary id.")
+                .err_notes(&url)?
+                .to_string();
+            let bytes = response.bytes().await.err_notes(&url)?;
+            info!(
+                address = self.addresses[idx],
+                id = id,
+                "Using zstd dictionary of the backup service."
+            );
+            Some(Arc::new(ZstdDictionary {
+                id,
+                decoder: DecoderDictionary::copy(&bytes),
+            }))
+        };
+        self.dictionaries.lock().insert(idx, dictionary.clone());
+        Ok(dictionary)
+    }
+
+    /// Tries the nodes in turn, see `candidates`, until `request` succeeds with one.
+    async fn get_with<F, Fut, T>(&self, min_version: Option<Version>, request: F) -> Result<T>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_err = None;
+        for idx in self.candidates(min_version) {
+            match request(idx).await {
+                Ok(reader) => {
+                    self.mark(idx, true);
+                    return Ok(reader);
+                },
+                Err(err) => {
+                    if self.addresses.len() > 1 {
+                        warn!(
+                            address = self.addresses[idx],
+                            error = ?err,
+                            "Backup service request failed, trying next source."
+                        );
+                    }
+                    // A node that pruned the versions asked for is healthy still.
+                    if err.downcast_ref::<VersionPruned>().is_none() {
+                        self.mark(idx, false);
+                    }
+                    last_err = Some(err);
+                },
+            }
+        }
+        Err(last_err.expect("Tried at least one source."))
+    }
+
+    async fn probe(&self, idx: usize) -> Result<Option<DbState>> {
         let mut buf = Vec::new();
-        self.get("db_state").await?.read_to_end(&mut buf).await?;
+        self.get_from(&self.addresses[idx], "db_state")
+            .await?
+            .read_to_end(&mut buf)
+            .await?;
         Ok(bcs::from_bytes(&buf)?)
     }
 
+    /// Probes all nodes and returns the state of the most advanced healthy one, which becomes the
+    /// preferred source for subsequent requests.
+    pub async fn get_db_state(&self) -> Result<Option<DbState>> {
+        if self.addresses.len() == 1 {
+            return self.probe(0).await;
+        }
+
+        let results = join_all((0..self.addresses.len()).map(|idx| self.probe(idx))).await;
+        let mut best: Option<(usize, Option<DbState>)> = None;
+        let mut last_err = None;
+        for (idx, res) in results.into_iter().enumerate() {
+            match res {
+                Ok(db_state) => {
+                    {
+                        let mut status = self.status.lock();
+                        status[idx].healthy = true;
+                        status[idx].committed_version =
+                            db_state.as_ref().map(|s| s.committed_version);
+                    }
+                    let version = |s: &Option<DbState>| s.as_ref().map(|s| s.committed_version);
+                    if best
+                        .as_ref()
+                        .map_or(true, |(_, b)| version(&db_state) > version(b))
+                    {
+                        best = Some((idx, db_state));
+                    }
+                },
+                Err(err) => {
+                    warn!(
+                        address = self.addresses[idx],
+                        error = ?err,
+                        "Backup service is unhealthy."
+                    );
+                    self.status.lock()[idx].healthy = false;
+                    last_err = Some(err);
+                },
+            }
+        }
+
+        match best {
+            Some((idx, db_state)) => {
+                info!(
+                    address = self.addresses[idx],
+                    db_state = ?db_state,
+                    "Selected backup service source."
+                );
+                *self.preferred.lock() = idx;
+                Ok(db_state)
+            },
+            None => Err(last_err
+                .expect("Probed at least one source.")
+                .context("No healthy backup service.")),
+        }
+    }
+
+    /// Gets the range of data available on the preferred node, along with its latest ledger info.
+    pub async fn get_db_metadata(&self) -> Result<Option<DbMetadata>> {
+        let mut last_err = None;
+        for idx in self.candidates(None) {
+            let url = format!("{}/db_state", self.addresses[idx]);
+            let res = async {
+                self.client
+                    .get(&url)
+                    .header(reqwest::header::ACCEPT, "application/json")
+                    .send()
+                    .await
+                    .err_notes(&url)?
+                    .error_for_status()
+                    .err_notes(&url)?
+                    .json::<Option<DbMetadata>>()
+                    .await
+                    .err_notes(&url)
+            }
+            .await;
+            match res {
+                Ok(metadata) => return Ok(metadata),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.expect("Tried at least one source."))
+    }
+
     pub async fn get_account_range_proof(
         &self,
         key: HashValue,
         version: Version,
     ) -> Result<impl AsyncRead> {
-        self.get(&format!("state_range_proof/{}/{:x}", version, key))
-            .await
+        self.get(
+            &format!("state_range_proof/{}/{:x}", version, key),
+            Some(version),
+        )
+        .await
     }
 
     pub async fn get_state_snapshot(&self, version: Version) -> Result<impl AsyncRead> {
-        self.get(&format!("state_snapshot/{}", version)).await
+        self.get_records(
+            &format!("state_snapshot/{}", version),
+            Some(version),
+            false, /* compressible */
+        )
+        .await
     }
 
     pub async fn get_state_root_proof(&self, version: Version) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
-        self.get(&format!("state_root_proof/{}", version))
+        self.get(&format!("state_root_proof/{}", version), Some(version))
             .await?
             .read_to_end(&mut buf)
             .await?;
@@ -92,10 +456,11 @@ impl BackupServiceClient {
         start_epoch: u64,
         end_epoch: u64,
     ) -> Result<impl AsyncRead> {
-        self.get(&format!(
-            "epoch_ending_ledger_infos/{}/{}",
-            start_epoch, end_epoch
-        ))
+        self.get_records(
+            &format!("epoch_ending_ledger_infos/{}/{}", start_epoch, end_epoch),
+            None,
+            true, /* compressible */
+        )
         .await
     }
 
@@ -104,10 +469,11 @@ impl BackupServiceClient {
         start_version: Version,
         num_transactions: usize,
     ) -> Result<impl AsyncRead> {
-        self.get(&format!(
-            "transactions/{}/{}",
-            start_version, num_transactions
-        ))
+        self.get_records(
+            &format!("transactions/{}/{}", start_version, num_transactions),
+            Some(start_version + num_transactions as Version - 1),
+            true, /* compressible */
+        )
         .await
     }
 
@@ -116,10 +482,489 @@ impl BackupServiceClient {
         first_version: Version,
         last_version: Version,
     ) -> Result<impl AsyncRead> {
-        self.get(&format!(
-            "transaction_range_proof/{}/{}",
-            first_version, last_version,
-        ))
+        self.get(
+            &format!("transaction_range_proof/{}/{}", first_version, last_version),
+            Some(last_version),
+        )
         .await
     }
 }
+
+/// Body of the 410 replies of the backup service to requests for versions it pruned already,
+/// telling where they may be found instead.
+#[derive(Debug, Deserialize)]
+pub struct VersionPruned {
+    pub endpoint: String,
+    pub version: Version,
+    /// The node serves the data from this version on.
+    pub first_available_version: Version,
+    /// Other backup services or backup buckets the version may be found in, as configured on the
+    /// node.
+    pub hints: Vec<String>,
+}
+
+impl fmt::Display for VersionPruned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Version {} of {} is pruned, served from version {} on.",
+            self.version, self.endpoint, self.first_available_version
+        )?;
+        if !self.hints.is_empty() {
+            write!(f, " It may be found at: {}.", self.hints.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VersionPruned {}
+
+/// Checksum of a stream computed by the backup service, see its `checksum` endpoint.
+#[derive(Debug, Deserialize)]
+struct ServedChecksum {
+    sha256: String,
+    num_bytes: u64,
+}
+
+/// The checksum the node computes for the stream, or `None` if it doesn't serve checksums.
+async fn get_checksum(client: reqwest::Client, url: String) -> Result<Option<ServedChecksum>> {
+    let response = client.get(&url).send().await.err_notes(&url)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        warn!(
+            url = url,
+            "Backup service serves no checksum, not verifying."
+        );
+        return Ok(None);
+    }
+    Ok(Some(
+        response
+            .error_for_status()
+            .err_notes(&url)?
+            .json()
+            .await
+            .err_notes(&url)?,
+    ))
+}
+
+type ChecksumHandle = JoinHandle<Result<Option<ServedChecksum>>>;
+
+/// Hashes the stream as it's read, failing at its end if it doesn't match the checksum computed
+/// by the node, so that a download damaged on the way, e.g. by a caching proxy, is caught without
+/// downloading it a second time.
+struct ChecksummedReader<R> {
+    inner: R,
+    url: String,
+    hasher: Sha256,
+    num_bytes: u64,
+    /// `None` once checked.
+    expected: Option<ChecksumHandle>,
+}
+
+impl<R> ChecksummedReader<R> {
+    fn new(inner: R, url: String, expected: ChecksumHandle) -> Self {
+        Self {
+            inner,
+            url,
+            hasher: Sha256::new(),
+            num_bytes: 0,
+            expected: Some(expected),
+        }
+    }
+
+    fn verify(&mut self, expected: Option<ServedChecksum>) -> Result<()> {
+        let expected = match expected {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        let actual = hex::encode(std::mem::take(&mut self.hasher).finalize());
+        ensure!(
+            self.num_bytes == expected.num_bytes && actual == expected.sha256,
+            "Stream from {} doesn't match its checksum: {} bytes with SHA-256 {}, expected {} \
+            bytes with SHA-256 {}.",
+            self.url,
+            self.num_bytes,
+            actual,
+            expected.num_bytes,
+            expected.sha256,
+        );
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ChecksummedReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[filled..];
+        if !read.is_empty() {
+            this.hasher.update(read);
+            this.num_bytes += read.len() as u64;
+            return Poll::Ready(Ok(()));
+        }
+
+        // End of the stream.
+        let expected = match &mut this.expected {
+            Some(expected) => ready!(Pin::new(expected).poll(cx)),
+            None => return Poll::Ready(Ok(())),
+        };
+        this.expected = None;
+        let result = expected
+            .map_err(anyhow::Error::from)
+            .and_then(|expected| expected)
+            .and_then(|expected| this.verify(expected));
+        Poll::Ready(result.map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e)))
+    }
+}
+
+fn into_async_read(response: reqwest::Response) -> impl AsyncRead + Send + Unpin {
+    response
+        .bytes_stream()
+        .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
+        .into_async_read()
+        .compat()
+}
+
+/// Turns records framed as `len: u32 | seq: u64 | crc32: u32 | record`, and ended by a frame of
+/// length `u32::MAX` carrying their number, back into size prefixed records, failing at the first
+/// record damaged, out of sequence or missing, including at the end.
+fn unframe_records(
+    reader: impl AsyncRead + Send + Unpin + 'static,
+) -> impl AsyncRead + Send + Unpin {
+    let records = futures::stream::try_unfold((reader, 0u64), |(mut reader, seq)| async move {
+        let record = read_framed_record(&mut reader, seq).await;
+        record.map(|record| record.map(|record| (record, (reader, seq + 1))))
+    });
+    Box::pin(records)
+        .map_err(|e: anyhow::Error| futures::io::Error::new(futures::io::ErrorKind::Other, e))
+        .into_async_read()
+        .compat()
+}
+
+/// Reads the `seq`-th record, size prefixed, or `None` at the end of the stream.
+async fn read_framed_record<R: AsyncRead + Send + Unpin>(
+    reader: &mut R,
+    seq: u64,
+) -> Result<Option<Bytes>> {
+    let mut header = BytesMut::with_capacity(16);
+    reader.read_full_buf_or_none(&mut header).await?;
+    if header.is_empty() {
+        bail!(
+            "Stream cut short after {} records, without the end of stream frame.",
+            seq
+        );
+    }
+    let mut header = header.freeze();
+    let len = header.get_u32();
+    let frame_seq = header.get_u64();
+    let crc = header.get_u32();
+    if len == END_OF_STREAM {
+        ensure!(
+            frame_seq == seq,
+            "Stream ended after {} records, but {} were sent.",
+            seq,
+            frame_seq,
+        );
+        return Ok(None);
+    }
+    ensure!(
+        frame_seq == seq,
+        "Record {} of the stream is out of sequence, numbered {}.",
+        seq,
+        frame_seq,
+    );
+
+    let mut record = BytesMut::with_capacity(len as usize);
+    if len > 0 {
+        reader.read_full_buf_or_none(&mut record).await?;
+        ensure!(
+            !record.is_empty(),
+            "Stream cut short in the middle of record {}.",
+            seq
+        );
+    }
+    let actual_crc = crc32fast::hash(&record);
+    ensure!(
+        actual_crc == crc,
+        "Record {} of the stream is corrupted, CRC32 {:08x}, expected {:08x}.",
+        seq,
+        actual_crc,
+        crc,
+    );
+    let mut framed = BytesMut::with_capacity(4 + record.len());
+    framed.extend_from_slice(&len.to_be_bytes());
+    framed.extend_from_slice(&record);
+    Ok(Some(framed.freeze()))
+}
+
+/// Decompresses each of the size prefixed records, keeping the framing.
+fn decompress_records(
+    reader: impl AsyncRead + Send + Unpin + 'static,
+    dictionary: Arc<ZstdDictionary>,
+) -> impl AsyncRead + Send + Unpin {
+    let records = futures::stream::try_unfold(reader, move |mut reader| {
+        let dictionary = dictionary.clone();
+        async move {
+            let record = read_decompressed_record(&mut reader, &dictionary).await;
+            record.map(|record| record.map(|record| (record, reader)))
+        }
+    });
+    Box::pin(records)
+        .map_err(|e: anyhow::Error| futures::io::Error::new(futures::io::ErrorKind::Other, e))
+        .into_async_read()
+        .compat()
+}
+
+async fn read_decompressed_record<R: AsyncRead + Send + Unpin>(
+    reader: &mut R,
+    dictionary: &ZstdDictionary,
+) -> Result<Option<Bytes>> {
+    let compressed = match reader.read_record_bytes().await? {
+        Some(compressed) => compressed,
+        None => return Ok(None),
+    };
+    let mut record = Vec::new();
+    zstd::stream::read::Decoder::with_prepared_dictionary(
+        compressed.as_ref(),
+        &dictionary.decoder,
+    )?
+    .read_to_end(&mut record)?;
+    let mut framed = (record.len() as u32).to_be_bytes().to_vec();
+    framed.append(&mut record);
+    Ok(Some(Bytes::from(framed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unframe_records, BackupServiceClient, ChecksummedReader, ServedChecksum};
+    use crate::utils::test_utils::{start_local_backup_service, tmp_db_with_random_content};
+    use aptos_backup_service::start_backup_service_with_config;
+    use aptos_config::{config::BackupServiceConfig, utils::get_available_port};
+    use aptos_temppath::TempPath;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_fail_over() {
+        let (_db_dir, db, _blocks) = tmp_db_with_random_content();
+        let (rt, port) = start_local_backup_service(db);
+        // Nothing listens on the first address.
+        let client = BackupServiceClient::new_with_addresses(vec![
+            format!("http://localhost:{}", get_available_port()),
+            format!("http://localhost:{}", port),
+        ]);
+
+        rt.block_on(async {
+            let db_state = client.get_db_state().await.unwrap().unwrap();
+            assert_eq!(*client.preferred.lock(), 1);
+            assert!(!client.status.lock()[0].healthy);
+
+            // Requests go to the healthy node, and fail over once the preferred one is reset.
+            *client.preferred.lock() = 0;
+            client.status.lock()[0].healthy = true;
+            let mut buf = Vec::new();
+            client
+                .get_transactions(0, db_state.committed_version as usize + 1)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert!(!buf.is_empty());
+            assert_eq!(*client.preferred.lock(), 1);
+
+            let metadata = client.get_db_metadata().await.unwrap().unwrap();
+            assert_eq!(metadata.last_version, db_state.committed_version);
+            assert_eq!(metadata.epoch, db_state.epoch);
+            assert_eq!(metadata.first_version, 0);
+        });
+    }
+
+    #[test]
+    fn test_zstd_dictionary() {
+        let (_db_dir, db, _blocks) = tmp_db_with_random_content();
+        let num_transactions = db
+            .get_backup_handler()
+            .get_db_state()
+            .unwrap()
+            .unwrap()
+            .committed_version as usize
+            + 1;
+        // Too few transactions to train a dictionary on, make one of their content.
+        let dictionary_file = TempPath::new();
+        let dictionary: Vec<u8> = db
+            .get_backup_handler()
+            .get_transaction_iter(0, num_transactions)
+            .unwrap()
+            .take(10)
+            .flat_map(|record| bcs::to_bytes(&record.unwrap()).unwrap())
+            .collect();
+        std::fs::write(dictionary_file.path(), dictionary).unwrap();
+        let port = get_available_port();
+        let rt = start_backup_service_with_config(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            db,
+            BackupServiceConfig {
+                zstd_dictionary_path: Some(dictionary_file.path().to_path_buf()),
+                ..Default::default()
+            },
+        );
+        let address = format!("http://localhost:{}", port);
+        let plain_client = BackupServiceClient::new(address.clone());
+        let client = BackupServiceClient::new(address).with_zstd_dictionary();
+
+        rt.block_on(async {
+            let (mut plain, mut decompressed) = (Vec::new(), Vec::new());
+            plain_client
+                .get_transactions(0, num_transactions)
+                .await
+                .unwrap()
+                .read_to_end(&mut plain)
+                .await
+                .unwrap();
+            client
+                .get_transactions(0, num_transactions)
+                .await
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .await
+                .unwrap();
+            assert!(client.dictionaries.lock()[&0].is_some());
+            assert_eq!(decompressed, plain);
+        });
+    }
+
+    #[test]
+    fn test_verify_checksums() {
+        let (_db_dir, db, _blocks) = tmp_db_with_random_content();
+        let (rt, port) = start_local_backup_service(db);
+        let client = BackupServiceClient::new(format!("http://localhost:{}", port))
+            .with_checksum_verification();
+
+        rt.block_on(async {
+            let db_state = client.get_db_state().await.unwrap().unwrap();
+            let mut buf = Vec::new();
+            client
+                .get_transactions(0, db_state.committed_version as usize + 1)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert!(!buf.is_empty());
+            client
+                .get_state_snapshot(db_state.committed_version)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+
+            // What doesn't match its checksum fails to read.
+            let expected = tokio::spawn(async {
+                Ok(Some(ServedChecksum {
+                    sha256: hex::encode([0; 32]),
+                    num_bytes: 3,
+                }))
+            });
+            let err = ChecksummedReader::new(&b"abc"[..], "url".to_string(), expected)
+                .read_to_end(&mut Vec::new())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("doesn't match its checksum"));
+        });
+    }
+
+    fn frame(seq: u64, record: &[u8]) -> Vec<u8> {
+        let mut framed = (record.len() as u32).to_be_bytes().to_vec();
+        framed.extend_from_slice(&seq.to_be_bytes());
+        framed.extend_from_slice(&crc32fast::hash(record).to_be_bytes());
+        framed.extend_from_slice(record);
+        framed
+    }
+
+    fn end_of_stream(num_records: u64) -> Vec<u8> {
+        let mut framed = u32::MAX.to_be_bytes().to_vec();
+        framed.extend_from_slice(&num_records.to_be_bytes());
+        framed.extend_from_slice(&[0; 4]);
+        framed
+    }
+
+    async fn unframe(stream: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        let mut unframed = Vec::new();
+        unframe_records(std::io::Cursor::new(stream))
+            .read_to_end(&mut unframed)
+            .await?;
+        Ok(unframed)
+    }
+
+    #[tokio::test]
+    async fn test_unframe_records() {
+        let stream = [frame(0, b"abc"), frame(1, b""), end_of_stream(2)].concat();
+        assert_eq!(
+            unframe(stream.clone()).await.unwrap(),
+            [&[0, 0, 0, 3, b'a', b'b', b'c'][..], &[0, 0, 0, 0]].concat()
+        );
+
+        // Flipped bit.
+        let mut corrupted = stream.clone();
+        corrupted[17] ^= 1;
+        assert!(unframe(corrupted)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("Record 0 of the stream is corrupted"));
+        // Cut at a record boundary.
+        let truncated = frame(0, b"abc");
+        assert!(unframe(truncated)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("cut short after 1 records"));
+        // Missing record.
+        let skipped = [frame(0, b"abc"), frame(2, b"d"), end_of_stream(3)].concat();
+        assert!(unframe(skipped)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("Record 1 of the stream is out of sequence"));
+    }
+
+    #[test]
+    fn test_checked_framing() {
+        let (_db_dir, db, _blocks) = tmp_db_with_random_content();
+        let num_transactions = db
+            .get_backup_handler()
+            .get_db_state()
+            .unwrap()
+            .unwrap()
+            .committed_version as usize
+            + 1;
+        let (rt, port) = start_local_backup_service(db);
+        let client = BackupServiceClient::new(format!("http://localhost:{}", port));
+
+        rt.block_on(async {
+            let (mut unframed, mut size_prefixed) = (Vec::new(), Vec::new());
+            client
+                .get_transactions(0, num_transactions)
+                .await
+                .unwrap()
+                .read_to_end(&mut unframed)
+                .await
+                .unwrap();
+            // What a client predating the framing gets.
+            client
+                .get(&format!("transactions/0/{}", num_transactions), None)
+                .await
+                .unwrap()
+                .read_to_end(&mut size_prefixed)
+                .await
+                .unwrap();
+            assert_eq!(unframed, size_prefixed);
+        });
+    }
+}
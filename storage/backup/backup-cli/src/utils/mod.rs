@@ -2,6 +2,7 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod alert_hooks;
 pub mod backup_service_client;
 pub(crate) mod error_notes;
 pub mod read_record_bytes;
@@ -11,9 +12,9 @@ pub(crate) mod stream;
 #[cfg(test)]
 pub mod test_utils;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use aptos_config::config::{
-    RocksdbConfig, RocksdbConfigs, BUFFERED_STATE_TARGET_ITEMS,
+    RocksdbConfig, RocksdbConfigs, StorageConfig, BUFFERED_STATE_TARGET_ITEMS,
     DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD, NO_OP_STORAGE_PRUNER_CONFIG,
 };
 use aptos_crypto::HashValue;
@@ -22,12 +23,14 @@ use aptos_db::{
     state_restore::{
         StateSnapshotProgress, StateSnapshotRestore, StateValueBatch, StateValueWriter,
     },
-    AptosDB, GetRestoreHandler,
+    AptosDB, GetRestoreHandler, LEDGER_DB_NAME, STATE_KV_DB_NAME,
 };
 use aptos_infallible::duration_since_epoch;
 use aptos_jellyfish_merkle::{NodeBatch, TreeWriter};
 use aptos_logger::info;
 use aptos_types::{
+    chain_id::ChainId,
+    ledger_info::LedgerInfoWithSignatures,
     state_store::{
         state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
     },
@@ -53,6 +56,13 @@ pub struct GlobalBackupOpt {
         help = "Maximum chunk file size in bytes."
     )]
     pub max_chunk_size: usize,
+    #[clap(
+        long,
+        help = "Chain ID of the backed up network, attached to the uploaded files as the \
+        `chain_id` tag, next to the backup type, its range and the tool version, in storages \
+        that support tags."
+    )]
+    pub chain_id: Option<ChainId>,
 }
 
 #[derive(Clone, Parser)]
@@ -65,7 +75,11 @@ pub struct RocksdbOpt {
     state_merkle_db_max_open_files: i32,
     #[clap(long, hidden(true), default_value = "1073741824")] // 1GB
     state_merkle_db_max_total_wal_size: u64,
-    #[clap(long, hidden(true))]
+    #[clap(
+        long,
+        help = "Keep state values in their own DB (state_kv_db), as nodes with \
+        `storage.rocksdb_configs.use_state_kv_db` expect."
+    )]
     use_state_kv_db: bool,
     #[clap(long, hidden(true), default_value = "5000")]
     state_kv_db_max_open_files: i32,
@@ -77,6 +91,15 @@ pub struct RocksdbOpt {
     index_db_max_total_wal_size: u64,
     #[clap(long, hidden(true), default_value = "16")]
     max_background_jobs: i32,
+    #[clap(
+        long,
+        parse(from_os_str),
+        help = "Restore state snapshots by building SST files in this directory and ingesting \
+        them into the DB, rather than through the normal write path, which is much faster for \
+        large states. Best on the file system of the DB, for the files to be moved rather than \
+        copied."
+    )]
+    state_snapshot_sst_dir: Option<PathBuf>,
 }
 
 impl From<RocksdbOpt> for RocksdbConfigs {
@@ -143,6 +166,9 @@ pub struct GlobalRestoreOpt {
     #[clap(flatten)]
     pub rocksdb_opt: RocksdbOpt,
 
+    #[clap(flatten)]
+    pub target_db: TargetDbOpt,
+
     #[clap(flatten)]
     pub concurrent_downloads: ConcurrentDownloadsOpt,
 
@@ -150,6 +176,111 @@ pub struct GlobalRestoreOpt {
     pub replay_concurrency_level: ReplayConcurrencyLevelOpt,
 }
 
+/// What the restored DB is for, so that it's laid out the way the node opening it expects.
+#[derive(Clone, Default, Parser)]
+pub struct TargetDbOpt {
+    #[clap(
+        long,
+        parse(from_os_str),
+        help = "Config of the node the restored DB is for. The DB layout, RocksDB options and \
+        indexer are taken from its `storage` section instead of the defaults of this tool, and \
+        options conflicting with it are rejected."
+    )]
+    pub target_node_config: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Build the internal indexer along with the DB, for nodes with \
+        `storage.enable_indexer`. The indexer expects all transactions since genesis."
+    )]
+    pub enable_indexer: bool,
+}
+
+/// How the target DB is opened: its RocksDB configs, including the layout, and whether the
+/// indexer is built along.
+pub struct TargetDbLayout {
+    pub rocksdb_configs: RocksdbConfigs,
+    pub enable_indexer: bool,
+}
+
+impl TargetDbOpt {
+    pub fn layout(&self, rocksdb_opt: RocksdbOpt) -> Result<TargetDbLayout> {
+        let use_state_kv_db = rocksdb_opt.use_state_kv_db;
+        let path = match &self.target_node_config {
+            Some(path) => path,
+            None => {
+                return Ok(TargetDbLayout {
+                    rocksdb_configs: rocksdb_opt.into(),
+                    enable_indexer: self.enable_indexer,
+                })
+            },
+        };
+
+        let storage = load_storage_config(path)?;
+        ensure!(
+            !use_state_kv_db || storage.rocksdb_configs.use_state_kv_db,
+            "--use-state-kv-db is given, but the node config {} doesn't use state_kv_db.",
+            path.display(),
+        );
+        ensure!(
+            !self.enable_indexer || storage.enable_indexer,
+            "--enable-indexer is given, but the node config {} doesn't enable the indexer.",
+            path.display(),
+        );
+        info!(
+            node_config = path.display().to_string(),
+            use_state_kv_db = storage.rocksdb_configs.use_state_kv_db,
+            enable_indexer = storage.enable_indexer,
+            "Restoring for the storage config of the node."
+        );
+        Ok(TargetDbLayout {
+            rocksdb_configs: storage.rocksdb_configs,
+            enable_indexer: storage.enable_indexer,
+        })
+    }
+}
+
+/// The `storage` section of a node config, leaving out the rest, which might refer to keys and
+/// files only present on the node.
+fn load_storage_config(path: &Path) -> Result<StorageConfig> {
+    #[derive(serde::Deserialize)]
+    struct PartialNodeConfig {
+        #[serde(default)]
+        storage: StorageConfig,
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read node config {}", path.display()))?;
+    let config: PartialNodeConfig = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse node config {}", path.display()))?;
+    Ok(config.storage)
+}
+
+/// A restore resumed with another layout than it started with would leave the DB half in each.
+fn ensure_layout_matches(db_dir: &Path, layout: &TargetDbLayout) -> Result<()> {
+    if !db_dir.join(LEDGER_DB_NAME).exists() {
+        return Ok(());
+    }
+    let has_state_kv_db = db_dir.join(STATE_KV_DB_NAME).exists();
+    ensure!(
+        has_state_kv_db == layout.rocksdb_configs.use_state_kv_db,
+        "Target DB {} {} state_kv_db, but the restore is set to {}use it. Restore to an empty \
+        directory, or with the same layout as before.",
+        db_dir.display(),
+        if has_state_kv_db {
+            "has"
+        } else {
+            "doesn't have"
+        },
+        if layout.rocksdb_configs.use_state_kv_db {
+            ""
+        } else {
+            "not "
+        },
+    );
+    Ok(())
+}
+
 pub enum RestoreRunMode {
     Restore { restore_handler: RestoreHandler },
     Verify,
@@ -218,6 +349,20 @@ impl RestoreRunMode {
         }
     }
 
+    /// Writes state values of the snapshot being restored to `version` ahead of the restore.
+    pub fn save_state_values_ahead(
+        &self,
+        version: Version,
+        values: &[(StateKey, StateValue)],
+    ) -> Result<()> {
+        match self {
+            Self::Restore { restore_handler } => {
+                restore_handler.save_state_values_ahead(version, values)
+            },
+            Self::Verify => Ok(()),
+        }
+    }
+
     pub fn finish(&self) {
         match self {
             Self::Restore { restore_handler } => {
@@ -239,6 +384,15 @@ impl RestoreRunMode {
         }
     }
 
+    pub fn get_genesis_ledger_info(&self) -> Result<Option<LedgerInfoWithSignatures>> {
+        match self {
+            RestoreRunMode::Restore { restore_handler } => {
+                restore_handler.get_genesis_ledger_info()
+            },
+            RestoreRunMode::Verify => Ok(None),
+        }
+    }
+
     pub fn get_in_progress_state_snapshot(&self) -> Result<Option<Version>> {
         match self {
             RestoreRunMode::Restore { restore_handler } => {
@@ -266,16 +420,27 @@ impl TryFrom<GlobalRestoreOpt> for GlobalRestoreOptions {
         let concurrent_downloads = opt.concurrent_downloads.get();
         let replay_concurrency_level = opt.replay_concurrency_level.get();
         let run_mode = if let Some(db_dir) = &opt.db_dir {
+            let state_snapshot_sst_dir = opt.rocksdb_opt.state_snapshot_sst_dir.clone();
+            if let Some(sst_dir) = &state_snapshot_sst_dir {
+                std::fs::create_dir_all(sst_dir).with_context(|| {
+                    format!("Failed to create SST directory {}", sst_dir.display())
+                })?;
+            }
+            let layout = opt.target_db.layout(opt.rocksdb_opt)?;
+            ensure_layout_matches(db_dir, &layout)?;
+            // The DB is opened without pruning, the node prunes it per its own config once it
+            // starts.
             let restore_handler = Arc::new(AptosDB::open(
                 db_dir,
                 false,                       /* read_only */
                 NO_OP_STORAGE_PRUNER_CONFIG, /* pruner config */
-                opt.rocksdb_opt.into(),
-                false,
+                layout.rocksdb_configs,
+                layout.enable_indexer,
                 BUFFERED_STATE_TARGET_ITEMS,
                 DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
             )?)
-            .get_restore_handler();
+            .get_restore_handler()
+            .with_state_snapshot_ingestion(state_snapshot_sst_dir);
             RestoreRunMode::Restore { restore_handler }
         } else {
             RestoreRunMode::Verify
@@ -390,3 +555,42 @@ impl<T: AsRef<Path>> PathToString for T {
 pub(crate) fn unix_timestamp_sec() -> i64 {
     duration_since_epoch().as_secs() as i64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ensure_layout_matches, RocksdbOpt, TargetDbOpt};
+    use aptos_db::{LEDGER_DB_NAME, STATE_KV_DB_NAME};
+    use aptos_temppath::TempPath;
+    use clap::Parser;
+
+    #[test]
+    fn test_target_db_layout() {
+        let config = TempPath::new();
+        std::fs::write(
+            config.path(),
+            "base:\n  role: full_node\nstorage:\n  enable_indexer: true\n  rocksdb_configs:\n    use_state_kv_db: true\n",
+        )
+        .unwrap();
+        let target_db = TargetDbOpt {
+            target_node_config: Some(config.path().to_path_buf()),
+            enable_indexer: false,
+        };
+        let layout = target_db.layout(RocksdbOpt::default()).unwrap();
+        assert!(layout.rocksdb_configs.use_state_kv_db);
+        assert!(layout.enable_indexer);
+
+        // The node config doesn't use what is asked for.
+        std::fs::write(config.path(), "storage:\n  enable_indexer: false\n").unwrap();
+        let use_state_kv_db = RocksdbOpt::from_iter(vec!["exe", "--use-state-kv-db"]);
+        assert!(target_db.layout(use_state_kv_db).is_err());
+
+        // A DB restored without state_kv_db can't be resumed with it.
+        let db_dir = TempPath::new();
+        db_dir.create_as_dir().unwrap();
+        assert!(ensure_layout_matches(db_dir.path(), &layout).is_ok());
+        std::fs::create_dir(db_dir.path().join(LEDGER_DB_NAME)).unwrap();
+        assert!(ensure_layout_matches(db_dir.path(), &layout).is_err());
+        std::fs::create_dir(db_dir.path().join(STATE_KV_DB_NAME)).unwrap();
+        assert!(ensure_layout_matches(db_dir.path(), &layout).is_ok());
+    }
+}
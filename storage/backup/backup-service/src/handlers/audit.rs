@@ -0,0 +1,256 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use aptos_config::config::BackupServiceConfig;
+use aptos_infallible::Mutex;
+use aptos_logger::prelude::*;
+use aptos_types::transaction::Version;
+use serde::Serialize;
+use std::{
+    convert::Infallible,
+    fs::{File, OpenOptions},
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use warp::{filters::path::FullPath, Filter};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum AuditStatus {
+    Completed,
+    /// The handler errored, or the stream broke off, e.g. the client went away.
+    Failed,
+    Throttled,
+    /// The versions requested are pruned, see `PrunedVersion`.
+    Pruned,
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp_ms: u128,
+    client_ip: Option<IpAddr>,
+    endpoint: &'static str,
+    path: &'a str,
+    first_version: Option<Version>,
+    last_version: Option<Version>,
+    bytes: u64,
+    duration_ms: u128,
+    status: AuditStatus,
+}
+
+/// Appends JSON lines to a file, rotating it once it grows over the max size.
+pub(super) struct AuditLog {
+    path: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    /// The file being appended to and its size.
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, config: &BackupServiceConfig) -> Self {
+        Self {
+            path,
+            max_file_size: config.audit_log_max_file_size_bytes,
+            max_files: config.audit_log_max_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        if let Err(e) = self.write_impl(record) {
+            warn!("Failed to write backup service audit log: {:#}", e);
+        }
+    }
+
+    fn write_impl(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock();
+        if let Some((_, size)) = file.as_ref() {
+            if *size > 0 && *size + line.len() as u64 > self.max_file_size {
+                *file = None;
+                self.rotate();
+            }
+        }
+        if file.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            let size = opened.metadata()?.len();
+            *file = Some((opened, size));
+        }
+        let (opened, size) = file.as_mut().expect("Opened above.");
+        opened.write_all(&line)?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `audit.log` to `audit.log.1`, `audit.log.1` to `audit.log.2` and so on, dropping
+    /// the oldest.
+    fn rotate(&self) {
+        let rotated = |i: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", i));
+            PathBuf::from(path)
+        };
+        if self.max_files == 0 {
+            let _ = std::fs::remove_file(&self.path);
+            return;
+        }
+        for i in (1..self.max_files).rev() {
+            let from = rotated(i);
+            if from.exists() {
+                let _ = std::fs::rename(&from, rotated(i + 1));
+            }
+        }
+        if let Err(e) = std::fs::rename(&self.path, rotated(1)) {
+            warn!("Failed to rotate backup service audit log: {}", e);
+        }
+    }
+}
+
+/// What's known about a request so far, written to the audit log once it finishes. No-op if
+/// auditing is disabled.
+pub(super) struct RequestAudit {
+    log: Option<Arc<AuditLog>>,
+    client: Option<SocketAddr>,
+    endpoint: &'static str,
+    path: String,
+    versions: Option<(Version, Version)>,
+    start: Instant,
+}
+
+impl RequestAudit {
+    pub fn new(
+        log: Option<Arc<AuditLog>>,
+        client: Option<SocketAddr>,
+        endpoint: &'static str,
+        path: &str,
+    ) -> Self {
+        Self {
+            log,
+            client,
+            endpoint,
+            path: path.to_string(),
+            versions: None,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn with_versions(mut self, first_version: Version, last_version: Version) -> Self {
+        self.versions = Some((first_version, last_version));
+        self
+    }
+
+    pub fn endpoint(&self) -> &'static str {
+        self.endpoint
+    }
+
+    pub fn client(&self) -> Option<SocketAddr> {
+        self.client
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn versions(&self) -> Option<(Version, Version)> {
+        self.versions
+    }
+
+    pub fn finish(mut self, bytes: u64, status: AuditStatus) {
+        self.write(bytes, status);
+        // Nothing left for `drop()` to write.
+        self.log = None;
+    }
+
+    fn write(&self, bytes: u64, status: AuditStatus) {
+        if let Some(log) = &self.log {
+            log.write(&AuditRecord {
+                timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis()),
+                client_ip: self.client.map(|addr| addr.ip()),
+                endpoint: self.endpoint,
+                path: &self.path,
+                first_version: self.versions.map(|(first, _)| first),
+                last_version: self.versions.map(|(_, last)| last),
+                bytes,
+                duration_ms: self.start.elapsed().as_millis(),
+                status,
+            });
+        }
+    }
+}
+
+/// A request dropped without finishing, e.g. because the handler errored, is recorded as failed.
+impl Drop for RequestAudit {
+    fn drop(&mut self) {
+        self.write(0, AuditStatus::Failed);
+    }
+}
+
+/// Extracts the `RequestAudit` of a request to the endpoint.
+pub(super) fn request_audit(
+    log: Option<Arc<AuditLog>>,
+    endpoint: &'static str,
+) -> impl Filter<Extract = (RequestAudit,), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::path::full())
+        .map(move |client, path: FullPath| {
+            RequestAudit::new(log.clone(), client, endpoint, path.as_str())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditLog, AuditStatus, RequestAudit};
+    use aptos_config::config::BackupServiceConfig;
+    use aptos_temppath::TempPath;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_audit_log_rotation() {
+        let dir = TempPath::new();
+        dir.create_as_dir().unwrap();
+        let path = dir.path().join("audit.log");
+        let config = BackupServiceConfig {
+            audit_log_max_file_size_bytes: 500,
+            audit_log_max_files: 2,
+            ..Default::default()
+        };
+        let log = Arc::new(AuditLog::new(path.clone(), &config));
+
+        for _ in 0..20 {
+            RequestAudit::new(
+                Some(log.clone()),
+                Some("1.2.3.4:5678".parse().unwrap()),
+                "transactions",
+                "/transactions/100/10",
+            )
+            .with_versions(100, 109)
+            .finish(1234, AuditStatus::Completed);
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let record: serde_json::Value =
+            serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(record["client_ip"], "1.2.3.4");
+        assert_eq!(record["first_version"], 100);
+        assert_eq!(record["last_version"], 109);
+        assert_eq!(record["bytes"], 1234);
+        assert_eq!(record["status"], "completed");
+
+        assert!(std::fs::metadata(&path).unwrap().len() <= 500);
+        assert!(dir.path().join("audit.log.1").exists());
+        assert!(dir.path().join("audit.log.2").exists());
+        assert!(!dir.path().join("audit.log.3").exists());
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checksums of what the endpoints serve.
+//!
+//! `GET checksum/<endpoint>/<parameters>` replies with the SHA-256 of the exact bytes the GET of
+//! the endpoint with the same parameters and query would send, computed by reading the records
+//! without sending them, so that clients can check what they downloaded, and operators spot check
+//! what's cached downstream, without a second transfer.
+
+use crate::handlers::{
+    dictionary::ZstdDictionary,
+    framing::Framing,
+    utils::{unwrap_or_500, Format, RecordEncoder},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use warp::Reply;
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(super) struct Checksum {
+    /// Hex encoded SHA-256 of the bytes.
+    pub sha256: String,
+    pub num_bytes: u64,
+    pub num_records: u64,
+}
+
+#[derive(Default)]
+struct ChecksumWriter {
+    hasher: Sha256,
+    num_bytes: u64,
+}
+
+impl ChecksumWriter {
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+        self.num_bytes += bytes.len() as u64;
+    }
+
+    fn finish(self, num_records: u64) -> Checksum {
+        Checksum {
+            sha256: hex::encode(self.hasher.finalize()),
+            num_bytes: self.num_bytes,
+            num_records,
+        }
+    }
+}
+
+/// Checksum of the stream of the records, as `send_records` sends it.
+pub(super) fn checksum_records<I, R>(
+    iter_res: Result<I>,
+    format: Format,
+    dictionary: Option<&ZstdDictionary>,
+    framing: Framing,
+) -> Result<Checksum>
+where
+    I: Iterator<Item = Result<R>>,
+    R: Serialize,
+{
+    let mut encoder = RecordEncoder::new(format, dictionary, framing)?;
+    let mut writer = ChecksumWriter::default();
+    for record_res in iter_res? {
+        writer.write(&encoder.encode(&record_res?)?);
+    }
+    if let Some(end_of_stream) = encoder.end_of_stream() {
+        writer.write(&end_of_stream);
+    }
+    Ok(writer.finish(encoder.num_records()))
+}
+
+/// Checksum of the record, as `reply_with_record` sends it.
+pub(super) fn checksum_record<R: Serialize>(record: &R, format: Format) -> Result<Checksum> {
+    let mut writer = ChecksumWriter::default();
+    match format {
+        Format::Bcs => writer.write(&bcs::to_bytes(record)?),
+        Format::Json => writer.write(&serde_json::to_vec(record)?),
+    }
+    Ok(writer.finish(1))
+}
+
+/// Replies in JSON with the checksum, computed off the async runtime since it reads the DB.
+pub(super) async fn reply_with_checksum<F>(checksum: F) -> Box<dyn Reply>
+where
+    F: FnOnce() -> Result<Checksum> + Send + 'static,
+{
+    let result = match tokio::task::spawn_blocking(checksum).await {
+        Ok(Ok(checksum)) => Ok(Box::new(warp::reply::json(&checksum)) as Box<dyn Reply>),
+        Ok(Err(e)) => Err(e),
+        Err(e) => Err(e.into()),
+    };
+    unwrap_or_500(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum_record, checksum_records};
+    use crate::handlers::{framing::Framing, utils::Format};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_checksum_records() {
+        let records = vec![1u64, 2, 3];
+        let checksum = checksum_records(
+            Ok(records.iter().map(|record| Ok(*record))),
+            Format::Bcs,
+            None,
+            Framing::Checked,
+        )
+        .unwrap();
+
+        let mut expected = vec![];
+        for (seq, record) in records.iter().enumerate() {
+            expected.extend_from_slice(
+                &Framing::Checked.frame(seq as u64, bcs::to_bytes(record).unwrap()),
+            );
+        }
+        expected.extend_from_slice(&Framing::Checked.end_of_stream(3).unwrap());
+        assert_eq!(checksum.num_records, 3);
+        assert_eq!(checksum.num_bytes, expected.len() as u64);
+        assert_eq!(checksum.sha256, hex::encode(Sha256::digest(&expected)));
+
+        let checksum = checksum_record(&7u64, Format::Bcs).unwrap();
+        assert_eq!(checksum.num_bytes, 8);
+        assert_eq!(
+            checksum.sha256,
+            hex::encode(Sha256::digest(&bcs::to_bytes(&7u64).unwrap()))
+        );
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compressing small records with a pre-trained zstd dictionary.
+//!
+//! Transactions and epoch ending ledger infos are small and look alike, so compressing them one
+//! by one gains little, unless the compressor starts off with what they have in common: a
+//! dictionary, trained on a sample of them. Clients fetch it from `GET zstd_dictionary`, and ask
+//! for records compressed with it with `?zstd_dictionary=<id>`. Each record is then compressed on
+//! its own, keeping the size prefixed framing of the stream.
+
+use anyhow::{bail, ensure, Result};
+use aptos_crypto::HashValue;
+use aptos_db::backup::backup_handler::BackupHandler;
+use aptos_logger::prelude::*;
+use serde::Deserialize;
+use std::{path::Path, sync::Arc};
+use warp::{reject::Reject, Filter, Rejection};
+use zstd::dict::EncoderDictionary;
+
+/// Header carrying the id of the dictionary, in replies compressed with it and along with it.
+pub(super) const DICTIONARY_ID_HEADER: &str = "x-backup-zstd-dictionary";
+
+const COMPRESSION_LEVEL: i32 = 3;
+const MAX_DICTIONARY_SIZE: usize = 112 << 10;
+const NUM_TRAINING_TRANSACTIONS: usize = 10_000;
+const NUM_TRAINING_LEDGER_INFOS: u64 = 1_000;
+/// Too few samples make for a poor dictionary.
+const MIN_TRAINING_SAMPLES: usize = 1_000;
+
+pub(super) struct ZstdDictionary {
+    /// Hex of the first bytes of the hash of the dictionary.
+    pub id: String,
+    pub bytes: Vec<u8>,
+    encoder: EncoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            id: HashValue::sha3_256_of(&bytes).to_hex()[..16].to_string(),
+            encoder: EncoderDictionary::copy(&bytes, COMPRESSION_LEVEL),
+            bytes,
+        }
+    }
+
+    /// Loads the dictionary from `path`, or trains one on the latest transactions and epoch
+    /// ending ledger infos and saves it there, for clients to keep using it across restarts.
+    pub fn load_or_train(path: &Path, backup_handler: &BackupHandler) -> Result<Self> {
+        if path.exists() {
+            return Ok(Self::new(std::fs::read(path)?));
+        }
+
+        let db_state = match backup_handler.get_db_state()? {
+            Some(db_state) => db_state,
+            None => bail!("Nothing to train the dictionary on."),
+        };
+        let num_transactions = (db_state.committed_version + 1) as usize;
+        let start_version = num_transactions.saturating_sub(NUM_TRAINING_TRANSACTIONS);
+        let mut samples = backup_handler
+            .get_transaction_iter(start_version as u64, num_transactions - start_version)?
+            .map(|record| Ok(bcs::to_bytes(&record?)?))
+            .collect::<Result<Vec<_>>>()?;
+        samples.extend(
+            backup_handler
+                .get_epoch_ending_ledger_info_iter(
+                    db_state.epoch.saturating_sub(NUM_TRAINING_LEDGER_INFOS),
+                    db_state.epoch,
+                )?
+                .map(|record| Ok(bcs::to_bytes(&record?)?))
+                .collect::<Result<Vec<_>>>()?,
+        );
+        ensure!(
+            samples.len() >= MIN_TRAINING_SAMPLES,
+            "Only {} records to train the dictionary on, need {}.",
+            samples.len(),
+            MIN_TRAINING_SAMPLES,
+        );
+
+        let bytes = zstd::dict::from_samples(&samples, MAX_DICTIONARY_SIZE)?;
+        std::fs::write(path, &bytes)?;
+        let dictionary = Self::new(bytes);
+        info!(
+            id = dictionary.id,
+            num_samples = samples.len(),
+            path = path.display().to_string(),
+            "Trained zstd dictionary."
+        );
+        Ok(dictionary)
+    }
+
+    pub fn compressor(&self) -> Result<zstd::bulk::Compressor<'_>> {
+        Ok(zstd::bulk::Compressor::with_prepared_dictionary(
+            &self.encoder,
+        )?)
+    }
+}
+
+/// The client asked for a dictionary other than the one served, e.g. one from before the node
+/// was given another. Replied with 409, for the client to fetch the dictionary again.
+#[derive(Debug)]
+pub(super) struct DictionaryMismatch;
+
+impl Reject for DictionaryMismatch {}
+
+#[derive(Deserialize)]
+struct DictionaryQuery {
+    zstd_dictionary: Option<String>,
+}
+
+/// Extracts the dictionary the records are to be compressed with, if the request asks for one
+/// with `?zstd_dictionary=<id>`.
+pub(super) fn dictionary(
+    served: Option<Arc<ZstdDictionary>>,
+) -> impl Filter<Extract = (Option<Arc<ZstdDictionary>>,), Error = Rejection> + Clone {
+    warp::query::<DictionaryQuery>().and_then(move |query: DictionaryQuery| {
+        let served = served.clone();
+        async move {
+            match (query.zstd_dictionary, served) {
+                (None, _) => Ok(None),
+                (Some(id), Some(served)) if id == served.id => Ok(Some(served)),
+                _ => Err(warp::reject::custom(DictionaryMismatch)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZstdDictionary;
+
+    #[test]
+    fn test_compress_with_dictionary() {
+        let samples: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| {
+                format!(
+                    "{{\"sender\":\"0x{:064x}\",\"sequence_number\":{},\"payload\":\"transfer\"}}",
+                    i % 10,
+                    i
+                )
+                .into_bytes()
+            })
+            .collect();
+        let dictionary = ZstdDictionary::new(zstd::dict::from_samples(&samples, 16 << 10).unwrap());
+        assert_eq!(dictionary.id.len(), 16);
+
+        let record = &samples[123];
+        let compressed = dictionary.compressor().unwrap().compress(record).unwrap();
+        let plain = zstd::bulk::compress(record, 3).unwrap();
+        assert!(compressed.len() < plain.len());
+
+        let decompressed = zstd::bulk::Decompressor::with_dictionary(&dictionary.bytes)
+            .unwrap()
+            .decompress(&compressed, record.len())
+            .unwrap();
+        assert_eq!(&decompressed, record);
+    }
+}
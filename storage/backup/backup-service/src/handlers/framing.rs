@@ -0,0 +1,126 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Framing of the records of a BCS stream.
+//!
+//! Records are prefixed with their size (version 1), which says nothing of the records being
+//! damaged or cut short on the way, e.g. by a proxy, until they fail to deserialize or to verify at
+//! restore time. Clients asking for `?framing=2` get each record framed as
+//!
+//!   `len: u32 | seq: u64 | crc32: u32 | record`, all big endian,
+//!
+//! `seq` counting the records from 0 and the CRC32 covering the record as sent, and the stream
+//! ends with a frame of length `u32::MAX` carrying the number of records, so that a stream cut at
+//! a record boundary doesn't pass for a complete one. Clients ask for the latest version they
+//! know, and the reply tells in `x-backup-framing` which version it is framed with, the latest
+//! both know. Replies of services predating this have no such header, and are size prefixed.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::Deserialize;
+use warp::{Filter, Rejection, Reply};
+
+/// Header of the streaming replies telling the version of the framing, unless size prefixed.
+pub(super) const FRAMING_HEADER: &str = "x-backup-framing";
+
+/// Length of the frame ending the stream.
+const END_OF_STREAM: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum Framing {
+    /// Records prefixed with their size, what clients not asking for anything get.
+    SizePrefixed,
+    /// Records framed with their sequence number and CRC32, and the stream ended by a frame
+    /// carrying the number of records.
+    Checked,
+}
+
+impl Framing {
+    /// Latest version known to both, given the latest one the client knows.
+    fn negotiate(client_version: Option<u32>) -> Self {
+        match client_version {
+            Some(version) if version >= 2 => Self::Checked,
+            _ => Self::SizePrefixed,
+        }
+    }
+
+    fn version(&self) -> u32 {
+        match self {
+            Self::SizePrefixed => 1,
+            Self::Checked => 2,
+        }
+    }
+
+    /// Frames the `seq`-th record.
+    pub fn frame(&self, seq: u64, record: Vec<u8>) -> Bytes {
+        let mut framed = BytesMut::with_capacity(record.len() + 16);
+        framed.put_u32(record.len() as u32);
+        if *self == Self::Checked {
+            framed.put_u64(seq);
+            framed.put_u32(crc32fast::hash(&record));
+        }
+        framed.put_slice(&record);
+        framed.freeze()
+    }
+
+    /// What ends a stream of `num_records` records, if anything.
+    pub fn end_of_stream(&self, num_records: u64) -> Option<Bytes> {
+        match self {
+            Self::SizePrefixed => None,
+            Self::Checked => {
+                let mut framed = BytesMut::with_capacity(16);
+                framed.put_u32(END_OF_STREAM);
+                framed.put_u64(num_records);
+                framed.put_u32(0);
+                Some(framed.freeze())
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FramingQuery {
+    framing: Option<u32>,
+}
+
+/// Extracts the framing of the records, given the `?framing=<version>` of the request.
+pub(super) fn framing() -> impl Filter<Extract = (Framing,), Error = Rejection> + Clone {
+    warp::query::<FramingQuery>().map(|query: FramingQuery| Framing::negotiate(query.framing))
+}
+
+/// Tells the client which framing the records are sent with, unless size prefixed.
+pub(super) fn with_framing(reply: Box<dyn Reply>, framing: Framing) -> Box<dyn Reply> {
+    match framing {
+        Framing::SizePrefixed => reply,
+        Framing::Checked => Box::new(warp::reply::with_header(
+            reply,
+            FRAMING_HEADER,
+            framing.version().to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Framing;
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(Framing::negotiate(None), Framing::SizePrefixed);
+        assert_eq!(Framing::negotiate(Some(1)), Framing::SizePrefixed);
+        assert_eq!(Framing::negotiate(Some(2)), Framing::Checked);
+        // Clients knowing of later versions get the latest one known here.
+        assert_eq!(Framing::negotiate(Some(3)), Framing::Checked);
+
+        assert_eq!(Framing::SizePrefixed.frame(7, vec![1, 2]).as_ref(), &[
+            0, 0, 0, 2, 1, 2
+        ]);
+        let framed = Framing::Checked.frame(7, vec![1, 2]);
+        assert_eq!(&framed[..12], &[0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(&framed[12..16], &crc32fast::hash(&[1, 2]).to_be_bytes());
+        assert_eq!(&framed[16..], &[1, 2]);
+        assert!(Framing::SizePrefixed.end_of_stream(1).is_none());
+        assert_eq!(&Framing::Checked.end_of_stream(1).unwrap()[..12], &[
+            255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 1
+        ]);
+    }
+}
@@ -2,40 +2,181 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+mod audit;
+mod checksum;
+mod dictionary;
+mod framing;
+mod pruned;
+pub(crate) mod status;
+mod streams;
 mod utils;
 
-use crate::handlers::utils::{
-    handle_rejection, reply_with_async_channel_writer, reply_with_bcs_bytes,
-    send_size_prefixed_bcs_bytes, unwrap_or_500, LATENCY_HISTOGRAM,
+use crate::handlers::{
+    audit::{request_audit, AuditLog, AuditStatus, RequestAudit},
+    checksum::{checksum_record, checksum_records, reply_with_checksum},
+    dictionary::{dictionary, ZstdDictionary, DICTIONARY_ID_HEADER},
+    framing::{framing, with_framing, Framing},
+    pruned::{PrunableData, PruningCheck},
+    status::BackupServiceStatus,
+    streams::{admin, StreamRegistry},
+    utils::{
+        estimate_stream, format, handle_rejection, reply_with_async_channel_writer,
+        reply_with_bcs_bytes, reply_with_estimate, reply_with_json, reply_with_record,
+        send_records, unwrap_or_500, Format, StreamLimiter, LATENCY_HISTOGRAM,
+    },
 };
+use aptos_config::config::BackupServiceConfig;
 use aptos_crypto::hash::HashValue;
 use aptos_db::backup::backup_handler::BackupHandler;
+use aptos_logger::prelude::*;
 use aptos_types::transaction::Version;
-use warp::{filters::BoxedFilter, reply::Reply, Filter};
+use std::{cmp::min, sync::Arc, time::Duration};
+use warp::{filters::BoxedFilter, http::StatusCode, reply::Reply, Filter, Rejection};
 
 static DB_STATE: &str = "db_state";
+static STATUS: &str = "status";
 static STATE_RANGE_PROOF: &str = "state_range_proof";
 static STATE_SNAPSHOT: &str = "state_snapshot";
 static STATE_ROOT_PROOF: &str = "state_root_proof";
 static EPOCH_ENDING_LEDGER_INFOS: &str = "epoch_ending_ledger_infos";
 static TRANSACTIONS: &str = "transactions";
 static TRANSACTION_RANGE_PROOF: &str = "transaction_range_proof";
+static ZSTD_DICTIONARY: &str = "zstd_dictionary";
+static CHECKSUM: &str = "checksum";
+
+/// Tells the client which dictionary the records are compressed with, if any.
+fn with_dictionary_id(
+    reply: Box<dyn Reply>,
+    dictionary: Option<&ZstdDictionary>,
+) -> Box<dyn Reply> {
+    match dictionary {
+        Some(dictionary) => Box::new(warp::reply::with_header(
+            reply,
+            DICTIONARY_ID_HEADER,
+            dictionary.id.clone(),
+        )),
+        None => reply,
+    }
+}
+
+/// Records rendered in JSON are one per line, whatever framing was asked for.
+fn bcs_framing(format: Format, framing: Framing) -> Framing {
+    match format {
+        Format::Bcs => framing,
+        Format::Json => Framing::SizePrefixed,
+    }
+}
+
+/// Matches the endpoint, unless disabled in the config, in which case it's as if it didn't exist.
+fn endpoint(name: &'static str, enabled: bool) -> BoxedFilter<()> {
+    warp::path(name)
+        .and_then(move || async move {
+            if enabled {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+pub(crate) fn get_routes(
+    backup_handler: BackupHandler,
+    config: BackupServiceConfig,
+) -> BoxedFilter<(impl Reply,)> {
+    let streams = Arc::new(StreamRegistry::default());
+    let audit_log = config
+        .audit_log_path
+        .clone()
+        .map(|path| Arc::new(AuditLog::new(path, &config)));
+    let zstd_dictionary = config.zstd_dictionary_path.as_ref().and_then(|path| {
+        ZstdDictionary::load_or_train(path, &backup_handler)
+            .map_err(|e| {
+                warn!(
+                    "Not serving records compressed with a zstd dictionary: {:#}",
+                    e
+                )
+            })
+            .ok()
+            .map(Arc::new)
+    });
+
+    let max_records_per_chunk = config.max_records_per_chunk;
+    let pruning = PruningCheck::new(backup_handler.clone(), config.pruned_data_hints.clone());
 
-pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Reply,)> {
     // GET db_state
+    // With "Accept: application/json", replies with the full `DbMetadata` in JSON instead.
     let bh = backup_handler.clone();
     let db_state = warp::path::end()
-        .map(move || reply_with_bcs_bytes(DB_STATE, &bh.get_db_state()?))
+        .and(warp::header::optional::<String>("accept"))
+        .and(request_audit(audit_log.clone(), DB_STATE))
+        .map(move |accept: Option<String>, audit| {
+            if accept.map_or(false, |accept| accept.contains("application/json")) {
+                reply_with_json(DB_STATE, &bh.get_db_metadata()?, audit)
+            } else {
+                reply_with_bcs_bytes(DB_STATE, &bh.get_db_state()?, audit)
+            }
+        })
         .map(unwrap_or_500)
         .recover(handle_rejection);
 
+    // GET status
+    // Replies in JSON with the endpoints served and the versions they serve, see
+    // `BackupServiceStatus`. Served whatever endpoints are enabled, for nodes to be discoverable.
+    let bh = backup_handler.clone();
+    let endpoints = config.endpoints;
+    let status = warp::path!("status")
+        .and(request_audit(audit_log.clone(), STATUS))
+        .map(move |audit| {
+            reply_with_json(
+                STATUS,
+                &BackupServiceStatus::new(endpoints, bh.get_db_metadata()?),
+                audit,
+            )
+        })
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // GET zstd_dictionary
+    // The dictionary transactions and epoch ending ledger infos are compressed with given
+    // `?zstd_dictionary=<id>`, with its id in `x-backup-zstd-dictionary`. 404 if there is none.
+    let served_dictionary = zstd_dictionary.clone();
+    let zstd_dictionary_route = warp::path::end()
+        .map(move || -> Box<dyn Reply> {
+            match &served_dictionary {
+                Some(dictionary) => Box::new(warp::reply::with_header(
+                    dictionary.bytes.clone(),
+                    DICTIONARY_ID_HEADER,
+                    dictionary.id.clone(),
+                )),
+                None => Box::new(StatusCode::NOT_FOUND),
+            }
+        })
+        .recover(handle_rejection);
+
+    // Below, endpoints serving records and proofs render them in JSON given `?format=json`, see
+    // `Format`, and reply 410 to requests for versions pruned already, see `PrunedVersion`.
+
     // GET state_range_proof/<version>/<end_key>
     let bh = backup_handler.clone();
+    let pruning_check = pruning.clone();
     let state_range_proof = warp::path!(Version / HashValue)
-        .map(move |version, end_key| {
-            reply_with_bcs_bytes(
+        .and(format())
+        .and(request_audit(audit_log.clone(), STATE_RANGE_PROOF))
+        .map(move |version, end_key, format, audit: RequestAudit| {
+            let audit = audit.with_versions(version, version);
+            if let Some(reply) =
+                pruning_check.reply_if_pruned(STATE_RANGE_PROOF, PrunableData::State, version)?
+            {
+                audit.finish(0, AuditStatus::Pruned);
+                return Ok(reply);
+            }
+            reply_with_record(
                 STATE_RANGE_PROOF,
                 &bh.get_account_state_range_proof(end_key, version)?,
+                format,
+                audit,
             )
         })
         .map(unwrap_or_500)
@@ -43,84 +184,514 @@ pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Rep
 
     // GET state_snapshot/<version>
     let bh = backup_handler.clone();
+    let registry = streams.clone();
+    let limiter = StreamLimiter::new(
+        STATE_SNAPSHOT,
+        config.max_concurrent_state_snapshot_streams,
+        Duration::from_millis(config.state_snapshot_queue_timeout_ms),
+        config.retry_after_secs,
+    );
+    let snapshot_limiter = limiter.clone();
+    let pruning_check = pruning.clone();
     let state_snapshot = warp::path!(Version)
-        .map(move |version| {
-            reply_with_async_channel_writer(&bh, STATE_SNAPSHOT, |bh, sender| {
-                send_size_prefixed_bcs_bytes(bh.get_account_iter(version), sender)
-            })
-        })
+        .and(format())
+        .and(framing())
+        .and(request_audit(audit_log.clone(), STATE_SNAPSHOT))
+        .and_then(
+            move |version, format: Format, framing, audit: RequestAudit| {
+                let bh = bh.clone();
+                let limiter = snapshot_limiter.clone();
+                let registry = registry.clone();
+                let pruning_check = pruning_check.clone();
+                let framing = bcs_framing(format, framing);
+                async move {
+                    let audit = audit.with_versions(version, version);
+                    match pruning_check.reply_if_pruned(
+                        STATE_SNAPSHOT,
+                        PrunableData::State,
+                        version,
+                    ) {
+                        Ok(None) => (),
+                        Ok(Some(reply)) => {
+                            audit.finish(0, AuditStatus::Pruned);
+                            return Ok::<_, Rejection>(reply);
+                        },
+                        Err(e) => return Ok(unwrap_or_500(Err(e))),
+                    }
+                    let permit = match limiter.acquire().await {
+                        Ok(permit) => permit,
+                        Err(reply) => {
+                            audit.finish(0, AuditStatus::Throttled);
+                            return Ok(reply);
+                        },
+                    };
+                    let reply = reply_with_async_channel_writer(
+                        &bh,
+                        STATE_SNAPSHOT,
+                        audit,
+                        &registry,
+                        |bh, sender| async move {
+                            send_records(
+                                bh.get_account_iter(version),
+                                format,
+                                None,
+                                framing,
+                                max_records_per_chunk,
+                                sender,
+                            )
+                            .await;
+                            // Hold the slot until the whole snapshot is sent.
+                            drop(permit);
+                        },
+                    );
+                    Ok(with_framing(reply, framing))
+                }
+            },
+        )
         .recover(handle_rejection);
 
     // GET state_root_proof/<version>
+    // The proof is made of the transaction info at the version, pruned along with transactions.
     let bh = backup_handler.clone();
+    let pruning_check = pruning.clone();
     let state_root_proof = warp::path!(Version)
-        .map(move |version| {
-            reply_with_bcs_bytes(STATE_ROOT_PROOF, &bh.get_state_root_proof(version)?)
+        .and(format())
+        .and(request_audit(audit_log.clone(), STATE_ROOT_PROOF))
+        .map(move |version, format, audit: RequestAudit| {
+            let audit = audit.with_versions(version, version);
+            if let Some(reply) = pruning_check.reply_if_pruned(
+                STATE_ROOT_PROOF,
+                PrunableData::Transactions,
+                version,
+            )? {
+                audit.finish(0, AuditStatus::Pruned);
+                return Ok(reply);
+            }
+            reply_with_record(
+                STATE_ROOT_PROOF,
+                &bh.get_state_root_proof(version)?,
+                format,
+                audit,
+            )
         })
         .map(unwrap_or_500)
         .recover(handle_rejection);
 
     // GET epoch_ending_ledger_infos/<start_epoch>/<end_epoch>/
     let bh = backup_handler.clone();
+    let registry = streams.clone();
     let epoch_ending_ledger_infos = warp::path!(u64 / u64)
-        .map(move |start_epoch, end_epoch| {
-            // use async move block to group `bh` and the iterator into the same lifetime, since the
-            // latter references the former.
-            reply_with_async_channel_writer(
-                &bh,
-                EPOCH_ENDING_LEDGER_INFOS,
-                |bh, sender| async move {
-                    send_size_prefixed_bcs_bytes(
-                        bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch),
-                        sender,
-                    )
-                    .await
-                },
-            )
-        })
+        .and(format())
+        .and(dictionary(zstd_dictionary.clone()))
+        .and(framing())
+        .and(request_audit(audit_log.clone(), EPOCH_ENDING_LEDGER_INFOS))
+        .map(
+            move |start_epoch,
+                  end_epoch,
+                  format,
+                  dictionary: Option<Arc<ZstdDictionary>>,
+                  framing,
+                  audit| {
+                let dictionary_id = dictionary.clone();
+                let framing = bcs_framing(format, framing);
+                // use async move block to group `bh` and the iterator into the same lifetime, since the
+                // latter references the former.
+                let reply = reply_with_async_channel_writer(
+                    &bh,
+                    EPOCH_ENDING_LEDGER_INFOS,
+                    audit,
+                    &registry,
+                    |bh, sender| async move {
+                        send_records(
+                            bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch),
+                            format,
+                            dictionary,
+                            framing,
+                            max_records_per_chunk,
+                            sender,
+                        )
+                        .await
+                    },
+                );
+                with_framing(with_dictionary_id(reply, dictionary_id.as_deref()), framing)
+            },
+        )
         .recover(handle_rejection);
 
     // GET transactions/<start_version>/<num_transactions>
     let bh = backup_handler.clone();
+    let registry = streams.clone();
+    let pruning_check = pruning.clone();
     let transactions = warp::path!(Version / usize)
-        .map(move |start_version, num_transactions| {
-            // use async move block to group `bh` and the iterator into the same lifetime, since the
-            // latter references the former.
-            reply_with_async_channel_writer(&bh, TRANSACTIONS, |bh, sender| async move {
-                send_size_prefixed_bcs_bytes(
+        .and(format())
+        .and(dictionary(zstd_dictionary.clone()))
+        .and(framing())
+        .and(request_audit(audit_log.clone(), TRANSACTIONS))
+        .map(
+            move |start_version: Version,
+                  num_transactions,
+                  format,
+                  dictionary: Option<Arc<ZstdDictionary>>,
+                  framing,
+                  audit: RequestAudit|
+                  -> anyhow::Result<Box<dyn Reply>> {
+                let framing = bcs_framing(format, framing);
+                let audit = audit.with_versions(
+                    start_version,
+                    start_version.saturating_add((num_transactions as u64).saturating_sub(1)),
+                );
+                if let Some(reply) = pruning_check.reply_if_pruned(
+                    TRANSACTIONS,
+                    PrunableData::Transactions,
+                    start_version,
+                )? {
+                    audit.finish(0, AuditStatus::Pruned);
+                    return Ok(reply);
+                }
+                let dictionary_id = dictionary.clone();
+                // use async move block to group `bh` and the iterator into the same lifetime, since the
+                // latter references the former.
+                let reply = reply_with_async_channel_writer(
+                    &bh,
+                    TRANSACTIONS,
+                    audit,
+                    &registry,
+                    |bh, sender| async move {
+                        send_records(
+                            bh.get_transaction_iter(start_version, num_transactions),
+                            format,
+                            dictionary,
+                            framing,
+                            max_records_per_chunk,
+                            sender,
+                        )
+                        .await
+                    },
+                );
+                Ok(with_framing(
+                    with_dictionary_id(reply, dictionary_id.as_deref()),
+                    framing,
+                ))
+            },
+        )
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // HEAD on the streaming endpoints replies with what the GET would send uncompressed, measured
+    // on its first records: the number of records in `x-backup-record-count` and the size in
+    // `Content-Length`, for clients to plan disk space and show progress. `x-backup-size-exact`
+    // tells whether the size is extrapolated.
+
+    // HEAD state_snapshot/<version>
+    // Without estimate if the DB doesn't track the number of state items.
+    let bh = backup_handler.clone();
+    let state_snapshot_head = warp::path!(Version)
+        .and(format())
+        .map(
+            move |version: Version, format: Format| -> anyhow::Result<Box<dyn Reply>> {
+                let estimate = match bh.get_state_item_count(version)? {
+                    Some(num_items) => Some(estimate_stream(
+                        bh.get_account_iter(version),
+                        num_items as u64,
+                        format,
+                    )?),
+                    None => None,
+                };
+                Ok(reply_with_estimate(estimate))
+            },
+        )
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // HEAD epoch_ending_ledger_infos/<start_epoch>/<end_epoch>/
+    let bh = backup_handler.clone();
+    let epoch_ending_ledger_infos_head = warp::path!(u64 / u64)
+        .and(format())
+        .map(
+            move |start_epoch: u64,
+                  end_epoch: u64,
+                  format: Format|
+                  -> anyhow::Result<Box<dyn Reply>> {
+                // Epochs before the current one have ended.
+                let current_epoch = bh.get_db_state()?.map_or(0, |state| state.epoch);
+                let num_records = min(end_epoch, current_epoch).saturating_sub(start_epoch);
+                Ok(reply_with_estimate(Some(estimate_stream(
+                    bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch),
+                    num_records,
+                    format,
+                )?)))
+            },
+        )
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // HEAD transactions/<start_version>/<num_transactions>
+    let bh = backup_handler.clone();
+    let transactions_head = warp::path!(Version / usize)
+        .and(format())
+        .map(
+            move |start_version: Version,
+                  num_transactions: usize,
+                  format: Format|
+                  -> anyhow::Result<Box<dyn Reply>> {
+                let num_committed = bh
+                    .get_db_state()?
+                    .map_or(0, |state| state.committed_version + 1);
+                let num_records = min(
+                    num_transactions as u64,
+                    num_committed.saturating_sub(start_version),
+                );
+                Ok(reply_with_estimate(Some(estimate_stream(
                     bh.get_transaction_iter(start_version, num_transactions),
-                    sender,
-                )
-                .await
-            })
+                    num_records,
+                    format,
+                )?)))
+            },
+        )
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // GET checksum/<endpoint>/<parameters>
+    // Replies in JSON with the SHA-256 of the bytes the GET of the endpoint with the same
+    // parameters and query would send, see `Checksum`. Checksums of state snapshots take up one of
+    // their streams while computed.
+
+    // GET checksum/state_snapshot/<version>
+    let bh = backup_handler.clone();
+    let state_snapshot_checksum = warp::path!(Version)
+        .and(format())
+        .and(framing())
+        .and_then(move |version: Version, format: Format, framing| {
+            let bh = bh.clone();
+            let limiter = limiter.clone();
+            async move {
+                let _permit = match limiter.acquire().await {
+                    Ok(permit) => permit,
+                    Err(reply) => return Ok::<_, Rejection>(reply),
+                };
+                Ok(reply_with_checksum(move || {
+                    checksum_records(
+                        bh.get_account_iter(version),
+                        format,
+                        None,
+                        bcs_framing(format, framing),
+                    )
+                })
+                .await)
+            }
         })
         .recover(handle_rejection);
 
-    // GET transaction_range_proof/<first_version>/<last_version>
-    let bh = backup_handler;
-    let transaction_range_proof = warp::path!(Version / Version)
-        .map(move |first_version, last_version| {
-            reply_with_bcs_bytes(
-                TRANSACTION_RANGE_PROOF,
+    // GET checksum/epoch_ending_ledger_infos/<start_epoch>/<end_epoch>/
+    let bh = backup_handler.clone();
+    let epoch_ending_ledger_infos_checksum = warp::path!(u64 / u64)
+        .and(format())
+        .and(dictionary(zstd_dictionary.clone()))
+        .and(framing())
+        .and_then(
+            move |start_epoch: u64,
+                  end_epoch: u64,
+                  format: Format,
+                  dictionary: Option<Arc<ZstdDictionary>>,
+                  framing| {
+                let bh = bh.clone();
+                async move {
+                    Ok::<_, Rejection>(
+                        reply_with_checksum(move || {
+                            checksum_records(
+                                bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch),
+                                format,
+                                dictionary.as_deref(),
+                                bcs_framing(format, framing),
+                            )
+                        })
+                        .await,
+                    )
+                }
+            },
+        )
+        .recover(handle_rejection);
+
+    // GET checksum/transactions/<start_version>/<num_transactions>
+    let bh = backup_handler.clone();
+    let transactions_checksum = warp::path!(Version / usize)
+        .and(format())
+        .and(dictionary(zstd_dictionary))
+        .and(framing())
+        .and_then(
+            move |start_version: Version,
+                  num_transactions: usize,
+                  format: Format,
+                  dictionary: Option<Arc<ZstdDictionary>>,
+                  framing| {
+                let bh = bh.clone();
+                async move {
+                    Ok::<_, Rejection>(
+                        reply_with_checksum(move || {
+                            checksum_records(
+                                bh.get_transaction_iter(start_version, num_transactions),
+                                format,
+                                dictionary.as_deref(),
+                                bcs_framing(format, framing),
+                            )
+                        })
+                        .await,
+                    )
+                }
+            },
+        )
+        .recover(handle_rejection);
+
+    // GET checksum/state_range_proof/<version>/<end_key>
+    let bh = backup_handler.clone();
+    let state_range_proof_checksum = warp::path!(Version / HashValue)
+        .and(format())
+        .map(move |version, end_key, format| {
+            checksum_record(&bh.get_account_state_range_proof(end_key, version)?, format)
+                .map(|checksum| Box::new(warp::reply::json(&checksum)) as Box<dyn Reply>)
+        })
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // GET checksum/state_root_proof/<version>
+    let bh = backup_handler.clone();
+    let state_root_proof_checksum = warp::path!(Version)
+        .and(format())
+        .map(move |version, format| {
+            checksum_record(&bh.get_state_root_proof(version)?, format)
+                .map(|checksum| Box::new(warp::reply::json(&checksum)) as Box<dyn Reply>)
+        })
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // GET checksum/transaction_range_proof/<first_version>/<last_version>
+    let bh = backup_handler.clone();
+    let transaction_range_proof_checksum = warp::path!(Version / Version)
+        .and(format())
+        .map(move |first_version, last_version, format| {
+            checksum_record(
                 &bh.get_transaction_range_proof(first_version, last_version)?,
+                format,
             )
+            .map(|checksum| Box::new(warp::reply::json(&checksum)) as Box<dyn Reply>)
         })
         .map(unwrap_or_500)
         .recover(handle_rejection);
 
+    // GET transaction_range_proof/<first_version>/<last_version>
+    let bh = backup_handler;
+    let transaction_range_proof = warp::path!(Version / Version)
+        .and(format())
+        .and(request_audit(audit_log, TRANSACTION_RANGE_PROOF))
+        .map(
+            move |first_version, last_version, format, audit: RequestAudit| {
+                let audit = audit.with_versions(first_version, last_version);
+                if let Some(reply) = pruning.reply_if_pruned(
+                    TRANSACTION_RANGE_PROOF,
+                    PrunableData::Transactions,
+                    first_version,
+                )? {
+                    audit.finish(0, AuditStatus::Pruned);
+                    return Ok(reply);
+                }
+                reply_with_record(
+                    TRANSACTION_RANGE_PROOF,
+                    &bh.get_transaction_range_proof(first_version, last_version)?,
+                    format,
+                    audit,
+                )
+            },
+        )
+        .map(unwrap_or_500)
+        .recover(handle_rejection);
+
+    // Below, admin endpoints are only served if the config has an admin token, and reply 401 to
+    // requests without it.
+
+    // GET admin/streams
+    // Lists the streams in flight in JSON, see `StreamSummary`.
+    let registry = streams.clone();
+    let list_streams = warp::path!("streams")
+        .map(move |authorized: bool| -> Box<dyn Reply> {
+            if !authorized {
+                return Box::new(StatusCode::UNAUTHORIZED);
+            }
+            Box::new(warp::reply::json(&registry.list()))
+        })
+        .recover(handle_rejection);
+
+    // DELETE admin/streams/<id>
+    // Cancels the stream, which is then aborted, as if the connection broke. 404 if it's not in
+    // flight.
+    let registry = streams;
+    let cancel_stream = warp::path!("streams" / u64)
+        .map(move |authorized: bool, id: u64| {
+            if !authorized {
+                StatusCode::UNAUTHORIZED
+            } else if registry.cancel(id) {
+                info!(stream_id = id, "Backup stream cancelled by an admin.");
+                StatusCode::NO_CONTENT
+            } else {
+                StatusCode::NOT_FOUND
+            }
+        })
+        .recover(handle_rejection);
+
+    let checksum_routes = warp::any()
+        .and(endpoint(STATE_SNAPSHOT, endpoints.state_snapshot).and(state_snapshot_checksum))
+        .or(endpoint(
+            EPOCH_ENDING_LEDGER_INFOS,
+            endpoints.epoch_ending_ledger_infos,
+        )
+        .and(epoch_ending_ledger_infos_checksum))
+        .or(endpoint(TRANSACTIONS, endpoints.transactions).and(transactions_checksum))
+        .or(endpoint(STATE_RANGE_PROOF, endpoints.state_range_proof)
+            .and(state_range_proof_checksum))
+        .or(endpoint(STATE_ROOT_PROOF, endpoints.state_root_proof).and(state_root_proof_checksum))
+        .or(
+            endpoint(TRANSACTION_RANGE_PROOF, endpoints.transaction_range_proof)
+                .and(transaction_range_proof_checksum),
+        );
+
     // Route by endpoint name.
     let routes = warp::any()
-        .and(warp::path(DB_STATE).and(db_state))
-        .or(warp::path(STATE_RANGE_PROOF).and(state_range_proof))
-        .or(warp::path(STATE_SNAPSHOT).and(state_snapshot))
-        .or(warp::path(STATE_ROOT_PROOF).and(state_root_proof))
-        .or(warp::path(EPOCH_ENDING_LEDGER_INFOS).and(epoch_ending_ledger_infos))
-        .or(warp::path(TRANSACTIONS).and(transactions))
-        .or(warp::path(TRANSACTION_RANGE_PROOF).and(transaction_range_proof));
-
-    // Serve all routes for GET only.
+        .and(endpoint(DB_STATE, endpoints.db_state).and(db_state))
+        .or(status)
+        .or(endpoint(STATE_RANGE_PROOF, endpoints.state_range_proof).and(state_range_proof))
+        .or(endpoint(STATE_SNAPSHOT, endpoints.state_snapshot).and(state_snapshot))
+        .or(endpoint(STATE_ROOT_PROOF, endpoints.state_root_proof).and(state_root_proof))
+        .or(endpoint(
+            EPOCH_ENDING_LEDGER_INFOS,
+            endpoints.epoch_ending_ledger_infos,
+        )
+        .and(epoch_ending_ledger_infos))
+        .or(endpoint(TRANSACTIONS, endpoints.transactions).and(transactions))
+        .or(
+            endpoint(TRANSACTION_RANGE_PROOF, endpoints.transaction_range_proof)
+                .and(transaction_range_proof),
+        )
+        .or(endpoint(ZSTD_DICTIONARY, endpoints.zstd_dictionary).and(zstd_dictionary_route))
+        .or(warp::path(CHECKSUM).and(checksum_routes));
+
+    let head_routes = warp::any()
+        .and(endpoint(STATE_SNAPSHOT, endpoints.state_snapshot).and(state_snapshot_head))
+        .or(endpoint(
+            EPOCH_ENDING_LEDGER_INFOS,
+            endpoints.epoch_ending_ledger_infos,
+        )
+        .and(epoch_ending_ledger_infos_head))
+        .or(endpoint(TRANSACTIONS, endpoints.transactions).and(transactions_head));
+
+    let admin_token = config.admin_token;
+    let admin_routes = warp::get()
+        .and(admin(admin_token.clone()).and(list_streams))
+        .or(warp::delete().and(admin(admin_token).and(cancel_stream)));
+
+    // Serve all routes for GET, the streaming ones for HEAD, and the admin ones.
     warp::get()
         .and(routes)
+        .or(warp::head().and(head_routes))
+        .or(admin_routes)
         .with(warp::log::custom(|info| {
             let endpoint = info.path().split('/').nth(1).unwrap_or("-");
             LATENCY_HISTOGRAM
@@ -0,0 +1,182 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replying to requests for versions the pruners of the node deleted already.
+//!
+//! Rather than failing with a 500, or partway through a stream, such requests get a 410 with a
+//! `PrunedVersion` in JSON, telling from which version on the data is served, and where the
+//! version may be found instead, see `BackupServiceConfig::pruned_data_hints`, for clients to fail
+//! over there.
+
+use crate::handlers::status::ServedRanges;
+use anyhow::Result;
+use aptos_db::backup::backup_handler::BackupHandler;
+use aptos_metrics_core::{register_int_counter_vec, IntCounterVec};
+use aptos_types::transaction::Version;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{cmp::min, sync::Arc};
+use warp::{http::StatusCode, Reply};
+
+static PRUNED_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_backup_service_pruned_requests",
+        "Requests rejected by the backup service because the versions asked for are pruned.",
+        &["endpoint"]
+    )
+    .unwrap()
+});
+
+/// What a request needs to be served, each pruned on its own schedule, see `ServedRanges`.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum PrunableData {
+    /// Transactions, and their infos the proofs are made of.
+    Transactions,
+    /// The state at the version.
+    State,
+}
+
+/// Body of the 410 replies.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub(super) struct PrunedVersion {
+    /// Always `pruned`, telling the reply from other 410s, e.g. of proxies.
+    error: &'static str,
+    endpoint: &'static str,
+    version: Version,
+    /// The data is served from this version on.
+    first_available_version: Version,
+    /// Where the version may be found instead, as configured.
+    hints: Vec<String>,
+}
+
+impl PrunedVersion {
+    fn check(
+        ranges: &ServedRanges,
+        endpoint: &'static str,
+        data: PrunableData,
+        version: Version,
+        hints: &[String],
+    ) -> Option<Self> {
+        let (first_available_version, pruned) = match data {
+            PrunableData::Transactions => (
+                ranges.first_transaction_version,
+                version < ranges.first_transaction_version,
+            ),
+            // State snapshots at epoch endings are kept for longer, so the state is only known to
+            // be pruned at versions older than both windows.
+            PrunableData::State => (
+                ranges.first_state_snapshot_version,
+                version
+                    < min(
+                        ranges.first_state_snapshot_version,
+                        ranges.first_epoch_ending_snapshot_version,
+                    ),
+            ),
+        };
+        pruned.then(|| Self {
+            error: "pruned",
+            endpoint,
+            version,
+            first_available_version,
+            hints: hints.to_vec(),
+        })
+    }
+}
+
+/// Checks the versions requested against the pruners of the node.
+#[derive(Clone)]
+pub(super) struct PruningCheck {
+    backup_handler: BackupHandler,
+    hints: Arc<Vec<String>>,
+}
+
+impl PruningCheck {
+    pub fn new(backup_handler: BackupHandler, hints: Vec<String>) -> Self {
+        Self {
+            backup_handler,
+            hints: Arc::new(hints),
+        }
+    }
+
+    /// The 410 to reply with if the data at `version` is pruned.
+    pub fn reply_if_pruned(
+        &self,
+        endpoint: &'static str,
+        data: PrunableData,
+        version: Version,
+    ) -> Result<Option<Box<dyn Reply>>> {
+        let metadata = match self.backup_handler.get_db_metadata()? {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        let pruned = PrunedVersion::check(
+            &ServedRanges::new(&metadata),
+            endpoint,
+            data,
+            version,
+            &self.hints,
+        );
+        Ok(pruned.map(|pruned| {
+            PRUNED_REQUESTS.with_label_values(&[endpoint]).inc();
+            Box::new(warp::reply::with_status(
+                warp::reply::json(&pruned),
+                StatusCode::GONE,
+            )) as Box<dyn Reply>
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PrunableData, PrunedVersion};
+    use crate::handlers::status::ServedRanges;
+
+    #[test]
+    fn test_pruned_version() {
+        let ranges = ServedRanges {
+            epoch: 10,
+            last_version: 1000,
+            first_transaction_version: 100,
+            first_state_snapshot_version: 900,
+            first_epoch_ending_snapshot_version: 500,
+        };
+        let hints = vec!["http://archive:6186".to_string()];
+
+        assert_eq!(
+            PrunedVersion::check(
+                &ranges,
+                "transactions",
+                PrunableData::Transactions,
+                99,
+                &hints
+            ),
+            Some(PrunedVersion {
+                error: "pruned",
+                endpoint: "transactions",
+                version: 99,
+                first_available_version: 100,
+                hints: hints.clone(),
+            })
+        );
+        assert!(PrunedVersion::check(
+            &ranges,
+            "transactions",
+            PrunableData::Transactions,
+            100,
+            &hints
+        )
+        .is_none());
+
+        // May be the end of an epoch, with its snapshot kept.
+        assert!(
+            PrunedVersion::check(&ranges, "state_snapshot", PrunableData::State, 600, &hints)
+                .is_none()
+        );
+        assert_eq!(
+            PrunedVersion::check(&ranges, "state_snapshot", PrunableData::State, 499, &[])
+                .unwrap()
+                .first_available_version,
+            900
+        );
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_config::config::BackupServiceEndpoints;
+use aptos_db::backup::backup_handler::DbMetadata;
+use aptos_types::transaction::Version;
+use serde::{Deserialize, Serialize};
+
+/// What the backup service serves, for fleet tooling to find nodes suitable as backup sources
+/// without probing each endpoint, see `GET status`. Also exposed by the inspection service of the
+/// node.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackupServiceStatus {
+    pub endpoints: BackupServiceEndpoints,
+    /// None until the DB has committed anything.
+    pub ranges: Option<ServedRanges>,
+}
+
+/// Versions the records served are available at, as far as the pruners let them be.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ServedRanges {
+    pub epoch: u64,
+    /// Latest committed version.
+    pub last_version: Version,
+    /// Transactions are served from this version on.
+    pub first_transaction_version: Version,
+    /// State snapshots are served at versions from this one on.
+    pub first_state_snapshot_version: Version,
+    /// State snapshots at epoch endings are served from this version on.
+    pub first_epoch_ending_snapshot_version: Version,
+}
+
+impl BackupServiceStatus {
+    pub(super) fn new(endpoints: BackupServiceEndpoints, metadata: Option<DbMetadata>) -> Self {
+        Self {
+            endpoints,
+            ranges: metadata.as_ref().map(ServedRanges::new),
+        }
+    }
+}
+
+impl ServedRanges {
+    pub(super) fn new(metadata: &DbMetadata) -> Self {
+        Self {
+            epoch: metadata.epoch,
+            last_version: metadata.last_version,
+            first_transaction_version: metadata.first_version,
+            first_state_snapshot_version: metadata.state_merkle_pruner.min_readable_version,
+            first_epoch_ending_snapshot_version: metadata
+                .epoch_snapshot_pruner
+                .min_readable_version,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::handlers::audit::RequestAudit;
+use aptos_infallible::Mutex;
+use aptos_types::transaction::Version;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::sync::Notify;
+use warp::{filters::BoxedFilter, Filter};
+
+/// A stream in flight, as listed by `GET /admin/streams`.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub(super) struct StreamSummary {
+    pub id: u64,
+    pub endpoint: &'static str,
+    pub client: Option<SocketAddr>,
+    pub path: String,
+    pub first_version: Option<Version>,
+    pub last_version: Option<Version>,
+    pub bytes_sent: u64,
+    pub duration_ms: u128,
+    /// Cancelled, but not yet noticed by the stream, e.g. because it's busy reading the DB.
+    pub cancelled: bool,
+}
+
+struct StreamState {
+    endpoint: &'static str,
+    client: Option<SocketAddr>,
+    path: String,
+    versions: Option<(Version, Version)>,
+    start: Instant,
+    bytes_sent: AtomicU64,
+    cancelled: AtomicBool,
+    cancel: Notify,
+}
+
+/// The streams in flight, for operators to find the stuck ones and cancel them without
+/// restarting the node.
+#[derive(Default)]
+pub(super) struct StreamRegistry {
+    next_id: AtomicU64,
+    streams: Mutex<BTreeMap<u64, Arc<StreamState>>>,
+}
+
+impl StreamRegistry {
+    pub fn register(self: &Arc<Self>, audit: &RequestAudit) -> StreamHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let state = Arc::new(StreamState {
+            endpoint: audit.endpoint(),
+            client: audit.client(),
+            path: audit.path().to_string(),
+            versions: audit.versions(),
+            start: Instant::now(),
+            bytes_sent: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+            cancel: Notify::new(),
+        });
+        self.streams.lock().insert(id, state.clone());
+        StreamHandle {
+            registry: self.clone(),
+            id,
+            state,
+        }
+    }
+
+    /// The streams in flight, oldest first.
+    pub fn list(&self) -> Vec<StreamSummary> {
+        self.streams
+            .lock()
+            .iter()
+            .map(|(id, state)| StreamSummary {
+                id: *id,
+                endpoint: state.endpoint,
+                client: state.client,
+                path: state.path.clone(),
+                first_version: state.versions.map(|(first, _)| first),
+                last_version: state.versions.map(|(_, last)| last),
+                bytes_sent: state.bytes_sent.load(Ordering::Relaxed),
+                duration_ms: state.start.elapsed().as_millis(),
+                cancelled: state.cancelled.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Cancels the stream, returning false if there is no such stream in flight.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.streams.lock().get(&id) {
+            Some(state) => {
+                state.cancelled.store(true, Ordering::Relaxed);
+                state.cancel.notify_waiters();
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// Held by a stream for as long as it's in flight.
+pub(super) struct StreamHandle {
+    registry: Arc<StreamRegistry>,
+    id: u64,
+    state: Arc<StreamState>,
+}
+
+impl StreamHandle {
+    pub fn add_bytes_sent(&self, n_bytes: u64) {
+        self.state.bytes_sent.fetch_add(n_bytes, Ordering::Relaxed);
+    }
+
+    /// Resolves once the stream is cancelled.
+    pub async fn cancelled(&self) {
+        // Created before checking the flag, not to miss a cancellation in between.
+        let notified = self.state.cancel.notified();
+        if self.state.cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        notified.await
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        self.registry.streams.lock().remove(&self.id);
+    }
+}
+
+/// Matches `admin/` if an admin token is configured, as if it didn't exist otherwise, and tells
+/// whether the request carries the token.
+pub(super) fn admin(admin_token: Option<String>) -> BoxedFilter<(bool,)> {
+    warp::path("admin")
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |authorization: Option<String>| {
+            let admin_token = admin_token.clone();
+            async move {
+                match admin_token {
+                    Some(token) => Ok(authorization.map_or(false, |authorization| {
+                        constant_time_eq(
+                            authorization.as_bytes(),
+                            format!("Bearer {}", token).as_bytes(),
+                        )
+                    })),
+                    None => Err(warp::reject::not_found()),
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Compares without short-circuiting, not to leak through timing how much of the token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamRegistry;
+    use crate::handlers::audit::RequestAudit;
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn test_stream_registry() {
+        let registry = Arc::new(StreamRegistry::default());
+        let audit = RequestAudit::new(
+            None,
+            Some("127.0.0.1:1234".parse().unwrap()),
+            "transactions",
+            "/transactions/100/10",
+        )
+        .with_versions(100, 109);
+        let stream = registry.register(&audit);
+        let other = registry.register(&audit);
+        stream.add_bytes_sent(42);
+
+        let streams = registry.list();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].endpoint, "transactions");
+        assert_eq!(streams[0].client, Some("127.0.0.1:1234".parse().unwrap()));
+        assert_eq!(streams[0].first_version, Some(100));
+        assert_eq!(streams[0].last_version, Some(109));
+        assert_eq!(streams[0].bytes_sent, 42);
+        assert_eq!(streams[1].bytes_sent, 0);
+
+        // Only the cancelled stream notices.
+        let id = streams[0].id;
+        assert!(registry.cancel(id));
+        tokio::time::timeout(Duration::from_secs(1), stream.cancelled())
+            .await
+            .unwrap();
+        assert!(registry.list()[0].cancelled);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), other.cancelled())
+                .await
+                .is_err()
+        );
+
+        // Finished streams are no longer listed.
+        drop(stream);
+        assert!(!registry.cancel(id));
+        assert_eq!(registry.list().len(), 1);
+        drop(other);
+        assert!(registry.list().is_empty());
+    }
+}
@@ -2,18 +2,36 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use crate::handlers::{
+    audit::{AuditStatus, RequestAudit},
+    dictionary::{DictionaryMismatch, ZstdDictionary},
+    framing::Framing,
+    streams::{StreamHandle, StreamRegistry},
+};
+use anyhow::{bail, Result};
 use aptos_db::backup::backup_handler::BackupHandler;
 use aptos_logger::prelude::*;
 use aptos_metrics_core::{
-    register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, HistogramVec,
+    IntCounterVec,
 };
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use hyper::Body;
 use once_cell::sync::Lazy;
-use serde::Serialize;
-use std::{convert::Infallible, future::Future};
-use warp::{reply::Response, Rejection, Reply};
+use serde::{Deserialize, Serialize};
+use std::{cmp::max, convert::Infallible, future::Future, sync::Arc, time::Duration};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use warp::{
+    http::{header::CONTENT_LENGTH, HeaderValue, StatusCode},
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+/// Header of the replies to HEAD requests carrying the number of records the stream would send.
+pub(super) const RECORD_COUNT_HEADER: &str = "x-backup-record-count";
+/// Header of the replies to HEAD requests telling whether their `Content-Length` is exact, rather
+/// than an estimate.
+pub(super) const SIZE_EXACT_HEADER: &str = "x-backup-size-exact";
 
 pub(super) static LATENCY_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
@@ -33,44 +51,223 @@ pub(super) static THROUGHPUT_COUNTER: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(super) static CHUNK_RECORDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_backup_service_chunk_records",
+        "Number of records in the chunks of the streams sent by the backup service.",
+        &["endpoint"],
+        exponential_buckets(1.0, 2.0, 14).unwrap()
+    )
+    .unwrap()
+});
+
+pub(super) static CHUNK_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_backup_service_chunk_bytes",
+        "Size of the chunks of the streams sent by the backup service.",
+        &["endpoint"],
+        exponential_buckets(256.0, 2.0, 20).unwrap()
+    )
+    .unwrap()
+});
+
+pub(super) static THROTTLED_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_backup_service_throttled_requests",
+        "Requests rejected by the backup service because too many streams are in flight.",
+        &["endpoint"]
+    )
+    .unwrap()
+});
+
+/// Caps the number of concurrent streams served by an endpoint. Requests beyond the cap wait for
+/// up to `queue_timeout` for a stream to finish, then get a 429 with Retry-After.
+#[derive(Clone)]
+pub(super) struct StreamLimiter {
+    endpoint: &'static str,
+    /// `None` if there is no limit.
+    semaphore: Option<Arc<Semaphore>>,
+    queue_timeout: Duration,
+    retry_after_secs: u64,
+}
+
+impl StreamLimiter {
+    pub fn new(
+        endpoint: &'static str,
+        max_concurrent_streams: usize,
+        queue_timeout: Duration,
+        retry_after_secs: u64,
+    ) -> Self {
+        Self {
+            endpoint,
+            semaphore: (max_concurrent_streams > 0)
+                .then(|| Arc::new(Semaphore::new(max_concurrent_streams))),
+            queue_timeout,
+            retry_after_secs,
+        }
+    }
+
+    /// Returns a permit to be held until the stream finishes, or the reply to reject the request
+    /// with.
+    pub async fn acquire(
+        &self,
+    ) -> std::result::Result<Option<OwnedSemaphorePermit>, Box<dyn Reply>> {
+        let semaphore = match &self.semaphore {
+            Some(semaphore) => semaphore.clone(),
+            None => return Ok(None),
+        };
+        match tokio::time::timeout(self.queue_timeout, semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            _ => {
+                warn!(
+                    endpoint = self.endpoint,
+                    "Too many concurrent streams, rejecting request."
+                );
+                THROTTLED_REQUESTS.with_label_values(&[self.endpoint]).inc();
+                Err(Box::new(warp::reply::with_header(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Retry-After",
+                    self.retry_after_secs.to_string(),
+                )))
+            },
+        }
+    }
+}
+
+/// How records are rendered.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Format {
+    /// What backup clients expect. Streamed records are each prefixed with their size.
+    Bcs,
+    /// For operators to check what's being served, e.g. with curl. Streamed records are rendered
+    /// one per line. Only served with the `json-debug` feature.
+    Json,
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<Format>,
+}
+
+/// Extracts the `?format=` of the request, BCS if not given. Rejects unknown formats, and JSON
+/// unless built with the `json-debug` feature.
+pub(super) fn format() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::query::<FormatQuery>().and_then(|query: FormatQuery| async move {
+        match query.format.unwrap_or(Format::Bcs) {
+            Format::Json if !cfg!(feature = "json-debug") => Err(warp::reject()),
+            format => Ok(format),
+        }
+    })
+}
+
+pub(super) fn reply_with_record<R: Serialize>(
+    endpoint: &str,
+    record: &R,
+    format: Format,
+    audit: RequestAudit,
+) -> Result<Box<dyn Reply>> {
+    match format {
+        Format::Bcs => reply_with_bcs_bytes(endpoint, record, audit),
+        Format::Json => reply_with_json(endpoint, record, audit),
+    }
+}
+
 pub(super) fn reply_with_bcs_bytes<R: Serialize>(
     endpoint: &str,
     record: &R,
+    audit: RequestAudit,
 ) -> Result<Box<dyn Reply>> {
     let bytes = bcs::to_bytes(record)?;
     THROUGHPUT_COUNTER
         .with_label_values(&[endpoint])
         .inc_by(bytes.len() as u64);
+    audit.finish(bytes.len() as u64, AuditStatus::Completed);
     Ok(Box::new(bytes))
 }
 
+pub(super) fn reply_with_json<R: Serialize>(
+    endpoint: &str,
+    record: &R,
+    audit: RequestAudit,
+) -> Result<Box<dyn Reply>> {
+    let bytes = serde_json::to_vec(record)?;
+    THROUGHPUT_COUNTER
+        .with_label_values(&[endpoint])
+        .inc_by(bytes.len() as u64);
+    audit.finish(bytes.len() as u64, AuditStatus::Completed);
+    Ok(Box::new(warp::reply::with_header(
+        bytes,
+        "Content-Type",
+        "application/json",
+    )))
+}
+
 pub(super) struct BytesSender {
     endpoint: &'static str,
     inner: hyper::body::Sender,
+    audit: RequestAudit,
+    stream: StreamHandle,
+    bytes_sent: u64,
 }
 
 impl BytesSender {
-    fn new(endpoint: &'static str, inner: hyper::body::Sender) -> Self {
-        Self { endpoint, inner }
+    fn new(
+        endpoint: &'static str,
+        inner: hyper::body::Sender,
+        audit: RequestAudit,
+        stream: StreamHandle,
+    ) -> Self {
+        Self {
+            endpoint,
+            inner,
+            audit,
+            stream,
+            bytes_sent: 0,
+        }
+    }
+
+    /// Sends a chunk made of `num_records` records.
+    async fn send_chunk(&mut self, chunk: Bytes, num_records: usize) -> Result<()> {
+        CHUNK_RECORDS
+            .with_label_values(&[self.endpoint])
+            .observe(num_records as f64);
+        CHUNK_BYTES
+            .with_label_values(&[self.endpoint])
+            .observe(chunk.len() as f64);
+        self.send_data(chunk).await
     }
 
     async fn send_data(&mut self, chunk: Bytes) -> Result<()> {
         let n_bytes = chunk.len();
-        self.inner.send_data(chunk).await?;
+        tokio::select! {
+            res = self.inner.send_data(chunk) => res?,
+            _ = self.stream.cancelled() => bail!("Stream cancelled by an admin."),
+        }
         THROUGHPUT_COUNTER
             .with_label_values(&[self.endpoint])
             .inc_by(n_bytes as u64);
+        self.stream.add_bytes_sent(n_bytes as u64);
+        self.bytes_sent += n_bytes as u64;
         Ok(())
     }
 
+    fn finish(self) {
+        self.audit.finish(self.bytes_sent, AuditStatus::Completed)
+    }
+
     fn abort(self) {
+        self.audit.finish(self.bytes_sent, AuditStatus::Failed);
         self.inner.abort()
     }
 }
 
+/// Streams the body written by the future, listing it in `streams` until it finishes.
 pub(super) fn reply_with_async_channel_writer<G, F>(
     backup_handler: &BackupHandler,
     endpoint: &'static str,
+    audit: RequestAudit,
+    streams: &Arc<StreamRegistry>,
     get_channel_writer: G,
 ) -> Box<dyn Reply>
 where
@@ -78,44 +275,206 @@ where
     F: Future<Output = ()> + Send + 'static,
 {
     let (sender, body) = Body::channel();
-    let sender = BytesSender::new(endpoint, sender);
+    let stream = streams.register(&audit);
+    let sender = BytesSender::new(endpoint, sender, audit, stream);
     let bh = backup_handler.clone();
     tokio::spawn(get_channel_writer(bh, sender));
 
     Box::new(Response::new(body))
 }
 
-pub(super) async fn send_size_prefixed_bcs_bytes<I, R>(iter_res: Result<I>, mut sender: BytesSender)
-where
+/// Sends the records, each compressed on its own with the dictionary if given, then framed, in
+/// BCS format, up to `max_records_per_chunk` of them per chunk of the body.
+pub(super) async fn send_records<I, R>(
+    iter_res: Result<I>,
+    format: Format,
+    dictionary: Option<Arc<ZstdDictionary>>,
+    framing: Framing,
+    max_records_per_chunk: usize,
+    mut sender: BytesSender,
+) where
     I: Iterator<Item = Result<R>>,
     R: Serialize,
 {
-    send_size_prefixed_bcs_bytes_impl(iter_res, &mut sender)
-        .await
-        .unwrap_or_else(|e| {
+    match send_records_impl(
+        iter_res,
+        format,
+        dictionary.as_deref(),
+        framing,
+        max_records_per_chunk,
+        &mut sender,
+    )
+    .await
+    {
+        Ok(()) => sender.finish(),
+        Err(e) => {
             warn!("Failed writing to output http body: {:?}", e);
             sender.abort()
-        });
+        },
+    }
 }
 
-async fn send_size_prefixed_bcs_bytes_impl<I, R>(
+async fn send_records_impl<I, R>(
     iter_res: Result<I>,
+    format: Format,
+    dictionary: Option<&ZstdDictionary>,
+    framing: Framing,
+    max_records_per_chunk: usize,
     sender: &mut BytesSender,
 ) -> Result<()>
 where
     I: Iterator<Item = Result<R>>,
     R: Serialize,
 {
+    let max_records_per_chunk = max(max_records_per_chunk, 1);
+    let mut encoder = RecordEncoder::new(format, dictionary, framing)?;
+    let mut chunk = BytesMut::new();
+    let mut num_records = 0;
     for record_res in iter_res? {
-        let record = record_res?;
-        let record_bytes = bcs::to_bytes(&record)?;
-        let size_bytes = (record_bytes.len() as u32).to_be_bytes();
-        sender.send_data(Bytes::from(size_bytes.to_vec())).await?;
-        sender.send_data(Bytes::from(record_bytes)).await?;
+        chunk.extend_from_slice(&encoder.encode(&record_res?)?);
+        num_records += 1;
+        if num_records == max_records_per_chunk {
+            sender
+                .send_chunk(chunk.split().freeze(), num_records)
+                .await?;
+            num_records = 0;
+        }
+    }
+    if let Some(end_of_stream) = encoder.end_of_stream() {
+        chunk.extend_from_slice(&end_of_stream);
+    }
+    if !chunk.is_empty() {
+        sender.send_chunk(chunk.freeze(), num_records).await?;
     }
     Ok(())
 }
 
+/// Renders records one after the other into what `send_records` sends.
+pub(super) struct RecordEncoder<'a> {
+    format: Format,
+    compressor: Option<zstd::bulk::Compressor<'a>>,
+    framing: Framing,
+    num_records: u64,
+}
+
+impl<'a> RecordEncoder<'a> {
+    pub fn new(
+        format: Format,
+        dictionary: Option<&'a ZstdDictionary>,
+        framing: Framing,
+    ) -> Result<Self> {
+        Ok(Self {
+            format,
+            compressor: dictionary.map(ZstdDictionary::compressor).transpose()?,
+            framing,
+            num_records: 0,
+        })
+    }
+
+    pub fn encode<R: Serialize>(&mut self, record: &R) -> Result<Bytes> {
+        let bytes = match self.format {
+            Format::Bcs => {
+                let mut record_bytes = bcs::to_bytes(record)?;
+                if let Some(compressor) = &mut self.compressor {
+                    record_bytes = compressor.compress(&record_bytes)?;
+                }
+                self.framing.frame(self.num_records, record_bytes)
+            },
+            Format::Json => {
+                let mut line = serde_json::to_vec(record)?;
+                line.push(b'\n');
+                Bytes::from(line)
+            },
+        };
+        self.num_records += 1;
+        Ok(bytes)
+    }
+
+    /// What ends the stream of the records encoded so far, if anything.
+    pub fn end_of_stream(&self) -> Option<Bytes> {
+        match self.format {
+            Format::Bcs => self.framing.end_of_stream(self.num_records),
+            Format::Json => None,
+        }
+    }
+
+    pub fn num_records(&self) -> u64 {
+        self.num_records
+    }
+}
+
+/// Size of what streaming records would send.
+#[derive(Debug, Eq, PartialEq)]
+pub(super) struct StreamEstimate {
+    pub num_records: u64,
+    pub num_bytes: u64,
+    /// Whether all the records were measured, rather than extrapolated from the first ones.
+    pub exact: bool,
+}
+
+/// Number of records measured to estimate the size of a stream.
+const ESTIMATE_SAMPLE_SIZE: usize = 32;
+
+/// Estimates what streaming the `num_records` records of the iterator would send, from the first
+/// few of them.
+pub(super) fn estimate_stream<I, R>(
+    iter_res: Result<I>,
+    num_records: u64,
+    format: Format,
+) -> Result<StreamEstimate>
+where
+    I: Iterator<Item = Result<R>>,
+    R: Serialize,
+{
+    let mut sampled = 0u64;
+    let mut sampled_bytes = 0u64;
+    for record_res in iter_res?.take(ESTIMATE_SAMPLE_SIZE) {
+        sampled += 1;
+        sampled_bytes += encoded_size(&record_res?, format)?;
+    }
+    Ok(estimate_from_sample(sampled, sampled_bytes, num_records))
+}
+
+fn estimate_from_sample(sampled: u64, sampled_bytes: u64, num_records: u64) -> StreamEstimate {
+    if sampled < ESTIMATE_SAMPLE_SIZE as u64 || sampled >= num_records {
+        // Measured it all, the iterator having run out.
+        StreamEstimate {
+            num_records: sampled,
+            num_bytes: sampled_bytes,
+            exact: true,
+        }
+    } else {
+        StreamEstimate {
+            num_records,
+            num_bytes: (sampled_bytes as u128 * num_records as u128 / sampled as u128) as u64,
+            exact: false,
+        }
+    }
+}
+
+/// Size of a record as `send_records` sends it.
+fn encoded_size<R: Serialize>(record: &R, format: Format) -> Result<u64> {
+    Ok(match format {
+        Format::Bcs => bcs::serialized_size(record)? as u64 + 4,
+        Format::Json => serde_json::to_vec(record)?.len() as u64 + 1,
+    })
+}
+
+/// Replies to a HEAD request with the estimate in the headers, if there is one.
+pub(super) fn reply_with_estimate(estimate: Option<StreamEstimate>) -> Box<dyn Reply> {
+    let mut response = Response::new(Body::empty());
+    if let Some(estimate) = estimate {
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_LENGTH, estimate.num_bytes.into());
+        headers.insert(RECORD_COUNT_HEADER, estimate.num_records.into());
+        headers.insert(
+            SIZE_EXACT_HEADER,
+            HeaderValue::from_static(if estimate.exact { "true" } else { "false" }),
+        );
+    }
+    Box::new(response)
+}
+
 /// Return 500 on any error raised by the request handler.
 pub(super) fn unwrap_or_500(result: Result<Box<dyn Reply>>) -> Box<dyn Reply> {
     match result {
@@ -127,8 +486,65 @@ pub(super) fn unwrap_or_500(result: Result<Box<dyn Reply>>) -> Box<dyn Reply> {
     }
 }
 
-/// Return 400 on any rejections (parameter parsing errors).
+/// Return 400 on any rejections (parameter parsing errors), and 409 if the request asks for a
+/// dictionary other than the one served.
 pub(super) async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    if err.find::<DictionaryMismatch>().is_some() {
+        return Ok(warp::http::StatusCode::CONFLICT);
+    }
     warn!("bad request: {:?}", err);
     Ok(warp::http::StatusCode::BAD_REQUEST)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_from_sample, StreamEstimate, StreamLimiter};
+    use std::time::Duration;
+    use warp::{http::StatusCode, Reply};
+
+    #[tokio::test]
+    async fn test_stream_limiter() {
+        let limiter = StreamLimiter::new("test", 1, Duration::from_millis(10), 30);
+        let permit = limiter.acquire().await.ok().unwrap();
+        assert!(permit.is_some());
+
+        let resp = limiter.acquire().await.err().unwrap().into_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()["Retry-After"], "30");
+
+        // A queued request gets the slot once the stream finishes.
+        let queued = {
+            let limiter = StreamLimiter::new("test", 1, Duration::from_secs(10), 30);
+            let permit = limiter.acquire().await.ok().unwrap();
+            let limiter_clone = limiter.clone();
+            let queued = tokio::spawn(async move { limiter_clone.acquire().await.is_ok() });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(permit);
+            queued
+        };
+        assert!(queued.await.unwrap());
+
+        let unlimited = StreamLimiter::new("test", 0, Duration::from_millis(10), 30);
+        assert!(unlimited.acquire().await.ok().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_estimate_from_sample() {
+        // The iterator ran out before the sample was full.
+        assert_eq!(estimate_from_sample(10, 1000, 100), StreamEstimate {
+            num_records: 10,
+            num_bytes: 1000,
+            exact: true,
+        });
+        assert_eq!(estimate_from_sample(32, 3200, 32), StreamEstimate {
+            num_records: 32,
+            num_bytes: 3200,
+            exact: true,
+        });
+        assert_eq!(estimate_from_sample(32, 3200, 1000), StreamEstimate {
+            num_records: 1000,
+            num_bytes: 100_000,
+            exact: false,
+        });
+    }
+}
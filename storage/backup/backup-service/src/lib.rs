@@ -5,14 +5,24 @@
 mod handlers;
 
 use crate::handlers::get_routes;
+pub use crate::handlers::status::{BackupServiceStatus, ServedRanges};
+use aptos_config::config::BackupServiceConfig;
 use aptos_db::AptosDB;
 use aptos_logger::prelude::*;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::runtime::Runtime;
 
 pub fn start_backup_service(address: SocketAddr, db: Arc<AptosDB>) -> Runtime {
+    start_backup_service_with_config(address, db, BackupServiceConfig::default())
+}
+
+pub fn start_backup_service_with_config(
+    address: SocketAddr,
+    db: Arc<AptosDB>,
+    config: BackupServiceConfig,
+) -> Runtime {
     let backup_handler = db.get_backup_handler();
-    let routes = get_routes(backup_handler);
+    let routes = get_routes(backup_handler, config);
 
     let runtime = aptos_runtimes::spawn_named_runtime("backup".into(), None);
 
@@ -33,7 +43,7 @@ pub fn start_backup_service(address: SocketAddr, db: Arc<AptosDB>) -> Runtime {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aptos_config::utils::get_available_port;
+    use aptos_config::{config::BackupServiceEndpoints, utils::get_available_port};
     use aptos_crypto::hash::HashValue;
     use aptos_temppath::TempPath;
     use reqwest::blocking::get;
@@ -73,6 +83,27 @@ mod tests {
         let resp = get(format!("http://127.0.0.1:{}/state_range_proof/1/ff", port)).unwrap();
         assert_eq!(resp.status(), 400);
 
+        // Unknown format, or JSON without the feature.
+        let resp = get(format!(
+            "http://127.0.0.1:{}/state_root_proof/0?format=xml",
+            port
+        ))
+        .unwrap();
+        assert_eq!(resp.status(), 400);
+        let resp = get(format!(
+            "http://127.0.0.1:{}/state_root_proof/0?format=json",
+            port
+        ))
+        .unwrap();
+        assert_eq!(
+            resp.status(),
+            if cfg!(feature = "json-debug") {
+                500
+            } else {
+                400
+            }
+        );
+
         // Request handler raised Error (non-bootstrapped DB)
         let resp = get(format!(
             "http://127.0.0.1:{}/state_range_proof/1/{}",
@@ -89,5 +120,99 @@ mod tests {
         // before the termination of the connection, resulting in slightly different behavior:
         let res = get(format!("http://127.0.0.1:{}/state_snapshot/1", port));
         assert!(res.is_err() || res.unwrap().bytes().is_err());
+
+        // HEAD on a streaming endpoint replies with the estimated size, nothing to send here.
+        let resp = reqwest::blocking::Client::new()
+            .head(format!("http://127.0.0.1:{}/transactions/0/10", port))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-backup-record-count"], "0");
+        assert_eq!(resp.headers()["content-length"], "0");
+    }
+
+    #[test]
+    fn disabled_endpoints() {
+        let tmpdir = TempPath::new();
+        let db = Arc::new(AptosDB::new_for_test(&tmpdir));
+        let port = get_available_port();
+        let _rt = start_backup_service_with_config(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+            db,
+            BackupServiceConfig {
+                endpoints: BackupServiceEndpoints {
+                    state_snapshot: false,
+                    transactions: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+
+        let resp = get(format!("http://127.0.0.1:{}/state_snapshot/1", port)).unwrap();
+        assert_eq!(resp.status(), 404);
+        let resp = get(format!("http://127.0.0.1:{}/transactions/0/10", port)).unwrap();
+        assert_eq!(resp.status(), 404);
+        let resp = reqwest::blocking::Client::new()
+            .head(format!("http://127.0.0.1:{}/transactions/0/10", port))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), 404);
+        let resp = get(format!(
+            "http://127.0.0.1:{}/checksum/transactions/0/10",
+            port
+        ))
+        .unwrap();
+        assert_eq!(resp.status(), 404);
+        // Without an admin token.
+        let resp = get(format!("http://127.0.0.1:{}/admin/streams", port)).unwrap();
+        assert_eq!(resp.status(), 404);
+
+        // Others are still served.
+        let resp = get(format!("http://127.0.0.1:{}/db_state", port)).unwrap();
+        assert_eq!(resp.status(), 200);
+
+        // The status tells which, with nothing committed to serve yet.
+        let status: BackupServiceStatus = get(format!("http://127.0.0.1:{}/status", port))
+            .unwrap()
+            .json()
+            .unwrap();
+        assert!(!status.endpoints.state_snapshot);
+        assert!(!status.endpoints.transactions);
+        assert!(status.endpoints.db_state);
+        assert_eq!(status.ranges, None);
+    }
+
+    #[test]
+    fn admin_streams() {
+        let tmpdir = TempPath::new();
+        let db = Arc::new(AptosDB::new_for_test(&tmpdir));
+        let port = get_available_port();
+        let _rt = start_backup_service_with_config(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+            db,
+            BackupServiceConfig {
+                admin_token: Some("secret".to_string()),
+                ..Default::default()
+            },
+        );
+        let client = reqwest::blocking::Client::new();
+        let url = format!("http://127.0.0.1:{}/admin/streams", port);
+
+        let resp = client.get(&url).send().unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = client.get(&url).bearer_auth("wrong").send().unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let resp = client.get(&url).bearer_auth("secret").send().unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().unwrap(), "[]");
+
+        let resp = client
+            .delete(format!("{}/0", url))
+            .bearer_auth("secret")
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), 404);
     }
 }
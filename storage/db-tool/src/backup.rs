@@ -10,11 +10,15 @@ use aptos_backup_cli::{
     },
     coordinators::{
         backup::{BackupCoordinator, BackupCoordinatorOpt},
-        verify::VerifyCoordinator,
+        gap_repair::GapRepairCoordinator,
+        metadata_merge::MetadataMergeCoordinator,
+        staleness_check::StalenessCheckCoordinator,
+        verify::{VerifyCoordinator, VerifySamplingOpt},
     },
     metadata::{cache, cache::MetadataCacheOpt},
-    storage::DBToolStorageOpt,
+    storage::{DBToolSourceStorageOpt, DBToolStorageOpt},
     utils::{
+        alert_hooks::{Alert, AlertHooks, AlertHooksOpt, AlertKind},
         backup_service_client::{BackupServiceClient, BackupServiceClientOpt},
         ConcurrentDownloadsOpt, GlobalBackupOpt, TrustedWaypointOpt,
     },
@@ -41,6 +45,31 @@ pub enum Command {
     Query(OneShotQueryType),
     #[clap(about = "verify the backup through restoring with the backup files")]
     Verify(VerifyOpt),
+    #[clap(
+        about = "Fail, and fire the alert hooks, if the newest transaction backup is older than \
+        the given age."
+    )]
+    StalenessCheck(StalenessCheckOpt),
+    #[clap(
+        about = "Find the epochs and versions missing from the epoch ending and transaction \
+        backups, failing if there are any, and optionally back them up."
+    )]
+    CheckGaps(CheckGapsOpt),
+    #[clap(
+        subcommand,
+        about = "Manage the metadata of the backups in the storage."
+    )]
+    Metadata(MetadataCommand),
+}
+
+#[derive(Parser)]
+pub enum MetadataCommand {
+    #[clap(
+        about = "Copy the backups of a source storage missing from the storage, e.g. to \
+        consolidate historical buckets, leaving out those overlapping its backups, and save \
+        their metadata in the storage."
+    )]
+    Merge(MetadataMergeOpt),
 }
 
 #[derive(Parser)]
@@ -54,6 +83,11 @@ pub enum OneShotQueryType {
         about = "Queries the latest epoch and versions of the existing backups in the storage."
     )]
     BackupStorageState(OneShotQueryBackupStorageStateOpt),
+    #[clap(
+        about = "Lists the manifests of the existing backups in the storage, with the tags \
+        attached to them, in storages that support tags."
+    )]
+    BackupTags(OneShotQueryBackupStorageStateOpt),
 }
 
 #[derive(Parser)]
@@ -80,6 +114,9 @@ pub struct OneoffBackupOpt {
     #[clap(flatten)]
     client: BackupServiceClientOpt,
 
+    #[clap(flatten)]
+    alert_hooks: AlertHooksOpt,
+
     #[clap(subcommand)]
     backup_type: BackupType,
 }
@@ -117,6 +154,9 @@ pub struct CoordinatorRunOpt {
     #[clap(flatten)]
     coordinator: BackupCoordinatorOpt,
 
+    #[clap(flatten)]
+    alert_hooks: AlertHooksOpt,
+
     #[clap[flatten]]
     storage: DBToolStorageOpt,
 }
@@ -131,6 +171,60 @@ pub struct VerifyOpt {
     storage: DBToolStorageOpt,
     #[clap(flatten)]
     concurrent_downloads: ConcurrentDownloadsOpt,
+    #[clap(flatten)]
+    sampling_opt: VerifySamplingOpt,
+}
+
+#[derive(Parser)]
+pub struct StalenessCheckOpt {
+    #[clap(flatten)]
+    metadata_cache: MetadataCacheOpt,
+    #[clap(flatten)]
+    concurrent_downloads: ConcurrentDownloadsOpt,
+    #[clap(
+        long,
+        help = "Maximum age in seconds of the newest transaction backup, as given by the ledger \
+        timestamp of its last chunk."
+    )]
+    max_age_secs: u64,
+    #[clap(flatten)]
+    alert_hooks: AlertHooksOpt,
+    #[clap(flatten)]
+    storage: DBToolStorageOpt,
+}
+
+#[derive(Parser)]
+pub struct CheckGapsOpt {
+    #[clap(flatten)]
+    metadata_cache: MetadataCacheOpt,
+    #[clap(flatten)]
+    concurrent_downloads: ConcurrentDownloadsOpt,
+    #[clap(
+        long,
+        help = "Back up the missing epochs and versions from the backup service, which must not \
+        have pruned them."
+    )]
+    repair: bool,
+    #[clap(flatten)]
+    global: GlobalBackupOpt,
+    #[clap(flatten)]
+    client: BackupServiceClientOpt,
+    #[clap(flatten)]
+    storage: DBToolStorageOpt,
+}
+
+#[derive(Parser)]
+pub struct MetadataMergeOpt {
+    #[clap(flatten)]
+    metadata_cache: MetadataCacheOpt,
+    #[clap(flatten)]
+    concurrent_downloads: ConcurrentDownloadsOpt,
+    #[clap(long, help = "Only report the backups that would be copied.")]
+    dry_run: bool,
+    #[clap(flatten)]
+    source: DBToolSourceStorageOpt,
+    #[clap(flatten)]
+    storage: DBToolStorageOpt,
 }
 
 impl Command {
@@ -141,49 +235,77 @@ impl Command {
             Command::Oneoff(opt) => {
                 let client = Arc::new(BackupServiceClient::new_with_opt(opt.client));
                 let global_opt = opt.global;
+                let alert_hooks = AlertHooks::new(opt.alert_hooks);
 
-                match opt.backup_type {
-                    BackupType::EpochEnding { opt, storage } => {
-                        EpochEndingBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
-                    },
-                    BackupType::StateSnapshot { opt, storage } => {
-                        StateSnapshotBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
-                    },
-                    BackupType::Transaction { opt, storage } => {
-                        TransactionBackupController::new(
-                            opt,
-                            global_opt,
-                            client,
-                            storage.init_storage().await?,
-                        )
-                        .run()
-                        .await?;
-                    },
+                let res = async {
+                    match opt.backup_type {
+                        BackupType::EpochEnding { opt, storage } => {
+                            EpochEndingBackupController::new(
+                                opt,
+                                global_opt,
+                                client,
+                                storage.init_storage().await?,
+                            )
+                            .run()
+                            .await?;
+                        },
+                        BackupType::StateSnapshot { opt, storage } => {
+                            StateSnapshotBackupController::new(
+                                opt,
+                                global_opt,
+                                client,
+                                storage.init_storage().await?,
+                            )
+                            .run()
+                            .await?;
+                        },
+                        BackupType::Transaction { opt, storage } => {
+                            TransactionBackupController::new(
+                                opt,
+                                global_opt,
+                                client,
+                                storage.init_storage().await?,
+                            )
+                            .run()
+                            .await?;
+                        },
+                    }
+                    Ok::<_, anyhow::Error>(())
+                }
+                .await;
+                if let Err(e) = &res {
+                    alert_hooks
+                        .fire(Alert::new(
+                            AlertKind::BackupFailed,
+                            format!("Backup failed: {:#}", e),
+                        ))
+                        .await;
                 }
+                res?
             },
             Command::Continuously(opt) => {
-                BackupCoordinator::new(
-                    opt.coordinator,
-                    opt.global,
-                    Arc::new(BackupServiceClient::new_with_opt(opt.client)),
-                    opt.storage.init_storage().await?,
-                )
-                .run()
-                .await?;
+                let alert_hooks = AlertHooks::new(opt.alert_hooks.clone());
+                let res = async {
+                    BackupCoordinator::new(
+                        opt.coordinator,
+                        opt.global,
+                        Arc::new(BackupServiceClient::new_with_opt(opt.client)),
+                        opt.storage.init_storage().await?,
+                    )
+                    .with_alert_hooks(AlertHooks::new(opt.alert_hooks))
+                    .run()
+                    .await
+                }
+                .await;
+                if let Err(e) = &res {
+                    alert_hooks
+                        .fire(Alert::new(
+                            AlertKind::BackupFailed,
+                            format!("Backup coordinator exited: {:#}", e),
+                        ))
+                        .await;
+                }
+                res?
             },
             Command::Query(typ) => match typ {
                 OneShotQueryType::NodeState(opt) => {
@@ -203,6 +325,24 @@ impl Command {
                     .await?;
                     println!("{}", view.get_storage_state()?)
                 },
+                OneShotQueryType::BackupTags(opt) => {
+                    let storage = opt.storage.init_storage().await?;
+                    let view = cache::sync_and_load(
+                        &opt.metadata_cache,
+                        storage.clone(),
+                        opt.concurrent_downloads.get(),
+                    )
+                    .await?;
+                    for manifest in view.manifests() {
+                        match storage.read_tags(&manifest).await? {
+                            Some(tags) => println!("{} {}", manifest, tags),
+                            None => {
+                                println!("Backup storage doesn't keep tags.");
+                                break;
+                            },
+                        }
+                    }
+                },
             },
             Command::Verify(opt) => {
                 VerifyCoordinator::new(
@@ -210,10 +350,47 @@ impl Command {
                     opt.metadata_cache_opt,
                     opt.trusted_waypoints_opt,
                     opt.concurrent_downloads.get(),
+                    opt.sampling_opt,
                 )?
                 .run()
                 .await?
             },
+            Command::StalenessCheck(opt) => {
+                StalenessCheckCoordinator::new(
+                    opt.storage.init_storage().await?,
+                    opt.metadata_cache,
+                    opt.concurrent_downloads.get(),
+                    opt.max_age_secs,
+                    AlertHooks::new(opt.alert_hooks),
+                )
+                .run()
+                .await?
+            },
+            Command::CheckGaps(opt) => {
+                let mut coordinator = GapRepairCoordinator::new(
+                    opt.storage.init_storage().await?,
+                    opt.metadata_cache,
+                    opt.concurrent_downloads.get(),
+                );
+                if opt.repair {
+                    coordinator = coordinator.with_repair(
+                        opt.global,
+                        Arc::new(BackupServiceClient::new_with_opt(opt.client)),
+                    );
+                }
+                coordinator.run().await?
+            },
+            Command::Metadata(MetadataCommand::Merge(opt)) => {
+                MetadataMergeCoordinator::new(
+                    opt.source.init_storage().await?,
+                    opt.storage.init_storage().await?,
+                    opt.metadata_cache,
+                    opt.concurrent_downloads.get(),
+                )
+                .with_dry_run(opt.dry_run)
+                .run()
+                .await?
+            },
         }
         Ok(())
     }
@@ -0,0 +1,100 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use aptos_backup_cli::{
+    backup_types::state_snapshot::export::StateSnapshotExportController,
+    storage::{DBToolStorageOpt, FileHandle},
+    utils::{ConcurrentDownloadsOpt, RocksdbOpt},
+};
+use aptos_config::config::{
+    BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use aptos_db::AptosDB;
+use aptos_logger::{Level, Logger};
+use aptos_storage_interface::DbReader;
+use aptos_types::transaction::Version;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Export the resources, resource groups, modules and table items of a state snapshot, tagged
+/// with their Move types, to a file of JSON lines.
+#[derive(Parser)]
+pub struct Opt {
+    #[clap(long, parse(from_os_str), help = "File to write the JSON lines to.")]
+    output: PathBuf,
+    #[clap(subcommand)]
+    source: Source,
+}
+
+#[derive(Subcommand)]
+enum Source {
+    #[clap(about = "Export a state snapshot backup directly, without restoring it.")]
+    Backup {
+        #[clap(long = "state-manifest")]
+        manifest_handle: FileHandle,
+        #[clap(flatten)]
+        concurrent_downloads: ConcurrentDownloadsOpt,
+        #[clap(flatten)]
+        storage: DBToolStorageOpt,
+    },
+    #[clap(about = "Export the state of a DB, e.g. a restored one.")]
+    Db {
+        #[clap(long = "target-db-dir", parse(from_os_str))]
+        db_dir: PathBuf,
+        #[clap(flatten)]
+        rocksdb_opt: RocksdbOpt,
+        #[clap(
+            long,
+            help = "Version to export the state at. [Defaults to the latest state checkpoint in \
+            the DB]"
+        )]
+        version: Option<Version>,
+    },
+}
+
+impl Opt {
+    pub async fn run(self) -> Result<()> {
+        Logger::new().level(Level::Info).init();
+        let controller = StateSnapshotExportController::new(self.output);
+        match self.source {
+            Source::Backup {
+                manifest_handle,
+                concurrent_downloads,
+                storage,
+            } => {
+                controller
+                    .export_backup(
+                        storage.init_storage().await?,
+                        manifest_handle,
+                        concurrent_downloads.get(),
+                    )
+                    .await
+            },
+            Source::Db {
+                db_dir,
+                rocksdb_opt,
+                version,
+            } => {
+                let db = AptosDB::open(
+                    db_dir,
+                    true,                        /* read_only */
+                    NO_OP_STORAGE_PRUNER_CONFIG, /* pruner config */
+                    rocksdb_opt.into(),
+                    false,
+                    BUFFERED_STATE_TARGET_ITEMS,
+                    DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+                )?;
+                let version = match version {
+                    Some(version) => version,
+                    None => match db.get_latest_state_checkpoint_version()? {
+                        Some(version) => version,
+                        None => bail!("The DB has no state to export."),
+                    },
+                };
+                tokio::task::spawn_blocking(move || controller.export_db(&db, version)).await?
+            },
+        }
+    }
+}
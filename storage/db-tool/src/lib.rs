@@ -5,10 +5,13 @@ extern crate core;
 
 mod backup;
 mod debugger;
+mod export_state;
 mod replay_verify;
 mod restore;
+mod smoke_test;
 #[cfg(test)]
 mod tests;
+mod verify_against_node;
 
 use anyhow::Result;
 use clap::Parser;
@@ -23,6 +26,9 @@ pub enum DBTool {
     ReplayVerify(replay_verify::Opt),
     #[clap(subcommand)]
     Debug(debugger::Command),
+    VerifyAgainstNode(verify_against_node::Opt),
+    ExportState(export_state::Opt),
+    SmokeTest(smoke_test::Opt),
 }
 
 impl DBTool {
@@ -32,6 +38,9 @@ impl DBTool {
             DBTool::Restore(cmd) => cmd.run().await,
             DBTool::ReplayVerify(cmd) => cmd.run().await,
             DBTool::Debug(cmd) => cmd.run(),
+            DBTool::VerifyAgainstNode(cmd) => cmd.run().await,
+            DBTool::ExportState(cmd) => cmd.run().await,
+            DBTool::SmokeTest(cmd) => cmd.run().await,
         }
     }
 }
@@ -0,0 +1,297 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::verify_against_node::{compare_transaction, Divergences};
+use anyhow::{bail, Context, Result};
+use aptos_backup_cli::{
+    backup_types::{
+        epoch_ending::backup::{EpochEndingBackupController, EpochEndingBackupOpt},
+        state_snapshot::{
+            backup::{StateSnapshotBackupController, StateSnapshotBackupOpt},
+            manifest::StateSnapshotBackup,
+        },
+        transaction::backup::{TransactionBackupController, TransactionBackupOpt},
+    },
+    coordinators::restore::{RestoreCoordinator, RestoreCoordinatorOpt},
+    metadata::cache::MetadataCacheOpt,
+    storage::{local_fs::LocalFs, BackupStorage},
+    utils::{
+        backup_service_client::{BackupServiceClient, BackupServiceClientOpt},
+        storage_ext::BackupStorageExt,
+        ConcurrentDownloadsOpt, GlobalBackupOpt, GlobalRestoreOpt, ReplayConcurrencyLevelOpt,
+        RocksdbOpt, TargetDbOpt, TrustedWaypointOpt,
+    },
+};
+use aptos_config::config::{
+    BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use aptos_db::{backup::backup_handler::DbState, AptosDB};
+use aptos_logger::{info, Level, Logger};
+use aptos_rest_client::Client;
+use aptos_temppath::TempPath;
+use aptos_types::transaction::Version;
+use clap::Parser;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::process::{Child, Command};
+use url::Url;
+
+/// Back up the recent versions of a local node, restore them into a temporary DB and compare the
+/// result against the node, to check the tool works end to end without cloud storage, e.g.
+/// after packaging changes.
+///
+/// The backup holds the epoch endings, a state snapshot at the latest epoch ending, and the
+/// transactions since. The snapshot is restored first, and the transactions then replayed on top
+/// of it, before the hashes of the transaction at the target version are compared with those of
+/// the node. Everything is written to a temporary directory, removed afterwards.
+#[derive(Parser)]
+pub struct Opt {
+    #[clap(
+        long,
+        parse(from_os_str),
+        help = "aptos-node binary to spin up a local node with, in test mode, serving the backup \
+        service and the REST API on their default ports. [Defaults to attaching to a running \
+        node]"
+    )]
+    node_binary: Option<PathBuf>,
+    #[clap(flatten)]
+    client: BackupServiceClientOpt,
+    #[clap(flatten)]
+    global: GlobalBackupOpt,
+    #[clap(
+        long,
+        default_value = "http://localhost:8080",
+        help = "REST API of the node, which the restored DB is compared against."
+    )]
+    node_url: Url,
+    #[clap(
+        long,
+        default_value = "100",
+        help = "Number of transactions after the state snapshot to back up and replay, waited \
+        for if the node doesn't have them yet."
+    )]
+    num_transactions: u64,
+    #[clap(
+        long,
+        default_value = "300",
+        help = "Time to wait for the node to start and to have the transactions to back up."
+    )]
+    timeout_secs: u64,
+    #[clap(
+        long,
+        help = "Keep the node data, the backup and the restored DB, whose location is logged, \
+        for inspection."
+    )]
+    keep_dir: bool,
+}
+
+impl Opt {
+    pub async fn run(self) -> Result<()> {
+        Logger::new().level(Level::Info).init();
+
+        let mut dir = TempPath::new();
+        dir.create_as_dir()?;
+        if self.keep_dir {
+            dir.persist();
+        }
+        info!(
+            dir = dir.path().display().to_string(),
+            "Smoke test started."
+        );
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(self.timeout_secs);
+
+        // Killed when dropped, i.e. when done, failed or not.
+        let _node = match &self.node_binary {
+            Some(node_binary) => Some(spawn_node(node_binary, dir.path())?),
+            None => None,
+        };
+        let client = Arc::new(BackupServiceClient::new_with_opt(self.client));
+        let storage: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(dir.path().join("backup")));
+
+        let version = backup(
+            &client,
+            &storage,
+            self.global,
+            self.num_transactions,
+            deadline,
+        )
+        .await?;
+        let db_dir = dir.path().join("db");
+        restore(&storage, dir.path(), &db_dir, version).await?;
+        verify(&db_dir, &Client::new(self.node_url), version).await?;
+
+        info!(
+            version = version,
+            time = start.elapsed().as_secs(),
+            "Smoke test passed."
+        );
+        Ok(())
+    }
+}
+
+fn spawn_node(node_binary: &Path, dir: &Path) -> Result<Child> {
+    let log = std::fs::File::create(dir.join("node.log"))?;
+    let child = Command::new(node_binary)
+        .arg("--test")
+        .arg("--test-dir")
+        .arg(dir.join("node"))
+        .stdout(Stdio::from(log.try_clone()?))
+        .stderr(Stdio::from(log))
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to spawn {}", node_binary.display()))?;
+    info!(
+        node_binary = node_binary.display().to_string(),
+        "Local node spawned."
+    );
+    Ok(child)
+}
+
+/// Polls the node until its state satisfies the condition.
+async fn wait_for_node(
+    client: &BackupServiceClient,
+    deadline: Instant,
+    what: &str,
+    condition: impl Fn(&DbState) -> bool,
+) -> Result<DbState> {
+    loop {
+        match client.get_db_state().await {
+            Ok(Some(db_state)) if condition(&db_state) => return Ok(db_state),
+            Ok(_) | Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_secs(1)).await
+            },
+            Ok(db_state) => bail!("Timed out waiting for {}, node at {:?}.", what, db_state),
+            Err(e) => return Err(e.context(format!("Timed out waiting for {}", what))),
+        }
+    }
+}
+
+/// Backs up the epoch endings, a snapshot at the latest epoch ending and `num_transactions` since,
+/// returning the version of the last of them.
+async fn backup(
+    client: &Arc<BackupServiceClient>,
+    storage: &Arc<dyn BackupStorage>,
+    global_opt: GlobalBackupOpt,
+    num_transactions: u64,
+    deadline: Instant,
+) -> Result<Version> {
+    // The epoch of genesis ends right away, so there is always an epoch ending to snapshot at.
+    let db_state = wait_for_node(client, deadline, "the node to start", |db_state| {
+        db_state.epoch > 0
+    })
+    .await?;
+
+    EpochEndingBackupController::new(
+        EpochEndingBackupOpt {
+            start_epoch: 0,
+            end_epoch: db_state.epoch,
+        },
+        global_opt.clone(),
+        client.clone(),
+        storage.clone(),
+    )
+    .run()
+    .await?;
+    let manifest_handle = StateSnapshotBackupController::new(
+        StateSnapshotBackupOpt {
+            epoch: db_state.epoch - 1,
+        },
+        global_opt.clone(),
+        client.clone(),
+        storage.clone(),
+    )
+    .run()
+    .await?;
+    let snapshot_version = storage
+        .load_json_file::<StateSnapshotBackup>(&manifest_handle)
+        .await?
+        .version;
+
+    let last_version = snapshot_version + num_transactions;
+    wait_for_node(
+        client,
+        deadline,
+        "the transactions to back up",
+        |db_state| db_state.committed_version >= last_version,
+    )
+    .await?;
+    // Starting at the snapshot version, which the restore needs the transaction of.
+    TransactionBackupController::new(
+        TransactionBackupOpt {
+            start_version: snapshot_version,
+            num_transactions: num_transactions as usize + 1,
+        },
+        global_opt,
+        client.clone(),
+        storage.clone(),
+    )
+    .run()
+    .await?;
+    info!(
+        snapshot_version = snapshot_version,
+        last_version = last_version,
+        "Backup done."
+    );
+    Ok(last_version)
+}
+
+/// Restores the snapshot, then replays the transactions after it up to the version.
+async fn restore(
+    storage: &Arc<dyn BackupStorage>,
+    dir: &Path,
+    db_dir: &Path,
+    version: Version,
+) -> Result<()> {
+    for catch_up in [false, true] {
+        RestoreCoordinator::new(
+            RestoreCoordinatorOpt {
+                metadata_cache_opt: MetadataCacheOpt::new(Some(dir.join("metadata-cache"))),
+                replay_all: false,
+                ledger_history_start_version: None,
+                skip_epoch_endings: false,
+                catch_up,
+                epoch_history_only: false,
+                priority_state: Default::default(),
+            },
+            GlobalRestoreOpt {
+                dry_run: false,
+                db_dir: Some(db_dir.to_path_buf()),
+                target_version: Some(version),
+                trusted_waypoints: TrustedWaypointOpt::default(),
+                rocksdb_opt: RocksdbOpt::default(),
+                target_db: TargetDbOpt::default(),
+                concurrent_downloads: ConcurrentDownloadsOpt::default(),
+                replay_concurrency_level: ReplayConcurrencyLevelOpt::default(),
+            }
+            .try_into()?,
+            storage.clone(),
+        )
+        .run()
+        .await?;
+    }
+    info!(version = version, "Restore done.");
+    Ok(())
+}
+
+/// Compares the transaction at the version, with its info and the root of the transaction
+/// accumulator, between the restored DB and the node.
+async fn verify(db_dir: &Path, client: &Client, version: Version) -> Result<()> {
+    let db = AptosDB::open(
+        db_dir,
+        true,                        /* read_only */
+        NO_OP_STORAGE_PRUNER_CONFIG, /* pruner config */
+        RocksdbOpt::default().into(),
+        false,
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )?;
+    let mut divergences = Divergences::default();
+    compare_transaction(&db, client, version, &mut divergences).await?;
+    divergences.check(version)
+}
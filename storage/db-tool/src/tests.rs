@@ -55,6 +55,14 @@ fn test_various_cmd_parsing() {
         "--local-fs-dir",
         ".",
     ]);
+    run_cmd(&[
+        "aptos-db-tool",
+        "smoke-test",
+        "--node-binary",
+        "aptos-node",
+        "--num-transactions",
+        "10",
+    ]);
 }
 
 fn run_cmd(args: &[&str]) {
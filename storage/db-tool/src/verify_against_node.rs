@@ -0,0 +1,223 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use aptos_backup_cli::utils::RocksdbOpt;
+use aptos_config::config::{
+    BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use aptos_db::AptosDB;
+use aptos_logger::{error, info, Level, Logger};
+use aptos_rest_client::{
+    aptos_api_types::{TransactionData, TransactionOnChainData},
+    error::RestError,
+    Client,
+};
+use aptos_storage_interface::DbReader;
+use aptos_types::{
+    access_path::AccessPath,
+    account_address::AccountAddress,
+    account_config::AccountResource,
+    state_store::state_key::StateKey,
+    transaction::{TransactionInfo, Version},
+};
+use clap::Parser;
+use move_core_types::move_resource::MoveStructType;
+use std::{fmt::Debug, path::PathBuf};
+use url::Url;
+
+/// Compare a restored DB against a live fullnode at a version, before promoting the node.
+///
+/// The transaction info at the version (which carries the hashes of the transaction, its
+/// changes, its events and the state checkpoint if any) and the root of the transaction
+/// accumulator are compared, along with the account resources of the given accounts. Any
+/// divergence is reported, and fails the command.
+#[derive(Parser)]
+pub struct Opt {
+    #[clap(long = "target-db-dir", parse(from_os_str))]
+    pub db_dir: PathBuf,
+    #[clap(flatten)]
+    pub rocksdb_opt: RocksdbOpt,
+    #[clap(
+        long,
+        help = "REST API of the fullnode to compare against, e.g. http://localhost:8080"
+    )]
+    node_url: Url,
+    #[clap(
+        long,
+        help = "Version to compare at. [Defaults to the version of the latest ledger info in the \
+        DB]"
+    )]
+    version: Option<Version>,
+    #[clap(
+        long,
+        multiple = true,
+        help = "Accounts to compare the account resource of, on top of the ledger."
+    )]
+    accounts: Vec<AccountAddress>,
+}
+
+impl Opt {
+    pub async fn run(self) -> Result<()> {
+        Logger::new().level(Level::Info).init();
+
+        let db = AptosDB::open(
+            self.db_dir,
+            true,                        /* read_only */
+            NO_OP_STORAGE_PRUNER_CONFIG, /* pruner config */
+            self.rocksdb_opt.into(),
+            false,
+            BUFFERED_STATE_TARGET_ITEMS,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        )?;
+        let client = Client::new(self.node_url);
+
+        let ledger_info = db.get_latest_ledger_info()?;
+        let ledger_version = ledger_info.ledger_info().version();
+        let version = self.version.unwrap_or(ledger_version);
+        if version > ledger_version {
+            bail!(
+                "Version {} is beyond the DB, which ends at {}.",
+                version,
+                ledger_version
+            );
+        }
+        info!(
+            version = version,
+            "Comparing the DB against {}.",
+            client.path_prefix_string()
+        );
+
+        let mut divergences = Divergences::default();
+
+        let node_txn = compare_transaction(&db, &client, version, &mut divergences).await?;
+        if version == ledger_version {
+            // What the ledger info in the DB, and so the waypoint of the node, is built on.
+            divergences.compare(
+                "ledger info transaction accumulator hash",
+                ledger_info.ledger_info().transaction_accumulator_hash(),
+                node_txn.accumulator_root_hash,
+            );
+        }
+
+        for address in self.accounts {
+            let state_key = StateKey::access_path(AccessPath::resource_access_path(
+                address,
+                AccountResource::struct_tag(),
+            )?);
+            let db_resource = db
+                .get_state_value_by_version(&state_key, version)?
+                .map(|value| value.bytes().to_vec());
+            let node_resource = match client
+                .get_account_resource_at_version_bytes(
+                    address,
+                    &AccountResource::struct_tag().to_string(),
+                    version,
+                )
+                .await
+            {
+                Ok(response) => Some(response.into_inner()),
+                Err(RestError::Api(e)) if e.status_code.as_u16() == 404 => None,
+                Err(e) => return Err(e.into()),
+            };
+            divergences.compare(
+                &format!("account resource of {}", address),
+                db_resource,
+                node_resource,
+            );
+        }
+
+        divergences.check(version)
+    }
+}
+
+/// Compares the transaction at the version, with its info and the root of the transaction
+/// accumulator, between the DB and the node, returning what the node has.
+pub(crate) async fn compare_transaction(
+    db: &AptosDB,
+    client: &Client,
+    version: Version,
+    divergences: &mut Divergences,
+) -> Result<TransactionOnChainData> {
+    // The transaction is proven against the accumulator at its own version, which doesn't need
+    // a ledger info there, e.g. in a DB restored up to a version in the middle of an epoch.
+    let db_txn = db.get_transaction_by_version(version, version, false)?;
+    let node_txn = match client
+        .get_transaction_by_version_bcs(version)
+        .await
+        .context("Failed to get the transaction from the node, is the version pruned?")?
+        .into_inner()
+    {
+        TransactionData::OnChain(txn) => txn,
+        TransactionData::Pending(_) => bail!("Transaction {} is pending on the node.", version),
+    };
+    divergences.compare(
+        "transaction accumulator root",
+        db.get_accumulator_root_hash(version)?,
+        node_txn.accumulator_root_hash,
+    );
+    compare_transaction_infos(divergences, &db_txn.proof.transaction_info, &node_txn.info);
+    Ok(node_txn)
+}
+
+fn compare_transaction_infos(
+    divergences: &mut Divergences,
+    db_info: &TransactionInfo,
+    node_info: &TransactionInfo,
+) {
+    divergences.compare(
+        "transaction hash",
+        db_info.transaction_hash(),
+        node_info.transaction_hash(),
+    );
+    divergences.compare(
+        "state change hash",
+        db_info.state_change_hash(),
+        node_info.state_change_hash(),
+    );
+    divergences.compare(
+        "event root hash",
+        db_info.event_root_hash(),
+        node_info.event_root_hash(),
+    );
+    divergences.compare(
+        "state checkpoint hash",
+        db_info.state_checkpoint_hash(),
+        node_info.state_checkpoint_hash(),
+    );
+    divergences.compare("gas used", db_info.gas_used(), node_info.gas_used());
+    divergences.compare("status", db_info.status(), node_info.status());
+}
+
+/// What differs between the DB and the node, described.
+#[derive(Default)]
+pub(crate) struct Divergences(Vec<String>);
+
+impl Divergences {
+    fn compare<T: Debug + PartialEq>(&mut self, what: &str, db: T, node: T) {
+        if db != node {
+            self.0.push(format!(
+                "{} differs: {:?} in the DB, {:?} on the node.",
+                what, db, node
+            ));
+        }
+    }
+
+    /// Fails if anything differs, logging what.
+    pub(crate) fn check(self, version: Version) -> Result<()> {
+        if self.0.is_empty() {
+            info!(version = version, "The DB matches the node.");
+            Ok(())
+        } else {
+            for divergence in &self.0 {
+                error!("{}", divergence);
+            }
+            bail!(
+                "The DB diverges from the node at version {} in {} places.",
+                version,
+                self.0.len()
+            )
+        }
+    }
+}
@@ -272,6 +272,53 @@ fn test_two_schema_batches() {
     );
 }
 
+#[test]
+fn test_ingest_schemas() {
+    let db = TestDB::new();
+    let sst_dir = aptos_temppath::TempPath::new();
+    sst_dir.create_as_dir().unwrap();
+
+    db.put::<TestSchema1>(&TestField(0), &TestField(0)).unwrap();
+    db.put::<TestSchema2>(&TestField(3), &TestField(3)).unwrap();
+
+    let db_batch = SchemaBatch::new();
+    db_batch
+        .put::<TestSchema1>(&TestField(2), &TestField(2))
+        .unwrap();
+    db_batch
+        .put::<TestSchema1>(&TestField(1), &TestField(1))
+        .unwrap();
+    // Overwrites what's in the DB, and what's earlier in the batch.
+    db_batch
+        .put::<TestSchema1>(&TestField(0), &TestField(10))
+        .unwrap();
+    db_batch
+        .put::<TestSchema1>(&TestField(2), &TestField(12))
+        .unwrap();
+    db_batch.delete::<TestSchema2>(&TestField(3)).unwrap();
+    db_batch
+        .put::<TestSchema2>(&TestField(4), &TestField(4))
+        .unwrap();
+    db.ingest_schemas(db_batch, sst_dir.path()).unwrap();
+
+    assert_eq!(
+        collect_values::<TestSchema1>(&db),
+        gen_expected_values(&[(0, 10), (1, 1), (2, 12)]),
+    );
+    assert_eq!(
+        collect_values::<TestSchema2>(&db),
+        gen_expected_values(&[(4, 4)]),
+    );
+
+    // Writes that follow take precedence over what was ingested.
+    db.put::<TestSchema2>(&TestField(4), &TestField(14))
+        .unwrap();
+    assert_eq!(
+        db.get::<TestSchema2>(&TestField(4)).unwrap(),
+        Some(TestField(14)),
+    );
+}
+
 #[test]
 fn test_reopen() {
     let tmpdir = aptos_temppath::TempPath::new();
@@ -925,7 +925,7 @@ fn individual_workload_tests(test_name: String, config: ForgeConfig) -> ForgeCon
             } else {
                 job.transaction_type(match test_name.as_str() {
                     "account_creation" => TransactionType::default_account_generation(),
-                    "nft_mint" => TransactionType::NftMintAndTransfer,
+                    "nft_mint" => TransactionType::default_nft_mint_and_transfer(),
                     "publishing" => TransactionType::PublishPackage {
                         use_account_pool: false,
                     },
@@ -1407,7 +1407,7 @@ fn large_db_test(
                 .transaction_mix(vec![
                     (TransactionType::default_coin_transfer(), 75),
                     (TransactionType::default_account_generation(), 20),
-                    (TransactionType::NftMintAndTransfer, 5),
+                    (TransactionType::default_nft_mint_and_transfer(), 5),
                 ]),
         )
         .with_success_criteria(
@@ -144,7 +144,9 @@ impl TransactinWorkload {
                     vec![(write_type, 1)],
                 ])
             },
-            Self::NftMint => request.transaction_type(TransactionType::NftMintAndTransfer),
+            Self::NftMint => {
+                request.transaction_type(TransactionType::default_nft_mint_and_transfer())
+            },
         }
     }
 }